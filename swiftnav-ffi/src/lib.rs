@@ -0,0 +1,515 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! C API for the `swiftnav` crate
+//!
+//! The coordinate conversions, GPS and UTC time conversions, and reference
+//! frame transformations of [`swiftnav`] are exposed as `extern "C"`
+//! functions on plain `#[repr(C)]` structs. The types are named in the C style
+//! so the generated header reads naturally.
+//!
+//! Every function taking pointers checks them for null and returns a
+//! [`swiftnav_status_t`]. Outputs are only written on success. Panics never
+//! unwind into the caller, they are reported as [`swiftnav_status_t::Panic`].
+
+#![allow(non_camel_case_types)]
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::panic::{self, UnwindSafe};
+use swiftnav::coords::{Coordinate, LLHRadians, ECEF};
+use swiftnav::reference_frame::ReferenceFrame;
+use swiftnav::time::{GpsTime, TimeDelta, UtcTime};
+
+/// Result of a call
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum swiftnav_status_t {
+    Ok = 0,
+    /// A required pointer was null
+    NullPointer = -1,
+    /// An argument was out of range or couldn't be parsed
+    InvalidArgument = -2,
+    /// There is no transformation between the reference frames
+    TransformationNotFound = -3,
+    /// The library hit an internal error and the call was abandoned
+    Panic = -4,
+}
+
+/// Earth centered earth fixed cartesian coordinates, in meters
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct swiftnav_ecef_t {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+/// Geodetic coordinates on the WGS84 ellipsoid, with the latitude and
+/// longitude in radians and the height in meters
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct swiftnav_llh_t {
+    pub lat: f64,
+    pub lon: f64,
+    pub height: f64,
+}
+
+/// A GPS time, as a week number and time of week in seconds
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct swiftnav_gps_time_t {
+    pub wn: i16,
+    pub tow: f64,
+}
+
+/// A UTC date and time
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct swiftnav_utc_time_t {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub seconds: f64,
+}
+
+/// A position, with an optional velocity, at an epoch
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct swiftnav_coordinate_t {
+    pub position: swiftnav_ecef_t,
+    /// The velocity, in m/yr, only used when `has_velocity` is set
+    pub velocity: swiftnav_ecef_t,
+    pub has_velocity: bool,
+    pub epoch: swiftnav_gps_time_t,
+}
+
+impl From<ECEF> for swiftnav_ecef_t {
+    fn from(ecef: ECEF) -> swiftnav_ecef_t {
+        swiftnav_ecef_t {
+            x: ecef.x(),
+            y: ecef.y(),
+            z: ecef.z(),
+        }
+    }
+}
+
+impl From<swiftnav_ecef_t> for ECEF {
+    fn from(ecef: swiftnav_ecef_t) -> ECEF {
+        ECEF::new(ecef.x, ecef.y, ecef.z)
+    }
+}
+
+impl From<LLHRadians> for swiftnav_llh_t {
+    fn from(llh: LLHRadians) -> swiftnav_llh_t {
+        swiftnav_llh_t {
+            lat: llh.latitude(),
+            lon: llh.longitude(),
+            height: llh.height(),
+        }
+    }
+}
+
+impl From<swiftnav_llh_t> for LLHRadians {
+    fn from(llh: swiftnav_llh_t) -> LLHRadians {
+        LLHRadians::new(llh.lat, llh.lon, llh.height)
+    }
+}
+
+impl From<GpsTime> for swiftnav_gps_time_t {
+    fn from(time: GpsTime) -> swiftnav_gps_time_t {
+        swiftnav_gps_time_t {
+            wn: time.wn(),
+            tow: time.tow(),
+        }
+    }
+}
+
+impl swiftnav_gps_time_t {
+    fn to_gps_time(self) -> Result<GpsTime, swiftnav_status_t> {
+        GpsTime::new(self.wn, self.tow).map_err(|_| swiftnav_status_t::InvalidArgument)
+    }
+}
+
+impl From<UtcTime> for swiftnav_utc_time_t {
+    fn from(utc: UtcTime) -> swiftnav_utc_time_t {
+        swiftnav_utc_time_t {
+            year: utc.year(),
+            month: utc.month(),
+            day: utc.day_of_month(),
+            hour: utc.hour(),
+            minute: utc.minute(),
+            seconds: utc.seconds(),
+        }
+    }
+}
+
+impl swiftnav_coordinate_t {
+    fn to_coordinate(self, frame: ReferenceFrame) -> Result<Coordinate, swiftnav_status_t> {
+        let velocity = if self.has_velocity {
+            Some(self.velocity.into())
+        } else {
+            None
+        };
+        Ok(Coordinate::new(
+            frame,
+            self.position.into(),
+            velocity,
+            self.epoch.to_gps_time()?,
+        ))
+    }
+}
+
+impl From<Coordinate> for swiftnav_coordinate_t {
+    fn from(coord: Coordinate) -> swiftnav_coordinate_t {
+        swiftnav_coordinate_t {
+            position: coord.position().into(),
+            velocity: coord.velocity().unwrap_or_default().into(),
+            has_velocity: coord.velocity().is_some(),
+            epoch: coord.epoch().into(),
+        }
+    }
+}
+
+/// Reads a pointer argument, or returns the null pointer status from the
+/// enclosing function
+macro_rules! read_arg {
+    ($ptr:expr) => {
+        match $ptr.as_ref() {
+            Some(value) => *value,
+            None => return swiftnav_status_t::NullPointer,
+        }
+    };
+}
+
+/// Writes an output, after all of the arguments have been checked
+unsafe fn write_output<T, U: Into<T>>(
+    out: *mut T,
+    value: Result<U, swiftnav_status_t>,
+) -> swiftnav_status_t {
+    match (out.as_mut(), value) {
+        (None, _) => swiftnav_status_t::NullPointer,
+        (_, Err(status)) => status,
+        (Some(out), Ok(value)) => {
+            *out = value.into();
+            swiftnav_status_t::Ok
+        }
+    }
+}
+
+/// Runs the body of an entry point, stopping a panic from unwinding across
+/// the C boundary
+fn guard<R>(on_panic: R, body: impl FnOnce() -> R + UnwindSafe) -> R {
+    panic::catch_unwind(body).unwrap_or(on_panic)
+}
+
+unsafe fn parse_frame(name: *const c_char) -> Result<ReferenceFrame, swiftnav_status_t> {
+    if name.is_null() {
+        return Err(swiftnav_status_t::NullPointer);
+    }
+    CStr::from_ptr(name)
+        .to_str()
+        .ok()
+        .and_then(|name| name.parse().ok())
+        .ok_or(swiftnav_status_t::InvalidArgument)
+}
+
+/// Gets the version of the library, as a nul terminated string
+#[no_mangle]
+pub extern "C" fn swiftnav_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+/// Converts geodetic coordinates to ECEF coordinates
+///
+/// # Safety
+///
+/// `llh` and `ecef` must be null or point to valid structs.
+#[no_mangle]
+pub unsafe extern "C" fn swiftnav_llh_to_ecef(
+    llh: *const swiftnav_llh_t,
+    ecef: *mut swiftnav_ecef_t,
+) -> swiftnav_status_t {
+    guard(swiftnav_status_t::Panic, || {
+        let llh: LLHRadians = read_arg!(llh).into();
+        write_output(ecef, Ok(llh.to_ecef()))
+    })
+}
+
+/// Converts ECEF coordinates to geodetic coordinates
+///
+/// # Safety
+///
+/// `ecef` and `llh` must be null or point to valid structs.
+#[no_mangle]
+pub unsafe extern "C" fn swiftnav_ecef_to_llh(
+    ecef: *const swiftnav_ecef_t,
+    llh: *mut swiftnav_llh_t,
+) -> swiftnav_status_t {
+    guard(swiftnav_status_t::Panic, || {
+        let ecef: ECEF = read_arg!(ecef).into();
+        write_output(llh, Ok(ecef.to_llh()))
+    })
+}
+
+/// Checks if a GPS time is valid, with a non-negative week number and a time
+/// of week within the week
+///
+/// # Safety
+///
+/// `time` must be null or point to a valid struct.
+#[no_mangle]
+pub unsafe extern "C" fn swiftnav_gps_time_is_valid(time: *const swiftnav_gps_time_t) -> bool {
+    guard(false, || match time.as_ref() {
+        Some(time) => time.to_gps_time().is_ok(),
+        None => false,
+    })
+}
+
+/// Gets the difference `end - begin` between two GPS times, in seconds
+///
+/// # Safety
+///
+/// All pointers must be null or point to valid values.
+#[no_mangle]
+pub unsafe extern "C" fn swiftnav_gps_time_diff(
+    end: *const swiftnav_gps_time_t,
+    begin: *const swiftnav_gps_time_t,
+    seconds: *mut f64,
+) -> swiftnav_status_t {
+    guard(swiftnav_status_t::Panic, || {
+        let end = read_arg!(end).to_gps_time();
+        let begin = read_arg!(begin).to_gps_time();
+        let diff = end.and_then(|end| begin.map(|begin| end.diff(&begin)));
+        write_output(seconds, diff)
+    })
+}
+
+/// Adds a number of seconds, which may be negative, to a GPS time
+///
+/// A sum before the start of GPS time is reported as an invalid argument.
+///
+/// # Safety
+///
+/// `time` and `result` must be null or point to valid structs.
+#[no_mangle]
+pub unsafe extern "C" fn swiftnav_gps_time_add_seconds(
+    time: *const swiftnav_gps_time_t,
+    seconds: f64,
+    result: *mut swiftnav_gps_time_t,
+) -> swiftnav_status_t {
+    guard(swiftnav_status_t::Panic, || {
+        let time = read_arg!(time).to_gps_time();
+        let sum = time.and_then(|time| {
+            if !seconds.is_finite() {
+                return Err(swiftnav_status_t::InvalidArgument);
+            }
+            let sum = time + TimeDelta::from_secs_f64(seconds);
+            if sum.is_valid() {
+                Ok(sum)
+            } else {
+                Err(swiftnav_status_t::InvalidArgument)
+            }
+        });
+        write_output(result, sum)
+    })
+}
+
+/// Converts a GPS time to UTC with the leap seconds built into the library
+///
+/// # Safety
+///
+/// `time` and `utc` must be null or point to valid structs.
+#[no_mangle]
+pub unsafe extern "C" fn swiftnav_gps_time_to_utc(
+    time: *const swiftnav_gps_time_t,
+    utc: *mut swiftnav_utc_time_t,
+) -> swiftnav_status_t {
+    guard(swiftnav_status_t::Panic, || {
+        let time = read_arg!(time).to_gps_time();
+        write_output(utc, time.map(GpsTime::to_utc_hardcoded))
+    })
+}
+
+/// Converts a UTC time to GPS time with the leap seconds built into the
+/// library
+///
+/// # Safety
+///
+/// `utc` and `time` must be null or point to valid structs.
+#[no_mangle]
+pub unsafe extern "C" fn swiftnav_utc_time_to_gps(
+    utc: *const swiftnav_utc_time_t,
+    time: *mut swiftnav_gps_time_t,
+) -> swiftnav_status_t {
+    guard(swiftnav_status_t::Panic, || {
+        let utc = read_arg!(utc);
+        let valid = (1..=12).contains(&utc.month)
+            && (1..=31).contains(&utc.day)
+            && utc.hour < 24
+            && utc.minute < 60
+            && (0.0..61.0).contains(&utc.seconds);
+        let converted = if valid {
+            Ok(UtcTime::from_date(
+                utc.year,
+                utc.month,
+                utc.day,
+                utc.hour,
+                utc.minute,
+                utc.seconds,
+            )
+            .to_gps_hardcoded())
+        } else {
+            Err(swiftnav_status_t::InvalidArgument)
+        };
+        write_output(time, converted)
+    })
+}
+
+/// Transforms a coordinate between two reference frames, keeping its epoch
+///
+/// The frames are given by name, such as `"ITRF2014"` or `"NAD83(2011)"`.
+///
+/// # Safety
+///
+/// The frame names must be null or valid nul terminated strings, and the
+/// coordinates must be null or point to valid structs.
+#[no_mangle]
+pub unsafe extern "C" fn swiftnav_transform_coordinate(
+    from: *const c_char,
+    to: *const c_char,
+    coord: *const swiftnav_coordinate_t,
+    result: *mut swiftnav_coordinate_t,
+) -> swiftnav_status_t {
+    guard(swiftnav_status_t::Panic, || {
+        let coord = read_arg!(coord);
+        let transformed = parse_frame(from).and_then(|from| {
+            let to = parse_frame(to)?;
+            coord
+                .to_coordinate(from)?
+                .transform_to(to)
+                .map_err(|_| swiftnav_status_t::TransformationNotFound)
+        });
+        write_output(result, transformed)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    #[test]
+    fn coordinates() {
+        let llh = swiftnav_llh_t {
+            lat: 0.6592,
+            lon: -2.1361,
+            height: 10.0,
+        };
+        let mut ecef = swiftnav_ecef_t::default();
+        let mut back = swiftnav_llh_t::default();
+        unsafe {
+            assert_eq!(swiftnav_llh_to_ecef(&llh, &mut ecef), swiftnav_status_t::Ok);
+            assert_eq!(
+                swiftnav_ecef_to_llh(&ecef, &mut back),
+                swiftnav_status_t::Ok
+            );
+            assert_eq!(
+                swiftnav_llh_to_ecef(ptr::null(), &mut ecef),
+                swiftnav_status_t::NullPointer
+            );
+            assert_eq!(
+                swiftnav_llh_to_ecef(&llh, ptr::null_mut()),
+                swiftnav_status_t::NullPointer
+            );
+        }
+        assert!((back.lat - llh.lat).abs() < 1e-12);
+        assert!((back.lon - llh.lon).abs() < 1e-12);
+        assert!((back.height - llh.height).abs() < 1e-6);
+    }
+
+    #[test]
+    fn times() {
+        let begin = swiftnav_gps_time_t {
+            wn: 2100,
+            tow: 604_000.0,
+        };
+        let mut end = swiftnav_gps_time_t::default();
+        let mut diff = 0.0;
+        unsafe {
+            assert!(swiftnav_gps_time_is_valid(&begin));
+            assert!(!swiftnav_gps_time_is_valid(&swiftnav_gps_time_t {
+                wn: 2100,
+                tow: -1.0
+            }));
+            assert_eq!(
+                swiftnav_gps_time_add_seconds(&begin, 1000.0, &mut end),
+                swiftnav_status_t::Ok
+            );
+            assert_eq!(
+                end,
+                swiftnav_gps_time_t {
+                    wn: 2101,
+                    tow: 200.0
+                }
+            );
+            assert_eq!(
+                swiftnav_gps_time_diff(&end, &begin, &mut diff),
+                swiftnav_status_t::Ok
+            );
+            assert_eq!(
+                swiftnav_gps_time_add_seconds(&begin, f64::NAN, &mut end),
+                swiftnav_status_t::InvalidArgument
+            );
+            assert_eq!(
+                swiftnav_gps_time_add_seconds(&begin, -1.0e10, &mut end),
+                swiftnav_status_t::InvalidArgument
+            );
+        }
+        assert!((diff - 1000.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn frames() {
+        let coord = swiftnav_coordinate_t {
+            position: swiftnav_ecef_t {
+                x: -2_703_115.9,
+                y: -4_262_833.7,
+                z: 3_885_033.5,
+            },
+            velocity: swiftnav_ecef_t::default(),
+            has_velocity: false,
+            epoch: swiftnav_gps_time_t { wn: 2100, tow: 0.0 },
+        };
+        let mut result = swiftnav_coordinate_t::default();
+        let itrf2014 = b"ITRF2014\0".as_ptr() as *const c_char;
+        let itrf2020 = b"ITRF2020\0".as_ptr() as *const c_char;
+        let unknown = b"WGS72\0".as_ptr() as *const c_char;
+        unsafe {
+            assert_eq!(
+                swiftnav_transform_coordinate(itrf2014, itrf2020, &coord, &mut result),
+                swiftnav_status_t::Ok
+            );
+            assert_eq!(
+                swiftnav_transform_coordinate(unknown, itrf2020, &coord, &mut result),
+                swiftnav_status_t::InvalidArgument
+            );
+            assert_eq!(
+                swiftnav_transform_coordinate(itrf2014, ptr::null(), &coord, &mut result),
+                swiftnav_status_t::NullPointer
+            );
+        }
+        assert_eq!(result.epoch, coord.epoch);
+        assert!(!result.has_velocity);
+        assert!((result.position.x - coord.position.x).abs() < 0.1);
+        assert!((result.position.z - coord.position.z).abs() < 0.1);
+    }
+}
@@ -0,0 +1,27 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use swiftnav::ephemeris::{Ephemeris, GAL_INAV_CONTENT_BYTE};
+use swiftnav::time::GpsTime;
+
+fuzz_target!(|data: &[u8]| {
+    let mut words = [[0u32; 8]; 3];
+    for (word, bytes) in words.iter_mut().flatten().zip(data.chunks_exact(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    let gps = Ephemeris::decode_gps(&words, 0.0);
+
+    let mut pages = [[0u8; GAL_INAV_CONTENT_BYTE]; 5];
+    for (byte, value) in pages.iter_mut().flatten().zip(data.iter()) {
+        *byte = *value;
+    }
+    let gal = Ephemeris::decode_gal(&pages);
+
+    // Whatever was decoded must be safe to evaluate
+    let t = GpsTime::new(2200, 0.0).unwrap();
+    for ephemeris in [gps, gal].iter() {
+        if ephemeris.is_valid_at_time(t) {
+            let _ = ephemeris.calc_satellite_state(t);
+        }
+    }
+});
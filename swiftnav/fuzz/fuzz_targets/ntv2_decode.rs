@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use swiftnav::coords::LLHRadians;
+use swiftnav::reference_frame::grid::DatumShiftGrid;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(grid) = DatumShiftGrid::from_ntv2(data) {
+        let _ = grid.apply_inverse(&LLHRadians::new(0.9, -1.8, 0.0));
+    }
+});
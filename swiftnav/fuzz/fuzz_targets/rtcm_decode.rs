@@ -0,0 +1,26 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use swiftnav::rtcm::{FrameReader, MsmDecoder, RtcmMessage};
+use swiftnav::time::GpsTime;
+
+fuzz_target!(|data: &[u8]| {
+    // Payloads are decoded both directly and after framing
+    let _ = RtcmMessage::decode(data);
+
+    let mut reader = FrameReader::new();
+    let reference = GpsTime::new(2200, 0.0).unwrap();
+    let mut decoder = MsmDecoder::new(reference);
+    reader.push(data);
+    while let Some(payload) = reader.next_payload() {
+        match RtcmMessage::decode(&payload) {
+            Ok(RtcmMessage::Msm(msm)) => {
+                let _ = decoder.push(&msm);
+            }
+            Ok(RtcmMessage::Ephemeris(ephemeris)) => {
+                let _ = ephemeris.to_ephemeris(&reference);
+            }
+            _ => {}
+        }
+    }
+});
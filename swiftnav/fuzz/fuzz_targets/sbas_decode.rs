@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use swiftnav::sbas::{SbasMessage, SBAS_MESSAGE_BYTES};
+
+fuzz_target!(|data: &[u8]| {
+    let mut message = [0u8; SBAS_MESSAGE_BYTES];
+    for (byte, value) in message.iter_mut().zip(data.iter()) {
+        *byte = *value;
+    }
+    let _ = SbasMessage::decode(&message);
+});
@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use swiftnav::reference_frame::TransformationRepository;
+use swiftnav::solver::hint::CoarseHint;
+use swiftnav::tides::parse_blq;
+use swiftnav::time::{GpsTime, UtcTime};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        let _ = CoarseHint::from_gga(text);
+        let _ = parse_blq(text);
+        let _ = text.parse::<GpsTime>();
+        let _ = text.parse::<UtcTime>();
+        let _ = GpsTime::from_rinex_epoch(text);
+        let _ = TransformationRepository::new().add_wkt(text);
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use swiftnav::ubx::{FrameReader, UbxMessage};
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = FrameReader::new();
+    reader.push(data);
+    while let Some(frame) = reader.next_frame() {
+        let _ = UbxMessage::decode(&frame);
+    }
+});
@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use swiftnav::time::{GpsTime, UtcParams};
+
+fuzz_target!(|data: &[u8]| {
+    let mut words = [0u32; 8];
+    for (word, bytes) in words.iter_mut().zip(data.chunks_exact(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+
+    let t = GpsTime::new(2200, 0.0).unwrap();
+    if let Some(params) = UtcParams::decode(&words, &t) {
+        // Decoded parameters must be usable for time conversions
+        let _ = t.utc_offset(&params);
+        let _ = t.is_leap_second_event(&params);
+        let _ = t.to_utc(&params);
+    }
+});
@@ -0,0 +1,479 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Satellite and receiver code biases
+//!
+//! Each code is delayed by a slightly different amount in the satellite and
+//! receiver hardware. The broadcast satellite clocks are referenced to a
+//! particular code combination, so pseudoranges on other codes carry a
+//! differential code bias (DCB) of up to several meters. Analysis centers
+//! publish these biases in the SINEX BIAS format, either as differences
+//! between two codes (DSB) or as the bias of each code on its own, the
+//! observable specific biases (OSB).
+//!
+//! [`CodeBiases`] loads the code biases of a SINEX BIAS product and applies
+//! the observable specific biases to [`NavigationMeasurement`]s. Phase biases
+//! are skipped.
+//!
+//! # References
+//!   * SINEX BIAS - Solution (Software/technique) INdependent EXchange Format
+//!     for GNSS BIASes, Version 1.00, 2016
+
+use crate::navmeas::NavigationMeasurement;
+use crate::signal::{Code, Constellation, GnssSignal};
+use crate::time::GpsTime;
+use crate::SPEED_OF_LIGHT;
+use std::error::Error;
+use std::fmt;
+
+/// The kind of a bias solution
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BiasKind {
+    /// Observable specific bias, the bias of a single code
+    Osb,
+    /// Differential signal bias, the bias of one code relative to another
+    Dsb,
+    /// Inter-system bias, the bias of a code relative to another constellation
+    Isb,
+}
+
+impl BiasKind {
+    fn from_sinex(kind: &str) -> Option<BiasKind> {
+        match kind {
+            "OSB" => Some(BiasKind::Osb),
+            "DSB" => Some(BiasKind::Dsb),
+            "ISB" => Some(BiasKind::Isb),
+            _ => None,
+        }
+    }
+}
+
+/// A single code bias
+///
+/// A satellite bias has a satellite and no station, a receiver bias has a
+/// station and may or may not have a satellite. A differential bias is the
+/// bias of `code1` minus that of `code2`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeBias {
+    pub kind: BiasKind,
+    /// The station name, for receiver biases
+    pub station: Option<String>,
+    pub constellation: Constellation,
+    /// The satellite number, in the numbering of [`GnssSignal`]
+    pub sat: Option<u16>,
+    pub code1: Code,
+    /// The second code of a differential bias
+    pub code2: Option<Code>,
+    /// Start of the validity interval, `None` if unbounded
+    pub start: Option<GpsTime>,
+    /// End of the validity interval, `None` if unbounded
+    pub end: Option<GpsTime>,
+    /// The bias, in meters
+    pub value: f64,
+    /// The standard deviation of the bias, in meters
+    pub sigma: Option<f64>,
+}
+
+impl CodeBias {
+    /// Checks if the bias is valid at a time
+    ///
+    /// The interval includes its start but not its end, so consecutive
+    /// intervals don't overlap.
+    pub fn is_valid_at(&self, t: &GpsTime) -> bool {
+        let started = match &self.start {
+            Some(start) => start <= t,
+            None => true,
+        };
+        let ended = match &self.end {
+            Some(end) => end <= t,
+            None => false,
+        };
+        started && !ended
+    }
+
+    fn is_satellite(&self, sid: GnssSignal) -> bool {
+        self.constellation == sid.to_constellation() && self.sat == Some(sid.sat())
+    }
+}
+
+/// An error parsing a SINEX BIAS file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinexBiasError {
+    /// A bias line could not be parsed
+    InvalidLine(usize),
+    /// The file has no `BIAS/SOLUTION` block
+    MissingSolution,
+}
+
+impl fmt::Display for SinexBiasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SinexBiasError::InvalidLine(line) => write!(f, "Invalid SINEX bias on line {}", line),
+            SinexBiasError::MissingSolution => write!(f, "Missing SINEX BIAS/SOLUTION block"),
+        }
+    }
+}
+
+impl Error for SinexBiasError {}
+
+/// A collection of code biases
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CodeBiases {
+    entries: Vec<CodeBias>,
+}
+
+impl CodeBiases {
+    /// Makes an empty collection
+    pub fn new() -> CodeBiases {
+        CodeBiases {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Loads the code biases of a SINEX BIAS file
+    ///
+    /// Biases in units other than nanoseconds, such as phase biases in
+    /// cycles, and biases on codes without a [`Code`] are skipped.
+    pub fn from_sinex(text: &str) -> Result<CodeBiases, SinexBiasError> {
+        let mut biases = CodeBiases::new();
+        let mut in_solution = false;
+        let mut found_solution = false;
+
+        for (index, line) in text.lines().enumerate() {
+            if line.starts_with("+BIAS/SOLUTION") {
+                in_solution = true;
+                found_solution = true;
+                continue;
+            }
+            if line.starts_with("-BIAS/SOLUTION") {
+                in_solution = false;
+                continue;
+            }
+            if !in_solution || line.starts_with('*') || line.trim().is_empty() {
+                continue;
+            }
+            match parse_bias_line(line) {
+                Ok(Some(bias)) => biases.push(bias),
+                Ok(None) => {}
+                Err(()) => return Err(SinexBiasError::InvalidLine(index + 1)),
+            }
+        }
+
+        if found_solution {
+            Ok(biases)
+        } else {
+            Err(SinexBiasError::MissingSolution)
+        }
+    }
+
+    pub fn push(&mut self, bias: CodeBias) {
+        self.entries.push(bias);
+    }
+
+    pub fn entries(&self) -> &[CodeBias] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Gets the observable specific bias of a satellite's signal at a time,
+    /// in meters
+    pub fn satellite_osb(&self, sid: GnssSignal, t: &GpsTime) -> Option<f64> {
+        self.entries
+            .iter()
+            .find(|bias| {
+                bias.kind == BiasKind::Osb
+                    && bias.station.is_none()
+                    && bias.is_satellite(sid)
+                    && bias.code1 == sid.code()
+                    && bias.is_valid_at(t)
+            })
+            .map(|bias| bias.value)
+    }
+
+    /// Gets the observable specific bias of a receiver on a signal at a
+    /// time, in meters
+    ///
+    /// A bias specific to the signal's satellite is preferred over one for
+    /// the whole constellation.
+    pub fn receiver_osb(&self, station: &str, sid: GnssSignal, t: &GpsTime) -> Option<f64> {
+        let candidates = || {
+            self.entries.iter().filter(move |bias| {
+                bias.kind == BiasKind::Osb
+                    && bias.station.as_deref() == Some(station)
+                    && bias.constellation == sid.to_constellation()
+                    && bias.code1 == sid.code()
+                    && bias.is_valid_at(t)
+            })
+        };
+        candidates()
+            .find(|bias| bias.sat == Some(sid.sat()))
+            .or_else(|| candidates().find(|bias| bias.sat.is_none()))
+            .map(|bias| bias.value)
+    }
+
+    /// Gets the differential bias of a satellite's signal relative to another
+    /// code at a time, in meters
+    ///
+    /// A bias stored the other way round is negated.
+    pub fn satellite_dsb(&self, sid: GnssSignal, code2: Code, t: &GpsTime) -> Option<f64> {
+        self.entries
+            .iter()
+            .filter(|bias| {
+                bias.kind == BiasKind::Dsb
+                    && bias.station.is_none()
+                    && bias.is_satellite(sid)
+                    && bias.is_valid_at(t)
+            })
+            .find_map(|bias| {
+                if bias.code1 == sid.code() && bias.code2 == Some(code2) {
+                    Some(bias.value)
+                } else if bias.code1 == code2 && bias.code2 == Some(sid.code()) {
+                    Some(-bias.value)
+                } else {
+                    None
+                }
+            })
+    }
+
+    /// Removes the observable specific biases from the pseudoranges
+    ///
+    /// The satellite biases, and the receiver biases of `station` if given,
+    /// are subtracted from the pseudoranges. The signals with a pseudorange
+    /// but no satellite bias are returned, their pseudoranges are only
+    /// corrected for the receiver bias.
+    pub fn apply(
+        &self,
+        measurements: &mut [NavigationMeasurement],
+        t: &GpsTime,
+        station: Option<&str>,
+    ) -> Vec<GnssSignal> {
+        let mut missing = Vec::new();
+        for nm in measurements.iter_mut() {
+            let pseudorange = match nm.pseudorange() {
+                Some(pseudorange) => pseudorange,
+                None => continue,
+            };
+            let sid = nm.sid();
+            let satellite = self.satellite_osb(sid, t).unwrap_or_else(|| {
+                missing.push(sid);
+                0.0
+            });
+            let receiver = station
+                .and_then(|station| self.receiver_osb(station, sid, t))
+                .unwrap_or(0.0);
+            nm.set_pseudorange(pseudorange - satellite - receiver);
+        }
+        missing
+    }
+}
+
+impl Extend<CodeBias> for CodeBiases {
+    fn extend<T: IntoIterator<Item = CodeBias>>(&mut self, iter: T) {
+        self.entries.extend(iter);
+    }
+}
+
+/// Gets a fixed column field of a line, trimmed
+fn field(line: &str, start: usize, end: usize) -> &str {
+    let end = end.min(line.len());
+    line.get(start..end).map_or("", str::trim)
+}
+
+fn parse_epoch(epoch: &str) -> Result<Option<GpsTime>, ()> {
+    let is_unbounded = epoch
+        .chars()
+        .all(|c| c == '0' || c == ':' || c.is_whitespace());
+    if is_unbounded {
+        Ok(None)
+    } else {
+        GpsTime::from_sinex_epoch(epoch).map(Some).map_err(|_| ())
+    }
+}
+
+/// Parses a `BIAS/SOLUTION` line, `None` for a bias that is skipped
+fn parse_bias_line(line: &str) -> Result<Option<CodeBias>, ()> {
+    let kind = BiasKind::from_sinex(field(line, 1, 5)).ok_or(())?;
+    let svn = field(line, 6, 10);
+    let prn = field(line, 11, 14);
+    let station = field(line, 15, 24);
+    let obs1 = field(line, 25, 29);
+    let obs2 = field(line, 30, 34);
+    let unit = field(line, 65, 69);
+
+    if unit != "ns" || !obs1.starts_with('C') {
+        return Ok(None);
+    }
+    let system = prn
+        .chars()
+        .next()
+        .or_else(|| svn.chars().next())
+        .ok_or(())?;
+    let constellation = match Constellation::from_rinex_char(system) {
+        Some(constellation) => constellation,
+        None => return Ok(None),
+    };
+    let sat = if prn.is_empty() {
+        None
+    } else {
+        match GnssSignal::from_rinex(prn, obs1) {
+            Some(sid) => Some(sid.sat()),
+            None => return Ok(None),
+        }
+    };
+    let code1 = match Code::from_rinex(constellation, obs1) {
+        Some(code) => code,
+        None => return Ok(None),
+    };
+    let code2 = if obs2.is_empty() {
+        None
+    } else {
+        match Code::from_rinex(constellation, obs2) {
+            Some(code) => Some(code),
+            None => return Ok(None),
+        }
+    };
+
+    let start = parse_epoch(field(line, 35, 49))?;
+    let end = parse_epoch(field(line, 50, 64))?;
+    let value: f64 = field(line, 70, 91).parse().map_err(|_| ())?;
+    let sigma = match field(line, 92, 103) {
+        "" => None,
+        sigma => Some(sigma.parse::<f64>().map_err(|_| ())?),
+    };
+    let to_meters = SPEED_OF_LIGHT * 1e-9;
+
+    Ok(Some(CodeBias {
+        kind,
+        station: if station.is_empty() {
+            None
+        } else {
+            Some(station.to_string())
+        },
+        constellation,
+        sat,
+        code1,
+        code2,
+        start,
+        end,
+        value: value * to_meters,
+        sigma: sigma.map(|sigma| sigma * to_meters),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+
+    const SINEX: &str = "%=BIA 1.00 COD 2020:001:00000 COD 2020:001:00000 2020:003:00000 R 00000006
++BIAS/SOLUTION
+*BIAS SVN_ PRN STATION__ OBS1 OBS2 BIAS_START____ BIAS_END______ UNIT __ESTIMATED_VALUE____ _STD_DEV___
+ OSB  G063 G01           C1C       2020:001:00000 2020:002:00000 ns                 10.2470      0.0044
+ OSB  G063 G01           C1W       2020:001:00000 2020:002:00000 ns                 12.1000      0.0050
+ OSB  G063 G01           L1C       2020:001:00000 2020:002:00000 cyc                 0.1234      0.0010
+ DSB  E201 E01           C1C  C5Q  2020:001:00000 0000:000:00000 ns                 -1.5000
+ OSB  G        ABMF00GLP C1C       2020:001:00000 2020:002:00000 ns                  3.0000      0.1000
+ OSB  G063 G01           C1C       2020:002:00000 2020:003:00000 ns                 10.5000      0.0044
+-BIAS/SOLUTION
+%=ENDBIA
+";
+
+    const NS: f64 = SPEED_OF_LIGHT * 1e-9;
+
+    fn gps_l1ca() -> GnssSignal {
+        GnssSignal::new(1, Code::GpsL1ca).unwrap()
+    }
+
+    #[test]
+    fn parse_sinex() {
+        let biases = CodeBiases::from_sinex(SINEX).unwrap();
+        assert_eq!(biases.len(), 5);
+
+        let first = &biases.entries()[0];
+        assert_eq!(first.kind, BiasKind::Osb);
+        assert_eq!(first.station, None);
+        assert_eq!(first.sat, Some(1));
+        assert_eq!(first.code1, Code::GpsL1ca);
+        assert_eq!(first.start, Some(GpsTime::new(2086, 259_200.0).unwrap()));
+        assert_float_eq!(first.value, 10.247 * NS, abs <= 1e-9);
+        assert_float_eq!(first.sigma.unwrap(), 0.0044 * NS, abs <= 1e-9);
+
+        let dsb = &biases.entries()[2];
+        assert_eq!(dsb.kind, BiasKind::Dsb);
+        assert_eq!(dsb.constellation, Constellation::Gal);
+        assert_eq!(dsb.end, None);
+        assert_eq!(dsb.sigma, None);
+
+        let receiver = &biases.entries()[3];
+        assert_eq!(receiver.station.as_deref(), Some("ABMF00GLP"));
+        assert_eq!(receiver.constellation, Constellation::Gps);
+        assert_eq!(receiver.sat, None);
+
+        assert_eq!(
+            CodeBiases::from_sinex(&SINEX.replace("10.2470", "10.2x70")),
+            Err(SinexBiasError::InvalidLine(4))
+        );
+        assert_eq!(
+            CodeBiases::from_sinex("%=ENDBIA\n"),
+            Err(SinexBiasError::MissingSolution)
+        );
+    }
+
+    #[test]
+    fn lookup_and_apply() {
+        let biases = CodeBiases::from_sinex(SINEX).unwrap();
+        let day1 = GpsTime::new(2086, 300_000.0).unwrap();
+        let day2 = GpsTime::new(2086, 350_000.0).unwrap();
+
+        let osb = biases.satellite_osb(gps_l1ca(), &day1).unwrap();
+        assert_float_eq!(osb, 10.247 * NS, abs <= 1e-9);
+        let osb = biases.satellite_osb(gps_l1ca(), &day2).unwrap();
+        assert_float_eq!(osb, 10.5 * NS, abs <= 1e-9);
+        assert_eq!(
+            biases.satellite_osb(gps_l1ca(), &GpsTime::new(2086, 500_000.0).unwrap()),
+            None
+        );
+        let receiver = biases.receiver_osb("ABMF00GLP", gps_l1ca(), &day1).unwrap();
+        assert_float_eq!(receiver, 3.0 * NS, abs <= 1e-9);
+        assert_eq!(biases.receiver_osb("ZIMM00CHE", gps_l1ca(), &day1), None);
+
+        let gal_e1 = GnssSignal::from_rinex("E01", "C1C").unwrap();
+        let gal_e5a = GnssSignal::from_rinex("E01", "C5Q").unwrap();
+        let dsb = biases.satellite_dsb(gal_e1, gal_e5a.code(), &day1).unwrap();
+        assert_float_eq!(dsb, -1.5 * NS, abs <= 1e-9);
+        let dsb = biases.satellite_dsb(gal_e5a, gal_e1.code(), &day1).unwrap();
+        assert_float_eq!(dsb, 1.5 * NS, abs <= 1e-9);
+
+        let mut measurements = vec![NavigationMeasurement::new(); 2];
+        measurements[0].set_sid(gps_l1ca());
+        measurements[0].set_pseudorange(20e6);
+        measurements[1].set_sid(GnssSignal::new(2, Code::GpsL1ca).unwrap());
+        measurements[1].set_pseudorange(21e6);
+        let missing = biases.apply(&mut measurements, &day1, Some("ABMF00GLP"));
+        assert_eq!(missing, vec![GnssSignal::new(2, Code::GpsL1ca).unwrap()]);
+        assert_float_eq!(
+            measurements[0].pseudorange().unwrap(),
+            20e6 - 13.247 * NS,
+            abs <= 1e-6
+        );
+        assert_float_eq!(
+            measurements[1].pseudorange().unwrap(),
+            21e6 - 3.0 * NS,
+            abs <= 1e-6
+        );
+    }
+}
@@ -0,0 +1,444 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Processing configuration
+//!
+//! Collects all of the settings that influence how measurements are processed
+//! into a single [`ProcessingConfig`]. With the `serde` feature enabled the
+//! configuration, and each of the individual settings types it is made of, can
+//! be serialized so that processing settings can be stored alongside results
+//! and reproduced later.
+//!
+//! Serialized configurations carry a version number. Configurations written by
+//! an older version of this crate can still be read, missing sections take
+//! their default values. Configurations with a version newer than
+//! [`CONFIG_VERSION`] are rejected since they may contain settings this
+//! version doesn't understand.
+//!
+//! Only settings are part of the configuration, not the data the processing
+//! depends on. The ionosphere and troposphere models, GLONASS inter-frequency
+//! bias tables, measurement weighting models, ephemerides and a priori
+//! positions are provided to the solvers separately.
+
+use crate::navmeas::merge::MergeSettings;
+use crate::navmeas::selection::SelectionSettings;
+use crate::navmeas::smoothing::HatchSettings;
+use crate::navmeas::synchronize::SyncSettings;
+use crate::navmeas::NavigationMeasurement;
+use crate::signal::Constellation;
+use crate::solver::filter::{ConstraintSettings, KalmanSettings};
+use crate::solver::protection::ProtectionLevelSettings;
+use crate::solver::raim::RaimSettings;
+use crate::solver::rtk::RtkSettings;
+use crate::solver::PvtSettings;
+use std::error::Error;
+use std::fmt;
+
+/// The current version of the configuration format
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Errors when handling a processing configuration
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum ConfigError {
+    /// The configuration was written by a newer version of the format
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported configuration version {} (newest supported is {})",
+                version, CONFIG_VERSION
+            ),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// Criteria for accepting measurements into processing
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MeasurementMask {
+    elevation_mask: f64,
+    min_cn0: Option<f64>,
+    excluded_constellations: Vec<Constellation>,
+}
+
+impl MeasurementMask {
+    /// Makes the default mask
+    ///
+    /// Note: The default mask consists of
+    ///  * A 10 degree elevation mask
+    ///  * No CN0 mask
+    ///  * All constellations enabled
+    pub fn new() -> MeasurementMask {
+        MeasurementMask {
+            elevation_mask: 10.0,
+            min_cn0: None,
+            excluded_constellations: Vec::new(),
+        }
+    }
+
+    /// Sets the minimum satellite elevation, in degrees
+    pub fn set_elevation_mask(self, elevation_mask: f64) -> MeasurementMask {
+        MeasurementMask {
+            elevation_mask,
+            ..self
+        }
+    }
+
+    /// Sets the minimum CN0, in dB-Hz
+    ///
+    /// Measurements without a valid CN0 are rejected when a CN0 mask is set
+    pub fn set_min_cn0(self, min_cn0: Option<f64>) -> MeasurementMask {
+        MeasurementMask { min_cn0, ..self }
+    }
+
+    /// Excludes all measurements from a constellation
+    pub fn exclude_constellation(mut self, constellation: Constellation) -> MeasurementMask {
+        if !self.excluded_constellations.contains(&constellation) {
+            self.excluded_constellations.push(constellation);
+        }
+        self
+    }
+
+    pub fn elevation_mask(&self) -> f64 {
+        self.elevation_mask
+    }
+
+    pub fn min_cn0(&self) -> Option<f64> {
+        self.min_cn0
+    }
+
+    pub fn excluded_constellations(&self) -> &[Constellation] {
+        &self.excluded_constellations
+    }
+
+    /// Checks if a measurement passes the mask, given the elevation of its
+    /// satellite in degrees
+    pub fn accepts(&self, nm: &NavigationMeasurement, elevation: f64) -> bool {
+        if elevation < self.elevation_mask {
+            return false;
+        }
+        if self
+            .excluded_constellations
+            .contains(&nm.sid().to_constellation())
+        {
+            return false;
+        }
+        match self.min_cn0 {
+            Some(min_cn0) => matches!(nm.cn0(), Some(cn0) if cn0 >= min_cn0),
+            None => true,
+        }
+    }
+}
+
+impl Default for MeasurementMask {
+    fn default() -> MeasurementMask {
+        MeasurementMask::new()
+    }
+}
+
+/// The complete set of processing settings
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProcessingConfig {
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_version"))]
+    version: u32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pvt: PvtSettings,
+    #[cfg_attr(feature = "serde", serde(default))]
+    mask: MeasurementMask,
+    #[cfg_attr(feature = "serde", serde(default))]
+    selection: SelectionSettings,
+    #[cfg_attr(feature = "serde", serde(default))]
+    smoothing: Option<HatchSettings>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    merge: MergeSettings,
+    #[cfg_attr(feature = "serde", serde(default))]
+    raim: RaimSettings,
+    #[cfg_attr(feature = "serde", serde(default))]
+    protection: ProtectionLevelSettings,
+    #[cfg_attr(feature = "serde", serde(default))]
+    kalman: KalmanSettings,
+    #[cfg_attr(feature = "serde", serde(default))]
+    sync: SyncSettings,
+    #[cfg_attr(feature = "serde", serde(default))]
+    rtk: Option<RtkSettings>,
+}
+
+impl ProcessingConfig {
+    /// Makes a configuration with the default settings, in the current version
+    /// of the format
+    ///
+    /// Carrier smoothing and RTK processing are disabled by default.
+    pub fn new() -> ProcessingConfig {
+        ProcessingConfig {
+            version: CONFIG_VERSION,
+            pvt: PvtSettings::new(),
+            mask: MeasurementMask::new(),
+            selection: SelectionSettings::new(),
+            smoothing: None,
+            merge: MergeSettings::new(),
+            raim: RaimSettings::new(),
+            protection: ProtectionLevelSettings::new(),
+            kalman: KalmanSettings::new(),
+            sync: SyncSettings::new(),
+            rtk: None,
+        }
+    }
+
+    /// Sets the single epoch solver settings
+    pub fn set_pvt(self, pvt: PvtSettings) -> ProcessingConfig {
+        ProcessingConfig { pvt, ..self }
+    }
+
+    /// Sets the measurement mask
+    pub fn set_mask(self, mask: MeasurementMask) -> ProcessingConfig {
+        ProcessingConfig { mask, ..self }
+    }
+
+    /// Sets how signals are selected when a satellite is tracked on several
+    /// codes of the same band
+    pub fn set_selection(self, selection: SelectionSettings) -> ProcessingConfig {
+        ProcessingConfig { selection, ..self }
+    }
+
+    /// Enables carrier smoothing of the pseudoranges with the given settings
+    pub fn enable_smoothing(self, smoothing: HatchSettings) -> ProcessingConfig {
+        ProcessingConfig {
+            smoothing: Some(smoothing),
+            ..self
+        }
+    }
+
+    /// Disables carrier smoothing
+    pub fn disable_smoothing(self) -> ProcessingConfig {
+        ProcessingConfig {
+            smoothing: None,
+            ..self
+        }
+    }
+
+    /// Sets how measurement streams are merged
+    pub fn set_merge(self, merge: MergeSettings) -> ProcessingConfig {
+        ProcessingConfig { merge, ..self }
+    }
+
+    /// Sets the RAIM settings
+    ///
+    /// Whether RAIM runs at all is controlled by the single epoch solver
+    /// settings, see [`set_pvt`](Self::set_pvt).
+    pub fn set_raim(self, raim: RaimSettings) -> ProcessingConfig {
+        ProcessingConfig { raim, ..self }
+    }
+
+    /// Sets the protection level settings
+    pub fn set_protection(self, protection: ProtectionLevelSettings) -> ProcessingConfig {
+        ProcessingConfig { protection, ..self }
+    }
+
+    /// Sets the Kalman filter settings
+    pub fn set_kalman(self, kalman: KalmanSettings) -> ProcessingConfig {
+        ProcessingConfig { kalman, ..self }
+    }
+
+    /// Sets the motion constraints used when filtering
+    pub fn set_constraints(self, constraints: ConstraintSettings) -> ProcessingConfig {
+        ProcessingConfig {
+            kalman: self.kalman.set_constraints(constraints),
+            ..self
+        }
+    }
+
+    /// Sets how base and rover measurements are synchronized
+    pub fn set_sync(self, sync: SyncSettings) -> ProcessingConfig {
+        ProcessingConfig { sync, ..self }
+    }
+
+    /// Enables RTK processing with the given settings
+    pub fn enable_rtk(self, rtk: RtkSettings) -> ProcessingConfig {
+        ProcessingConfig {
+            rtk: Some(rtk),
+            ..self
+        }
+    }
+
+    /// Disables RTK processing
+    pub fn disable_rtk(self) -> ProcessingConfig {
+        ProcessingConfig { rtk: None, ..self }
+    }
+
+    /// Gets the version of the format the configuration was made with
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn pvt(&self) -> &PvtSettings {
+        &self.pvt
+    }
+
+    pub fn mask(&self) -> &MeasurementMask {
+        &self.mask
+    }
+
+    pub fn selection(&self) -> &SelectionSettings {
+        &self.selection
+    }
+
+    pub fn smoothing(&self) -> Option<&HatchSettings> {
+        self.smoothing.as_ref()
+    }
+
+    pub fn merge(&self) -> &MergeSettings {
+        &self.merge
+    }
+
+    pub fn raim(&self) -> &RaimSettings {
+        &self.raim
+    }
+
+    pub fn protection(&self) -> &ProtectionLevelSettings {
+        &self.protection
+    }
+
+    pub fn kalman(&self) -> &KalmanSettings {
+        &self.kalman
+    }
+
+    pub fn constraints(&self) -> &ConstraintSettings {
+        self.kalman.constraints()
+    }
+
+    pub fn sync(&self) -> &SyncSettings {
+        &self.sync
+    }
+
+    pub fn rtk(&self) -> Option<&RtkSettings> {
+        self.rtk.as_ref()
+    }
+
+    /// Checks that the configuration version is supported
+    pub fn check_version(&self) -> Result<(), ConfigError> {
+        check_version(self.version)
+    }
+
+    /// Brings the configuration up to the current version of the format
+    pub fn upgrade(self) -> Result<ProcessingConfig, ConfigError> {
+        self.check_version()?;
+        Ok(ProcessingConfig {
+            version: CONFIG_VERSION,
+            ..self
+        })
+    }
+}
+
+impl Default for ProcessingConfig {
+    fn default() -> ProcessingConfig {
+        ProcessingConfig::new()
+    }
+}
+
+fn check_version(version: u32) -> Result<(), ConfigError> {
+    if version == 0 || version > CONFIG_VERSION {
+        Err(ConfigError::UnsupportedVersion(version))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+fn deserialize_version<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let version = <u32 as serde::Deserialize>::deserialize(deserializer)?;
+    check_version(version).map_err(serde::de::Error::custom)?;
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::{Code, GnssSignal};
+
+    #[test]
+    fn mask() {
+        let mask = MeasurementMask::new()
+            .set_elevation_mask(15.0)
+            .set_min_cn0(Some(30.0))
+            .exclude_constellation(Constellation::Glo);
+
+        let mut nm = NavigationMeasurement::new();
+        nm.set_sid(GnssSignal::new(3, Code::GpsL1ca).unwrap());
+        assert!(!mask.accepts(&nm, 20.0));
+        nm.set_cn0(35.0);
+        assert!(mask.accepts(&nm, 20.0));
+        assert!(!mask.accepts(&nm, 10.0));
+        nm.set_sid(GnssSignal::new(3, Code::GloL1of).unwrap());
+        assert!(!mask.accepts(&nm, 20.0));
+    }
+
+    #[test]
+    fn versions() {
+        let config = ProcessingConfig::new();
+        assert_eq!(config.version(), CONFIG_VERSION);
+        assert_eq!(config.check_version(), Ok(()));
+
+        let future = ProcessingConfig {
+            version: CONFIG_VERSION + 1,
+            ..config
+        };
+        assert_eq!(
+            future.upgrade(),
+            Err(ConfigError::UnsupportedVersion(CONFIG_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn constraints() {
+        let constraints = ConstraintSettings::new().enable_zupt(Default::default());
+        let config = ProcessingConfig::new()
+            .set_kalman(KalmanSettings::new().set_max_gap(5.0))
+            .set_constraints(constraints);
+        assert_eq!(config.constraints(), &constraints);
+        assert_eq!(config.kalman().constraints(), &constraints);
+        assert_eq!(config.kalman().max_gap(), 5.0);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_round_trip() {
+        let config = ProcessingConfig::new()
+            .set_mask(MeasurementMask::new().exclude_constellation(Constellation::Bds))
+            .set_selection(
+                SelectionSettings::new()
+                    .set_policy(crate::navmeas::selection::SelectionPolicy::Preferred)
+                    .set_preferred_codes(vec![Code::GpsL1ca, Code::GalE1b]),
+            )
+            .enable_smoothing(HatchSettings::new().set_window(50))
+            .set_raim(RaimSettings::new().set_max_exclusions(2))
+            .set_kalman(KalmanSettings::new().set_max_gap(5.0))
+            .enable_rtk(RtkSettings::new().set_code_sigma(0.5));
+
+        let json = serde_json::to_string(&config).unwrap();
+        let parsed: ProcessingConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, config);
+
+        // Missing sections take their default values
+        let minimal: ProcessingConfig = serde_json::from_str("{\"version\":1}").unwrap();
+        assert_eq!(minimal, ProcessingConfig::new());
+
+        let future = format!("{{\"version\":{}}}", CONFIG_VERSION + 1);
+        assert!(serde_json::from_str::<ProcessingConfig>(&future).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Angles with explicit units
+//!
+//! Most of the coordinate types store their angles as plain [`f64`] values,
+//! and mixing up degrees and radians is an easy mistake which the compiler
+//! can't catch. [`Degrees`] and [`Radians`] carry the unit in the type, and
+//! convert between each other explicitly. The coordinate types have
+//! constructors taking these types, which also check the angles are in range,
+//! and accessors returning them. The plain [`f64`] constructors and accessors
+//! remain available.
+
+use std::error::Error;
+use std::fmt;
+use std::ops::{Add, Neg, Sub};
+
+/// An angle in degrees
+#[derive(Debug, Copy, Clone, Default, PartialEq, PartialOrd)]
+pub struct Degrees(f64);
+
+impl Degrees {
+    pub const fn new(degrees: f64) -> Degrees {
+        Degrees(degrees)
+    }
+
+    /// Gets the angle as a plain number of degrees
+    pub const fn value(self) -> f64 {
+        self.0
+    }
+
+    pub fn to_radians(self) -> Radians {
+        Radians(self.0.to_radians())
+    }
+}
+
+impl From<Radians> for Degrees {
+    fn from(radians: Radians) -> Degrees {
+        radians.to_degrees()
+    }
+}
+
+impl Add for Degrees {
+    type Output = Degrees;
+    fn add(self, rhs: Degrees) -> Degrees {
+        Degrees(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Degrees {
+    type Output = Degrees;
+    fn sub(self, rhs: Degrees) -> Degrees {
+        Degrees(self.0 - rhs.0)
+    }
+}
+
+impl Neg for Degrees {
+    type Output = Degrees;
+    fn neg(self) -> Degrees {
+        Degrees(-self.0)
+    }
+}
+
+impl fmt::Display for Degrees {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)?;
+        write!(f, "°")
+    }
+}
+
+/// An angle in radians
+#[derive(Debug, Copy, Clone, Default, PartialEq, PartialOrd)]
+pub struct Radians(f64);
+
+impl Radians {
+    pub const fn new(radians: f64) -> Radians {
+        Radians(radians)
+    }
+
+    /// Gets the angle as a plain number of radians
+    pub const fn value(self) -> f64 {
+        self.0
+    }
+
+    pub fn to_degrees(self) -> Degrees {
+        Degrees(self.0.to_degrees())
+    }
+
+    pub fn sin(self) -> f64 {
+        self.0.sin()
+    }
+
+    pub fn cos(self) -> f64 {
+        self.0.cos()
+    }
+
+    pub fn sin_cos(self) -> (f64, f64) {
+        self.0.sin_cos()
+    }
+}
+
+impl From<Degrees> for Radians {
+    fn from(degrees: Degrees) -> Radians {
+        degrees.to_radians()
+    }
+}
+
+impl Add for Radians {
+    type Output = Radians;
+    fn add(self, rhs: Radians) -> Radians {
+        Radians(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Radians {
+    type Output = Radians;
+    fn sub(self, rhs: Radians) -> Radians {
+        Radians(self.0 - rhs.0)
+    }
+}
+
+impl Neg for Radians {
+    type Output = Radians;
+    fn neg(self) -> Radians {
+        Radians(-self.0)
+    }
+}
+
+impl fmt::Display for Radians {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)?;
+        write!(f, " rad")
+    }
+}
+
+/// An angle given to a coordinate constructor was out of range, the invalid
+/// value is given in degrees
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub enum InvalidAngle {
+    /// Latitudes must be within [-90°, 90°]
+    Latitude(f64),
+    /// Longitudes must be within [-180°, 180°]
+    Longitude(f64),
+    /// Azimuths must be finite
+    Azimuth(f64),
+    /// Elevations must be within [-90°, 90°]
+    Elevation(f64),
+}
+
+impl fmt::Display for InvalidAngle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidAngle::Latitude(lat) => write!(f, "Latitude out of range ({}°)", lat),
+            InvalidAngle::Longitude(lon) => write!(f, "Longitude out of range ({}°)", lon),
+            InvalidAngle::Azimuth(az) => write!(f, "Azimuth is not finite ({}°)", az),
+            InvalidAngle::Elevation(el) => write!(f, "Elevation out of range ({}°)", el),
+        }
+    }
+}
+
+impl Error for InvalidAngle {}
+
+/// The angle types, for checking ranges in the unit of the angle
+pub(crate) trait Angle: Copy {
+    const HALF_TURN: f64;
+    fn value(self) -> f64;
+    fn degrees(self) -> f64;
+}
+
+impl Angle for Degrees {
+    const HALF_TURN: f64 = 180.0;
+    fn value(self) -> f64 {
+        self.0
+    }
+    fn degrees(self) -> f64 {
+        self.0
+    }
+}
+
+impl Angle for Radians {
+    const HALF_TURN: f64 = std::f64::consts::PI;
+    fn value(self) -> f64 {
+        self.0
+    }
+    fn degrees(self) -> f64 {
+        self.0.to_degrees()
+    }
+}
+
+pub(crate) fn check_latitude<A: Angle>(lat: A) -> Result<A, InvalidAngle> {
+    if lat.value().abs() <= A::HALF_TURN / 2.0 {
+        Ok(lat)
+    } else {
+        Err(InvalidAngle::Latitude(lat.degrees()))
+    }
+}
+
+pub(crate) fn check_longitude<A: Angle>(lon: A) -> Result<A, InvalidAngle> {
+    if lon.value().abs() <= A::HALF_TURN {
+        Ok(lon)
+    } else {
+        Err(InvalidAngle::Longitude(lon.degrees()))
+    }
+}
+
+pub(crate) fn check_azimuth<A: Angle>(az: A) -> Result<A, InvalidAngle> {
+    if az.value().is_finite() {
+        Ok(az)
+    } else {
+        Err(InvalidAngle::Azimuth(az.degrees()))
+    }
+}
+
+pub(crate) fn check_elevation<A: Angle>(el: A) -> Result<A, InvalidAngle> {
+    if el.value().abs() <= A::HALF_TURN / 2.0 {
+        Ok(el)
+    } else {
+        Err(InvalidAngle::Elevation(el.degrees()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::{AzimuthElevation, LLHDegrees, LLHRadians};
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn conversions() {
+        let angle = Degrees::new(180.0);
+        assert_float_eq!(
+            angle.to_radians().value(),
+            std::f64::consts::PI,
+            abs <= 1e-15
+        );
+        assert_float_eq!(
+            Degrees::from(Radians::from(angle)).value(),
+            180.0,
+            abs <= 1e-12
+        );
+        assert_eq!(Degrees::new(10.0) - Degrees::new(30.0), -Degrees::new(20.0));
+        assert_eq!(format!("{:.1}", Degrees::new(12.25)), "12.2°");
+        assert_eq!(format!("{}", Radians::new(0.5)), "0.5 rad");
+    }
+
+    #[test]
+    fn coordinates() {
+        let llh = LLHDegrees::from_angles(Degrees::new(37.5), Radians::new(-2.0), 10.0).unwrap();
+        assert_eq!(llh.latitude_angle(), Degrees::new(37.5));
+        assert_float_eq!(llh.longitude(), -2f64.to_degrees(), abs <= 1e-12);
+
+        let llh = LLHRadians::from_angles(Degrees::new(-45.0), Degrees::new(90.0), 0.0).unwrap();
+        assert_float_eq!(llh.latitude(), -std::f64::consts::FRAC_PI_4, abs <= 1e-15);
+        assert_float_eq!(
+            llh.longitude_angle().to_degrees().value(),
+            90.0,
+            abs <= 1e-12
+        );
+
+        assert_eq!(
+            LLHDegrees::from_angles(Degrees::new(91.0), Degrees::new(0.0), 0.0),
+            Err(InvalidAngle::Latitude(91.0))
+        );
+        assert!(matches!(
+            LLHRadians::from_angles(Radians::new(0.0), Degrees::new(-181.0), 0.0),
+            Err(InvalidAngle::Longitude(_))
+        ));
+
+        let azel = AzimuthElevation::from_angles(Degrees::new(-90.0), Degrees::new(30.0)).unwrap();
+        assert_float_eq!(azel.azimuth().to_degrees().value(), 270.0, abs <= 1e-12);
+        assert_float_eq!(azel.elevation().to_degrees().value(), 30.0, abs <= 1e-12);
+        assert!(matches!(
+            AzimuthElevation::from_angles(Degrees::new(0.0), Degrees::new(95.0)),
+            Err(InvalidAngle::Elevation(_))
+        ));
+        assert!(matches!(
+            AzimuthElevation::from_angles(Radians::new(f64::NAN), Degrees::new(0.0)),
+            Err(InvalidAngle::Azimuth(_))
+        ));
+    }
+}
@@ -0,0 +1,547 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Batch conversions between geodetic and ECEF coordinates
+//!
+//! Converting millions of points one [`ECEF`] at a time spends most of its
+//! time shuffling the interleaved `[x, y, z]` arrays around. The batches here
+//! keep each component in its own contiguous array instead, a structure of
+//! arrays layout, and convert them in fixed size chunks.
+//!
+//! The ECEF to geodetic conversion uses the same Fukushima (2006) method as
+//! [`ECEF::to_llh()`], but with a fixed number of Halley iterations and no
+//! branches inside the iteration, so the compiler can vectorize it across the
+//! points of a chunk. Halley's method converges cubically, so the three
+//! iterations reach the limits of double precision for points from near the
+//! center of the Earth out beyond the GNSS orbits.
+//!
+//! # References
+//!   * "Transformation from Cartesian to Geodetic Coordinates Accelerated by
+//!      Halley’s Method", T. Fukushima (2006), Journal of Geodesy.
+
+use crate::coords::{LLHRadians, ECEF};
+use crate::ellipsoid::{Ellipsoid, WGS84};
+use std::f64::consts::FRAC_PI_2;
+
+/// Number of points converted together
+const LANES: usize = 8;
+
+/// Number of Halley iterations in the ECEF to geodetic conversion
+const ITERATIONS: usize = 3;
+
+/// A batch of ECEF positions, stored as separate arrays of x, y and z
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EcefBatch {
+    x: Vec<f64>,
+    y: Vec<f64>,
+    z: Vec<f64>,
+}
+
+impl EcefBatch {
+    pub fn new() -> EcefBatch {
+        EcefBatch::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> EcefBatch {
+        EcefBatch {
+            x: Vec::with_capacity(capacity),
+            y: Vec::with_capacity(capacity),
+            z: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Makes a batch from the component arrays, in meters
+    ///
+    /// # Panics
+    ///
+    /// Panics if the arrays have different lengths
+    pub fn from_components(x: Vec<f64>, y: Vec<f64>, z: Vec<f64>) -> EcefBatch {
+        assert!(
+            x.len() == y.len() && y.len() == z.len(),
+            "The component arrays must have the same length"
+        );
+        EcefBatch { x, y, z }
+    }
+
+    pub fn push(&mut self, ecef: ECEF) {
+        self.x.push(ecef.x());
+        self.y.push(ecef.y());
+        self.z.push(ecef.z());
+    }
+
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<ECEF> {
+        Some(ECEF::new(*self.x.get(index)?, self.y[index], self.z[index]))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ECEF> + '_ {
+        (0..self.len()).map(move |i| ECEF::new(self.x[i], self.y[i], self.z[i]))
+    }
+
+    pub fn x(&self) -> &[f64] {
+        &self.x
+    }
+
+    pub fn y(&self) -> &[f64] {
+        &self.y
+    }
+
+    pub fn z(&self) -> &[f64] {
+        &self.z
+    }
+
+    /// Splits the batch into its x, y and z arrays
+    pub fn into_components(self) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+        (self.x, self.y, self.z)
+    }
+
+    /// Converts the positions to WGS84 geodetic coordinates
+    pub fn to_llh(&self) -> LlhBatch {
+        self.to_llh_on(&WGS84)
+    }
+
+    /// Converts the positions to geodetic coordinates on the given ellipsoid
+    pub fn to_llh_on<E: Ellipsoid + ?Sized>(&self, ellipsoid: &E) -> LlhBatch {
+        let mut llh = LlhBatch {
+            lat: vec![0.0; self.len()],
+            lon: vec![0.0; self.len()],
+            height: vec![0.0; self.len()],
+        };
+        ecef_to_llh_on(
+            &self.x,
+            &self.y,
+            &self.z,
+            &mut llh.lat,
+            &mut llh.lon,
+            &mut llh.height,
+            ellipsoid,
+        );
+        llh
+    }
+}
+
+impl Extend<ECEF> for EcefBatch {
+    fn extend<T: IntoIterator<Item = ECEF>>(&mut self, iter: T) {
+        for ecef in iter {
+            self.push(ecef);
+        }
+    }
+}
+
+impl std::iter::FromIterator<ECEF> for EcefBatch {
+    fn from_iter<T: IntoIterator<Item = ECEF>>(iter: T) -> EcefBatch {
+        let mut batch = EcefBatch::new();
+        batch.extend(iter);
+        batch
+    }
+}
+
+/// A batch of geodetic positions, stored as separate arrays of latitude,
+/// longitude and height
+///
+/// The latitudes and longitudes are in radians, the heights in meters.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LlhBatch {
+    lat: Vec<f64>,
+    lon: Vec<f64>,
+    height: Vec<f64>,
+}
+
+impl LlhBatch {
+    pub fn new() -> LlhBatch {
+        LlhBatch::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> LlhBatch {
+        LlhBatch {
+            lat: Vec::with_capacity(capacity),
+            lon: Vec::with_capacity(capacity),
+            height: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Makes a batch from the component arrays, in radians and meters
+    ///
+    /// # Panics
+    ///
+    /// Panics if the arrays have different lengths
+    pub fn from_components(lat: Vec<f64>, lon: Vec<f64>, height: Vec<f64>) -> LlhBatch {
+        assert!(
+            lat.len() == lon.len() && lon.len() == height.len(),
+            "The component arrays must have the same length"
+        );
+        LlhBatch { lat, lon, height }
+    }
+
+    pub fn push(&mut self, llh: LLHRadians) {
+        self.lat.push(llh.latitude());
+        self.lon.push(llh.longitude());
+        self.height.push(llh.height());
+    }
+
+    pub fn len(&self) -> usize {
+        self.lat.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lat.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<LLHRadians> {
+        Some(LLHRadians::new(
+            *self.lat.get(index)?,
+            self.lon[index],
+            self.height[index],
+        ))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = LLHRadians> + '_ {
+        (0..self.len()).map(move |i| LLHRadians::new(self.lat[i], self.lon[i], self.height[i]))
+    }
+
+    pub fn latitude(&self) -> &[f64] {
+        &self.lat
+    }
+
+    pub fn longitude(&self) -> &[f64] {
+        &self.lon
+    }
+
+    pub fn height(&self) -> &[f64] {
+        &self.height
+    }
+
+    /// Splits the batch into its latitude, longitude and height arrays
+    pub fn into_components(self) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+        (self.lat, self.lon, self.height)
+    }
+
+    /// Converts WGS84 geodetic coordinates to ECEF positions
+    pub fn to_ecef(&self) -> EcefBatch {
+        self.to_ecef_on(&WGS84)
+    }
+
+    /// Converts geodetic coordinates on the given ellipsoid to ECEF
+    /// positions
+    pub fn to_ecef_on<E: Ellipsoid + ?Sized>(&self, ellipsoid: &E) -> EcefBatch {
+        let mut ecef = EcefBatch {
+            x: vec![0.0; self.len()],
+            y: vec![0.0; self.len()],
+            z: vec![0.0; self.len()],
+        };
+        llh_to_ecef_on(
+            &self.lat,
+            &self.lon,
+            &self.height,
+            &mut ecef.x,
+            &mut ecef.y,
+            &mut ecef.z,
+            ellipsoid,
+        );
+        ecef
+    }
+}
+
+impl Extend<LLHRadians> for LlhBatch {
+    fn extend<T: IntoIterator<Item = LLHRadians>>(&mut self, iter: T) {
+        for llh in iter {
+            self.push(llh);
+        }
+    }
+}
+
+impl std::iter::FromIterator<LLHRadians> for LlhBatch {
+    fn from_iter<T: IntoIterator<Item = LLHRadians>>(iter: T) -> LlhBatch {
+        let mut batch = LlhBatch::new();
+        batch.extend(iter);
+        batch
+    }
+}
+
+/// Converts ECEF positions, in meters, into geodetic coordinates on an
+/// ellipsoid, writing the results into the output slices
+///
+/// # Panics
+///
+/// Panics if the slices have different lengths
+pub fn ecef_to_llh_on<E: Ellipsoid + ?Sized>(
+    x: &[f64],
+    y: &[f64],
+    z: &[f64],
+    lat: &mut [f64],
+    lon: &mut [f64],
+    height: &mut [f64],
+    ellipsoid: &E,
+) {
+    let n = x.len();
+    assert!(
+        [y.len(), z.len(), lat.len(), lon.len(), height.len()]
+            .iter()
+            .all(|len| *len == n),
+        "The input and output slices must have the same length"
+    );
+    let constants = FukushimaConstants::new(ellipsoid);
+
+    let full = n - n % LANES;
+    for start in (0..full).step_by(LANES) {
+        let end = start + LANES;
+        convert_chunk(
+            &constants,
+            &x[start..end],
+            &y[start..end],
+            &z[start..end],
+            &mut lat[start..end],
+            &mut lon[start..end],
+            &mut height[start..end],
+        );
+    }
+    for i in full..n {
+        let (la, lo, h) = constants.convert(x[i], y[i], z[i]);
+        lat[i] = la;
+        lon[i] = lo;
+        height[i] = h;
+    }
+}
+
+/// Converts WGS84 ECEF positions into WGS84 geodetic coordinates, see
+/// [`ecef_to_llh_on()`]
+pub fn ecef_to_llh(
+    x: &[f64],
+    y: &[f64],
+    z: &[f64],
+    lat: &mut [f64],
+    lon: &mut [f64],
+    height: &mut [f64],
+) {
+    ecef_to_llh_on(x, y, z, lat, lon, height, &WGS84)
+}
+
+/// Converts geodetic coordinates on an ellipsoid, in radians and meters, into
+/// ECEF positions, writing the results into the output slices
+///
+/// # Panics
+///
+/// Panics if the slices have different lengths
+pub fn llh_to_ecef_on<E: Ellipsoid + ?Sized>(
+    lat: &[f64],
+    lon: &[f64],
+    height: &[f64],
+    x: &mut [f64],
+    y: &mut [f64],
+    z: &mut [f64],
+    ellipsoid: &E,
+) {
+    let n = lat.len();
+    assert!(
+        [lon.len(), height.len(), x.len(), y.len(), z.len()]
+            .iter()
+            .all(|len| *len == n),
+        "The input and output slices must have the same length"
+    );
+    let a = ellipsoid.semi_major_axis();
+    let e2 = ellipsoid.eccentricity_squared();
+
+    for i in 0..n {
+        let (sin_lat, cos_lat) = lat[i].sin_cos();
+        let (sin_lon, cos_lon) = lon[i].sin_cos();
+        let n = a / (1.0 - e2 * sin_lat * sin_lat).sqrt();
+        let h = height[i];
+        x[i] = (n + h) * cos_lat * cos_lon;
+        y[i] = (n + h) * cos_lat * sin_lon;
+        z[i] = (n * (1.0 - e2) + h) * sin_lat;
+    }
+}
+
+/// Converts WGS84 geodetic coordinates into WGS84 ECEF positions, see
+/// [`llh_to_ecef_on()`]
+pub fn llh_to_ecef(
+    lat: &[f64],
+    lon: &[f64],
+    height: &[f64],
+    x: &mut [f64],
+    y: &mut [f64],
+    z: &mut [f64],
+) {
+    llh_to_ecef_on(lat, lon, height, x, y, z, &WGS84)
+}
+
+/// The ellipsoid constants used by the Fukushima conversion
+struct FukushimaConstants {
+    a: f64,
+    e2: f64,
+    /// sqrt(1 - e²)
+    ec: f64,
+}
+
+impl FukushimaConstants {
+    fn new<E: Ellipsoid + ?Sized>(ellipsoid: &E) -> FukushimaConstants {
+        let e2 = ellipsoid.eccentricity_squared();
+        FukushimaConstants {
+            a: ellipsoid.semi_major_axis(),
+            e2,
+            ec: (1.0 - e2).sqrt(),
+        }
+    }
+
+    /// Finds the sine and cosine, up to a common scale, of the parametric
+    /// latitude from the normalized distances from the axis and equator
+    #[inline(always)]
+    fn iterate(&self, p_norm: f64, z_norm: f64) -> (f64, f64) {
+        let mut s = z_norm;
+        let mut c = self.ec * p_norm;
+        for _ in 0..ITERATIONS {
+            let a2 = s * s + c * c;
+            let a = a2.sqrt();
+            let d = z_norm * a2 * a + self.e2 * s * s * s;
+            let f = p_norm * a2 * a - self.e2 * c * c * c;
+            let b = 1.5 * self.e2 * s * c * c * (a * (p_norm * s - z_norm * c) - self.e2 * s * c);
+            let next_s = d * f - b * s;
+            let next_c = f * f - b * c;
+            // Rescale to keep the values from overflowing
+            let scale = next_s.max(next_c);
+            s = next_s / scale;
+            c = next_c / scale;
+        }
+        (s, c)
+    }
+
+    /// Turns the result of the iteration into a latitude and height
+    #[inline(always)]
+    fn finish(&self, p: f64, z: f64, s: f64, c: f64) -> (f64, f64) {
+        if p < self.a * 1e-16 {
+            // On the polar axis the iteration breaks down
+            (FRAC_PI_2.copysign(z), z.abs() - self.a * self.ec)
+        } else {
+            let a = (s * s + c * c).sqrt();
+            let lat = (s / (self.ec * c)).atan().copysign(z);
+            let height = (p * self.ec * c + z.abs() * s - self.a * self.ec * a)
+                / (self.ec * self.ec * c * c + s * s).sqrt();
+            (lat, height)
+        }
+    }
+
+    fn convert(&self, x: f64, y: f64, z: f64) -> (f64, f64, f64) {
+        let p = x.hypot(y);
+        let (s, c) = self.iterate(p / self.a, z.abs() * self.ec / self.a);
+        let (lat, height) = self.finish(p, z, s, c);
+        (lat, y.atan2(x), height)
+    }
+}
+
+/// Converts a chunk of [`LANES`] points
+///
+/// The stages are split into separate loops over fixed size arrays so the
+/// iteration, which only uses arithmetic and square roots, vectorizes.
+fn convert_chunk(
+    constants: &FukushimaConstants,
+    x: &[f64],
+    y: &[f64],
+    z: &[f64],
+    lat: &mut [f64],
+    lon: &mut [f64],
+    height: &mut [f64],
+) {
+    let mut p = [0.0; LANES];
+    let mut s = [0.0; LANES];
+    let mut c = [0.0; LANES];
+    for i in 0..LANES {
+        p[i] = (x[i] * x[i] + y[i] * y[i]).sqrt();
+    }
+    for i in 0..LANES {
+        let (si, ci) =
+            constants.iterate(p[i] / constants.a, z[i].abs() * constants.ec / constants.a);
+        s[i] = si;
+        c[i] = ci;
+    }
+    for i in 0..LANES {
+        let (la, h) = constants.finish(p[i], z[i], s[i], c[i]);
+        lat[i] = la;
+        height[i] = h;
+        lon[i] = y[i].atan2(x[i]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ellipsoid::GRS80;
+    use float_eq::assert_float_eq;
+
+    fn test_points() -> Vec<LLHRadians> {
+        let mut points = Vec::new();
+        for lat in (-90..=90).step_by(15) {
+            for lon in (-180..180).step_by(45) {
+                for height in [-100.0, 0.0, 1_000.0, 400e3, 20_200e3].iter() {
+                    points.push(LLHRadians::new(
+                        f64::from(lat).to_radians(),
+                        f64::from(lon).to_radians(),
+                        *height,
+                    ));
+                }
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn round_trip() {
+        let points = test_points();
+        let llh: LlhBatch = points.iter().copied().collect();
+        let ecef = llh.to_ecef();
+        assert_eq!(ecef.len(), points.len());
+        let back = ecef.to_llh();
+        assert_eq!(back.len(), points.len());
+
+        for (i, point) in points.iter().enumerate() {
+            let expected = WGS84.llh_to_ecef(point);
+            let position = ecef.get(i).unwrap();
+            assert_float_eq!(position.x(), expected.x(), abs <= 1e-6);
+            assert_float_eq!(position.y(), expected.y(), abs <= 1e-6);
+            assert_float_eq!(position.z(), expected.z(), abs <= 1e-6);
+
+            let converted = back.get(i).unwrap();
+            assert_float_eq!(converted.latitude(), point.latitude(), abs <= 1e-11);
+            assert_float_eq!(converted.height(), point.height(), abs <= 1e-5);
+            // The longitude is undefined at the poles
+            if point.latitude().abs() < FRAC_PI_2 - 1e-9 {
+                let dlon = (converted.longitude() - point.longitude()).sin();
+                assert_float_eq!(dlon, 0.0, abs <= 1e-11);
+            }
+        }
+    }
+
+    #[test]
+    fn matches_single_conversion() {
+        let points: EcefBatch = test_points()
+            .iter()
+            .map(|llh| GRS80.llh_to_ecef(llh))
+            .collect();
+        let llh = points.to_llh_on(&GRS80);
+        for (position, converted) in points.iter().zip(llh.iter()) {
+            let expected = GRS80.ecef_to_llh(&position);
+            assert_float_eq!(converted.latitude(), expected.latitude(), abs <= 1e-11);
+            assert_float_eq!(converted.height(), expected.height(), abs <= 1e-5);
+        }
+
+        let (x, y, z) = points.clone().into_components();
+        let mut lat = vec![0.0; x.len()];
+        let mut lon = vec![0.0; x.len()];
+        let mut height = vec![0.0; x.len()];
+        ecef_to_llh_on(&x, &y, &z, &mut lat, &mut lon, &mut height, &GRS80);
+        assert_eq!(LlhBatch::from_components(lat, lon, height), llh);
+        assert_eq!(EcefBatch::from_components(x, y, z), points);
+    }
+}
@@ -0,0 +1,279 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Text formats for coordinates
+//!
+//! [`LLHDegrees`] and [`ECEF`] positions are written as three comma separated
+//! values, such as `37.5, -122.1, 20` and `-2706104.3, -4261137.5, 3885386.2`.
+//! Without a precision the shortest representation of each value is used,
+//! which parses back to exactly the same position. With a precision, e.g.
+//! `{:.9}`, each value is written with that many decimals.
+//!
+//! Geodetic positions can also be written in degrees, minutes and seconds
+//! with the alternate flag, e.g. `{:#}` gives `37°30'00.00000"N,
+//! 122°06'00.00000"W, 20`. The precision then gives the number of decimals of
+//! the seconds, five by default.
+//!
+//! Both forms can be parsed, and the values may also be separated by
+//! whitespace instead of commas.
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use super::angle::{self, Degrees};
+use super::{LLHDegrees, ECEF};
+
+/// Error type when a coordinate string can't be parsed
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ParseCoordinateError {
+    /// The string doesn't have the expected layout
+    InvalidFormat,
+    /// One of the values of the string is out of range
+    InvalidValue,
+}
+
+impl fmt::Display for ParseCoordinateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseCoordinateError::InvalidFormat => write!(f, "Invalid coordinate format"),
+            ParseCoordinateError::InvalidValue => write!(f, "Coordinate value out of range"),
+        }
+    }
+}
+
+impl Error for ParseCoordinateError {}
+
+fn write_value(f: &mut fmt::Formatter<'_>, value: f64) -> fmt::Result {
+    match f.precision() {
+        Some(precision) => write!(f, "{:.*}", precision, value),
+        None => write!(f, "{}", value),
+    }
+}
+
+fn write_dms(
+    f: &mut fmt::Formatter<'_>,
+    value: f64,
+    positive: char,
+    negative: char,
+) -> fmt::Result {
+    let decimals = f.precision().unwrap_or(5).min(9);
+    let scale = 10u64.pow(decimals as u32);
+    let hemisphere = if value < 0.0 { negative } else { positive };
+    // Round once in units of the last decimal of the seconds, so a rounded up
+    // second carries into the minutes and degrees
+    let units = (value.abs() * 3600.0 * scale as f64).round() as u64;
+    let minutes = units / (60 * scale);
+    let seconds = (units % (60 * scale)) as f64 / scale as f64;
+    let width = if decimals > 0 { decimals + 3 } else { 2 };
+    write!(
+        f,
+        "{}°{:02}'{:0width$.decimals$}\"{}",
+        minutes / 60,
+        minutes % 60,
+        seconds,
+        hemisphere,
+        width = width,
+        decimals = decimals
+    )
+}
+
+/// Formats the position as `latitude, longitude, height`, in decimal degrees
+/// or with the alternate flag in degrees, minutes and seconds
+impl fmt::Display for LLHDegrees {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() && self.latitude().is_finite() && self.longitude().is_finite() {
+            write_dms(f, self.latitude(), 'N', 'S')?;
+            write!(f, ", ")?;
+            write_dms(f, self.longitude(), 'E', 'W')?;
+        } else {
+            write_value(f, self.latitude())?;
+            write!(f, ", ")?;
+            write_value(f, self.longitude())?;
+        }
+        write!(f, ", ")?;
+        write_value(f, self.height())
+    }
+}
+
+/// Formats the position as `x, y, z` in meters
+impl fmt::Display for ECEF {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_value(f, self.x())?;
+        write!(f, ", ")?;
+        write_value(f, self.y())?;
+        write!(f, ", ")?;
+        write_value(f, self.z())
+    }
+}
+
+fn split_fields(s: &str) -> Result<[&str; 3], ParseCoordinateError> {
+    let fields: Vec<&str> = if s.contains(',') {
+        s.split(',').map(str::trim).collect()
+    } else {
+        s.split_whitespace().collect()
+    };
+    match fields[..] {
+        [a, b, c] => Ok([a, b, c]),
+        _ => Err(ParseCoordinateError::InvalidFormat),
+    }
+}
+
+fn parse_number(s: &str) -> Result<f64, ParseCoordinateError> {
+    s.parse().map_err(|_| ParseCoordinateError::InvalidFormat)
+}
+
+/// Parses an angle in decimal degrees or in degrees, minutes and seconds,
+/// with an optional sign or hemisphere letter
+fn parse_angle(field: &str, positive: char, negative: char) -> Result<f64, ParseCoordinateError> {
+    let field: String = field.chars().filter(|c| !c.is_whitespace()).collect();
+    let (body, mut sign) = match field.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some(c) if c == positive => (&field[..field.len() - 1], 1.0),
+        Some(c) if c == negative => (&field[..field.len() - 1], -1.0),
+        _ => (field.as_str(), 1.0),
+    };
+    let body = match body.strip_prefix('-') {
+        Some(body) => {
+            sign = -sign;
+            body
+        }
+        None => body.strip_prefix('+').unwrap_or(body),
+    };
+    if body.starts_with(['-', '+']) {
+        return Err(ParseCoordinateError::InvalidFormat);
+    }
+
+    let (degrees, rest) = match body.split_once('°') {
+        Some((degrees, rest)) => (parse_number(degrees)?, rest),
+        None => (parse_number(body)?, ""),
+    };
+    let (minutes, rest) = match rest.split_once('\'') {
+        Some((minutes, rest)) => (parse_number(minutes)?, rest),
+        None => (0.0, rest),
+    };
+    let seconds = match rest.split_once('"') {
+        Some((seconds, "")) => parse_number(seconds)?,
+        None if rest.is_empty() => 0.0,
+        _ => return Err(ParseCoordinateError::InvalidFormat),
+    };
+    if !(0.0..60.0).contains(&minutes) || !(0.0..60.0).contains(&seconds) {
+        return Err(ParseCoordinateError::InvalidValue);
+    }
+    Ok(sign * (degrees + minutes / 60.0 + seconds / 3600.0))
+}
+
+/// Parses a position in the `latitude, longitude, height` notation
+///
+/// The angles can be in decimal degrees or in degrees, minutes and seconds,
+/// and can have a hemisphere letter instead of a sign. The height can be
+/// followed by `m`.
+impl FromStr for LLHDegrees {
+    type Err = ParseCoordinateError;
+
+    fn from_str(s: &str) -> Result<LLHDegrees, ParseCoordinateError> {
+        let [lat, lon, height] = split_fields(s.trim())?;
+        let lat = angle::check_latitude(Degrees::new(parse_angle(lat, 'N', 'S')?))
+            .map_err(|_| ParseCoordinateError::InvalidValue)?;
+        let lon = angle::check_longitude(Degrees::new(parse_angle(lon, 'E', 'W')?))
+            .map_err(|_| ParseCoordinateError::InvalidValue)?;
+        let height = parse_number(height.strip_suffix('m').unwrap_or(height).trim_end())?;
+        if !height.is_finite() {
+            return Err(ParseCoordinateError::InvalidValue);
+        }
+        Ok(LLHDegrees::new(lat.value(), lon.value(), height))
+    }
+}
+
+/// Parses a position in the `x, y, z` notation, in meters
+impl FromStr for ECEF {
+    type Err = ParseCoordinateError;
+
+    fn from_str(s: &str) -> Result<ECEF, ParseCoordinateError> {
+        let [x, y, z] = split_fields(s.trim())?;
+        let ecef = ECEF::new(parse_number(x)?, parse_number(y)?, parse_number(z)?);
+        if ecef.as_array_ref().iter().all(|v| v.is_finite()) {
+            Ok(ecef)
+        } else {
+            Err(ParseCoordinateError::InvalidValue)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn geodetic() {
+        let llh = LLHDegrees::new(37.5, -122.1, 20.25);
+        assert_eq!(format!("{}", llh), "37.5, -122.1, 20.25");
+        assert_eq!(format!("{:.3}", llh), "37.500, -122.100, 20.250");
+        assert_eq!(
+            format!("{:#}", llh),
+            "37°30'00.00000\"N, 122°06'00.00000\"W, 20.25"
+        );
+        assert_eq!(
+            format!("{:#.0}", LLHDegrees::new(-0.99999, 179.5, 0.0)),
+            "1°00'00\"S, 179°30'00\"E, 0"
+        );
+        assert_eq!(llh.to_string().parse::<LLHDegrees>(), Ok(llh));
+
+        let parsed: LLHDegrees = format!("{:#}", llh).parse().unwrap();
+        assert_float_eq!(parsed.latitude(), 37.5, abs <= 1e-9);
+        assert_float_eq!(parsed.longitude(), -122.1, abs <= 1e-9);
+        assert_float_eq!(parsed.height(), 20.25, abs <= 1e-9);
+
+        let parsed: LLHDegrees = "33° 51' 54\" s, 151°12'35.5\"E, 58 m".parse().unwrap();
+        assert_float_eq!(parsed.latitude(), -33.865, abs <= 1e-9);
+        assert_float_eq!(
+            parsed.longitude(),
+            151.0 + 12.0 / 60.0 + 35.5 / 3600.0,
+            abs <= 1e-9
+        );
+        assert_float_eq!(parsed.height(), 58.0, abs <= 1e-9);
+
+        assert_eq!(
+            "91, 0, 0".parse::<LLHDegrees>(),
+            Err(ParseCoordinateError::InvalidValue)
+        );
+        assert_eq!(
+            "10°61'0\"N, 0, 0".parse::<LLHDegrees>(),
+            Err(ParseCoordinateError::InvalidValue)
+        );
+        assert_eq!(
+            "10N, 20N, 0".parse::<LLHDegrees>(),
+            Err(ParseCoordinateError::InvalidFormat)
+        );
+        assert_eq!(
+            "10, 20".parse::<LLHDegrees>(),
+            Err(ParseCoordinateError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn ecef() {
+        let ecef = ECEF::new(-2706104.25, -4261137.5, 3885386.125);
+        assert_eq!(format!("{}", ecef), "-2706104.25, -4261137.5, 3885386.125");
+        assert_eq!(
+            format!("{:.3}", ecef),
+            "-2706104.250, -4261137.500, 3885386.125"
+        );
+        assert_eq!(ecef.to_string().parse::<ECEF>(), Ok(ecef));
+        assert_eq!("1.5 -2 3e6".parse::<ECEF>(), Ok(ECEF::new(1.5, -2.0, 3e6)));
+        assert_eq!(
+            "1, 2, nan".parse::<ECEF>(),
+            Err(ParseCoordinateError::InvalidValue)
+        );
+        assert_eq!(
+            "1, 2, x".parse::<ECEF>(),
+            Err(ParseCoordinateError::InvalidFormat)
+        );
+    }
+}
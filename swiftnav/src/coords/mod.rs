@@ -0,0 +1,2578 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Coordinates and conversions
+//!
+//! These four primary coordinates types are defined:
+//!  * [LLHDegrees]/[LLHRadians] - Geodetic coordinates, Latitude Lontitude Height
+//!  * [ECEF] - Cartesian coordinates, Earth Centered, Earth Fixed
+//!  * [NED] - Relative direction coordinates, North East Down
+//!  * [AzimuthElevation] - Relative direction coordinates, Azimith Elevation
+//!
+//! Large numbers of points can be converted between geodetic and ECEF
+//! coordinates with the structure of arrays types in [batch].
+//!
+//! The geodetic and direction types store their angles as plain numbers, they
+//! can also be made from and give out [Degrees] and [Radians] to keep the units
+//! explicit, see [angle].
+//!
+//! Positions read from outside sources can be sanity checked with the
+//! functions in [validation].
+//!
+//! Geodetic and ECEF positions can be written to and parsed from text, see
+//! [format] for the notations.
+//!
+//! Networks of stations measured with baselines between them can be adjusted
+//! with [network].
+//!
+//! --------
+//! Conversion from geodetic coordinates latitude, longitude and height
+//! (ϕ, λ, h) into Cartesian coordinates (X, Y, Z) can be
+//! achieved with the following formulae:
+//!  * X = (N(ϕ) + h) * cos(ϕ) * cos(λ)
+//!  * Y = (N(ϕ) + h) * cos(ϕ) * sin(λ)
+//!  * Z = [(1-e^2) * N(ϕ) + h] * sin(ϕ)
+//!
+//! Where the 'radius of curvature', N(ϕ), is defined as:
+//!  * N(ϕ) = a / sqrt(1-e^2 / sin^2(ϕ))
+//!
+//! and `a` is the WGS84 semi-major axis and `e` is the WGS84
+//! eccentricity.
+//!
+//! --------
+//! Conversion from Cartesian to geodetic coordinates is a much harder problem
+//! than conversion from geodetic to Cartesian. There is no satisfactory closed
+//! form solution but many different iterative approaches exist.
+//!
+//! Here we implement a relatively new algorithm due to Fukushima (2006) that is
+//! very computationally efficient, not requiring any transcendental function
+//! calls during iteration and very few divisions. It also exhibits cubic
+//! convergence rates compared to the quadratic rate of convergence seen with
+//! the more common algortihms based on the Newton-Raphson method.
+//!
+//! # References
+//!   * "A comparison of methods used in rectangular to Geodetic Coordinates
+//!      Transformations", Burtch R. R. (2006), American Congress for Surveying
+//!      and Mapping Annual Conference. Orlando, Florida.
+//!   * "Transformation from Cartesian to Geodetic Coordinates Accelerated by
+//!      Halley’s Method", T. Fukushima (2006), Journal of Geodesy.
+
+pub mod angle;
+pub mod batch;
+pub mod format;
+pub mod network;
+pub mod validation;
+
+pub use angle::{Degrees, InvalidAngle, Radians};
+pub use format::ParseCoordinateError;
+pub use validation::InvalidCoordinate;
+
+use std::error::Error;
+use std::fmt;
+use std::ops::{Add, AddAssign, Mul, MulAssign, Sub, SubAssign};
+
+use crate::{
+    ellipsoid::{Ellipsoid, WGS84},
+    reference_frame::{
+        get_transformation_at, CoordinateCovariance, ReferenceFrame, TransformationGraph,
+        TransformationNotFound, ValidityWarning,
+    },
+    time::GpsTime,
+};
+
+/// WGS84 geodetic coordinates (Latitude, Longitude, Height)
+///
+/// Internally stored as an array of 3 [f64](std::f64) values: latitude, longitude (both in the given angular units) and height above the geoid in meters
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub struct LLHDegrees([f64; 3]);
+
+impl LLHDegrees {
+    pub fn new(lat: f64, lon: f64, height: f64) -> LLHDegrees {
+        LLHDegrees([lat, lon, height])
+    }
+
+    /// Makes a position from angles in either unit, checking the latitude is
+    /// within [-90°, 90°] and the longitude within [-180°, 180°]
+    pub fn from_angles(
+        lat: impl Into<Degrees>,
+        lon: impl Into<Degrees>,
+        height: f64,
+    ) -> Result<LLHDegrees, InvalidAngle> {
+        let lat = angle::check_latitude(lat.into())?;
+        let lon = angle::check_longitude(lon.into())?;
+        Ok(LLHDegrees([lat.value(), lon.value(), height]))
+    }
+
+    pub fn from_array(array: &[f64; 3]) -> LLHDegrees {
+        LLHDegrees(*array)
+    }
+
+    pub fn as_ptr(&self) -> *const [f64; 3] {
+        &self.0
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut [f64; 3] {
+        &mut self.0
+    }
+
+    pub fn as_array_ref(&self) -> &[f64; 3] {
+        &self.0
+    }
+
+    pub fn as_mut_array_ref(&mut self) -> &mut [f64; 3] {
+        &mut self.0
+    }
+
+    pub fn latitude(&self) -> f64 {
+        self.0[0]
+    }
+
+    pub fn longitude(&self) -> f64 {
+        self.0[1]
+    }
+
+    pub fn height(&self) -> f64 {
+        self.0[2]
+    }
+
+    pub fn latitude_angle(&self) -> Degrees {
+        Degrees::new(self.0[0])
+    }
+
+    pub fn longitude_angle(&self) -> Degrees {
+        Degrees::new(self.0[1])
+    }
+
+    /// Converts a LLH position from degrees to radians. The position doesn't change,
+    /// just the representation of the angular values.
+    pub fn to_radians(&self) -> LLHRadians {
+        let mut rad = LLHRadians::default();
+        unsafe { swiftnav_sys::llhdeg2rad(self.as_ptr(), rad.as_mut_ptr()) };
+        rad
+    }
+
+    /// Converts from WGS84 geodetic coordinates (latitude, longitude and height)
+    /// into WGS84 Earth Centered, Earth Fixed Cartesian (ECEF) coordinates
+    /// (X, Y and Z).
+    pub fn to_ecef(&self) -> ECEF {
+        self.to_radians().to_ecef()
+    }
+}
+
+impl Default for LLHDegrees {
+    fn default() -> LLHDegrees {
+        LLHDegrees::new(0., 0., 0.)
+    }
+}
+
+impl AsRef<[f64; 3]> for LLHDegrees {
+    fn as_ref(&self) -> &[f64; 3] {
+        &self.0
+    }
+}
+
+impl AsMut<[f64; 3]> for LLHDegrees {
+    fn as_mut(&mut self) -> &mut [f64; 3] {
+        &mut self.0
+    }
+}
+
+impl From<LLHDegrees> for LLHRadians {
+    fn from(deg: LLHDegrees) -> LLHRadians {
+        deg.to_radians()
+    }
+}
+
+impl From<ECEF> for LLHRadians {
+    fn from(ecef: ECEF) -> LLHRadians {
+        ecef.to_llh()
+    }
+}
+
+/// WGS84 geodetic coordinates (Latitude, Longitude, Height).
+///
+/// Internally stored as an array of 3 [f64](std::f64) values: latitude, longitude (both in the given angular units) and height above the geoid in meters
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub struct LLHRadians([f64; 3]);
+
+impl LLHRadians {
+    pub fn new(lat: f64, lon: f64, height: f64) -> LLHRadians {
+        LLHRadians([lat, lon, height])
+    }
+
+    /// Makes a position from angles in either unit, checking the latitude is
+    /// within [-π/2, π/2] and the longitude within [-π, π]
+    pub fn from_angles(
+        lat: impl Into<Radians>,
+        lon: impl Into<Radians>,
+        height: f64,
+    ) -> Result<LLHRadians, InvalidAngle> {
+        let lat = angle::check_latitude(lat.into())?;
+        let lon = angle::check_longitude(lon.into())?;
+        Ok(LLHRadians([lat.value(), lon.value(), height]))
+    }
+
+    pub fn from_array(array: &[f64; 3]) -> LLHRadians {
+        LLHRadians(*array)
+    }
+
+    pub fn as_ptr(&self) -> *const [f64; 3] {
+        &self.0
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut [f64; 3] {
+        &mut self.0
+    }
+
+    pub fn as_array_ref(&self) -> &[f64; 3] {
+        &self.0
+    }
+
+    pub fn as_mut_array_ref(&mut self) -> &mut [f64; 3] {
+        &mut self.0
+    }
+
+    pub fn latitude(&self) -> f64 {
+        self.0[0]
+    }
+
+    pub fn longitude(&self) -> f64 {
+        self.0[1]
+    }
+
+    pub fn height(&self) -> f64 {
+        self.0[2]
+    }
+
+    pub fn latitude_angle(&self) -> Radians {
+        Radians::new(self.0[0])
+    }
+
+    pub fn longitude_angle(&self) -> Radians {
+        Radians::new(self.0[1])
+    }
+
+    /// Converts a LLH position from radians to degrees. The position doesn't change,
+    /// just the representation of the angular values.
+    pub fn to_degrees(&self) -> LLHDegrees {
+        let mut deg = LLHDegrees::default();
+        unsafe { swiftnav_sys::llhrad2deg(self.as_ptr(), deg.as_mut_ptr()) };
+        deg
+    }
+
+    /// Converts from WGS84 geodetic coordinates (latitude, longitude and height)
+    /// into WGS84 Earth Centered, Earth Fixed Cartesian (ECEF) coordinates
+    /// (X, Y and Z).
+    pub fn to_ecef(&self) -> ECEF {
+        let mut ecef = ECEF::default();
+        unsafe { swiftnav_sys::wgsllh2ecef(self.as_ptr(), ecef.as_mut_ptr()) };
+        ecef
+    }
+
+    /// Converts from geodetic coordinates on the given ellipsoid into Earth
+    /// Centered, Earth Fixed Cartesian (ECEF) coordinates.
+    pub fn to_ecef_on<E: Ellipsoid + ?Sized>(&self, ellipsoid: &E) -> ECEF {
+        ellipsoid.llh_to_ecef(self)
+    }
+
+    /// Solves the inverse geodesic problem, finding the shortest path along
+    /// the WGS84 ellipsoid from this point to another one.
+    ///
+    /// The heights of the points are ignored. Uses Vincenty's iterative
+    /// method, which is accurate to well below a millimeter but fails to
+    /// converge for some nearly antipodal points.
+    ///
+    /// # References
+    ///   * "Direct and Inverse Solutions of Geodesics on the Ellipsoid with
+    ///     Application of Nested Equations", T. Vincenty (1975), Survey Review
+    pub fn geodesic_to(&self, other: &LLHRadians) -> Result<Geodesic, GeodesicError> {
+        let a = WGS84::SEMI_MAJOR_AXIS;
+        let f = WGS84::FLATTENING;
+        let b = WGS84.semi_minor_axis();
+
+        let l = other.longitude() - self.longitude();
+        let u1 = ((1.0 - f) * self.latitude().tan()).atan();
+        let u2 = ((1.0 - f) * other.latitude().tan()).atan();
+        let (sin_u1, cos_u1) = u1.sin_cos();
+        let (sin_u2, cos_u2) = u2.sin_cos();
+
+        let mut lambda = l;
+        let mut converged = false;
+        let (mut sin_sigma, mut cos_sigma, mut sigma) = (0.0, 1.0, 0.0);
+        let (mut cos2_alpha, mut cos_2sigma_m) = (1.0, 0.0);
+        for _ in 0..200 {
+            let (sin_lambda, cos_lambda) = lambda.sin_cos();
+            sin_sigma = ((cos_u2 * sin_lambda).powi(2)
+                + (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda).powi(2))
+            .sqrt();
+            if sin_sigma == 0.0 {
+                // Coincident points
+                return Ok(Geodesic {
+                    distance: 0.0,
+                    initial_azimuth: 0.0,
+                    final_azimuth: 0.0,
+                });
+            }
+            cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+            sigma = sin_sigma.atan2(cos_sigma);
+            let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+            cos2_alpha = 1.0 - sin_alpha * sin_alpha;
+            // Both points on the equator
+            cos_2sigma_m = if cos2_alpha != 0.0 {
+                cos_sigma - 2.0 * sin_u1 * sin_u2 / cos2_alpha
+            } else {
+                0.0
+            };
+            let c = f / 16.0 * cos2_alpha * (4.0 + f * (4.0 - 3.0 * cos2_alpha));
+            let previous = lambda;
+            lambda = l
+                + (1.0 - c)
+                    * f
+                    * sin_alpha
+                    * (sigma
+                        + c * sin_sigma
+                            * (cos_2sigma_m
+                                + c * cos_sigma * (-1.0 + 2.0 * cos_2sigma_m * cos_2sigma_m)));
+            if (lambda - previous).abs() < 1e-12 {
+                converged = true;
+                break;
+            }
+        }
+        if !converged {
+            return Err(GeodesicError::NotConverged);
+        }
+
+        let u_sq = cos2_alpha * (a * a - b * b) / (b * b);
+        let (big_a, big_b) = vincenty_coefficients(u_sq);
+        let delta_sigma = vincenty_delta_sigma(big_b, sin_sigma, cos_sigma, cos_2sigma_m);
+
+        let (sin_lambda, cos_lambda) = lambda.sin_cos();
+        Ok(Geodesic {
+            distance: b * big_a * (sigma - delta_sigma),
+            initial_azimuth: normalize_azimuth(
+                (cos_u2 * sin_lambda).atan2(cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda),
+            ),
+            final_azimuth: normalize_azimuth(
+                (cos_u1 * sin_lambda).atan2(-sin_u1 * cos_u2 + cos_u1 * sin_u2 * cos_lambda),
+            ),
+        })
+    }
+
+    /// Solves the direct geodesic problem, finding the point reached by
+    /// travelling `distance` meters along the WGS84 ellipsoid starting with
+    /// the given azimuth, in radians.
+    ///
+    /// Returns the destination, at the same height as this point, and the
+    /// azimuth of the path when it arrives there. Uses Vincenty's method,
+    /// which converges for all distances.
+    pub fn geodesic_destination(&self, azimuth: f64, distance: f64) -> (LLHRadians, f64) {
+        let a = WGS84::SEMI_MAJOR_AXIS;
+        let f = WGS84::FLATTENING;
+        let b = WGS84.semi_minor_axis();
+
+        let (sin_alpha1, cos_alpha1) = azimuth.sin_cos();
+        let u1 = ((1.0 - f) * self.latitude().tan()).atan();
+        let (sin_u1, cos_u1) = u1.sin_cos();
+        let sigma1 = u1.tan().atan2(cos_alpha1);
+        let sin_alpha = cos_u1 * sin_alpha1;
+        let cos2_alpha = 1.0 - sin_alpha * sin_alpha;
+        let u_sq = cos2_alpha * (a * a - b * b) / (b * b);
+        let (big_a, big_b) = vincenty_coefficients(u_sq);
+
+        let mut sigma = distance / (b * big_a);
+        for _ in 0..200 {
+            let cos_2sigma_m = (2.0 * sigma1 + sigma).cos();
+            let delta_sigma = vincenty_delta_sigma(big_b, sigma.sin(), sigma.cos(), cos_2sigma_m);
+            let previous = sigma;
+            sigma = distance / (b * big_a) + delta_sigma;
+            if (sigma - previous).abs() < 1e-12 {
+                break;
+            }
+        }
+        let (sin_sigma, cos_sigma) = sigma.sin_cos();
+        let cos_2sigma_m = (2.0 * sigma1 + sigma).cos();
+
+        let tmp = sin_u1 * sin_sigma - cos_u1 * cos_sigma * cos_alpha1;
+        let lat = (sin_u1 * cos_sigma + cos_u1 * sin_sigma * cos_alpha1)
+            .atan2((1.0 - f) * (sin_alpha * sin_alpha + tmp * tmp).sqrt());
+        let lambda =
+            (sin_sigma * sin_alpha1).atan2(cos_u1 * cos_sigma - sin_u1 * sin_sigma * cos_alpha1);
+        let c = f / 16.0 * cos2_alpha * (4.0 + f * (4.0 - 3.0 * cos2_alpha));
+        let l = lambda
+            - (1.0 - c)
+                * f
+                * sin_alpha
+                * (sigma
+                    + c * sin_sigma
+                        * (cos_2sigma_m
+                            + c * cos_sigma * (-1.0 + 2.0 * cos_2sigma_m * cos_2sigma_m)));
+        let lon = (self.longitude() + l + std::f64::consts::PI)
+            .rem_euclid(2.0 * std::f64::consts::PI)
+            - std::f64::consts::PI;
+
+        (
+            LLHRadians::new(lat, lon, self.height()),
+            normalize_azimuth(sin_alpha.atan2(-tmp)),
+        )
+    }
+}
+
+/// Gets Vincenty's A and B series coefficients
+fn vincenty_coefficients(u_sq: f64) -> (f64, f64) {
+    let a = 1.0 + u_sq / 16384.0 * (4096.0 + u_sq * (-768.0 + u_sq * (320.0 - 175.0 * u_sq)));
+    let b = u_sq / 1024.0 * (256.0 + u_sq * (-128.0 + u_sq * (74.0 - 47.0 * u_sq)));
+    (a, b)
+}
+
+fn vincenty_delta_sigma(b: f64, sin_sigma: f64, cos_sigma: f64, cos_2sigma_m: f64) -> f64 {
+    let cos_2sigma_m_sq = cos_2sigma_m * cos_2sigma_m;
+    b * sin_sigma
+        * (cos_2sigma_m
+            + b / 4.0
+                * (cos_sigma * (-1.0 + 2.0 * cos_2sigma_m_sq)
+                    - b / 6.0
+                        * cos_2sigma_m
+                        * (-3.0 + 4.0 * sin_sigma * sin_sigma)
+                        * (-3.0 + 4.0 * cos_2sigma_m_sq)))
+}
+
+/// Wraps an azimuth into [0, 2π)
+fn normalize_azimuth(azimuth: f64) -> f64 {
+    azimuth.rem_euclid(2.0 * std::f64::consts::PI)
+}
+
+/// Solution of the inverse geodesic problem between two points
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct Geodesic {
+    distance: f64,
+    initial_azimuth: f64,
+    final_azimuth: f64,
+}
+
+impl Geodesic {
+    /// Gets the length of the path along the ellipsoid, in meters
+    pub fn distance(&self) -> f64 {
+        self.distance
+    }
+
+    /// Gets the azimuth of the path at the starting point, in radians
+    pub fn initial_azimuth(&self) -> f64 {
+        self.initial_azimuth
+    }
+
+    /// Gets the azimuth of the path at the end point, in radians
+    ///
+    /// The azimuth from the end point back to the start is this plus π.
+    pub fn final_azimuth(&self) -> f64 {
+        self.final_azimuth
+    }
+}
+
+/// Errors which can occur when computing geodesics
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum GeodesicError {
+    /// The iterative solution did not converge, which happens for some
+    /// nearly antipodal points
+    NotConverged,
+}
+
+impl fmt::Display for GeodesicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeodesicError::NotConverged => write!(f, "Geodesic solution did not converge"),
+        }
+    }
+}
+
+impl Error for GeodesicError {}
+
+impl Default for LLHRadians {
+    fn default() -> LLHRadians {
+        LLHRadians::new(0., 0., 0.)
+    }
+}
+
+impl AsRef<[f64; 3]> for LLHRadians {
+    fn as_ref(&self) -> &[f64; 3] {
+        &self.0
+    }
+}
+
+impl AsMut<[f64; 3]> for LLHRadians {
+    fn as_mut(&mut self) -> &mut [f64; 3] {
+        &mut self.0
+    }
+}
+
+impl From<LLHRadians> for LLHDegrees {
+    fn from(rad: LLHRadians) -> LLHDegrees {
+        rad.to_degrees()
+    }
+}
+
+impl From<ECEF> for LLHDegrees {
+    fn from(ecef: ECEF) -> LLHDegrees {
+        ecef.to_llh().to_degrees()
+    }
+}
+
+/// WGS84 Earth Centered, Earth Fixed (ECEF) Cartesian coordinates (X, Y, Z).
+///
+/// Internally stored as an array of 3 [f64](std::f64) values: x, y, z all in meters
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub struct ECEF([f64; 3]);
+
+impl ECEF {
+    pub fn new(x: f64, y: f64, z: f64) -> ECEF {
+        ECEF([x, y, z])
+    }
+
+    pub fn from_array(array: &[f64; 3]) -> ECEF {
+        ECEF(*array)
+    }
+
+    pub fn as_ptr(&self) -> *const [f64; 3] {
+        &self.0
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut [f64; 3] {
+        &mut self.0
+    }
+
+    pub fn as_single_ptr(&self) -> *const f64 {
+        &self.0[0]
+    }
+
+    pub fn as_array_ref(&self) -> &[f64; 3] {
+        &self.0
+    }
+
+    pub fn as_mut_array_ref(&mut self) -> &mut [f64; 3] {
+        &mut self.0
+    }
+
+    pub fn x(&self) -> f64 {
+        self.0[0]
+    }
+
+    pub fn y(&self) -> f64 {
+        self.0[1]
+    }
+
+    pub fn z(&self) -> f64 {
+        self.0[2]
+    }
+
+    /// Gets the dot product of this vector and another
+    pub fn dot(&self, other: &ECEF) -> f64 {
+        self.x() * other.x() + self.y() * other.y() + self.z() * other.z()
+    }
+
+    /// Gets the cross product of this vector and another
+    pub fn cross(&self, other: &ECEF) -> ECEF {
+        ECEF::new(
+            self.y() * other.z() - self.z() * other.y(),
+            self.z() * other.x() - self.x() * other.z(),
+            self.x() * other.y() - self.y() * other.x(),
+        )
+    }
+
+    /// Gets the length of the vector
+    pub fn norm(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    /// Gets the vector scaled to a length of one
+    ///
+    /// The zero vector has no direction, its components come out as NaN.
+    pub fn unit_vector(&self) -> ECEF {
+        (1.0 / self.norm()) * *self
+    }
+
+    /// Converts from WGS84 Earth Centered, Earth Fixed (ECEF) Cartesian
+    /// coordinates (X, Y and Z) into WGS84 geodetic coordinates (latitude,
+    /// longitude and height).
+    pub fn to_llh(&self) -> LLHRadians {
+        let mut llh = LLHRadians::from_array(&[0.0; 3]);
+        unsafe { swiftnav_sys::wgsecef2llh(self.as_ptr(), llh.as_mut_ptr()) };
+        llh
+    }
+
+    /// Converts from Earth Centered, Earth Fixed (ECEF) Cartesian coordinates
+    /// into geodetic coordinates on the given ellipsoid.
+    pub fn to_llh_on<E: Ellipsoid + ?Sized>(&self, ellipsoid: &E) -> LLHRadians {
+        ellipsoid.ecef_to_llh(self)
+    }
+
+    /// Determine the azimuth and elevation of a point in WGS84 Earth Centered,
+    /// Earth Fixed (ECEF) Cartesian coordinates from a reference point given in
+    /// WGS84 ECEF coordinates.
+    ///
+    /// First the vector between the points is converted into the local North, East,
+    /// Down frame of the reference point. Then we can directly calculate the
+    /// azimuth and elevation.
+    pub fn azel_of(&self, point: &ECEF) -> AzimuthElevation {
+        let mut azel = AzimuthElevation::new(0.0, 0.0);
+        unsafe {
+            swiftnav_sys::wgsecef2azel(point.as_ptr(), self.as_ptr(), &mut azel.az, &mut azel.el)
+        };
+        azel
+    }
+
+    /// Rotate a vector from ECEF coordinates into NED coordinates, at a given
+    /// reference point. This is approporiate for converting velocity vectors.
+    ///
+    /// This is the inverse of [NED::ecef_vector_at].
+    pub fn ned_vector_at(&self, point: &ECEF) -> NED {
+        let mut ned = NED::default();
+        unsafe { swiftnav_sys::wgsecef2ned(self.as_ptr(), point.as_ptr(), ned.as_mut_ptr()) };
+        ned
+    }
+
+    /// Finds where a ray, starting at this point in the given direction,
+    /// first crosses the surface at `height` meters above the WGS84 ellipsoid.
+    ///
+    /// This gives the ionospheric pierce point of a signal when `height` is the
+    /// height of the ionospheric shell, or the point on the ground seen by a
+    /// camera looking down when `height` is zero. The azimuth and elevation
+    /// are in radians, relative to the local horizon of this point.
+    ///
+    /// Returns `None` if the ray never reaches the surface, e.g. when looking
+    /// up from above it.
+    ///
+    /// The ray is first intersected with an ellipsoid whose axes are extended
+    /// by `height`, which is then refined to the exact height with a few
+    /// Newton iterations along the ray.
+    pub fn ray_intersection(
+        &self,
+        direction: &AzimuthElevation,
+        height: f64,
+    ) -> Option<LLHRadians> {
+        let dir = direction.line_of_sight_at(self);
+
+        let a = WGS84::SEMI_MAJOR_AXIS + height;
+        let b = WGS84.semi_minor_axis() + height;
+        let p = [self.x() / a, self.y() / a, self.z() / b];
+        let d = [dir.x() / a, dir.y() / a, dir.z() / b];
+        let qa = d[0] * d[0] + d[1] * d[1] + d[2] * d[2];
+        let qb = 2.0 * (p[0] * d[0] + p[1] * d[1] + p[2] * d[2]);
+        let qc = p[0] * p[0] + p[1] * p[1] + p[2] * p[2] - 1.0;
+        let discriminant = qb * qb - 4.0 * qa * qc;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let roots = [
+            (-qb - discriminant.sqrt()) / (2.0 * qa),
+            (-qb + discriminant.sqrt()) / (2.0 * qa),
+        ];
+        let mut range = *roots.iter().find(|s| **s >= 0.0)?;
+
+        let mut llh = (*self + range * dir).to_llh();
+        for _ in 0..10 {
+            let (lat, lon) = (llh.latitude(), llh.longitude());
+            let climb_rate = lat.cos() * lon.cos() * dir.x()
+                + lat.cos() * lon.sin() * dir.y()
+                + lat.sin() * dir.z();
+            if climb_rate.abs() < 1e-9 {
+                break;
+            }
+            let step = (height - llh.height()) / climb_rate;
+            range += step;
+            llh = (*self + range * dir).to_llh();
+            if step.abs() < 1e-6 {
+                break;
+            }
+        }
+        Some(llh)
+    }
+}
+
+impl Default for ECEF {
+    fn default() -> Self {
+        Self::new(0., 0., 0.)
+    }
+}
+
+impl AsRef<[f64; 3]> for ECEF {
+    fn as_ref(&self) -> &[f64; 3] {
+        &self.0
+    }
+}
+
+impl AsMut<[f64; 3]> for ECEF {
+    fn as_mut(&mut self) -> &mut [f64; 3] {
+        &mut self.0
+    }
+}
+
+impl Add for ECEF {
+    type Output = ECEF;
+    fn add(self, rhs: ECEF) -> ECEF {
+        ECEF([self.x() + rhs.x(), self.y() + rhs.y(), self.z() + rhs.z()])
+    }
+}
+
+impl Add<&ECEF> for ECEF {
+    type Output = ECEF;
+    fn add(self, rhs: &ECEF) -> ECEF {
+        self + *rhs
+    }
+}
+
+impl Add<&ECEF> for &ECEF {
+    type Output = ECEF;
+    fn add(self, rhs: &ECEF) -> ECEF {
+        *self + *rhs
+    }
+}
+
+impl AddAssign for ECEF {
+    fn add_assign(&mut self, rhs: ECEF) {
+        *self += &rhs;
+    }
+}
+
+impl AddAssign<&ECEF> for ECEF {
+    fn add_assign(&mut self, rhs: &ECEF) {
+        self.0[0] += rhs.x();
+        self.0[1] += rhs.y();
+        self.0[2] += rhs.z();
+    }
+}
+
+impl Sub for ECEF {
+    type Output = ECEF;
+    fn sub(self, rhs: ECEF) -> ECEF {
+        ECEF([self.x() - rhs.x(), self.y() - rhs.y(), self.z() - rhs.z()])
+    }
+}
+
+impl Sub<&ECEF> for ECEF {
+    type Output = ECEF;
+    fn sub(self, rhs: &ECEF) -> ECEF {
+        self - *rhs
+    }
+}
+
+impl Sub<&ECEF> for &ECEF {
+    type Output = ECEF;
+    fn sub(self, rhs: &ECEF) -> ECEF {
+        *self - *rhs
+    }
+}
+
+impl SubAssign for ECEF {
+    fn sub_assign(&mut self, rhs: ECEF) {
+        *self -= &rhs;
+    }
+}
+
+impl SubAssign<&ECEF> for ECEF {
+    fn sub_assign(&mut self, rhs: &ECEF) {
+        self.0[0] -= rhs.x();
+        self.0[1] -= rhs.y();
+        self.0[2] -= rhs.z();
+    }
+}
+
+impl Mul<ECEF> for f64 {
+    type Output = ECEF;
+    fn mul(self, rhs: ECEF) -> ECEF {
+        ECEF([self * rhs.x(), self * rhs.y(), self * rhs.z()])
+    }
+}
+
+impl Mul<&ECEF> for f64 {
+    type Output = ECEF;
+    fn mul(self, rhs: &ECEF) -> ECEF {
+        self * *rhs
+    }
+}
+
+impl MulAssign<f64> for ECEF {
+    fn mul_assign(&mut self, rhs: f64) {
+        *self *= &rhs;
+    }
+}
+
+impl MulAssign<&f64> for ECEF {
+    fn mul_assign(&mut self, rhs: &f64) {
+        self.0[0] *= *rhs;
+        self.0[1] *= *rhs;
+        self.0[2] *= *rhs;
+    }
+}
+
+/// Local North East Down reference frame coordinates
+///
+/// Internally stored as an array of 3 [f64](std::f64) values: N, E, D all in meters
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub struct NED([f64; 3]);
+
+impl NED {
+    pub fn new(n: f64, e: f64, d: f64) -> NED {
+        NED([n, e, d])
+    }
+
+    pub fn from_array(array: &[f64; 3]) -> NED {
+        NED(*array)
+    }
+
+    pub fn as_ptr(&self) -> *const [f64; 3] {
+        &self.0
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut [f64; 3] {
+        &mut self.0
+    }
+
+    pub fn as_array_ref(&self) -> &[f64; 3] {
+        &self.0
+    }
+
+    pub fn as_mut_array_ref(&mut self) -> &mut [f64; 3] {
+        &mut self.0
+    }
+
+    pub fn n(&self) -> f64 {
+        self.0[0]
+    }
+
+    pub fn e(&self) -> f64 {
+        self.0[1]
+    }
+
+    pub fn d(&self) -> f64 {
+        self.0[2]
+    }
+
+    /// Rotate a vector from NED coordinates into ECEF coordinates, at a given
+    /// reference point. This is approporiate for converting velocity vectors.
+    ///
+    /// This is the inverse of [ECEF::ned_vector_at].
+    pub fn ecef_vector_at(&self, ref_ecef: &ECEF) -> ECEF {
+        let mut ecef = ECEF::default();
+        unsafe { swiftnav_sys::wgsned2ecef(self.as_ptr(), ref_ecef.as_ptr(), ecef.as_mut_ptr()) };
+        ecef
+    }
+}
+
+/// Gets the unit vector pointing in a direction, see
+/// [AzimuthElevation::to_ned]
+impl From<AzimuthElevation> for NED {
+    fn from(direction: AzimuthElevation) -> NED {
+        direction.to_ned()
+    }
+}
+
+impl Default for NED {
+    fn default() -> Self {
+        Self::new(0., 0., 0.)
+    }
+}
+
+impl AsRef<[f64; 3]> for NED {
+    fn as_ref(&self) -> &[f64; 3] {
+        &self.0
+    }
+}
+
+impl AsMut<[f64; 3]> for NED {
+    fn as_mut(&mut self) -> &mut [f64; 3] {
+        &mut self.0
+    }
+}
+
+/// Horizontal speed, course and vertical speed derived from a NED velocity,
+/// along with their standard deviations
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub struct SpeedAndCourse {
+    speed_over_ground: f64,
+    speed_over_ground_sigma: f64,
+    course_over_ground: Option<f64>,
+    course_over_ground_sigma: f64,
+    vertical_speed: f64,
+    vertical_speed_sigma: f64,
+}
+
+impl SpeedAndCourse {
+    /// Computes the speed and course from a NED velocity, in m/s, and its
+    /// covariance, in (m/s)²
+    ///
+    /// The uncertainties are propagated to first order, which breaks down
+    /// when the speed is similar to its uncertainty. The course uncertainty
+    /// is capped at 180 degrees.
+    pub fn from_ned(velocity: &NED, covariance: &[[f64; 3]; 3]) -> SpeedAndCourse {
+        let (n, e) = (velocity.n(), velocity.e());
+        let (cnn, cne, cee) = (covariance[0][0], covariance[0][1], covariance[1][1]);
+        let speed_squared = n * n + e * e;
+        let speed = speed_squared.sqrt();
+
+        let (speed_sigma, course, course_sigma) = if speed > 0.0 {
+            let speed_var = (n * n * cnn + 2.0 * n * e * cne + e * e * cee) / speed_squared;
+            let course_var =
+                (e * e * cnn - 2.0 * n * e * cne + n * n * cee) / (speed_squared * speed_squared);
+            (
+                speed_var.max(0.0).sqrt(),
+                Some(e.atan2(n).to_degrees().rem_euclid(360.0)),
+                course_var.max(0.0).sqrt().to_degrees().min(180.0),
+            )
+        } else {
+            // With no motion the speed error is the length of the horizontal
+            // error, whose variance is the trace of the covariance
+            ((cnn + cee).max(0.0).sqrt(), None, 180.0)
+        };
+
+        SpeedAndCourse {
+            speed_over_ground: speed,
+            speed_over_ground_sigma: speed_sigma,
+            course_over_ground: course,
+            course_over_ground_sigma: course_sigma,
+            vertical_speed: -velocity.d(),
+            vertical_speed_sigma: covariance[2][2].max(0.0).sqrt(),
+        }
+    }
+
+    /// Gets the horizontal speed, in m/s
+    pub fn speed_over_ground(&self) -> f64 {
+        self.speed_over_ground
+    }
+
+    /// Gets the standard deviation of the horizontal speed, in m/s
+    pub fn speed_over_ground_sigma(&self) -> f64 {
+        self.speed_over_ground_sigma
+    }
+
+    /// Gets the direction of travel, in degrees clockwise from north in the
+    /// range [0, 360)
+    ///
+    /// Returns `None` if there is no horizontal motion
+    pub fn course_over_ground(&self) -> Option<f64> {
+        self.course_over_ground
+    }
+
+    /// Gets the standard deviation of the direction of travel, in degrees
+    pub fn course_over_ground_sigma(&self) -> f64 {
+        self.course_over_ground_sigma
+    }
+
+    /// Gets the vertical speed, in m/s, positive upwards
+    pub fn vertical_speed(&self) -> f64 {
+        self.vertical_speed
+    }
+
+    /// Gets the standard deviation of the vertical speed, in m/s
+    pub fn vertical_speed_sigma(&self) -> f64 {
+        self.vertical_speed_sigma
+    }
+}
+
+/// Rotation from ECEF into the local north, east, down frame at a location
+fn ned_rotation(location: &LLHRadians) -> [[f64; 3]; 3] {
+    let (sin_lat, cos_lat) = location.latitude().sin_cos();
+    let (sin_lon, cos_lon) = location.longitude().sin_cos();
+    [
+        [-sin_lat * cos_lon, -sin_lat * sin_lon, cos_lat],
+        [-sin_lon, cos_lon, 0.0],
+        [-cos_lat * cos_lon, -cos_lat * sin_lon, -sin_lat],
+    ]
+}
+
+/// Rotation from ECEF into the local east, north, up frame at a location
+fn enu_rotation(location: &LLHRadians) -> [[f64; 3]; 3] {
+    let [north, east, down] = ned_rotation(location);
+    [east, north, down.map(|v| -v)]
+}
+
+/// Computes `R C Rᵀ`, or `Rᵀ C R` if `inverse` is set
+fn rotate_covariance(
+    rotation: &[[f64; 3]; 3],
+    covariance: &[[f64; 3]; 3],
+    inverse: bool,
+) -> [[f64; 3]; 3] {
+    let r = |i: usize, j: usize| {
+        if inverse {
+            rotation[j][i]
+        } else {
+            rotation[i][j]
+        }
+    };
+    let mut rotated = [[0.0; 3]; 3];
+    for (i, row) in rotated.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            for (k, covariance_row) in covariance.iter().enumerate() {
+                for (l, c) in covariance_row.iter().enumerate() {
+                    *value += r(i, k) * c * r(j, l);
+                }
+            }
+        }
+    }
+    rotated
+}
+
+/// Rotates an ECEF position covariance into the local north, east, down
+/// frame at a location
+///
+/// Only the latitude and longitude of the location are used.
+pub fn ecef_to_ned_covariance(covariance: &[[f64; 3]; 3], location: &LLHRadians) -> [[f64; 3]; 3] {
+    rotate_covariance(&ned_rotation(location), covariance, false)
+}
+
+/// Rotates a north, east, down position covariance at a location into ECEF
+///
+/// This is the inverse of [ecef_to_ned_covariance].
+pub fn ned_to_ecef_covariance(covariance: &[[f64; 3]; 3], location: &LLHRadians) -> [[f64; 3]; 3] {
+    rotate_covariance(&ned_rotation(location), covariance, true)
+}
+
+/// Rotates an ECEF position covariance into the local east, north, up frame
+/// at a location
+///
+/// Only the latitude and longitude of the location are used.
+pub fn ecef_to_enu_covariance(covariance: &[[f64; 3]; 3], location: &LLHRadians) -> [[f64; 3]; 3] {
+    rotate_covariance(&enu_rotation(location), covariance, false)
+}
+
+/// Rotates an east, north, up position covariance at a location into ECEF
+///
+/// This is the inverse of [ecef_to_enu_covariance].
+pub fn enu_to_ecef_covariance(covariance: &[[f64; 3]; 3], location: &LLHRadians) -> [[f64; 3]; 3] {
+    rotate_covariance(&enu_rotation(location), covariance, true)
+}
+
+/// Error ellipse of a horizontal position
+///
+/// The axes are one standard deviation long. They can be scaled for other
+/// confidence levels, e.g. by 2.4477 for 95% with a normal distribution.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub struct ErrorEllipse {
+    semi_major: f64,
+    semi_minor: f64,
+    orientation: f64,
+}
+
+impl ErrorEllipse {
+    /// Computes the error ellipse from a north, east, down covariance, in
+    /// meters squared
+    ///
+    /// Only the horizontal terms of the covariance are used.
+    pub fn from_ned_covariance(covariance: &[[f64; 3]; 3]) -> ErrorEllipse {
+        let (north, east, north_east) = (covariance[0][0], covariance[1][1], covariance[0][1]);
+        let mean = 0.5 * (north + east);
+        let radius = (0.25 * (north - east).powi(2) + north_east.powi(2)).sqrt();
+        ErrorEllipse {
+            semi_major: (mean + radius).max(0.0).sqrt(),
+            semi_minor: (mean - radius).max(0.0).sqrt(),
+            orientation: (0.5 * (2.0 * north_east).atan2(north - east))
+                .to_degrees()
+                .rem_euclid(180.0),
+        }
+    }
+
+    /// Gets the ellipse with both axes multiplied by a factor
+    pub fn scaled(&self, factor: f64) -> ErrorEllipse {
+        ErrorEllipse {
+            semi_major: self.semi_major * factor,
+            semi_minor: self.semi_minor * factor,
+            ..*self
+        }
+    }
+
+    /// Gets the length of the semi-major axis, in meters
+    pub fn semi_major(&self) -> f64 {
+        self.semi_major
+    }
+
+    /// Gets the length of the semi-minor axis, in meters
+    pub fn semi_minor(&self) -> f64 {
+        self.semi_minor
+    }
+
+    /// Gets the direction of the semi-major axis, in degrees clockwise from
+    /// north in the range [0, 180)
+    pub fn orientation(&self) -> f64 {
+        self.orientation
+    }
+}
+
+/// Standard deviations of a position, as reported by receivers
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub struct PositionAccuracy {
+    north_sigma: f64,
+    east_sigma: f64,
+    vertical_sigma: f64,
+    ellipse: ErrorEllipse,
+}
+
+impl PositionAccuracy {
+    /// Computes the accuracy from a north, east, down covariance, in meters
+    /// squared
+    pub fn from_ned_covariance(covariance: &[[f64; 3]; 3]) -> PositionAccuracy {
+        PositionAccuracy {
+            north_sigma: covariance[0][0].max(0.0).sqrt(),
+            east_sigma: covariance[1][1].max(0.0).sqrt(),
+            vertical_sigma: covariance[2][2].max(0.0).sqrt(),
+            ellipse: ErrorEllipse::from_ned_covariance(covariance),
+        }
+    }
+
+    /// Computes the accuracy from an ECEF covariance, in meters squared, of a
+    /// position at a location
+    pub fn from_ecef_covariance(
+        covariance: &[[f64; 3]; 3],
+        location: &LLHRadians,
+    ) -> PositionAccuracy {
+        PositionAccuracy::from_ned_covariance(&ecef_to_ned_covariance(covariance, location))
+    }
+
+    /// Gets the standard deviation of the north error, in meters
+    pub fn north_sigma(&self) -> f64 {
+        self.north_sigma
+    }
+
+    /// Gets the standard deviation of the east error, in meters
+    pub fn east_sigma(&self) -> f64 {
+        self.east_sigma
+    }
+
+    /// Gets the horizontal standard deviation, in meters
+    ///
+    /// This is the root mean square length of the horizontal error, also
+    /// known as DRMS.
+    pub fn horizontal_sigma(&self) -> f64 {
+        self.north_sigma.hypot(self.east_sigma)
+    }
+
+    /// Gets the standard deviation of the vertical error, in meters
+    pub fn vertical_sigma(&self) -> f64 {
+        self.vertical_sigma
+    }
+
+    /// Gets the horizontal error ellipse
+    pub fn ellipse(&self) -> ErrorEllipse {
+        self.ellipse
+    }
+}
+
+/// Direction relative to the local horizon of a point
+///
+/// The azimuth is clockwise from north and the elevation is up from the
+/// horizon, both in radians.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub struct AzimuthElevation {
+    pub az: f64,
+    pub el: f64,
+}
+
+impl AzimuthElevation {
+    pub fn new(az: f64, el: f64) -> AzimuthElevation {
+        AzimuthElevation { az, el }
+    }
+
+    /// Makes a direction from angles in either unit, checking the elevation
+    /// is within [-π/2, π/2]
+    ///
+    /// The azimuth is wrapped into [0, 2π).
+    pub fn from_angles(
+        az: impl Into<Radians>,
+        el: impl Into<Radians>,
+    ) -> Result<AzimuthElevation, InvalidAngle> {
+        let az = angle::check_azimuth(az.into())?;
+        let el = angle::check_elevation(el.into())?;
+        Ok(AzimuthElevation {
+            az: normalize_azimuth(az.value()),
+            el: el.value(),
+        })
+    }
+
+    pub fn azimuth(&self) -> Radians {
+        Radians::new(self.az)
+    }
+
+    pub fn elevation(&self) -> Radians {
+        Radians::new(self.el)
+    }
+
+    /// Gets the direction of a vector in the local north, east, down frame
+    ///
+    /// The vector doesn't need to be a unit vector. The azimuth is in the
+    /// range [0, 2π), and is zero for vertical vectors.
+    pub fn from_ned(ned: &NED) -> AzimuthElevation {
+        let horizontal = ned.n().hypot(ned.e());
+        AzimuthElevation {
+            az: normalize_azimuth(ned.e().atan2(ned.n())),
+            el: (-ned.d()).atan2(horizontal),
+        }
+    }
+
+    /// Gets the unit vector in the local north, east, down frame pointing in
+    /// this direction
+    pub fn to_ned(&self) -> NED {
+        let (sin_az, cos_az) = self.az.sin_cos();
+        let (sin_el, cos_el) = self.el.sin_cos();
+        NED::new(cos_el * cos_az, cos_el * sin_az, -sin_el)
+    }
+
+    /// Gets the ECEF unit vector pointing in this direction from the local
+    /// horizon of a site
+    pub fn line_of_sight_at(&self, site: &ECEF) -> ECEF {
+        self.to_ned().ecef_vector_at(site)
+    }
+
+    /// Gets the same direction with the elevation in [-π/2, π/2] and the
+    /// azimuth in [0, 2π)
+    ///
+    /// Elevations past the zenith or the nadir are folded back, looking in
+    /// the opposite azimuth.
+    pub fn normalized(&self) -> AzimuthElevation {
+        use std::f64::consts::{FRAC_PI_2, PI};
+        let mut az = self.az;
+        // Wrap into (-π, π]
+        let mut el = PI - (PI - self.el).rem_euclid(2.0 * PI);
+        if el > FRAC_PI_2 {
+            el = PI - el;
+            az += PI;
+        } else if el < -FRAC_PI_2 {
+            el = -PI - el;
+            az += PI;
+        }
+        AzimuthElevation {
+            az: normalize_azimuth(az),
+            el,
+        }
+    }
+
+    /// Gets the same direction as [AzimuthElevation::normalized], but with
+    /// the azimuth in (-π, π]
+    pub fn normalized_signed(&self) -> AzimuthElevation {
+        use std::f64::consts::PI;
+        let normalized = self.normalized();
+        AzimuthElevation {
+            az: PI - (PI - normalized.az).rem_euclid(2.0 * PI),
+            ..normalized
+        }
+    }
+}
+
+impl Default for AzimuthElevation {
+    fn default() -> Self {
+        Self::new(0., 0.)
+    }
+}
+
+/// Formats the azimuth and elevation in degrees, by default with one decimal
+impl fmt::Display for AzimuthElevation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precision = f.precision().unwrap_or(1);
+        write!(
+            f,
+            "az {:.*}°, el {:.*}°",
+            precision,
+            self.az.to_degrees(),
+            precision,
+            self.el.to_degrees()
+        )
+    }
+}
+
+/// Complete coordinate used for transforming between reference frames
+///
+/// Velocities are optional, but when present they will be transformed
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub struct Coordinate {
+    reference_frame: ReferenceFrame,
+    position: ECEF,
+    velocity: Option<ECEF>,
+    epoch: GpsTime,
+}
+
+impl Coordinate {
+    pub fn new(
+        reference_frame: ReferenceFrame,
+        position: ECEF,
+        velocity: Option<ECEF>,
+        epoch: GpsTime,
+    ) -> Self {
+        Coordinate {
+            reference_frame,
+            position,
+            velocity,
+            epoch,
+        }
+    }
+
+    pub fn without_velocity(
+        reference_frame: ReferenceFrame,
+        position: ECEF,
+        epoch: GpsTime,
+    ) -> Self {
+        Coordinate {
+            reference_frame,
+            position,
+            velocity: None,
+            epoch,
+        }
+    }
+
+    pub fn with_velocity(
+        reference_frame: ReferenceFrame,
+        position: ECEF,
+        velocity: ECEF,
+        epoch: GpsTime,
+    ) -> Self {
+        Coordinate {
+            reference_frame,
+            position,
+            velocity: Some(velocity),
+            epoch,
+        }
+    }
+
+    pub fn reference_frame(&self) -> ReferenceFrame {
+        self.reference_frame
+    }
+
+    pub fn position(&self) -> ECEF {
+        self.position
+    }
+
+    pub fn velocity(&self) -> Option<ECEF> {
+        self.velocity
+    }
+
+    pub fn epoch(&self) -> GpsTime {
+        self.epoch
+    }
+
+    /// Gets the geodetic coordinates of the position, on the ellipsoid of
+    /// the coordinate's reference frame
+    pub fn llh(&self) -> LLHRadians {
+        self.position.to_llh_on(self.reference_frame.ellipsoid())
+    }
+
+    /// Use the velocity term to adjust the epoch of the coordinate.
+    /// When a coordinate has no velocity the position won't be changed.
+    pub fn adjust_epoch(&self, new_epoch: &GpsTime) -> Self {
+        let dt =
+            new_epoch.to_fractional_year_hardcoded() - self.epoch.to_fractional_year_hardcoded();
+        let v = self.velocity.unwrap_or_default();
+
+        Coordinate {
+            position: self.position + dt * v,
+            velocity: self.velocity,
+            epoch: *new_epoch,
+            reference_frame: self.reference_frame,
+        }
+    }
+
+    /// Transforms the coordinate into a different reference frame
+    ///
+    /// Parameters which are valid at the coordinate's epoch are preferred,
+    /// use [`transform_to_with_warning`](Self::transform_to_with_warning) to
+    /// find out if the parameters had to be used outside of their validity.
+    pub fn transform_to(&self, new_frame: ReferenceFrame) -> Result<Self, TransformationNotFound> {
+        self.transform_to_with_warning(new_frame)
+            .map(|(coord, _)| coord)
+    }
+
+    /// Transforms the coordinate into a different reference frame, along with
+    /// a warning if the transformation isn't valid at the coordinate's epoch
+    pub fn transform_to_with_warning(
+        &self,
+        new_frame: ReferenceFrame,
+    ) -> Result<(Self, Option<ValidityWarning>), TransformationNotFound> {
+        let transformation = get_transformation_at(self.reference_frame, new_frame, &self.epoch)?;
+        Ok(transformation.transform_with_warning(self))
+    }
+
+    /// Transforms the coordinate into a different reference frame, going
+    /// through intermediate frames when there is no direct transformation
+    ///
+    /// The path is taken from `graph`, preferring transformations which are
+    /// valid at the coordinate's epoch.
+    pub fn transform_via(
+        &self,
+        new_frame: ReferenceFrame,
+        graph: &TransformationGraph,
+    ) -> Result<Self, TransformationNotFound> {
+        if self.reference_frame == new_frame {
+            return Ok(*self);
+        }
+        let path = graph
+            .get_shortest_path_at(self.reference_frame, new_frame, &self.epoch)
+            .ok_or(TransformationNotFound(self.reference_frame, new_frame))?;
+        path.iter()
+            .skip(1)
+            .try_fold(*self, |coord, frame| coord.transform_to(*frame))
+    }
+
+    /// Transforms the coordinate into a frame and moves it to the epoch
+    /// coordinates of that frame are conventionally given at
+    ///
+    /// The epoch is chosen following [`ReferenceFrame::epoch_convention()`],
+    /// e.g. NAD83(2011) coordinates are given at epoch 2010.0 whatever the
+    /// epoch of the observation. The coordinate is moved with its velocity
+    /// once transformed, coordinates without a velocity keep their position,
+    /// see [`TransformationRepository::to_regional_standard_with_plate()`]
+    /// for using a plate motion model instead.
+    ///
+    /// [`TransformationRepository::to_regional_standard_with_plate()`]: crate::reference_frame::TransformationRepository::to_regional_standard_with_plate
+    pub fn to_regional_standard(
+        &self,
+        frame: ReferenceFrame,
+    ) -> Result<Self, TransformationNotFound> {
+        let epoch = frame.epoch_convention().epoch_for(&self.epoch);
+        Ok(self
+            .transform_via(frame, &TransformationGraph::new())?
+            .adjust_epoch(&epoch))
+    }
+
+    /// Transforms the coordinate and its covariance into a different
+    /// reference frame, going through intermediate frames when there is no
+    /// direct transformation
+    ///
+    /// The covariance is propagated through each transformation of the path,
+    /// which is found as in [`Coordinate::transform_via()`].
+    pub fn transform_via_with_covariance(
+        &self,
+        new_frame: ReferenceFrame,
+        graph: &TransformationGraph,
+        covariance: &CoordinateCovariance,
+    ) -> Result<(Self, CoordinateCovariance), TransformationNotFound> {
+        if self.reference_frame == new_frame {
+            return Ok((*self, *covariance));
+        }
+        let path = graph
+            .get_shortest_path_at(self.reference_frame, new_frame, &self.epoch)
+            .ok_or(TransformationNotFound(self.reference_frame, new_frame))?;
+        path.iter()
+            .skip(1)
+            .try_fold((*self, *covariance), |(coord, covariance), frame| {
+                let transformation =
+                    get_transformation_at(coord.reference_frame, *frame, &coord.epoch)?;
+                Ok(transformation.transform_with_covariance(&coord, &covariance))
+            })
+    }
+
+    /// Computes the difference from this coordinate to `other`
+    ///
+    /// `other` is first transformed into this coordinate's reference frame,
+    /// using the transformations in `graph`, and moved to this coordinate's
+    /// epoch using its velocity. The difference is then expressed in the local
+    /// level frame at this coordinate's position.
+    pub fn difference(
+        &self,
+        other: &Coordinate,
+        graph: &TransformationGraph,
+    ) -> Result<CoordinateDifference, TransformationNotFound> {
+        let transformed = other
+            .transform_via(self.reference_frame, graph)?
+            .adjust_epoch(&self.epoch);
+        let ned = (transformed.position - self.position).ned_vector_at(&self.position);
+        let epoch_difference =
+            other.epoch.to_fractional_year_hardcoded() - self.epoch.to_fractional_year_hardcoded();
+        Ok(CoordinateDifference {
+            north: ned.n(),
+            east: ned.e(),
+            up: -ned.d(),
+            reference_frame: self.reference_frame,
+            epoch: self.epoch,
+            other_frame: other.reference_frame,
+            epoch_difference,
+            propagated: other.velocity.is_some(),
+        })
+    }
+
+    /// Checks if two coordinates are within `tolerance` meters of each other
+    /// once expressed in the same reference frame and epoch
+    ///
+    /// See [`Coordinate::difference()`] for how the coordinates are brought
+    /// together. Comparing the raw positions of coordinates in different
+    /// frames can be off by several decimeters, or more when the epochs are
+    /// far apart.
+    pub fn approx_eq(
+        &self,
+        other: &Coordinate,
+        tolerance: f64,
+        graph: &TransformationGraph,
+    ) -> Result<bool, TransformationNotFound> {
+        Ok(self.difference(other, graph)?.distance() <= tolerance)
+    }
+
+    /// Computes the baseline from this coordinate to `other`, in the local
+    /// level frame at this coordinate's position
+    ///
+    /// The coordinates must be in the same reference frame. When they are at
+    /// different epochs `policy` decides if `other` is moved to this
+    /// coordinate's epoch using its velocity, or if that is an error. Unlike
+    /// [`Coordinate::difference()`] nothing is done silently, so a baseline
+    /// between incompatible coordinates can't be mistaken for a displacement.
+    ///
+    /// The baseline has no covariance, see [`CoordinateEstimate::baseline_to()`].
+    pub fn baseline_to(
+        &self,
+        other: &Coordinate,
+        policy: EpochPolicy,
+    ) -> Result<Baseline, BaselineError> {
+        if self.reference_frame != other.reference_frame {
+            return Err(BaselineError::FrameMismatch(
+                self.reference_frame,
+                other.reference_frame,
+            ));
+        }
+        let epoch_difference =
+            other.epoch.to_fractional_year_hardcoded() - self.epoch.to_fractional_year_hardcoded();
+        let other_position = if other.epoch == self.epoch {
+            other.position
+        } else {
+            match (policy, other.velocity) {
+                (EpochPolicy::Exact, _) => {
+                    return Err(BaselineError::EpochMismatch(epoch_difference))
+                }
+                (EpochPolicy::Propagate, None) => return Err(BaselineError::MissingVelocity),
+                (EpochPolicy::Propagate, Some(_)) => other.adjust_epoch(&self.epoch).position,
+            }
+        };
+        Ok(Baseline {
+            ned: (other_position - self.position).ned_vector_at(&self.position),
+            covariance: None,
+            reference_frame: self.reference_frame,
+            epoch: self.epoch,
+            epoch_difference,
+        })
+    }
+}
+
+/// How [`Coordinate::baseline_to()`] handles coordinates at different epochs
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EpochPolicy {
+    /// The coordinates must be at the same epoch
+    Exact,
+    /// The second coordinate is moved to the epoch of the first one using
+    /// its velocity, which it must have
+    Propagate,
+}
+
+/// Errors which can occur when computing a baseline between coordinates
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub enum BaselineError {
+    /// The coordinates are in different reference frames, which are returned
+    FrameMismatch(ReferenceFrame, ReferenceFrame),
+    /// The coordinates are at different epochs, with the epoch of the second
+    /// relative to the first returned in years
+    EpochMismatch(f64),
+    /// The second coordinate has no velocity to move it to the epoch of the
+    /// first one
+    MissingVelocity,
+}
+
+impl fmt::Display for BaselineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BaselineError::FrameMismatch(a, b) => {
+                write!(f, "Coordinates are in different frames ({} and {})", a, b)
+            }
+            BaselineError::EpochMismatch(dt) => {
+                write!(f, "Coordinates are {} years apart", dt)
+            }
+            BaselineError::MissingVelocity => {
+                write!(f, "Coordinate has no velocity to change its epoch")
+            }
+        }
+    }
+}
+
+impl Error for BaselineError {}
+
+/// Baseline between two coordinates, as computed by
+/// [`Coordinate::baseline_to()`]
+///
+/// The baseline is in meters, in the local level frame at the first
+/// coordinate.
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub struct Baseline {
+    ned: NED,
+    covariance: Option<[[f64; 3]; 3]>,
+    reference_frame: ReferenceFrame,
+    epoch: GpsTime,
+    epoch_difference: f64,
+}
+
+impl Baseline {
+    pub fn ned(&self) -> NED {
+        self.ned
+    }
+
+    /// Gets the horizontal length, in meters
+    pub fn horizontal(&self) -> f64 {
+        self.ned.n().hypot(self.ned.e())
+    }
+
+    /// Gets the 3D length, in meters
+    pub fn length(&self) -> f64 {
+        self.horizontal().hypot(self.ned.d())
+    }
+
+    /// Gets the covariance of the baseline in the north, east and down
+    /// directions, in meters squared, when the coordinates had one
+    pub fn covariance(&self) -> Option<[[f64; 3]; 3]> {
+        self.covariance
+    }
+
+    /// Gets the standard deviation of the 3D length, in meters, when the
+    /// coordinates had a covariance
+    pub fn length_sigma(&self) -> Option<f64> {
+        let covariance = self.covariance?;
+        let length = self.length();
+        if length == 0.0 {
+            return None;
+        }
+        let unit = [
+            self.ned.n() / length,
+            self.ned.e() / length,
+            self.ned.d() / length,
+        ];
+        let variance: f64 = (0..3)
+            .flat_map(|i| (0..3).map(move |j| (i, j)))
+            .map(|(i, j)| unit[i] * covariance[i][j] * unit[j])
+            .sum();
+        Some(variance.max(0.0).sqrt())
+    }
+
+    pub fn reference_frame(&self) -> ReferenceFrame {
+        self.reference_frame
+    }
+
+    /// Gets the epoch of the baseline, which is the epoch of the first
+    /// coordinate
+    pub fn epoch(&self) -> GpsTime {
+        self.epoch
+    }
+
+    /// Gets the epoch of the second coordinate relative to the first, in
+    /// years, non zero when it was moved with its velocity
+    pub fn epoch_difference(&self) -> f64 {
+        self.epoch_difference
+    }
+}
+
+/// Difference between two coordinates, as computed by
+/// [`Coordinate::difference()`]
+///
+/// The components are in meters, in the local level frame at the first
+/// coordinate. The other fields note what was done to bring the second
+/// coordinate into the first's frame and epoch.
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub struct CoordinateDifference {
+    north: f64,
+    east: f64,
+    up: f64,
+    reference_frame: ReferenceFrame,
+    epoch: GpsTime,
+    other_frame: ReferenceFrame,
+    epoch_difference: f64,
+    propagated: bool,
+}
+
+impl CoordinateDifference {
+    pub fn north(&self) -> f64 {
+        self.north
+    }
+
+    pub fn east(&self) -> f64 {
+        self.east
+    }
+
+    pub fn up(&self) -> f64 {
+        self.up
+    }
+
+    /// Gets the horizontal distance, in meters
+    pub fn horizontal(&self) -> f64 {
+        self.north.hypot(self.east)
+    }
+
+    /// Gets the 3D distance, in meters
+    pub fn distance(&self) -> f64 {
+        self.horizontal().hypot(self.up)
+    }
+
+    /// Gets the reference frame the coordinates were compared in
+    pub fn reference_frame(&self) -> ReferenceFrame {
+        self.reference_frame
+    }
+
+    /// Gets the epoch the coordinates were compared at
+    pub fn epoch(&self) -> GpsTime {
+        self.epoch
+    }
+
+    /// Gets the original reference frame of the second coordinate
+    pub fn other_frame(&self) -> ReferenceFrame {
+        self.other_frame
+    }
+
+    /// Checks if the second coordinate had to be transformed
+    pub fn was_transformed(&self) -> bool {
+        self.other_frame != self.reference_frame
+    }
+
+    /// Gets the epoch of the second coordinate relative to the comparison
+    /// epoch, in years
+    pub fn epoch_difference(&self) -> f64 {
+        self.epoch_difference
+    }
+
+    /// Checks if the comparison is affected by the second coordinate being at
+    /// a different epoch without a velocity to move it with
+    pub fn is_epoch_mismatched(&self) -> bool {
+        self.epoch_difference != 0.0 && !self.propagated
+    }
+}
+
+/// A coordinate along with the covariance of its position
+///
+/// The covariance is of the ECEF position, in meters squared
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub struct CoordinateEstimate {
+    coordinate: Coordinate,
+    covariance: [[f64; 3]; 3],
+}
+
+impl CoordinateEstimate {
+    pub fn new(coordinate: Coordinate, covariance: [[f64; 3]; 3]) -> Self {
+        CoordinateEstimate {
+            coordinate,
+            covariance,
+        }
+    }
+
+    pub fn coordinate(&self) -> Coordinate {
+        self.coordinate
+    }
+
+    pub fn covariance(&self) -> [[f64; 3]; 3] {
+        self.covariance
+    }
+
+    /// Moves the estimate into a different reference frame and epoch
+    ///
+    /// The covariance is propagated through the reference frame
+    /// transformation. The change of epoch uses the velocity of the
+    /// coordinate, which has no covariance in an estimate, so it leaves the
+    /// covariance unchanged.
+    pub fn transform_to(
+        &self,
+        new_frame: ReferenceFrame,
+        new_epoch: &GpsTime,
+    ) -> Result<Self, TransformationNotFound> {
+        let (coordinate, covariance) = if self.coordinate.reference_frame == new_frame {
+            (self.coordinate, self.covariance)
+        } else {
+            let transformation = get_transformation_at(
+                self.coordinate.reference_frame,
+                new_frame,
+                &self.coordinate.epoch,
+            )?;
+            let (coordinate, covariance) = transformation.transform_with_covariance(
+                &self.coordinate,
+                &CoordinateCovariance::Position(self.covariance),
+            );
+            (coordinate, covariance.position())
+        };
+        Ok(CoordinateEstimate {
+            coordinate: coordinate.adjust_epoch(new_epoch),
+            covariance,
+        })
+    }
+
+    /// Computes the baseline from this estimate to `other`, as
+    /// [`Coordinate::baseline_to()`] does, along with its covariance
+    ///
+    /// The estimates are taken to be independent, so the covariance of the
+    /// baseline is the sum of their covariances rotated into the local level
+    /// frame. Moving `other` to this estimate's epoch doesn't change its
+    /// covariance, as in [`CoordinateEstimate::transform_to()`].
+    pub fn baseline_to(
+        &self,
+        other: &CoordinateEstimate,
+        policy: EpochPolicy,
+    ) -> Result<Baseline, BaselineError> {
+        let mut baseline = self.coordinate.baseline_to(&other.coordinate, policy)?;
+        let reference = self.coordinate.position;
+        // The columns of the rotation are the ECEF axes in the local frame
+        let axes = [
+            ECEF::new(1.0, 0.0, 0.0).ned_vector_at(&reference),
+            ECEF::new(0.0, 1.0, 0.0).ned_vector_at(&reference),
+            ECEF::new(0.0, 0.0, 1.0).ned_vector_at(&reference),
+        ];
+        let mut rotation = [[0.0; 3]; 3];
+        let mut sum = [[0.0; 3]; 3];
+        for i in 0..3 {
+            for j in 0..3 {
+                rotation[i][j] = axes[j].as_array_ref()[i];
+                sum[i][j] = self.covariance[i][j] + other.covariance[i][j];
+            }
+        }
+        let mut covariance = [[0.0; 3]; 3];
+        for i in 0..3 {
+            for j in 0..3 {
+                covariance[i][j] = (0..3)
+                    .flat_map(|k| (0..3).map(move |l| (k, l)))
+                    .map(|(k, l)| rotation[i][k] * sum[k][l] * rotation[j][l])
+                    .sum();
+            }
+        }
+        baseline.covariance = Some(covariance);
+        Ok(baseline)
+    }
+}
+
+/// Errors which can occur when merging coordinate estimates
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub enum MergeError {
+    /// No estimates were given to merge
+    NoEstimates,
+    /// An estimate could not be transformed into the common frame
+    TransformationNotFound(TransformationNotFound),
+    /// An estimate's covariance, or the combined covariance, is not invertible
+    SingularCovariance,
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeError::NoEstimates => write!(f, "No coordinate estimates to merge"),
+            MergeError::TransformationNotFound(e) => e.fmt(f),
+            MergeError::SingularCovariance => write!(f, "Singular coordinate covariance"),
+        }
+    }
+}
+
+impl Error for MergeError {}
+
+impl From<TransformationNotFound> for MergeError {
+    fn from(e: TransformationNotFound) -> Self {
+        MergeError::TransformationNotFound(e)
+    }
+}
+
+/// Merges several estimates of the same point into a single estimate
+///
+/// Each estimate is first transformed into `reference_frame` and propagated to
+/// `epoch`, then the positions are combined weighted by their inverse
+/// covariances. The covariance of the merged estimate is the inverse of the
+/// sum of the weights.
+///
+/// The merged coordinate has a velocity only if all of the estimates have
+/// one, in which case the velocities are combined with the same weights as
+/// the positions.
+pub fn merge_coordinates(
+    estimates: &[CoordinateEstimate],
+    reference_frame: ReferenceFrame,
+    epoch: &GpsTime,
+) -> Result<CoordinateEstimate, MergeError> {
+    if estimates.is_empty() {
+        return Err(MergeError::NoEstimates);
+    }
+
+    let mut information = [[0.0; 3]; 3];
+    let mut weighted_position = [0.0; 3];
+    let mut weighted_velocity = Some([0.0; 3]);
+    for estimate in estimates {
+        let estimate = estimate.transform_to(reference_frame, epoch)?;
+        let weight = invert_3x3(&estimate.covariance).ok_or(MergeError::SingularCovariance)?;
+        let position = estimate.coordinate.position();
+        let velocity = estimate.coordinate.velocity();
+        for i in 0..3 {
+            for j in 0..3 {
+                information[i][j] += weight[i][j];
+                weighted_position[i] += weight[i][j] * position.as_array_ref()[j];
+            }
+        }
+        weighted_velocity = match (weighted_velocity, velocity) {
+            (Some(mut sum), Some(velocity)) => {
+                for (i, row) in weight.iter().enumerate() {
+                    for (j, w) in row.iter().enumerate() {
+                        sum[i] += w * velocity.as_array_ref()[j];
+                    }
+                }
+                Some(sum)
+            }
+            _ => None,
+        };
+    }
+
+    let covariance = invert_3x3(&information).ok_or(MergeError::SingularCovariance)?;
+    let apply = |v: &[f64; 3]| {
+        let mut out = [0.0; 3];
+        for (i, row) in covariance.iter().enumerate() {
+            out[i] = row.iter().zip(v.iter()).map(|(c, v)| c * v).sum();
+        }
+        ECEF::from_array(&out)
+    };
+
+    Ok(CoordinateEstimate {
+        coordinate: Coordinate::new(
+            reference_frame,
+            apply(&weighted_position),
+            weighted_velocity.as_ref().map(apply),
+            *epoch,
+        ),
+        covariance,
+    })
+}
+
+fn invert_3x3(m: &[[f64; 3]; 3]) -> Option<[[f64; 3]; 3]> {
+    let cofactor =
+        |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+    let adjugate = [
+        [
+            cofactor(1, 2, 1, 2),
+            -cofactor(0, 2, 1, 2),
+            cofactor(0, 1, 1, 2),
+        ],
+        [
+            -cofactor(1, 2, 0, 2),
+            cofactor(0, 2, 0, 2),
+            -cofactor(0, 1, 0, 2),
+        ],
+        [
+            cofactor(1, 2, 0, 1),
+            -cofactor(0, 2, 0, 1),
+            cofactor(0, 1, 0, 1),
+        ],
+    ];
+    let determinant =
+        m[0][0] * adjugate[0][0] + m[0][1] * adjugate[1][0] + m[0][2] * adjugate[2][0];
+    let scale = m
+        .iter()
+        .flat_map(|row| row.iter())
+        .fold(0.0_f64, |acc, v| acc.max(v.abs()));
+    if determinant.abs() <= 1e-12 * scale.powi(3) || scale == 0.0 {
+        return None;
+    }
+    let mut inverse = adjugate;
+    for row in inverse.iter_mut() {
+        for value in row.iter_mut() {
+            *value /= determinant;
+        }
+    }
+    Some(inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+
+    use crate::time::UtcTime;
+
+    use super::*;
+
+    const D2R: f64 = std::f64::consts::PI / 180.0;
+    /* Maximum allowable error in quantities with units of length (in meters). */
+    const MAX_DIST_ERROR_M: f64 = 1e-6;
+    /* Maximum allowable error in quantities with units of angle (in sec of arc).
+     * 1 second of arc on the equator is ~31 meters. */
+    const MAX_ANGLE_ERROR_DEF: f64 = 1e-7;
+    const MAX_ANGLE_ERROR_RAD: f64 = MAX_ANGLE_ERROR_DEF * D2R;
+
+    #[test]
+    fn llhrad2deg() {
+        let zeros = LLHRadians::from_array(&[0.0; 3]);
+
+        let deg = zeros.to_degrees();
+        assert_eq!(0.0, deg.latitude());
+        assert_eq!(0.0, deg.longitude());
+        assert_eq!(0.0, deg.height());
+
+        let swift_home = LLHDegrees::from_array(&[37.779804, -122.391751, 60.0]);
+        let rads = swift_home.to_radians();
+
+        assert!((rads.latitude() - 0.659381970558).abs() < MAX_ANGLE_ERROR_RAD);
+        assert!((rads.longitude() + 2.136139032231).abs() < MAX_ANGLE_ERROR_RAD);
+        assert!(rads.height() == swift_home.height());
+    }
+
+    const LLH_VALUES: [LLHRadians; 10] = [
+        LLHRadians([0.0, 0.0, 0.0]), /* On the Equator and Prime Meridian. */
+        LLHRadians([0.0, 180.0 * D2R, 0.0]), /* On the Equator. */
+        LLHRadians([0.0, 90.0 * D2R, 0.0]), /* On the Equator. */
+        LLHRadians([0.0, -90.0 * D2R, 0.0]), /* On the Equator. */
+        LLHRadians([90.0 * D2R, 0.0, 0.0]), /* North pole. */
+        LLHRadians([-90.0 * D2R, 0.0, 0.0]), /* South pole. */
+        LLHRadians([90.0 * D2R, 0.0, 22.0]), /* 22m above the north pole. */
+        LLHRadians([-90.0 * D2R, 0.0, 22.0]), /* 22m above the south pole. */
+        LLHRadians([0.0, 0.0, 22.0]), /* 22m above the Equator and Prime Meridian. */
+        LLHRadians([0.0, 180.0 * D2R, 22.0]), /* 22m above the Equator. */
+    ];
+
+    /* Semi-major axis. */
+    const EARTH_A: f64 = 6378137.0;
+    /* Semi-minor axis. */
+    const EARTH_B: f64 = 6356752.31424517929553985595703125;
+
+    const ECEF_VALUES: [ECEF; 10] = [
+        ECEF([EARTH_A, 0.0, 0.0]),
+        ECEF([-EARTH_A, 0.0, 0.0]),
+        ECEF([0.0, EARTH_A, 0.0]),
+        ECEF([0.0, -EARTH_A, 0.0]),
+        ECEF([0.0, 0.0, EARTH_B]),
+        ECEF([0.0, 0.0, -EARTH_B]),
+        ECEF([0.0, 0.0, (EARTH_B + 22.0)]),
+        ECEF([0.0, 0.0, -(EARTH_B + 22.0)]),
+        ECEF([(22.0 + EARTH_A), 0.0, 0.0]),
+        ECEF([-(22.0 + EARTH_A), 0.0, 0.0]),
+    ];
+
+    #[test]
+    fn wgsllh2ecef() {
+        for (llh_input, expected_ecef) in LLH_VALUES.iter().zip(ECEF_VALUES.iter()) {
+            let ecef = llh_input.to_ecef();
+
+            assert!(!ecef.x().is_nan());
+            assert!(!ecef.y().is_nan());
+            assert!(!ecef.z().is_nan());
+
+            let x_err = ecef.x() - expected_ecef.x();
+            assert!(x_err.abs() < MAX_DIST_ERROR_M);
+
+            let y_err = ecef.y() - expected_ecef.y();
+            assert!(y_err.abs() < MAX_DIST_ERROR_M);
+
+            let z_err = ecef.z() - expected_ecef.z();
+            assert!(z_err.abs() < MAX_DIST_ERROR_M);
+        }
+    }
+
+    #[test]
+    fn wgsecef2llh() {
+        for (ecef_input, expected_llh) in ECEF_VALUES.iter().zip(LLH_VALUES.iter()) {
+            let llh = ecef_input.to_llh();
+
+            assert!(!llh.latitude().is_nan());
+            assert!(!llh.longitude().is_nan());
+            assert!(!llh.height().is_nan());
+
+            let lat_err = llh.latitude() - expected_llh.latitude();
+            assert!(lat_err.abs() < MAX_ANGLE_ERROR_RAD);
+
+            let lon_err = llh.longitude() - expected_llh.longitude();
+            assert!(lon_err.abs() < MAX_ANGLE_ERROR_RAD);
+
+            let height_err = llh.height() - expected_llh.height();
+            assert!(height_err.abs() < MAX_DIST_ERROR_M);
+        }
+    }
+
+    #[test]
+    fn ecef_ops() {
+        let a = ECEF::new(1.0, 2.0, 3.0);
+        let b = ECEF::new(4.0, 5.0, 6.0);
+
+        let result = a + b;
+        assert_eq!(5.0, result.x());
+        assert_eq!(7.0, result.y());
+        assert_eq!(9.0, result.z());
+
+        let result = a + a + a;
+        assert_eq!(3.0, result.x());
+        assert_eq!(6.0, result.y());
+        assert_eq!(9.0, result.z());
+
+        let result = a - b;
+        assert_eq!(-3.0, result.x());
+        assert_eq!(-3.0, result.y());
+        assert_eq!(-3.0, result.z());
+
+        let result = 2.0 * a;
+        assert_eq!(2.0, result.x());
+        assert_eq!(4.0, result.y());
+        assert_eq!(6.0, result.z());
+
+        let mut result = a;
+        result += b;
+        assert_eq!(5.0, result.x());
+        assert_eq!(7.0, result.y());
+        assert_eq!(9.0, result.z());
+
+        let mut result = a;
+        result -= b;
+        assert_eq!(-3.0, result.x());
+        assert_eq!(-3.0, result.y());
+        assert_eq!(-3.0, result.z());
+
+        let mut result = a;
+        result *= 2.0;
+        assert_eq!(2.0, result.x());
+        assert_eq!(4.0, result.y());
+        assert_eq!(6.0, result.z());
+
+        assert_eq!(32.0, a.dot(&b));
+        let result = a.cross(&b);
+        assert_eq!(-3.0, result.x());
+        assert_eq!(6.0, result.y());
+        assert_eq!(-3.0, result.z());
+        assert_eq!(0.0, result.dot(&a));
+        assert_eq!(7.0, ECEF::new(2.0, 3.0, 6.0).norm());
+
+        let result = ECEF::new(0.0, 3.0, 4.0).unit_vector();
+        assert_float_eq!(result.norm(), 1.0, abs <= 1e-15);
+        assert_float_eq!(result.y(), 0.6, abs <= 1e-15);
+    }
+
+    #[test]
+    fn coordinate_epoch() {
+        let initial_epoch = UtcTime::from_date(2020, 1, 1, 0, 0, 0.).to_gps_hardcoded();
+        let new_epoch = UtcTime::from_date(2021, 1, 1, 0, 0, 0.).to_gps_hardcoded();
+        let initial_coord = Coordinate::with_velocity(
+            ReferenceFrame::ITRF2020,
+            ECEF::new(0.0, 0.0, 0.0),
+            ECEF::new(1.0, 2.0, 3.0),
+            initial_epoch,
+        );
+
+        let new_coord = initial_coord.adjust_epoch(&new_epoch);
+
+        assert_eq!(initial_coord.reference_frame, new_coord.reference_frame);
+        assert_float_eq!(new_coord.position.x(), 1.0, abs <= 0.001);
+        assert_float_eq!(new_coord.position.y(), 2.0, abs <= 0.001);
+        assert_float_eq!(new_coord.position.z(), 3.0, abs <= 0.001);
+        assert_float_eq!(new_coord.velocity.unwrap().x(), 1.0, abs <= 0.001);
+        assert_float_eq!(new_coord.velocity.unwrap().y(), 2.0, abs <= 0.001);
+        assert_float_eq!(new_coord.velocity.unwrap().z(), 3.0, abs <= 0.001);
+        assert_eq!(new_epoch, new_coord.epoch());
+    }
+
+    #[test]
+    fn merge_coordinate_estimates() {
+        let epoch = UtcTime::from_date(2020, 1, 1, 0, 0, 0.).to_gps_hardcoded();
+        let diagonal = |sigma: f64| {
+            let v = sigma * sigma;
+            [[v, 0.0, 0.0], [0.0, v, 0.0], [0.0, 0.0, v]]
+        };
+        let a = CoordinateEstimate::new(
+            Coordinate::without_velocity(
+                ReferenceFrame::ITRF2014,
+                ECEF::new(-2703764.0, -4261273.0, 3887158.0),
+                epoch,
+            ),
+            diagonal(0.01),
+        );
+        let b = CoordinateEstimate::new(
+            Coordinate::without_velocity(
+                ReferenceFrame::ITRF2014,
+                ECEF::new(-2703764.03, -4261273.0, 3887158.0),
+                epoch,
+            ),
+            diagonal(0.02),
+        );
+
+        let merged = merge_coordinates(&[a, b], ReferenceFrame::ITRF2014, &epoch).unwrap();
+        let position = merged.coordinate().position();
+        // Weights of 4:1 in favor of the first estimate
+        assert_float_eq!(position.x(), -2703764.006, abs <= MAX_DIST_ERROR_M);
+        assert_float_eq!(position.y(), -4261273.0, abs <= MAX_DIST_ERROR_M);
+        assert_float_eq!(merged.covariance()[0][0], 0.00008, abs <= 1e-12);
+        assert_float_eq!(merged.covariance()[0][1], 0.0, abs <= 1e-12);
+        assert!(merged.coordinate().velocity().is_none());
+
+        // Merging into another frame transforms the estimates first
+        let merged = merge_coordinates(&[a, b], ReferenceFrame::NAD83_2011, &epoch).unwrap();
+        let expected = a
+            .coordinate()
+            .transform_to(ReferenceFrame::NAD83_2011)
+            .unwrap();
+        assert_eq!(
+            merged.coordinate().reference_frame(),
+            ReferenceFrame::NAD83_2011
+        );
+        assert_float_eq!(
+            merged.coordinate().position().x(),
+            expected.position().x() - 0.006,
+            abs <= 1e-4
+        );
+
+        assert_eq!(
+            merge_coordinates(&[], ReferenceFrame::ITRF2014, &epoch),
+            Err(MergeError::NoEstimates)
+        );
+        let singular = CoordinateEstimate::new(a.coordinate(), [[0.0; 3]; 3]);
+        assert_eq!(
+            merge_coordinates(&[a, singular], ReferenceFrame::ITRF2014, &epoch),
+            Err(MergeError::SingularCovariance)
+        );
+    }
+
+    #[test]
+    fn baseline() {
+        let epoch = UtcTime::from_date(2020, 1, 1, 0, 0, 0.).to_gps_hardcoded();
+        let later = UtcTime::from_date(2022, 1, 1, 0, 0, 0.).to_gps_hardcoded();
+        let position = LLHDegrees::new(45.0, 10.0, 100.0).to_ecef();
+        let base = Coordinate::without_velocity(ReferenceFrame::ITRF2014, position, epoch);
+
+        // 3 m north and 4 m east, moving 1 m/yr east and observed two years
+        // later
+        let offset = NED::new(3.0, 4.0, 0.0).ecef_vector_at(&position);
+        let velocity = NED::new(0.0, 1.0, 0.0).ecef_vector_at(&position);
+        let rover = Coordinate::with_velocity(
+            ReferenceFrame::ITRF2014,
+            position + offset + 2.0 * velocity,
+            velocity,
+            later,
+        );
+        assert!(matches!(
+            base.baseline_to(&rover, EpochPolicy::Exact),
+            Err(BaselineError::EpochMismatch(_))
+        ));
+        let baseline = base.baseline_to(&rover, EpochPolicy::Propagate).unwrap();
+        assert_float_eq!(baseline.ned().n(), 3.0, abs <= 1e-3);
+        assert_float_eq!(baseline.ned().e(), 4.0, abs <= 1e-3);
+        assert_float_eq!(baseline.length(), 5.0, abs <= 1e-3);
+        assert_float_eq!(baseline.epoch_difference(), 2.0, abs <= 0.01);
+        assert_eq!(baseline.covariance(), None);
+        assert_eq!(
+            rover.baseline_to(&base, EpochPolicy::Propagate),
+            Err(BaselineError::MissingVelocity)
+        );
+        let nad83 = Coordinate::without_velocity(ReferenceFrame::NAD83_2011, position, epoch);
+        assert_eq!(
+            base.baseline_to(&nad83, EpochPolicy::Exact),
+            Err(BaselineError::FrameMismatch(
+                ReferenceFrame::ITRF2014,
+                ReferenceFrame::NAD83_2011
+            ))
+        );
+
+        // Isotropic covariances stay isotropic in the local frame
+        let cov = [[0.01, 0.0, 0.0], [0.0, 0.01, 0.0], [0.0, 0.0, 0.01]];
+        let baseline = CoordinateEstimate::new(base, cov)
+            .baseline_to(&CoordinateEstimate::new(rover, cov), EpochPolicy::Propagate)
+            .unwrap();
+        let covariance = baseline.covariance().unwrap();
+        for (i, row) in covariance.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                let expected = if i == j { 0.02 } else { 0.0 };
+                assert_float_eq!(*value, expected, abs <= 1e-12);
+            }
+        }
+        assert_float_eq!(
+            baseline.length_sigma().unwrap(),
+            0.02f64.sqrt(),
+            abs <= 1e-9
+        );
+    }
+
+    #[test]
+    fn coordinate_difference() {
+        let graph = TransformationGraph::new();
+        let epoch = UtcTime::from_date(2020, 1, 1, 0, 0, 0.).to_gps_hardcoded();
+        let later = UtcTime::from_date(2022, 1, 1, 0, 0, 0.).to_gps_hardcoded();
+        let itrf = Coordinate::with_velocity(
+            ReferenceFrame::ITRF2014,
+            ECEF::new(-2703764.0, -4261273.0, 3887158.0),
+            ECEF::new(-0.01, 0.0, 0.02),
+            epoch,
+        );
+
+        // The same point in another frame and at another epoch
+        let nad83 = itrf
+            .transform_to(ReferenceFrame::NAD83_2011)
+            .unwrap()
+            .adjust_epoch(&later);
+        assert!((nad83.position() - itrf.position()).as_array_ref()[0].abs() > 0.5);
+        assert!(itrf.approx_eq(&nad83, 1e-3, &graph).unwrap());
+        let difference = itrf.difference(&nad83, &graph).unwrap();
+        assert!(difference.distance() < 1e-3);
+        assert!(difference.was_transformed());
+        assert_eq!(difference.reference_frame(), ReferenceFrame::ITRF2014);
+        assert_eq!(difference.other_frame(), ReferenceFrame::NAD83_2011);
+        assert_float_eq!(difference.epoch_difference(), 2.0, abs <= 0.01);
+        assert!(!difference.is_epoch_mismatched());
+
+        // A point 3 m east and 1 m up, at the same epoch and without velocity
+        let offset = NED::new(0.0, 3.0, -1.0).ecef_vector_at(&itrf.position());
+        let moved =
+            Coordinate::without_velocity(ReferenceFrame::ITRF2014, itrf.position() + offset, later);
+        let difference = itrf.difference(&moved, &graph).unwrap();
+        assert_float_eq!(difference.north(), 0.0, abs <= MAX_DIST_ERROR_M);
+        assert_float_eq!(difference.east(), 3.0, abs <= MAX_DIST_ERROR_M);
+        assert_float_eq!(difference.up(), 1.0, abs <= MAX_DIST_ERROR_M);
+        assert_float_eq!(difference.horizontal(), 3.0, abs <= MAX_DIST_ERROR_M);
+        assert!(!difference.was_transformed());
+        assert!(difference.is_epoch_mismatched());
+        assert!(!itrf.approx_eq(&moved, 3.0, &graph).unwrap());
+    }
+
+    #[test]
+    fn speed_and_course() {
+        let covariance = [[0.04, 0.0, 0.0], [0.0, 0.01, 0.0], [0.0, 0.0, 0.09]];
+
+        let east = SpeedAndCourse::from_ned(&NED::new(0.0, 10.0, -1.0), &covariance);
+        assert_float_eq!(east.speed_over_ground(), 10.0, abs <= 1e-12);
+        assert_float_eq!(east.speed_over_ground_sigma(), 0.1, abs <= 1e-12);
+        assert_float_eq!(east.course_over_ground().unwrap(), 90.0, abs <= 1e-12);
+        // The north error of 0.2 m/s is perpendicular to travel
+        assert_float_eq!(
+            east.course_over_ground_sigma(),
+            (0.2_f64 / 10.0).to_degrees(),
+            abs <= 1e-9
+        );
+        assert_float_eq!(east.vertical_speed(), 1.0, abs <= 1e-12);
+        assert_float_eq!(east.vertical_speed_sigma(), 0.3, abs <= 1e-12);
+
+        let south_west = SpeedAndCourse::from_ned(&NED::new(-1.0, -1.0, 0.0), &covariance);
+        assert_float_eq!(south_west.course_over_ground().unwrap(), 225.0, abs <= 1e-9);
+
+        let stopped = SpeedAndCourse::from_ned(&NED::default(), &covariance);
+        assert!(stopped.course_over_ground().is_none());
+        assert_float_eq!(stopped.course_over_ground_sigma(), 180.0, abs <= 1e-12);
+    }
+
+    fn assert_covariance_eq(a: &[[f64; 3]; 3], b: &[[f64; 3]; 3]) {
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert_float_eq!(*a, *b, abs_all <= 1e-12);
+        }
+    }
+
+    #[test]
+    fn covariance_rotations() {
+        let covariance = [[4.0, 1.0, 0.5], [1.0, 9.0, -2.0], [0.5, -2.0, 16.0]];
+
+        // At the equator and prime meridian north is Z, east is Y and down is -X
+        let origin = LLHRadians::new(0.0, 0.0, 0.0);
+        let ned = ecef_to_ned_covariance(&covariance, &origin);
+        assert_covariance_eq(
+            &ned,
+            &[[16.0, -2.0, -0.5], [-2.0, 9.0, -1.0], [-0.5, -1.0, 4.0]],
+        );
+        let enu = ecef_to_enu_covariance(&covariance, &origin);
+        assert_covariance_eq(
+            &enu,
+            &[[9.0, -2.0, 1.0], [-2.0, 16.0, 0.5], [1.0, 0.5, 4.0]],
+        );
+
+        // At the north pole down is -Z
+        let pole = LLHRadians::new(90.0 * D2R, 0.0, 0.0);
+        let ned = ecef_to_ned_covariance(&covariance, &pole);
+        assert_float_eq!(ned[2][2], 16.0, abs <= 1e-12);
+
+        let location = LLHDegrees::new(37.77, -122.39, 10.0).to_radians();
+        let ned = ecef_to_ned_covariance(&covariance, &location);
+        let enu = ecef_to_enu_covariance(&covariance, &location);
+        let trace = |c: &[[f64; 3]; 3]| c[0][0] + c[1][1] + c[2][2];
+        assert_float_eq!(trace(&ned), trace(&covariance), abs <= 1e-12);
+        assert_float_eq!(ned[0][0], enu[1][1], abs <= 1e-12);
+        assert_float_eq!(ned[0][2], -enu[1][2], abs <= 1e-12);
+        assert_covariance_eq(&ned_to_ecef_covariance(&ned, &location), &covariance);
+        assert_covariance_eq(&enu_to_ecef_covariance(&enu, &location), &covariance);
+    }
+
+    #[test]
+    fn error_ellipse() {
+        let ellipse =
+            ErrorEllipse::from_ned_covariance(&[[4.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 9.0]]);
+        assert_float_eq!(ellipse.semi_major(), 2.0, abs <= 1e-12);
+        assert_float_eq!(ellipse.semi_minor(), 1.0, abs <= 1e-12);
+        assert_float_eq!(ellipse.orientation(), 0.0, abs <= 1e-12);
+        assert_float_eq!(ellipse.scaled(2.0).semi_minor(), 2.0, abs <= 1e-12);
+
+        // Elongated along the north-east diagonal
+        let ellipse =
+            ErrorEllipse::from_ned_covariance(&[[2.5, 1.5, 0.0], [1.5, 2.5, 0.0], [0.0, 0.0, 0.0]]);
+        assert_float_eq!(ellipse.semi_major(), 2.0, abs <= 1e-12);
+        assert_float_eq!(ellipse.semi_minor(), 1.0, abs <= 1e-12);
+        assert_float_eq!(ellipse.orientation(), 45.0, abs <= 1e-9);
+
+        // and along the north-west diagonal
+        let ellipse = ErrorEllipse::from_ned_covariance(&[
+            [2.5, -1.5, 0.0],
+            [-1.5, 2.5, 0.0],
+            [0.0, 0.0, 0.0],
+        ]);
+        assert_float_eq!(ellipse.orientation(), 135.0, abs <= 1e-9);
+
+        let accuracy = PositionAccuracy::from_ned_covariance(&[
+            [9.0, 0.0, 0.0],
+            [0.0, 16.0, 0.0],
+            [0.0, 0.0, 4.0],
+        ]);
+        assert_float_eq!(accuracy.north_sigma(), 3.0, abs <= 1e-12);
+        assert_float_eq!(accuracy.east_sigma(), 4.0, abs <= 1e-12);
+        assert_float_eq!(accuracy.horizontal_sigma(), 5.0, abs <= 1e-12);
+        assert_float_eq!(accuracy.vertical_sigma(), 2.0, abs <= 1e-12);
+        assert_float_eq!(accuracy.ellipse().orientation(), 90.0, abs <= 1e-12);
+
+        let location = LLHDegrees::new(37.77, -122.39, 10.0).to_radians();
+        let ecef = ned_to_ecef_covariance(
+            &[[9.0, 0.0, 0.0], [0.0, 16.0, 0.0], [0.0, 0.0, 4.0]],
+            &location,
+        );
+        let from_ecef = PositionAccuracy::from_ecef_covariance(&ecef, &location);
+        assert_float_eq!(from_ecef.horizontal_sigma(), 5.0, abs <= 1e-9);
+        assert_float_eq!(from_ecef.vertical_sigma(), 2.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn azimuth_elevation() {
+        let azel = AzimuthElevation::new(30.0 * D2R, 20.0 * D2R);
+        let ned = azel.to_ned();
+        assert_float_eq!(
+            ned.n().powi(2) + ned.e().powi(2) + ned.d().powi(2),
+            1.0,
+            abs <= 1e-12
+        );
+        assert!(ned.n() > 0.0 && ned.e() > 0.0 && ned.d() < 0.0);
+        let back = AzimuthElevation::from_ned(&ned);
+        assert_float_eq!(back.az, azel.az, abs <= 1e-12);
+        assert_float_eq!(back.el, azel.el, abs <= 1e-12);
+
+        // Any length, with the azimuth wrapped into [0, 2π)
+        let west_down = AzimuthElevation::from_ned(&NED::new(0.0, -5.0, 5.0));
+        assert_float_eq!(west_down.az, 270.0 * D2R, abs <= 1e-12);
+        assert_float_eq!(west_down.el, -45.0 * D2R, abs <= 1e-12);
+        let zenith = AzimuthElevation::from_ned(&NED::new(0.0, 0.0, -1.0));
+        assert_float_eq!(zenith.az, 0.0, abs <= 1e-12);
+        assert_float_eq!(zenith.el, 90.0 * D2R, abs <= 1e-12);
+
+        // At the equator and prime meridian up is X and north is Z
+        let site = LLHDegrees::new(0.0, 0.0, 0.0).to_ecef();
+        let up = AzimuthElevation::new(0.0, 90.0 * D2R).line_of_sight_at(&site);
+        assert_float_eq!(up.as_array_ref(), &[1.0, 0.0, 0.0], abs_all <= 1e-9);
+        let north = AzimuthElevation::new(0.0, 0.0).line_of_sight_at(&site);
+        assert_float_eq!(north.as_array_ref(), &[0.0, 0.0, 1.0], abs_all <= 1e-9);
+        let target = site + 1000.0 * azel.line_of_sight_at(&site);
+        let seen = site.azel_of(&target);
+        assert_float_eq!(seen.az, azel.az, abs <= 1e-9);
+        assert_float_eq!(seen.el, azel.el, abs <= 1e-9);
+    }
+
+    #[test]
+    fn normalize_azimuth_elevation() {
+        for (az, el, expected_az, expected_el) in [
+            (30.0, 20.0, 30.0, 20.0),
+            (-90.0, 10.0, 270.0, 10.0),
+            (720.0 + 45.0, -10.0, 45.0, -10.0),
+            (10.0, 100.0, 190.0, 80.0),
+            (10.0, -100.0, 190.0, -80.0),
+            (200.0, 370.0, 200.0, 10.0),
+        ]
+        .iter()
+        {
+            let normalized = AzimuthElevation::new(az * D2R, el * D2R).normalized();
+            assert_float_eq!(normalized.az, expected_az * D2R, abs <= 1e-12);
+            assert_float_eq!(normalized.el, expected_el * D2R, abs <= 1e-12);
+        }
+
+        let signed = AzimuthElevation::new(270.0 * D2R, 0.0).normalized_signed();
+        assert_float_eq!(signed.az, -90.0 * D2R, abs <= 1e-12);
+        let signed = AzimuthElevation::new(-180.0 * D2R, 0.0).normalized_signed();
+        assert_float_eq!(signed.az, 180.0 * D2R, abs <= 1e-12);
+    }
+
+    #[test]
+    fn display_azimuth_elevation() {
+        let azel = AzimuthElevation::new(123.456 * D2R, -5.0 * D2R);
+        assert_eq!(azel.to_string(), "az 123.5°, el -5.0°");
+        assert_eq!(format!("{:.3}", azel), "az 123.456°, el -5.000°");
+    }
+
+    #[test]
+    fn ray_intersection() {
+        let observer = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+
+        let zenith = observer
+            .ray_intersection(&AzimuthElevation::new(0.0, 90.0 * D2R), 350_000.0)
+            .unwrap();
+        assert_float_eq!(zenith.latitude(), 37.77 * D2R, abs <= 1e-9);
+        assert_float_eq!(zenith.longitude(), -122.39 * D2R, abs <= 1e-9);
+        assert_float_eq!(zenith.height(), 350_000.0, abs <= 1e-3);
+
+        for (az, el) in [(10.0, 5.0), (45.0, 30.0), (200.0, 60.0), (300.0, 0.0)].iter() {
+            let direction = AzimuthElevation::new(az * D2R, el * D2R);
+            let pierce = observer.ray_intersection(&direction, 350_000.0).unwrap();
+            assert_float_eq!(pierce.height(), 350_000.0, abs <= 1e-3);
+            let azel = observer.azel_of(&pierce.to_ecef());
+            assert_float_eq!(azel.az, direction.az, abs <= 1e-9);
+            assert_float_eq!(azel.el, direction.el, abs <= 1e-9);
+        }
+
+        // Looking down from an aircraft at 45° below the horizon
+        let aircraft = LLHDegrees::new(37.77, -122.39, 1000.0).to_ecef();
+        let down = AzimuthElevation::new(90.0 * D2R, -45.0 * D2R);
+        let ground = aircraft.ray_intersection(&down, 0.0).unwrap();
+        assert_float_eq!(ground.height(), 0.0, abs <= 1e-3);
+        let offset = (ground.to_ecef() - aircraft).ned_vector_at(&aircraft);
+        assert_float_eq!(offset.e(), 1000.0, abs <= 1.0);
+        assert_float_eq!(offset.n(), 0.0, abs <= 1.0);
+
+        let up = AzimuthElevation::new(0.0, 10.0 * D2R);
+        assert!(aircraft.ray_intersection(&up, 0.0).is_none());
+        let horizontal = AzimuthElevation::new(0.0, 0.0);
+        assert!(aircraft.ray_intersection(&horizontal, 0.0).is_none());
+    }
+
+    #[test]
+    fn geodesics() {
+        use std::f64::consts::PI;
+        let dms = |d: f64, m: f64, s: f64| d.signum() * (d.abs() + m / 60.0 + s / 3600.0) * D2R;
+        // Flinders Peak to Buninyong, Vincenty (1975)
+        let flinders = LLHRadians::new(dms(-37.0, 57.0, 3.72030), dms(144.0, 25.0, 29.52440), 0.0);
+        let buninyong =
+            LLHRadians::new(dms(-37.0, 39.0, 10.15610), dms(143.0, 55.0, 35.38390), 0.0);
+
+        let geodesic = flinders.geodesic_to(&buninyong).unwrap();
+        assert_float_eq!(geodesic.distance(), 54_972.271, abs <= 1e-3);
+        assert_float_eq!(
+            geodesic.initial_azimuth(),
+            dms(306.0, 52.0, 5.37),
+            abs <= 1e-7
+        );
+        assert_float_eq!(
+            geodesic.final_azimuth(),
+            dms(307.0, 10.0, 25.07),
+            abs <= 1e-7
+        );
+
+        let (destination, final_azimuth) =
+            flinders.geodesic_destination(geodesic.initial_azimuth(), geodesic.distance());
+        assert_float_eq!(destination.latitude(), buninyong.latitude(), abs <= 1e-10);
+        assert_float_eq!(destination.longitude(), buninyong.longitude(), abs <= 1e-10);
+        assert_float_eq!(final_azimuth, geodesic.final_azimuth(), abs <= 1e-9);
+
+        // A quarter of the equator, and a meridian arc from pole to pole
+        let origin = LLHRadians::new(0.0, 0.0, 0.0);
+        let east = origin
+            .geodesic_to(&LLHRadians::new(0.0, 90.0 * D2R, 0.0))
+            .unwrap();
+        assert_float_eq!(
+            east.distance(),
+            WGS84::SEMI_MAJOR_AXIS * PI / 2.0,
+            abs <= 1e-3
+        );
+        assert_float_eq!(east.initial_azimuth(), PI / 2.0, abs <= 1e-12);
+        let south_pole = LLHRadians::new(-PI / 2.0, 0.0, 0.0);
+        let meridian = LLHRadians::new(PI / 2.0, 0.0, 0.0)
+            .geodesic_to(&south_pole)
+            .unwrap();
+        assert_float_eq!(meridian.distance(), 20_003_931.4586, abs <= 1e-3);
+
+        // Crossing the antimeridian
+        let (across, _) = LLHRadians::new(0.0, 179.0 * D2R, 0.0)
+            .geodesic_destination(PI / 2.0, east.distance() / 45.0);
+        assert_float_eq!(across.longitude(), -179.0 * D2R, abs <= 1e-9);
+
+        assert_eq!(origin.geodesic_to(&origin).unwrap().distance(), 0.0);
+        let antipodal = LLHRadians::new(0.5 * D2R, 179.7 * D2R, 0.0);
+        assert_eq!(
+            origin.geodesic_to(&antipodal),
+            Err(GeodesicError::NotConverged)
+        );
+    }
+}
@@ -0,0 +1,455 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Least squares adjustment of survey networks
+//!
+//! A survey network is made of stations connected by measured baselines,
+//! such as the vectors between GNSS receivers occupying the stations at the
+//! same time. Some of the stations have known coordinates and are held fixed,
+//! the coordinates of the other ones are estimated from the baselines.
+//!
+//! Each baseline is an observation of the difference of the positions of its
+//! two stations, weighted by the inverse of its covariance. The baselines are
+//! taken to be independent of each other. Since the observations are linear
+//! in the positions the adjustment is solved directly, without iterating.
+//!
+//! The network is meant for small surveys, the normal equations are solved
+//! densely.
+
+use std::error::Error;
+use std::fmt;
+
+use super::{ECEF, NED};
+use crate::solver::linalg::Matrix;
+
+/// Identifies a station of a [`Network`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StationId(usize);
+
+/// Errors which can occur when adjusting a network
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum NetworkError {
+    /// None of the stations are held fixed
+    NoFixedStation,
+    /// A baseline refers to a station of another network
+    UnknownStation(StationId),
+    /// The covariance of the baseline with the given index isn't invertible
+    SingularCovariance(usize),
+    /// Some stations aren't connected to a fixed station by baselines, so
+    /// their coordinates can't be determined
+    Underdetermined,
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkError::NoFixedStation => write!(f, "No fixed station in the network"),
+            NetworkError::UnknownStation(id) => write!(f, "Unknown station {}", id.0),
+            NetworkError::SingularCovariance(index) => {
+                write!(f, "Singular covariance of baseline {}", index)
+            }
+            NetworkError::Underdetermined => {
+                write!(f, "Stations not connected to a fixed station")
+            }
+        }
+    }
+}
+
+impl Error for NetworkError {}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Station {
+    name: String,
+    position: ECEF,
+    fixed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Observation {
+    from: StationId,
+    to: StationId,
+    vector: ECEF,
+    covariance: [[f64; 3]; 3],
+}
+
+/// A survey network of stations and baselines between them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Network {
+    stations: Vec<Station>,
+    baselines: Vec<Observation>,
+}
+
+impl Network {
+    pub fn new() -> Network {
+        Network::default()
+    }
+
+    /// Adds a station whose coordinates are estimated
+    ///
+    /// The approximate position is only used to rotate the baselines given
+    /// in the local level frame, it can be meters off.
+    pub fn add_station(&mut self, name: &str, approximate: ECEF) -> StationId {
+        self.push_station(name, approximate, false)
+    }
+
+    /// Adds a station with known coordinates, held fixed by the adjustment
+    pub fn add_fixed_station(&mut self, name: &str, position: ECEF) -> StationId {
+        self.push_station(name, position, true)
+    }
+
+    fn push_station(&mut self, name: &str, position: ECEF, fixed: bool) -> StationId {
+        self.stations.push(Station {
+            name: name.to_string(),
+            position,
+            fixed,
+        });
+        StationId(self.stations.len() - 1)
+    }
+
+    /// Finds a station by name
+    pub fn find_station(&self, name: &str) -> Option<StationId> {
+        self.stations
+            .iter()
+            .position(|station| station.name == name)
+            .map(StationId)
+    }
+
+    pub fn station_name(&self, id: StationId) -> Option<&str> {
+        self.stations.get(id.0).map(|station| station.name.as_str())
+    }
+
+    /// Adds a baseline from one station to another, as an ECEF vector with
+    /// its covariance in meters squared
+    pub fn add_baseline(
+        &mut self,
+        from: StationId,
+        to: StationId,
+        vector: ECEF,
+        covariance: [[f64; 3]; 3],
+    ) {
+        self.baselines.push(Observation {
+            from,
+            to,
+            vector,
+            covariance,
+        });
+    }
+
+    /// Adds a baseline from one station to another, in the local level frame
+    /// at the `from` station with its covariance in meters squared
+    ///
+    /// The baseline is rotated into ECEF at the approximate position of the
+    /// `from` station, so it must already be in the network.
+    pub fn add_ned_baseline(
+        &mut self,
+        from: StationId,
+        to: StationId,
+        ned: NED,
+        covariance: [[f64; 3]; 3],
+    ) -> Result<(), NetworkError> {
+        let origin = self
+            .stations
+            .get(from.0)
+            .ok_or(NetworkError::UnknownStation(from))?
+            .position;
+        // The columns of the rotation are the local axes in ECEF
+        let axes = [
+            NED::new(1.0, 0.0, 0.0).ecef_vector_at(&origin),
+            NED::new(0.0, 1.0, 0.0).ecef_vector_at(&origin),
+            NED::new(0.0, 0.0, 1.0).ecef_vector_at(&origin),
+        ];
+        let rotation = |i: usize, j: usize| axes[j].as_array_ref()[i];
+        let mut ecef_covariance = [[0.0; 3]; 3];
+        for (i, row) in ecef_covariance.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..3)
+                    .flat_map(|k| (0..3).map(move |l| (k, l)))
+                    .map(|(k, l)| rotation(i, k) * covariance[k][l] * rotation(j, l))
+                    .sum();
+            }
+        }
+        self.add_baseline(from, to, ned.ecef_vector_at(&origin), ecef_covariance);
+        Ok(())
+    }
+
+    pub fn station_count(&self) -> usize {
+        self.stations.len()
+    }
+
+    pub fn baseline_count(&self) -> usize {
+        self.baselines.len()
+    }
+
+    /// Estimates the coordinates of the stations which aren't fixed
+    pub fn adjust(&self) -> Result<NetworkSolution, NetworkError> {
+        if !self.stations.iter().any(|station| station.fixed) {
+            return Err(NetworkError::NoFixedStation);
+        }
+        // Index of each free station among the unknowns
+        let mut unknowns = vec![None; self.stations.len()];
+        let mut count = 0;
+        for (station, unknown) in self.stations.iter().zip(unknowns.iter_mut()) {
+            if !station.fixed {
+                *unknown = Some(count);
+                count += 1;
+            }
+        }
+
+        let mut weights = Vec::with_capacity(self.baselines.len());
+        for (index, baseline) in self.baselines.iter().enumerate() {
+            for id in [baseline.from, baseline.to] {
+                if id.0 >= self.stations.len() {
+                    return Err(NetworkError::UnknownStation(id));
+                }
+            }
+            let weight = to_matrix(&baseline.covariance)
+                .inverse()
+                .ok_or(NetworkError::SingularCovariance(index))?;
+            weights.push(weight);
+        }
+
+        // Accumulates the normal equations, the design matrix of a baseline
+        // is -I for its start station and I for its end station
+        let mut normal = Matrix::zeros(3 * count, 3 * count);
+        let mut rhs = vec![0.0; 3 * count];
+        for (baseline, weight) in self.baselines.iter().zip(weights.iter()) {
+            // The observation with the fixed stations moved to the right
+            let mut reduced = *baseline.vector.as_array_ref();
+            let ends = [(baseline.from, -1.0), (baseline.to, 1.0)];
+            for (id, sign) in ends {
+                if unknowns[id.0].is_none() {
+                    let position = self.stations[id.0].position;
+                    for (r, p) in reduced.iter_mut().zip(position.as_array_ref()) {
+                        *r -= sign * p;
+                    }
+                }
+            }
+            let weighted = weight.mul_vec(&reduced);
+            for (a, sign_a) in ends {
+                let a = match unknowns[a.0] {
+                    Some(a) => a,
+                    None => continue,
+                };
+                for i in 0..3 {
+                    rhs[3 * a + i] += sign_a * weighted[i];
+                }
+                for (b, sign_b) in ends {
+                    let b = match unknowns[b.0] {
+                        Some(b) => b,
+                        None => continue,
+                    };
+                    for i in 0..3 {
+                        for j in 0..3 {
+                            normal[(3 * a + i, 3 * b + j)] += sign_a * sign_b * weight[(i, j)];
+                        }
+                    }
+                }
+            }
+        }
+
+        let cofactor = if count > 0 {
+            normal.inverse().ok_or(NetworkError::Underdetermined)?
+        } else {
+            Matrix::zeros(0, 0)
+        };
+        let solution = cofactor.mul_vec(&rhs);
+
+        let positions: Vec<ECEF> = self
+            .stations
+            .iter()
+            .zip(unknowns.iter())
+            .map(|(station, unknown)| match unknown {
+                Some(u) => ECEF::new(solution[3 * u], solution[3 * u + 1], solution[3 * u + 2]),
+                None => station.position,
+            })
+            .collect();
+        let covariances = unknowns
+            .iter()
+            .map(|unknown| {
+                let mut covariance = [[0.0; 3]; 3];
+                if let Some(u) = unknown {
+                    for (i, row) in covariance.iter_mut().enumerate() {
+                        for (j, value) in row.iter_mut().enumerate() {
+                            *value = cofactor[(3 * u + i, 3 * u + j)];
+                        }
+                    }
+                }
+                covariance
+            })
+            .collect();
+
+        let mut residuals = Vec::with_capacity(self.baselines.len());
+        let mut weighted_sum = 0.0;
+        for (baseline, weight) in self.baselines.iter().zip(weights.iter()) {
+            let residual = positions[baseline.to.0] - positions[baseline.from.0] - baseline.vector;
+            let v = residual.as_array_ref();
+            weighted_sum += weight
+                .mul_vec(v)
+                .iter()
+                .zip(v.iter())
+                .map(|(wv, v)| wv * v)
+                .sum::<f64>();
+            residuals.push(residual);
+        }
+        let degrees_of_freedom = (3 * self.baselines.len()).saturating_sub(3 * count);
+
+        Ok(NetworkSolution {
+            positions,
+            covariances,
+            residuals,
+            variance_factor: if degrees_of_freedom > 0 {
+                weighted_sum / degrees_of_freedom as f64
+            } else {
+                1.0
+            },
+            degrees_of_freedom,
+        })
+    }
+}
+
+fn to_matrix(array: &[[f64; 3]; 3]) -> Matrix {
+    let mut matrix = Matrix::zeros(3, 3);
+    for (i, row) in array.iter().enumerate() {
+        for (j, value) in row.iter().enumerate() {
+            matrix[(i, j)] = *value;
+        }
+    }
+    matrix
+}
+
+/// The result of adjusting a [`Network`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkSolution {
+    positions: Vec<ECEF>,
+    covariances: Vec<[[f64; 3]; 3]>,
+    residuals: Vec<ECEF>,
+    variance_factor: f64,
+    degrees_of_freedom: usize,
+}
+
+impl NetworkSolution {
+    /// Gets the adjusted position of a station, fixed stations keep their
+    /// position
+    pub fn position(&self, id: StationId) -> ECEF {
+        self.positions[id.0]
+    }
+
+    /// Gets the covariance of the adjusted position of a station, in meters
+    /// squared
+    ///
+    /// This is the covariance propagated from the baselines' covariances, it
+    /// isn't scaled by the variance factor. The covariance of fixed stations
+    /// is zero.
+    pub fn covariance(&self, id: StationId) -> [[f64; 3]; 3] {
+        self.covariances[id.0]
+    }
+
+    /// Gets the residual of each baseline, the adjusted vector minus the
+    /// observed one, in the order the baselines were added
+    pub fn residuals(&self) -> &[ECEF] {
+        &self.residuals
+    }
+
+    /// Gets the a posteriori variance factor, the weighted sum of the squared
+    /// residuals divided by the degrees of freedom
+    ///
+    /// Values much larger than one mean the baselines' covariances are too
+    /// optimistic, or that some baselines are blunders. It is one when there
+    /// is no redundancy.
+    pub fn variance_factor(&self) -> f64 {
+        self.variance_factor
+    }
+
+    pub fn degrees_of_freedom(&self) -> usize {
+        self.degrees_of_freedom
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::LLHDegrees;
+    use float_eq::assert_float_eq;
+
+    const COVARIANCE: [[f64; 3]; 3] = [[1e-4, 0.0, 0.0], [0.0, 1e-4, 0.0], [0.0, 0.0, 1e-4]];
+
+    #[test]
+    fn triangle() {
+        let a = LLHDegrees::new(45.0, 7.0, 300.0).to_ecef();
+        let b_true = a + ECEF::new(1000.0, 0.0, 0.0);
+        let c_true = a + ECEF::new(0.0, 2000.0, 0.0);
+
+        let mut network = Network::new();
+        let a_id = network.add_fixed_station("A", a);
+        let b_id = network.add_station("B", b_true + ECEF::new(3.0, -2.0, 1.0));
+        let c_id = network.add_station("C", c_true);
+        // The loop doesn't close by 6 mm along x
+        network.add_baseline(a_id, b_id, ECEF::new(1000.002, 0.0, 0.0), COVARIANCE);
+        network.add_baseline(b_id, c_id, ECEF::new(-1000.002, 2000.0, 0.0), COVARIANCE);
+        network.add_baseline(c_id, a_id, ECEF::new(-0.006, -2000.0, 0.0), COVARIANCE);
+        assert_eq!(network.find_station("C"), Some(c_id));
+        assert_eq!(network.station_name(b_id), Some("B"));
+
+        let solution = network.adjust().unwrap();
+        assert_eq!(solution.degrees_of_freedom(), 3);
+        assert_eq!(solution.position(a_id), a);
+        // The misclosure is spread equally over the three baselines
+        let b = solution.position(b_id) - b_true;
+        let c = solution.position(c_id) - c_true;
+        assert_float_eq!(b.as_array_ref(), &[0.004, 0.0, 0.0], abs_all <= 1e-6);
+        assert_float_eq!(c.as_array_ref(), &[0.004, 0.0, 0.0], abs_all <= 1e-6);
+        for residual in solution.residuals() {
+            assert_float_eq!(residual.x(), 0.002, abs <= 1e-6);
+        }
+        assert_float_eq!(
+            solution.variance_factor(),
+            3.0 * 4e-6 / 1e-4 / 3.0,
+            abs <= 1e-9
+        );
+        // Each free station is tied to A by two paths of independent baselines
+        let covariance = solution.covariance(b_id);
+        assert_float_eq!(covariance[0][0], 1e-4 * 2.0 / 3.0, abs <= 1e-12);
+        assert_eq!(solution.covariance(a_id), [[0.0; 3]; 3]);
+    }
+
+    #[test]
+    fn errors_and_local_baselines() {
+        let a = LLHDegrees::new(-33.0, 151.0, 20.0).to_ecef();
+        let mut network = Network::new();
+        let b_id = network.add_station("B", a);
+        assert_eq!(network.adjust(), Err(NetworkError::NoFixedStation));
+
+        let a_id = network.add_fixed_station("A", a);
+        let c_id = network.add_station("C", a);
+        network
+            .add_ned_baseline(a_id, b_id, NED::new(30.0, 40.0, -5.0), COVARIANCE)
+            .unwrap();
+        assert_eq!(network.adjust(), Err(NetworkError::Underdetermined));
+
+        network
+            .add_ned_baseline(b_id, c_id, NED::new(-30.0, -40.0, 5.0), COVARIANCE)
+            .unwrap();
+        let solution = network.adjust().unwrap();
+        assert_eq!(solution.degrees_of_freedom(), 0);
+        let b = (solution.position(b_id) - a).ned_vector_at(&a);
+        assert_float_eq!(b.as_array_ref(), &[30.0, 40.0, -5.0], abs_all <= 1e-6);
+        // Local level covariances are rotated, so isotropic ones are unchanged
+        assert_float_eq!(solution.covariance(b_id)[2][2], 1e-4, abs <= 1e-12);
+        assert_float_eq!(solution.covariance(c_id)[0][0], 2e-4, abs <= 1e-12);
+
+        let mut unknown = network.clone();
+        unknown.add_baseline(a_id, StationId(10), ECEF::default(), COVARIANCE);
+        assert_eq!(
+            unknown.adjust(),
+            Err(NetworkError::UnknownStation(StationId(10)))
+        );
+        network.add_baseline(a_id, c_id, ECEF::default(), [[0.0; 3]; 3]);
+        assert_eq!(network.adjust(), Err(NetworkError::SingularCovariance(2)));
+    }
+}
@@ -0,0 +1,240 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Sanity checks for coordinates
+//!
+//! Positions coming from files, network streams or other programs can be
+//! corrupt, e.g. latitudes given in radians to a degrees field, swapped
+//! axes, or uninitialized values. These checks catch values which can't be
+//! a position on or near the surface of the Earth, so they can be rejected
+//! before being used.
+//!
+//! The checks are deliberately loose, a position passing them isn't
+//! necessarily correct, only not obviously wrong.
+
+use std::error::Error;
+use std::fmt;
+
+use super::angle::{self, Degrees, InvalidAngle, Radians};
+use super::{Coordinate, LLHDegrees, LLHRadians, ECEF};
+use crate::time::{GpsTime, InvalidGpsTime};
+
+/// Lowest plausible height of a position above the ellipsoid, in meters
+///
+/// This is a bit below the bottom of the deepest ocean trench.
+pub const MIN_PLAUSIBLE_HEIGHT: f64 = -12_000.0;
+
+/// Highest plausible height of a position above the ellipsoid, in meters
+///
+/// This is the conventional edge of space, anything higher is not a position
+/// on Earth.
+pub const MAX_PLAUSIBLE_HEIGHT: f64 = 100_000.0;
+
+/// Largest plausible velocity of a coordinate, in meters per year
+///
+/// Coordinate velocities model the movement of the tectonic plates, which is
+/// at most a few tens of centimeters per year.
+pub const MAX_PLAUSIBLE_VELOCITY: f64 = 1.0;
+
+/// Reasons a coordinate failed validation
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub enum InvalidCoordinate {
+    /// A component of the position is NaN or infinite
+    NotFinite,
+    /// The latitude or longitude is out of range
+    Angle(InvalidAngle),
+    /// The height is outside of the plausible range, with the height in
+    /// meters returned
+    Height(f64),
+    /// The velocity is not finite or is implausibly large, with its magnitude
+    /// in meters per year returned
+    Velocity(f64),
+    /// The epoch is not a valid GPS time
+    Epoch(InvalidGpsTime),
+}
+
+impl fmt::Display for InvalidCoordinate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidCoordinate::NotFinite => write!(f, "Position is not finite"),
+            InvalidCoordinate::Angle(angle) => write!(f, "{}", angle),
+            InvalidCoordinate::Height(height) => write!(
+                f,
+                "Height of {} m is outside of [{}, {}] m",
+                height, MIN_PLAUSIBLE_HEIGHT, MAX_PLAUSIBLE_HEIGHT
+            ),
+            InvalidCoordinate::Velocity(speed) => write!(
+                f,
+                "Velocity of {} m/yr is over {} m/yr",
+                speed, MAX_PLAUSIBLE_VELOCITY
+            ),
+            InvalidCoordinate::Epoch(epoch) => write!(f, "Invalid epoch, {}", epoch),
+        }
+    }
+}
+
+impl Error for InvalidCoordinate {}
+
+impl From<InvalidAngle> for InvalidCoordinate {
+    fn from(angle: InvalidAngle) -> InvalidCoordinate {
+        InvalidCoordinate::Angle(angle)
+    }
+}
+
+fn check_finite(values: &[f64; 3]) -> Result<(), InvalidCoordinate> {
+    if values.iter().all(|v| v.is_finite()) {
+        Ok(())
+    } else {
+        Err(InvalidCoordinate::NotFinite)
+    }
+}
+
+fn check_height(height: f64) -> Result<(), InvalidCoordinate> {
+    if (MIN_PLAUSIBLE_HEIGHT..=MAX_PLAUSIBLE_HEIGHT).contains(&height) {
+        Ok(())
+    } else {
+        Err(InvalidCoordinate::Height(height))
+    }
+}
+
+impl LLHDegrees {
+    /// Checks the position is finite, the latitude within [-90°, 90°], the
+    /// longitude within [-180°, 180°], and the height within
+    /// [`MIN_PLAUSIBLE_HEIGHT`] and [`MAX_PLAUSIBLE_HEIGHT`]
+    pub fn validate(&self) -> Result<(), InvalidCoordinate> {
+        check_finite(self.as_array_ref())?;
+        angle::check_latitude(Degrees::new(self.latitude()))?;
+        angle::check_longitude(Degrees::new(self.longitude()))?;
+        check_height(self.height())
+    }
+}
+
+impl LLHRadians {
+    /// Checks the position is finite, the latitude within [-π/2, π/2], the
+    /// longitude within [-π, π], and the height within
+    /// [`MIN_PLAUSIBLE_HEIGHT`] and [`MAX_PLAUSIBLE_HEIGHT`]
+    pub fn validate(&self) -> Result<(), InvalidCoordinate> {
+        check_finite(self.as_array_ref())?;
+        angle::check_latitude(Radians::new(self.latitude()))?;
+        angle::check_longitude(Radians::new(self.longitude()))?;
+        check_height(self.height())
+    }
+}
+
+impl ECEF {
+    /// Checks if the position is finite and its height above the WGS84
+    /// ellipsoid is within [`MIN_PLAUSIBLE_HEIGHT`] and
+    /// [`MAX_PLAUSIBLE_HEIGHT`]
+    pub fn is_plausible_earth_position(&self) -> bool {
+        check_finite(self.as_array_ref()).is_ok() && check_height(self.to_llh().height()).is_ok()
+    }
+}
+
+impl Coordinate {
+    /// Checks the coordinate is a plausible position on Earth
+    ///
+    /// The position must be finite with a height above the ellipsoid of the
+    /// reference frame within [`MIN_PLAUSIBLE_HEIGHT`] and
+    /// [`MAX_PLAUSIBLE_HEIGHT`], the velocity, if any, must be finite and
+    /// slower than [`MAX_PLAUSIBLE_VELOCITY`], and the epoch must be a valid
+    /// GPS time.
+    pub fn validate(&self) -> Result<(), InvalidCoordinate> {
+        check_finite(self.position().as_array_ref())?;
+        check_height(self.llh().height())?;
+        if let Some(velocity) = self.velocity() {
+            let speed = velocity
+                .as_array_ref()
+                .iter()
+                .map(|v| v * v)
+                .sum::<f64>()
+                .sqrt();
+            if !speed.is_finite() || speed > MAX_PLAUSIBLE_VELOCITY {
+                return Err(InvalidCoordinate::Velocity(speed));
+            }
+        }
+        let epoch = self.epoch();
+        GpsTime::new(epoch.wn(), epoch.tow()).map_err(InvalidCoordinate::Epoch)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reference_frame::ReferenceFrame;
+
+    #[test]
+    fn geodetic() {
+        assert_eq!(LLHDegrees::new(37.5, -122.1, 20.0).validate(), Ok(()));
+        assert_eq!(
+            LLHDegrees::new(95.0, 0.0, 0.0).validate(),
+            Err(InvalidCoordinate::Angle(InvalidAngle::Latitude(95.0)))
+        );
+        assert_eq!(
+            LLHDegrees::new(0.0, 200.0, 0.0).validate(),
+            Err(InvalidCoordinate::Angle(InvalidAngle::Longitude(200.0)))
+        );
+        assert_eq!(
+            LLHDegrees::new(0.0, 0.0, 6.4e6).validate(),
+            Err(InvalidCoordinate::Height(6.4e6))
+        );
+        assert_eq!(
+            LLHDegrees::new(f64::NAN, 0.0, 0.0).validate(),
+            Err(InvalidCoordinate::NotFinite)
+        );
+        assert!(matches!(
+            LLHRadians::new(0.0, 4.0, 0.0).validate(),
+            Err(InvalidCoordinate::Angle(InvalidAngle::Longitude(_)))
+        ));
+
+        let ecef = LLHDegrees::new(-33.9, 151.2, 50.0).to_ecef();
+        assert!(ecef.is_plausible_earth_position());
+        assert!(!ECEF::new(0.0, 0.0, 0.0).is_plausible_earth_position());
+        assert!(!(2.0 * ecef).is_plausible_earth_position());
+        assert!(!ECEF::new(f64::INFINITY, 0.0, 0.0).is_plausible_earth_position());
+    }
+
+    #[test]
+    fn coordinate() {
+        let position = LLHDegrees::new(37.5, -122.1, 20.0).to_ecef();
+        let epoch = GpsTime::new(2200, 0.0).unwrap();
+        let coord = Coordinate::with_velocity(
+            ReferenceFrame::ITRF2014,
+            position,
+            ECEF::new(0.01, -0.02, 0.005),
+            epoch,
+        );
+        assert_eq!(coord.validate(), Ok(()));
+
+        let coord = Coordinate::with_velocity(
+            ReferenceFrame::ITRF2014,
+            position,
+            ECEF::new(3.0, 0.0, 4.0),
+            epoch,
+        );
+        assert_eq!(coord.validate(), Err(InvalidCoordinate::Velocity(5.0)));
+
+        let coord =
+            Coordinate::without_velocity(ReferenceFrame::ITRF2014, ECEF::new(1.0, 2.0, 3.0), epoch);
+        assert!(matches!(
+            coord.validate(),
+            Err(InvalidCoordinate::Height(_))
+        ));
+
+        let coord = Coordinate::without_velocity(
+            ReferenceFrame::ITRF2014,
+            position,
+            GpsTime::new_unchecked(2200, -5.0),
+        );
+        assert_eq!(
+            coord.validate(),
+            Err(InvalidCoordinate::Epoch(InvalidGpsTime::InvalidTOW(-5.0)))
+        );
+    }
+}
@@ -0,0 +1,571 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Satellite coverage evaluation
+//!
+//! Evaluates the number of visible satellites and the dilution of precision
+//! over a grid of locations, either at a single epoch or over a span of time.
+//! This is the kind of evaluation used to produce service coverage maps, e.g.
+//! for SBAS or regional augmentation systems.
+//!
+//! The satellite orbits can come from any [`Orbit`], such as broadcast
+//! [ephemerides](crate::ephemeris::Ephemeris) or
+//! [almanacs](crate::ephemeris::almanac::Almanac). Satellites whose orbit
+//! can't be used at an epoch are considered not visible at that epoch. The resulting [`CoverageMap`] can be exported as
+//! GeoJSON for display in GIS tools.
+
+use crate::coords::{LLHDegrees, ECEF};
+use crate::solver::Dops;
+use crate::time::GpsTime;
+use crate::visibility::Orbit;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Invalid grid definition
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct InvalidGrid(&'static str);
+
+impl fmt::Display for InvalidGrid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid grid: {}", self.0)
+    }
+}
+
+impl Error for InvalidGrid {}
+
+/// A regular latitude/longitude grid of evaluation points
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct Grid {
+    south: f64,
+    north: f64,
+    west: f64,
+    east: f64,
+    step: f64,
+    height: f64,
+}
+
+impl Grid {
+    /// Makes a new grid spanning the given bounds, in degrees, with a constant
+    /// spacing between points, also in degrees
+    ///
+    /// The points are all at a height of zero above the ellipsoid, use
+    /// [`Grid::set_height()`] to change this.
+    pub fn new(
+        south: f64,
+        north: f64,
+        west: f64,
+        east: f64,
+        step: f64,
+    ) -> Result<Grid, InvalidGrid> {
+        if !(step.is_finite() && step > 0.0) {
+            return Err(InvalidGrid("step must be positive"));
+        }
+        if !(-90.0..=90.0).contains(&south) || !(-90.0..=90.0).contains(&north) {
+            return Err(InvalidGrid("latitude out of range"));
+        }
+        if !(-180.0..=180.0).contains(&west) || !(-180.0..=180.0).contains(&east) {
+            return Err(InvalidGrid("longitude out of range"));
+        }
+        if south > north || west > east {
+            return Err(InvalidGrid("bounds are reversed"));
+        }
+
+        Ok(Grid {
+            south,
+            north,
+            west,
+            east,
+            step,
+            height: 0.0,
+        })
+    }
+
+    /// Sets the height of the evaluation points above the ellipsoid, in meters
+    pub fn set_height(self, height: f64) -> Grid {
+        Grid { height, ..self }
+    }
+
+    /// Gets the number of points along the latitude axis
+    pub fn rows(&self) -> usize {
+        ((self.north - self.south) / self.step + 1e-9).floor() as usize + 1
+    }
+
+    /// Gets the number of points along the longitude axis
+    pub fn columns(&self) -> usize {
+        ((self.east - self.west) / self.step + 1e-9).floor() as usize + 1
+    }
+
+    /// Gets all the points of the grid, row by row starting from the south
+    /// west corner
+    pub fn points(&self) -> Vec<LLHDegrees> {
+        let mut points = Vec::with_capacity(self.rows() * self.columns());
+        for row in 0..self.rows() {
+            let lat = self.south + row as f64 * self.step;
+            for column in 0..self.columns() {
+                let lon = self.west + column as f64 * self.step;
+                points.push(LLHDegrees::new(lat, lon, self.height));
+            }
+        }
+        points
+    }
+}
+
+/// Settings for the coverage evaluation
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CoverageSettings {
+    elevation_mask: f64,
+    max_pdop: f64,
+}
+
+impl CoverageSettings {
+    /// Makes the default settings
+    ///
+    /// The default elevation mask is 5 degrees and positioning is considered
+    /// available when the PDOP is 6 or less.
+    pub fn new() -> CoverageSettings {
+        CoverageSettings {
+            elevation_mask: 5.0,
+            max_pdop: 6.0,
+        }
+    }
+
+    /// Sets the minimum elevation of visible satellites, in degrees
+    pub fn set_elevation_mask(self, elevation_mask: f64) -> CoverageSettings {
+        CoverageSettings {
+            elevation_mask,
+            ..self
+        }
+    }
+
+    /// Sets the largest PDOP for which positioning is considered available
+    pub fn set_max_pdop(self, max_pdop: f64) -> CoverageSettings {
+        CoverageSettings { max_pdop, ..self }
+    }
+
+    pub fn elevation_mask(&self) -> f64 {
+        self.elevation_mask
+    }
+
+    pub fn max_pdop(&self) -> f64 {
+        self.max_pdop
+    }
+}
+
+impl Default for CoverageSettings {
+    fn default() -> CoverageSettings {
+        CoverageSettings::new()
+    }
+}
+
+/// Coverage statistics of a single grid point
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct CoverageCell {
+    location: LLHDegrees,
+    ecef: ECEF,
+    min_satellites: usize,
+    max_satellites: usize,
+    worst_dops: Option<(f64, f64, f64)>,
+    available_epochs: usize,
+    solvable_epochs: usize,
+}
+
+impl CoverageCell {
+    fn new(location: LLHDegrees) -> CoverageCell {
+        CoverageCell {
+            location,
+            ecef: location.to_ecef(),
+            min_satellites: usize::MAX,
+            max_satellites: 0,
+            worst_dops: None,
+            available_epochs: 0,
+            solvable_epochs: 0,
+        }
+    }
+
+    pub fn location(&self) -> LLHDegrees {
+        self.location
+    }
+
+    /// Gets the smallest number of visible satellites over all epochs
+    pub fn min_satellites(&self) -> usize {
+        self.min_satellites.min(self.max_satellites)
+    }
+
+    /// Gets the largest number of visible satellites over all epochs
+    pub fn max_satellites(&self) -> usize {
+        self.max_satellites
+    }
+
+    /// Gets the largest PDOP over all epochs
+    ///
+    /// Returns `None` if the DOP couldn't be computed at one or more epochs
+    pub fn worst_pdop(&self) -> Option<f64> {
+        self.worst_dops.map(|dops| dops.0)
+    }
+
+    /// Gets the largest HDOP over all epochs
+    ///
+    /// Returns `None` if the DOP couldn't be computed at one or more epochs
+    pub fn worst_hdop(&self) -> Option<f64> {
+        self.worst_dops.map(|dops| dops.1)
+    }
+
+    /// Gets the largest VDOP over all epochs
+    ///
+    /// Returns `None` if the DOP couldn't be computed at one or more epochs
+    pub fn worst_vdop(&self) -> Option<f64> {
+        self.worst_dops.map(|dops| dops.2)
+    }
+
+    /// Gets the number of epochs at which the PDOP was within the limit
+    pub fn available_epochs(&self) -> usize {
+        self.available_epochs
+    }
+
+    fn add_epoch(&mut self, settings: &CoverageSettings, satellites: &[ECEF]) {
+        let mask = settings.elevation_mask.to_radians();
+        let visible: Vec<ECEF> = satellites
+            .iter()
+            .filter(|sat| self.ecef.azel_of(sat).el >= mask)
+            .copied()
+            .collect();
+
+        let first = self.min_satellites == usize::MAX;
+        self.min_satellites = self.min_satellites.min(visible.len());
+        self.max_satellites = self.max_satellites.max(visible.len());
+
+        match Dops::from_geometry(&self.ecef, &visible) {
+            Some(dops) => {
+                if dops.pdop() <= settings.max_pdop {
+                    self.available_epochs += 1;
+                }
+                let current = (dops.pdop(), dops.hdop(), dops.vdop());
+                if first {
+                    self.worst_dops = Some(current);
+                } else if let Some(worst) = self.worst_dops.as_mut() {
+                    worst.0 = worst.0.max(current.0);
+                    worst.1 = worst.1.max(current.1);
+                    worst.2 = worst.2.max(current.2);
+                }
+                self.solvable_epochs += 1;
+            }
+            None => self.worst_dops = None,
+        }
+    }
+}
+
+/// Coverage statistics over a grid
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub struct CoverageMap {
+    grid: Grid,
+    settings: CoverageSettings,
+    cells: Vec<CoverageCell>,
+    epochs: usize,
+}
+
+impl CoverageMap {
+    /// Makes an empty coverage map, with no epochs evaluated
+    pub fn new(grid: Grid, settings: CoverageSettings) -> CoverageMap {
+        CoverageMap {
+            grid,
+            settings,
+            cells: grid.points().into_iter().map(CoverageCell::new).collect(),
+            epochs: 0,
+        }
+    }
+
+    /// Evaluates the coverage at a single epoch
+    pub fn evaluate<O: Orbit>(
+        grid: Grid,
+        settings: CoverageSettings,
+        orbits: &[O],
+        t: GpsTime,
+    ) -> CoverageMap {
+        let mut map = CoverageMap::new(grid, settings);
+        map.add_epoch_from_orbits(orbits, t);
+        map
+    }
+
+    /// Evaluates the coverage over a span of time, `start` and `end` included
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `step` is zero
+    pub fn evaluate_range<O: Orbit>(
+        grid: Grid,
+        settings: CoverageSettings,
+        orbits: &[O],
+        start: GpsTime,
+        end: GpsTime,
+        step: Duration,
+    ) -> CoverageMap {
+        assert!(step > Duration::ZERO, "Time step must be larger than zero");
+
+        let mut map = CoverageMap::new(grid, settings);
+        let mut t = start;
+        while t <= end {
+            map.add_epoch_from_orbits(orbits, t);
+            t += step;
+        }
+        map
+    }
+
+    /// Adds an epoch to the statistics, with the satellite positions computed
+    /// from the orbits
+    ///
+    /// When several orbits are given for the same satellite the first one
+    /// usable at `t` is used.
+    pub fn add_epoch_from_orbits<O: Orbit>(&mut self, orbits: &[O], t: GpsTime) {
+        let mut seen = HashSet::new();
+        let mut satellites = Vec::new();
+        for orbit in orbits {
+            let sid = match orbit.sid() {
+                Some(sid) => sid,
+                None => continue,
+            };
+            if seen.contains(&(sid.to_constellation(), sid.sat())) {
+                continue;
+            }
+            if let Some(pos) = orbit.position(t) {
+                seen.insert((sid.to_constellation(), sid.sat()));
+                satellites.push(pos);
+            }
+        }
+        self.add_epoch(&satellites);
+    }
+
+    /// Adds an epoch to the statistics given the satellite positions in ECEF
+    pub fn add_epoch(&mut self, satellites: &[ECEF]) {
+        for cell in &mut self.cells {
+            cell.add_epoch(&self.settings, satellites);
+        }
+        self.epochs += 1;
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    pub fn settings(&self) -> &CoverageSettings {
+        &self.settings
+    }
+
+    /// Gets the number of evaluated epochs
+    pub fn epochs(&self) -> usize {
+        self.epochs
+    }
+
+    /// Gets the statistics of all the grid points, in the same order as
+    /// [`Grid::points()`]
+    pub fn cells(&self) -> &[CoverageCell] {
+        &self.cells
+    }
+
+    /// Gets the fraction of epochs at which positioning was available at a
+    /// grid point
+    pub fn availability(&self, cell: &CoverageCell) -> f64 {
+        if self.epochs == 0 {
+            0.0
+        } else {
+            cell.available_epochs as f64 / self.epochs as f64
+        }
+    }
+
+    /// Exports the map as a GeoJSON feature collection
+    ///
+    /// Each grid point is a `Point` feature with the coverage statistics as
+    /// its properties. DOPs which couldn't be computed are given as `null`.
+    pub fn to_geojson(&self) -> String {
+        fn number(value: Option<f64>) -> String {
+            match value {
+                Some(value) if value.is_finite() => format!("{}", value),
+                _ => "null".to_string(),
+            }
+        }
+
+        let mut json = String::from("{\"type\":\"FeatureCollection\",\"features\":[");
+        for (i, cell) in self.cells.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            // Writing to a String never fails
+            let _ = write!(
+                json,
+                "{{\"type\":\"Feature\",\"geometry\":{{\"type\":\"Point\",\"coordinates\":[{},{}]}},\
+                \"properties\":{{\"min_satellites\":{},\"max_satellites\":{},\"pdop\":{},\"hdop\":{},\
+                \"vdop\":{},\"availability\":{}}}}}",
+                cell.location.longitude(),
+                cell.location.latitude(),
+                cell.min_satellites(),
+                cell.max_satellites(),
+                number(cell.worst_pdop()),
+                number(cell.worst_hdop()),
+                number(cell.worst_vdop()),
+                self.availability(cell),
+            );
+        }
+        json.push_str("]}");
+        json
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::{Code, GnssSignal};
+    use float_eq::assert_float_eq;
+
+    /// Satellites spread over the sky of a point on the equator at 0 longitude
+    fn equator_satellites() -> Vec<ECEF> {
+        let receiver = LLHDegrees::new(0.0, 0.0, 0.0).to_ecef();
+        let range = 20_000_000.0;
+        [
+            (0.0, 90.0),
+            (0.0, 30.0),
+            (90.0, 30.0),
+            (180.0, 30.0),
+            (270.0, 30.0),
+            (45.0, -20.0),
+        ]
+        .iter()
+        .map(|&(az, el): &(f64, f64)| {
+            let (az, el) = (az.to_radians(), el.to_radians());
+            let ned = crate::coords::NED::new(
+                range * el.cos() * az.cos(),
+                range * el.cos() * az.sin(),
+                -range * el.sin(),
+            );
+            receiver + ned.ecef_vector_at(&receiver)
+        })
+        .collect()
+    }
+
+    #[test]
+    fn grid_points() {
+        let grid = Grid::new(-1.0, 1.0, 10.0, 12.5, 0.5).unwrap();
+        assert_eq!(grid.rows(), 5);
+        assert_eq!(grid.columns(), 6);
+        let points = grid.points();
+        assert_eq!(points.len(), 30);
+        assert_float_eq!(points[0].latitude(), -1.0, abs <= 1e-12);
+        assert_float_eq!(points[0].longitude(), 10.0, abs <= 1e-12);
+        assert_float_eq!(points[29].latitude(), 1.0, abs <= 1e-12);
+        assert_float_eq!(points[29].longitude(), 12.5, abs <= 1e-12);
+
+        assert!(Grid::new(1.0, -1.0, 0.0, 1.0, 0.5).is_err());
+        assert!(Grid::new(-1.0, 1.0, 0.0, 1.0, 0.0).is_err());
+        assert!(Grid::new(-91.0, 1.0, 0.0, 1.0, 0.5).is_err());
+    }
+
+    #[test]
+    fn single_epoch() {
+        let grid = Grid::new(0.0, 0.0, 0.0, 0.0, 1.0).unwrap();
+        let mut map = CoverageMap::new(grid, CoverageSettings::new());
+        map.add_epoch(&equator_satellites());
+
+        assert_eq!(map.epochs(), 1);
+        let cell = &map.cells()[0];
+        // The satellite below the horizon is excluded
+        assert_eq!(cell.min_satellites(), 5);
+        assert_eq!(cell.max_satellites(), 5);
+        let pdop = cell.worst_pdop().unwrap();
+        let hdop = cell.worst_hdop().unwrap();
+        let vdop = cell.worst_vdop().unwrap();
+        assert_float_eq!(pdop * pdop, hdop * hdop + vdop * vdop, abs <= 1e-9);
+        assert!(pdop < 6.0);
+        assert_float_eq!(map.availability(cell), 1.0, abs <= 1e-12);
+
+        // Not enough satellites left above the mask for a solution
+        map.add_epoch(&equator_satellites()[..3]);
+        let cell = &map.cells()[0];
+        assert_eq!(cell.min_satellites(), 3);
+        assert_eq!(cell.max_satellites(), 5);
+        assert_eq!(cell.worst_pdop(), None);
+        assert_float_eq!(map.availability(cell), 0.5, abs <= 1e-12);
+    }
+
+    /// A satellite standing still, usable until a given time
+    struct FixedOrbit {
+        sid: GnssSignal,
+        pos: ECEF,
+        until: GpsTime,
+    }
+
+    impl Orbit for FixedOrbit {
+        fn sid(&self) -> Option<GnssSignal> {
+            Some(self.sid)
+        }
+
+        fn position(&self, t: GpsTime) -> Option<ECEF> {
+            if t <= self.until {
+                Some(self.pos)
+            } else {
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn orbits() {
+        let start = GpsTime::new(2100, 0.0).unwrap();
+        let step = Duration::from_secs(60);
+        let mut orbits: Vec<FixedOrbit> = equator_satellites()
+            .into_iter()
+            .enumerate()
+            .map(|(i, pos)| FixedOrbit {
+                sid: GnssSignal::new(i as u16 + 1, Code::GpsL1ca).unwrap(),
+                pos,
+                until: start + step,
+            })
+            .collect();
+        // The second orbit of the same satellite is ignored while the first one
+        // is usable
+        orbits.push(FixedOrbit {
+            sid: orbits[1].sid,
+            pos: orbits[5].pos,
+            until: start + step * 2,
+        });
+
+        let grid = Grid::new(0.0, 0.0, 0.0, 0.0, 1.0).unwrap();
+        let map = CoverageMap::evaluate(grid, CoverageSettings::new(), &orbits, start);
+        assert_eq!(map.cells()[0].max_satellites(), 5);
+
+        let map = CoverageMap::evaluate_range(
+            grid,
+            CoverageSettings::new(),
+            &orbits,
+            start,
+            start + step * 2,
+            step,
+        );
+        assert_eq!(map.epochs(), 3);
+        let cell = &map.cells()[0];
+        assert_eq!(cell.min_satellites(), 0);
+        assert_eq!(cell.max_satellites(), 5);
+        assert_float_eq!(map.availability(cell), 2.0 / 3.0, abs <= 1e-12);
+    }
+
+    #[test]
+    fn geojson() {
+        let grid = Grid::new(0.0, 0.0, 0.0, 1.0, 1.0).unwrap();
+        let mut map = CoverageMap::new(grid, CoverageSettings::new());
+        map.add_epoch(&equator_satellites()[..2]);
+
+        let json = map.to_geojson();
+        assert!(json.starts_with("{\"type\":\"FeatureCollection\",\"features\":[{"));
+        assert!(json.ends_with("}]}"));
+        assert_eq!(json.matches("\"type\":\"Feature\"").count(), 2);
+        assert!(json.contains("\"coordinates\":[1,0]"));
+        assert!(json.contains("\"pdop\":null"));
+    }
+}
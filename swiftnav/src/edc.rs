@@ -8,6 +8,27 @@
 // EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
 // WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
 //! Error detection code
+//!
+//! The checksums can either be computed over a whole buffer at once, or with
+//! the streaming [`Crc24q`] and [`Crc16Ccitt`] types, one chunk at a time.
+//! The streaming types implement [`Hasher`] and [`io::Write`], so a reader
+//! can be checksummed with [`io::copy`] without buffering all of it.
+//!
+//! The error correcting codes of the GPS LNAV, GLONASS and BeiDou D1/D2
+//! navigation messages are also implemented, so raw navigation message bits
+//! can be checked and corrected before being decoded. Signals which use
+//! forward error correction can be decoded from soft symbols with
+//! [`ConvolutionalCode`], and Galileo I/NAV page parts can be decoded with
+//! [`galileo_inav_decode_page_part()`].
+//!
+//! # References
+//!   * IS-GPS-200, Section 20.3.5
+//!   * GLONASS ICD, Edition 5.1, Section 4.7
+//!   * BeiDou ICD B1I, Version 3.0, Section 5.1.3
+//!   * Galileo OS SIS ICD, Issue 2.0, Sections 4.1 and 4.3
+
+use std::hash::Hasher;
+use std::io;
 
 /// Calculate Qualcomm 24-bit Cyclical Redundancy Check (CRC-24Q).
 ///
@@ -22,8 +43,598 @@ pub fn compute_crc24q(buf: &[u8], initial_value: u32) -> u32 {
     unsafe { swiftnav_sys::crc24q(buf.as_ptr(), buf.len() as u32, initial_value) }
 }
 
+/// Calculate the 16-bit CCITT Cyclical Redundancy Check (CRC-16/XMODEM).
+///
+/// This CRC is used with the Swift Binary Protocol (SBP)
+///
+/// The CRC polynomial used is:
+///   x^{16} + x^{12} + x^5 + 1
+///
+/// Mask 0x1021, not reversed, not XOR'd
+pub fn compute_crc16_ccitt(buf: &[u8], initial_value: u16) -> u16 {
+    buf.iter().fold(initial_value, |crc, byte| {
+        let mut crc = crc ^ (u16::from(*byte) << 8);
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// Calculate the CRC-24Q of a number of bits which needn't be a whole number
+/// of bytes
+///
+/// The bits are taken from the start of the buffer, MSB first. This is how
+/// the CRC is used by the Galileo I/NAV message, see
+/// [`galileo_inav_crc_check()`].
+pub fn compute_crc24q_bits(buf: &[u8], num_bits: usize, initial_value: u32) -> u32 {
+    (0..num_bits).fold(initial_value & 0xFF_FFFF, |crc, i| {
+        let bit = u32::from(buf[i / 8] >> (7 - i % 8)) & 1;
+        let crc = crc ^ (bit << 23);
+        if crc & 0x80_0000 != 0 {
+            ((crc << 1) ^ 0x86_4CFB) & 0xFF_FFFF
+        } else {
+            crc << 1
+        }
+    })
+}
+
+/// Streaming CRC-24Q, see [`compute_crc24q`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Crc24q {
+    crc: u32,
+}
+
+impl Crc24q {
+    /// Starts a CRC with an initial value of zero, as used by RTCM
+    pub fn new() -> Crc24q {
+        Crc24q::with_initial_value(0)
+    }
+
+    pub fn with_initial_value(initial_value: u32) -> Crc24q {
+        Crc24q { crc: initial_value }
+    }
+
+    /// Adds the next chunk of data to the CRC
+    pub fn update(&mut self, buf: &[u8]) {
+        self.crc = compute_crc24q(buf, self.crc);
+    }
+
+    /// Gets the CRC of all of the data so far
+    pub fn finalize(&self) -> u32 {
+        self.crc
+    }
+}
+
+impl Default for Crc24q {
+    fn default() -> Crc24q {
+        Crc24q::new()
+    }
+}
+
+impl Hasher for Crc24q {
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        u64::from(self.finalize())
+    }
+}
+
+impl io::Write for Crc24q {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Streaming CRC-16/XMODEM, see [`compute_crc16_ccitt`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Crc16Ccitt {
+    crc: u16,
+}
+
+impl Crc16Ccitt {
+    /// Starts a CRC with an initial value of zero, as used by SBP
+    pub fn new() -> Crc16Ccitt {
+        Crc16Ccitt::with_initial_value(0)
+    }
+
+    pub fn with_initial_value(initial_value: u16) -> Crc16Ccitt {
+        Crc16Ccitt { crc: initial_value }
+    }
+
+    /// Adds the next chunk of data to the CRC
+    pub fn update(&mut self, buf: &[u8]) {
+        self.crc = compute_crc16_ccitt(buf, self.crc);
+    }
+
+    /// Gets the CRC of all of the data so far
+    pub fn finalize(&self) -> u16 {
+        self.crc
+    }
+}
+
+impl Default for Crc16Ccitt {
+    fn default() -> Crc16Ccitt {
+        Crc16Ccitt::new()
+    }
+}
+
+impl Hasher for Crc16Ccitt {
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        u64::from(self.finalize())
+    }
+}
+
+impl io::Write for Crc16Ccitt {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Source data bits, numbered from 1 at the MSB, in each GPS LNAV parity
+/// bit, along with whether D29* (`true`) or D30* (`false`) of the previous
+/// word is included
+const GPS_PARITY_BITS: [(bool, &[u32]); 6] = [
+    (true, &[1, 2, 3, 5, 6, 10, 11, 12, 13, 14, 17, 18, 20, 23]),
+    (false, &[2, 3, 4, 6, 7, 11, 12, 13, 14, 15, 18, 19, 21, 24]),
+    (true, &[1, 3, 4, 5, 7, 8, 12, 13, 14, 15, 16, 19, 20, 22]),
+    (false, &[2, 4, 5, 6, 8, 9, 13, 14, 15, 16, 17, 20, 21, 23]),
+    (
+        false,
+        &[1, 3, 5, 6, 7, 9, 10, 14, 15, 16, 17, 18, 21, 22, 24],
+    ),
+    (true, &[3, 5, 6, 8, 9, 10, 11, 13, 15, 19, 22, 23, 24]),
+];
+
+/// Computes the six parity bits of a GPS LNAV word
+///
+/// `data` is the 24 source data bits, in the 24 LSBs. `previous` is the
+/// previous word as transmitted, only its two LSBs (D29* and D30*) are used.
+pub fn gps_lnav_parity(data: u32, previous: u32) -> u32 {
+    let (d29, d30) = ((previous >> 1) & 1, previous & 1);
+    GPS_PARITY_BITS.iter().fold(0, |parity, (uses_d29, bits)| {
+        let bit = bits
+            .iter()
+            .fold(if *uses_d29 { d29 } else { d30 }, |bit, i| {
+                bit ^ ((data >> (24 - i)) & 1)
+            });
+        (parity << 1) | bit
+    })
+}
+
+/// Makes a GPS LNAV word as it is transmitted from 24 source data bits
+///
+/// The data bits are complemented when D30* of the previous word is set.
+pub fn gps_lnav_encode(data: u32, previous: u32) -> u32 {
+    let data = data & 0xFF_FFFF;
+    let transmitted = if previous & 1 != 0 {
+        !data & 0xFF_FFFF
+    } else {
+        data
+    };
+    (transmitted << 6) | gps_lnav_parity(data, previous)
+}
+
+/// Checks the parity of a GPS LNAV word as it was transmitted
+///
+/// The word is in the 30 LSBs, as is the previous word.
+pub fn gps_lnav_check(word: u32, previous: u32) -> bool {
+    gps_lnav_encode(gps_lnav_source_data(word, previous), previous) == word & 0x3FFF_FFFF
+}
+
+/// Checks the parity of a GPS LNAV word as it was transmitted, correcting
+/// a single bit error
+///
+/// Returns the word with the source data bits, i.e. no longer complemented
+/// when D30* of the previous word is set, followed by the parity bits. This
+/// is the form of the words given to [`Ephemeris::decode_gps()`]. `None` is
+/// returned if there is more than one bit error.
+///
+/// [`Ephemeris::decode_gps()`]: crate::ephemeris::Ephemeris::decode_gps
+pub fn gps_lnav_correct(word: u32, previous: u32) -> Option<u32> {
+    let word = word & 0x3FFF_FFFF;
+    let corrected = std::iter::once(word)
+        .chain((0..30).map(|bit| word ^ (1 << bit)))
+        .find(|candidate| gps_lnav_check(*candidate, previous))?;
+    Some((gps_lnav_source_data(corrected, previous) << 6) | (corrected & 0x3F))
+}
+
+fn gps_lnav_source_data(word: u32, previous: u32) -> u32 {
+    let data = (word >> 6) & 0xFF_FFFF;
+    if previous & 1 != 0 {
+        !data & 0xFF_FFFF
+    } else {
+        data
+    }
+}
+
+/// Bits 9 to 85 of a GLONASS string, which hold the data
+const GLO_DATA_BITS: u128 = ((1 << 85) - 1) & !0xFF;
+
+/// Masks of the data bits checked by each of the GLONASS check bits β1 to β7
+///
+/// The data bits are the bits of a Hamming code which aren't at power of two
+/// positions, β_j checks the bits whose position has bit j - 1 set.
+const GLO_CHECK_MASKS: [u128; 7] = glonass_check_masks();
+
+const fn glonass_check_masks() -> [u128; 7] {
+    let mut masks = [0; 7];
+    let mut position: u32 = 1;
+    let mut bit = 9;
+    while bit <= 85 {
+        position += 1;
+        if position.is_power_of_two() {
+            continue;
+        }
+        let mut j = 0;
+        while j < 7 {
+            if position & (1 << j) != 0 {
+                masks[j] |= 1 << (bit - 1);
+            }
+            j += 1;
+        }
+        bit += 1;
+    }
+    masks
+}
+
+fn parity_u128(bits: u128) -> u128 {
+    u128::from(bits.count_ones() & 1)
+}
+
+/// Fills in the check bits (KX) of a GLONASS string
+///
+/// Bit `i` of the string, numbered from 1 to 85 as in the ICD, is at
+/// `1 << (i - 1)`. The check bits β1 to β8 are bits 1 to 8.
+pub fn glonass_string_encode(string: u128) -> u128 {
+    let data = string & GLO_DATA_BITS;
+    let check = GLO_CHECK_MASKS
+        .iter()
+        .enumerate()
+        .fold(0, |check, (j, mask)| {
+            check | (parity_u128(data & mask) << j)
+        });
+    let check = check | (parity_u128(data | check) << 7);
+    data | check
+}
+
+/// Checks a GLONASS string with its check bits, correcting a single bit
+/// error in the data bits
+///
+/// See [`glonass_string_encode()`] for the numbering of the bits. Returns the
+/// corrected string, or `None` if there is more than one bit error. As in the
+/// ICD, an error in a single check bit is not corrected, since the data is
+/// still correct.
+pub fn glonass_string_correct(string: u128) -> Option<u128> {
+    let string = string & ((1 << 85) - 1);
+    let syndrome = GLO_CHECK_MASKS
+        .iter()
+        .enumerate()
+        .fold(0u32, |syndrome, (j, mask)| {
+            let c = parity_u128(string & mask) ^ ((string >> j) & 1);
+            syndrome | ((c as u32) << j)
+        });
+    let sum = parity_u128(string) == 1;
+    match (syndrome.count_ones(), sum) {
+        // Either no error, or an error in one of the check bits
+        (0, false) | (1, true) => Some(string),
+        (n, true) if n >= 2 => {
+            // The number of the most significant non-zero check sum
+            let k = 32 - syndrome.leading_zeros();
+            let bit = syndrome + 8 - k;
+            if bit <= 85 {
+                Some(string ^ (1 << (bit - 1)))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Checks that a GLONASS string has no bit errors, including in the check
+/// bits
+///
+/// See [`glonass_string_encode()`] for the numbering of the bits.
+pub fn glonass_string_check(string: u128) -> bool {
+    let string = string & ((1 << 85) - 1);
+    glonass_string_encode(string) == string
+}
+
+/// Generator polynomial of the BCH(15,11,1) code, x^4 + x + 1
+const BCH_GENERATOR: u16 = 0b1_0011;
+
+/// Remainder of a 15 bit codeword divided by the generator polynomial
+fn bch_15_11_remainder(codeword: u16) -> u16 {
+    (4..15).rev().fold(codeword & 0x7FFF, |remainder, bit| {
+        if remainder & (1 << bit) != 0 {
+            remainder ^ (BCH_GENERATOR << (bit - 4))
+        } else {
+            remainder
+        }
+    })
+}
+
+/// Makes a BCH(15,11,1) codeword from 11 data bits
+///
+/// The data bits are followed by the 4 parity bits in the codeword.
+pub fn bch_15_11_encode(data: u16) -> u16 {
+    let shifted = (data & 0x7FF) << 4;
+    shifted | bch_15_11_remainder(shifted)
+}
+
+/// Corrects a single bit error in a BCH(15,11,1) codeword
+///
+/// Any codeword with an error is corrected to the nearest valid codeword,
+/// so errors of more than one bit aren't detected.
+pub fn bch_15_11_correct(codeword: u16) -> u16 {
+    let codeword = codeword & 0x7FFF;
+    match bch_15_11_remainder(codeword) {
+        0 => codeword,
+        syndrome => (0..15)
+            .map(|bit| 1 << bit)
+            .find(|error| bch_15_11_remainder(*error) == syndrome)
+            .map_or(codeword, |error| codeword ^ error),
+    }
+}
+
+/// Corrects the BCH(15,11,1) codewords of a BeiDou D1/D2 navigation message
+/// word
+///
+/// The word is in the 30 LSBs. The first word of a subframe has 15 unencoded
+/// bits followed by a single codeword. The other words are expected to have
+/// been deinterleaved with [`bds_deinterleave()`], giving the data bits of
+/// both codewords followed by the parity bits of both. This is the form of
+/// the words given to [`Ephemeris::decode_bds()`].
+///
+/// [`Ephemeris::decode_bds()`]: crate::ephemeris::Ephemeris::decode_bds
+pub fn bds_word_correct(word: u32, first_word: bool) -> u32 {
+    if first_word {
+        let codeword = bch_15_11_correct((word & 0x7FFF) as u16);
+        return (word & 0x3FFF_8000) | u32::from(codeword);
+    }
+    let first = bch_15_11_correct(((((word >> 19) & 0x7FF) << 4) | ((word >> 4) & 0xF)) as u16);
+    let second = bch_15_11_correct(((((word >> 8) & 0x7FF) << 4) | (word & 0xF)) as u16);
+    let (first, second) = (u32::from(first), u32::from(second));
+    ((first >> 4) << 19) | ((second >> 4) << 8) | ((first & 0xF) << 4) | (second & 0xF)
+}
+
+/// Checks the BCH(15,11,1) codewords of a BeiDou D1/D2 navigation message
+/// word, see [`bds_word_correct()`]
+pub fn bds_word_check(word: u32, first_word: bool) -> bool {
+    bds_word_correct(word, first_word) == word & 0x3FFF_FFFF
+}
+
+/// Separates the two interleaved codewords of a BeiDou D1/D2 navigation
+/// message word, other than the first word of a subframe
+///
+/// The bits of the two codewords alternate in the transmitted word. The
+/// data bits of both codewords are moved to the front, followed by the
+/// parity bits of both.
+pub fn bds_deinterleave(word: u32) -> u32 {
+    let (mut first, mut second) = (0, 0);
+    for i in 0..15 {
+        first = (first << 1) | ((word >> (29 - 2 * i)) & 1);
+        second = (second << 1) | ((word >> (28 - 2 * i)) & 1);
+    }
+    ((first >> 4) << 19) | ((second >> 4) << 8) | ((first & 0xF) << 4) | (second & 0xF)
+}
+
+/// Interleaves the two codewords of a BeiDou D1/D2 navigation message word
+/// for transmission, the inverse of [`bds_deinterleave()`]
+pub fn bds_interleave(word: u32) -> u32 {
+    let first = (((word >> 19) & 0x7FF) << 4) | ((word >> 4) & 0xF);
+    let second = (((word >> 8) & 0x7FF) << 4) | (word & 0xF);
+    (0..15).fold(0, |interleaved, i| {
+        (interleaved << 2) | (((first >> (14 - i)) & 1) << 1) | ((second >> (14 - i)) & 1)
+    })
+}
+
+/// Rate 1/2 convolutional code with a constraint length of 7
+///
+/// Each input bit gives two output symbols, one from each of the generator
+/// polynomials. The encoder is expected to start in the zero state.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ConvolutionalCode {
+    polynomials: [u8; 2],
+    inverted: [bool; 2],
+}
+
+impl ConvolutionalCode {
+    /// The code used by GPS L2C and L5, and by SBAS
+    pub const GPS: ConvolutionalCode = ConvolutionalCode::new([0o171, 0o133], [false, false]);
+
+    /// The code used by the Galileo I/NAV and F/NAV messages, where the
+    /// symbols of the second polynomial are inverted
+    pub const GALILEO: ConvolutionalCode = ConvolutionalCode::new([0o171, 0o133], [false, true]);
+
+    /// Makes a code from its two generator polynomials, given with the tap
+    /// of the current input bit as the MSB of the 7 bits
+    pub const fn new(polynomials: [u8; 2], inverted: [bool; 2]) -> ConvolutionalCode {
+        ConvolutionalCode {
+            polynomials,
+            inverted,
+        }
+    }
+
+    /// Gets the two symbols output for a bit given the previous 6 bits, with
+    /// the most recent bit as the MSB of the state
+    fn output(&self, state: usize, bit: bool) -> [bool; 2] {
+        let register = (usize::from(bit) << 6) | state;
+        [0, 1].map(|i| {
+            let parity = (register & usize::from(self.polynomials[i])).count_ones() & 1 == 1;
+            parity != self.inverted[i]
+        })
+    }
+
+    /// Encodes bits, giving two symbols for each bit
+    pub fn encode(&self, bits: &[bool]) -> Vec<bool> {
+        let mut state = 0;
+        let mut symbols = Vec::with_capacity(2 * bits.len());
+        for bit in bits {
+            symbols.extend_from_slice(&self.output(state, *bit));
+            state = ((usize::from(*bit) << 6) | state) >> 1;
+        }
+        symbols
+    }
+
+    /// Decodes soft symbols with the Viterbi algorithm
+    ///
+    /// Positive symbols are ones and negative symbols are zeros, with the
+    /// magnitude giving the confidence. Hard decisions can be given as ±1,
+    /// and erased symbols as 0. Any trailing odd symbol is ignored.
+    ///
+    /// The most likely path through the trellis is returned, whichever state
+    /// it ends in. Use [`decode_terminated()`](Self::decode_terminated) when
+    /// the bits end with zero tail bits.
+    pub fn decode(&self, symbols: &[f64]) -> Vec<bool> {
+        self.viterbi(symbols, false)
+    }
+
+    /// Decodes soft symbols of bits which end with 6 zero tail bits, which
+    /// return the encoder to the zero state
+    ///
+    /// See [`decode()`](Self::decode) for the soft symbols. The tail bits are
+    /// included in the decoded bits.
+    pub fn decode_terminated(&self, symbols: &[f64]) -> Vec<bool> {
+        self.viterbi(symbols, true)
+    }
+
+    fn viterbi(&self, symbols: &[f64], terminated: bool) -> Vec<bool> {
+        const STATES: usize = 64;
+
+        // Precompute the expected symbols of every branch as ±1
+        let mut branches = [[[0.0; 2]; 2]; STATES];
+        for (state, outputs) in branches.iter_mut().enumerate() {
+            for (bit, expected) in outputs.iter_mut().enumerate() {
+                *expected = self
+                    .output(state, bit == 1)
+                    .map(|symbol| if symbol { 1.0 } else { -1.0 });
+            }
+        }
+
+        let mut metrics = [f64::NEG_INFINITY; STATES];
+        metrics[0] = 0.0;
+        let mut decisions: Vec<[u8; STATES]> = Vec::with_capacity(symbols.len() / 2);
+        for pair in symbols.chunks_exact(2) {
+            let mut next = [f64::NEG_INFINITY; STATES];
+            let mut previous = [0u8; STATES];
+            for (state, metric) in metrics.iter().enumerate() {
+                if *metric == f64::NEG_INFINITY {
+                    continue;
+                }
+                for (bit, expected) in branches[state].iter().enumerate() {
+                    let candidate = metric + pair[0] * expected[0] + pair[1] * expected[1];
+                    let next_state = ((bit << 6) | state) >> 1;
+                    if candidate > next[next_state] {
+                        next[next_state] = candidate;
+                        previous[next_state] = state as u8;
+                    }
+                }
+            }
+            metrics = next;
+            decisions.push(previous);
+        }
+
+        let mut state = if terminated {
+            0
+        } else {
+            (0..STATES)
+                .max_by(|a, b| metrics[*a].total_cmp(&metrics[*b]))
+                .unwrap_or(0)
+        };
+        let mut bits = vec![false; decisions.len()];
+        for (bit, previous) in bits.iter_mut().zip(decisions.iter()).rev() {
+            // The input bit is the MSB of the state it leads to
+            *bit = state & 0x20 != 0;
+            state = usize::from(previous[state]);
+        }
+        bits
+    }
+}
+
+/// Number of symbols in a Galileo I/NAV page part, after the synchronization
+/// pattern
+pub const GALILEO_INAV_PAGE_PART_SYMBOLS: usize = 240;
+
+/// Number of bits in a decoded Galileo I/NAV page part, including the tail
+/// bits
+pub const GALILEO_INAV_PAGE_PART_BITS: usize = GALILEO_INAV_PAGE_PART_SYMBOLS / 2;
+
+/// Rows and columns of the Galileo I/NAV block interleaver
+const GALILEO_INAV_INTERLEAVER: (usize, usize) = (8, 30);
+
+/// Reverses the block interleaving of the symbols of a Galileo I/NAV page
+/// part
+///
+/// The symbols are written into the interleaver column by column, and
+/// transmitted row by row.
+pub fn galileo_inav_deinterleave(
+    symbols: &[f64; GALILEO_INAV_PAGE_PART_SYMBOLS],
+) -> [f64; GALILEO_INAV_PAGE_PART_SYMBOLS] {
+    let (rows, columns) = GALILEO_INAV_INTERLEAVER;
+    let mut deinterleaved = [0.0; GALILEO_INAV_PAGE_PART_SYMBOLS];
+    for row in 0..rows {
+        for column in 0..columns {
+            deinterleaved[column * rows + row] = symbols[row * columns + column];
+        }
+    }
+    deinterleaved
+}
+
+/// Decodes the soft symbols of a Galileo I/NAV page part, following the
+/// synchronization pattern
+///
+/// See [`ConvolutionalCode::decode()`] for the soft symbols. The 120 decoded
+/// bits are packed MSB first, the last 6 bits being the tail bits.
+pub fn galileo_inav_decode_page_part(
+    symbols: &[f64; GALILEO_INAV_PAGE_PART_SYMBOLS],
+) -> [u8; GALILEO_INAV_PAGE_PART_BITS / 8] {
+    let bits = ConvolutionalCode::GALILEO.decode_terminated(&galileo_inav_deinterleave(symbols));
+    let mut page_part = [0; GALILEO_INAV_PAGE_PART_BITS / 8];
+    for (i, bit) in bits.iter().enumerate() {
+        page_part[i / 8] |= u8::from(*bit) << (7 - i % 8);
+    }
+    page_part
+}
+
+/// Checks the CRC of a Galileo I/NAV nominal page, from its decoded even and
+/// odd page parts
+///
+/// The CRC covers the first 114 bits of the even page part and the first 82
+/// bits of the odd page part, and is followed by the CRC in the odd page part.
+pub fn galileo_inav_crc_check(
+    even: &[u8; GALILEO_INAV_PAGE_PART_BITS / 8],
+    odd: &[u8; GALILEO_INAV_PAGE_PART_BITS / 8],
+) -> bool {
+    let crc = compute_crc24q_bits(even, 114, 0);
+    compute_crc24q_bits(odd, 82 + 24, crc) == 0
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     const TEST_DATA: &[u8] = "123456789".as_bytes();
 
     #[test]
@@ -51,4 +662,250 @@ mod tests {
             crc
         );
     }
+
+    #[test]
+    fn streaming_crc24q() {
+        let mut crc = Crc24q::with_initial_value(0xB704CE);
+        for chunk in TEST_DATA.chunks(4) {
+            crc.update(chunk);
+        }
+        assert_eq!(crc.finalize(), 0x21CF02);
+        assert_eq!(Crc24q::new().finalize(), 0);
+
+        // Checksumming a reader
+        let mut crc = Crc24q::new();
+        let copied = io::copy(&mut &TEST_DATA[..], &mut crc).unwrap();
+        assert_eq!(copied, TEST_DATA.len() as u64);
+        assert_eq!(crc.finalize(), compute_crc24q(TEST_DATA, 0));
+
+        let mut hasher = Crc24q::new();
+        hasher.write(TEST_DATA);
+        assert_eq!(hasher.finish(), u64::from(compute_crc24q(TEST_DATA, 0)));
+    }
+
+    #[test]
+    fn crc16_ccitt() {
+        assert_eq!(compute_crc16_ccitt(&[], 0), 0);
+        assert_eq!(compute_crc16_ccitt(&[], 22), 22);
+        // Check value of CRC-16/XMODEM
+        assert_eq!(compute_crc16_ccitt(TEST_DATA, 0), 0x31C3);
+
+        let mut crc = Crc16Ccitt::new();
+        for chunk in TEST_DATA.chunks(2) {
+            crc.update(chunk);
+        }
+        assert_eq!(crc.finalize(), 0x31C3);
+
+        let mut crc = Crc16Ccitt::default();
+        io::copy(&mut &TEST_DATA[..], &mut crc).unwrap();
+        assert_eq!(Hasher::finish(&crc), 0x31C3);
+    }
+
+    /// Words 3 to 5 of a GPS subframe 4, from the ionosphere tests, with the
+    /// data bits no longer complemented
+    const GPS_WORDS: [u32; 3] = [0x1e0300c9, 0x7fff8c24, 0x23fbdc2];
+
+    #[test]
+    fn gps_lnav_parity_check() {
+        for pair in GPS_WORDS.windows(2) {
+            let (previous, stored) = (pair[0], pair[1]);
+            // Back to the word as it was transmitted
+            let transmitted = gps_lnav_encode(stored >> 6, previous);
+            assert_eq!(transmitted & 0x3F, stored & 0x3F);
+            assert!(gps_lnav_check(transmitted, previous));
+            // The two MSBs of the stored words are D29* and D30*
+            let stored = stored & 0x3FFF_FFFF;
+            assert_eq!(gps_lnav_correct(transmitted, previous), Some(stored));
+
+            for bit in [0, 5, 6, 17, 29].iter() {
+                let corrupted = transmitted ^ (1 << bit);
+                assert!(!gps_lnav_check(corrupted, previous));
+                assert_eq!(gps_lnav_correct(corrupted, previous), Some(stored));
+            }
+            assert_eq!(gps_lnav_correct(transmitted ^ 0b11 << 10, previous), None);
+        }
+        // The second word follows a word ending with D30* set
+        assert_ne!(
+            gps_lnav_encode(GPS_WORDS[1] >> 6, GPS_WORDS[0]),
+            GPS_WORDS[1]
+        );
+    }
+
+    #[test]
+    fn glonass_check_bits() {
+        // Bits checked by β3 to β7, as listed in the ICD
+        let ranges = |ranges: &[(u32, u32)]| {
+            ranges.iter().fold(0u128, |mask, (first, last)| {
+                (*first..=*last).fold(mask, |mask, bit| mask | 1 << (bit - 1))
+            })
+        };
+        assert_eq!(
+            GLO_CHECK_MASKS[2],
+            ranges(&[
+                (10, 12),
+                (16, 19),
+                (23, 26),
+                (31, 34),
+                (38, 41),
+                (46, 49),
+                (54, 57),
+                (62, 65),
+                (69, 72),
+                (77, 80),
+                (85, 85)
+            ])
+        );
+        assert_eq!(
+            GLO_CHECK_MASKS[3],
+            ranges(&[(13, 19), (27, 34), (42, 49), (58, 65), (73, 80)])
+        );
+        assert_eq!(GLO_CHECK_MASKS[4], ranges(&[(20, 34), (50, 65), (81, 85)]));
+        assert_eq!(GLO_CHECK_MASKS[5], ranges(&[(35, 65)]));
+        assert_eq!(GLO_CHECK_MASKS[6], ranges(&[(66, 85)]));
+
+        let string = glonass_string_encode(0x0123_4567_89AB_CDEF_0123_4500 & GLO_DATA_BITS);
+        assert!(glonass_string_check(string));
+        for bit in (1..=85).filter(|bit| *bit != 8) {
+            let corrupted = string ^ (1 << (bit - 1));
+            assert!(!glonass_string_check(corrupted), "{}", bit);
+            let corrected = glonass_string_correct(corrupted).unwrap();
+            // Errors in the check bits leave the data unchanged
+            assert_eq!(corrected & GLO_DATA_BITS, string & GLO_DATA_BITS, "{}", bit);
+        }
+        // An error in β8 alone is indistinguishable from an odd number of
+        // errors, so the ICD treats it as uncorrectable
+        assert_eq!(glonass_string_correct(string ^ (1 << 7)), None);
+        assert_eq!(glonass_string_correct(string ^ (0b11 << 20)), None);
+    }
+
+    #[test]
+    fn bds_bch() {
+        // Deinterleaved words 1 to 4 of a BeiDou D1 subframe, from the
+        // ephemeris tests
+        let words = [0x38901714, 0x5F81035, 0x5BEE184, 0x3FDF95];
+        assert!(bds_word_check(words[0], true));
+        for word in words[1..].iter() {
+            assert!(bds_word_check(*word, false));
+            assert_eq!(bds_deinterleave(bds_interleave(*word)), *word);
+            for bit in [0, 4, 8, 19, 29].iter() {
+                let corrupted = word ^ (1 << bit);
+                assert!(!bds_word_check(corrupted, false));
+                assert_eq!(bds_word_correct(corrupted, false), *word);
+            }
+            // One error in each codeword
+            assert_eq!(bds_word_correct(word ^ (1 << 25) ^ (1 << 10), false), *word);
+        }
+        assert_eq!(bds_word_correct(words[0] ^ (1 << 3), true), words[0]);
+
+        assert_eq!(bch_15_11_encode(0), 0);
+        let codeword = bch_15_11_encode(0x5A5);
+        assert_eq!(codeword >> 4, 0x5A5);
+        assert_eq!(bch_15_11_remainder(codeword), 0);
+        for bit in 0..15 {
+            assert_eq!(bch_15_11_correct(codeword ^ (1 << bit)), codeword);
+        }
+    }
+
+    #[test]
+    fn crc24q_bits() {
+        let buf = b"123456789";
+        assert_eq!(compute_crc24q_bits(buf, 72, 0), compute_crc24q(buf, 0));
+        assert_eq!(compute_crc24q_bits(buf, 72, 0), 0xCDE703);
+
+        let crc = compute_crc24q_bits(buf, 68, 0);
+        let mut with_crc = [0u8; 13];
+        with_crc[..9].copy_from_slice(buf);
+        with_crc[8] &= 0xF0;
+        with_crc[8] |= (crc >> 20) as u8;
+        with_crc[9..12].copy_from_slice(&((crc << 4) & 0xFF_FFFF).to_be_bytes()[1..]);
+        assert_eq!(compute_crc24q_bits(&with_crc, 92, 0), 0);
+    }
+
+    #[test]
+    fn viterbi() {
+        let bits: Vec<bool> = (0..200u32)
+            .map(|i| i.wrapping_mul(2_654_435_761) >> 31 == 1)
+            .chain(std::iter::repeat(false).take(6))
+            .collect();
+        for code in [ConvolutionalCode::GPS, ConvolutionalCode::GALILEO].iter() {
+            let symbols = code.encode(&bits);
+            assert_eq!(symbols.len(), 2 * bits.len());
+            let mut soft: Vec<f64> = symbols
+                .iter()
+                .map(|s| if *s { 1.0 } else { -1.0 })
+                .collect();
+            assert_eq!(code.decode(&soft), bits);
+
+            // Spread out symbol errors and erasures
+            for i in (3..soft.len()).step_by(17) {
+                soft[i] = -soft[i];
+                soft[i - 2] = 0.0;
+            }
+            assert_eq!(code.decode_terminated(&soft), bits);
+            // Without the tail, the end of the bits is less well protected
+            let decoded = code.decode(&soft[..soft.len() - 24]);
+            assert_eq!(decoded[..190], bits[..190]);
+        }
+        assert_ne!(
+            ConvolutionalCode::GPS.encode(&bits),
+            ConvolutionalCode::GALILEO.encode(&bits)
+        );
+    }
+
+    #[test]
+    fn galileo_inav_page() {
+        fn set_bits(buf: &mut [u8], start: usize, len: usize, value: u32) {
+            for i in 0..len {
+                let bit = (value >> (len - 1 - i)) & 1 == 1;
+                let n = start + i;
+                if bit {
+                    buf[n / 8] |= 1 << (7 - n % 8);
+                }
+            }
+        }
+        fn transmit(page_part: &[u8; 15]) -> [f64; GALILEO_INAV_PAGE_PART_SYMBOLS] {
+            let bits: Vec<bool> = (0..GALILEO_INAV_PAGE_PART_BITS)
+                .map(|i| (page_part[i / 8] >> (7 - i % 8)) & 1 == 1)
+                .collect();
+            let symbols = ConvolutionalCode::GALILEO.encode(&bits);
+            let (rows, columns) = GALILEO_INAV_INTERLEAVER;
+            let mut transmitted = [0.0; GALILEO_INAV_PAGE_PART_SYMBOLS];
+            for (i, symbol) in symbols.iter().enumerate() {
+                let (column, row) = (i / rows, i % rows);
+                transmitted[row * columns + column] = if *symbol { 1.0 } else { -1.0 };
+            }
+            transmitted
+        }
+
+        // Even page part, a word type 1 with the rest of the data arbitrary
+        let mut even = [0u8; 15];
+        set_bits(&mut even, 2, 6, 1);
+        for (i, byte) in even.iter_mut().enumerate().skip(1).take(13) {
+            *byte |= (i as u8).wrapping_mul(37);
+        }
+        even[14] &= 0x80;
+        let mut odd = [0u8; 15];
+        set_bits(&mut odd, 0, 1, 1);
+        set_bits(&mut odd, 2, 16, 0xBEEF);
+        let crc = compute_crc24q_bits(&even, 114, 0);
+        let crc = compute_crc24q_bits(&odd, 82, crc);
+        set_bits(&mut odd, 82, 24, crc);
+        assert!(galileo_inav_crc_check(&even, &odd));
+
+        let mut even_symbols = transmit(&even);
+        let mut odd_symbols = transmit(&odd);
+        // Symbol errors in bursts, spread out by the interleaver
+        for i in [40, 41, 42, 150, 151].iter() {
+            even_symbols[*i] = -even_symbols[*i];
+            odd_symbols[*i + 20] = -odd_symbols[*i + 20];
+        }
+        let decoded_even = galileo_inav_decode_page_part(&even_symbols);
+        let decoded_odd = galileo_inav_decode_page_part(&odd_symbols);
+        assert_eq!(decoded_even, even);
+        assert_eq!(decoded_odd, odd);
+        assert!(galileo_inav_crc_check(&decoded_even, &decoded_odd));
+
+        odd[3] ^= 0x10;
+        assert!(!galileo_inav_crc_check(&even, &odd));
+    }
 }
@@ -0,0 +1,497 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Reference ellipsoid geometry
+//!
+//! Geodetic coordinates are given relative to a reference ellipsoid, an
+//! oblate spheroid with semi-major axis `a` and flattening `f`. The
+//! [`Ellipsoid`] trait is implemented for the ellipsoids used by the GNSS
+//! reference frames:
+//!  * [`WGS84`] - Used by GPS, and by the coordinate types in [`crate::coords`]
+//!  * [`GRS80`] - Used by the ITRF and its regional densifications, such as
+//!    ETRF and NAD83
+//!  * [`PZ90`] - The PZ-90.11 ellipsoid used by GLONASS
+//!  * [`CGCS2000`] - Used by BeiDou
+//!
+//! Besides the defining parameters the trait provides conversions between
+//! geodetic and ECEF coordinates, the radii of curvature of the ellipsoid and
+//! the conversions between the geodetic latitude and the auxiliary latitudes
+//! used by projections and geodesic calculations:
+//!  * Geocentric latitude - The angle between the equator and the line from
+//!    the center of the ellipsoid to the point
+//!  * Parametric (reduced) latitude - The latitude of the point projected onto
+//!    a sphere of radius `a`, parallel to the polar axis
+//!  * Authalic latitude - The latitude on a sphere of equal surface area,
+//!    used by equal area projections
+//!  * Conformal latitude - The latitude on a sphere onto which the ellipsoid
+//!    is mapped conformally, used by conformal projections
+//!
+//! All latitudes are in radians and are valid over [-π/2, π/2].
+//!
+//! The difference between WGS84 and GRS80 is only 0.1 mm in the semi-minor
+//! axis, but the other ellipsoids differ by up to a meter, so geodetic
+//! coordinates should be computed with the ellipsoid of their reference frame
+//! (see [`ReferenceFrame::ellipsoid()`](crate::reference_frame::ReferenceFrame::ellipsoid)).
+//!
+//! # References
+//!   * Map Projections - A Working Manual, J P Snyder, 1987, USGS Professional
+//!     Paper 1395
+//!   * Department of Defense World Geodetic System 1984, NGA.STND.0036_1.0.0
+//!   * Geodetic Reference System 1980, H Moritz, 2000, Journal of Geodesy
+//!   * Parametry Zemli 1990 (PZ-90.11) Reference Document, 2014
+//!   * BeiDou Navigation Satellite System Signal In Space Interface Control
+//!     Document, Open Service Signal B1I, Version 3.0
+
+use crate::coords::{LLHRadians, ECEF};
+use std::f64::consts::FRAC_PI_2;
+
+/// Largest number of iterations when inverting the auxiliary latitudes
+const MAX_ITERATIONS: usize = 20;
+/// Convergence threshold when inverting the auxiliary latitudes, in radians
+const CONVERGENCE: f64 = 1e-14;
+
+/// A reference ellipsoid, defined by its semi-major axis and flattening
+pub trait Ellipsoid {
+    /// Gets the semi-major (equatorial) axis, in meters
+    fn semi_major_axis(&self) -> f64;
+
+    /// Gets the flattening
+    fn flattening(&self) -> f64;
+
+    /// Gets the semi-minor (polar) axis, in meters
+    fn semi_minor_axis(&self) -> f64 {
+        self.semi_major_axis() * (1.0 - self.flattening())
+    }
+
+    /// Gets the square of the first eccentricity
+    fn eccentricity_squared(&self) -> f64 {
+        let f = self.flattening();
+        f * (2.0 - f)
+    }
+
+    /// Gets the square of the second eccentricity
+    fn second_eccentricity_squared(&self) -> f64 {
+        let e2 = self.eccentricity_squared();
+        e2 / (1.0 - e2)
+    }
+
+    /// Converts geodetic coordinates on this ellipsoid into ECEF coordinates
+    fn llh_to_ecef(&self, llh: &LLHRadians) -> ECEF {
+        let (sin_lat, cos_lat) = llh.latitude().sin_cos();
+        let (sin_lon, cos_lon) = llh.longitude().sin_cos();
+        let n = self.prime_vertical_radius(llh.latitude());
+        let h = llh.height();
+        ECEF::new(
+            (n + h) * cos_lat * cos_lon,
+            (n + h) * cos_lat * sin_lon,
+            (n * (1.0 - self.eccentricity_squared()) + h) * sin_lat,
+        )
+    }
+
+    /// Converts ECEF coordinates into geodetic coordinates on this ellipsoid
+    ///
+    /// The latitude is found by fixed point iteration, which converges to
+    /// well below a micrometer within a few iterations for points near the
+    /// surface of the Earth.
+    fn ecef_to_llh(&self, ecef: &ECEF) -> LLHRadians {
+        let a = self.semi_major_axis();
+        let e2 = self.eccentricity_squared();
+        let p = ecef.x().hypot(ecef.y());
+        let lon = ecef.y().atan2(ecef.x());
+
+        let mut lat = ecef.z().atan2(p * (1.0 - e2));
+        for _ in 0..MAX_ITERATIONS {
+            let n = self.prime_vertical_radius(lat);
+            let next = (ecef.z() + e2 * n * lat.sin()).atan2(p);
+            let step = next - lat;
+            lat = next;
+            if step.abs() < CONVERGENCE {
+                break;
+            }
+        }
+        // Valid at all latitudes, unlike p / cos(lat) - N
+        let (sin_lat, cos_lat) = lat.sin_cos();
+        let h = p * cos_lat + ecef.z() * sin_lat - a * (1.0 - e2 * sin_lat * sin_lat).sqrt();
+        LLHRadians::new(lat, lon, h)
+    }
+
+    /// Gets the mean radius (2a + b) / 3, in meters
+    fn mean_radius(&self) -> f64 {
+        (2.0 * self.semi_major_axis() + self.semi_minor_axis()) / 3.0
+    }
+
+    /// Gets the radius of the sphere with the same surface area as the
+    /// ellipsoid, in meters
+    fn authalic_radius(&self) -> f64 {
+        let q = snyder_q(self.eccentricity_squared(), FRAC_PI_2);
+        self.semi_major_axis() * (0.5 * q).sqrt()
+    }
+
+    /// Gets the radius of curvature in the meridian at a geodetic latitude,
+    /// in meters
+    fn meridian_radius(&self, lat: f64) -> f64 {
+        let e2 = self.eccentricity_squared();
+        let w2 = 1.0 - e2 * lat.sin().powi(2);
+        self.semi_major_axis() * (1.0 - e2) / (w2 * w2.sqrt())
+    }
+
+    /// Gets the radius of curvature in the prime vertical at a geodetic
+    /// latitude, in meters
+    ///
+    /// This is the `N(ϕ)` used when converting geodetic coordinates to ECEF.
+    fn prime_vertical_radius(&self, lat: f64) -> f64 {
+        let e2 = self.eccentricity_squared();
+        self.semi_major_axis() / (1.0 - e2 * lat.sin().powi(2)).sqrt()
+    }
+
+    /// Gets the radius of curvature of the normal section in the given
+    /// azimuth (rad, clockwise from north) at a geodetic latitude, in meters
+    fn radius_in_azimuth(&self, lat: f64, azimuth: f64) -> f64 {
+        let (sin_az, cos_az) = azimuth.sin_cos();
+        1.0 / (cos_az * cos_az / self.meridian_radius(lat)
+            + sin_az * sin_az / self.prime_vertical_radius(lat))
+    }
+
+    /// Gets the Gaussian mean radius of curvature at a geodetic latitude, in
+    /// meters
+    fn gaussian_radius(&self, lat: f64) -> f64 {
+        (self.meridian_radius(lat) * self.prime_vertical_radius(lat)).sqrt()
+    }
+
+    /// Converts a geodetic latitude into a geocentric latitude
+    fn geocentric_latitude(&self, lat: f64) -> f64 {
+        let (sin_lat, cos_lat) = lat.sin_cos();
+        ((1.0 - self.eccentricity_squared()) * sin_lat).atan2(cos_lat)
+    }
+
+    /// Converts a geocentric latitude into a geodetic latitude
+    fn geodetic_from_geocentric(&self, geocentric: f64) -> f64 {
+        let (sin_lat, cos_lat) = geocentric.sin_cos();
+        sin_lat.atan2((1.0 - self.eccentricity_squared()) * cos_lat)
+    }
+
+    /// Converts a geodetic latitude into a parametric latitude
+    fn parametric_latitude(&self, lat: f64) -> f64 {
+        let (sin_lat, cos_lat) = lat.sin_cos();
+        ((1.0 - self.flattening()) * sin_lat).atan2(cos_lat)
+    }
+
+    /// Converts a parametric latitude into a geodetic latitude
+    fn geodetic_from_parametric(&self, parametric: f64) -> f64 {
+        let (sin_lat, cos_lat) = parametric.sin_cos();
+        sin_lat.atan2((1.0 - self.flattening()) * cos_lat)
+    }
+
+    /// Converts a geodetic latitude into an authalic latitude
+    fn authalic_latitude(&self, lat: f64) -> f64 {
+        // The arcsine is poorly conditioned right at the poles
+        if FRAC_PI_2 - lat.abs() < CONVERGENCE {
+            return lat;
+        }
+        let e2 = self.eccentricity_squared();
+        (snyder_q(e2, lat) / snyder_q(e2, FRAC_PI_2))
+            .clamp(-1.0, 1.0)
+            .asin()
+    }
+
+    /// Converts an authalic latitude into a geodetic latitude
+    ///
+    /// The conversion is iterative, see Snyder equation 3-16.
+    fn geodetic_from_authalic(&self, authalic: f64) -> f64 {
+        if FRAC_PI_2 - authalic.abs() < CONVERGENCE {
+            return authalic;
+        }
+        let e2 = self.eccentricity_squared();
+        let e = e2.sqrt();
+        let q = snyder_q(e2, FRAC_PI_2) * authalic.sin();
+        let mut lat = authalic;
+        for _ in 0..MAX_ITERATIONS {
+            let (sin_lat, cos_lat) = lat.sin_cos();
+            let w2 = 1.0 - e2 * sin_lat * sin_lat;
+            let step = w2 * w2 / (2.0 * cos_lat)
+                * (q / (1.0 - e2) - sin_lat / w2
+                    + ((1.0 - e * sin_lat) / (1.0 + e * sin_lat)).ln() / (2.0 * e));
+            lat += step;
+            if step.abs() < CONVERGENCE {
+                break;
+            }
+        }
+        lat
+    }
+
+    /// Converts a geodetic latitude into a conformal latitude
+    fn conformal_latitude(&self, lat: f64) -> f64 {
+        let e = self.eccentricity_squared().sqrt();
+        let isometric = lat.tan().asinh() - e * (e * lat.sin()).atanh();
+        isometric.sinh().atan()
+    }
+
+    /// Converts a conformal latitude into a geodetic latitude
+    ///
+    /// The conversion is iterative, see Snyder equation 3-4.
+    fn geodetic_from_conformal(&self, conformal: f64) -> f64 {
+        if FRAC_PI_2 - conformal.abs() < CONVERGENCE {
+            return conformal;
+        }
+        let e = self.eccentricity_squared().sqrt();
+        let isometric = conformal.tan().asinh();
+        let mut lat = conformal;
+        for _ in 0..MAX_ITERATIONS {
+            let next = (isometric + e * (e * lat.sin()).atanh()).sinh().atan();
+            let step = next - lat;
+            lat = next;
+            if step.abs() < CONVERGENCE {
+                break;
+            }
+        }
+        lat
+    }
+}
+
+/// Snyder's `q` function, equation 3-12
+fn snyder_q(e2: f64, lat: f64) -> f64 {
+    let e = e2.sqrt();
+    let sin_lat = lat.sin();
+    (1.0 - e2)
+        * (sin_lat / (1.0 - e2 * sin_lat * sin_lat)
+            - ((1.0 - e * sin_lat) / (1.0 + e * sin_lat)).ln() / (2.0 * e))
+}
+
+/// The WGS84 ellipsoid
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct WGS84;
+
+impl WGS84 {
+    /// Semi-major axis, in meters
+    pub const SEMI_MAJOR_AXIS: f64 = 6_378_137.0;
+    /// Flattening
+    pub const FLATTENING: f64 = 1.0 / 298.257_223_563;
+}
+
+impl Ellipsoid for WGS84 {
+    fn semi_major_axis(&self) -> f64 {
+        Self::SEMI_MAJOR_AXIS
+    }
+
+    fn flattening(&self) -> f64 {
+        Self::FLATTENING
+    }
+}
+
+/// The GRS80 ellipsoid
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct GRS80;
+
+impl GRS80 {
+    /// Semi-major axis, in meters
+    pub const SEMI_MAJOR_AXIS: f64 = 6_378_137.0;
+    /// Flattening
+    pub const FLATTENING: f64 = 1.0 / 298.257_222_101;
+}
+
+impl Ellipsoid for GRS80 {
+    fn semi_major_axis(&self) -> f64 {
+        Self::SEMI_MAJOR_AXIS
+    }
+
+    fn flattening(&self) -> f64 {
+        Self::FLATTENING
+    }
+}
+
+/// The PZ-90.11 ellipsoid
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct PZ90;
+
+impl PZ90 {
+    /// Semi-major axis, in meters
+    pub const SEMI_MAJOR_AXIS: f64 = 6_378_136.0;
+    /// Flattening
+    pub const FLATTENING: f64 = 1.0 / 298.257_84;
+}
+
+impl Ellipsoid for PZ90 {
+    fn semi_major_axis(&self) -> f64 {
+        Self::SEMI_MAJOR_AXIS
+    }
+
+    fn flattening(&self) -> f64 {
+        Self::FLATTENING
+    }
+}
+
+/// The CGCS2000 ellipsoid
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct CGCS2000;
+
+impl CGCS2000 {
+    /// Semi-major axis, in meters
+    pub const SEMI_MAJOR_AXIS: f64 = 6_378_137.0;
+    /// Flattening
+    pub const FLATTENING: f64 = 1.0 / 298.257_222_101;
+}
+
+impl Ellipsoid for CGCS2000 {
+    fn semi_major_axis(&self) -> f64 {
+        Self::SEMI_MAJOR_AXIS
+    }
+
+    fn flattening(&self) -> f64 {
+        Self::FLATTENING
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+
+    const D2R: f64 = std::f64::consts::PI / 180.0;
+
+    #[test]
+    fn radii_of_curvature() {
+        let a = WGS84::SEMI_MAJOR_AXIS;
+        let b = WGS84.semi_minor_axis();
+        let e2 = WGS84.eccentricity_squared();
+        assert_float_eq!(b, 6_356_752.314_245, abs <= 1e-6);
+        assert_float_eq!(e2, 6.694_379_990_14e-3, abs <= 1e-14);
+        assert_float_eq!(WGS84.authalic_radius(), 6_371_007.181, abs <= 1e-3);
+
+        // At the equator the meridian radius is smallest and the prime
+        // vertical radius is the semi-major axis
+        assert_float_eq!(WGS84.meridian_radius(0.0), a * (1.0 - e2), abs <= 1e-6);
+        assert_float_eq!(WGS84.prime_vertical_radius(0.0), a, abs <= 1e-6);
+        // At the poles the ellipsoid is locally a sphere of radius a²/b
+        let polar = a * a / b;
+        assert_float_eq!(WGS84.meridian_radius(FRAC_PI_2), polar, abs <= 1e-6);
+        assert_float_eq!(WGS84.prime_vertical_radius(FRAC_PI_2), polar, abs <= 1e-6);
+
+        let lat = 45.0 * D2R;
+        assert_float_eq!(
+            WGS84.radius_in_azimuth(lat, 0.0),
+            WGS84.meridian_radius(lat),
+            abs <= 1e-6
+        );
+        assert_float_eq!(
+            WGS84.radius_in_azimuth(lat, 90.0 * D2R),
+            WGS84.prime_vertical_radius(lat),
+            abs <= 1e-6
+        );
+        let gaussian = WGS84.gaussian_radius(lat);
+        assert!(gaussian > WGS84.meridian_radius(lat));
+        assert!(gaussian < WGS84.prime_vertical_radius(lat));
+    }
+
+    #[test]
+    fn auxiliary_latitudes() {
+        let lat = 45.0 * D2R;
+        assert_float_eq!(
+            WGS84.geocentric_latitude(lat),
+            44.807_576_784 * D2R,
+            abs <= 1e-11
+        );
+        assert_float_eq!(
+            WGS84.parametric_latitude(lat),
+            44.903_787_849 * D2R,
+            abs <= 1e-11
+        );
+        assert_float_eq!(
+            WGS84.authalic_latitude(lat),
+            44.871_702_873 * D2R,
+            abs <= 1e-11
+        );
+        assert_float_eq!(
+            WGS84.conformal_latitude(lat),
+            44.807_684_056 * D2R,
+            abs <= 1e-11
+        );
+
+        for deg in [-90.0, -89.9, -60.0, -1.0, 0.0, 30.0, 45.0, 75.0, 90.0].iter() {
+            let lat = deg * D2R;
+            let geocentric = WGS84.geocentric_latitude(lat);
+            assert_float_eq!(
+                WGS84.geodetic_from_geocentric(geocentric),
+                lat,
+                abs <= 1e-14
+            );
+            let parametric = WGS84.parametric_latitude(lat);
+            assert_float_eq!(
+                WGS84.geodetic_from_parametric(parametric),
+                lat,
+                abs <= 1e-14
+            );
+            let authalic = WGS84.authalic_latitude(lat);
+            assert_float_eq!(WGS84.geodetic_from_authalic(authalic), lat, abs <= 1e-12);
+            let conformal = WGS84.conformal_latitude(lat);
+            assert_float_eq!(WGS84.geodetic_from_conformal(conformal), lat, abs <= 1e-12);
+            // The auxiliary latitudes are all closer to the equator
+            for aux in [geocentric, parametric, authalic, conformal].iter() {
+                assert!(aux.abs() <= lat.abs() + 1e-15);
+            }
+        }
+    }
+
+    #[test]
+    fn other_ellipsoids() {
+        // GRS80 and WGS84 only differ by a tenth of a millimeter at the poles
+        assert_float_eq!(GRS80.semi_minor_axis(), 6_356_752.314_140, abs <= 1e-6);
+        assert_float_eq!(
+            GRS80.semi_minor_axis() - WGS84.semi_minor_axis(),
+            -0.000_105,
+            abs <= 1e-6
+        );
+        assert_eq!(CGCS2000.semi_minor_axis(), GRS80.semi_minor_axis());
+        assert_float_eq!(PZ90.semi_minor_axis(), 6_356_751.361_8, abs <= 1e-4);
+
+        // The ellipsoids can be used interchangeably through the trait
+        let ellipsoids: [&dyn Ellipsoid; 4] = [&WGS84, &GRS80, &PZ90, &CGCS2000];
+        for ellipsoid in ellipsoids.iter() {
+            assert!(ellipsoid.authalic_radius() > ellipsoid.semi_minor_axis());
+            assert!(ellipsoid.authalic_radius() < ellipsoid.semi_major_axis());
+        }
+    }
+
+    #[test]
+    fn geodetic_conversions() {
+        let ellipsoids: [&dyn Ellipsoid; 4] = [&WGS84, &GRS80, &PZ90, &CGCS2000];
+        for ellipsoid in ellipsoids.iter() {
+            for (lat, lon, height) in [
+                (0.0, 0.0, 0.0),
+                (37.77, -122.39, 10.0),
+                (-33.85, 151.2, -50.0),
+                (89.999, 45.0, 1000.0),
+                (-90.0, 0.0, 100.0),
+                (10.0, 179.0, 20_200_000.0),
+            ]
+            .iter()
+            {
+                let llh = LLHRadians::new(lat * D2R, lon * D2R, *height);
+                let ecef = ellipsoid.llh_to_ecef(&llh);
+                let round_trip = ellipsoid.ecef_to_llh(&ecef);
+                assert_float_eq!(round_trip.latitude(), llh.latitude(), abs <= 1e-12);
+                if lat.abs() < 90.0 {
+                    assert_float_eq!(round_trip.longitude(), llh.longitude(), abs <= 1e-12);
+                }
+                assert_float_eq!(round_trip.height(), llh.height(), abs <= 1e-6);
+            }
+        }
+
+        // Matches the WGS84 conversions of the coordinate types
+        let llh = LLHRadians::new(37.77 * D2R, -122.39 * D2R, 10.0);
+        let ecef = WGS84.llh_to_ecef(&llh);
+        let reference = llh.to_ecef();
+        assert_float_eq!(ecef.x(), reference.x(), abs <= 1e-6);
+        assert_float_eq!(ecef.y(), reference.y(), abs <= 1e-6);
+        assert_float_eq!(ecef.z(), reference.z(), abs <= 1e-6);
+
+        // The same point has a different height on each ellipsoid
+        let pz90 = PZ90.ecef_to_llh(&ecef);
+        assert_float_eq!(pz90.height() - llh.height(), 0.93, abs <= 0.1);
+    }
+}
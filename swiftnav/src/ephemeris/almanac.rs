@@ -0,0 +1,140 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Almanac orbits
+//!
+//! An almanac is a reduced, less accurate, version of the ephemeris which the
+//! satellites broadcast for the whole constellation. It stays usable for
+//! weeks, with errors of a few kilometers, which is enough to predict which
+//! satellites will be visible but not to compute a position.
+//!
+//! # References
+//!   * IS-GPS-200D, Section 20.3.3.5.2.1 and Table 20-VI
+
+use crate::ephemeris::kepler::KeplerEphemeris;
+use crate::ephemeris::SatelliteState;
+use crate::signal::{Constellation, GnssSignal};
+use crate::time::GpsTime;
+
+/// The almanac orbit of a GPS, Galileo, BeiDou or QZSS satellite
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct Almanac {
+    sid: GnssSignal,
+    orbit: KeplerEphemeris,
+}
+
+impl Almanac {
+    /// Makes an almanac from the broadcast terms
+    ///
+    /// The angles are in radians rather than semicircles, and `inc` is the
+    /// full inclination rather than the offset from the reference inclination
+    /// which some constellations broadcast. `af0` and `af1` are the clock
+    /// offset in seconds and drift in seconds per second.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `sid` isn't a GPS, Galileo, BeiDou or QZSS
+    /// signal
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        sid: GnssSignal,
+        toa: GpsTime,
+        sqrta: f64,
+        ecc: f64,
+        inc: f64,
+        omega0: f64,
+        omegadot: f64,
+        w: f64,
+        m0: f64,
+        af0: f64,
+        af1: f64,
+    ) -> Almanac {
+        let constellation = sid.to_constellation();
+        assert!(
+            matches!(
+                constellation,
+                Constellation::Gps | Constellation::Gal | Constellation::Bds | Constellation::Qzs
+            ),
+            "Invalid constellation for an almanac"
+        );
+        Almanac {
+            sid,
+            orbit: KeplerEphemeris::from_almanac_terms(
+                constellation,
+                sid.sat(),
+                toa,
+                sqrta,
+                ecc,
+                inc,
+                omega0,
+                omegadot,
+                w,
+                m0,
+                af0,
+                af1,
+            ),
+        }
+    }
+
+    pub fn sid(&self) -> GnssSignal {
+        self.sid
+    }
+
+    /// Gets the reference time of the almanac
+    pub fn toa(&self) -> GpsTime {
+        self.orbit.toe()
+    }
+
+    /// Calculates the approximate satellite state at a time
+    pub fn calc_satellite_state(&self, t: GpsTime) -> SatelliteState {
+        self.orbit.calc_satellite_state(t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::Code;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn almanac_orbit() {
+        let toa = GpsTime::new(2100, 405_504.0).unwrap();
+        let almanac = Almanac::new(
+            GnssSignal::new(5, Code::GpsL1ca).unwrap(),
+            toa,
+            5_153.6,
+            0.005,
+            55.0_f64.to_radians(),
+            1.2,
+            -8.0e-9,
+            0.9,
+            -2.1,
+            1.0e-4,
+            0.0,
+        );
+        assert_eq!(almanac.toa(), toa);
+
+        // The satellite stays on its orbit radius over the following days
+        for hours in [0.0, 6.0, 30.0, 72.0].iter() {
+            let t = toa + std::time::Duration::from_secs_f64(hours * 3600.0);
+            let state = almanac.calc_satellite_state(t);
+            let radius = state
+                .pos
+                .as_array_ref()
+                .iter()
+                .map(|x| x * x)
+                .sum::<f64>()
+                .sqrt();
+            let a = 5_153.6 * 5_153.6;
+            assert!(radius > a * (1.0 - 0.005) - 1.0 && radius < a * (1.0 + 0.005) + 1.0);
+            assert_float_eq!(state.clock_err, 1.0e-4, abs <= 1e-7);
+        }
+    }
+}
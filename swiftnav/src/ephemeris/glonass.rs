@@ -0,0 +1,244 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! GLONASS ephemeris propagation
+//!
+//! GLONASS satellites don't broadcast orbital elements, instead they broadcast
+//! the position, velocity and luni-solar acceleration of the satellite in the
+//! PZ-90 frame at the reference time. The position at other times is found by
+//! numerically integrating the equations of motion, which include the central
+//! gravity term, the J2 oblateness term and the earth rotation terms of the
+//! rotating frame. The luni-solar acceleration is held constant over the
+//! integration.
+//!
+//! This is a pure Rust implementation of the integration done by
+//! libswiftnav, using a fourth order Runge-Kutta integrator.
+//!
+//! # References
+//!   * GLONASS ICD, Edition 5.1 2008, Appendix J
+
+use crate::coords::ECEF;
+use crate::ephemeris::{Ephemeris, SatelliteState};
+use crate::signal::Constellation;
+use crate::time::GpsTime;
+
+/// Earth's gravitational constant in the PZ-90 frame, in m³/s²
+const GLO_GM: f64 = 398_600.441_8e9;
+/// Semi-major axis of the PZ-90 ellipsoid, in meters
+const GLO_A_E: f64 = 6_378_136.0;
+/// Second zonal harmonic of the geopotential
+const GLO_J02: f64 = 1.082_625_75e-3;
+/// Earth's rotation rate, in rad/s
+const GLO_OMEGAE_DOT: f64 = 7.292_115e-5;
+/// Longest integration step, in seconds
+const GLO_MAX_STEP_LENGTH: f64 = 30.0;
+
+/// The broadcast orbit of a GLONASS satellite
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct GloEphemeris {
+    toe: GpsTime,
+    gamma: f64,
+    tau: f64,
+    pos: ECEF,
+    vel: ECEF,
+    acc: ECEF,
+    iod: u8,
+}
+
+impl GloEphemeris {
+    /// Makes a GLONASS ephemeris from the broadcast terms
+    ///
+    /// `pos`, `vel` and `acc` are the PZ-90 position, velocity and luni-solar
+    /// acceleration at `toe`, in meters and seconds. `gamma` is the relative
+    /// frequency offset and `tau` the clock offset, in seconds, with the
+    /// signs used in the GLONASS navigation message.
+    pub fn new(
+        toe: GpsTime,
+        gamma: f64,
+        tau: f64,
+        pos: ECEF,
+        vel: ECEF,
+        acc: ECEF,
+        iod: u8,
+    ) -> GloEphemeris {
+        GloEphemeris {
+            toe,
+            gamma,
+            tau,
+            pos,
+            vel,
+            acc,
+            iod,
+        }
+    }
+
+    /// Gets the GLONASS terms of an ephemeris
+    ///
+    /// Returns `None` if the ephemeris isn't for a GLONASS satellite
+    pub fn from_ephemeris(ephemeris: &Ephemeris) -> Option<GloEphemeris> {
+        let sid = ephemeris.sid().ok()?;
+        if sid.to_constellation() != Constellation::Glo {
+            return None;
+        }
+        // Safe because the constellation determines the active union member
+        let glo = unsafe { ephemeris.0.data.glo };
+        Some(GloEphemeris::new(
+            GpsTime::new_unchecked(ephemeris.0.toe.wn, ephemeris.0.toe.tow),
+            glo.gamma,
+            glo.tau,
+            ECEF::from_array(&glo.pos),
+            ECEF::from_array(&glo.vel),
+            ECEF::from_array(&glo.acc),
+            glo.iod,
+        ))
+    }
+
+    pub fn toe(&self) -> GpsTime {
+        self.toe
+    }
+
+    pub fn iod(&self) -> u8 {
+        self.iod
+    }
+
+    /// Calculates the satellite position, velocity, acceleration and clock
+    /// offset at a time
+    ///
+    /// The orbit is integrated from the reference time in steps of at most 30
+    /// seconds. No check is made that `t` is within the fit interval of the
+    /// ephemeris.
+    pub fn calc_satellite_state(&self, t: GpsTime) -> SatelliteState {
+        let dt = t.diff(&self.toe);
+
+        let steps = (dt.abs() / GLO_MAX_STEP_LENGTH).ceil();
+        let step = if steps > 0.0 { dt / steps } else { 0.0 };
+        let acc = *self.acc.as_array_ref();
+
+        let mut y = [0.0; 6];
+        y[..3].copy_from_slice(self.pos.as_array_ref());
+        y[3..].copy_from_slice(self.vel.as_array_ref());
+        for _ in 0..steps as usize {
+            y = rk4_step(&y, &acc, step);
+        }
+        let derivatives = derivatives(&y, &acc);
+
+        SatelliteState {
+            pos: ECEF::new(y[0], y[1], y[2]),
+            vel: ECEF::new(y[3], y[4], y[5]),
+            acc: ECEF::new(derivatives[3], derivatives[4], derivatives[5]),
+            clock_err: -self.tau + self.gamma * dt,
+            clock_rate_err: self.gamma,
+            iodc: self.iod.into(),
+            iode: self.iod,
+        }
+    }
+}
+
+/// Time derivative of the position and velocity state vector
+fn derivatives(y: &[f64; 6], acc: &[f64; 3]) -> [f64; 6] {
+    let (x, y_, z) = (y[0], y[1], y[2]);
+    let (vx, vy) = (y[3], y[4]);
+
+    let r2 = x * x + y_ * y_ + z * z;
+    let r = r2.sqrt();
+    let m_r3 = GLO_GM / (r2 * r);
+    let j2_term = 1.5 * GLO_J02 * GLO_GM * GLO_A_E * GLO_A_E / (r2 * r2 * r);
+    let z2_r2 = 5.0 * z * z / r2;
+    let omega2 = GLO_OMEGAE_DOT * GLO_OMEGAE_DOT;
+
+    [
+        y[3],
+        y[4],
+        y[5],
+        -m_r3 * x - j2_term * x * (1.0 - z2_r2) + omega2 * x + 2.0 * GLO_OMEGAE_DOT * vy + acc[0],
+        -m_r3 * y_ - j2_term * y_ * (1.0 - z2_r2) + omega2 * y_ - 2.0 * GLO_OMEGAE_DOT * vx
+            + acc[1],
+        -m_r3 * z - j2_term * z * (3.0 - z2_r2) + acc[2],
+    ]
+}
+
+/// Advances the state vector by one fourth order Runge-Kutta step
+fn rk4_step(y: &[f64; 6], acc: &[f64; 3], h: f64) -> [f64; 6] {
+    let offset = |k: &[f64; 6], scale: f64| {
+        let mut out = *y;
+        for (o, k) in out.iter_mut().zip(k.iter()) {
+            *o += scale * k;
+        }
+        out
+    };
+
+    let k1 = derivatives(y, acc);
+    let k2 = derivatives(&offset(&k1, h / 2.0), acc);
+    let k3 = derivatives(&offset(&k2, h / 2.0), acc);
+    let k4 = derivatives(&offset(&k3, h), acc);
+
+    let mut out = *y;
+    for i in 0..6 {
+        out[i] += h / 6.0 * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+    use std::time::Duration;
+
+    fn icd_example() -> GloEphemeris {
+        // Example from the GLONASS ICD, Appendix J, with the time of day
+        // placed in an arbitrary week
+        GloEphemeris::new(
+            GpsTime::new(2000, 11700.0).unwrap(),
+            1e-9,
+            -2e-5,
+            ECEF::new(7_003_008.789, -12_206_626.953, 21_280_765.625),
+            ECEF::new(783.5417, 2804.2530, 1352.5150),
+            ECEF::new(0.0, 1.7e-6, -5.41e-6),
+            5,
+        )
+    }
+
+    #[test]
+    fn icd_example_propagation() {
+        let eph = icd_example();
+        let state = eph.calc_satellite_state(eph.toe() + Duration::from_secs(600));
+
+        // The ICD values are computed with the full luni-solar model, so small
+        // differences are expected
+        assert_float_eq!(state.pos.x(), 7_523_174.819, abs <= 1.0);
+        assert_float_eq!(state.pos.y(), -10_506_961.965, abs <= 1.0);
+        assert_float_eq!(state.pos.z(), 21_999_239.413, abs <= 1.0);
+        assert_float_eq!(state.vel.x(), 950.126_007, abs <= 5e-3);
+        assert_float_eq!(state.vel.y(), 2855.687_825, abs <= 5e-3);
+        assert_float_eq!(state.vel.z(), 1040.679_862, abs <= 5e-3);
+
+        assert_float_eq!(state.clock_err, 2e-5 + 600e-9, abs <= 1e-15);
+        assert_float_eq!(state.clock_rate_err, 1e-9, abs <= 1e-18);
+        assert_eq!(state.iode, 5);
+    }
+
+    #[test]
+    fn propagation_is_reversible() {
+        let eph = icd_example();
+        let state = eph.calc_satellite_state(eph.toe());
+        assert_eq!(state.pos, eph.pos);
+        assert_eq!(state.vel, eph.vel);
+
+        // Integrating back from a propagated state returns to the start
+        let later = eph.toe() + Duration::from_secs(900);
+        let forward = eph.calc_satellite_state(later);
+        let back = GloEphemeris::new(later, 0.0, 0.0, forward.pos, forward.vel, eph.acc, 5)
+            .calc_satellite_state(eph.toe());
+        assert_float_eq!(back.pos.x(), eph.pos.x(), abs <= 1e-3);
+        assert_float_eq!(back.pos.y(), eph.pos.y(), abs <= 1e-3);
+        assert_float_eq!(back.pos.z(), eph.pos.z(), abs <= 1e-3);
+        assert_float_eq!(back.vel.z(), eph.vel.z(), abs <= 1e-6);
+    }
+}
@@ -0,0 +1,511 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Broadcast group delays
+//!
+//! The broadcast satellite clock corrections are referenced to a particular
+//! signal, or combination of signals. Users of other signals must apply a group
+//! delay term to get the clock offset appropriate for their signal. Each
+//! constellation broadcasts these terms differently:
+//!  * GPS and QZSS broadcast a T<sub>GD</sub> term in LNAV, and additionally
+//!    inter-signal corrections (ISC) in CNAV
+//!  * Galileo broadcasts the BGD<sub>E1,E5a</sub> and BGD<sub>E1,E5b</sub>
+//!    terms in both I/NAV and F/NAV
+//!  * Beidou broadcasts T<sub>GD1</sub> and T<sub>GD2</sub> in D1/D2
+//!
+//! [`GroupDelays`] holds the terms for a single satellite and can combine terms
+//! received in different navigation messages. [`GroupDelayTable`] keeps track
+//! of the terms for many satellites. Both can then provide the group delay to
+//! apply for a particular [`Code`], or apply it to a [`SatelliteState`] and
+//! give back the term that was applied.
+//!
+//! # References
+//!   * IS-GPS-200H, Section 20.3.3.3.3.2
+//!   * IS-GPS-705D, Section 20.3.3.3.1.2
+//!   * Galileo OS SIS ICD Issue 2.0, Section 5.1.5
+//!   * BDS-SIS-ICD-2.1, Section 5.2.4.10
+
+use super::{inav_field, inav_signed_field, Ephemeris, SatelliteState, GAL_INAV_CONTENT_BYTE};
+use crate::signal::{Code, Constellation, GnssSignal};
+use std::collections::HashMap;
+
+/// Ratio of the squares of the GPS L1 and L2 carrier frequencies
+const GAMMA_L1_L2: f64 = (77.0 * 77.0) / (60.0 * 60.0);
+/// Ratio of the squares of the Galileo E1 and E5a carrier frequencies
+const GAMMA_E1_E5A: f64 = (154.0 * 154.0) / (115.0 * 115.0);
+/// Ratio of the squares of the Galileo E1 and E5b carrier frequencies
+const GAMMA_E1_E5B: f64 = (154.0 * 154.0) / (118.0 * 118.0);
+
+/// The navigation message a set of group delay terms was decoded from
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum GroupDelaySource {
+    /// GPS or QZSS legacy navigation message
+    Lnav,
+    /// GPS or QZSS civil navigation message
+    Cnav,
+    /// Galileo I/NAV or F/NAV message
+    GalNav,
+    /// Beidou D1 or D2 message
+    BdsD1D2,
+}
+
+/// A group delay applied to a satellite clock offset
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct AppliedGroupDelay {
+    /// The code the clock offset was corrected for
+    pub code: Code,
+    /// The group delay subtracted from the clock offset, in seconds
+    pub delay: f64,
+    /// The navigation message the group delay terms came from
+    pub source: GroupDelaySource,
+}
+
+/// Group delay terms of a single satellite, in seconds
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq, Default)]
+pub struct GroupDelays {
+    tgd_lnav: Option<f64>,
+    tgd_cnav: Option<f64>,
+    isc_l1ca: Option<f64>,
+    isc_l2c: Option<f64>,
+    isc_l5i5: Option<f64>,
+    isc_l5q5: Option<f64>,
+    bgd_e1e5a: Option<f64>,
+    bgd_e1e5b: Option<f64>,
+    tgd1: Option<f64>,
+    tgd2: Option<f64>,
+}
+
+impl GroupDelays {
+    /// Makes a set of terms decoded from a GPS or QZSS LNAV message
+    pub fn from_lnav(tgd: f64) -> GroupDelays {
+        GroupDelays {
+            tgd_lnav: Some(tgd),
+            ..Default::default()
+        }
+    }
+
+    /// Makes a set of terms decoded from a GPS or QZSS CNAV message
+    ///
+    /// The inter-signal corrections are optional since they may be
+    /// unavailable, which is indicated by a special value in the message
+    pub fn from_cnav(
+        tgd: f64,
+        isc_l1ca: Option<f64>,
+        isc_l2c: Option<f64>,
+        isc_l5i5: Option<f64>,
+        isc_l5q5: Option<f64>,
+    ) -> GroupDelays {
+        GroupDelays {
+            tgd_cnav: Some(tgd),
+            isc_l1ca,
+            isc_l2c,
+            isc_l5i5,
+            isc_l5q5,
+            ..Default::default()
+        }
+    }
+
+    /// Makes a set of terms decoded from a Galileo I/NAV or F/NAV message
+    ///
+    /// Either BGD may be unavailable, since I/NAV and F/NAV only carry one of
+    /// the two clock models each.
+    pub fn from_gal(bgd_e1e5a: Option<f64>, bgd_e1e5b: Option<f64>) -> GroupDelays {
+        GroupDelays {
+            bgd_e1e5a,
+            bgd_e1e5b,
+            ..Default::default()
+        }
+    }
+
+    /// Decodes the BGD terms from a Galileo I/NAV word of type 5
+    ///
+    /// Returns `None` if the word isn't of type 5.
+    pub fn decode_gal_inav(word: &[u8; GAL_INAV_CONTENT_BYTE]) -> Option<GroupDelays> {
+        if inav_field(word, 0, 6) != 5 {
+            return None;
+        }
+        let scale = 2f64.powi(-32);
+        Some(GroupDelays::from_gal(
+            Some(inav_signed_field(word, 47, 10) as f64 * scale),
+            Some(inav_signed_field(word, 57, 10) as f64 * scale),
+        ))
+    }
+
+    /// Makes a set of terms decoded from a Beidou D1 or D2 message
+    pub fn from_bds(tgd1: f64, tgd2: f64) -> GroupDelays {
+        GroupDelays {
+            tgd1: Some(tgd1),
+            tgd2: Some(tgd2),
+            ..Default::default()
+        }
+    }
+
+    /// Extracts the group delay terms stored in a keplerian ephemeris
+    ///
+    /// Returns `None` for ephemerides which don't carry group delay terms (i.e.
+    /// GLONASS and SBAS) or which have an invalid signal
+    pub fn from_ephemeris(ephemeris: &Ephemeris) -> Option<GroupDelays> {
+        let constellation = ephemeris.sid().ok()?.to_constellation();
+        // Safety: the kepler terms are the active member of the union for the
+        // constellations listed below, see `Ephemeris::new()`
+        let tgd = unsafe { ephemeris.0.data.kepler.tgd };
+        match constellation {
+            Constellation::Gps => Some(GroupDelays::from_lnav(unsafe { tgd.gps_s[0] } as f64)),
+            Constellation::Qzs => Some(GroupDelays::from_lnav(unsafe { tgd.qzss_s[0] } as f64)),
+            Constellation::Gal => {
+                let bgd = unsafe { tgd.gal_s };
+                Some(GroupDelays::from_gal(
+                    Some(bgd[0] as f64),
+                    Some(bgd[1] as f64),
+                ))
+            }
+            Constellation::Bds => {
+                let bds = unsafe { tgd.bds_s };
+                Some(GroupDelays::from_bds(bds[0] as f64, bds[1] as f64))
+            }
+            Constellation::Glo | Constellation::Sbas => None,
+        }
+    }
+
+    /// Gets the source messages that contributed to this set of terms
+    pub fn sources(&self) -> Vec<GroupDelaySource> {
+        let mut sources = Vec::new();
+        if self.tgd_lnav.is_some() {
+            sources.push(GroupDelaySource::Lnav);
+        }
+        if self.tgd_cnav.is_some() {
+            sources.push(GroupDelaySource::Cnav);
+        }
+        if self.bgd_e1e5a.is_some() || self.bgd_e1e5b.is_some() {
+            sources.push(GroupDelaySource::GalNav);
+        }
+        if self.tgd1.is_some() || self.tgd2.is_some() {
+            sources.push(GroupDelaySource::BdsD1D2);
+        }
+        sources
+    }
+
+    /// Combines this set of terms with a more recently received set
+    ///
+    /// Any terms present in `newer` replace the ones currently held, all other
+    /// terms are kept as they are.
+    pub fn update(&mut self, newer: &GroupDelays) {
+        fn pick(current: &mut Option<f64>, newer: Option<f64>) {
+            if newer.is_some() {
+                *current = newer;
+            }
+        }
+
+        pick(&mut self.tgd_lnav, newer.tgd_lnav);
+        pick(&mut self.tgd_cnav, newer.tgd_cnav);
+        pick(&mut self.isc_l1ca, newer.isc_l1ca);
+        pick(&mut self.isc_l2c, newer.isc_l2c);
+        pick(&mut self.isc_l5i5, newer.isc_l5i5);
+        pick(&mut self.isc_l5q5, newer.isc_l5q5);
+        pick(&mut self.bgd_e1e5a, newer.bgd_e1e5a);
+        pick(&mut self.bgd_e1e5b, newer.bgd_e1e5b);
+        pick(&mut self.tgd1, newer.tgd1);
+        pick(&mut self.tgd2, newer.tgd2);
+    }
+
+    /// Gets the group delay for a particular code, in seconds
+    ///
+    /// The clock offset of the satellite as seen on the given code is the
+    /// broadcast clock offset minus the group delay. Returns `None` if the
+    /// terms needed for the code have not been received.
+    ///
+    /// For GPS and QZSS the inter-signal corrections are always combined with
+    /// the T<sub>GD</sub> from the same CNAV message. The LNAV T<sub>GD</sub>
+    /// is used for the P(Y) codes and for L1 C/A when no CNAV data is
+    /// available. The Galileo E1 and E5b delays are relative to the I/NAV
+    /// clock, and the E5a delay is relative to the F/NAV clock.
+    pub fn group_delay(&self, code: Code) -> Option<f64> {
+        self.group_delay_with_source(code).map(|(delay, _)| delay)
+    }
+
+    /// Gets the group delay for a particular code, in seconds, along with the
+    /// navigation message the terms used came from
+    pub fn group_delay_with_source(&self, code: Code) -> Option<(f64, GroupDelaySource)> {
+        use GroupDelaySource::*;
+
+        let lnav = self.tgd_lnav.map(|tgd| (tgd, Lnav));
+        let tgd_p = lnav.or_else(|| self.tgd_cnav.map(|tgd| (tgd, Cnav)));
+        let cnav = |isc: Option<f64>| Some((self.tgd_cnav? - isc?, Cnav));
+        let gal = |bgd: Option<f64>, scale: f64| bgd.map(|bgd| (scale * bgd, GalNav));
+        let bds = |tgd: Option<f64>| tgd.map(|tgd| (tgd, BdsD1D2));
+
+        match code {
+            Code::GpsL1ca | Code::QzsL1ca => cnav(self.isc_l1ca).or(lnav),
+            Code::GpsL1p => tgd_p,
+            Code::GpsL2p => tgd_p.map(|(tgd, source)| (GAMMA_L1_L2 * tgd, source)),
+            Code::GpsL2cm
+            | Code::GpsL2cl
+            | Code::GpsL2cx
+            | Code::QzsL2cm
+            | Code::QzsL2cl
+            | Code::QzsL2cx => cnav(self.isc_l2c),
+            Code::GpsL5i | Code::QzsL5i => cnav(self.isc_l5i5),
+            Code::GpsL5q | Code::GpsL5x | Code::QzsL5q | Code::QzsL5x => cnav(self.isc_l5q5),
+            Code::GalE1b | Code::GalE1c | Code::GalE1x => gal(self.bgd_e1e5b, 1.0),
+            Code::GalE5i | Code::GalE5q | Code::GalE5x => gal(self.bgd_e1e5a, GAMMA_E1_E5A),
+            Code::GalE7i | Code::GalE7q | Code::GalE7x => gal(self.bgd_e1e5b, GAMMA_E1_E5B),
+            Code::Bds2B1 => bds(self.tgd1),
+            Code::Bds2B2 => bds(self.tgd2),
+            // The D1/D2 clock is referenced to B3I
+            Code::Bds3B3i | Code::Bds3B3q | Code::Bds3B3x => Some((0.0, BdsD1D2)),
+            _ => None,
+        }
+    }
+
+    /// Applies the group delay of a code to the clock offset of a satellite
+    /// state, returning the applied term
+    ///
+    /// Returns `None`, leaving the state unchanged, if the terms needed for
+    /// the code have not been received. The state doesn't record whether a
+    /// group delay has already been applied to it, keep the returned term to
+    /// avoid applying one twice.
+    pub fn apply(&self, code: Code, state: &mut SatelliteState) -> Option<AppliedGroupDelay> {
+        let (delay, source) = self.group_delay_with_source(code)?;
+        state.clock_err -= delay;
+        Some(AppliedGroupDelay {
+            code,
+            delay,
+            source,
+        })
+    }
+}
+
+/// Group delay terms of many satellites
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GroupDelayTable {
+    satellites: HashMap<(Constellation, u16), GroupDelays>,
+}
+
+impl GroupDelayTable {
+    pub fn new() -> GroupDelayTable {
+        GroupDelayTable {
+            satellites: HashMap::new(),
+        }
+    }
+
+    /// Adds newly received terms for a satellite
+    ///
+    /// See [`GroupDelays::update()`] for how the terms are combined
+    pub fn update(&mut self, constellation: Constellation, sat: u16, delays: &GroupDelays) {
+        self.satellites
+            .entry((constellation, sat))
+            .or_default()
+            .update(delays);
+    }
+
+    /// Adds the terms carried by an ephemeris
+    ///
+    /// Returns `false` if the ephemeris doesn't carry group delay terms
+    pub fn update_from_ephemeris(&mut self, ephemeris: &Ephemeris) -> bool {
+        match (ephemeris.sid(), GroupDelays::from_ephemeris(ephemeris)) {
+            (Ok(sid), Some(delays)) => {
+                self.update(sid.to_constellation(), sid.sat(), &delays);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Gets the terms of a satellite
+    pub fn get(&self, constellation: Constellation, sat: u16) -> Option<&GroupDelays> {
+        self.satellites.get(&(constellation, sat))
+    }
+
+    /// Gets the group delay of a signal, in seconds
+    ///
+    /// See [`GroupDelays::group_delay()`] for more details
+    pub fn group_delay(&self, sid: GnssSignal) -> Option<f64> {
+        self.get(sid.to_constellation(), sid.sat())?
+            .group_delay(sid.code())
+    }
+
+    /// Applies the group delay of a signal to the clock offset of its
+    /// satellite's state
+    ///
+    /// See [`GroupDelays::apply()`] for more details
+    pub fn apply(&self, sid: GnssSignal, state: &mut SatelliteState) -> Option<AppliedGroupDelay> {
+        self.get(sid.to_constellation(), sid.sat())?
+            .apply(sid.code(), state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn gps_lnav_only() {
+        let delays = GroupDelays::from_lnav(-1.0e-8);
+        assert_eq!(delays.group_delay(Code::GpsL1ca), Some(-1.0e-8));
+        assert_eq!(delays.group_delay(Code::GpsL1p), Some(-1.0e-8));
+        assert_float_eq!(
+            delays.group_delay(Code::GpsL2p).unwrap(),
+            -1.0e-8 * 1.6469444,
+            abs <= 1e-14
+        );
+        assert_eq!(delays.group_delay(Code::GpsL2cm), None);
+        assert_eq!(delays.group_delay(Code::GpsL5q), None);
+        assert_eq!(delays.sources(), vec![GroupDelaySource::Lnav]);
+    }
+
+    #[test]
+    fn gps_lnav_and_cnav() {
+        let mut delays = GroupDelays::from_lnav(-1.0e-8);
+        delays.update(&GroupDelays::from_cnav(
+            -1.1e-8,
+            Some(2.0e-9),
+            Some(3.0e-9),
+            Some(4.0e-9),
+            None,
+        ));
+
+        // ISCs are always paired with the CNAV TGD
+        assert_float_eq!(
+            delays.group_delay(Code::GpsL1ca).unwrap(),
+            -1.3e-8,
+            abs <= 1e-18
+        );
+        assert_float_eq!(
+            delays.group_delay(Code::GpsL2cl).unwrap(),
+            -1.4e-8,
+            abs <= 1e-18
+        );
+        assert_float_eq!(
+            delays.group_delay(Code::GpsL5i).unwrap(),
+            -1.5e-8,
+            abs <= 1e-18
+        );
+        assert_eq!(delays.group_delay(Code::GpsL5q), None);
+        // The P(Y) codes keep using the LNAV TGD
+        assert_eq!(delays.group_delay(Code::GpsL1p), Some(-1.0e-8));
+        assert_eq!(
+            delays.sources(),
+            vec![GroupDelaySource::Lnav, GroupDelaySource::Cnav]
+        );
+    }
+
+    #[test]
+    fn galileo() {
+        let mut delays = GroupDelays::from_gal(None, Some(2.0e-9));
+        assert_eq!(delays.group_delay(Code::GalE1b), Some(2.0e-9));
+        assert_eq!(delays.group_delay(Code::GalE5q), None);
+
+        delays.update(&GroupDelays::from_gal(Some(1.0e-9), None));
+        assert_eq!(delays.group_delay(Code::GalE1c), Some(2.0e-9));
+        assert_float_eq!(
+            delays.group_delay(Code::GalE5q).unwrap(),
+            1.0e-9 * GAMMA_E1_E5A,
+            abs <= 1e-18
+        );
+        assert_float_eq!(
+            delays.group_delay(Code::GalE7i).unwrap(),
+            2.0e-9 * GAMMA_E1_E5B,
+            abs <= 1e-18
+        );
+        assert_eq!(delays.group_delay(Code::GalE6b), None);
+    }
+
+    #[test]
+    fn galileo_inav_word() {
+        let mut word = [0u8; GAL_INAV_CONTENT_BYTE];
+        fn set(word: &mut [u8], start: usize, len: usize, value: i64) {
+            for k in 0..len {
+                let bit = start + k;
+                if (value >> (len - 1 - k)) & 1 == 1 {
+                    word[bit / 8] |= 1 << (7 - bit % 8);
+                }
+            }
+        }
+        set(&mut word, 0, 6, 5);
+        set(&mut word, 47, 10, -12);
+        set(&mut word, 57, 10, 37);
+
+        let delays = GroupDelays::decode_gal_inav(&word).unwrap();
+        assert_eq!(
+            delays.group_delay(Code::GalE1b),
+            Some(37.0 * 2f64.powi(-32))
+        );
+        assert_float_eq!(
+            delays.group_delay(Code::GalE5i).unwrap(),
+            -12.0 * 2f64.powi(-32) * GAMMA_E1_E5A,
+            abs <= 1e-18
+        );
+
+        word[0] = 10 << 2;
+        assert_eq!(GroupDelays::decode_gal_inav(&word), None);
+    }
+
+    #[test]
+    fn apply_to_state() {
+        let state = || SatelliteState {
+            pos: crate::coords::ECEF::default(),
+            vel: crate::coords::ECEF::default(),
+            acc: crate::coords::ECEF::default(),
+            clock_err: 1.0e-4,
+            clock_rate_err: 0.0,
+            iodc: 0,
+            iode: 0,
+        };
+        let mut delays = GroupDelays::from_lnav(-1.0e-8);
+        delays.update(&GroupDelays::from_cnav(
+            -1.1e-8,
+            None,
+            Some(3.0e-9),
+            None,
+            None,
+        ));
+
+        let mut l2p = state();
+        let applied = delays.apply(Code::GpsL2p, &mut l2p).unwrap();
+        assert_float_eq!(l2p.clock_err, 1.0e-4 + 1.0e-8 * GAMMA_L1_L2, abs <= 1e-18);
+        assert_eq!(applied.code, Code::GpsL2p);
+        assert_eq!(applied.source, GroupDelaySource::Lnav);
+        assert_float_eq!(applied.delay, -1.0e-8 * GAMMA_L1_L2, abs <= 1e-18);
+
+        let mut l2c = state();
+        let applied = delays.apply(Code::GpsL2cm, &mut l2c);
+        assert_float_eq!(l2c.clock_err, 1.0e-4 + 1.4e-8, abs <= 1e-18);
+        assert_eq!(
+            applied.map(|applied| applied.source),
+            Some(GroupDelaySource::Cnav)
+        );
+
+        let mut l5 = state();
+        assert!(delays.apply(Code::GpsL5q, &mut l5).is_none());
+        assert_eq!(l5.clock_err, 1.0e-4);
+    }
+
+    #[test]
+    fn table() {
+        let mut table = GroupDelayTable::new();
+        table.update(
+            Constellation::Bds,
+            10,
+            &GroupDelays::from_bds(1.0e-9, 2.0e-9),
+        );
+        table.update(Constellation::Gps, 10, &GroupDelays::from_lnav(3.0e-9));
+
+        let b1 = GnssSignal::new(10, Code::Bds2B1).unwrap();
+        let b2 = GnssSignal::new(10, Code::Bds2B2).unwrap();
+        let b3 = GnssSignal::new(10, Code::Bds3B3i).unwrap();
+        let l1 = GnssSignal::new(10, Code::GpsL1ca).unwrap();
+        let other = GnssSignal::new(11, Code::GpsL1ca).unwrap();
+        assert_eq!(table.group_delay(b1), Some(1.0e-9));
+        assert_eq!(table.group_delay(b2), Some(2.0e-9));
+        assert_eq!(table.group_delay(b3), Some(0.0));
+        assert_eq!(table.group_delay(l1), Some(3.0e-9));
+        assert_eq!(table.group_delay(other), None);
+    }
+}
@@ -0,0 +1,513 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Keplerian ephemeris evaluation
+//!
+//! GPS, Galileo, BeiDou and QZSS satellites broadcast their orbits as
+//! Keplerian elements with harmonic correction terms. This is a pure Rust
+//! implementation of the evaluation done by libswiftnav, computing the
+//! satellite position, velocity, acceleration and clock offset.
+//!
+//! The clock offset includes the relativistic correction but not the group
+//! delay, see [`group_delay`](crate::ephemeris::group_delay) for that.
+//!
+//! # References
+//!   * IS-GPS-200D, Section 20.3.3.3.3.1 and Table 20-IV
+//!   * Galileo OS SIS ICD, Issue 1.3, Section 5.1.1
+//!   * BeiDou SIS ICD, Version 2.1, Section 5.2.4.12
+
+use crate::coords::ECEF;
+use crate::ephemeris::{Ephemeris, SatelliteState};
+use crate::signal::Constellation;
+use crate::time::GpsTime;
+use crate::SPEED_OF_LIGHT;
+
+/// Earth's gravitational constant used by GPS and QZSS, in m³/s²
+const GPS_GM: f64 = 3.986_005e14;
+/// Earth's gravitational constant used by Galileo and BeiDou, in m³/s²
+const GAL_BDS_GM: f64 = 3.986_004_418e14;
+/// Earth's rotation rate used by GPS, QZSS and Galileo, in rad/s
+const GPS_OMEGAE_DOT: f64 = 7.292_115_146_7e-5;
+/// Earth's rotation rate used by BeiDou, in rad/s
+const BDS_OMEGAE_DOT: f64 = 7.292_115e-5;
+/// Offset between BeiDou time and GPS time, in seconds
+const BDS_SECOND_TO_GPS_SECOND: f64 = 14.0;
+const WEEK_SECS: f64 = 604_800.0;
+/// Inclination of the orbital frame of BeiDou GEO satellites, in radians
+const BDS_GEO_INCLINATION: f64 = -5.0 * std::f64::consts::PI / 180.0;
+
+/// The broadcast orbit of a GPS, Galileo, BeiDou or QZSS satellite
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct KeplerEphemeris {
+    constellation: Constellation,
+    sat: u16,
+    toe: GpsTime,
+    toc: GpsTime,
+    crc: f64,
+    crs: f64,
+    cuc: f64,
+    cus: f64,
+    cic: f64,
+    cis: f64,
+    dn: f64,
+    m0: f64,
+    ecc: f64,
+    sqrta: f64,
+    omega0: f64,
+    omegadot: f64,
+    w: f64,
+    inc: f64,
+    inc_dot: f64,
+    af0: f64,
+    af1: f64,
+    af2: f64,
+    iodc: u16,
+    iode: u16,
+}
+
+impl KeplerEphemeris {
+    /// Gets the Keplerian terms of an ephemeris
+    ///
+    /// Returns `None` if the ephemeris isn't for a GPS, Galileo, BeiDou or
+    /// QZSS satellite
+    pub fn from_ephemeris(ephemeris: &Ephemeris) -> Option<KeplerEphemeris> {
+        let sid = ephemeris.sid().ok()?;
+        let constellation = sid.to_constellation();
+        if !matches!(
+            constellation,
+            Constellation::Gps | Constellation::Gal | Constellation::Bds | Constellation::Qzs
+        ) {
+            return None;
+        }
+        // Safe because the constellation determines the active union member
+        let k = unsafe { ephemeris.0.data.kepler };
+        Some(KeplerEphemeris {
+            constellation,
+            sat: sid.sat(),
+            toe: GpsTime::new_unchecked(ephemeris.0.toe.wn, ephemeris.0.toe.tow),
+            toc: GpsTime::new_unchecked(k.toc.wn, k.toc.tow),
+            crc: k.crc,
+            crs: k.crs,
+            cuc: k.cuc,
+            cus: k.cus,
+            cic: k.cic,
+            cis: k.cis,
+            dn: k.dn,
+            m0: k.m0,
+            ecc: k.ecc,
+            sqrta: k.sqrta,
+            omega0: k.omega0,
+            omegadot: k.omegadot,
+            w: k.w,
+            inc: k.inc,
+            inc_dot: k.inc_dot,
+            af0: k.af0,
+            af1: k.af1,
+            af2: k.af2,
+            iodc: k.iodc,
+            iode: k.iode,
+        })
+    }
+
+    /// Makes an orbit from the reduced set of elements of an almanac, which
+    /// has no harmonic corrections, mean motion correction or inclination rate
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn from_almanac_terms(
+        constellation: Constellation,
+        sat: u16,
+        toa: GpsTime,
+        sqrta: f64,
+        ecc: f64,
+        inc: f64,
+        omega0: f64,
+        omegadot: f64,
+        w: f64,
+        m0: f64,
+        af0: f64,
+        af1: f64,
+    ) -> KeplerEphemeris {
+        KeplerEphemeris {
+            constellation,
+            sat,
+            toe: toa,
+            toc: toa,
+            crc: 0.0,
+            crs: 0.0,
+            cuc: 0.0,
+            cus: 0.0,
+            cic: 0.0,
+            cis: 0.0,
+            dn: 0.0,
+            m0,
+            ecc,
+            sqrta,
+            omega0,
+            omegadot,
+            w,
+            inc,
+            inc_dot: 0.0,
+            af0,
+            af1,
+            af2: 0.0,
+            iodc: 0,
+            iode: 0,
+        }
+    }
+
+    pub fn constellation(&self) -> Constellation {
+        self.constellation
+    }
+
+    pub fn toe(&self) -> GpsTime {
+        self.toe
+    }
+
+    pub fn toc(&self) -> GpsTime {
+        self.toc
+    }
+
+    /// Checks if the satellite is a BeiDou satellite in geostationary orbit,
+    /// which uses a different orbital frame
+    pub fn is_bds_geo(&self) -> bool {
+        self.constellation == Constellation::Bds && (self.sat <= 5 || self.sat >= 59)
+    }
+
+    /// Calculates the satellite position, velocity, acceleration and clock
+    /// offset at a time
+    ///
+    /// No check is made that `t` is within the fit interval of the ephemeris.
+    pub fn calc_satellite_state(&self, t: GpsTime) -> SatelliteState {
+        let (gm, omegae_dot) = match self.constellation {
+            Constellation::Gal => (GAL_BDS_GM, GPS_OMEGAE_DOT),
+            Constellation::Bds => (GAL_BDS_GM, BDS_OMEGAE_DOT),
+            _ => (GPS_GM, GPS_OMEGAE_DOT),
+        };
+        let relativistic_f = -2.0 * gm.sqrt() / (SPEED_OF_LIGHT * SPEED_OF_LIGHT);
+
+        // Clock polynomial
+        let dt = t.diff(&self.toc);
+        let mut clock_err = self.af0 + dt * (self.af1 + dt * self.af2);
+        let mut clock_rate_err = self.af1 + 2.0 * dt * self.af2;
+
+        let dt = t.diff(&self.toe);
+
+        // Solve Kepler's equation for the eccentric anomaly
+        let a = self.sqrta * self.sqrta;
+        let ma_dot = (gm / (a * a * a)).sqrt() + self.dn;
+        let ma = self.m0 + ma_dot * dt;
+        let mut ea = ma;
+        for _ in 0..10 {
+            let delta = (ma - ea + self.ecc * ea.sin()) / (1.0 - self.ecc * ea.cos());
+            ea += delta;
+            if delta.abs() < 1e-14 {
+                break;
+            }
+        }
+        let ea_dot = ma_dot / (1.0 - self.ecc * ea.cos());
+
+        clock_err += relativistic_f * self.ecc * self.sqrta * ea.sin();
+        clock_rate_err += relativistic_f * self.ecc * self.sqrta * ea.cos() * ea_dot;
+
+        // Argument of latitude, radius and inclination with the harmonic
+        // corrections applied
+        let temp = (1.0 - self.ecc * self.ecc).sqrt();
+        let al = (temp * ea.sin()).atan2(ea.cos() - self.ecc) + self.w;
+        let al_dot = temp * ea_dot / (1.0 - self.ecc * ea.cos());
+        let (sin_2al, cos_2al) = (2.0 * al).sin_cos();
+
+        let cal = al + self.cus * sin_2al + self.cuc * cos_2al;
+        let cal_dot = al_dot * (1.0 + 2.0 * (self.cus * cos_2al - self.cuc * sin_2al));
+        let r = a * (1.0 - self.ecc * ea.cos()) + self.crc * cos_2al + self.crs * sin_2al;
+        let r_dot = a * self.ecc * ea.sin() * ea_dot
+            + 2.0 * al_dot * (self.crs * cos_2al - self.crc * sin_2al);
+        let inc = self.inc + self.inc_dot * dt + self.cic * cos_2al + self.cis * sin_2al;
+        let inc_dot = self.inc_dot + 2.0 * al_dot * (self.cis * cos_2al - self.cic * sin_2al);
+
+        // Position and velocity in the orbital plane
+        let x = r * cal.cos();
+        let y = r * cal.sin();
+        let x_dot = r_dot * cal.cos() - y * cal_dot;
+        let y_dot = r_dot * cal.sin() + x * cal_dot;
+
+        // The longitude of the ascending node is referenced to the start of the
+        // week of the constellation's own time
+        let toe_tow = if self.constellation == Constellation::Bds {
+            (self.toe.tow() - BDS_SECOND_TO_GPS_SECOND).rem_euclid(WEEK_SECS)
+        } else {
+            self.toe.tow()
+        };
+
+        let (pos, vel) = if self.is_bds_geo() {
+            // GEO satellites are computed in an inertial frame which is then
+            // inclined and rotated into ECEF
+            let om = self.omega0 + self.omegadot * dt - omegae_dot * toe_tow;
+            let (pos, vel) = orbital_to_frame(x, y, x_dot, y_dot, inc, inc_dot, om, self.omegadot);
+            let (sin_x, cos_x) = BDS_GEO_INCLINATION.sin_cos();
+            let incline = |v: [f64; 3]| {
+                [
+                    v[0],
+                    cos_x * v[1] + sin_x * v[2],
+                    -sin_x * v[1] + cos_x * v[2],
+                ]
+            };
+            let (pos, vel) = (incline(pos), incline(vel));
+            let (sin_z, cos_z) = (omegae_dot * dt).sin_cos();
+            let rotated_pos = [
+                cos_z * pos[0] + sin_z * pos[1],
+                -sin_z * pos[0] + cos_z * pos[1],
+                pos[2],
+            ];
+            let rotated_vel = [
+                cos_z * vel[0] + sin_z * vel[1] + omegae_dot * rotated_pos[1],
+                -sin_z * vel[0] + cos_z * vel[1] - omegae_dot * rotated_pos[0],
+                vel[2],
+            ];
+            (rotated_pos, rotated_vel)
+        } else {
+            let om = self.omega0 + dt * (self.omegadot - omegae_dot) - omegae_dot * toe_tow;
+            orbital_to_frame(
+                x,
+                y,
+                x_dot,
+                y_dot,
+                inc,
+                inc_dot,
+                om,
+                self.omegadot - omegae_dot,
+            )
+        };
+
+        // Gravitational acceleration, with the centrifugal and coriolis terms
+        // of the rotating frame
+        let r3 = r * r * r;
+        let w2 = omegae_dot * omegae_dot;
+        let acc = ECEF::new(
+            -gm * pos[0] / r3 + w2 * pos[0] + 2.0 * omegae_dot * vel[1],
+            -gm * pos[1] / r3 + w2 * pos[1] - 2.0 * omegae_dot * vel[0],
+            -gm * pos[2] / r3,
+        );
+
+        SatelliteState {
+            pos: ECEF::from_array(&pos),
+            vel: ECEF::from_array(&vel),
+            acc,
+            clock_err,
+            clock_rate_err,
+            iodc: self.iodc,
+            iode: self.iode as u8,
+        }
+    }
+}
+
+/// Rotates a position and velocity from the orbital plane into the frame the
+/// longitude of the ascending node is given in
+#[allow(clippy::too_many_arguments)]
+fn orbital_to_frame(
+    x: f64,
+    y: f64,
+    x_dot: f64,
+    y_dot: f64,
+    inc: f64,
+    inc_dot: f64,
+    om: f64,
+    om_dot: f64,
+) -> ([f64; 3], [f64; 3]) {
+    let (sin_om, cos_om) = om.sin_cos();
+    let (sin_inc, cos_inc) = inc.sin_cos();
+
+    let pos = [
+        x * cos_om - y * cos_inc * sin_om,
+        x * sin_om + y * cos_inc * cos_om,
+        y * sin_inc,
+    ];
+    let vel = [
+        -om_dot * pos[1] + x_dot * cos_om - y_dot * cos_inc * sin_om
+            + y * sin_inc * sin_om * inc_dot,
+        om_dot * pos[0] + x_dot * sin_om + y_dot * cos_inc * cos_om
+            - y * sin_inc * inc_dot * cos_om,
+        y * cos_inc * inc_dot + y_dot * sin_inc,
+    ];
+    (pos, vel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ephemeris::EphemerisTerms;
+    use crate::signal::{Code, GnssSignal};
+    use float_eq::assert_float_eq;
+    use std::time::Duration;
+
+    fn gps_ephemeris() -> Ephemeris {
+        Ephemeris::new(
+            GnssSignal::new(9, Code::GpsL1ca).unwrap(),
+            GpsTime::new_unchecked(2022, 460800.0),
+            2.0,
+            14400,
+            1,
+            0,
+            0,
+            EphemerisTerms::new_kepler(
+                Constellation::Gps,
+                [5.122274160385132e-09, 0.0],
+                234.375,
+                -54.46875,
+                -2.7976930141448975e-06,
+                5.9623271226882935e-06,
+                -2.0489096641540527e-08,
+                1.0430812835693359e-07,
+                4.4702933163541758e-09,
+                -0.38267963709463544,
+                0.0094148930022493005,
+                5153.6586399078369,
+                1.0410897787730672,
+                -7.9046149006334169e-09,
+                -2.1106093003101522,
+                0.9653939455366247,
+                -3.1143515413542874e-10,
+                -0.00024583423510193825,
+                -2.0463630789890885e-12,
+                0.0,
+                GpsTime::new_unchecked(2022, 460800.0),
+                45,
+                45,
+            ),
+        )
+    }
+
+    fn bds_geo_ephemeris() -> Ephemeris {
+        Ephemeris::new(
+            GnssSignal::new(3, Code::Bds2B1).unwrap(),
+            GpsTime::new_unchecked(2091, 460814.0),
+            2.0,
+            3600,
+            1,
+            0,
+            0,
+            EphemerisTerms::new_kepler(
+                Constellation::Bds,
+                [1.4e-08, 1.4e-08],
+                -454.65625,
+                -80.90625,
+                -2.6836059987545013e-06,
+                -1.5720538794994354e-05,
+                3.3527612686157227e-08,
+                -1.3457611203193665e-07,
+                -1.0250427007770538e-10,
+                2.3548012447837604,
+                0.00072652287781238556,
+                6493.4349212646484,
+                -2.9536747396297473,
+                1.1107605240924082e-09,
+                -0.66389045689657834,
+                0.094249813630493885,
+                -1.5250001939939256e-10,
+                -0.00019508344121277332,
+                4.2419845006557465e-11,
+                0.0,
+                GpsTime::new_unchecked(2091, 460814.0),
+                257,
+                1,
+            ),
+        )
+    }
+
+    fn gal_ephemeris() -> Ephemeris {
+        Ephemeris::new(
+            GnssSignal::new(8, Code::GalE1b).unwrap(),
+            GpsTime::new_unchecked(2090, 135000.0),
+            3.12,
+            14400,
+            1,
+            0,
+            0,
+            EphemerisTerms::new_kepler(
+                Constellation::Gal,
+                [-5.5879354476928711e-09, -6.5192580223083496e-09],
+                62.375,
+                -54.0625,
+                -2.3748725652694702e-06,
+                1.2902542948722839e-05,
+                7.4505805969238281e-09,
+                4.6566128730773926e-08,
+                2.9647663515616992e-09,
+                1.1731263781996162,
+                0.00021702353842556477,
+                5440.6276874542236,
+                0.7101536200630526,
+                -5.363080536688408e-09,
+                0.39999676368790066,
+                0.95957029480011957,
+                4.3751822439020375e-10,
+                0.0062288472545333198,
+                -5.4427573559223666e-12,
+                0.0,
+                GpsTime::new_unchecked(2090, 135000.0),
+                97,
+                97,
+            ),
+        )
+    }
+
+    fn assert_states_eq(a: &SatelliteState, b: &SatelliteState) {
+        for (a, b) in [(a.pos, b.pos), (a.vel, b.vel)].iter() {
+            assert_float_eq!(a.x(), b.x(), abs <= 1e-6);
+            assert_float_eq!(a.y(), b.y(), abs <= 1e-6);
+            assert_float_eq!(a.z(), b.z(), abs <= 1e-6);
+        }
+        assert_float_eq!(a.clock_err, b.clock_err, abs <= 1e-15);
+        assert_float_eq!(a.clock_rate_err, b.clock_rate_err, abs <= 1e-18);
+    }
+
+    #[test]
+    fn matches_libswiftnav() {
+        for ephemeris in [gps_ephemeris(), gal_ephemeris(), bds_geo_ephemeris()].iter() {
+            let kepler = KeplerEphemeris::from_ephemeris(ephemeris).unwrap();
+            for offset in [0, 600, 1700].iter() {
+                let t = kepler.toe() + Duration::from_secs(*offset);
+                let expected = ephemeris.calc_satellite_state(t).unwrap();
+                assert_states_eq(&kepler.calc_satellite_state(t), &expected);
+            }
+        }
+    }
+
+    #[test]
+    fn velocity_matches_position() {
+        for ephemeris in [gps_ephemeris(), gal_ephemeris(), bds_geo_ephemeris()].iter() {
+            let kepler = KeplerEphemeris::from_ephemeris(ephemeris).unwrap();
+            let t = kepler.toe() + Duration::from_secs(1000);
+            let state = kepler.calc_satellite_state(t);
+            let before = kepler.calc_satellite_state(t - Duration::from_millis(500));
+            let after = kepler.calc_satellite_state(t + Duration::from_millis(500));
+
+            let velocity = after.pos - before.pos;
+            assert_float_eq!(state.vel.x(), velocity.x(), abs <= 1e-3);
+            assert_float_eq!(state.vel.y(), velocity.y(), abs <= 1e-3);
+            assert_float_eq!(state.vel.z(), velocity.z(), abs <= 1e-3);
+            let acceleration = after.vel - before.vel;
+            assert_float_eq!(state.acc.x(), acceleration.x(), abs <= 1e-4);
+            assert_float_eq!(state.acc.y(), acceleration.y(), abs <= 1e-4);
+            assert_float_eq!(state.acc.z(), acceleration.z(), abs <= 1e-4);
+
+            let clock_rate = after.clock_err - before.clock_err;
+            assert_float_eq!(state.clock_rate_err, clock_rate, abs <= 1e-15);
+        }
+
+        let kepler = KeplerEphemeris::from_ephemeris(&gps_ephemeris()).unwrap();
+        let radius = kepler.calc_satellite_state(kepler.toe()).pos;
+        let radius = radius
+            .as_array_ref()
+            .iter()
+            .map(|v| v * v)
+            .sum::<f64>()
+            .sqrt();
+        assert_float_eq!(radius, 26_560_000.0, abs <= 300_000.0);
+        assert!(!kepler.is_bds_geo());
+    }
+}
@@ -15,20 +15,51 @@
 //!
 //! Broadcast ephemerides are only valid of a particular period of time, and the
 //! constellations will update the ephemerides regularly to make sure they are
-//! always valid when they need to be.
+//! always valid when they need to be. The [`store::EphemerisStore`] keeps the
+//! ephemerides of many satellites and picks the right one for a given time.
 
+pub mod almanac;
+pub mod glonass;
+pub mod group_delay;
+pub mod kepler;
+pub mod store;
+
+use self::group_delay::{AppliedGroupDelay, GroupDelays};
 use crate::{
     coords::{AzimuthElevation, ECEF},
     signal::{Code, Constellation, GnssSignal, InvalidGnssSignal},
-    time::GpsTime,
+    time::{nearest_week, GgtoParams, GpsTime, GPS_WEEK_MODULUS},
 };
 use std::error::Error;
 use std::fmt;
+use std::time::Duration;
 
 /// Number of bytes in  the Galileo INAV message
 // TODO(jbangelo) bindgen doesn't catch this variable on linux for some reason
 pub const GAL_INAV_CONTENT_BYTE: usize = (128 + 8 - 1) / 8;
 
+/// Reads an unsigned big endian bit field of a Galileo I/NAV word
+pub(crate) fn inav_field(word: &[u8; GAL_INAV_CONTENT_BYTE], start: usize, len: usize) -> u64 {
+    (start..start + len).fold(0, |acc, bit| {
+        (acc << 1) | u64::from((word[bit / 8] >> (7 - bit % 8)) & 1)
+    })
+}
+
+/// Reads a two's complement signed big endian bit field of a Galileo I/NAV
+/// word
+pub(crate) fn inav_signed_field(
+    word: &[u8; GAL_INAV_CONTENT_BYTE],
+    start: usize,
+    len: usize,
+) -> i64 {
+    let value = inav_field(word, start, len) as i64;
+    if value & (1 << (len - 1)) != 0 {
+        value - (1 << len)
+    } else {
+        value
+    }
+}
+
 /// Different ways an ephemeris can be invalid
 #[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub enum InvalidEphemeris {
@@ -93,6 +124,34 @@ impl Status {
     }
 }
 
+/// Usability of an ephemeris at a particular time
+///
+/// Unlike [`Status`] the reason an ephemeris can't be used comes with the
+/// details needed to decide how to degrade, e.g. keep using a slightly
+/// expired ephemeris with a larger error estimate.
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub enum Validity {
+    /// The time is within the fit interval of a healthy ephemeris
+    Valid,
+    /// The time is after the end of the fit interval, `age` is the time since
+    /// the time of ephemeris
+    Expired { age: Duration },
+    /// The time is before the start of the fit interval
+    NotYetValid,
+    /// The satellite is marked as unhealthy, `flags` are the health bits as
+    /// stored in the ephemeris
+    Unhealthy { flags: u8 },
+    /// The ephemeris isn't complete, it was not decoded successfully or has
+    /// no reference time
+    Invalid,
+}
+
+impl Validity {
+    pub fn is_valid(&self) -> bool {
+        *self == Validity::Valid
+    }
+}
+
 /// Orbital terms of an ephemeris
 #[derive(Clone)]
 pub enum EphemerisTerms {
@@ -209,6 +268,7 @@ impl EphemerisTerms {
 }
 
 /// Representation of full ephemeris
+#[derive(Clone)]
 pub struct Ephemeris(swiftnav_sys::ephemeris_t);
 
 impl Ephemeris {
@@ -255,6 +315,16 @@ impl Ephemeris {
         })
     }
 
+    /// Sets the signal of an ephemeris decoded from navigation data which
+    /// doesn't identify the satellite
+    pub(crate) fn set_sid(&mut self, sid: GnssSignal) {
+        self.0.sid = sid.to_gnss_signal_t();
+    }
+
+    pub(crate) fn set_valid(&mut self, valid: bool) {
+        self.0.valid = u8::from(valid);
+    }
+
     /// Decode ephemeris from L1 C/A GPS navigation message frames.
     ///
     /// This function does not check for parity errors. You should check the
@@ -275,6 +345,28 @@ impl Ephemeris {
         e
     }
 
+    /// Decode ephemeris from L1 C/A GPS navigation message frames, resolving
+    /// the 10 bit week number against a reference time
+    ///
+    /// [`Ephemeris::decode_gps()`] resolves the week number with a reference
+    /// week compiled into the library, which stops working about 10 years
+    /// later. This function instead picks the week closest to `reference`,
+    /// see [`GpsTime::from_truncated_wn()`].
+    pub fn decode_gps_near(
+        frame_words: &[[u32; 8]; 3],
+        tot_tow: f64,
+        reference: &GpsTime,
+    ) -> Ephemeris {
+        let mut e = Ephemeris::decode_gps(frame_words, tot_tow);
+        let resolve =
+            |wn: i16| nearest_week(reference.wn(), i32::from(wn), i32::from(GPS_WEEK_MODULUS));
+        e.0.toe.wn = resolve(e.0.toe.wn);
+        unsafe {
+            e.0.data.kepler.toc.wn = resolve(e.0.data.kepler.toc.wn);
+        }
+        e
+    }
+
     /// Decodes Beidou D1 ephemeris.
     /// `words` should contain subframes (FraID) 1,2,3.
     pub fn decode_bds(words: &[[u32; 10]; 3], sid: GnssSignal) -> Ephemeris {
@@ -333,6 +425,59 @@ impl Ephemeris {
         Ok(sat)
     }
 
+    /// Calculate satellite position, velocity and clock offset from ephemeris,
+    /// with the clock offset as seen on a particular code
+    ///
+    /// The broadcast clock offset is referenced to a particular signal or
+    /// combination of signals, the group delay terms carried by the ephemeris
+    /// are applied to get the clock offset for `code`, see
+    /// [`GroupDelays::group_delay()`]. The applied term is returned along with
+    /// the satellite state. When the ephemeris doesn't carry the terms needed
+    /// for `code` the clock offset is left as broadcast, e.g. for GLONASS, or
+    /// for the GPS L2C and L5 codes whose inter-signal corrections are only
+    /// broadcast in CNAV. Use [`GroupDelayTable::apply()`](group_delay::GroupDelayTable::apply)
+    /// in that case.
+    pub fn calc_satellite_state_for_code(
+        &self,
+        t: GpsTime,
+        code: Code,
+    ) -> Result<(SatelliteState, Option<AppliedGroupDelay>), InvalidEphemeris> {
+        let mut state = self.calc_satellite_state(t)?;
+        let applied =
+            GroupDelays::from_ephemeris(self).and_then(|delays| delays.apply(code, &mut state));
+        Ok((state, applied))
+    }
+
+    /// Calculate the satellite state at each of several times
+    ///
+    /// The results line up with `times`, see
+    /// [`Ephemeris::calc_satellite_state()`].
+    pub fn calc_satellite_states(
+        &self,
+        times: &[GpsTime],
+    ) -> Vec<Result<SatelliteState, InvalidEphemeris>> {
+        times
+            .iter()
+            .map(|t| self.calc_satellite_state(*t))
+            .collect()
+    }
+
+    /// Calculate the satellite state at each of several times, spreading the
+    /// work over the rayon thread pool
+    ///
+    /// Gives the same results as [`Ephemeris::calc_satellite_states()`].
+    #[cfg(feature = "rayon")]
+    pub fn calc_satellite_states_par(
+        &self,
+        times: &[GpsTime],
+    ) -> Vec<Result<SatelliteState, InvalidEphemeris>> {
+        use rayon::prelude::*;
+        times
+            .par_iter()
+            .map(|t| self.calc_satellite_state(*t))
+            .collect()
+    }
+
     /// Calculate the azimuth and elevation of a satellite from a reference
     /// position given the satellite ephemeris.
     pub fn calc_satellite_az_el(
@@ -393,6 +538,60 @@ impl Ephemeris {
         GnssSignal::from_gnss_signal_t(self.0.sid)
     }
 
+    /// Gets the reference time of the orbit
+    pub fn toe(&self) -> GpsTime {
+        GpsTime::new_unchecked(self.0.toe.wn, self.0.toe.tow)
+    }
+
+    /// Gets the issue of data of the orbit: the IODE of GPS and QZSS, the
+    /// IODnav of Galileo, the AODE of BeiDou or the IOD of GLONASS
+    ///
+    /// Returns `None` for SBAS ephemerides, which have no issue of data, and
+    /// for ephemerides without a valid signal.
+    pub fn iod(&self) -> Option<u16> {
+        // Safe because the constellation determines the active union member
+        match self.sid().ok()?.to_constellation() {
+            Constellation::Gps | Constellation::Gal | Constellation::Bds | Constellation::Qzs => {
+                Some(unsafe { self.0.data.kepler.iode })
+            }
+            Constellation::Glo => Some(u16::from(unsafe { self.0.data.glo.iod })),
+            Constellation::Sbas => None,
+        }
+    }
+
+    /// Gets the issue of data of the clock terms: the IODC of GPS and QZSS,
+    /// the IODnav of Galileo or the AODC of BeiDou
+    ///
+    /// Returns `None` for GLONASS and SBAS ephemerides.
+    pub fn iodc(&self) -> Option<u16> {
+        match self.sid().ok()?.to_constellation() {
+            Constellation::Gps | Constellation::Gal | Constellation::Bds | Constellation::Qzs => {
+                // Safe because the constellation determines the active union
+                // member
+                Some(unsafe { self.0.data.kepler.iodc })
+            }
+            _ => None,
+        }
+    }
+
+    /// Gets the frequency channel number of a GLONASS ephemeris, from -7 to 6
+    ///
+    /// Returns `None` if the ephemeris isn't for a GLONASS satellite, or the
+    /// frequency channel isn't valid.
+    pub fn glo_fcn(&self) -> Option<i8> {
+        if self.sid().ok()?.to_constellation() != Constellation::Glo {
+            return None;
+        }
+        // Safe because the constellation determines the active union member.
+        // The channel is stored offset by 8, as in libswiftnav.
+        let fcn = unsafe { self.0.data.glo.fcn };
+        if (1..=14).contains(&fcn) {
+            Some(fcn as i8 - 8)
+        } else {
+            None
+        }
+    }
+
     /// Gets the status of an ephemeris - is the ephemeris invalid, unhealthy,
     /// or has some other condition which makes it unusable?
     pub fn status(&self) -> Status {
@@ -405,6 +604,49 @@ impl Ephemeris {
         })
     }
 
+    /// Checks whether the ephemeris can be used at a particular time, and if
+    /// not why
+    ///
+    /// The fit interval is centered on the time of ephemeris. Ephemerides
+    /// decoded without a fit interval use the nominal interval of their
+    /// constellation: 4 hours for GPS, QZSS and Galileo, 3 hours for BeiDou
+    /// and 30 minutes for GLONASS. The health bits are interpreted per
+    /// constellation by the decoders, any bit set marks the satellite as
+    /// unhealthy.
+    pub fn validity_at(&self, t: GpsTime) -> Validity {
+        if self.0.valid == 0 || self.0.toe.wn <= 0 {
+            return Validity::Invalid;
+        }
+        let constellation = match self.sid() {
+            Ok(sid) => sid.to_constellation(),
+            Err(_) => return Validity::Invalid,
+        };
+        if self.0.health_bits != 0 {
+            return Validity::Unhealthy {
+                flags: self.0.health_bits,
+            };
+        }
+        let fit_interval = match (self.0.fit_interval, constellation) {
+            (0, Constellation::Gps | Constellation::Qzs | Constellation::Gal) => 4 * 3600,
+            (0, Constellation::Bds) => 3 * 3600,
+            (0, Constellation::Glo) => 30 * 60,
+            (0, _) => return Validity::Invalid,
+            (fit_interval, _) => fit_interval,
+        };
+        let toe = GpsTime::new_unchecked(self.0.toe.wn, self.0.toe.tow);
+        let dt = t.diff(&toe);
+        let half_interval = f64::from(fit_interval) / 2.0;
+        if dt < -half_interval {
+            Validity::NotYetValid
+        } else if dt > half_interval {
+            Validity::Expired {
+                age: Duration::from_secs_f64(dt),
+            }
+        } else {
+            Validity::Valid
+        }
+    }
+
     /// Is this ephemeris usable?
     pub fn is_valid_at_time(&self, t: GpsTime) -> bool {
         let result = unsafe { swiftnav_sys::ephemeris_valid(&self.0, t.c_ptr()) };
@@ -425,12 +667,54 @@ impl PartialEq for Ephemeris {
 
 impl Eq for Ephemeris {}
 
+impl fmt::Debug for Ephemeris {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ephemeris")
+            .field("sid", &self.sid())
+            .field("toe", &self.toe())
+            .field("iod", &self.iod())
+            .field("valid", &self.0.valid)
+            .field("health_bits", &self.0.health_bits)
+            .finish()
+    }
+}
+
 impl Default for Ephemeris {
     fn default() -> Self {
         unsafe { std::mem::zeroed::<Ephemeris>() }
     }
 }
 
+/// Calculate the state of each of several satellites at a time
+///
+/// The results line up with `ephemerides`, see
+/// [`Ephemeris::calc_satellite_state()`].
+pub fn calc_satellite_states_at(
+    ephemerides: &[Ephemeris],
+    t: GpsTime,
+) -> Vec<Result<SatelliteState, InvalidEphemeris>> {
+    ephemerides
+        .iter()
+        .map(|ephemeris| ephemeris.calc_satellite_state(t))
+        .collect()
+}
+
+/// Calculate the state of each of several satellites at a time, spreading
+/// the work over the rayon thread pool
+///
+/// Gives the same results as [`calc_satellite_states_at()`].
+#[cfg(feature = "rayon")]
+pub fn calc_satellite_states_at_par(
+    ephemerides: &[Ephemeris],
+    t: GpsTime,
+) -> Vec<Result<SatelliteState, InvalidEphemeris>> {
+    use rayon::prelude::*;
+    ephemerides
+        .par_iter()
+        .map(|ephemeris| ephemeris.calc_satellite_state(t))
+        .collect()
+}
+
 /// Representation of a satellite state from evaluating its ephemeris at a
 /// certain time.
 pub struct SatelliteState {
@@ -450,10 +734,25 @@ pub struct SatelliteState {
     pub iode: u8,
 }
 
+impl SatelliteState {
+    /// Refers the clock error of a Galileo satellite to GPS time instead of
+    /// Galileo system time, using the broadcast GPS to Galileo time offset
+    ///
+    /// This lets Galileo satellites be used alongside GPS satellites without
+    /// estimating a separate receiver clock offset for Galileo. It must only
+    /// be applied to the states of Galileo satellites, once.
+    pub fn apply_ggto(&mut self, ggto: &GgtoParams, t: GpsTime) {
+        self.clock_err += ggto.offset(&t);
+        self.clock_rate_err += ggto.a1g();
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::ephemeris::{Ephemeris, EphemerisTerms};
-    use crate::signal::{Code, Constellation, GnssSignal};
+    use crate::ephemeris::{Ephemeris, EphemerisTerms, Validity};
+    #[cfg(feature = "rayon")]
+    use crate::ephemeris::{InvalidEphemeris, SatelliteState};
+    use crate::signal::{Code, Constellation, GloSlotMap, GnssSignal};
     use crate::time::GpsTime;
     use std::os::raw::c_int;
 
@@ -585,4 +884,146 @@ mod tests {
 
         assert!(expected_ephemeris == decoded_eph);
     }
+
+    #[test]
+    fn glo_fcn() {
+        let toe = GpsTime::new(2100, 7200.0).unwrap();
+        let glo = |fcn| {
+            Ephemeris::new(
+                GnssSignal::new(3, Code::GloL1of).unwrap(),
+                toe,
+                2.5,
+                1800,
+                1,
+                0,
+                0,
+                EphemerisTerms::new_glo(0.0, 0.0, 0.0, [0.0; 3], [0.0; 3], [0.0; 3], fcn, 8),
+            )
+        };
+        assert_eq!(glo(9).glo_fcn(), Some(1));
+        assert_eq!(glo(1).glo_fcn(), Some(-7));
+        assert_eq!(glo(0).glo_fcn(), None);
+
+        let mut slots = GloSlotMap::new();
+        assert!(slots.update_from_ephemeris(&glo(14)));
+        assert_eq!(slots.fcn(3), Some(6));
+        assert!(!slots.update_from_ephemeris(&glo(15)));
+        assert_eq!(slots.fcn(3), Some(6));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_states() {
+        use crate::ephemeris::{calc_satellite_states_at, calc_satellite_states_at_par};
+
+        let toe = GpsTime::new(2100, 7200.0).unwrap();
+        let gps = |sat| {
+            Ephemeris::new(
+                GnssSignal::new(sat, Code::GpsL1ca).unwrap(),
+                toe,
+                2.0,
+                14400,
+                1,
+                0,
+                0,
+                EphemerisTerms::new_kepler(
+                    Constellation::Gps,
+                    [0.0, 0.0],
+                    200.0,
+                    -20.0,
+                    -1e-6,
+                    1e-5,
+                    -4e-8,
+                    1e-7,
+                    4e-9,
+                    0.1 * f64::from(sat),
+                    0.01,
+                    5153.6,
+                    0.3 * f64::from(sat),
+                    -8e-9,
+                    0.5,
+                    0.96,
+                    0.0,
+                    1e-5,
+                    0.0,
+                    0.0,
+                    toe,
+                    30,
+                    30,
+                ),
+            )
+        };
+        let ephemerides: Vec<Ephemeris> = (1..=8).map(gps).collect();
+        let times: Vec<GpsTime> = (0..100)
+            .map(|i| GpsTime::new(2100, 7200.0 + f64::from(i) * 30.0).unwrap())
+            .collect();
+        // Past the end of the fit interval
+        let late = GpsTime::new(2100, 7200.0 + 9000.0).unwrap();
+
+        let positions = |states: Vec<Result<SatelliteState, InvalidEphemeris>>| {
+            states
+                .into_iter()
+                .map(|state| state.map(|state| state.pos))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            positions(ephemerides[0].calc_satellite_states_par(&times)),
+            positions(ephemerides[0].calc_satellite_states(&times))
+        );
+        assert_eq!(
+            positions(calc_satellite_states_at_par(&ephemerides, times[10])),
+            positions(calc_satellite_states_at(&ephemerides, times[10]))
+        );
+        assert!(ephemerides[0].calc_satellite_states_par(&[late])[0].is_err());
+    }
+
+    #[test]
+    fn validity() {
+        let toe = GpsTime::new(2100, 7200.0).unwrap();
+        let glo = |fit_interval, valid, health_bits| {
+            Ephemeris::new(
+                GnssSignal::new(3, Code::GloL1of).unwrap(),
+                toe,
+                2.5,
+                fit_interval,
+                valid,
+                health_bits,
+                0,
+                EphemerisTerms::new_glo(0.0, 0.0, 0.0, [0.0; 3], [0.0; 3], [0.0; 3], 9, 8),
+            )
+        };
+
+        let ephemeris = glo(3600, 1, 0);
+        assert!(ephemeris.validity_at(toe).is_valid());
+        assert_eq!(
+            ephemeris.validity_at(GpsTime::new(2100, 7200.0 + 1800.0).unwrap()),
+            Validity::Valid
+        );
+        assert_eq!(
+            ephemeris.validity_at(GpsTime::new(2100, 7200.0 - 1801.0).unwrap()),
+            Validity::NotYetValid
+        );
+        assert_eq!(
+            ephemeris.validity_at(GpsTime::new(2100, 7200.0 + 2000.0).unwrap()),
+            Validity::Expired {
+                age: std::time::Duration::from_secs(2000)
+            }
+        );
+
+        // The nominal GLONASS fit interval is used when none was decoded
+        let ephemeris = glo(0, 1, 0);
+        assert_eq!(
+            ephemeris.validity_at(GpsTime::new(2100, 7200.0 + 901.0).unwrap()),
+            Validity::Expired {
+                age: std::time::Duration::from_secs(901)
+            }
+        );
+
+        assert_eq!(
+            glo(3600, 1, 0b10).validity_at(toe),
+            Validity::Unhealthy { flags: 0b10 }
+        );
+        assert_eq!(glo(3600, 0, 0).validity_at(toe), Validity::Invalid);
+        assert_eq!(Ephemeris::default().validity_at(toe), Validity::Invalid);
+    }
 }
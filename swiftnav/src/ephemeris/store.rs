@@ -0,0 +1,378 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Storage of the ephemerides of many satellites
+//!
+//! Satellites broadcast a new ephemeris every couple of hours, and the fit
+//! intervals of consecutive ephemerides overlap. The [`EphemerisStore`] keeps
+//! several ephemerides per satellite so that the right one can be picked for
+//! the time of a measurement, including when measurements are processed with
+//! some latency or corrections refer to an older issue of data.
+//!
+//! Each satellite's ephemerides are identified by their time of ephemeris. An
+//! ephemeris received again with the same time and issue of data is a
+//! duplicate and is ignored. One with the same time of ephemeris but a
+//! different issue of data is an upload cutover: the control segment has
+//! uploaded new orbit terms, and the new ephemeris replaces the old one.
+
+use crate::ephemeris::{Ephemeris, InvalidEphemeris, Validity};
+use crate::signal::Constellation;
+use crate::time::GpsTime;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Settings of an [`EphemerisStore`]
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StoreSettings {
+    max_per_satellite: usize,
+    max_age: Duration,
+}
+
+impl StoreSettings {
+    /// Makes the default settings
+    ///
+    /// Note: The default settings consist of
+    ///  * Up to 4 ephemerides are kept per satellite
+    ///  * Ephemerides are evicted 6 hours after their time of ephemeris
+    pub fn new() -> StoreSettings {
+        StoreSettings {
+            max_per_satellite: 4,
+            max_age: Duration::from_secs(6 * 3600),
+        }
+    }
+
+    /// Sets the most ephemerides kept per satellite, the oldest ephemerides
+    /// are dropped first
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `max_per_satellite` is zero
+    pub fn set_max_per_satellite(self, max_per_satellite: usize) -> StoreSettings {
+        assert!(max_per_satellite > 0, "At least one ephemeris must be kept");
+        StoreSettings {
+            max_per_satellite,
+            ..self
+        }
+    }
+
+    /// Sets how long after their time of ephemeris ephemerides are evicted by
+    /// [`EphemerisStore::evict()`]
+    pub fn set_max_age(self, max_age: Duration) -> StoreSettings {
+        StoreSettings { max_age, ..self }
+    }
+
+    pub fn max_per_satellite(&self) -> usize {
+        self.max_per_satellite
+    }
+
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+}
+
+impl Default for StoreSettings {
+    fn default() -> StoreSettings {
+        StoreSettings::new()
+    }
+}
+
+/// What happened to an ephemeris added to an [`EphemerisStore`]
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum StoreUpdate {
+    /// The ephemeris has a new time of ephemeris and was stored
+    Added,
+    /// The same ephemeris was already stored
+    Duplicate,
+    /// The ephemeris replaced one with the same time of ephemeris but a
+    /// different issue of data, `previous_iod` is the issue of data of the
+    /// replaced ephemeris
+    Cutover { previous_iod: Option<u16> },
+    /// The ephemeris is older than all of the ephemerides of its satellite
+    /// and the store is full, it wasn't stored
+    Stale,
+}
+
+struct Entry {
+    ephemeris: Ephemeris,
+    sequence: u64,
+}
+
+/// A collection of ephemerides for many satellites
+pub struct EphemerisStore {
+    settings: StoreSettings,
+    satellites: BTreeMap<(Constellation, u16), Vec<Entry>>,
+    sequence: u64,
+}
+
+impl EphemerisStore {
+    pub fn new(settings: StoreSettings) -> EphemerisStore {
+        EphemerisStore {
+            settings,
+            satellites: BTreeMap::new(),
+            sequence: 0,
+        }
+    }
+
+    pub fn settings(&self) -> &StoreSettings {
+        &self.settings
+    }
+
+    /// Adds an ephemeris to the store
+    ///
+    /// Unhealthy ephemerides are stored too, so that [`EphemerisStore::get()`]
+    /// knows a satellite has been marked unhealthy.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the ephemeris doesn't have a valid signal, or
+    /// it wasn't decoded successfully.
+    pub fn insert(&mut self, ephemeris: Ephemeris) -> Result<StoreUpdate, InvalidEphemeris> {
+        let sid = ephemeris.sid().map_err(|_| InvalidEphemeris::InvalidSid)?;
+        let toe = ephemeris.toe();
+        if ephemeris.validity_at(toe) == Validity::Invalid {
+            return Err(InvalidEphemeris::Invalid);
+        }
+
+        self.sequence += 1;
+        let entry = Entry {
+            ephemeris,
+            sequence: self.sequence,
+        };
+        let entries = self
+            .satellites
+            .entry((sid.to_constellation(), sid.sat()))
+            .or_default();
+
+        if let Some(existing) = entries.iter_mut().find(|e| e.ephemeris.toe() == toe) {
+            let previous_iod = existing.ephemeris.iod();
+            if previous_iod == entry.ephemeris.iod()
+                && existing.ephemeris.iodc() == entry.ephemeris.iodc()
+            {
+                return Ok(StoreUpdate::Duplicate);
+            }
+            *existing = entry;
+            return Ok(StoreUpdate::Cutover { previous_iod });
+        }
+
+        let index = entries
+            .iter()
+            .position(|e| e.ephemeris.toe() > toe)
+            .unwrap_or(entries.len());
+        if entries.len() >= self.settings.max_per_satellite {
+            if index == 0 {
+                return Ok(StoreUpdate::Stale);
+            }
+            entries.remove(0);
+            entries.insert(index - 1, entry);
+        } else {
+            entries.insert(index, entry);
+        }
+        Ok(StoreUpdate::Added)
+    }
+
+    /// Gets the best ephemeris of a satellite for use at a time
+    ///
+    /// Of the ephemerides valid at `t`, the one with the time of ephemeris
+    /// closest to `t` is picked, or the most recently added one when two are
+    /// equally close. If the latest ephemeris with a time of ephemeris before
+    /// `t` marks the satellite as unhealthy, no ephemeris is returned even if
+    /// older ones are healthy.
+    pub fn get(&self, constellation: Constellation, sat: u16, t: GpsTime) -> Option<&Ephemeris> {
+        let entries = self.satellites.get(&(constellation, sat))?;
+
+        let latest = entries.iter().rev().find(|e| e.ephemeris.toe() <= t);
+        if let Some(latest) = latest {
+            if let Validity::Unhealthy { .. } = latest.ephemeris.validity_at(t) {
+                return None;
+            }
+        }
+
+        entries
+            .iter()
+            .filter(|e| e.ephemeris.validity_at(t).is_valid())
+            .min_by(|a, b| {
+                let da = t.diff(&a.ephemeris.toe()).abs();
+                let db = t.diff(&b.ephemeris.toe()).abs();
+                da.partial_cmp(&db)
+                    .unwrap()
+                    .then(b.sequence.cmp(&a.sequence))
+            })
+            .map(|e| &e.ephemeris)
+    }
+
+    /// Gets the ephemeris of a satellite with a particular issue of data, as
+    /// referred to by orbit corrections
+    ///
+    /// If several ephemerides have the same issue of data, the one with the
+    /// latest time of ephemeris is returned.
+    pub fn get_by_iod(
+        &self,
+        constellation: Constellation,
+        sat: u16,
+        iod: u16,
+    ) -> Option<&Ephemeris> {
+        self.satellites
+            .get(&(constellation, sat))?
+            .iter()
+            .rev()
+            .map(|e| &e.ephemeris)
+            .find(|ephemeris| ephemeris.iod() == Some(iod))
+    }
+
+    /// Iterates over the ephemerides of a satellite, ordered by time of
+    /// ephemeris
+    pub fn ephemerides(
+        &self,
+        constellation: Constellation,
+        sat: u16,
+    ) -> impl Iterator<Item = &Ephemeris> + '_ {
+        self.satellites
+            .get(&(constellation, sat))
+            .into_iter()
+            .flatten()
+            .map(|e| &e.ephemeris)
+    }
+
+    /// Iterates over the satellites with at least one ephemeris
+    pub fn satellites(&self) -> impl Iterator<Item = (Constellation, u16)> + '_ {
+        self.satellites.keys().copied()
+    }
+
+    /// Removes the ephemerides whose time of ephemeris is more than the
+    /// maximum age before `t`, and returns how many were removed
+    pub fn evict(&mut self, t: GpsTime) -> usize {
+        let max_age = self.settings.max_age.as_secs_f64();
+        let mut removed = 0;
+        self.satellites.retain(|_, entries| {
+            let before = entries.len();
+            entries.retain(|e| t.diff(&e.ephemeris.toe()) <= max_age);
+            removed += before - entries.len();
+            !entries.is_empty()
+        });
+        removed
+    }
+
+    /// Removes all of the ephemerides of a satellite, and returns how many
+    /// were removed
+    pub fn remove(&mut self, constellation: Constellation, sat: u16) -> usize {
+        self.satellites
+            .remove(&(constellation, sat))
+            .map_or(0, |entries| entries.len())
+    }
+
+    pub fn clear(&mut self) {
+        self.satellites.clear();
+    }
+
+    /// Gets the total number of ephemerides stored
+    pub fn len(&self) -> usize {
+        self.satellites.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.satellites.is_empty()
+    }
+}
+
+impl Default for EphemerisStore {
+    fn default() -> EphemerisStore {
+        EphemerisStore::new(StoreSettings::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ephemeris::EphemerisTerms;
+    use crate::signal::{Code, GnssSignal};
+
+    fn glo(sat: u16, tow: f64, iod: u8, health_bits: u8) -> Ephemeris {
+        Ephemeris::new(
+            GnssSignal::new(sat, Code::GloL1of).unwrap(),
+            GpsTime::new(2100, tow).unwrap(),
+            2.5,
+            1800,
+            1,
+            health_bits,
+            0,
+            EphemerisTerms::new_glo(0.0, 0.0, 0.0, [0.0; 3], [0.0; 3], [0.0; 3], 9, iod),
+        )
+    }
+
+    fn at(tow: f64) -> GpsTime {
+        GpsTime::new(2100, tow).unwrap()
+    }
+
+    #[test]
+    fn insert_and_select() {
+        let mut store = EphemerisStore::new(StoreSettings::new().set_max_per_satellite(3));
+        assert_eq!(store.insert(glo(4, 7200.0, 8, 0)), Ok(StoreUpdate::Added));
+        assert_eq!(store.insert(glo(4, 9000.0, 9, 0)), Ok(StoreUpdate::Added));
+        assert_eq!(
+            store.insert(glo(4, 7200.0, 8, 0)),
+            Ok(StoreUpdate::Duplicate)
+        );
+        assert_eq!(
+            store.insert(glo(4, 9000.0, 10, 0)),
+            Ok(StoreUpdate::Cutover {
+                previous_iod: Some(9)
+            })
+        );
+        assert_eq!(store.insert(glo(6, 9000.0, 9, 0)), Ok(StoreUpdate::Added));
+        assert_eq!(
+            store.insert(Ephemeris::default()),
+            Err(InvalidEphemeris::InvalidSid)
+        );
+        assert_eq!(store.len(), 3);
+
+        let iod = |t| {
+            store
+                .get(Constellation::Glo, 4, at(t))
+                .and_then(Ephemeris::iod)
+        };
+        assert_eq!(iod(7500.0), Some(8));
+        assert_eq!(iod(8200.0), Some(10));
+        assert_eq!(iod(11000.0), None);
+        assert_eq!(
+            store
+                .get_by_iod(Constellation::Glo, 4, 8)
+                .map(Ephemeris::toe),
+            Some(at(7200.0))
+        );
+        assert!(store.get_by_iod(Constellation::Glo, 4, 9).is_none());
+
+        // The store is full, so an older ephemeris is stale and a newer one
+        // pushes out the oldest
+        assert_eq!(store.insert(glo(4, 10800.0, 11, 0)), Ok(StoreUpdate::Added));
+        assert_eq!(store.insert(glo(4, 5400.0, 7, 0)), Ok(StoreUpdate::Stale));
+        assert_eq!(store.insert(glo(4, 12600.0, 12, 0)), Ok(StoreUpdate::Added));
+        let toes: Vec<f64> = store
+            .ephemerides(Constellation::Glo, 4)
+            .map(|e| e.toe().tow())
+            .collect();
+        assert_eq!(toes, vec![9000.0, 10800.0, 12600.0]);
+
+        // A newer unhealthy ephemeris stops the older ones from being used
+        assert_eq!(store.insert(glo(6, 10800.0, 11, 1)), Ok(StoreUpdate::Added));
+        assert!(store.get(Constellation::Glo, 6, at(9500.0)).is_some());
+        assert!(store.get(Constellation::Glo, 6, at(10900.0)).is_none());
+
+        let satellites: Vec<_> = store.satellites().collect();
+        assert_eq!(
+            satellites,
+            vec![(Constellation::Glo, 4), (Constellation::Glo, 6)]
+        );
+        assert_eq!(store.evict(at(9000.0 + 6.0 * 3600.0 + 1.0)), 2);
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.remove(Constellation::Glo, 6), 1);
+        store.clear();
+        assert!(store.is_empty());
+    }
+}
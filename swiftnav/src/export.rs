@@ -0,0 +1,365 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! GPX, KML and GeoJSON export of positions and trajectories
+//!
+//! Positions can be written in three common formats so they can be opened
+//! directly in mapping and GIS tools:
+//!  * [GPX](ExportFormat::Gpx) - waypoints (`wpt`) and tracks (`trk`)
+//!  * [KML](ExportFormat::Kml) - `Point` and `LineString` placemarks
+//!  * [GeoJSON](ExportFormat::GeoJson) - a `FeatureCollection` of `Point` or
+//!    `LineString` features
+//!
+//! Latitudes and longitudes are written with 9 decimals, and heights with 4.
+//! Heights are written as given, which for positions made by this crate are
+//! heights above the ellipsoid. GeoJSON uses heights above the ellipsoid, but
+//! GPX and KML viewers usually expect heights above the geoid, see
+//! [`crate::geoid`] for converting between the two.
+//!
+//! Only available with the `export` feature enabled.
+//!
+//! # References
+//!   * GPX 1.1 Schema Documentation, <https://www.topografix.com/GPX/1/1/>
+//!   * OGC KML 2.2, OGC 07-147r2
+//!   * RFC 7946, The GeoJSON Format
+
+use crate::coords::LLHDegrees;
+use crate::time::UtcTime;
+use crate::trajectory::Trajectory;
+use std::fmt::Write;
+
+/// A format positions can be exported to
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExportFormat {
+    /// GPS Exchange Format
+    Gpx,
+    /// Keyhole Markup Language
+    Kml,
+    /// GeoJSON
+    GeoJson,
+}
+
+impl ExportFormat {
+    /// Gets the usual file extension of the format, without the leading dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Gpx => "gpx",
+            ExportFormat::Kml => "kml",
+            ExportFormat::GeoJson => "geojson",
+        }
+    }
+
+    /// Gets the media type of the format
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Gpx => "application/gpx+xml",
+            ExportFormat::Kml => "application/vnd.google-earth.kml+xml",
+            ExportFormat::GeoJson => "application/geo+json",
+        }
+    }
+
+    /// Writes a document with each position as a separate point
+    pub fn points(&self, points: &[LLHDegrees]) -> String {
+        let mut out = String::new();
+        match self {
+            ExportFormat::Gpx => {
+                out.push_str(GPX_HEADER);
+                for point in points {
+                    writeln!(
+                        out,
+                        "  <wpt lat=\"{:.9}\" lon=\"{:.9}\"><ele>{:.4}</ele></wpt>",
+                        point.latitude(),
+                        point.longitude(),
+                        point.height()
+                    )
+                    .unwrap();
+                }
+                out.push_str("</gpx>\n");
+            }
+            ExportFormat::Kml => {
+                out.push_str(KML_HEADER);
+                for point in points {
+                    writeln!(
+                        out,
+                        "    <Placemark><Point><altitudeMode>absolute</altitudeMode><coordinates>{}</coordinates></Point></Placemark>",
+                        kml_position(point)
+                    )
+                    .unwrap();
+                }
+                out.push_str(KML_FOOTER);
+            }
+            ExportFormat::GeoJson => {
+                let features: Vec<String> = points
+                    .iter()
+                    .map(|point| {
+                        format!(
+                            "{{\"type\":\"Feature\",\"geometry\":{{\"type\":\"Point\",\"coordinates\":{}}},\"properties\":{{}}}}",
+                            json_position(point)
+                        )
+                    })
+                    .collect();
+                write_feature_collection(&mut out, &features);
+            }
+        }
+        out
+    }
+
+    /// Writes a document with the positions joined into a single named track
+    pub fn track(&self, name: &str, points: &[LLHDegrees]) -> String {
+        let points: Vec<(LLHDegrees, Option<UtcTime>)> =
+            points.iter().map(|point| (*point, None)).collect();
+        self.timed_track(name, &points)
+    }
+
+    /// Writes a document with a trajectory as a single named track
+    ///
+    /// The positions are on the ellipsoid of the trajectory's reference frame,
+    /// and are given the UTC times of the trajectory's points, which are
+    /// converted with the hardcoded list of leap seconds.
+    pub fn trajectory(&self, name: &str, trajectory: &Trajectory) -> String {
+        let points: Vec<(LLHDegrees, Option<UtcTime>)> = trajectory
+            .coordinates()
+            .map(|coordinate| {
+                (
+                    coordinate.llh().to_degrees(),
+                    Some(coordinate.epoch().to_utc_hardcoded()),
+                )
+            })
+            .collect();
+        self.timed_track(name, &points)
+    }
+
+    fn timed_track(&self, name: &str, points: &[(LLHDegrees, Option<UtcTime>)]) -> String {
+        let mut out = String::new();
+        match self {
+            ExportFormat::Gpx => {
+                out.push_str(GPX_HEADER);
+                writeln!(
+                    out,
+                    "  <trk>\n    <name>{}</name>\n    <trkseg>",
+                    escape_xml(name)
+                )
+                .unwrap();
+                for (point, time) in points {
+                    write!(
+                        out,
+                        "      <trkpt lat=\"{:.9}\" lon=\"{:.9}\"><ele>{:.4}</ele>",
+                        point.latitude(),
+                        point.longitude(),
+                        point.height()
+                    )
+                    .unwrap();
+                    if let Some(time) = time {
+                        write!(out, "<time>{}</time>", time).unwrap();
+                    }
+                    out.push_str("</trkpt>\n");
+                }
+                out.push_str("    </trkseg>\n  </trk>\n</gpx>\n");
+            }
+            ExportFormat::Kml => {
+                out.push_str(KML_HEADER);
+                writeln!(
+                    out,
+                    "    <Placemark>\n      <name>{}</name>",
+                    escape_xml(name)
+                )
+                .unwrap();
+                if let (Some((_, Some(begin))), Some((_, Some(end)))) =
+                    (points.first(), points.last())
+                {
+                    writeln!(
+                        out,
+                        "      <TimeSpan><begin>{}</begin><end>{}</end></TimeSpan>",
+                        begin, end
+                    )
+                    .unwrap();
+                }
+                let coordinates: Vec<String> = points
+                    .iter()
+                    .map(|(point, _)| kml_position(point))
+                    .collect();
+                writeln!(
+                    out,
+                    "      <LineString><altitudeMode>absolute</altitudeMode><coordinates>{}</coordinates></LineString>\n    </Placemark>",
+                    coordinates.join(" ")
+                )
+                .unwrap();
+                out.push_str(KML_FOOTER);
+            }
+            ExportFormat::GeoJson => {
+                let coordinates: Vec<String> = points
+                    .iter()
+                    .map(|(point, _)| json_position(point))
+                    .collect();
+                let mut properties = format!("\"name\":\"{}\"", escape_json(name));
+                if points.iter().all(|(_, time)| time.is_some()) && !points.is_empty() {
+                    let times: Vec<String> = points
+                        .iter()
+                        .filter_map(|(_, time)| time.as_ref())
+                        .map(|time| format!("\"{}\"", time))
+                        .collect();
+                    write!(properties, ",\"times\":[{}]", times.join(",")).unwrap();
+                }
+                let feature = format!(
+                    "{{\"type\":\"Feature\",\"geometry\":{{\"type\":\"LineString\",\"coordinates\":[{}]}},\"properties\":{{{}}}}}",
+                    coordinates.join(","),
+                    properties
+                );
+                write_feature_collection(&mut out, &[feature]);
+            }
+        }
+        out
+    }
+}
+
+const GPX_HEADER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<gpx version=\"1.1\" creator=\"swiftnav\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n";
+
+const KML_HEADER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n  <Document>\n";
+
+const KML_FOOTER: &str = "  </Document>\n</kml>\n";
+
+/// KML positions are longitude first, without spaces
+fn kml_position(point: &LLHDegrees) -> String {
+    format!(
+        "{:.9},{:.9},{:.4}",
+        point.longitude(),
+        point.latitude(),
+        point.height()
+    )
+}
+
+/// GeoJSON positions are longitude first
+fn json_position(point: &LLHDegrees) -> String {
+    format!(
+        "[{:.9},{:.9},{:.4}]",
+        point.longitude(),
+        point.latitude(),
+        point.height()
+    )
+}
+
+fn write_feature_collection(out: &mut String, features: &[String]) {
+    writeln!(
+        out,
+        "{{\"type\":\"FeatureCollection\",\"features\":[{}]}}",
+        features.join(",")
+    )
+    .unwrap();
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::{Coordinate, LLHRadians};
+    use crate::reference_frame::ReferenceFrame;
+    use crate::time::GpsTime;
+
+    fn points() -> Vec<LLHDegrees> {
+        vec![
+            LLHDegrees::new(37.77, -122.41, 12.5),
+            LLHDegrees::new(37.78, -122.42, 13.25),
+        ]
+    }
+
+    #[test]
+    fn points_and_tracks() {
+        let gpx = ExportFormat::Gpx.points(&points());
+        assert!(gpx.starts_with("<?xml"));
+        assert!(gpx
+            .contains("<wpt lat=\"37.770000000\" lon=\"-122.410000000\"><ele>12.5000</ele></wpt>"));
+        assert!(gpx.ends_with("</gpx>\n"));
+
+        let kml = ExportFormat::Kml.track("Drive <1>", &points());
+        assert!(kml.contains("<name>Drive &lt;1&gt;</name>"));
+        assert!(kml.contains(
+            "<coordinates>-122.410000000,37.770000000,12.5000 -122.420000000,37.780000000,13.2500</coordinates>"
+        ));
+        assert!(!kml.contains("TimeSpan"));
+
+        let json = ExportFormat::GeoJson.track("Drive \"1\"", &points());
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let feature = &value["features"][0];
+        assert_eq!(feature["properties"]["name"], "Drive \"1\"");
+        assert_eq!(feature["geometry"]["type"], "LineString");
+        assert_eq!(feature["geometry"]["coordinates"][1][0], -122.42);
+        assert_eq!(feature["geometry"]["coordinates"][1][2], 13.25);
+        assert!(feature["properties"].get("times").is_none());
+
+        let json = ExportFormat::GeoJson.points(&points());
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["features"].as_array().unwrap().len(), 2);
+        assert_eq!(value["features"][0]["geometry"]["coordinates"][1], 37.77);
+    }
+
+    #[test]
+    fn trajectory() {
+        let time = GpsTime::new(2200, 3600.0).unwrap();
+        let coordinates: Vec<Coordinate> = points()
+            .iter()
+            .enumerate()
+            .map(|(i, point)| {
+                let llh: LLHRadians = (*point).into();
+                Coordinate::without_velocity(
+                    ReferenceFrame::ITRF2020,
+                    llh.to_ecef_on(ReferenceFrame::ITRF2020.ellipsoid()),
+                    time + std::time::Duration::from_secs(i as u64),
+                )
+            })
+            .collect();
+        let trajectory =
+            Trajectory::from_coordinates(ReferenceFrame::ITRF2020, &coordinates).unwrap();
+
+        let gpx = ExportFormat::Gpx.trajectory("Drive", &trajectory);
+        assert!(gpx.contains("<trkpt lat=\"37.770000000\" lon=\"-122.410000000\"><ele>12.5000</ele><time>2022-03-06T00:59:42.000Z</time></trkpt>"));
+
+        let kml = ExportFormat::Kml.trajectory("Drive", &trajectory);
+        assert!(kml.contains(
+            "<TimeSpan><begin>2022-03-06T00:59:42.000Z</begin><end>2022-03-06T00:59:43.000Z</end></TimeSpan>"
+        ));
+
+        let json = ExportFormat::GeoJson.trajectory("Drive", &trajectory);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value["features"][0]["properties"]["times"][1],
+            "2022-03-06T00:59:43.000Z"
+        );
+    }
+}
@@ -0,0 +1,421 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Fixed capacity history buffers
+//!
+//! Cycle slip detectors and quality monitors need to remember the last few
+//! values of each signal, such as the carrier phase or CN0. The buffers here
+//! hold a fixed number of values inline, set at compile time, so the memory
+//! used per signal is known up front and never grows. Once full, pushing a
+//! new value evicts the oldest one.
+//!
+//! [`RingBuffer`] holds plain values and [`TimeSeries`] holds values tagged
+//! with a [`GpsTime`]. Neither allocates, but like the rest of the crate they
+//! need the standard library, the GPS time being backed by `libswiftnav`.
+//! They aren't usable in `no_std` builds.
+//!
+//! ```
+//! use swiftnav::history::TimeSeries;
+//! use swiftnav::time::GpsTime;
+//!
+//! let mut cn0: TimeSeries<f64, 4> = TimeSeries::new();
+//! for i in 0..6 {
+//!     let t = GpsTime::new(2200, i as f64).unwrap();
+//!     cn0.push(t, 40.0 + i as f64).unwrap();
+//! }
+//! assert_eq!(cn0.len(), 4);
+//! assert_eq!(cn0.oldest().unwrap().1, 42.0);
+//! ```
+
+use crate::time::GpsTime;
+use core::cmp::Ordering;
+use core::fmt;
+
+/// A fixed capacity first in first out buffer
+#[derive(Debug, Copy, Clone)]
+pub struct RingBuffer<T: Copy, const N: usize> {
+    values: [Option<T>; N],
+    start: usize,
+    len: usize,
+}
+
+impl<T: Copy, const N: usize> RingBuffer<T, N> {
+    pub fn new() -> RingBuffer<T, N> {
+        RingBuffer {
+            values: [None; N],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    /// Gets the maximum number of values the buffer can hold
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Adds a value to the end of the buffer
+    ///
+    /// If the buffer is full the oldest value is removed and returned. A
+    /// buffer with no capacity immediately returns the value.
+    pub fn push(&mut self, value: T) -> Option<T> {
+        if N == 0 {
+            return Some(value);
+        }
+        if self.is_full() {
+            let evicted = self.values[self.start].replace(value);
+            self.start = (self.start + 1) % N;
+            evicted
+        } else {
+            self.values[(self.start + self.len) % N] = Some(value);
+            self.len += 1;
+            None
+        }
+    }
+
+    /// Removes and returns the oldest value
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        let value = self.values[self.start].take();
+        self.start = (self.start + 1) % N;
+        self.len -= 1;
+        value
+    }
+
+    /// Removes and returns the newest value
+    pub fn pop_back(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        self.len -= 1;
+        self.values[(self.start + self.len) % N].take()
+    }
+
+    /// Gets a value by its age order, with index 0 being the oldest
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index < self.len {
+            self.values[(self.start + index) % N].as_ref()
+        } else {
+            None
+        }
+    }
+
+    /// Gets the oldest value
+    pub fn front(&self) -> Option<&T> {
+        self.get(0)
+    }
+
+    /// Gets the newest value
+    pub fn back(&self) -> Option<&T> {
+        self.len.checked_sub(1).and_then(|i| self.get(i))
+    }
+
+    /// Iterates over the values from oldest to newest
+    pub fn iter(&self) -> Iter<'_, T, N> {
+        Iter {
+            buffer: self,
+            front: 0,
+            back: self.len,
+        }
+    }
+
+    /// Removes all values from the buffer
+    pub fn clear(&mut self) {
+        self.values = [None; N];
+        self.start = 0;
+        self.len = 0;
+    }
+}
+
+impl<T: Copy + PartialEq, const N: usize> PartialEq for RingBuffer<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Copy + PartialOrd, const N: usize> PartialOrd for RingBuffer<T, N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<T: Copy, const N: usize> Default for RingBuffer<T, N> {
+    fn default() -> RingBuffer<T, N> {
+        RingBuffer::new()
+    }
+}
+
+impl<'a, T: Copy, const N: usize> IntoIterator for &'a RingBuffer<T, N> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, N>;
+
+    fn into_iter(self) -> Iter<'a, T, N> {
+        self.iter()
+    }
+}
+
+/// Iterator over the values in a [`RingBuffer`], from oldest to newest
+#[derive(Debug, Clone)]
+pub struct Iter<'a, T: Copy, const N: usize> {
+    buffer: &'a RingBuffer<T, N>,
+    front: usize,
+    back: usize,
+}
+
+impl<'a, T: Copy, const N: usize> Iterator for Iter<'a, T, N> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.front == self.back {
+            return None;
+        }
+        let value = self.buffer.get(self.front);
+        self.front += 1;
+        value
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.back - self.front;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a, T: Copy, const N: usize> DoubleEndedIterator for Iter<'a, T, N> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.buffer.get(self.back)
+    }
+}
+
+impl<'a, T: Copy, const N: usize> ExactSizeIterator for Iter<'a, T, N> {}
+
+/// Error returned when time tagged data is added out of time order, e.g. to a
+/// [`TimeSeries`]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OutOfOrder {
+    /// Time of the rejected value
+    pub time: GpsTime,
+    /// Time of the newest value in the series
+    pub latest: GpsTime,
+}
+
+impl fmt::Display for OutOfOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Value is {} s older than the latest value",
+            self.latest.diff(&self.time)
+        )
+    }
+}
+
+impl std::error::Error for OutOfOrder {}
+
+/// A fixed capacity buffer of time tagged values, ordered by time
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct TimeSeries<T: Copy, const N: usize> {
+    buffer: RingBuffer<(GpsTime, T), N>,
+}
+
+impl<T: Copy, const N: usize> TimeSeries<T, N> {
+    pub fn new() -> TimeSeries<T, N> {
+        TimeSeries {
+            buffer: RingBuffer::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.buffer.is_full()
+    }
+
+    /// Adds a value at time `t`, returning the evicted value if the series
+    /// was full
+    ///
+    /// Values must be pushed in increasing time order, a value at the same
+    /// time or before the latest value is rejected.
+    pub fn push(&mut self, t: GpsTime, value: T) -> Result<Option<(GpsTime, T)>, OutOfOrder> {
+        if let Some((latest, _)) = self.latest() {
+            if t.diff(&latest) <= 0.0 {
+                return Err(OutOfOrder { time: t, latest });
+            }
+        }
+        Ok(self.buffer.push((t, value)))
+    }
+
+    /// Gets the oldest value
+    pub fn oldest(&self) -> Option<(GpsTime, T)> {
+        self.buffer.front().copied()
+    }
+
+    /// Gets the newest value
+    pub fn latest(&self) -> Option<(GpsTime, T)> {
+        self.buffer.back().copied()
+    }
+
+    /// Gets the time between the oldest and newest values, in seconds
+    pub fn span(&self) -> f64 {
+        match (self.oldest(), self.latest()) {
+            (Some((first, _)), Some((last, _))) => last.diff(&first),
+            _ => 0.0,
+        }
+    }
+
+    /// Iterates over the values from oldest to newest
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (GpsTime, T)> + '_ {
+        self.buffer.iter().copied()
+    }
+
+    /// Iterates over the values at or after time `t`, from oldest to newest
+    pub fn since(&self, t: GpsTime) -> impl DoubleEndedIterator<Item = (GpsTime, T)> + '_ {
+        self.iter().filter(move |(time, _)| time.diff(&t) >= 0.0)
+    }
+
+    /// Removes all values older than time `t`, returning how many were
+    /// removed
+    pub fn prune_before(&mut self, t: GpsTime) -> usize {
+        let mut removed = 0;
+        while let Some((time, _)) = self.oldest() {
+            if time.diff(&t) >= 0.0 {
+                break;
+            }
+            self.buffer.pop_front();
+            removed += 1;
+        }
+        removed
+    }
+
+    /// Removes all values from the series
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+}
+
+impl<T: Copy, const N: usize> Default for TimeSeries<T, N> {
+    fn default() -> TimeSeries<T, N> {
+        TimeSeries::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_buffer() {
+        let mut buffer: RingBuffer<u32, 3> = RingBuffer::new();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.capacity(), 3);
+        assert_eq!(buffer.back(), None);
+
+        for i in 0..3 {
+            assert_eq!(buffer.push(i), None);
+        }
+        assert!(buffer.is_full());
+        assert_eq!(buffer.push(3), Some(0));
+        assert_eq!(buffer.push(4), Some(1));
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), vec![2, 3, 4]);
+        assert_eq!(
+            buffer.iter().rev().copied().collect::<Vec<_>>(),
+            vec![4, 3, 2]
+        );
+        assert_eq!(buffer.iter().len(), 3);
+        assert_eq!(buffer.front(), Some(&2));
+        assert_eq!(buffer.back(), Some(&4));
+        assert_eq!(buffer.get(1), Some(&3));
+        assert_eq!(buffer.get(3), None);
+
+        assert_eq!(buffer.pop_front(), Some(2));
+        assert_eq!(buffer.pop_back(), Some(4));
+        assert_eq!(buffer.len(), 1);
+        buffer.push(5);
+        buffer.push(6);
+        assert_eq!(
+            (&buffer).into_iter().copied().collect::<Vec<_>>(),
+            vec![3, 5, 6]
+        );
+        let mut other = RingBuffer::new();
+        for i in [3, 5, 6].iter() {
+            other.push(*i);
+        }
+        assert_eq!(buffer, other);
+        other.push(7);
+        assert!(buffer < other);
+
+        buffer.clear();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.pop_front(), None);
+
+        let mut empty: RingBuffer<u32, 0> = RingBuffer::new();
+        assert_eq!(empty.push(1), Some(1));
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn time_series() {
+        let t0 = GpsTime::new(2200, 100.0).unwrap();
+        let at = |secs: f64| t0 + std::time::Duration::from_secs_f64(secs);
+        let mut phase: TimeSeries<f64, 4> = TimeSeries::new();
+        assert_eq!(phase.span(), 0.0);
+
+        for i in 0..5 {
+            phase.push(at(i as f64), 100.0 * i as f64).unwrap();
+        }
+        assert_eq!(phase.len(), 4);
+        assert_eq!(phase.oldest(), Some((at(1.0), 100.0)));
+        assert_eq!(phase.latest(), Some((at(4.0), 400.0)));
+        assert_eq!(phase.span(), 3.0);
+
+        assert_eq!(
+            phase.push(at(4.0), 0.0),
+            Err(OutOfOrder {
+                time: at(4.0),
+                latest: at(4.0)
+            })
+        );
+        assert!(phase.push(at(3.5), 0.0).is_err());
+
+        let recent: Vec<f64> = phase.since(at(2.5)).map(|(_, v)| v).collect();
+        assert_eq!(recent, vec![300.0, 400.0]);
+        assert_eq!(phase.prune_before(at(3.0)), 2);
+        assert_eq!(phase.oldest(), Some((at(3.0), 300.0)));
+        assert_eq!(phase.prune_before(at(0.0)), 0);
+
+        phase.clear();
+        assert!(phase.is_empty());
+        assert!(phase.push(at(0.0), 0.0).is_ok());
+    }
+}
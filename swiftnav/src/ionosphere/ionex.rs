@@ -0,0 +1,662 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! IONEX global ionosphere maps
+//!
+//! Analysis centers publish maps of the vertical total electron content (TEC)
+//! of the ionosphere in the IONosphere map EXchange format, typically every
+//! one or two hours on a 2.5° by 5° grid. [`IonexMaps`] loads the TEC and RMS
+//! maps of a file and computes the slant TEC and delay of a signal with the
+//! single layer model: the whole ionosphere is assumed to be a thin shell at
+//! the height of the maps, and the vertical TEC at the point where the signal
+//! crosses the shell, the ionospheric pierce point, is mapped to the slant
+//! with the `1/cos(z')` mapping function.
+//!
+//! The vertical TEC is interpolated bilinearly between the four grid points
+//! around a location, and in time between the two maps around the time after
+//! rotating them by the motion of the sun, as recommended by the format
+//! description. Only two dimensional maps, with a single height, are
+//! supported. Height maps are skipped.
+//!
+//! # References
+//!   * IONEX: The IONosphere Map EXchange Format Version 1, Schaer et al.,
+//!     1998
+
+use crate::coords::{AzimuthElevation, LLHRadians};
+use crate::time::{GpsTime, UtcTime};
+use std::error::Error;
+use std::fmt;
+
+/// Ionospheric delay of 1 TECU on a signal of 1 Hz, in meters
+const TECU_DELAY: f64 = 40.3e16;
+/// Value of a grid point without a value
+const MISSING_VALUE: i64 = 9999;
+/// Most points of a map grid, a global grid at 0.05° by 0.1°
+const MAX_GRID_POINTS: usize = 3601 * 3601;
+/// Rotation of the sun fixed maps, in degrees per second
+const EARTH_ROTATION_DEG: f64 = 360.0 / 86400.0;
+
+/// An error parsing an IONEX file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IonexError {
+    /// A line could not be parsed
+    InvalidLine(usize),
+    /// The header is missing the grid definition or the end of the header
+    IncompleteHeader,
+    /// The maps are three dimensional, with more than one height
+    MultipleHeights,
+    /// The file has no TEC maps
+    NoMaps,
+}
+
+impl fmt::Display for IonexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IonexError::InvalidLine(line) => write!(f, "Invalid IONEX record on line {}", line),
+            IonexError::IncompleteHeader => write!(f, "Incomplete IONEX header"),
+            IonexError::MultipleHeights => write!(f, "Three dimensional IONEX maps"),
+            IonexError::NoMaps => write!(f, "No TEC maps in IONEX file"),
+        }
+    }
+}
+
+impl Error for IonexError {}
+
+/// The latitudes or longitudes of the grid points, in degrees
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Axis {
+    start: f64,
+    step: f64,
+    count: usize,
+}
+
+impl Axis {
+    /// Makes an axis covering at most `span` degrees
+    fn new(start: f64, end: f64, step: f64, span: f64) -> Option<Axis> {
+        if step == 0.0 || !step.is_finite() {
+            return None;
+        }
+        let intervals = ((end - start) / step).round();
+        if !(1.0..=(span / step.abs()).round()).contains(&intervals) {
+            return None;
+        }
+        Some(Axis {
+            start,
+            step,
+            count: intervals as usize + 1,
+        })
+    }
+
+    fn index_of(&self, value: f64) -> Option<usize> {
+        let u = (value - self.start) / self.step;
+        let index = u.round();
+        if (u - index).abs() > 1e-6 || index < 0.0 || index as usize >= self.count {
+            return None;
+        }
+        Some(index as usize)
+    }
+
+    /// Finds the interval around a value, and the position in the interval
+    fn locate(&self, value: f64) -> Option<(usize, f64)> {
+        let u = (value - self.start) / self.step;
+        let last = (self.count - 1) as f64;
+        if u < -1e-9 || u > last + 1e-9 {
+            return None;
+        }
+        let index = u.floor().max(0.0).min(last - 1.0);
+        Some((index as usize, (u - index).clamp(0.0, 1.0)))
+    }
+
+    fn is_global(&self) -> bool {
+        ((self.count - 1) as f64 * self.step).abs() >= 360.0 - 1e-6
+    }
+}
+
+/// A single map, of TEC or RMS values in TECU
+#[derive(Debug, Clone, PartialEq)]
+struct GridMap {
+    epoch: GpsTime,
+    values: Vec<Option<f64>>,
+}
+
+/// The vertical TEC maps of an IONEX file
+#[derive(Debug, Clone, PartialEq)]
+pub struct IonexMaps {
+    base_radius: f64,
+    height: f64,
+    latitudes: Axis,
+    longitudes: Axis,
+    tec: Vec<GridMap>,
+    rms: Vec<GridMap>,
+}
+
+/// The header values needed to read the maps
+#[derive(Default)]
+struct Header {
+    base_radius: Option<f64>,
+    height: Option<f64>,
+    latitudes: Option<Axis>,
+    longitudes: Option<Axis>,
+    exponent: i32,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum MapKind {
+    Tec,
+    Rms,
+    Height,
+}
+
+/// A map being read
+struct PartialMap {
+    kind: MapKind,
+    epoch: Option<GpsTime>,
+    exponent: i32,
+    values: Vec<Option<f64>>,
+    /// The start of the latitude row being read, and its number of values
+    row: Option<(usize, usize)>,
+}
+
+impl IonexMaps {
+    /// Loads the TEC and RMS maps of an IONEX file
+    ///
+    /// The epochs of the maps are in UTC, and are converted to GPS time with
+    /// the hardcoded list of leap seconds.
+    pub fn from_ionex(text: &str) -> Result<IonexMaps, IonexError> {
+        let mut header = Header {
+            exponent: -1,
+            ..Header::default()
+        };
+        let mut lines = text.lines().enumerate();
+
+        let mut header_ended = false;
+        for (index, line) in &mut lines {
+            let (content, label) = split_record(line);
+            let invalid = || IonexError::InvalidLine(index + 1);
+            match label {
+                "BASE RADIUS" => {
+                    header.base_radius = Some(parse_f64(content).ok_or_else(invalid)? * 1e3);
+                }
+                "HGT1 / HGT2 / DHGT" => {
+                    let values = fixed_f64(content, 3).ok_or_else(invalid)?;
+                    if values[2] != 0.0 || values[0] != values[1] {
+                        return Err(IonexError::MultipleHeights);
+                    }
+                    header.height = Some(values[0] * 1e3);
+                }
+                "LAT1 / LAT2 / DLAT" => {
+                    let values = fixed_f64(content, 3).ok_or_else(invalid)?;
+                    header.latitudes = Some(
+                        Axis::new(values[0], values[1], values[2], 180.0).ok_or_else(invalid)?,
+                    );
+                }
+                "LON1 / LON2 / DLON" => {
+                    let values = fixed_f64(content, 3).ok_or_else(invalid)?;
+                    header.longitudes = Some(
+                        Axis::new(values[0], values[1], values[2], 360.0).ok_or_else(invalid)?,
+                    );
+                }
+                "EXPONENT" => {
+                    header.exponent = content.trim().parse().map_err(|_| invalid())?;
+                }
+                "END OF HEADER" => {
+                    if let (Some(latitudes), Some(longitudes)) =
+                        (header.latitudes, header.longitudes)
+                    {
+                        match latitudes.count.checked_mul(longitudes.count) {
+                            Some(points) if points <= MAX_GRID_POINTS => {}
+                            _ => return Err(invalid()),
+                        }
+                    }
+                    header_ended = true;
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        let (base_radius, height, latitudes, longitudes) = match (
+            header_ended,
+            header.base_radius,
+            header.height,
+            header.latitudes,
+            header.longitudes,
+        ) {
+            (true, Some(radius), Some(height), Some(latitudes), Some(longitudes)) => {
+                (radius, height, latitudes, longitudes)
+            }
+            _ => return Err(IonexError::IncompleteHeader),
+        };
+
+        let mut maps = IonexMaps {
+            base_radius,
+            height,
+            latitudes,
+            longitudes,
+            tec: Vec::new(),
+            rms: Vec::new(),
+        };
+        let grid_size = latitudes.count * longitudes.count;
+        let mut current: Option<PartialMap> = None;
+
+        for (index, line) in lines {
+            let invalid = || IonexError::InvalidLine(index + 1);
+
+            // Data lines fill all 80 columns, so they are read before looking
+            // for a label
+            if let Some(map) = &mut current {
+                if let Some((start, read)) = map.row {
+                    let scale = 10f64.powi(map.exponent);
+                    let values = fixed_i64(line).ok_or_else(invalid)?;
+                    let count = values.len();
+                    if read + count > longitudes.count {
+                        return Err(invalid());
+                    }
+                    for (offset, value) in values.into_iter().enumerate() {
+                        map.values[start + read + offset] = if value == MISSING_VALUE {
+                            None
+                        } else {
+                            Some(value as f64 * scale)
+                        };
+                    }
+                    let read = read + count;
+                    map.row = if read >= longitudes.count {
+                        None
+                    } else {
+                        Some((start, read))
+                    };
+                    continue;
+                }
+            }
+
+            let (content, label) = split_record(line);
+            match label {
+                "START OF TEC MAP" | "START OF RMS MAP" | "START OF HEIGHT MAP" => {
+                    let kind = match label {
+                        "START OF TEC MAP" => MapKind::Tec,
+                        "START OF RMS MAP" => MapKind::Rms,
+                        _ => MapKind::Height,
+                    };
+                    current = Some(PartialMap {
+                        kind,
+                        epoch: None,
+                        exponent: header.exponent,
+                        values: vec![None; grid_size],
+                        row: None,
+                    });
+                }
+                "EPOCH OF CURRENT MAP" => {
+                    let map = current.as_mut().ok_or_else(invalid)?;
+                    map.epoch = Some(parse_epoch(content).ok_or_else(invalid)?);
+                }
+                "EXPONENT" => {
+                    let map = current.as_mut().ok_or_else(invalid)?;
+                    map.exponent = content.trim().parse().map_err(|_| invalid())?;
+                }
+                "LAT/LON1/LON2/DLON/H" => {
+                    let map = current.as_mut().ok_or_else(invalid)?;
+                    let values = fixed_f64(content, 5).ok_or_else(invalid)?;
+                    let row = latitudes.index_of(values[0]).ok_or_else(invalid)?;
+                    map.row = Some((row * longitudes.count, 0));
+                }
+                "END OF TEC MAP" | "END OF RMS MAP" | "END OF HEIGHT MAP" => {
+                    let map = current.take().ok_or_else(invalid)?;
+                    let epoch = map.epoch.ok_or_else(invalid)?;
+                    let grid = GridMap {
+                        epoch,
+                        values: map.values,
+                    };
+                    match map.kind {
+                        MapKind::Tec => maps.tec.push(grid),
+                        MapKind::Rms => maps.rms.push(grid),
+                        MapKind::Height => {}
+                    }
+                }
+                "END OF FILE" => break,
+                _ => {}
+            }
+        }
+
+        if maps.tec.is_empty() {
+            return Err(IonexError::NoMaps);
+        }
+        maps.tec
+            .sort_by(|a, b| a.epoch.partial_cmp(&b.epoch).unwrap());
+        maps.rms
+            .sort_by(|a, b| a.epoch.partial_cmp(&b.epoch).unwrap());
+        Ok(maps)
+    }
+
+    /// Gets the epochs of the TEC maps
+    pub fn epochs(&self) -> Vec<GpsTime> {
+        self.tec.iter().map(|map| map.epoch).collect()
+    }
+
+    /// Gets the height of the ionospheric shell, in meters
+    pub fn height(&self) -> f64 {
+        self.height
+    }
+
+    /// Gets the earth radius the shell height is relative to, in meters
+    pub fn base_radius(&self) -> f64 {
+        self.base_radius
+    }
+
+    /// Interpolates the vertical TEC, in TECU, at a time and a location given
+    /// in degrees
+    ///
+    /// Returns `None` outside of the times or area covered by the maps, or if
+    /// any of the grid points used is missing a value.
+    pub fn vertical_tec(&self, t: &GpsTime, lat: f64, lon: f64) -> Option<f64> {
+        self.interpolate(&self.tec, t, lat, lon)
+    }
+
+    /// Interpolates the RMS error of the vertical TEC, in TECU, at a time and
+    /// a location given in degrees
+    pub fn vertical_tec_rms(&self, t: &GpsTime, lat: f64, lon: f64) -> Option<f64> {
+        self.interpolate(&self.rms, t, lat, lon)
+    }
+
+    /// Computes the ionospheric pierce point of a signal received at
+    /// `receiver` from the direction `azel`, and the mapping factor from
+    /// vertical to slant TEC at that point
+    ///
+    /// The pierce point is returned on the shell, with the shell height.
+    pub fn pierce_point(
+        &self,
+        receiver: &LLHRadians,
+        azel: &AzimuthElevation,
+    ) -> (LLHRadians, f64) {
+        let zenith = std::f64::consts::FRAC_PI_2 - azel.el;
+        let sin_zenith_pp = self.base_radius / (self.base_radius + self.height) * zenith.sin();
+        let zenith_pp = sin_zenith_pp.asin();
+        let psi = zenith - zenith_pp;
+
+        let lat_u = receiver.latitude();
+        let lat_pp = (lat_u.sin() * psi.cos() + lat_u.cos() * psi.sin() * azel.az.cos()).asin();
+        let lon_pp = receiver.longitude() + (psi.sin() * azel.az.sin() / lat_pp.cos()).asin();
+
+        (
+            LLHRadians::new(lat_pp, lon_pp, self.height),
+            1.0 / zenith_pp.cos(),
+        )
+    }
+
+    /// Computes the slant TEC, in TECU, of a signal received at `receiver`
+    /// from the direction `azel`
+    pub fn slant_tec(
+        &self,
+        t: &GpsTime,
+        receiver: &LLHRadians,
+        azel: &AzimuthElevation,
+    ) -> Option<f64> {
+        let (pierce_point, mapping) = self.pierce_point(receiver, azel);
+        let vertical = self.vertical_tec(
+            t,
+            pierce_point.latitude().to_degrees(),
+            pierce_point.longitude().to_degrees(),
+        )?;
+        Some(mapping * vertical)
+    }
+
+    /// Computes the ionospheric delay, in meters, of a signal of frequency
+    /// `frequency` in Hz received at `receiver` from the direction `azel`
+    ///
+    /// The delay is the delay of the code, the carrier phase is advanced by
+    /// the same amount.
+    pub fn slant_delay(
+        &self,
+        t: &GpsTime,
+        receiver: &LLHRadians,
+        azel: &AzimuthElevation,
+        frequency: f64,
+    ) -> Option<f64> {
+        let tec = self.slant_tec(t, receiver, azel)?;
+        Some(TECU_DELAY * tec / (frequency * frequency))
+    }
+
+    /// Interpolates between the two maps around a time, rotating them with the
+    /// sun
+    fn interpolate(&self, maps: &[GridMap], t: &GpsTime, lat: f64, lon: f64) -> Option<f64> {
+        let after = maps.partition_point(|map| map.epoch < *t);
+        if let Some(map) = maps.get(after) {
+            if map.epoch.diff(t).abs() < 1e-6 {
+                return self.interpolate_spatial(map, lat, lon);
+            }
+        }
+        if after == 0 || after == maps.len() {
+            return None;
+        }
+        let (before, after) = (&maps[after - 1], &maps[after]);
+        let span = after.epoch.diff(&before.epoch);
+        let dt_before = t.diff(&before.epoch);
+        let dt_after = t.diff(&after.epoch);
+        let value_before =
+            self.interpolate_spatial(before, lat, lon + dt_before * EARTH_ROTATION_DEG)?;
+        let value_after =
+            self.interpolate_spatial(after, lat, lon + dt_after * EARTH_ROTATION_DEG)?;
+        Some((-dt_after * value_before + dt_before * value_after) / span)
+    }
+
+    fn interpolate_spatial(&self, map: &GridMap, lat: f64, lon: f64) -> Option<f64> {
+        let (row, q) = self.latitudes.locate(lat)?;
+        let lon = if self.longitudes.is_global() {
+            let start = self.longitudes.start.min(
+                self.longitudes.start + (self.longitudes.count - 1) as f64 * self.longitudes.step,
+            );
+            (lon - start).rem_euclid(360.0) + start
+        } else {
+            lon
+        };
+        let (column, p) = self.longitudes.locate(lon)?;
+
+        let value = |row: usize, column: usize| map.values[row * self.longitudes.count + column];
+        let e00 = value(row, column)?;
+        let e10 = value(row, column + 1)?;
+        let e01 = value(row + 1, column)?;
+        let e11 = value(row + 1, column + 1)?;
+        Some((1.0 - p) * (1.0 - q) * e00 + p * (1.0 - q) * e10 + q * (1.0 - p) * e01 + p * q * e11)
+    }
+}
+
+/// Splits a record into its content, the first 60 columns, and its label
+fn split_record(line: &str) -> (&str, &str) {
+    match (line.get(..60), line.get(60..)) {
+        (Some(content), Some(label)) => (content, label.trim()),
+        _ => (line, ""),
+    }
+}
+
+fn parse_f64(text: &str) -> Option<f64> {
+    text.split_whitespace().next()?.parse().ok()
+}
+
+/// Parses the `2X,nF6.1` values of a grid definition, which may run into
+/// each other
+fn fixed_f64(content: &str, count: usize) -> Option<Vec<f64>> {
+    (0..count)
+        .map(|i| content.get(2 + 6 * i..8 + 6 * i)?.trim().parse().ok())
+        .collect()
+}
+
+/// Parses the `16I5` values of a data line
+fn fixed_i64(line: &str) -> Option<Vec<i64>> {
+    let line = line.trim_end();
+    (0..(line.len() + 4) / 5)
+        .map(|i| {
+            line.get(5 * i..(5 * i + 5).min(line.len()))?
+                .trim()
+                .parse()
+                .ok()
+        })
+        .collect()
+}
+
+/// Parses a `6I6` epoch, in UTC
+fn parse_epoch(content: &str) -> Option<GpsTime> {
+    let values: Option<Vec<u16>> = content
+        .split_whitespace()
+        .take(6)
+        .map(|value| value.parse().ok())
+        .collect();
+    let values = values?;
+    if values.len() != 6 || values[1] > 12 || values[2] > 31 {
+        return None;
+    }
+    let utc = UtcTime::from_date(
+        values[0],
+        values[1] as u8,
+        values[2] as u8,
+        values[3] as u8,
+        values[4] as u8,
+        f64::from(values[5]),
+    );
+    Some(utc.to_gps_hardcoded())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+
+    fn record(content: &str, label: &str) -> String {
+        format!("{:<60}{}\n", content, label)
+    }
+
+    fn tec_map(index: usize, hour: u8, rows: [[i64; 5]; 2]) -> String {
+        let mut map = record(&format!("{:6}", index), "START OF TEC MAP");
+        map += &record(
+            &format!("  2022     3     6 {:5}     0     0", hour),
+            "EPOCH OF CURRENT MAP",
+        );
+        for (lat, row) in [40.0, 30.0].iter().zip(rows.iter()) {
+            map += &record(
+                &format!("  {:6.1}-140.0-100.0  10.0 450.0", lat),
+                "LAT/LON1/LON2/DLON/H",
+            );
+            let values: Vec<String> = row.iter().map(|value| format!("{:5}", value)).collect();
+            map += &values.concat();
+            map += "\n";
+        }
+        map + &record(&format!("{:6}", index), "END OF TEC MAP")
+    }
+
+    fn ionex() -> String {
+        let mut text = record(
+            "     1.0            IONOSPHERE MAPS     GPS",
+            "IONEX VERSION / TYPE",
+        );
+        text += &record("  COSZ", "MAPPING FUNCTION");
+        text += &record("  6371.0", "BASE RADIUS");
+        text += &record("   450.0 450.0   0.0", "HGT1 / HGT2 / DHGT");
+        text += &record("    40.0  30.0 -10.0", "LAT1 / LAT2 / DLAT");
+        text += &record("  -140.0-100.0  10.0", "LON1 / LON2 / DLON");
+        text += &record("    -1", "EXPONENT");
+        text += &record("", "END OF HEADER");
+        text += &tec_map(1, 0, [[10, 20, 30, 40, 50], [30, 40, 50, 60, 70]]);
+        text += &tec_map(2, 2, [[20, 40, 60, 80, 100], [60, 80, 100, 120, 9999]]);
+        text + &record("", "END OF FILE")
+    }
+
+    #[test]
+    fn parse_and_interpolate() {
+        let maps = IonexMaps::from_ionex(&ionex()).unwrap();
+        let first = GpsTime::new(2200, 18.0).unwrap();
+        let second = GpsTime::new(2200, 7218.0).unwrap();
+        assert_eq!(maps.epochs(), vec![first, second]);
+        assert_float_eq!(maps.height(), 450e3, abs <= 1e-9);
+        assert_float_eq!(maps.base_radius(), 6371e3, abs <= 1e-9);
+
+        // On the grid and between grid points
+        assert_float_eq!(
+            maps.vertical_tec(&first, 40.0, -130.0).unwrap(),
+            2.0,
+            abs <= 1e-12
+        );
+        assert_float_eq!(
+            maps.vertical_tec(&first, 35.0, -125.0).unwrap(),
+            3.5,
+            abs <= 1e-12
+        );
+
+        // Halfway between the maps, each map is rotated by 15°
+        let middle = GpsTime::new(2200, 3618.0).unwrap();
+        assert_float_eq!(
+            maps.vertical_tec(&middle, 35.0, -125.0).unwrap(),
+            4.5,
+            abs <= 1e-9
+        );
+
+        assert_eq!(maps.vertical_tec(&second, 35.0, -105.0), None);
+        assert_eq!(maps.vertical_tec(&first, 45.0, -125.0), None);
+        assert_eq!(
+            maps.vertical_tec(&GpsTime::new(2200, 0.0).unwrap(), 35.0, -125.0),
+            None
+        );
+        assert_eq!(maps.vertical_tec_rms(&first, 35.0, -125.0), None);
+
+        // Straight up the pierce point is above the receiver
+        let receiver = LLHRadians::new(35f64.to_radians(), -125f64.to_radians(), 0.0);
+        let zenith = AzimuthElevation::new(0.0, std::f64::consts::FRAC_PI_2);
+        assert_float_eq!(
+            maps.slant_tec(&first, &receiver, &zenith).unwrap(),
+            3.5,
+            abs <= 1e-9
+        );
+        let l1 = 1.57542e9;
+        assert_float_eq!(
+            maps.slant_delay(&first, &receiver, &zenith, l1).unwrap(),
+            40.3e16 * 3.5 / (l1 * l1),
+            abs <= 1e-12
+        );
+
+        let low = AzimuthElevation::new(0.0, 30f64.to_radians());
+        let (pierce_point, mapping) = maps.pierce_point(&receiver, &low);
+        assert!(pierce_point.latitude() > receiver.latitude());
+        assert_float_eq!(pierce_point.longitude(), receiver.longitude(), abs <= 1e-12);
+        assert!(mapping > 1.0 && mapping < 2.0);
+    }
+
+    #[test]
+    fn invalid_files() {
+        let text = ionex();
+        assert_eq!(
+            IonexMaps::from_ionex(&text.replace("  6371.0", "")),
+            Err(IonexError::IncompleteHeader)
+        );
+        assert_eq!(
+            IonexMaps::from_ionex(&text.replace("   450.0 450.0   0.0", "   450.0 800.0  50.0")),
+            Err(IonexError::MultipleHeights)
+        );
+        let header_end = text.find("START OF TEC MAP").unwrap();
+        let header = &text[..text[..header_end].rfind('\n').unwrap() + 1];
+        assert_eq!(IonexMaps::from_ionex(header), Err(IonexError::NoMaps));
+        assert_eq!(
+            IonexMaps::from_ionex(&text.replace("   10   20", "   1x   20")),
+            Err(IonexError::InvalidLine(12))
+        );
+        // An axis longer than the globe, or a grid too fine to hold
+        let lat_line = text.lines().position(|line| line.contains("LAT1")).unwrap();
+        assert_eq!(
+            IonexMaps::from_ionex(&text.replace("    40.0  30.0 -10.0", "    90.0 -95.0 -10.0")),
+            Err(IonexError::InvalidLine(lat_line + 1))
+        );
+        let fine = text
+            .replace("    40.0  30.0 -10.0", "    40.0  30.0-0.001")
+            .replace("  -140.0-100.0  10.0", "  -140.0-100.0 0.001");
+        let header_end = text
+            .lines()
+            .position(|line| line.contains("END OF HEADER"))
+            .unwrap();
+        assert_eq!(
+            IonexMaps::from_ionex(&fine),
+            Err(IonexError::InvalidLine(header_end + 1))
+        );
+    }
+}
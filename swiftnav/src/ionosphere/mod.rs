@@ -13,9 +13,15 @@
 //! parameters are broadcast by the GPS constellation. A function to decode the
 //! parameters from the raw subframe is provided.
 //!
+//! For post-processing, the global ionosphere maps published by analysis
+//! centers in the IONEX format are much more accurate, and can be loaded with
+//! the [`ionex`] module.
+//!
 //! # References
 //!  * IS-GPS-200H, Section 20.3.3.5.2.5 and Figure 20-4
 
+pub mod ionex;
+
 use crate::time::GpsTime;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
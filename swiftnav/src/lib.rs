@@ -24,7 +24,11 @@
 //! the location of itself in relation to the satellites.
 //!
 //! `swiftnav` does not provide any functionality for communicating with
-//! receivers made by Swift Navigation, or any manufacturer.
+//! receivers made by Swift Navigation, or any manufacturer. It can however
+//! decode the raw observations and navigation data output by u-blox receivers
+//! in the [`ubx`] module, and the RTCM corrections sent by reference stations
+//! in the [`rtcm`] module. With the `futures` feature enabled, the `stream`
+//! module decodes them from asynchronous byte streams.
 //! [libsbp](https://github.com/swift-nav/libsbp) is the library to use if you
 //! want to communicate with receivers using Swift Binary Protocol (SBP).
 //!
@@ -38,7 +42,8 @@
 //! Several different coordinate types have representations and the ability to
 //! convert between them. Earth centered earth fixed (ECEF), Latitude longitude and
 //! height (both in radians and degrees), and Azimuth and elevation coordinates are
-//! available.
+//! available. With the `export` feature enabled, positions and trajectories can
+//! be written as GPX, KML or GeoJSON by the `export` module.
 //!
 //! ## Ephemeris
 //! Decoding and evaluation of broadcast ephemeris for all major GNSS constellations
@@ -48,8 +53,8 @@
 //! ## Troposphere and Ionosphere
 //! Two major sources of signal error in GNSS are the troposphere and ionosphere.
 //! `swiftnav` provides the ability to decode and use the broadcast Klobuchar
-//! ionosphere model. An implementation of the UNM3m troposphere model is also
-//! provided.
+//! ionosphere model. Implementations of the UNM3m, Saastamoinen and Hopfield
+//! troposphere models are also provided.
 //!
 //! ## Single epoch position solver
 //! A simple least squares position solver is also included. This allows you to
@@ -57,15 +62,47 @@
 //! It uses a least squares algorith, so no state is maintained between solves.
 //! This can be used to seed your own position estimation algorithm with a rough
 //! starting location.
+//!
+//! Going the other way, a rough position from another source such as an NMEA
+//! GGA sentence or a cell tower lookup can be described as a
+//! [`CoarseHint`](solver::hint::CoarseHint) with its uncertainty. Hints can
+//! seed the [filter based solver](solver::filter::KalmanPvt::seed) and narrow
+//! the doppler search window used during acquisition.
 
+pub mod bias;
+pub mod config;
 pub mod coords;
+pub mod coverage;
 pub mod edc;
+pub mod ellipsoid;
 pub mod ephemeris;
+#[cfg(feature = "export")]
+pub mod export;
 pub mod geoid;
+pub mod history;
 pub mod ionosphere;
+pub mod monitor;
 pub mod navmeas;
+pub mod nmea;
+pub mod quality;
 pub mod reference_frame;
+pub mod route;
+pub mod rtcm;
+pub mod satellite_attitude;
+pub mod sbas;
 pub mod signal;
+pub mod simulate;
+pub mod sky;
 pub mod solver;
+#[cfg(feature = "futures")]
+pub mod stream;
+pub mod tides;
 pub mod time;
+pub mod trajectory;
 pub mod troposphere;
+pub mod ubx;
+pub mod visibility;
+
+/// Speed of light in vacuum, in m/s, as defined by the GNSS interface
+/// specifications
+pub const SPEED_OF_LIGHT: f64 = 299_792_458.0;
@@ -0,0 +1,447 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Spoofing and jamming heuristics
+//!
+//! The [`IntegrityMonitor`] looks at consecutive epochs of measurements and
+//! solutions for the symptoms commonly seen when a receiver is jammed or
+//! spoofed:
+//!  * A sudden change of the CN0 common to most signals, caused by a jammer
+//!    raising the noise floor or a spoofer overpowering the real signals
+//!  * Jumps in the receiver clock drift, as a spoofer takes over the timing
+//!  * Position changes which don't agree with the estimated velocity
+//!  * The same signal being tracked more than once with different ranges
+//!
+//! These are heuristics only, each of them can also be triggered by benign
+//! events such as driving under a bridge. The alerts are meant to be combined
+//! with other information before any action is taken.
+
+use crate::coords::ECEF;
+use crate::navmeas::NavigationMeasurement;
+use crate::signal::GnssSignal;
+use crate::time::GpsTime;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Thresholds used by the [`IntegrityMonitor`]
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MonitorSettings {
+    cn0_jump: f64,
+    min_cn0_signals: usize,
+    max_clock_drift_rate: f64,
+    max_position_discrepancy: f64,
+    duplicate_tolerance: f64,
+    max_epoch_gap: f64,
+}
+
+impl MonitorSettings {
+    /// Makes the default settings
+    ///
+    /// Note: The default settings consist of
+    ///  * An average CN0 change of 6 dB-Hz over at least 4 signals
+    ///  * A clock drift change of 1e-8 s/s per second
+    ///  * A position discrepancy of 10 m
+    ///  * Duplicate signals which differ by more than 10 m
+    ///  * Epochs more than 5 s apart aren't compared
+    pub fn new() -> MonitorSettings {
+        MonitorSettings {
+            cn0_jump: 6.0,
+            min_cn0_signals: 4,
+            max_clock_drift_rate: 1e-8,
+            max_position_discrepancy: 10.0,
+            duplicate_tolerance: 10.0,
+            max_epoch_gap: 5.0,
+        }
+    }
+
+    /// Sets the average CN0 change, in dB-Hz, which triggers an alert
+    pub fn set_cn0_jump(self, cn0_jump: f64) -> MonitorSettings {
+        MonitorSettings { cn0_jump, ..self }
+    }
+
+    /// Sets the number of signals needed in consecutive epochs to check the
+    /// CN0
+    pub fn set_min_cn0_signals(self, min_cn0_signals: usize) -> MonitorSettings {
+        MonitorSettings {
+            min_cn0_signals,
+            ..self
+        }
+    }
+
+    /// Sets the largest expected rate of change of the clock drift, in s/s²
+    pub fn set_max_clock_drift_rate(self, max_clock_drift_rate: f64) -> MonitorSettings {
+        MonitorSettings {
+            max_clock_drift_rate,
+            ..self
+        }
+    }
+
+    /// Sets the largest difference, in meters, between the change in position
+    /// and the integrated velocity
+    pub fn set_max_position_discrepancy(self, max_position_discrepancy: f64) -> MonitorSettings {
+        MonitorSettings {
+            max_position_discrepancy,
+            ..self
+        }
+    }
+
+    /// Sets the largest pseudorange difference, in meters, between two
+    /// records of the same signal which isn't reported
+    pub fn set_duplicate_tolerance(self, duplicate_tolerance: f64) -> MonitorSettings {
+        MonitorSettings {
+            duplicate_tolerance,
+            ..self
+        }
+    }
+
+    /// Sets the longest time, in seconds, between two epochs which are
+    /// compared with each other
+    pub fn set_max_epoch_gap(self, max_epoch_gap: f64) -> MonitorSettings {
+        MonitorSettings {
+            max_epoch_gap,
+            ..self
+        }
+    }
+
+    pub fn cn0_jump(&self) -> f64 {
+        self.cn0_jump
+    }
+
+    pub fn min_cn0_signals(&self) -> usize {
+        self.min_cn0_signals
+    }
+
+    pub fn max_clock_drift_rate(&self) -> f64 {
+        self.max_clock_drift_rate
+    }
+
+    pub fn max_position_discrepancy(&self) -> f64 {
+        self.max_position_discrepancy
+    }
+
+    pub fn duplicate_tolerance(&self) -> f64 {
+        self.duplicate_tolerance
+    }
+
+    pub fn max_epoch_gap(&self) -> f64 {
+        self.max_epoch_gap
+    }
+}
+
+impl Default for MonitorSettings {
+    fn default() -> MonitorSettings {
+        MonitorSettings::new()
+    }
+}
+
+/// An alert raised by the [`IntegrityMonitor`]
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub enum Alert {
+    /// The CN0 of most signals changed together
+    CommonModeCn0Jump {
+        /// Average change of the CN0, in dB-Hz
+        change: f64,
+        /// Number of signals compared
+        signals: usize,
+    },
+    /// The receiver clock drift changed faster than expected
+    ClockDriftAnomaly {
+        /// Clock drift of the latest epoch, in s/s
+        drift: f64,
+        /// Rate of change of the clock drift, in s/s²
+        rate: f64,
+    },
+    /// The change in position doesn't agree with the estimated velocity
+    PositionVelocityMismatch {
+        /// Distance between the position and the one predicted from the
+        /// velocity, in meters
+        discrepancy: f64,
+    },
+    /// A signal was tracked more than once with different pseudoranges
+    DuplicateSignal {
+        sid: GnssSignal,
+        /// Number of records of the signal
+        count: usize,
+        /// Difference between the largest and smallest pseudoranges, in meters
+        spread: f64,
+    },
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Alert::CommonModeCn0Jump { change, signals } => write!(
+                f,
+                "Common mode CN0 change of {:.1} dB-Hz over {} signals",
+                change, signals
+            ),
+            Alert::ClockDriftAnomaly { drift, rate } => write!(
+                f,
+                "Clock drift anomaly, drift {:e} s/s changing at {:e} s/s²",
+                drift, rate
+            ),
+            Alert::PositionVelocityMismatch { discrepancy } => {
+                write!(f, "Position and velocity disagree by {:.1} m", discrepancy)
+            }
+            Alert::DuplicateSignal { sid, count, spread } => write!(
+                f,
+                "Signal {} tracked {} times with ranges {:.1} m apart",
+                sid, count, spread
+            ),
+        }
+    }
+}
+
+struct SolutionState {
+    time: GpsTime,
+    position: ECEF,
+    velocity: ECEF,
+    clock_drift: f64,
+}
+
+/// Monitors consecutive epochs for signs of jamming and spoofing
+///
+/// Measurements and solutions are checked separately, each is compared
+/// against the previous one given to the monitor.
+pub struct IntegrityMonitor {
+    settings: MonitorSettings,
+    last_cn0: Option<(GpsTime, BTreeMap<GnssSignal, f64>)>,
+    last_solution: Option<SolutionState>,
+}
+
+impl IntegrityMonitor {
+    pub fn new(settings: MonitorSettings) -> IntegrityMonitor {
+        IntegrityMonitor {
+            settings,
+            last_cn0: None,
+            last_solution: None,
+        }
+    }
+
+    pub fn settings(&self) -> &MonitorSettings {
+        &self.settings
+    }
+
+    /// Checks an epoch of measurements for common mode CN0 jumps and
+    /// duplicated signals
+    pub fn check_measurements(
+        &mut self,
+        time: GpsTime,
+        measurements: &[NavigationMeasurement],
+    ) -> Vec<Alert> {
+        let mut alerts = self.check_duplicates(measurements);
+
+        let mut cn0s = BTreeMap::new();
+        for nm in measurements {
+            if let Some(cn0) = nm.cn0() {
+                cn0s.entry(nm.sid()).or_insert(cn0);
+            }
+        }
+
+        if let Some((last_time, last_cn0s)) = &self.last_cn0 {
+            if time.diff(last_time).abs() <= self.settings.max_epoch_gap {
+                let changes: Vec<f64> = cn0s
+                    .iter()
+                    .filter_map(|(sid, cn0)| last_cn0s.get(sid).map(|last| cn0 - last))
+                    .collect();
+                if changes.len() >= self.settings.min_cn0_signals {
+                    let change = changes.iter().sum::<f64>() / changes.len() as f64;
+                    // Most of the signals have to move in the same direction,
+                    // a few large changes are more likely multipath
+                    let agreeing = changes
+                        .iter()
+                        .filter(|c| c.signum() == change.signum())
+                        .filter(|c| c.abs() >= 0.5 * self.settings.cn0_jump)
+                        .count();
+                    if change.abs() >= self.settings.cn0_jump && 4 * agreeing >= 3 * changes.len() {
+                        alerts.push(Alert::CommonModeCn0Jump {
+                            change,
+                            signals: changes.len(),
+                        });
+                    }
+                }
+            }
+        }
+
+        self.last_cn0 = Some((time, cn0s));
+        alerts
+    }
+
+    /// Checks a solution for clock drift anomalies and disagreement between
+    /// the position and velocity
+    ///
+    /// `clock_drift` is in s/s
+    pub fn check_solution(
+        &mut self,
+        time: GpsTime,
+        position: ECEF,
+        velocity: ECEF,
+        clock_drift: f64,
+    ) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+
+        if let Some(last) = &self.last_solution {
+            let dt = time.diff(&last.time);
+            if dt > 0.0 && dt <= self.settings.max_epoch_gap {
+                let rate = (clock_drift - last.clock_drift) / dt;
+                if rate.abs() > self.settings.max_clock_drift_rate {
+                    alerts.push(Alert::ClockDriftAnomaly {
+                        drift: clock_drift,
+                        rate,
+                    });
+                }
+
+                let mean_velocity = 0.5 * (last.velocity + velocity);
+                let predicted = last.position + dt * mean_velocity;
+                let error = position - predicted;
+                let discrepancy = error
+                    .as_array_ref()
+                    .iter()
+                    .map(|v| v * v)
+                    .sum::<f64>()
+                    .sqrt();
+                if discrepancy > self.settings.max_position_discrepancy {
+                    alerts.push(Alert::PositionVelocityMismatch { discrepancy });
+                }
+            }
+        }
+
+        self.last_solution = Some(SolutionState {
+            time,
+            position,
+            velocity,
+            clock_drift,
+        });
+        alerts
+    }
+
+    /// Forgets the previous epochs, e.g. after a loss of tracking
+    pub fn reset(&mut self) {
+        self.last_cn0 = None;
+        self.last_solution = None;
+    }
+
+    fn check_duplicates(&self, measurements: &[NavigationMeasurement]) -> Vec<Alert> {
+        let mut pseudoranges: BTreeMap<GnssSignal, Vec<f64>> = BTreeMap::new();
+        for nm in measurements {
+            if let Some(pseudorange) = nm.pseudorange() {
+                pseudoranges.entry(nm.sid()).or_default().push(pseudorange);
+            }
+        }
+
+        pseudoranges
+            .into_iter()
+            .filter(|(_, ranges)| ranges.len() > 1)
+            .filter_map(|(sid, ranges)| {
+                let max = ranges.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                let min = ranges.iter().cloned().fold(f64::INFINITY, f64::min);
+                let spread = max - min;
+                if spread > self.settings.duplicate_tolerance {
+                    Some(Alert::DuplicateSignal {
+                        sid,
+                        count: ranges.len(),
+                        spread,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::LLHDegrees;
+    use crate::signal::Code;
+    use std::time::Duration;
+
+    fn measurements(cn0: f64, duplicate: Option<f64>) -> Vec<NavigationMeasurement> {
+        let mut measurements: Vec<NavigationMeasurement> = (1..=6)
+            .map(|sat| {
+                let mut nm = NavigationMeasurement::new();
+                nm.set_sid(GnssSignal::new(sat, Code::GpsL1ca).unwrap());
+                nm.set_pseudorange(21_000_000.0 + 1000.0 * f64::from(sat));
+                nm.set_cn0(cn0 + f64::from(sat));
+                nm
+            })
+            .collect();
+        if let Some(offset) = duplicate {
+            let mut nm = measurements[0].clone();
+            nm.set_pseudorange(nm.pseudorange().unwrap() + offset);
+            measurements.push(nm);
+        }
+        measurements
+    }
+
+    #[test]
+    fn measurement_alerts() {
+        let mut monitor = IntegrityMonitor::new(MonitorSettings::new());
+        let t0 = GpsTime::new(2100, 1000.0).unwrap();
+        let t1 = t0 + Duration::from_secs(1);
+        let t2 = t0 + Duration::from_secs(2);
+
+        assert!(monitor
+            .check_measurements(t0, &measurements(40.0, None))
+            .is_empty());
+        assert!(monitor
+            .check_measurements(t1, &measurements(41.0, Some(2.0)))
+            .is_empty());
+
+        let alerts = monitor.check_measurements(t2, &measurements(32.0, Some(3000.0)));
+        assert_eq!(alerts.len(), 2);
+        assert!(matches!(
+            alerts[0],
+            Alert::DuplicateSignal { count: 2, spread, .. } if spread == 3000.0
+        ));
+        assert!(matches!(
+            alerts[1],
+            Alert::CommonModeCn0Jump { change, signals: 6 } if change == -9.0
+        ));
+    }
+
+    #[test]
+    fn solution_alerts() {
+        let mut monitor = IntegrityMonitor::new(MonitorSettings::new());
+        let t0 = GpsTime::new(2100, 1000.0).unwrap();
+        let position = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let velocity = ECEF::new(10.0, -5.0, 2.0);
+
+        assert!(monitor
+            .check_solution(t0, position, velocity, 1e-7)
+            .is_empty());
+        let t1 = t0 + Duration::from_secs(1);
+        let position = position + velocity;
+        assert!(monitor
+            .check_solution(t1, position, velocity, 1.05e-7)
+            .is_empty());
+
+        // The position jumps and the clock drift changes abruptly
+        let t2 = t1 + Duration::from_secs(1);
+        let alerts = monitor.check_solution(
+            t2,
+            position + velocity + ECEF::new(50.0, 0.0, 0.0),
+            velocity,
+            3e-7,
+        );
+        assert_eq!(alerts.len(), 2);
+        assert!(matches!(alerts[0], Alert::ClockDriftAnomaly { .. }));
+        assert!(
+            matches!(alerts[1], Alert::PositionVelocityMismatch { discrepancy } if (discrepancy - 50.0).abs() < 1e-6)
+        );
+
+        // Nothing is compared across a reset
+        monitor.reset();
+        assert!(monitor
+            .check_solution(t2, position, velocity, 1e-5)
+            .is_empty());
+    }
+}
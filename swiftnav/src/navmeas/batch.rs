@@ -0,0 +1,420 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Columnar storage of many epochs of measurements
+//!
+//! Post-processing a long session means working through millions of
+//! measurements, usually one observable at a time. An [`ObservationBatch`]
+//! stores the observables of many epochs in parallel arrays, one per
+//! observable, so a pass over all of the pseudoranges only touches
+//! pseudoranges. The measurements are stored epoch by epoch, in time order,
+//! and can be accessed per epoch through an [`EpochView`] or converted back to
+//! [`NavigationMeasurement`]s.
+//!
+//! Invalid observables are stored as NaN. Satellite states aren't stored, as
+//! they are derived from the ephemeris rather than observed.
+
+use super::NavigationMeasurement;
+use crate::history::OutOfOrder;
+use crate::signal::{Code, GnssSignal};
+use crate::time::GpsTime;
+use std::ops::Range;
+use std::time::Duration;
+
+/// Measurements of many epochs, stored with one array per observable
+#[derive(Debug, Clone, Default)]
+pub struct ObservationBatch {
+    times: Vec<GpsTime>,
+    epoch_starts: Vec<usize>,
+    sats: Vec<u16>,
+    codes: Vec<Code>,
+    pseudoranges: Vec<f64>,
+    carrier_phases: Vec<f64>,
+    dopplers: Vec<f64>,
+    cn0s: Vec<f64>,
+    lock_times: Vec<Duration>,
+    flags: Vec<u16>,
+}
+
+impl ObservationBatch {
+    pub fn new() -> ObservationBatch {
+        ObservationBatch::default()
+    }
+
+    /// Makes an empty batch with room for the given number of epochs and
+    /// measurements
+    pub fn with_capacity(epochs: usize, measurements: usize) -> ObservationBatch {
+        ObservationBatch {
+            times: Vec::with_capacity(epochs),
+            epoch_starts: Vec::with_capacity(epochs),
+            sats: Vec::with_capacity(measurements),
+            codes: Vec::with_capacity(measurements),
+            pseudoranges: Vec::with_capacity(measurements),
+            carrier_phases: Vec::with_capacity(measurements),
+            dopplers: Vec::with_capacity(measurements),
+            cn0s: Vec::with_capacity(measurements),
+            lock_times: Vec::with_capacity(measurements),
+            flags: Vec::with_capacity(measurements),
+        }
+    }
+
+    /// Makes a batch from epochs of measurements, which must be in
+    /// increasing time order
+    pub fn from_epochs<'a, I>(epochs: I) -> Result<ObservationBatch, OutOfOrder>
+    where
+        I: IntoIterator<Item = (GpsTime, &'a [NavigationMeasurement])>,
+    {
+        let mut batch = ObservationBatch::new();
+        for (t, measurements) in epochs {
+            batch.push_epoch(t, measurements)?;
+        }
+        Ok(batch)
+    }
+
+    /// Appends an epoch of measurements
+    ///
+    /// Epochs must be pushed in increasing time order, an epoch at the same
+    /// time or before the latest epoch is rejected.
+    pub fn push_epoch(
+        &mut self,
+        t: GpsTime,
+        measurements: &[NavigationMeasurement],
+    ) -> Result<(), OutOfOrder> {
+        if let Some(latest) = self.times.last() {
+            if t.diff(latest) <= 0.0 {
+                return Err(OutOfOrder {
+                    time: t,
+                    latest: *latest,
+                });
+            }
+        }
+
+        self.times.push(t);
+        self.epoch_starts.push(self.sats.len());
+        for nm in measurements {
+            let sid = nm.sid();
+            self.sats.push(sid.sat());
+            self.codes.push(sid.code());
+            self.pseudoranges.push(nm.pseudorange().unwrap_or(f64::NAN));
+            self.carrier_phases
+                .push(nm.carrier_phase().unwrap_or(f64::NAN));
+            self.dopplers
+                .push(nm.measured_doppler().unwrap_or(f64::NAN));
+            self.cn0s.push(nm.cn0().unwrap_or(f64::NAN));
+            self.lock_times.push(nm.lock_time());
+            self.flags.push(nm.flags());
+        }
+        Ok(())
+    }
+
+    /// Gets the number of epochs
+    pub fn epoch_count(&self) -> usize {
+        self.times.len()
+    }
+
+    /// Gets the total number of measurements across all epochs
+    pub fn len(&self) -> usize {
+        self.sats.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.times.is_empty()
+    }
+
+    /// Gets the time of each epoch
+    pub fn times(&self) -> &[GpsTime] {
+        &self.times
+    }
+
+    /// Gets the satellite number of each measurement
+    pub fn sats(&self) -> &[u16] {
+        &self.sats
+    }
+
+    /// Gets the signal code of each measurement
+    pub fn codes(&self) -> &[Code] {
+        &self.codes
+    }
+
+    /// Gets the pseudorange of each measurement, in meters
+    pub fn pseudoranges(&self) -> &[f64] {
+        &self.pseudoranges
+    }
+
+    /// Gets the carrier phase of each measurement, in cycles
+    pub fn carrier_phases(&self) -> &[f64] {
+        &self.carrier_phases
+    }
+
+    /// Gets the measured doppler of each measurement, in Hertz
+    pub fn dopplers(&self) -> &[f64] {
+        &self.dopplers
+    }
+
+    /// Gets the CN0 of each measurement, in dB-Hz
+    pub fn cn0s(&self) -> &[f64] {
+        &self.cn0s
+    }
+
+    pub fn lock_times(&self) -> &[Duration] {
+        &self.lock_times
+    }
+
+    pub fn flags(&self) -> &[u16] {
+        &self.flags
+    }
+
+    /// Gets the range of measurement indices belonging to an epoch
+    pub fn epoch_range(&self, epoch: usize) -> Option<Range<usize>> {
+        let start = *self.epoch_starts.get(epoch)?;
+        let end = self
+            .epoch_starts
+            .get(epoch + 1)
+            .copied()
+            .unwrap_or(self.sats.len());
+        Some(start..end)
+    }
+
+    /// Gets the epoch containing a measurement
+    pub fn epoch_of(&self, index: usize) -> Option<usize> {
+        if index >= self.len() {
+            return None;
+        }
+        // The number of epochs starting at or before the index, less one
+        Some(self.epoch_starts.partition_point(|start| *start <= index) - 1)
+    }
+
+    /// Gets the signal of a measurement
+    pub fn sid(&self, index: usize) -> Option<GnssSignal> {
+        let sat = *self.sats.get(index)?;
+        GnssSignal::new(sat, self.codes[index]).ok()
+    }
+
+    /// Rebuilds a single measurement
+    pub fn measurement(&self, index: usize) -> Option<NavigationMeasurement> {
+        let sid = self.sid(index)?;
+        let mut nm = NavigationMeasurement::new();
+        nm.set_sid(sid);
+        if self.pseudoranges[index].is_finite() {
+            nm.set_pseudorange(self.pseudoranges[index]);
+        }
+        if self.carrier_phases[index].is_finite() {
+            nm.set_carrier_phase(self.carrier_phases[index]);
+        }
+        if self.dopplers[index].is_finite() {
+            nm.set_measured_doppler(self.dopplers[index]);
+        }
+        if self.cn0s[index].is_finite() {
+            nm.set_cn0(self.cn0s[index]);
+        }
+        nm.set_lock_time(self.lock_times[index]);
+        nm.set_flags(self.flags[index]);
+        Some(nm)
+    }
+
+    /// Gets a view of a single epoch
+    pub fn epoch(&self, epoch: usize) -> Option<EpochView<'_>> {
+        Some(EpochView {
+            batch: self,
+            time: *self.times.get(epoch)?,
+            range: self.epoch_range(epoch)?,
+        })
+    }
+
+    /// Iterates over the epochs in time order
+    pub fn epochs(&self) -> impl DoubleEndedIterator<Item = EpochView<'_>> + ExactSizeIterator {
+        (0..self.epoch_count()).map(move |i| self.epoch(i).expect("Epoch is in range"))
+    }
+
+    /// Finds the epoch closest to time `t`, if one is within `tolerance`
+    /// seconds of it
+    pub fn find_epoch(&self, t: GpsTime, tolerance: f64) -> Option<usize> {
+        let after = self.times.partition_point(|time| time.diff(&t) < 0.0);
+        let candidates = [after.checked_sub(1), Some(after)];
+        candidates
+            .iter()
+            .flatten()
+            .filter(|i| **i < self.times.len())
+            .map(|i| (*i, self.times[*i].diff(&t).abs()))
+            .filter(|(_, dt)| *dt <= tolerance)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+
+    /// Converts the batch back into epochs of measurements
+    pub fn to_epochs(&self) -> Vec<(GpsTime, Vec<NavigationMeasurement>)> {
+        self.epochs()
+            .map(|epoch| (epoch.time(), epoch.measurements()))
+            .collect()
+    }
+
+    /// Removes all epochs, keeping the allocated memory
+    pub fn clear(&mut self) {
+        self.times.clear();
+        self.epoch_starts.clear();
+        self.sats.clear();
+        self.codes.clear();
+        self.pseudoranges.clear();
+        self.carrier_phases.clear();
+        self.dopplers.clear();
+        self.cn0s.clear();
+        self.lock_times.clear();
+        self.flags.clear();
+    }
+}
+
+/// A single epoch of an [`ObservationBatch`]
+///
+/// The observable slices only cover the measurements of this epoch.
+#[derive(Debug, Clone)]
+pub struct EpochView<'a> {
+    batch: &'a ObservationBatch,
+    time: GpsTime,
+    range: Range<usize>,
+}
+
+impl<'a> EpochView<'a> {
+    pub fn time(&self) -> GpsTime {
+        self.time
+    }
+
+    /// Gets the range of indices of the epoch measurements within the batch
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.range.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.range.is_empty()
+    }
+
+    pub fn sats(&self) -> &'a [u16] {
+        &self.batch.sats[self.range.clone()]
+    }
+
+    pub fn codes(&self) -> &'a [Code] {
+        &self.batch.codes[self.range.clone()]
+    }
+
+    pub fn pseudoranges(&self) -> &'a [f64] {
+        &self.batch.pseudoranges[self.range.clone()]
+    }
+
+    pub fn carrier_phases(&self) -> &'a [f64] {
+        &self.batch.carrier_phases[self.range.clone()]
+    }
+
+    pub fn dopplers(&self) -> &'a [f64] {
+        &self.batch.dopplers[self.range.clone()]
+    }
+
+    pub fn cn0s(&self) -> &'a [f64] {
+        &self.batch.cn0s[self.range.clone()]
+    }
+
+    /// Rebuilds the measurements of the epoch
+    pub fn measurements(&self) -> Vec<NavigationMeasurement> {
+        self.range
+            .clone()
+            .filter_map(|i| self.batch.measurement(i))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measurement(
+        sat: u16,
+        code: Code,
+        pseudorange: f64,
+        phase: Option<f64>,
+    ) -> NavigationMeasurement {
+        let mut nm = NavigationMeasurement::new();
+        nm.set_sid(GnssSignal::new(sat, code).unwrap());
+        nm.set_pseudorange(pseudorange);
+        if let Some(phase) = phase {
+            nm.set_carrier_phase(phase);
+            nm.set_half_cycle_known(true);
+        }
+        nm.set_cn0(45.0);
+        nm.set_lock_time(Duration::from_secs(10));
+        nm
+    }
+
+    fn epochs() -> Vec<(GpsTime, Vec<NavigationMeasurement>)> {
+        let t0 = GpsTime::new(2200, 1000.0).unwrap();
+        (0..3)
+            .map(|i| {
+                let t = t0 + Duration::from_secs(i);
+                let mut measurements = vec![
+                    measurement(1, Code::GpsL1ca, 2.1e7 + i as f64, Some(1.1e8)),
+                    measurement(7, Code::GalE1b, 2.4e7, None),
+                ];
+                if i == 1 {
+                    measurements.push(measurement(12, Code::GpsL2cm, 2.2e7, Some(8.6e7)));
+                }
+                (t, measurements)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let epochs = epochs();
+        let batch =
+            ObservationBatch::from_epochs(epochs.iter().map(|(t, nms)| (*t, nms.as_slice())))
+                .unwrap();
+
+        assert_eq!(batch.epoch_count(), 3);
+        assert_eq!(batch.len(), 7);
+        assert_eq!(batch.epoch_range(1), Some(2..5));
+        assert_eq!(batch.epoch_range(3), None);
+        assert_eq!(batch.epoch_of(4), Some(1));
+        assert_eq!(batch.epoch_of(6), Some(2));
+        assert_eq!(batch.epoch_of(7), None);
+        assert_eq!(batch.sats(), &[1, 7, 1, 7, 12, 1, 7]);
+        assert_eq!(batch.codes()[4], Code::GpsL2cm);
+        assert!(batch.carrier_phases()[1].is_nan());
+        assert!(batch.dopplers().iter().all(|d| d.is_nan()));
+
+        let epoch = batch.epoch(2).unwrap();
+        assert_eq!(epoch.len(), 2);
+        assert_eq!(epoch.pseudoranges(), &[2.1e7 + 2.0, 2.4e7]);
+        assert_eq!(batch.epochs().len(), 3);
+        assert_eq!(batch.to_epochs(), epochs);
+    }
+
+    #[test]
+    fn epoch_lookup() {
+        let mut batch = ObservationBatch::with_capacity(3, 8);
+        for (t, nms) in epochs() {
+            batch.push_epoch(t, &nms).unwrap();
+        }
+        let t0 = batch.times()[0];
+        assert!(batch.push_epoch(t0, &[]).is_err());
+
+        let at = |secs: f64| t0 + Duration::from_secs_f64(secs);
+        assert_eq!(batch.find_epoch(at(1.2), 0.5), Some(1));
+        assert_eq!(batch.find_epoch(at(1.6), 0.5), Some(2));
+        assert_eq!(batch.find_epoch(at(2.6), 0.5), None);
+        assert_eq!(
+            batch.find_epoch(t0 - Duration::from_millis(100), 0.5),
+            Some(0)
+        );
+
+        batch.clear();
+        assert!(batch.is_empty());
+        assert!(batch.epoch(0).is_none());
+    }
+}
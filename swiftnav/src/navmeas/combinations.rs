@@ -0,0 +1,366 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Linear combinations of dual frequency measurements
+//!
+//! Measurements of the same satellite on two different carrier frequencies can
+//! be combined to cancel out or isolate certain error sources. For carrier
+//! frequencies f1 and f2 the following combinations are provided:
+//!  * Ionosphere-free - removes the first order ionospheric delay:
+//!    (f1² O1 - f2² O2) / (f1² - f2²)
+//!  * Geometry-free - removes everything but the ionospheric delay and
+//!    hardware biases: P2 - P1 for pseudoranges, L1 - L2 for carrier phases
+//!  * Wide-lane - (f1 O1 - f2 O2) / (f1 - f2), with a wavelength of
+//!    c / (f1 - f2)
+//!  * Narrow-lane - (f1 O1 + f2 O2) / (f1 + f2), with a wavelength of
+//!    c / (f1 + f2)
+//!
+//! All combined observables are given in meters. The noise of each combined
+//! observable is propagated from the noise of the input measurements, which
+//! are assumed to be uncorrelated.
+
+use super::NavigationMeasurement;
+use crate::SPEED_OF_LIGHT;
+use std::error::Error;
+use std::fmt;
+
+/// Reasons a pair of measurements can't be combined
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum CombinationError {
+    /// The two measurements are not from the same satellite
+    DifferentSatellites,
+    /// The two measurements are on the same carrier frequency
+    SameFrequency,
+}
+
+impl fmt::Display for CombinationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CombinationError::DifferentSatellites => {
+                write!(f, "Measurements are from different satellites")
+            }
+            CombinationError::SameFrequency => {
+                write!(f, "Measurements are on the same carrier frequency")
+            }
+        }
+    }
+}
+
+impl Error for CombinationError {}
+
+/// Standard deviations of the measurement noise of a single signal
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MeasurementNoise {
+    /// Pseudorange noise, in meters
+    pub pseudorange: f64,
+    /// Carrier phase noise, in meters
+    pub carrier_phase: f64,
+}
+
+impl MeasurementNoise {
+    pub fn new(pseudorange: f64, carrier_phase: f64) -> MeasurementNoise {
+        MeasurementNoise {
+            pseudorange,
+            carrier_phase,
+        }
+    }
+}
+
+impl Default for MeasurementNoise {
+    /// Typical noise levels of 0.3 meters for pseudoranges and 3 millimeters for
+    /// carrier phases
+    fn default() -> MeasurementNoise {
+        MeasurementNoise::new(0.3, 0.003)
+    }
+}
+
+/// A combined observable
+///
+/// The pseudorange or carrier phase will only be present if the corresponding
+/// measurement was valid in both of the input measurements.
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct Combination {
+    pseudorange: Option<f64>,
+    carrier_phase: Option<f64>,
+    pseudorange_sigma: f64,
+    carrier_phase_sigma: f64,
+    wavelength: Option<f64>,
+}
+
+impl Combination {
+    /// Gets the combined pseudorange, in meters
+    pub fn pseudorange(&self) -> Option<f64> {
+        self.pseudorange
+    }
+
+    /// Gets the combined carrier phase, in meters
+    pub fn carrier_phase(&self) -> Option<f64> {
+        self.carrier_phase
+    }
+
+    /// Gets the standard deviation of the combined pseudorange, in meters
+    pub fn pseudorange_sigma(&self) -> f64 {
+        self.pseudorange_sigma
+    }
+
+    /// Gets the standard deviation of the combined carrier phase, in meters
+    pub fn carrier_phase_sigma(&self) -> f64 {
+        self.carrier_phase_sigma
+    }
+
+    /// Gets the wavelength of the combined carrier phase, in meters
+    ///
+    /// Combinations such as the ionosphere-free combination don't have a
+    /// meaningful wavelength, in which case `None` is returned
+    pub fn wavelength(&self) -> Option<f64> {
+        self.wavelength
+    }
+}
+
+/// A pair of measurements of the same satellite on different frequencies
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub struct DualFrequency<'a> {
+    first: &'a NavigationMeasurement,
+    second: &'a NavigationMeasurement,
+    first_noise: MeasurementNoise,
+    second_noise: MeasurementNoise,
+    f1: f64,
+    f2: f64,
+}
+
+impl<'a> DualFrequency<'a> {
+    /// Pairs up two measurements, using the default measurement noise for both
+    pub fn new(
+        first: &'a NavigationMeasurement,
+        second: &'a NavigationMeasurement,
+    ) -> Result<DualFrequency<'a>, CombinationError> {
+        let first_sid = first.sid();
+        let second_sid = second.sid();
+        if first_sid.sat() != second_sid.sat()
+            || first_sid.to_constellation() != second_sid.to_constellation()
+        {
+            return Err(CombinationError::DifferentSatellites);
+        }
+
+        let f1 = first_sid.carrier_frequency();
+        let f2 = second_sid.carrier_frequency();
+        if (f1 - f2).abs() < 1.0 {
+            return Err(CombinationError::SameFrequency);
+        }
+
+        Ok(DualFrequency {
+            first,
+            second,
+            first_noise: MeasurementNoise::default(),
+            second_noise: MeasurementNoise::default(),
+            f1,
+            f2,
+        })
+    }
+
+    /// Sets the noise of each of the measurements
+    pub fn with_noise(
+        self,
+        first_noise: MeasurementNoise,
+        second_noise: MeasurementNoise,
+    ) -> DualFrequency<'a> {
+        DualFrequency {
+            first_noise,
+            second_noise,
+            ..self
+        }
+    }
+
+    /// Carrier phase of a measurement converted into meters
+    fn carrier_phase_m(measurement: &NavigationMeasurement, frequency: f64) -> Option<f64> {
+        measurement
+            .carrier_phase()
+            .map(|cycles| cycles * SPEED_OF_LIGHT / frequency)
+    }
+
+    /// Forms `a * first + b * second` for both the pseudoranges and the carrier
+    /// phases, with separate coefficients for each
+    fn combine(
+        &self,
+        pseudorange_coeffs: (f64, f64),
+        carrier_phase_coeffs: (f64, f64),
+        wavelength: Option<f64>,
+    ) -> Combination {
+        let (pa, pb) = pseudorange_coeffs;
+        let (la, lb) = carrier_phase_coeffs;
+
+        let pseudorange = match (self.first.pseudorange(), self.second.pseudorange()) {
+            (Some(p1), Some(p2)) => Some(pa * p1 + pb * p2),
+            _ => None,
+        };
+        let carrier_phase = match (
+            Self::carrier_phase_m(self.first, self.f1),
+            Self::carrier_phase_m(self.second, self.f2),
+        ) {
+            (Some(l1), Some(l2)) => Some(la * l1 + lb * l2),
+            _ => None,
+        };
+
+        Combination {
+            pseudorange,
+            carrier_phase,
+            pseudorange_sigma: (pa * pa * self.first_noise.pseudorange.powi(2)
+                + pb * pb * self.second_noise.pseudorange.powi(2))
+            .sqrt(),
+            carrier_phase_sigma: (la * la * self.first_noise.carrier_phase.powi(2)
+                + lb * lb * self.second_noise.carrier_phase.powi(2))
+            .sqrt(),
+            wavelength,
+        }
+    }
+
+    /// Forms the ionosphere-free combination
+    pub fn ionosphere_free(&self) -> Combination {
+        let f1_2 = self.f1 * self.f1;
+        let f2_2 = self.f2 * self.f2;
+        let coeffs = (f1_2 / (f1_2 - f2_2), -f2_2 / (f1_2 - f2_2));
+        self.combine(coeffs, coeffs, None)
+    }
+
+    /// Forms the geometry-free combination
+    ///
+    /// The combination is ordered such that the ionospheric delay is positive
+    /// for both the pseudorange and carrier phase observables when the first
+    /// measurement is on the higher frequency.
+    pub fn geometry_free(&self) -> Combination {
+        self.combine((-1.0, 1.0), (1.0, -1.0), None)
+    }
+
+    /// Forms the wide-lane combination
+    pub fn wide_lane(&self) -> Combination {
+        let df = self.f1 - self.f2;
+        let coeffs = (self.f1 / df, -self.f2 / df);
+        self.combine(coeffs, coeffs, Some(SPEED_OF_LIGHT / df.abs()))
+    }
+
+    /// Forms the narrow-lane combination
+    pub fn narrow_lane(&self) -> Combination {
+        let sf = self.f1 + self.f2;
+        let coeffs = (self.f1 / sf, self.f2 / sf);
+        self.combine(coeffs, coeffs, Some(SPEED_OF_LIGHT / sf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::{Code, GnssSignal};
+    use float_eq::assert_float_eq;
+
+    const F1: f64 = 1575.42e6;
+    const F2: f64 = 1227.60e6;
+
+    fn make_nm(
+        code: Code,
+        sat: u16,
+        pseudorange: f64,
+        carrier_phase_m: f64,
+    ) -> NavigationMeasurement {
+        let mut nm = NavigationMeasurement::new();
+        let sid = GnssSignal::new(sat, code).unwrap();
+        nm.set_sid(sid);
+        nm.set_pseudorange(pseudorange);
+        nm.set_carrier_phase(carrier_phase_m * sid.carrier_frequency() / SPEED_OF_LIGHT);
+        nm
+    }
+
+    #[test]
+    fn invalid_pairs() {
+        let l1 = make_nm(Code::GpsL1ca, 1, 2e7, 2e7);
+        let l1_other = make_nm(Code::GpsL1ca, 2, 2e7, 2e7);
+        let l2 = make_nm(Code::GpsL2cm, 2, 2e7, 2e7);
+
+        assert_eq!(
+            DualFrequency::new(&l1, &l1_other).unwrap_err(),
+            CombinationError::DifferentSatellites
+        );
+        assert_eq!(
+            DualFrequency::new(&l1, &l2).unwrap_err(),
+            CombinationError::DifferentSatellites
+        );
+        assert_eq!(
+            DualFrequency::new(&l1_other, &l1_other).unwrap_err(),
+            CombinationError::SameFrequency
+        );
+    }
+
+    #[test]
+    fn ionosphere_free_removes_iono() {
+        let range = 2.2e7;
+        let iono_l1 = 5.0;
+        let iono_l2 = iono_l1 * (F1 * F1) / (F2 * F2);
+        let l1 = make_nm(Code::GpsL1ca, 1, range + iono_l1, range - iono_l1);
+        let l2 = make_nm(Code::GpsL2cm, 1, range + iono_l2, range - iono_l2);
+
+        let pair = DualFrequency::new(&l1, &l2).unwrap();
+        let iflc = pair.ionosphere_free();
+        assert_float_eq!(iflc.pseudorange().unwrap(), range, abs <= 1e-6);
+        assert_float_eq!(iflc.carrier_phase().unwrap(), range, abs <= 1e-6);
+        assert!(iflc.wavelength().is_none());
+
+        let gf = pair.geometry_free();
+        assert_float_eq!(gf.pseudorange().unwrap(), iono_l2 - iono_l1, abs <= 1e-6);
+        assert_float_eq!(gf.carrier_phase().unwrap(), iono_l2 - iono_l1, abs <= 1e-6);
+    }
+
+    #[test]
+    fn lane_combinations() {
+        let range = 2.2e7;
+        let l1 = make_nm(Code::GpsL1ca, 1, range, range);
+        let l2 = make_nm(Code::GpsL2cm, 1, range, range);
+        let pair = DualFrequency::new(&l1, &l2).unwrap();
+
+        let wl = pair.wide_lane();
+        assert_float_eq!(wl.pseudorange().unwrap(), range, abs <= 1e-6);
+        assert_float_eq!(wl.carrier_phase().unwrap(), range, abs <= 1e-6);
+        assert_float_eq!(wl.wavelength().unwrap(), 0.8619, abs <= 1e-4);
+
+        let nl = pair.narrow_lane();
+        assert_float_eq!(nl.pseudorange().unwrap(), range, abs <= 1e-6);
+        assert_float_eq!(nl.carrier_phase().unwrap(), range, abs <= 1e-6);
+        assert_float_eq!(nl.wavelength().unwrap(), 0.1070, abs <= 1e-4);
+    }
+
+    #[test]
+    fn noise_propagation() {
+        let l1 = make_nm(Code::GpsL1ca, 1, 2e7, 2e7);
+        let l2 = make_nm(Code::GpsL2cm, 1, 2e7, 2e7);
+        let noise = MeasurementNoise::new(1.0, 0.01);
+        let pair = DualFrequency::new(&l1, &l2)
+            .unwrap()
+            .with_noise(noise, noise);
+
+        let iflc = pair.ionosphere_free();
+        let a = F1 * F1 / (F1 * F1 - F2 * F2);
+        let b = F2 * F2 / (F1 * F1 - F2 * F2);
+        let scale = (a * a + b * b).sqrt();
+        assert_float_eq!(iflc.pseudorange_sigma(), scale, abs <= 1e-9);
+        assert_float_eq!(iflc.carrier_phase_sigma(), 0.01 * scale, abs <= 1e-9);
+
+        let gf = pair.geometry_free();
+        assert_float_eq!(gf.pseudorange_sigma(), 2f64.sqrt(), abs <= 1e-9);
+    }
+
+    #[test]
+    fn missing_observables() {
+        let mut l1 = make_nm(Code::GpsL1ca, 1, 2e7, 2e7);
+        let l2 = make_nm(Code::GpsL2cm, 1, 2e7, 2e7);
+        l1.invalidate_pseudorange();
+
+        let iflc = DualFrequency::new(&l1, &l2).unwrap().ionosphere_free();
+        assert!(iflc.pseudorange().is_none());
+        assert!(iflc.carrier_phase().is_some());
+    }
+}
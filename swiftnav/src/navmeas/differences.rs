@@ -0,0 +1,366 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Single and double differenced observations
+//!
+//! Differencing measurements of the same signal taken by two nearby receivers,
+//! a rover and a base, removes the errors common to both receivers such as the
+//! satellite clock error and most of the atmospheric delays. These are called
+//! single differences. Differencing two single differences of the same code
+//! then removes the receiver clock errors, leaving only the geometry and the
+//! integer ambiguities. These are called double differences, and they are the
+//! basic observable of RTK processing.
+//!
+//! Double differences are formed against a reference satellite, one is chosen
+//! for each [`Code`] present in both receivers' measurements. Signals which
+//! were only observed by one of the two receivers are ignored.
+
+use super::NavigationMeasurement;
+use crate::signal::{Code, GnssSignal};
+use std::collections::BTreeMap;
+
+/// The difference between a rover and a base measurement of the same signal
+///
+/// Each observable is only present if it was valid in both measurements.
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct SingleDifference {
+    sid: GnssSignal,
+    pseudorange: Option<f64>,
+    carrier_phase: Option<f64>,
+    doppler: Option<f64>,
+    rover_cn0: Option<f64>,
+}
+
+impl SingleDifference {
+    /// Differences two measurements of the same signal, `rover - base`
+    ///
+    /// Only carrier phases which pass the
+    /// [carrier phase checks](NavigationMeasurement::checked_carrier_phase) are
+    /// used. Returns `None` if the two measurements are of different signals.
+    pub fn new(
+        rover: &NavigationMeasurement,
+        base: &NavigationMeasurement,
+    ) -> Option<SingleDifference> {
+        let sid = rover.sid();
+        if sid != base.sid() {
+            return None;
+        }
+
+        Some(SingleDifference {
+            sid,
+            pseudorange: difference(rover.pseudorange(), base.pseudorange()),
+            carrier_phase: difference(
+                rover.checked_carrier_phase().ok(),
+                base.checked_carrier_phase().ok(),
+            ),
+            doppler: difference(rover.measured_doppler(), base.measured_doppler()),
+            rover_cn0: rover.cn0(),
+        })
+    }
+
+    pub fn sid(&self) -> GnssSignal {
+        self.sid
+    }
+
+    /// Gets the differenced pseudorange, in meters
+    pub fn pseudorange(&self) -> Option<f64> {
+        self.pseudorange
+    }
+
+    /// Gets the differenced carrier phase, in cycles
+    pub fn carrier_phase(&self) -> Option<f64> {
+        self.carrier_phase
+    }
+
+    /// Gets the differenced doppler, in Hertz
+    pub fn doppler(&self) -> Option<f64> {
+        self.doppler
+    }
+}
+
+/// The difference between the single differences of a signal and of the
+/// reference signal with the same code
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct DoubleDifference {
+    sid: GnssSignal,
+    reference: GnssSignal,
+    pseudorange: Option<f64>,
+    carrier_phase: Option<f64>,
+    doppler: Option<f64>,
+}
+
+impl DoubleDifference {
+    /// Differences two single differences, `single - reference`
+    ///
+    /// Returns `None` if the two single differences are not of the same code
+    pub fn new(
+        single: &SingleDifference,
+        reference: &SingleDifference,
+    ) -> Option<DoubleDifference> {
+        if single.sid.code() != reference.sid.code() {
+            return None;
+        }
+
+        Some(DoubleDifference {
+            sid: single.sid,
+            reference: reference.sid,
+            pseudorange: difference(single.pseudorange, reference.pseudorange),
+            carrier_phase: difference(single.carrier_phase, reference.carrier_phase),
+            doppler: difference(single.doppler, reference.doppler),
+        })
+    }
+
+    pub fn sid(&self) -> GnssSignal {
+        self.sid
+    }
+
+    /// Gets the signal of the reference satellite
+    pub fn reference(&self) -> GnssSignal {
+        self.reference
+    }
+
+    /// Gets the double differenced pseudorange, in meters
+    pub fn pseudorange(&self) -> Option<f64> {
+        self.pseudorange
+    }
+
+    /// Gets the double differenced carrier phase, in cycles
+    pub fn carrier_phase(&self) -> Option<f64> {
+        self.carrier_phase
+    }
+
+    /// Gets the double differenced doppler, in Hertz
+    pub fn doppler(&self) -> Option<f64> {
+        self.doppler
+    }
+}
+
+/// Strategies for choosing the reference satellite of each code
+#[derive(Debug, Default, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReferenceSelection {
+    /// Use the signal with the highest CN0 as measured by the rover
+    #[default]
+    HighestCn0,
+    /// Use the given satellite number when it is available, otherwise fall back
+    /// to [`ReferenceSelection::HighestCn0`]
+    PreferSatellite(u16),
+}
+
+/// All of the single and double differences formed from a single epoch of
+/// rover and base measurements
+#[derive(Debug, Clone, PartialEq)]
+pub struct DifferencedEpoch {
+    single_differences: BTreeMap<GnssSignal, SingleDifference>,
+    references: BTreeMap<Code, GnssSignal>,
+    double_differences: BTreeMap<GnssSignal, DoubleDifference>,
+}
+
+impl DifferencedEpoch {
+    /// Forms the single and double differences between the rover and base
+    /// measurements
+    ///
+    /// If a signal appears more than once in either set of measurements only
+    /// the first occurrence is used. Only signals with a valid differenced
+    /// pseudorange are considered as reference candidates.
+    pub fn new(
+        rover: &[NavigationMeasurement],
+        base: &[NavigationMeasurement],
+        selection: ReferenceSelection,
+    ) -> DifferencedEpoch {
+        let mut base_by_sid = BTreeMap::new();
+        for nm in base {
+            base_by_sid.entry(nm.sid()).or_insert(nm);
+        }
+
+        let mut single_differences = BTreeMap::new();
+        for nm in rover {
+            if let Some(base_nm) = base_by_sid.get(&nm.sid()) {
+                if let Some(sd) = SingleDifference::new(nm, base_nm) {
+                    single_differences.entry(sd.sid).or_insert(sd);
+                }
+            }
+        }
+
+        let references = select_references(&single_differences, selection);
+
+        let double_differences = single_differences
+            .values()
+            .filter_map(|sd| {
+                let reference_sid = references.get(&sd.sid.code())?;
+                if *reference_sid == sd.sid {
+                    return None;
+                }
+                DoubleDifference::new(sd, &single_differences[reference_sid])
+            })
+            .map(|dd| (dd.sid, dd))
+            .collect();
+
+        DifferencedEpoch {
+            single_differences,
+            references,
+            double_differences,
+        }
+    }
+
+    /// Gets the single differences of all signals observed by both receivers
+    pub fn single_differences(&self) -> &BTreeMap<GnssSignal, SingleDifference> {
+        &self.single_differences
+    }
+
+    /// Gets the reference signal chosen for each code
+    pub fn references(&self) -> &BTreeMap<Code, GnssSignal> {
+        &self.references
+    }
+
+    /// Gets the double differences, keyed by the non-reference signal
+    pub fn double_differences(&self) -> &BTreeMap<GnssSignal, DoubleDifference> {
+        &self.double_differences
+    }
+
+    /// Gets the reference signal for a particular code
+    pub fn reference(&self, code: Code) -> Option<GnssSignal> {
+        self.references.get(&code).copied()
+    }
+}
+
+fn difference(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a - b),
+        _ => None,
+    }
+}
+
+fn select_references(
+    single_differences: &BTreeMap<GnssSignal, SingleDifference>,
+    selection: ReferenceSelection,
+) -> BTreeMap<Code, GnssSignal> {
+    let mut references: BTreeMap<Code, &SingleDifference> = BTreeMap::new();
+
+    for sd in single_differences
+        .values()
+        .filter(|sd| sd.pseudorange.is_some())
+    {
+        let code = sd.sid.code();
+        let replace = match references.get(&code) {
+            None => true,
+            Some(current) => match selection {
+                ReferenceSelection::PreferSatellite(sat) if current.sid.sat() == sat => false,
+                ReferenceSelection::PreferSatellite(sat) if sd.sid.sat() == sat => true,
+                _ => {
+                    sd.rover_cn0.unwrap_or(f64::NEG_INFINITY)
+                        > current.rover_cn0.unwrap_or(f64::NEG_INFINITY)
+                }
+            },
+        };
+
+        if replace {
+            references.insert(code, sd);
+        }
+    }
+
+    references
+        .into_iter()
+        .map(|(code, sd)| (code, sd.sid))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+    use std::time::Duration;
+
+    fn make_nm(
+        sat: u16,
+        code: Code,
+        pseudorange: f64,
+        carrier_phase: f64,
+        cn0: f64,
+    ) -> NavigationMeasurement {
+        let mut nm = NavigationMeasurement::new();
+        nm.set_sid(GnssSignal::new(sat, code).unwrap());
+        nm.set_pseudorange(pseudorange);
+        nm.set_carrier_phase(carrier_phase);
+        nm.set_half_cycle_known(true);
+        nm.set_lock_time(Duration::from_secs(10));
+        nm.set_cn0(cn0);
+        nm
+    }
+
+    #[test]
+    fn single_difference() {
+        let rover = make_nm(1, Code::GpsL1ca, 100.0, 500.0, 40.0);
+        let mut base = make_nm(1, Code::GpsL1ca, 90.0, 450.0, 45.0);
+        base.set_half_cycle_known(false);
+
+        let sd = SingleDifference::new(&rover, &base).unwrap();
+        assert_float_eq!(sd.pseudorange().unwrap(), 10.0, abs <= 1e-9);
+        assert!(sd.carrier_phase().is_none());
+        assert!(sd.doppler().is_none());
+
+        let other = make_nm(2, Code::GpsL1ca, 90.0, 450.0, 45.0);
+        assert!(SingleDifference::new(&rover, &other).is_none());
+    }
+
+    #[test]
+    fn double_differences() {
+        let rover = [
+            make_nm(1, Code::GpsL1ca, 100.0, 500.0, 40.0),
+            make_nm(2, Code::GpsL1ca, 200.0, 800.0, 48.0),
+            make_nm(3, Code::GpsL1ca, 300.0, 900.0, 35.0),
+            make_nm(2, Code::GpsL2cm, 210.0, 700.0, 30.0),
+            make_nm(5, Code::GpsL1ca, 300.0, 900.0, 50.0),
+        ];
+        let base = [
+            make_nm(3, Code::GpsL1ca, 280.0, 870.0, 35.0),
+            make_nm(2, Code::GpsL1ca, 190.0, 790.0, 48.0),
+            make_nm(1, Code::GpsL1ca, 95.0, 490.0, 40.0),
+            make_nm(2, Code::GpsL2cm, 200.0, 690.0, 30.0),
+        ];
+
+        let epoch = DifferencedEpoch::new(&rover, &base, ReferenceSelection::HighestCn0);
+        assert_eq!(epoch.single_differences().len(), 4);
+
+        let reference = GnssSignal::new(2, Code::GpsL1ca).unwrap();
+        assert_eq!(epoch.reference(Code::GpsL1ca), Some(reference));
+        assert_eq!(
+            epoch.reference(Code::GpsL2cm),
+            Some(GnssSignal::new(2, Code::GpsL2cm).unwrap())
+        );
+
+        // Only the L1CA signals other than the reference are double differenced
+        assert_eq!(epoch.double_differences().len(), 2);
+        let dd = &epoch.double_differences()[&GnssSignal::new(3, Code::GpsL1ca).unwrap()];
+        assert_eq!(dd.reference(), reference);
+        assert_float_eq!(dd.pseudorange().unwrap(), 20.0 - 10.0, abs <= 1e-9);
+        assert_float_eq!(dd.carrier_phase().unwrap(), 30.0 - 10.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn preferred_reference() {
+        let rover = [
+            make_nm(1, Code::GpsL1ca, 100.0, 500.0, 40.0),
+            make_nm(2, Code::GpsL1ca, 200.0, 800.0, 48.0),
+        ];
+        let base = rover.clone();
+
+        let epoch = DifferencedEpoch::new(&rover, &base, ReferenceSelection::PreferSatellite(1));
+        assert_eq!(
+            epoch.reference(Code::GpsL1ca),
+            Some(GnssSignal::new(1, Code::GpsL1ca).unwrap())
+        );
+
+        let epoch = DifferencedEpoch::new(&rover, &base, ReferenceSelection::PreferSatellite(7));
+        assert_eq!(
+            epoch.reference(Code::GpsL1ca),
+            Some(GnssSignal::new(2, Code::GpsL1ca).unwrap())
+        );
+    }
+}
@@ -0,0 +1,343 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Merging of redundant measurement streams
+//!
+//! High availability setups often receive the measurements of the same
+//! receiver over several connections, each of which can drop or delay epochs
+//! independently. The [`StreamMerger`] combines these streams back into a
+//! single one, grouping the measurements by epoch and keeping a single record
+//! of each signal.
+//!
+//! When a signal is present in more than one stream the most complete record
+//! is kept, i.e. the one with the most valid observables. Records which are
+//! equally complete are ordered by the longest lock time, and then by arrival
+//! order.
+
+use super::NavigationMeasurement;
+use crate::signal::GnssSignal;
+use crate::time::GpsTime;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Settings for merging measurement streams
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MergeSettings {
+    epoch_tolerance: Duration,
+    max_latency: Duration,
+}
+
+impl MergeSettings {
+    /// Makes the default settings
+    ///
+    /// Note: The default settings consist of
+    ///  * Epochs less than 1 ms apart are considered the same epoch
+    ///  * Epochs are output 500 ms after a newer epoch has been received
+    pub fn new() -> MergeSettings {
+        MergeSettings {
+            epoch_tolerance: Duration::from_millis(1),
+            max_latency: Duration::from_millis(500),
+        }
+    }
+
+    /// Sets the largest time difference between two epochs which are
+    /// considered the same epoch
+    pub fn set_epoch_tolerance(self, epoch_tolerance: Duration) -> MergeSettings {
+        MergeSettings {
+            epoch_tolerance,
+            ..self
+        }
+    }
+
+    /// Sets how long an epoch waits for measurements from the other streams,
+    /// measured against the newest epoch received
+    pub fn set_max_latency(self, max_latency: Duration) -> MergeSettings {
+        MergeSettings {
+            max_latency,
+            ..self
+        }
+    }
+
+    pub fn epoch_tolerance(&self) -> Duration {
+        self.epoch_tolerance
+    }
+
+    pub fn max_latency(&self) -> Duration {
+        self.max_latency
+    }
+}
+
+impl Default for MergeSettings {
+    fn default() -> MergeSettings {
+        MergeSettings::new()
+    }
+}
+
+/// The measurements of a single epoch after merging
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergedEpoch {
+    time: GpsTime,
+    measurements: Vec<NavigationMeasurement>,
+    sources: usize,
+}
+
+impl MergedEpoch {
+    /// Gets the time of the epoch, as first received
+    pub fn time(&self) -> GpsTime {
+        self.time
+    }
+
+    /// Gets the merged measurements, one per signal and ordered by signal
+    pub fn measurements(&self) -> &[NavigationMeasurement] {
+        &self.measurements
+    }
+
+    /// Gets the number of times the epoch was received
+    pub fn sources(&self) -> usize {
+        self.sources
+    }
+}
+
+struct PendingEpoch {
+    time: GpsTime,
+    measurements: BTreeMap<GnssSignal, NavigationMeasurement>,
+    sources: usize,
+}
+
+impl PendingEpoch {
+    fn into_merged(self) -> MergedEpoch {
+        MergedEpoch {
+            time: self.time,
+            measurements: self.measurements.into_values().collect(),
+            sources: self.sources,
+        }
+    }
+}
+
+/// Merges redundant measurement streams by epoch and signal
+///
+/// Epochs are held back until either a newer epoch is `max_latency` ahead of
+/// them, or [`StreamMerger::flush()`] is called, giving the slower streams a
+/// chance to contribute. Epochs which arrive after their time has already been
+/// output are dropped.
+pub struct StreamMerger {
+    settings: MergeSettings,
+    pending: Vec<PendingEpoch>,
+    last_output: Option<GpsTime>,
+    dropped: usize,
+}
+
+impl StreamMerger {
+    pub fn new(settings: MergeSettings) -> StreamMerger {
+        StreamMerger {
+            settings,
+            pending: Vec::new(),
+            last_output: None,
+            dropped: 0,
+        }
+    }
+
+    pub fn settings(&self) -> &MergeSettings {
+        &self.settings
+    }
+
+    /// Gets the number of epochs dropped because they arrived too late
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Adds the measurements of an epoch from any of the streams
+    ///
+    /// Returns the epochs which are ready to be output, oldest first
+    pub fn push(
+        &mut self,
+        time: GpsTime,
+        measurements: &[NavigationMeasurement],
+    ) -> Vec<MergedEpoch> {
+        let tolerance = self.settings.epoch_tolerance.as_secs_f64();
+
+        let too_late = match self.last_output {
+            Some(last) => time.diff(&last) <= tolerance,
+            None => false,
+        };
+        if too_late {
+            self.dropped += 1;
+            return Vec::new();
+        }
+
+        let index = match self
+            .pending
+            .iter()
+            .position(|epoch| time.diff(&epoch.time).abs() <= tolerance)
+        {
+            Some(index) => index,
+            None => {
+                let index = self
+                    .pending
+                    .iter()
+                    .position(|epoch| epoch.time.diff(&time) > 0.0)
+                    .unwrap_or(self.pending.len());
+                self.pending.insert(
+                    index,
+                    PendingEpoch {
+                        time,
+                        measurements: BTreeMap::new(),
+                        sources: 0,
+                    },
+                );
+                index
+            }
+        };
+
+        let epoch = &mut self.pending[index];
+        epoch.sources += 1;
+        for nm in measurements {
+            let replace = match epoch.measurements.get(&nm.sid()) {
+                Some(current) => is_more_complete(nm, current),
+                None => true,
+            };
+            if replace {
+                epoch.measurements.insert(nm.sid(), nm.clone());
+            }
+        }
+
+        let newest = self.pending[self.pending.len() - 1].time;
+        let max_latency = self.settings.max_latency.as_secs_f64();
+        let ready = self
+            .pending
+            .iter()
+            .take_while(|epoch| newest.diff(&epoch.time) >= max_latency)
+            .count();
+        self.release(ready)
+    }
+
+    /// Outputs all of the pending epochs, oldest first
+    pub fn flush(&mut self) -> Vec<MergedEpoch> {
+        self.release(self.pending.len())
+    }
+
+    fn release(&mut self, count: usize) -> Vec<MergedEpoch> {
+        let released: Vec<MergedEpoch> = self
+            .pending
+            .drain(..count)
+            .map(PendingEpoch::into_merged)
+            .collect();
+        if let Some(last) = released.last() {
+            self.last_output = Some(last.time);
+        }
+        released
+    }
+}
+
+/// Merges several records of the same epoch into one record per signal
+///
+/// See the [module documentation](self) for how duplicate records are chosen
+/// between. The result is ordered by signal.
+pub fn merge_measurements<'a, I>(records: I) -> Vec<NavigationMeasurement>
+where
+    I: IntoIterator<Item = &'a [NavigationMeasurement]>,
+{
+    let mut merged: BTreeMap<GnssSignal, &NavigationMeasurement> = BTreeMap::new();
+    for nm in records.into_iter().flatten() {
+        let replace = match merged.get(&nm.sid()) {
+            Some(current) => is_more_complete(nm, current),
+            None => true,
+        };
+        if replace {
+            merged.insert(nm.sid(), nm);
+        }
+    }
+    merged.into_values().cloned().collect()
+}
+
+/// Number of valid observables in a measurement
+fn completeness(nm: &NavigationMeasurement) -> usize {
+    [
+        nm.pseudorange().is_some(),
+        nm.carrier_phase().is_some(),
+        nm.carrier_phase().is_some() && nm.half_cycle_known(),
+        nm.measured_doppler().is_some(),
+        nm.cn0().is_some(),
+    ]
+    .iter()
+    .filter(|valid| **valid)
+    .count()
+}
+
+fn is_more_complete(candidate: &NavigationMeasurement, current: &NavigationMeasurement) -> bool {
+    (completeness(candidate), candidate.lock_time()) > (completeness(current), current.lock_time())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::Code;
+
+    fn measurement(sat: u16, phase: bool, lock_time: u64) -> NavigationMeasurement {
+        let mut nm = NavigationMeasurement::new();
+        nm.set_sid(GnssSignal::new(sat, Code::GpsL1ca).unwrap());
+        nm.set_pseudorange(20_000_000.0 + f64::from(sat));
+        if phase {
+            nm.set_carrier_phase(100.0);
+        }
+        nm.set_lock_time(Duration::from_secs(lock_time));
+        nm
+    }
+
+    #[test]
+    fn merge_prefers_complete_records() {
+        let first = [measurement(1, false, 10), measurement(2, true, 10)];
+        let second = [measurement(1, true, 5), measurement(2, true, 20)];
+        let third = [measurement(3, false, 1)];
+
+        let merged = merge_measurements(vec![&first[..], &second[..], &third[..]]);
+        assert_eq!(merged.len(), 3);
+        assert!(merged[0].carrier_phase().is_some());
+        assert_eq!(merged[1].lock_time(), Duration::from_secs(20));
+        assert_eq!(merged[2].sid().sat(), 3);
+    }
+
+    #[test]
+    fn stream_merging() {
+        let mut merger = StreamMerger::new(MergeSettings::new());
+        let t0 = GpsTime::new(2100, 1000.0).unwrap();
+        let t1 = t0 + Duration::from_secs(1);
+        let t2 = t0 + Duration::from_secs(2);
+
+        assert!(merger.push(t0, &[measurement(1, false, 1)]).is_empty());
+        // The second stream is slightly offset and has an extra signal
+        let t0_offset = t0 + Duration::from_micros(100);
+        assert!(merger
+            .push(
+                t0_offset,
+                &[measurement(1, true, 1), measurement(2, true, 1)]
+            )
+            .is_empty());
+
+        let ready = merger.push(t1, &[measurement(1, true, 2)]);
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].time(), t0);
+        assert_eq!(ready[0].sources(), 2);
+        assert_eq!(ready[0].measurements().len(), 2);
+        assert!(ready[0].measurements()[0].carrier_phase().is_some());
+
+        // A late copy of an epoch which was already output is dropped
+        assert!(merger.push(t0, &[measurement(3, true, 1)]).is_empty());
+        assert_eq!(merger.dropped(), 1);
+
+        // Each epoch waits for the latency before being output
+        let ready = merger.push(t2, &[measurement(1, true, 3)]);
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].time(), t1);
+        let flushed = merger.flush();
+        assert_eq!(flushed.len(), 1);
+        assert_eq!(flushed[0].time(), t2);
+    }
+}
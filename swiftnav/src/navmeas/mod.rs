@@ -18,14 +18,51 @@
 //! and the [PVT solver function](crate::solver::calc_pvt) to get a position,
 //! velocity and time estimate.
 
-use crate::{ephemeris::SatelliteState, signal::GnssSignal};
+pub mod batch;
+pub mod combinations;
+pub mod differences;
+pub mod merge;
+pub mod selection;
+pub mod smoothing;
+pub mod synchronize;
+
+use crate::{coords::ECEF, ephemeris::SatelliteState, signal::GnssSignal};
+use std::error::Error;
+use std::fmt;
 use std::time::Duration;
 
 const NAV_MEAS_FLAG_CODE_VALID: u16 = 1 << 0;
+const NAV_MEAS_FLAG_PHASE_VALID: u16 = 1 << 1;
 const NAV_MEAS_FLAG_MEAS_DOPPLER_VALID: u16 = 1 << 2;
+const NAV_MEAS_FLAG_HALF_CYCLE_KNOWN: u16 = 1 << 4;
 const NAV_MEAS_FLAG_CN0_VALID: u16 = 1 << 5;
 pub const NAV_MEAS_FLAG_RAIM_EXCLUSION: u16 = 1 << 6;
 
+/// Reasons a carrier phase measurement is unsuitable for processing
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum CarrierPhaseError {
+    /// No valid carrier phase measurement has been set
+    NotValid,
+    /// The half cycle ambiguity of the carrier phase hasn't been resolved
+    HalfCycleUnknown,
+    /// The carrier tracking loop has not been locked
+    NoLock,
+}
+
+impl fmt::Display for CarrierPhaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CarrierPhaseError::NotValid => write!(f, "No valid carrier phase measurement"),
+            CarrierPhaseError::HalfCycleUnknown => {
+                write!(f, "Half cycle ambiguity is not resolved")
+            }
+            CarrierPhaseError::NoLock => write!(f, "Carrier phase lock time is zero"),
+        }
+    }
+}
+
+impl Error for CarrierPhaseError {}
+
 /// Represents a single raw GNSS measurement
 #[derive(Debug, Clone, PartialOrd, PartialEq)]
 #[repr(transparent)]
@@ -59,6 +96,69 @@ impl NavigationMeasurement {
         self.0.flags &= !NAV_MEAS_FLAG_CODE_VALID;
     }
 
+    /// Sets the carrier phase measurement value and marks it as valid
+    ///
+    /// Units of cycles. The sign convention is the same as RINEX, the carrier
+    /// phase increases as the range to the satellite increases.
+    ///
+    /// # Panics
+    /// This function will panic if `value` is not a finite number
+    pub fn set_carrier_phase(&mut self, value: f64) {
+        assert!(value.is_finite(), "Invalid carrier phase value: {}", value);
+        self.0.raw_carrier_phase = value;
+        self.0.flags |= NAV_MEAS_FLAG_PHASE_VALID;
+    }
+
+    /// Gets the carrier phase measurement, if a valid one has been set
+    ///
+    /// Units of cycles
+    pub fn carrier_phase(&self) -> Option<f64> {
+        if self.0.flags & NAV_MEAS_FLAG_PHASE_VALID != 0 {
+            Some(self.0.raw_carrier_phase)
+        } else {
+            None
+        }
+    }
+
+    /// Marks the carrier phase measurement as invalid
+    ///
+    /// This also clears the half cycle ambiguity flag
+    pub fn invalidate_carrier_phase(&mut self) {
+        self.0.flags &= !(NAV_MEAS_FLAG_PHASE_VALID | NAV_MEAS_FLAG_HALF_CYCLE_KNOWN);
+    }
+
+    /// Sets whether the half cycle ambiguity of the carrier phase has been
+    /// resolved
+    pub fn set_half_cycle_known(&mut self, known: bool) {
+        if known {
+            self.0.flags |= NAV_MEAS_FLAG_HALF_CYCLE_KNOWN;
+        } else {
+            self.0.flags &= !NAV_MEAS_FLAG_HALF_CYCLE_KNOWN;
+        }
+    }
+
+    /// Checks to see if the half cycle ambiguity of the carrier phase has been
+    /// resolved
+    pub fn half_cycle_known(&self) -> bool {
+        self.0.flags & NAV_MEAS_FLAG_HALF_CYCLE_KNOWN != 0
+    }
+
+    /// Gets the carrier phase measurement, after making sure it is suitable for
+    /// precise processing
+    ///
+    /// The carrier phase must be valid, the half cycle ambiguity resolved and
+    /// the tracking loop must have been locked for a non-zero amount of time.
+    pub fn checked_carrier_phase(&self) -> Result<f64, CarrierPhaseError> {
+        let value = self.carrier_phase().ok_or(CarrierPhaseError::NotValid)?;
+        if !self.half_cycle_known() {
+            return Err(CarrierPhaseError::HalfCycleUnknown);
+        }
+        if self.0.lock_time <= 0.0 {
+            return Err(CarrierPhaseError::NoLock);
+        }
+        Ok(value)
+    }
+
     /// Sets the measured doppler and marks it as valid
     ///
     /// Units of Hertz
@@ -92,6 +192,36 @@ impl NavigationMeasurement {
         self.0.sat_clock_err_rate = sat_state.clock_rate_err;
     }
 
+    /// Gets the position of the satellite, as set by
+    /// [`NavigationMeasurement::set_satellite_state()`]
+    pub fn satellite_position(&self) -> ECEF {
+        ECEF::from_array(&self.0.sat_pos)
+    }
+
+    /// Gets the velocity of the satellite, as set by
+    /// [`NavigationMeasurement::set_satellite_state()`]
+    pub fn satellite_velocity(&self) -> ECEF {
+        ECEF::from_array(&self.0.sat_vel)
+    }
+
+    /// Gets the acceleration of the satellite, as set by
+    /// [`NavigationMeasurement::set_satellite_state()`]
+    pub fn satellite_acceleration(&self) -> ECEF {
+        ECEF::from_array(&self.0.sat_acc)
+    }
+
+    /// Gets the clock error of the satellite in seconds, as set by
+    /// [`NavigationMeasurement::set_satellite_state()`]
+    pub fn satellite_clock_error(&self) -> f64 {
+        self.0.sat_clock_err
+    }
+
+    /// Gets the clock error rate of the satellite in seconds per second, as set
+    /// by [`NavigationMeasurement::set_satellite_state()`]
+    pub fn satellite_clock_error_rate(&self) -> f64 {
+        self.0.sat_clock_err_rate
+    }
+
     /// Sets the signal CN0 measurement and marks it as valid
     ///
     /// Units of dB-Hz
@@ -341,4 +471,35 @@ mod tests {
             value_to_encode
         );
     }
+
+    #[test]
+    fn carrier_phase() {
+        let mut nm = NavigationMeasurement::new();
+        assert_eq!(nm.carrier_phase(), None);
+        assert_eq!(nm.checked_carrier_phase(), Err(CarrierPhaseError::NotValid));
+
+        nm.set_carrier_phase(1.2345e8);
+        assert_eq!(nm.carrier_phase(), Some(1.2345e8));
+        assert_eq!(
+            nm.checked_carrier_phase(),
+            Err(CarrierPhaseError::HalfCycleUnknown)
+        );
+
+        nm.set_half_cycle_known(true);
+        assert_eq!(nm.checked_carrier_phase(), Err(CarrierPhaseError::NoLock));
+
+        nm.set_lock_time(Duration::from_secs(5));
+        assert_eq!(nm.checked_carrier_phase(), Ok(1.2345e8));
+
+        nm.invalidate_carrier_phase();
+        assert_eq!(nm.carrier_phase(), None);
+        assert!(!nm.half_cycle_known());
+    }
+
+    #[test]
+    #[should_panic]
+    fn carrier_phase_not_finite() {
+        let mut nm = NavigationMeasurement::new();
+        nm.set_carrier_phase(f64::NAN);
+    }
 }
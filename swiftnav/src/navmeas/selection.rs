@@ -0,0 +1,317 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Selection of a single signal per frequency band
+//!
+//! Receivers often track a satellite on several codes of the same band, e.g.
+//! GPS L2CM, L2CL and L2P(Y). These measurements share the same carrier and
+//! most of their errors, so passing all of them to a solver counts the same
+//! information several times and makes the solution overconfident. The
+//! [`SignalSelector`] reduces a set of measurements to a single measurement
+//! per satellite and band, by either picking one of them or merging them
+//! together according to a [`SelectionPolicy`].
+//!
+//! Bands are identified by the carrier frequency of the signals, so for
+//! example Galileo E5a, E5b and E5 AltBOC are kept as separate bands.
+
+use super::NavigationMeasurement;
+use crate::signal::{Code, Constellation};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+/// How a single measurement is chosen from the measurements of a band
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SelectionPolicy {
+    /// Keep the measurement with the highest C/N0
+    Strongest,
+    /// Keep the measurement with the longest lock time
+    LongestLock,
+    /// Keep the measurement whose code comes first in the preferred codes,
+    /// codes which aren't listed come after all of the listed ones
+    Preferred,
+    /// Keep the strongest measurement, with its pseudorange and doppler
+    /// replaced by the C/N0 weighted average of all of the measurements
+    ///
+    /// The carrier phase isn't averaged since the codes of a band can have
+    /// different phase offsets.
+    Merge,
+}
+
+/// Settings for selecting signals
+#[derive(Debug, Clone, PartialOrd, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SelectionSettings {
+    policy: SelectionPolicy,
+    preferred_codes: Vec<Code>,
+}
+
+impl SelectionSettings {
+    /// Makes the default settings
+    ///
+    /// Note: The default settings consist of
+    ///  * The strongest measurement of each band is kept
+    ///  * No preferred codes
+    pub fn new() -> SelectionSettings {
+        SelectionSettings {
+            policy: SelectionPolicy::Strongest,
+            preferred_codes: Vec::new(),
+        }
+    }
+
+    /// Sets how the measurement of each band is chosen
+    pub fn set_policy(self, policy: SelectionPolicy) -> SelectionSettings {
+        SelectionSettings { policy, ..self }
+    }
+
+    /// Sets the preferred codes, most preferred first
+    ///
+    /// The preferred codes are used by [`SelectionPolicy::Preferred`], and to
+    /// break ties between otherwise equal measurements with the other
+    /// policies.
+    pub fn set_preferred_codes(self, preferred_codes: Vec<Code>) -> SelectionSettings {
+        SelectionSettings {
+            preferred_codes,
+            ..self
+        }
+    }
+
+    pub fn policy(&self) -> SelectionPolicy {
+        self.policy
+    }
+
+    pub fn preferred_codes(&self) -> &[Code] {
+        &self.preferred_codes
+    }
+}
+
+impl Default for SelectionSettings {
+    fn default() -> SelectionSettings {
+        SelectionSettings::new()
+    }
+}
+
+/// Reduces measurements to a single measurement per satellite and band
+#[derive(Debug, Clone, PartialOrd, PartialEq, Eq, Hash)]
+pub struct SignalSelector {
+    settings: SelectionSettings,
+}
+
+impl SignalSelector {
+    pub fn new(settings: SelectionSettings) -> SignalSelector {
+        SignalSelector { settings }
+    }
+
+    pub fn settings(&self) -> &SelectionSettings {
+        &self.settings
+    }
+
+    /// Selects a single measurement for each satellite and band
+    ///
+    /// Measurements without a valid pseudorange are only kept when none of
+    /// the measurements of their band has one. The selected measurements are
+    /// returned in the same order as the input.
+    pub fn select(&self, measurements: &[NavigationMeasurement]) -> Vec<NavigationMeasurement> {
+        // Carrier frequencies are compared to the nearest kHz, the bands are
+        // separated by tens of MHz
+        let mut bands: BTreeMap<(Constellation, u16, i64), Vec<usize>> = BTreeMap::new();
+        for (i, nm) in measurements.iter().enumerate() {
+            let sid = nm.sid();
+            let band = (sid.carrier_frequency() / 1e3).round() as i64;
+            bands
+                .entry((sid.to_constellation(), sid.sat(), band))
+                .or_default()
+                .push(i);
+        }
+
+        let mut selected: Vec<(usize, NavigationMeasurement)> = bands
+            .into_values()
+            .map(|indices| {
+                let best = *indices
+                    .iter()
+                    .min_by(|a, b| self.compare(&measurements[**a], &measurements[**b]))
+                    .unwrap();
+                let nm = if self.settings.policy == SelectionPolicy::Merge {
+                    merge(
+                        &measurements[best],
+                        indices.iter().map(|i| &measurements[*i]),
+                    )
+                } else {
+                    measurements[best].clone()
+                };
+                (best, nm)
+            })
+            .collect();
+        selected.sort_by_key(|(i, _)| *i);
+        selected.into_iter().map(|(_, nm)| nm).collect()
+    }
+
+    /// Orders two measurements of the same band, the better one first
+    fn compare(&self, a: &NavigationMeasurement, b: &NavigationMeasurement) -> Ordering {
+        let cn0 = |nm: &NavigationMeasurement| nm.cn0().unwrap_or(f64::NEG_INFINITY);
+        let preference = |nm: &NavigationMeasurement| {
+            self.settings
+                .preferred_codes
+                .iter()
+                .position(|code| *code == nm.sid().code())
+                .unwrap_or(usize::MAX)
+        };
+
+        let by_pseudorange = b.pseudorange().is_some().cmp(&a.pseudorange().is_some());
+        let by_cn0 = cn0(b).partial_cmp(&cn0(a)).unwrap_or(Ordering::Equal);
+        let by_lock_time = b.lock_time().cmp(&a.lock_time());
+        let by_preference = preference(a).cmp(&preference(b));
+
+        let by_policy = match self.settings.policy {
+            SelectionPolicy::Strongest | SelectionPolicy::Merge => {
+                by_cn0.then(by_lock_time).then(by_preference)
+            }
+            SelectionPolicy::LongestLock => by_lock_time.then(by_cn0).then(by_preference),
+            SelectionPolicy::Preferred => by_preference.then(by_cn0).then(by_lock_time),
+        };
+        by_pseudorange
+            .then(by_policy)
+            .then(a.sid().code().cmp(&b.sid().code()))
+    }
+}
+
+impl Default for SignalSelector {
+    fn default() -> SignalSelector {
+        SignalSelector::new(SelectionSettings::new())
+    }
+}
+
+/// Replaces the pseudorange and doppler of `best` with the average of the
+/// measurements, weighted by their C/N0 in linear units
+fn merge<'a>(
+    best: &NavigationMeasurement,
+    measurements: impl Iterator<Item = &'a NavigationMeasurement>,
+) -> NavigationMeasurement {
+    let mut pseudorange = (0.0, 0.0);
+    let mut doppler = (0.0, 0.0);
+    for nm in measurements {
+        let weight = nm.cn0().map_or(1.0, |cn0| 10f64.powf(cn0 / 10.0));
+        if let Some(value) = nm.pseudorange() {
+            pseudorange.0 += weight * value;
+            pseudorange.1 += weight;
+        }
+        if let Some(value) = nm.measured_doppler() {
+            doppler.0 += weight * value;
+            doppler.1 += weight;
+        }
+    }
+
+    let mut merged = best.clone();
+    if pseudorange.1 > 0.0 {
+        merged.set_pseudorange(pseudorange.0 / pseudorange.1);
+    }
+    if doppler.1 > 0.0 {
+        merged.set_measured_doppler(doppler.0 / doppler.1);
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::GnssSignal;
+    use float_eq::assert_float_eq;
+    use std::time::Duration;
+
+    fn measurement(
+        sat: u16,
+        code: Code,
+        pseudorange: f64,
+        cn0: f64,
+        lock_time: u64,
+    ) -> NavigationMeasurement {
+        let mut nm = NavigationMeasurement::new();
+        nm.set_sid(GnssSignal::new(sat, code).unwrap());
+        nm.set_pseudorange(pseudorange);
+        nm.set_cn0(cn0);
+        nm.set_lock_time(Duration::from_secs(lock_time));
+        nm
+    }
+
+    fn codes(measurements: &[NavigationMeasurement]) -> Vec<(u16, Code)> {
+        measurements
+            .iter()
+            .map(|nm| (nm.sid().sat(), nm.sid().code()))
+            .collect()
+    }
+
+    #[test]
+    fn select_per_band() {
+        let mut no_code = measurement(4, Code::GpsL2cm, 0.0, 50.0, 100);
+        no_code.invalidate_pseudorange();
+        let measurements = [
+            measurement(4, Code::GpsL1ca, 2.0e7, 45.0, 10),
+            measurement(4, Code::GpsL2cl, 2.0e7 + 2.0, 38.0, 50),
+            measurement(4, Code::GpsL2p, 2.0e7 + 1.0, 35.0, 20),
+            no_code,
+            measurement(4, Code::GpsL5q, 2.0e7, 48.0, 10),
+            measurement(7, Code::GpsL2cm, 2.2e7, 40.0, 5),
+            measurement(7, Code::GpsL2cl, 2.2e7, 41.0, 5),
+            measurement(3, Code::GalE5q, 2.4e7, 44.0, 5),
+            measurement(3, Code::GalE7q, 2.4e7, 44.0, 5),
+        ];
+
+        let selector = SignalSelector::default();
+        assert_eq!(
+            codes(&selector.select(&measurements)),
+            vec![
+                (4, Code::GpsL1ca),
+                (4, Code::GpsL2cl),
+                (4, Code::GpsL5q),
+                (7, Code::GpsL2cl),
+                (3, Code::GalE5q),
+                (3, Code::GalE7q),
+            ]
+        );
+
+        let settings = SelectionSettings::new().set_policy(SelectionPolicy::LongestLock);
+        let selected = SignalSelector::new(settings).select(&measurements);
+        assert_eq!(selected.len(), 6);
+        assert_eq!(selected[1].sid().code(), Code::GpsL2cl);
+        assert_eq!(selected[3].sid().code(), Code::GpsL2cl);
+
+        let settings = SelectionSettings::new()
+            .set_policy(SelectionPolicy::Preferred)
+            .set_preferred_codes(vec![Code::GpsL2p, Code::GpsL2cm]);
+        let selected = SignalSelector::new(settings).select(&measurements);
+        assert_eq!(selected[1].sid().code(), Code::GpsL2p);
+        assert_eq!(selected[3].sid().code(), Code::GpsL2cm);
+    }
+
+    #[test]
+    fn merge_band() {
+        let mut l2cm = measurement(12, Code::GpsL2cm, 2.0e7, 40.0, 10);
+        l2cm.set_measured_doppler(-1000.0);
+        let mut l2cl = measurement(12, Code::GpsL2cl, 2.0e7 + 11.0, 50.0, 10);
+        l2cl.set_measured_doppler(-1011.0);
+        l2cl.set_carrier_phase(1.2e8);
+
+        let settings = SelectionSettings::new().set_policy(SelectionPolicy::Merge);
+        let selected = SignalSelector::new(settings).select(&[l2cm, l2cl]);
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].sid().code(), Code::GpsL2cl);
+        assert_float_eq!(
+            selected[0].pseudorange().unwrap(),
+            2.0e7 + 10.0,
+            abs <= 1e-6
+        );
+        assert_float_eq!(
+            selected[0].measured_doppler().unwrap(),
+            -1010.0,
+            abs <= 1e-9
+        );
+        assert_eq!(selected[0].carrier_phase(), Some(1.2e8));
+        assert_eq!(selected[0].cn0(), Some(50.0));
+    }
+}
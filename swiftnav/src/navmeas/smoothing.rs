@@ -0,0 +1,319 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Carrier smoothing of pseudoranges
+//!
+//! The carrier phase is much less noisy than the pseudorange, but ambiguous.
+//! The [`HatchFilter`] uses the change in carrier phase between epochs to
+//! average the pseudorange noise over time, without the ambiguity:
+//!
+//! P̂ₖ = Pₖ / n + (n - 1) / n · (P̂ₖ₋₁ + Φₖ - Φₖ₋₁)
+//!
+//! where n grows with each epoch up to the smoothing window. The filter of a
+//! signal is restarted whenever its carrier phase can't be trusted, i.e. when
+//! it is missing, the lock time goes backwards, epochs are missing, or the
+//! pseudorange diverges from the smoothed value.
+//!
+//! The ionospheric delay has opposite signs on the code and carrier, so the
+//! smoothed pseudorange lags behind changes in the delay. Longer windows give
+//! less noise but more of this divergence.
+//!
+//! # References
+//!   * Hatch R., "The Synergism of GPS Code and Carrier Measurements",
+//!     Proceedings of the Third International Geodetic Symposium on Satellite
+//!     Doppler Positioning, 1982
+
+use super::NavigationMeasurement;
+use crate::signal::GnssSignal;
+use crate::time::GpsTime;
+use crate::SPEED_OF_LIGHT;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Settings of the carrier smoothing
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HatchSettings {
+    window: u32,
+    max_gap: Duration,
+    max_divergence: f64,
+}
+
+impl HatchSettings {
+    /// Makes the default settings
+    ///
+    /// Note: The default settings consist of
+    ///  * A smoothing window of 100 epochs
+    ///  * Restarting after 5 seconds without a measurement of the signal
+    ///  * Restarting when the pseudorange is 30 m away from the smoothed value
+    pub fn new() -> HatchSettings {
+        HatchSettings {
+            window: 100,
+            max_gap: Duration::from_secs(5),
+            max_divergence: 30.0,
+        }
+    }
+
+    /// Sets the largest number of epochs averaged together
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `window` is zero
+    pub fn set_window(self, window: u32) -> HatchSettings {
+        assert!(window > 0, "Smoothing window must be larger than zero");
+        HatchSettings { window, ..self }
+    }
+
+    /// Sets the longest time between two measurements of a signal before
+    /// the smoothing is restarted
+    pub fn set_max_gap(self, max_gap: Duration) -> HatchSettings {
+        HatchSettings { max_gap, ..self }
+    }
+
+    /// Sets the largest difference between the pseudorange and the predicted
+    /// smoothed pseudorange before the smoothing is restarted, in meters
+    pub fn set_max_divergence(self, max_divergence: f64) -> HatchSettings {
+        HatchSettings {
+            max_divergence,
+            ..self
+        }
+    }
+
+    pub fn window(&self) -> u32 {
+        self.window
+    }
+
+    pub fn max_gap(&self) -> Duration {
+        self.max_gap
+    }
+
+    pub fn max_divergence(&self) -> f64 {
+        self.max_divergence
+    }
+}
+
+impl Default for HatchSettings {
+    fn default() -> HatchSettings {
+        HatchSettings::new()
+    }
+}
+
+/// Smoothing state of a single signal
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+struct SignalState {
+    time: GpsTime,
+    smoothed: f64,
+    carrier_phase: f64,
+    lock_time: Duration,
+    count: u32,
+}
+
+/// Carrier smoothing filter of the pseudoranges of several signals
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub struct HatchFilter {
+    settings: HatchSettings,
+    signals: BTreeMap<GnssSignal, SignalState>,
+}
+
+impl HatchFilter {
+    pub fn new(settings: HatchSettings) -> HatchFilter {
+        HatchFilter {
+            settings,
+            signals: BTreeMap::new(),
+        }
+    }
+
+    pub fn settings(&self) -> &HatchSettings {
+        &self.settings
+    }
+
+    /// Smooths the pseudoranges of an epoch of measurements
+    ///
+    /// The measurements are returned in the same order, with their
+    /// pseudorange replaced by the smoothed one. Measurements without a
+    /// pseudorange are returned unchanged, and so are the measurements
+    /// without a usable carrier phase, which also restarts their smoothing.
+    /// Signals missing from the epoch are forgotten.
+    pub fn process(
+        &mut self,
+        t: GpsTime,
+        measurements: &[NavigationMeasurement],
+    ) -> Vec<NavigationMeasurement> {
+        let mut signals = BTreeMap::new();
+        let smoothed = measurements
+            .iter()
+            .map(|nm| {
+                let mut nm = nm.clone();
+                let pseudorange = match nm.pseudorange() {
+                    Some(pseudorange) => pseudorange,
+                    None => return nm,
+                };
+                let carrier_phase = match nm.checked_carrier_phase() {
+                    Ok(cycles) => cycles * SPEED_OF_LIGHT / nm.sid().carrier_frequency(),
+                    Err(_) => return nm,
+                };
+                let state = match self.signals.get(&nm.sid()) {
+                    Some(previous) => self.update(previous, t, &nm, pseudorange, carrier_phase),
+                    None => None,
+                }
+                .unwrap_or(SignalState {
+                    time: t,
+                    smoothed: pseudorange,
+                    carrier_phase,
+                    lock_time: nm.lock_time(),
+                    count: 1,
+                });
+                nm.set_pseudorange(state.smoothed);
+                signals.insert(nm.sid(), state);
+                nm
+            })
+            .collect();
+        self.signals = signals;
+        smoothed
+    }
+
+    /// Updates the state of a signal, or returns `None` if the smoothing
+    /// must be restarted
+    fn update(
+        &self,
+        previous: &SignalState,
+        t: GpsTime,
+        nm: &NavigationMeasurement,
+        pseudorange: f64,
+        carrier_phase: f64,
+    ) -> Option<SignalState> {
+        let dt = t.diff(&previous.time);
+        if dt <= 0.0 || dt > self.settings.max_gap.as_secs_f64() {
+            return None;
+        }
+        if nm.lock_time() < previous.lock_time {
+            return None;
+        }
+        let predicted = previous.smoothed + (carrier_phase - previous.carrier_phase);
+        if (pseudorange - predicted).abs() > self.settings.max_divergence {
+            return None;
+        }
+        let count = (previous.count + 1).min(self.settings.window);
+        let n = f64::from(count);
+        Some(SignalState {
+            time: t,
+            smoothed: pseudorange / n + (n - 1.0) / n * predicted,
+            carrier_phase,
+            lock_time: nm.lock_time(),
+            count,
+        })
+    }
+
+    /// Gets the number of epochs averaged in the latest smoothed pseudorange
+    /// of a signal, `None` if the signal isn't being smoothed
+    pub fn smoothing_count(&self, sid: GnssSignal) -> Option<u32> {
+        self.signals.get(&sid).map(|state| state.count)
+    }
+
+    /// Restarts the smoothing of a signal
+    pub fn reset_signal(&mut self, sid: GnssSignal) {
+        self.signals.remove(&sid);
+    }
+
+    /// Restarts the smoothing of all signals
+    pub fn reset(&mut self) {
+        self.signals.clear();
+    }
+}
+
+impl Default for HatchFilter {
+    fn default() -> HatchFilter {
+        HatchFilter::new(HatchSettings::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::Code;
+    use float_eq::assert_float_eq;
+
+    fn measurement(
+        sid: GnssSignal,
+        range: f64,
+        noise: f64,
+        lock_time: u64,
+    ) -> NavigationMeasurement {
+        let mut nm = NavigationMeasurement::new();
+        nm.set_sid(sid);
+        nm.set_pseudorange(range + noise);
+        nm.set_carrier_phase((range + 1234.5) * sid.carrier_frequency() / SPEED_OF_LIGHT);
+        nm.set_half_cycle_known(true);
+        nm.set_lock_time(Duration::from_secs(lock_time));
+        nm
+    }
+
+    #[test]
+    fn smooths_noise() {
+        let sid = GnssSignal::new(7, Code::GpsL1ca).unwrap();
+        let mut filter = HatchFilter::new(HatchSettings::new().set_window(10));
+        let start = GpsTime::new(2100, 0.0).unwrap();
+        let mut last = 0.0;
+        for k in 0..30u64 {
+            let range = 21_000_000.0 + 500.0 * k as f64;
+            let noise = if k % 2 == 0 { 3.0 } else { -3.0 };
+            let t = start + Duration::from_secs(k);
+            let smoothed = filter.process(t, &[measurement(sid, range, noise, k + 1)]);
+            last = smoothed[0].pseudorange().unwrap() - range;
+        }
+        assert_eq!(filter.smoothing_count(sid), Some(10));
+        // The alternating noise averages out to a tenth of its size
+        assert!(last.abs() <= 0.3 + 1e-6);
+    }
+
+    #[test]
+    fn restarts() {
+        let sid = GnssSignal::new(7, Code::GpsL1ca).unwrap();
+        let other = GnssSignal::new(8, Code::GpsL1ca).unwrap();
+        let mut filter = HatchFilter::default();
+        let start = GpsTime::new(2100, 0.0).unwrap();
+        let at = |s: u64| start + Duration::from_secs(s);
+
+        filter.process(at(0), &[measurement(sid, 2e7, 0.0, 10)]);
+        filter.process(at(1), &[measurement(sid, 2e7, 0.0, 11)]);
+        assert_eq!(filter.smoothing_count(sid), Some(2));
+
+        // Lock time going backwards means a cycle slip
+        let smoothed = filter.process(at(2), &[measurement(sid, 2e7, 2.0, 1)]);
+        assert_eq!(filter.smoothing_count(sid), Some(1));
+        assert_float_eq!(smoothed[0].pseudorange().unwrap(), 2e7 + 2.0, abs <= 1e-9);
+
+        // A gap in the measurements
+        filter.process(at(3), &[measurement(sid, 2e7, 0.0, 2)]);
+        assert_eq!(filter.smoothing_count(sid), Some(2));
+        filter.process(at(13), &[measurement(sid, 2e7, 0.0, 12)]);
+        assert_eq!(filter.smoothing_count(sid), Some(1));
+
+        // A jump in the carrier phase
+        filter.process(at(14), &[measurement(sid, 2e7, 0.0, 13)]);
+        assert_eq!(filter.smoothing_count(sid), Some(2));
+        let mut slipped = measurement(sid, 2e7, 0.0, 14);
+        slipped.set_carrier_phase(slipped.carrier_phase().unwrap() + 1000.0);
+        filter.process(at(15), &[slipped]);
+        assert_eq!(filter.smoothing_count(sid), Some(1));
+
+        // Measurements without a usable carrier phase pass through
+        let mut unlocked = measurement(other, 2e7, 0.0, 0);
+        unlocked.set_half_cycle_known(false);
+        let smoothed = filter.process(at(16), &[unlocked.clone()]);
+        assert_eq!(smoothed[0], unlocked);
+        assert_eq!(filter.smoothing_count(other), None);
+        // and the signals missing from the epoch are forgotten
+        assert_eq!(filter.smoothing_count(sid), None);
+
+        filter.process(at(17), &[measurement(sid, 2e7, 0.0, 17)]);
+        filter.reset();
+        assert_eq!(filter.smoothing_count(sid), None);
+    }
+}
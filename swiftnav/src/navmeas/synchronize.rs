@@ -0,0 +1,548 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Time alignment of base and rover measurements
+//!
+//! Differential processing needs the base station measurements at the same
+//! time as the rover's, but the two receivers don't necessarily sample at the
+//! same instants, and the base measurements usually arrive later than the
+//! rover's over the correction link. The [`EpochSynchronizer`] holds the rover
+//! epochs back until the base measurements covering them have arrived, and
+//! pairs each rover epoch with base measurements aligned to its time:
+//!
+//!  * A base epoch within the epoch tolerance of the rover epoch is used as is
+//!  * Otherwise the base observables are linearly interpolated between the
+//!    base epochs either side of the rover epoch
+//!  * If no newer base epoch arrives within the latency limit, the base
+//!    observables can optionally be extrapolated from the two latest base
+//!    epochs
+//!
+//! Pseudoranges, carrier phases and dopplers are interpolated per signal, and
+//! only for signals present in both base epochs. A carrier phase is dropped if
+//! the lock time decreased between the two epochs, as that indicates a cycle
+//! slip. The other values, such as the C/N0 and the satellite state, are taken
+//! from the base epoch closest in time.
+//!
+//! The interpolation error grows with the square of the time between the base
+//! epochs, with the acceleration of the range to the satellite and with the
+//! base receiver clock drift, so the defaults are tight enough for carrier
+//! phase processing of a 1 Hz base. Base receivers which steer their clock in
+//! millisecond jumps must not be interpolated across a jump.
+
+use super::NavigationMeasurement;
+use crate::signal::GnssSignal;
+use crate::time::GpsTime;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Settings for synchronizing base and rover measurements
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SyncSettings {
+    epoch_tolerance: Duration,
+    max_interpolation_gap: Duration,
+    max_latency: Duration,
+    max_extrapolation: Duration,
+}
+
+impl SyncSettings {
+    /// Makes the default settings
+    ///
+    /// Note: The default settings consist of
+    ///  * Epochs less than 1 ms apart are considered the same epoch
+    ///  * Interpolation between base epochs up to 1 s apart
+    ///  * Rover epochs wait up to 2 s for the base measurements
+    ///  * No extrapolation of the base measurements
+    pub fn new() -> SyncSettings {
+        SyncSettings {
+            epoch_tolerance: Duration::from_millis(1),
+            max_interpolation_gap: Duration::from_secs(1),
+            max_latency: Duration::from_secs(2),
+            max_extrapolation: Duration::ZERO,
+        }
+    }
+
+    /// Sets the largest time difference between a base and a rover epoch
+    /// which are considered the same epoch
+    pub fn set_epoch_tolerance(self, epoch_tolerance: Duration) -> SyncSettings {
+        SyncSettings {
+            epoch_tolerance,
+            ..self
+        }
+    }
+
+    /// Sets the largest time between two base epochs which are interpolated
+    /// or extrapolated between
+    pub fn set_max_interpolation_gap(self, max_interpolation_gap: Duration) -> SyncSettings {
+        SyncSettings {
+            max_interpolation_gap,
+            ..self
+        }
+    }
+
+    /// Sets how long a rover epoch waits for the base measurements, measured
+    /// against the newest rover epoch received
+    pub fn set_max_latency(self, max_latency: Duration) -> SyncSettings {
+        SyncSettings {
+            max_latency,
+            ..self
+        }
+    }
+
+    /// Sets how far past the latest base epoch the base measurements can be
+    /// extrapolated, zero disables extrapolation
+    pub fn set_max_extrapolation(self, max_extrapolation: Duration) -> SyncSettings {
+        SyncSettings {
+            max_extrapolation,
+            ..self
+        }
+    }
+
+    pub fn epoch_tolerance(&self) -> Duration {
+        self.epoch_tolerance
+    }
+
+    pub fn max_interpolation_gap(&self) -> Duration {
+        self.max_interpolation_gap
+    }
+
+    pub fn max_latency(&self) -> Duration {
+        self.max_latency
+    }
+
+    pub fn max_extrapolation(&self) -> Duration {
+        self.max_extrapolation
+    }
+}
+
+impl Default for SyncSettings {
+    fn default() -> SyncSettings {
+        SyncSettings::new()
+    }
+}
+
+/// How the base measurements of a synchronized epoch were obtained
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Alignment {
+    /// A base epoch at the time of the rover epoch was used as is
+    Matched,
+    /// Interpolated between the base epochs either side of the rover epoch
+    Interpolated,
+    /// Extrapolated from the two latest base epochs before the rover epoch
+    Extrapolated,
+}
+
+/// A rover epoch paired with base measurements aligned to its time
+#[derive(Debug, Clone, PartialEq)]
+pub struct SynchronizedEpoch {
+    time: GpsTime,
+    rover: Vec<NavigationMeasurement>,
+    base: Vec<NavigationMeasurement>,
+    alignment: Alignment,
+    base_age: f64,
+}
+
+impl SynchronizedEpoch {
+    /// Gets the time of the rover epoch
+    pub fn time(&self) -> GpsTime {
+        self.time
+    }
+
+    /// Gets the rover measurements, as received
+    pub fn rover(&self) -> &[NavigationMeasurement] {
+        &self.rover
+    }
+
+    /// Gets the base measurements aligned to the rover epoch, ordered by signal
+    pub fn base(&self) -> &[NavigationMeasurement] {
+        &self.base
+    }
+
+    pub fn alignment(&self) -> Alignment {
+        self.alignment
+    }
+
+    /// Gets the time of the rover epoch minus the time of the latest base
+    /// epoch used, in seconds
+    ///
+    /// This is negative for interpolated epochs.
+    pub fn base_age(&self) -> f64 {
+        self.base_age
+    }
+}
+
+struct Epoch {
+    time: GpsTime,
+    measurements: BTreeMap<GnssSignal, NavigationMeasurement>,
+}
+
+/// Pairs rover epochs with time aligned base measurements
+///
+/// Rover epochs are output in the order they were received, once the base
+/// measurements after them have arrived, or once a newer rover epoch is
+/// `max_latency` ahead of them. Rover epochs which can't be aligned with the
+/// base measurements by then are dropped.
+pub struct EpochSynchronizer {
+    settings: SyncSettings,
+    base: Vec<Epoch>,
+    rover: Vec<(GpsTime, Vec<NavigationMeasurement>)>,
+    dropped: usize,
+}
+
+impl EpochSynchronizer {
+    pub fn new(settings: SyncSettings) -> EpochSynchronizer {
+        EpochSynchronizer {
+            settings,
+            base: Vec::new(),
+            rover: Vec::new(),
+            dropped: 0,
+        }
+    }
+
+    pub fn settings(&self) -> &SyncSettings {
+        &self.settings
+    }
+
+    /// Gets the number of rover epochs dropped because no base measurements
+    /// could be aligned with them
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Adds the measurements of a base epoch
+    ///
+    /// Base epochs can arrive out of order, measurements for an epoch already
+    /// received replace the previous ones signal by signal. Returns the rover
+    /// epochs which are ready to be output, oldest first.
+    pub fn push_base(
+        &mut self,
+        time: GpsTime,
+        measurements: &[NavigationMeasurement],
+    ) -> Vec<SynchronizedEpoch> {
+        let tolerance = self.settings.epoch_tolerance.as_secs_f64();
+        let index = match self
+            .base
+            .iter()
+            .position(|epoch| time.diff(&epoch.time).abs() <= tolerance)
+        {
+            Some(index) => index,
+            None => {
+                let index = self
+                    .base
+                    .iter()
+                    .position(|epoch| epoch.time.diff(&time) > 0.0)
+                    .unwrap_or(self.base.len());
+                self.base.insert(
+                    index,
+                    Epoch {
+                        time,
+                        measurements: BTreeMap::new(),
+                    },
+                );
+                index
+            }
+        };
+        let epoch = &mut self.base[index];
+        for nm in measurements {
+            epoch.measurements.insert(nm.sid(), nm.clone());
+        }
+        self.release(false)
+    }
+
+    /// Adds the measurements of a rover epoch
+    ///
+    /// Returns the rover epochs which are ready to be output, oldest first
+    pub fn push_rover(
+        &mut self,
+        time: GpsTime,
+        measurements: &[NavigationMeasurement],
+    ) -> Vec<SynchronizedEpoch> {
+        self.rover.push((time, measurements.to_vec()));
+        self.release(false)
+    }
+
+    /// Outputs all of the pending rover epochs which can be aligned with the
+    /// base measurements received so far, oldest first
+    ///
+    /// The others are dropped.
+    pub fn flush(&mut self) -> Vec<SynchronizedEpoch> {
+        self.release(true)
+    }
+
+    fn release(&mut self, flush: bool) -> Vec<SynchronizedEpoch> {
+        let newest = match self.rover.last() {
+            Some((time, _)) => *time,
+            None => return Vec::new(),
+        };
+        let max_latency = self.settings.max_latency.as_secs_f64();
+
+        let mut released = Vec::new();
+        let mut done = 0;
+        for (time, rover) in &self.rover {
+            let expired = flush || newest.diff(time) >= max_latency;
+            let aligned = match self.align(*time) {
+                Some(aligned) => Some(aligned),
+                None if expired => self.extrapolate(*time),
+                None => break,
+            };
+            match aligned {
+                Some((base, alignment, base_age)) => released.push(SynchronizedEpoch {
+                    time: *time,
+                    rover: rover.clone(),
+                    base,
+                    alignment,
+                    base_age,
+                }),
+                None => self.dropped += 1,
+            }
+            done += 1;
+        }
+        self.rover.drain(..done);
+        self.prune();
+        released
+    }
+
+    /// Aligns the base measurements by matching or interpolation
+    fn align(&self, time: GpsTime) -> Option<(Vec<NavigationMeasurement>, Alignment, f64)> {
+        let tolerance = self.settings.epoch_tolerance.as_secs_f64();
+        if let Some(epoch) = self
+            .base
+            .iter()
+            .find(|epoch| time.diff(&epoch.time).abs() <= tolerance)
+        {
+            let base = epoch.measurements.values().cloned().collect();
+            return Some((base, Alignment::Matched, time.diff(&epoch.time)));
+        }
+
+        let after = self
+            .base
+            .iter()
+            .position(|epoch| epoch.time.diff(&time) > 0.0)?;
+        if after == 0 {
+            return None;
+        }
+        let (before, after) = (&self.base[after - 1], &self.base[after]);
+        let base = self.interpolate(before, after, time)?;
+        Some((base, Alignment::Interpolated, time.diff(&after.time)))
+    }
+
+    /// Extrapolates the base measurements from the two latest base epochs
+    fn extrapolate(&self, time: GpsTime) -> Option<(Vec<NavigationMeasurement>, Alignment, f64)> {
+        let count = self.base.len();
+        if count < 2 {
+            return None;
+        }
+        let (before, latest) = (&self.base[count - 2], &self.base[count - 1]);
+        let age = time.diff(&latest.time);
+        if age < 0.0 || age > self.settings.max_extrapolation.as_secs_f64() {
+            return None;
+        }
+        let base = self.interpolate(before, latest, time)?;
+        Some((base, Alignment::Extrapolated, age))
+    }
+
+    fn interpolate(
+        &self,
+        first: &Epoch,
+        second: &Epoch,
+        time: GpsTime,
+    ) -> Option<Vec<NavigationMeasurement>> {
+        let gap = second.time.diff(&first.time);
+        let max_gap =
+            (self.settings.max_interpolation_gap + self.settings.epoch_tolerance).as_secs_f64();
+        if gap > max_gap {
+            return None;
+        }
+        let fraction = time.diff(&first.time) / gap;
+        let base = first
+            .measurements
+            .iter()
+            .filter_map(|(sid, a)| {
+                let b = second.measurements.get(sid)?;
+                interpolate_measurement(a, b, fraction)
+            })
+            .collect();
+        Some(base)
+    }
+
+    /// Drops the base epochs which are no longer needed by the pending rover
+    /// epochs, keeping two epochs for extrapolation
+    fn prune(&mut self) {
+        let oldest = match self.rover.first() {
+            Some((time, _)) => *time,
+            None => match self.base.last() {
+                Some(epoch) => epoch.time,
+                None => return,
+            },
+        };
+        let older = self
+            .base
+            .iter()
+            .take_while(|epoch| oldest.diff(&epoch.time) > 0.0)
+            .count();
+        self.base.drain(..older.saturating_sub(2));
+    }
+}
+
+/// Linearly interpolates the observables of a signal between two epochs
+///
+/// `fraction` is the position of the target time between the epochs, it is
+/// greater than one when extrapolating. Returns `None` if neither a pseudorange
+/// nor a carrier phase can be interpolated.
+fn interpolate_measurement(
+    a: &NavigationMeasurement,
+    b: &NavigationMeasurement,
+    fraction: f64,
+) -> Option<NavigationMeasurement> {
+    let lerp = |x: f64, y: f64| x + (y - x) * fraction;
+    let mut nm = if fraction < 0.5 { a.clone() } else { b.clone() };
+
+    match (a.pseudorange(), b.pseudorange()) {
+        (Some(x), Some(y)) => nm.set_pseudorange(lerp(x, y)),
+        _ => nm.invalidate_pseudorange(),
+    }
+    match (a.carrier_phase(), b.carrier_phase()) {
+        (Some(x), Some(y)) if b.lock_time() >= a.lock_time() => {
+            nm.set_carrier_phase(lerp(x, y));
+            nm.set_half_cycle_known(a.half_cycle_known() && b.half_cycle_known());
+        }
+        _ => nm.invalidate_carrier_phase(),
+    }
+    match (a.measured_doppler(), b.measured_doppler()) {
+        (Some(x), Some(y)) => nm.set_measured_doppler(lerp(x, y)),
+        _ => nm.invalidate_measured_doppler(),
+    }
+
+    if nm.pseudorange().is_none() && nm.carrier_phase().is_none() {
+        None
+    } else {
+        Some(nm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::Code;
+    use float_eq::assert_float_eq;
+
+    /// Base measurements with observables changing linearly over time
+    fn base_epoch(tow: f64, lock_time: u64) -> Vec<NavigationMeasurement> {
+        [1, 2]
+            .iter()
+            .map(|sat| {
+                let mut nm = NavigationMeasurement::new();
+                nm.set_sid(GnssSignal::new(*sat, Code::GpsL1ca).unwrap());
+                nm.set_pseudorange(20_000_000.0 + 100.0 * tow);
+                nm.set_carrier_phase(1_000.0 + 500.0 * tow);
+                nm.set_half_cycle_known(true);
+                nm.set_measured_doppler(-500.0);
+                nm.set_lock_time(Duration::from_secs(lock_time));
+                nm
+            })
+            .collect()
+    }
+
+    fn time(tow: f64) -> GpsTime {
+        GpsTime::new(2100, tow).unwrap()
+    }
+
+    #[test]
+    fn matching_and_interpolation() {
+        let mut sync = EpochSynchronizer::new(SyncSettings::new());
+        let rover = base_epoch(10.0, 1);
+
+        // The rover measurements arrive before the base ones
+        assert!(sync.push_rover(time(10.0), &rover).is_empty());
+        assert!(sync.push_base(time(9.6), &base_epoch(9.6, 5)).is_empty());
+        let ready = sync.push_base(time(10.6), &base_epoch(10.6, 6));
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].time(), time(10.0));
+        assert_eq!(ready[0].alignment(), Alignment::Interpolated);
+        assert_float_eq!(ready[0].base_age(), -0.6, abs <= 1e-9);
+        assert_eq!(ready[0].rover(), &rover[..]);
+        assert_eq!(ready[0].base().len(), 2);
+        let base = &ready[0].base()[0];
+        assert_float_eq!(base.pseudorange().unwrap(), 20_001_000.0, abs <= 1e-6);
+        assert_float_eq!(base.carrier_phase().unwrap(), 6_000.0, abs <= 1e-6);
+        assert_float_eq!(base.measured_doppler().unwrap(), -500.0, abs <= 1e-9);
+        assert!(base.half_cycle_known());
+
+        // A base epoch within the tolerance is used directly
+        let ready = sync.push_rover(time(10.6005), &rover);
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].alignment(), Alignment::Matched);
+        assert_eq!(ready[0].base(), &base_epoch(10.6, 6)[..]);
+
+        // A cycle slip between the base epochs drops the carrier phase
+        assert!(sync.push_rover(time(11.0), &rover).is_empty());
+        let ready = sync.push_base(time(11.6), &base_epoch(11.6, 1));
+        assert_eq!(ready.len(), 1);
+        let base = &ready[0].base()[0];
+        assert_float_eq!(base.pseudorange().unwrap(), 20_001_100.0, abs <= 1e-6);
+        assert!(base.carrier_phase().is_none());
+        assert!(!base.half_cycle_known());
+        assert_eq!(sync.dropped(), 0);
+    }
+
+    #[test]
+    fn latency_and_extrapolation() {
+        let rover = base_epoch(10.0, 1);
+
+        // Without extrapolation rover epochs are dropped once they expire
+        let mut sync = EpochSynchronizer::new(SyncSettings::new());
+        sync.push_base(time(8.0), &base_epoch(8.0, 5));
+        sync.push_base(time(9.0), &base_epoch(9.0, 6));
+        assert!(sync.push_rover(time(9.5), &rover).is_empty());
+        assert!(sync.push_rover(time(11.0), &rover).is_empty());
+        assert!(sync.push_rover(time(11.5), &rover).is_empty());
+        assert_eq!(sync.dropped(), 1);
+        assert!(sync.flush().is_empty());
+        assert_eq!(sync.dropped(), 3);
+
+        let settings = SyncSettings::new().set_max_extrapolation(Duration::from_millis(500));
+        let mut sync = EpochSynchronizer::new(settings);
+        sync.push_base(time(8.0), &base_epoch(8.0, 5));
+        sync.push_base(time(9.0), &base_epoch(9.0, 6));
+        assert!(sync.push_rover(time(9.5), &rover).is_empty());
+        let ready = sync.push_rover(time(11.5), &rover);
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].alignment(), Alignment::Extrapolated);
+        assert_float_eq!(ready[0].base_age(), 0.5, abs <= 1e-9);
+        let base = &ready[0].base()[1];
+        assert_eq!(base.sid().sat(), 2);
+        assert_float_eq!(base.pseudorange().unwrap(), 20_000_950.0, abs <= 1e-6);
+        assert_float_eq!(base.carrier_phase().unwrap(), 5_750.0, abs <= 1e-6);
+        // Too far past the latest base epoch
+        assert!(sync.flush().is_empty());
+        assert_eq!(sync.dropped(), 1);
+    }
+
+    #[test]
+    fn interpolation_gap() {
+        let mut sync = EpochSynchronizer::new(SyncSettings::new());
+        sync.push_base(time(8.0), &base_epoch(8.0, 5));
+        assert!(sync.push_rover(time(9.0), &base_epoch(9.0, 1)).is_empty());
+        assert!(sync.push_base(time(10.0), &base_epoch(10.0, 7)).is_empty());
+        assert!(sync.flush().is_empty());
+        assert_eq!(sync.dropped(), 1);
+
+        let settings = SyncSettings::new().set_max_interpolation_gap(Duration::from_secs(2));
+        let mut sync = EpochSynchronizer::new(settings);
+        sync.push_base(time(8.0), &base_epoch(8.0, 5));
+        assert!(sync.push_rover(time(9.0), &base_epoch(9.0, 1)).is_empty());
+        let ready = sync.push_base(time(10.0), &base_epoch(10.0, 7));
+        assert_eq!(ready.len(), 1);
+        assert_float_eq!(
+            ready[0].base()[0].pseudorange().unwrap(),
+            20_000_900.0,
+            abs <= 1e-6
+        );
+    }
+}
@@ -0,0 +1,1094 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! NMEA 0183 output formatting
+//!
+//! Downstream NMEA parsers are often strict about the exact layout of the
+//! fields they accept, so the number of decimal places and the talker ID used
+//! are configurable through [`NmeaFormat`].
+//!
+//! Latitudes and longitudes are formatted as degrees and decimal minutes
+//! (`ddmm.mmmm` and `dddmm.mmmm`) with a separate hemisphere field, as
+//! required by the standard.
+//!
+//! The GGA, RMC, GSA, VTG and ZDA sentences are made from a [`NmeaFix`],
+//! which is usually filled in from a [`GnssSolution`], and the GSV sentences
+//! from the [`SatelliteView`]s of the tracked satellites. Sentences are
+//! returned with their leading `$` and trailing checksum, but without the
+//! `\r\n` line ending.
+//!
+//! # References
+//!   * NMEA 0183 Standard for Interfacing Marine Electronic Devices, Version
+//!     4.11
+
+use crate::coords::{AzimuthElevation, LLHRadians, NED};
+use crate::geoid::get_geoid_offset;
+use crate::signal::{Constellation, GnssSignal};
+use crate::solver::{Dops, GnssSolution};
+use crate::time::UtcTime;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Knots per m/s
+const KNOTS_PER_MPS: f64 = 3600.0 / 1852.0;
+/// km/h per m/s
+const KPH_PER_MPS: f64 = 3.6;
+/// Most decimal places on latitude and longitude minutes, ~0.2 µm, more
+/// than any receiver can resolve
+pub const MAX_LAT_LON_DECIMALS: usize = 10;
+/// Number of satellite ID fields in a GSA sentence
+const GSA_SATELLITES: usize = 12;
+/// Number of satellites described by each GSV sentence
+const GSV_SATELLITES: usize = 4;
+
+/// Talker IDs identifying the source of a sentence
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TalkerId {
+    /// GPS, also used for SBAS and QZSS
+    GP,
+    /// GLONASS
+    GL,
+    /// Galileo
+    GA,
+    /// BeiDou
+    GB,
+    /// QZSS
+    GQ,
+    /// Combined GNSS
+    GN,
+}
+
+impl TalkerId {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TalkerId::GP => "GP",
+            TalkerId::GL => "GL",
+            TalkerId::GA => "GA",
+            TalkerId::GB => "GB",
+            TalkerId::GQ => "GQ",
+            TalkerId::GN => "GN",
+        }
+    }
+
+    /// Gets the talker ID of a single constellation
+    pub fn from_constellation(constellation: Constellation) -> TalkerId {
+        match constellation {
+            Constellation::Gps | Constellation::Sbas => TalkerId::GP,
+            Constellation::Glo => TalkerId::GL,
+            Constellation::Gal => TalkerId::GA,
+            Constellation::Bds => TalkerId::GB,
+            Constellation::Qzs => TalkerId::GQ,
+        }
+    }
+}
+
+impl fmt::Display for TalkerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Quality of a fix, as reported in the GGA sentence
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FixQuality {
+    Invalid,
+    /// Single point positioning
+    Single,
+    /// Differential or SBAS corrected positioning
+    Differential,
+    /// Precise positioning service
+    Pps,
+    /// RTK with fixed integer ambiguities
+    RtkFixed,
+    /// RTK with float ambiguities
+    RtkFloat,
+    DeadReckoning,
+    /// Manually entered position
+    Manual,
+    Simulation,
+}
+
+impl FixQuality {
+    /// Gets the value of the GGA quality field
+    pub fn gga_value(&self) -> u8 {
+        match self {
+            FixQuality::Invalid => 0,
+            FixQuality::Single => 1,
+            FixQuality::Differential => 2,
+            FixQuality::Pps => 3,
+            FixQuality::RtkFixed => 4,
+            FixQuality::RtkFloat => 5,
+            FixQuality::DeadReckoning => 6,
+            FixQuality::Manual => 7,
+            FixQuality::Simulation => 8,
+        }
+    }
+
+    /// Gets the mode indicator used by the RMC and VTG sentences
+    pub fn mode_indicator(&self) -> char {
+        match self {
+            FixQuality::Invalid => 'N',
+            FixQuality::Single | FixQuality::Pps => 'A',
+            FixQuality::Differential => 'D',
+            FixQuality::RtkFixed => 'R',
+            FixQuality::RtkFloat => 'F',
+            FixQuality::DeadReckoning => 'E',
+            FixQuality::Manual => 'M',
+            FixQuality::Simulation => 'S',
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        *self != FixQuality::Invalid
+    }
+}
+
+/// How the talker ID of a sentence is chosen
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TalkerPolicy {
+    /// Always use the same talker ID
+    Fixed(TalkerId),
+    /// Use the talker ID of the constellation when only one is used, and `GN`
+    /// when several are
+    Auto,
+    /// Use `GP` when only GPS, SBAS and QZSS are used, and `GN` otherwise.
+    /// Older parsers often only understand these two.
+    GpsOrGn,
+}
+
+/// Formatting settings for NMEA sentences
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NmeaFormat {
+    lat_lon_decimals: usize,
+    altitude_decimals: usize,
+    talker_policy: TalkerPolicy,
+}
+
+impl NmeaFormat {
+    /// Makes the default format
+    ///
+    /// Note: The default format consists of
+    ///  * 5 decimal places on latitude and longitude minutes, ~2 cm
+    ///  * 2 decimal places on altitudes
+    ///  * [`TalkerPolicy::Auto`] talker selection
+    pub fn new() -> NmeaFormat {
+        NmeaFormat {
+            lat_lon_decimals: 5,
+            altitude_decimals: 2,
+            talker_policy: TalkerPolicy::Auto,
+        }
+    }
+
+    /// Sets the number of decimal places on latitude and longitude minutes
+    ///
+    /// The number is limited to [`MAX_LAT_LON_DECIMALS`], larger values are
+    /// clamped to it.
+    pub fn set_lat_lon_decimals(self, lat_lon_decimals: usize) -> NmeaFormat {
+        NmeaFormat {
+            lat_lon_decimals: lat_lon_decimals.min(MAX_LAT_LON_DECIMALS),
+            ..self
+        }
+    }
+
+    /// Sets the number of decimal places on altitudes and heights
+    pub fn set_altitude_decimals(self, altitude_decimals: usize) -> NmeaFormat {
+        NmeaFormat {
+            altitude_decimals,
+            ..self
+        }
+    }
+
+    /// Sets how the talker ID is chosen
+    pub fn set_talker_policy(self, talker_policy: TalkerPolicy) -> NmeaFormat {
+        NmeaFormat {
+            talker_policy,
+            ..self
+        }
+    }
+
+    pub fn lat_lon_decimals(&self) -> usize {
+        self.lat_lon_decimals
+    }
+
+    pub fn altitude_decimals(&self) -> usize {
+        self.altitude_decimals
+    }
+
+    pub fn talker_policy(&self) -> TalkerPolicy {
+        self.talker_policy
+    }
+
+    /// Chooses the talker ID for a sentence given the constellations used
+    ///
+    /// With no constellations `GN` is used, unless the talker ID is fixed
+    pub fn talker_for(&self, constellations: &[Constellation]) -> TalkerId {
+        let is_gps_like = |c: &Constellation| {
+            matches!(
+                c,
+                Constellation::Gps | Constellation::Sbas | Constellation::Qzs
+            )
+        };
+        match self.talker_policy {
+            TalkerPolicy::Fixed(talker) => talker,
+            TalkerPolicy::Auto => {
+                let mut talkers = constellations
+                    .iter()
+                    .map(|c| TalkerId::from_constellation(*c));
+                match talkers.next() {
+                    Some(first) if talkers.all(|t| t == first) => first,
+                    _ => TalkerId::GN,
+                }
+            }
+            TalkerPolicy::GpsOrGn => {
+                if !constellations.is_empty() && constellations.iter().all(is_gps_like) {
+                    TalkerId::GP
+                } else {
+                    TalkerId::GN
+                }
+            }
+        }
+    }
+
+    /// Formats a latitude, in degrees, into the `ddmm.mmmm` and hemisphere
+    /// fields
+    pub fn format_latitude(&self, latitude: f64) -> (String, char) {
+        let hemisphere = if latitude < 0.0 { 'S' } else { 'N' };
+        (
+            format_degrees_minutes(latitude.abs(), 2, self.lat_lon_decimals),
+            hemisphere,
+        )
+    }
+
+    /// Formats a longitude, in degrees, into the `dddmm.mmmm` and hemisphere
+    /// fields
+    pub fn format_longitude(&self, longitude: f64) -> (String, char) {
+        let hemisphere = if longitude < 0.0 { 'W' } else { 'E' };
+        (
+            format_degrees_minutes(longitude.abs(), 3, self.lat_lon_decimals),
+            hemisphere,
+        )
+    }
+
+    /// Formats an altitude or height, in meters
+    pub fn format_altitude(&self, altitude: f64) -> String {
+        format!("{:.*}", self.altitude_decimals, altitude)
+    }
+
+    /// Makes the GGA sentence, with the time, position and quality of a fix
+    ///
+    /// The altitude is given above the geoid, using the geoid separation of
+    /// the fix.
+    pub fn gga(&self, fix: &NmeaFix) -> String {
+        let (lat, lon, altitude, separation) = match fix.position() {
+            Some(llh) => (
+                self.format_latitude(llh.latitude().to_degrees()),
+                self.format_longitude(llh.longitude().to_degrees()),
+                self.format_altitude(llh.height() - fix.geoid_separation()),
+                self.format_altitude(fix.geoid_separation()),
+            ),
+            None => empty_position(),
+        };
+        let age = fix
+            .differential_age()
+            .map(|age| format!("{:.1}", age))
+            .unwrap_or_default();
+        let station = fix
+            .station_id()
+            .map(|id| format!("{:04}", id))
+            .unwrap_or_default();
+        let fields = [
+            format_time(fix.time()),
+            lat.0,
+            hemisphere_field(lat.1),
+            lon.0,
+            hemisphere_field(lon.1),
+            fix.quality().gga_value().to_string(),
+            format!("{:02}", fix.sats_used()),
+            format_dop(fix.hdop()),
+            altitude,
+            unit_field(fix.position().is_some()),
+            separation,
+            unit_field(fix.position().is_some()),
+            age,
+            station,
+        ];
+        make_sentence(self.talker_for(fix.constellations()), "GGA", &fields)
+    }
+
+    /// Makes the RMC sentence, with the time, position, speed and course of a
+    /// fix
+    pub fn rmc(&self, fix: &NmeaFix) -> String {
+        let (lat, lon, _, _) = match fix.position() {
+            Some(llh) => (
+                self.format_latitude(llh.latitude().to_degrees()),
+                self.format_longitude(llh.longitude().to_degrees()),
+                String::new(),
+                String::new(),
+            ),
+            None => empty_position(),
+        };
+        let (speed, course) = speed_and_course(fix.velocity());
+        let utc = fix.time();
+        let status = if fix.quality().is_valid() { 'A' } else { 'V' };
+        let fields = [
+            format_time(utc),
+            status.to_string(),
+            lat.0,
+            hemisphere_field(lat.1),
+            lon.0,
+            hemisphere_field(lon.1),
+            speed
+                .map(|s| format!("{:.3}", s * KNOTS_PER_MPS))
+                .unwrap_or_default(),
+            course.map(|c| format!("{:.2}", c)).unwrap_or_default(),
+            format!(
+                "{:02}{:02}{:02}",
+                utc.day_of_month(),
+                utc.month(),
+                utc.year() % 100
+            ),
+            String::new(),
+            String::new(),
+            fix.quality().mode_indicator().to_string(),
+        ];
+        make_sentence(self.talker_for(fix.constellations()), "RMC", &fields)
+    }
+
+    /// Makes the GSA sentences, with the satellites used in a fix and its DOPs
+    ///
+    /// When the `GN` talker ID is used one sentence is made per constellation,
+    /// each with the system ID of the constellation appended. A sentence lists
+    /// at most twelve satellites, any more are left out. Without any used
+    /// signals a single empty sentence is made.
+    pub fn gsa(&self, fix: &NmeaFix) -> Vec<String> {
+        let talker = self.talker_for(fix.constellations());
+        let by_system = group_by_system(fix.used_signals().iter().copied());
+        let groups: Vec<(Option<u8>, Vec<u16>)> = if talker == TalkerId::GN && !by_system.is_empty()
+        {
+            by_system
+                .into_iter()
+                .map(|(system, ids)| (Some(system), ids))
+                .collect()
+        } else {
+            vec![(None, by_system.into_values().flatten().collect())]
+        };
+
+        let fix_type = if fix.quality().is_valid() && fix.position().is_some() {
+            '3'
+        } else {
+            '1'
+        };
+        groups
+            .into_iter()
+            .map(|(system, ids)| {
+                let mut fields = vec!["A".to_string(), fix_type.to_string()];
+                fields.extend((0..GSA_SATELLITES).map(|i| match ids.get(i) {
+                    Some(id) => format!("{:02}", id),
+                    None => String::new(),
+                }));
+                fields.push(format_dop(fix.pdop()));
+                fields.push(format_dop(fix.hdop()));
+                fields.push(format_dop(fix.vdop()));
+                if let Some(system) = system {
+                    fields.push(format!("{:X}", system));
+                }
+                make_sentence(talker, "GSA", &fields)
+            })
+            .collect()
+    }
+
+    /// Makes the GSV sentences describing the satellites in view
+    ///
+    /// Since the satellite IDs of different constellations overlap, the
+    /// satellites are always grouped by constellation, and each group uses the
+    /// talker ID of its constellation. When several signals of the same
+    /// satellite are given only the first is used.
+    pub fn gsv(&self, satellites: &[SatelliteView]) -> Vec<String> {
+        let mut groups: BTreeMap<TalkerId, Vec<&SatelliteView>> = BTreeMap::new();
+        for sat in satellites {
+            let talker = TalkerId::from_constellation(sat.sid().to_constellation());
+            let group = groups.entry(talker).or_default();
+            if !group
+                .iter()
+                .any(|s| nmea_satellite_id(s.sid()) == nmea_satellite_id(sat.sid()))
+            {
+                group.push(sat);
+            }
+        }
+
+        let mut sentences = Vec::new();
+        for (talker, mut group) in groups {
+            group.sort_by_key(|s| nmea_satellite_id(s.sid()));
+            let count = (group.len() + GSV_SATELLITES - 1) / GSV_SATELLITES;
+            for (i, chunk) in group.chunks(GSV_SATELLITES).enumerate() {
+                let mut fields = vec![
+                    count.to_string(),
+                    (i + 1).to_string(),
+                    format!("{:02}", group.len()),
+                ];
+                for sat in chunk {
+                    let el = sat.azel().el.to_degrees().round().clamp(0.0, 90.0);
+                    let az = sat.azel().az.to_degrees().round().rem_euclid(360.0);
+                    fields.push(format!("{:02}", nmea_satellite_id(sat.sid())));
+                    fields.push(format!("{:02}", el as u8));
+                    fields.push(format!("{:03}", az as u16));
+                    fields.push(
+                        sat.cn0()
+                            .map(|cn0| format!("{:02}", cn0.round().clamp(0.0, 99.0) as u8))
+                            .unwrap_or_default(),
+                    );
+                }
+                sentences.push(make_sentence(talker, "GSV", &fields));
+            }
+        }
+        sentences
+    }
+
+    /// Makes the VTG sentence, with the course and speed over ground of a fix
+    pub fn vtg(&self, fix: &NmeaFix) -> String {
+        let (speed, course) = speed_and_course(fix.velocity());
+        let fields = [
+            course.map(|c| format!("{:.2}", c)).unwrap_or_default(),
+            "T".to_string(),
+            String::new(),
+            "M".to_string(),
+            speed
+                .map(|s| format!("{:.3}", s * KNOTS_PER_MPS))
+                .unwrap_or_default(),
+            "N".to_string(),
+            speed
+                .map(|s| format!("{:.3}", s * KPH_PER_MPS))
+                .unwrap_or_default(),
+            "K".to_string(),
+            fix.quality().mode_indicator().to_string(),
+        ];
+        make_sentence(self.talker_for(fix.constellations()), "VTG", &fields)
+    }
+
+    /// Makes the ZDA sentence, with the UTC date and time of a fix
+    ///
+    /// The local time zone fields are always zero.
+    pub fn zda(&self, fix: &NmeaFix) -> String {
+        let utc = fix.time();
+        let fields = [
+            format_time(utc),
+            format!("{:02}", utc.day_of_month()),
+            format!("{:02}", utc.month()),
+            format!("{:04}", utc.year()),
+            "00".to_string(),
+            "00".to_string(),
+        ];
+        make_sentence(self.talker_for(fix.constellations()), "ZDA", &fields)
+    }
+}
+
+impl Default for NmeaFormat {
+    fn default() -> NmeaFormat {
+        NmeaFormat::new()
+    }
+}
+
+/// The contents of a fix needed to make the NMEA sentences
+#[derive(Clone)]
+pub struct NmeaFix {
+    time: UtcTime,
+    quality: FixQuality,
+    position: Option<LLHRadians>,
+    velocity: Option<NED>,
+    geoid_separation: f64,
+    sats_used: u8,
+    used_signals: Vec<GnssSignal>,
+    constellations: Vec<Constellation>,
+    pdop: Option<f64>,
+    hdop: Option<f64>,
+    vdop: Option<f64>,
+    differential_age: Option<f64>,
+    station_id: Option<u16>,
+}
+
+impl NmeaFix {
+    /// Makes an invalid fix at a time, with no position or velocity
+    pub fn new(time: UtcTime) -> NmeaFix {
+        NmeaFix {
+            time,
+            quality: FixQuality::Invalid,
+            position: None,
+            velocity: None,
+            geoid_separation: 0.0,
+            sats_used: 0,
+            used_signals: Vec::new(),
+            constellations: Vec::new(),
+            pdop: None,
+            hdop: None,
+            vdop: None,
+            differential_age: None,
+            station_id: None,
+        }
+    }
+
+    /// Makes a fix from the output of the PVT solver
+    ///
+    /// A valid solution is reported as a [`FixQuality::Single`] fix. The
+    /// geoid separation is taken from the built in geoid model, and the time
+    /// is converted to UTC with the hardcoded leap seconds.
+    pub fn from_solution(solution: &GnssSolution, dops: &Dops) -> NmeaFix {
+        let mut fix = NmeaFix::new(solution.time().to_utc_hardcoded()).set_dops(dops);
+        fix.sats_used = solution.sats_used();
+        fix.velocity = solution.vel_ned();
+        if let Some(llh) = solution.pos_llh() {
+            fix = fix.set_position(llh, FixQuality::Single);
+            fix.geoid_separation = get_geoid_offset(llh) as f64;
+        }
+        fix
+    }
+
+    /// Sets the position of the fix, with the height above the WGS84
+    /// ellipsoid, and its quality
+    pub fn set_position(self, position: LLHRadians, quality: FixQuality) -> NmeaFix {
+        NmeaFix {
+            position: Some(position),
+            quality,
+            ..self
+        }
+    }
+
+    /// Sets the velocity of the fix, in m/s
+    pub fn set_velocity(self, velocity: NED) -> NmeaFix {
+        NmeaFix {
+            velocity: Some(velocity),
+            ..self
+        }
+    }
+
+    /// Sets the height of the geoid above the WGS84 ellipsoid, in meters
+    pub fn set_geoid_separation(self, geoid_separation: f64) -> NmeaFix {
+        NmeaFix {
+            geoid_separation,
+            ..self
+        }
+    }
+
+    /// Sets the signals used in the fix
+    ///
+    /// The constellations of the signals choose the talker ID, and the number
+    /// of distinct satellites is used as the satellite count.
+    pub fn set_used_signals(self, used_signals: &[GnssSignal]) -> NmeaFix {
+        let mut constellations: Vec<Constellation> = used_signals
+            .iter()
+            .map(|sid| sid.to_constellation())
+            .collect();
+        constellations.sort();
+        constellations.dedup();
+        let mut sats: Vec<(Constellation, u16)> = used_signals
+            .iter()
+            .map(|sid| (sid.to_constellation(), sid.sat()))
+            .collect();
+        sats.sort();
+        sats.dedup();
+        NmeaFix {
+            sats_used: sats.len().min(u8::MAX as usize) as u8,
+            used_signals: used_signals.to_vec(),
+            constellations,
+            ..self
+        }
+    }
+
+    /// Sets the dilutions of precision of the fix
+    pub fn set_dops(self, dops: &Dops) -> NmeaFix {
+        NmeaFix {
+            pdop: Some(dops.pdop()),
+            hdop: Some(dops.hdop()),
+            vdop: Some(dops.vdop()),
+            ..self
+        }
+    }
+
+    /// Sets the age of the differential corrections, in seconds, and the ID
+    /// of the station they came from
+    pub fn set_differential(self, age: f64, station_id: u16) -> NmeaFix {
+        NmeaFix {
+            differential_age: Some(age),
+            station_id: Some(station_id),
+            ..self
+        }
+    }
+
+    pub fn time(&self) -> &UtcTime {
+        &self.time
+    }
+
+    pub fn quality(&self) -> FixQuality {
+        self.quality
+    }
+
+    pub fn position(&self) -> Option<LLHRadians> {
+        self.position
+    }
+
+    pub fn velocity(&self) -> Option<NED> {
+        self.velocity
+    }
+
+    pub fn geoid_separation(&self) -> f64 {
+        self.geoid_separation
+    }
+
+    pub fn sats_used(&self) -> u8 {
+        self.sats_used
+    }
+
+    pub fn used_signals(&self) -> &[GnssSignal] {
+        &self.used_signals
+    }
+
+    /// Gets the distinct constellations of the used signals
+    pub fn constellations(&self) -> &[Constellation] {
+        &self.constellations
+    }
+
+    pub fn pdop(&self) -> Option<f64> {
+        self.pdop
+    }
+
+    pub fn hdop(&self) -> Option<f64> {
+        self.hdop
+    }
+
+    pub fn vdop(&self) -> Option<f64> {
+        self.vdop
+    }
+
+    pub fn differential_age(&self) -> Option<f64> {
+        self.differential_age
+    }
+
+    pub fn station_id(&self) -> Option<u16> {
+        self.station_id
+    }
+}
+
+/// A satellite in view, as described by the GSV sentence
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct SatelliteView {
+    sid: GnssSignal,
+    azel: AzimuthElevation,
+    cn0: Option<f64>,
+}
+
+impl SatelliteView {
+    /// Makes a satellite view, with the azimuth and elevation in radians
+    pub fn new(sid: GnssSignal, azel: AzimuthElevation) -> SatelliteView {
+        SatelliteView {
+            sid,
+            azel,
+            cn0: None,
+        }
+    }
+
+    /// Sets the carrier to noise density of the tracked signal, in dB-Hz
+    pub fn set_cn0(self, cn0: f64) -> SatelliteView {
+        SatelliteView {
+            cn0: Some(cn0),
+            ..self
+        }
+    }
+
+    pub fn sid(&self) -> GnssSignal {
+        self.sid
+    }
+
+    pub fn azel(&self) -> AzimuthElevation {
+        self.azel
+    }
+
+    pub fn cn0(&self) -> Option<f64> {
+        self.cn0
+    }
+}
+
+/// Computes the checksum of a sentence, the XOR of all the characters
+/// between the `$` and the `*`
+pub fn checksum(body: &str) -> u8 {
+    body.bytes().fold(0, |acc, b| acc ^ b)
+}
+
+/// Gets the satellite ID used for a satellite in NMEA sentences
+///
+/// SBAS satellites are numbered from 33, GLONASS satellites from 65 and QZSS
+/// satellites from 1, while the other constellations use their PRN.
+pub fn nmea_satellite_id(sid: GnssSignal) -> u16 {
+    let sat = sid.sat();
+    match sid.to_constellation() {
+        Constellation::Sbas => sat - 87,
+        Constellation::Glo => sat + 64,
+        Constellation::Qzs => sat - 192,
+        Constellation::Gps | Constellation::Gal | Constellation::Bds => sat,
+    }
+}
+
+/// Gets the NMEA 4.11 system ID of a constellation, as used in the GSA
+/// sentence
+fn system_id(constellation: Constellation) -> u8 {
+    match constellation {
+        Constellation::Gps | Constellation::Sbas => 1,
+        Constellation::Glo => 2,
+        Constellation::Gal => 3,
+        Constellation::Bds => 4,
+        Constellation::Qzs => 5,
+    }
+}
+
+/// Groups the satellite IDs of a set of signals by system ID, removing
+/// duplicates
+fn group_by_system(signals: impl Iterator<Item = GnssSignal>) -> BTreeMap<u8, Vec<u16>> {
+    let mut groups: BTreeMap<u8, Vec<u16>> = BTreeMap::new();
+    for sid in signals {
+        groups
+            .entry(system_id(sid.to_constellation()))
+            .or_default()
+            .push(nmea_satellite_id(sid));
+    }
+    for ids in groups.values_mut() {
+        ids.sort_unstable();
+        ids.dedup();
+    }
+    groups
+}
+
+/// Joins the fields of a sentence and adds the checksum
+fn make_sentence(talker: TalkerId, kind: &str, fields: &[String]) -> String {
+    let body = format!("{}{},{}", talker, kind, fields.join(","));
+    format!("${}*{:02X}", body, checksum(&body))
+}
+
+/// Formats a UTC time as `hhmmss.ss`
+///
+/// The seconds are truncated rather than rounded, so that a time is never
+/// rounded up into the next minute
+fn format_time(utc: &UtcTime) -> String {
+    let centiseconds = (utc.seconds() * 100.0).floor() as u32;
+    format!(
+        "{:02}{:02}{:02}.{:02}",
+        utc.hour(),
+        utc.minute(),
+        centiseconds / 100,
+        centiseconds % 100
+    )
+}
+
+fn format_dop(dop: Option<f64>) -> String {
+    dop.map(|dop| format!("{:.1}", dop)).unwrap_or_default()
+}
+
+fn hemisphere_field(hemisphere: char) -> String {
+    if hemisphere == ' ' {
+        String::new()
+    } else {
+        hemisphere.to_string()
+    }
+}
+
+fn unit_field(present: bool) -> String {
+    if present {
+        "M".to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// Empty latitude, longitude, altitude and geoid separation fields
+fn empty_position() -> ((String, char), (String, char), String, String) {
+    (
+        (String::new(), ' '),
+        (String::new(), ' '),
+        String::new(),
+        String::new(),
+    )
+}
+
+/// Gets the speed, in m/s, and the course, in degrees, of a velocity
+///
+/// The course is left empty when there is no horizontal motion
+fn speed_and_course(velocity: Option<NED>) -> (Option<f64>, Option<f64>) {
+    match velocity {
+        Some(v) => {
+            let speed = v.n().hypot(v.e());
+            let course = if speed > 0.0 {
+                Some(v.e().atan2(v.n()).to_degrees().rem_euclid(360.0))
+            } else {
+                None
+            };
+            (Some(speed), course)
+        }
+        None => (None, None),
+    }
+}
+
+/// Formats a positive angle as zero padded degrees followed by decimal minutes
+///
+/// The rounding is done on the total number of minutes so that rounding up
+/// to 60 minutes carries into the degrees
+fn format_degrees_minutes(angle: f64, degree_digits: usize, decimals: usize) -> String {
+    // A deserialized format can hold more decimals than the setter allows
+    let decimals = decimals.min(MAX_LAT_LON_DECIMALS);
+    let scale = 10_u64.pow(decimals as u32);
+    let total = (angle * 60.0 * scale as f64).round() as u64;
+    let degrees = total / (60 * scale);
+    let minutes = total % (60 * scale);
+    let whole_minutes = minutes / scale;
+    let fraction = minutes % scale;
+    if decimals == 0 {
+        format!(
+            "{:0width$}{:02}",
+            degrees,
+            whole_minutes,
+            width = degree_digits
+        )
+    } else {
+        format!(
+            "{:0width$}{:02}.{:0decimals$}",
+            degrees,
+            whole_minutes,
+            fraction,
+            width = degree_digits,
+            decimals = decimals
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::ECEF;
+    use crate::signal::Code;
+
+    /// Checks the framing and checksum of a sentence and splits its fields
+    fn fields(sentence: &str) -> Vec<String> {
+        assert!(sentence.starts_with('$'));
+        let (body, cs) = sentence[1..].split_once('*').unwrap();
+        assert_eq!(u8::from_str_radix(cs, 16).unwrap(), checksum(body));
+        body.split(',').map(|f| f.to_string()).collect()
+    }
+
+    fn test_fix() -> NmeaFix {
+        let sids = [
+            GnssSignal::new(5, Code::GpsL1ca).unwrap(),
+            GnssSignal::new(5, Code::GpsL2cm).unwrap(),
+            GnssSignal::new(12, Code::GpsL1ca).unwrap(),
+            GnssSignal::new(3, Code::GloL1of).unwrap(),
+            GnssSignal::new(11, Code::GalE1b).unwrap(),
+        ];
+        let receiver = ECEF::new(-2_703_115.9, -4_262_833.7, 3_885_033.5);
+        let sats = [
+            ECEF::new(-18_000_000.0, -12_000_000.0, 15_000_000.0),
+            ECEF::new(8_000_000.0, -20_000_000.0, 14_000_000.0),
+            ECEF::new(-6_000_000.0, -24_000_000.0, 6_000_000.0),
+            ECEF::new(-14_000_000.0, -4_000_000.0, 21_000_000.0),
+            ECEF::new(-22_000_000.0, -14_000_000.0, 1_000_000.0),
+        ];
+        let dops = Dops::from_geometry(&receiver, &sats).unwrap();
+        NmeaFix::new(UtcTime::from_date(2021, 3, 9, 12, 34, 56.789))
+            .set_position(
+                LLHRadians::new(
+                    37.779_804_f64.to_radians(),
+                    -122.391_751_f64.to_radians(),
+                    20.0,
+                ),
+                FixQuality::RtkFixed,
+            )
+            .set_velocity(NED::new(3.0, 4.0, -0.5))
+            .set_geoid_separation(-32.1)
+            .set_used_signals(&sids)
+            .set_dops(&dops)
+            .set_differential(1.25, 42)
+    }
+
+    #[test]
+    fn known_sentence() {
+        let format = NmeaFormat::new().set_talker_policy(TalkerPolicy::Fixed(TalkerId::GP));
+        let fix = NmeaFix::new(UtcTime::from_date(2021, 3, 9, 12, 35, 19.0)).set_position(
+            LLHRadians::new(
+                48.117_3_f64.to_radians(),
+                11.516_666_7_f64.to_radians(),
+                591.8,
+            ),
+            FixQuality::Single,
+        );
+        let fix = fix.set_geoid_separation(46.9);
+        assert_eq!(
+            format
+                .set_lat_lon_decimals(3)
+                .set_altitude_decimals(1)
+                .gga(&fix),
+            "$GPGGA,123519.00,4807.038,N,01131.000,E,1,00,,544.9,M,46.9,M,,*4A"
+        );
+    }
+
+    #[test]
+    fn fix_sentences() {
+        let format = NmeaFormat::new();
+        let fix = test_fix();
+        assert_eq!(fix.sats_used(), 4);
+
+        let gga = fields(&format.gga(&fix));
+        assert_eq!(gga[0], "GNGGA");
+        assert_eq!(gga[1], "123456.78");
+        assert_eq!(&gga[2..6], ["3746.78824", "N", "12223.50506", "W"]);
+        assert_eq!(gga[6], "4");
+        assert_eq!(gga[7], "04");
+        assert_eq!(gga[9], "52.10");
+        assert_eq!(gga[11], "-32.10");
+        assert_eq!(&gga[13..], ["1.2", "0042"]);
+
+        let rmc = fields(&format.rmc(&fix));
+        assert_eq!(rmc[0], "GNRMC");
+        assert_eq!(rmc[2], "A");
+        assert_eq!(rmc[7], "9.719");
+        assert_eq!(rmc[8], "53.13");
+        assert_eq!(rmc[9], "090321");
+        assert_eq!(rmc[12], "R");
+
+        let vtg = fields(&format.vtg(&fix));
+        assert_eq!(
+            vtg,
+            ["GNVTG", "53.13", "T", "", "M", "9.719", "N", "18.000", "K", "R"]
+        );
+
+        let zda = fields(&format.zda(&fix));
+        assert_eq!(zda, ["GNZDA", "123456.78", "09", "03", "2021", "00", "00"]);
+
+        // One GSA per constellation with the combined talker
+        let gsa: Vec<Vec<String>> = format.gsa(&fix).iter().map(|s| fields(s)).collect();
+        assert_eq!(gsa.len(), 3);
+        assert_eq!(&gsa[0][..5], ["GNGSA", "A", "3", "05", "12"]);
+        assert_eq!(gsa[0][18], "1");
+        assert_eq!(gsa[1][3], "67");
+        assert_eq!(gsa[1][18], "2");
+        assert_eq!(gsa[2][18], "3");
+        assert_eq!(gsa[0][15], format!("{:.1}", fix.pdop().unwrap()));
+
+        // A single GSA without a system ID otherwise
+        let gps_only = format.set_talker_policy(TalkerPolicy::Fixed(TalkerId::GP));
+        let gsa = gps_only.gsa(&fix);
+        assert_eq!(gsa.len(), 1);
+        assert_eq!(fields(&gsa[0]).len(), 18);
+
+        // Without a fix most fields are left empty
+        let empty = NmeaFix::new(UtcTime::from_date(2021, 3, 9, 0, 0, 59.999));
+        let gga = fields(&format.gga(&empty));
+        assert_eq!(gga[1], "000059.99");
+        assert!(gga[2..6].iter().all(|f| f.is_empty()));
+        assert_eq!(gga[6], "0");
+        let rmc = fields(&format.rmc(&empty));
+        assert_eq!((rmc[2].as_str(), rmc[12].as_str()), ("V", "N"));
+        assert_eq!(fields(&format.gsa(&empty)[0])[2], "1");
+    }
+
+    #[test]
+    fn satellites_in_view() {
+        let view = |sat, code, az: f64, el: f64| {
+            SatelliteView::new(
+                GnssSignal::new(sat, code).unwrap(),
+                AzimuthElevation::new(az.to_radians(), el.to_radians()),
+            )
+        };
+        let sats = [
+            view(5, Code::GpsL1ca, 45.0, 30.0).set_cn0(42.4),
+            view(5, Code::GpsL2cm, 45.0, 30.0).set_cn0(38.0),
+            view(2, Code::GpsL1ca, 359.7, 60.2),
+            view(131, Code::SbasL1ca, 200.0, 20.0).set_cn0(40.0),
+            view(25, Code::GpsL1ca, 90.0, 5.0).set_cn0(30.0),
+            view(29, Code::GpsL1ca, 180.0, 75.0).set_cn0(47.0),
+            view(3, Code::GloL1of, 10.0, 15.0).set_cn0(35.0),
+            view(193, Code::QzsL1ca, 170.0, 80.0).set_cn0(45.0),
+        ];
+        let gsv: Vec<Vec<String>> = NmeaFormat::new()
+            .gsv(&sats)
+            .iter()
+            .map(|s| fields(s))
+            .collect();
+        assert_eq!(gsv.len(), 4);
+
+        // GPS and SBAS share a group, split over two sentences
+        assert_eq!(&gsv[0][..4], ["GPGSV", "2", "1", "05"]);
+        assert_eq!(&gsv[0][4..8], ["02", "60", "000", ""]);
+        assert_eq!(&gsv[0][8..12], ["05", "30", "045", "42"]);
+        assert_eq!(&gsv[1][..4], ["GPGSV", "2", "2", "05"]);
+        assert_eq!(&gsv[1][4..8], ["44", "20", "200", "40"]);
+        assert_eq!(fields(&NmeaFormat::new().gsv(&sats[6..7])[0])[4], "67");
+        assert_eq!(&gsv[2][..5], ["GLGSV", "1", "1", "01", "67"]);
+        assert_eq!(&gsv[3][..5], ["GQGSV", "1", "1", "01", "01"]);
+    }
+
+    #[test]
+    fn lat_lon_formatting() {
+        let format = NmeaFormat::new();
+        assert_eq!(
+            format.format_latitude(37.779_804),
+            ("3746.78824".to_string(), 'N')
+        );
+        assert_eq!(
+            format.format_longitude(-122.391_751),
+            ("12223.50506".to_string(), 'W')
+        );
+        assert_eq!(
+            format.format_latitude(-5.5),
+            ("0530.00000".to_string(), 'S')
+        );
+
+        // Rounding up to a whole degree carries over
+        let format = format.set_lat_lon_decimals(2);
+        assert_eq!(
+            format.format_latitude(10.999_999_9),
+            ("1100.00".to_string(), 'N')
+        );
+        assert_eq!(
+            format.set_lat_lon_decimals(0).format_longitude(1.5),
+            ("00130".to_string(), 'E')
+        );
+
+        // Too many decimals would overflow the fixed point minutes
+        let format = format.set_lat_lon_decimals(40);
+        assert_eq!(format.lat_lon_decimals(), MAX_LAT_LON_DECIMALS);
+        assert_eq!(
+            format.format_longitude(-179.999_999_999_9),
+            ("17959.9999999940".to_string(), 'W')
+        );
+
+        assert_eq!(format.format_altitude(60.123), "60.12");
+        assert_eq!(format.set_altitude_decimals(0).format_altitude(-3.6), "-4");
+    }
+
+    #[test]
+    fn talker_selection() {
+        let gps = [Constellation::Gps, Constellation::Sbas];
+        let mixed = [Constellation::Gps, Constellation::Gal];
+
+        let auto = NmeaFormat::new();
+        assert_eq!(auto.talker_for(&gps), TalkerId::GP);
+        assert_eq!(auto.talker_for(&[Constellation::Glo]), TalkerId::GL);
+        assert_eq!(auto.talker_for(&mixed), TalkerId::GN);
+        assert_eq!(auto.talker_for(&[]), TalkerId::GN);
+
+        let gps_or_gn = auto.set_talker_policy(TalkerPolicy::GpsOrGn);
+        assert_eq!(
+            gps_or_gn.talker_for(&[Constellation::Gps, Constellation::Qzs]),
+            TalkerId::GP
+        );
+        assert_eq!(gps_or_gn.talker_for(&[Constellation::Gal]), TalkerId::GN);
+
+        let fixed = auto.set_talker_policy(TalkerPolicy::Fixed(TalkerId::GP));
+        assert_eq!(fixed.talker_for(&mixed), TalkerId::GP);
+        assert_eq!(TalkerId::GN.to_string(), "GN");
+    }
+}
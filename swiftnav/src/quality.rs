@@ -0,0 +1,757 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Measurement quality metrics
+//!
+//! The [`QualityAnalyzer`] goes through epochs of raw measurements and
+//! gathers the metrics commonly used to evaluate a receiver, antenna or site:
+//!  * Code multipath, MP1 and MP2 - the pseudorange minus a combination of the
+//!    carrier phases on two frequencies, which cancels the geometry and the
+//!    ionosphere and leaves the code multipath and noise plus a constant. The
+//!    constant is removed by taking the mean over each continuous arc, and the
+//!    RMS of what's left is reported.
+//!  * C/N0 statistics in bins of satellite elevation
+//!  * Cycle slip counts - a slip is detected when the lock time goes
+//!    backwards, or on satellites tracked on two frequencies when the
+//!    geometry free carrier phase changes faster than the ionosphere can, or
+//!    when the multipath combination jumps
+//!
+//! For each satellite, MP1 and MP2 are formed from the measurements on the
+//! two highest frequencies tracked, the first frequency being the higher one,
+//! e.g. L1 and L2 for GPS. The satellite states must be set on the
+//! measurements to sort the C/N0 by elevation.
+//!
+//! # References
+//!   * Estey L. H. and Meertens C. M., "TEQC: The Multi-Purpose Toolkit for
+//!     GPS/GLONASS Data", GPS Solutions, 1999
+
+use crate::coords::ECEF;
+use crate::history::TimeSeries;
+use crate::navmeas::NavigationMeasurement;
+use crate::signal::{Constellation, GnssSignal};
+use crate::time::GpsTime;
+use crate::SPEED_OF_LIGHT;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Settings of the [`QualityAnalyzer`]
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QualitySettings {
+    elevation_bin_width: f64,
+    ionosphere_slip_rate: f64,
+    multipath_slip: f64,
+    max_gap: Duration,
+}
+
+impl QualitySettings {
+    /// Creates a default set of quality settings
+    ///
+    /// Note: The default settings consist of
+    ///  * 10° elevation bins
+    ///  * A slip when the geometry free phase changes by more than 4 m/min
+    ///  * A slip when MP1 or MP2 changes by more than 10 m between epochs
+    ///  * A new multipath arc after a 10 second gap in a satellite's
+    ///    measurements
+    pub fn new() -> QualitySettings {
+        QualitySettings {
+            elevation_bin_width: 10.0,
+            ionosphere_slip_rate: 4.0,
+            multipath_slip: 10.0,
+            max_gap: Duration::from_secs(10),
+        }
+    }
+
+    /// Sets the width of the elevation bins, in degrees
+    pub fn set_elevation_bin_width(self, elevation_bin_width: f64) -> QualitySettings {
+        QualitySettings {
+            elevation_bin_width,
+            ..self
+        }
+    }
+
+    /// Sets the rate of change of the geometry free carrier phase above which
+    /// a slip is detected, in meters per minute
+    pub fn set_ionosphere_slip_rate(self, ionosphere_slip_rate: f64) -> QualitySettings {
+        QualitySettings {
+            ionosphere_slip_rate,
+            ..self
+        }
+    }
+
+    /// Sets the change of MP1 or MP2 between epochs above which a slip is
+    /// detected, in meters
+    pub fn set_multipath_slip(self, multipath_slip: f64) -> QualitySettings {
+        QualitySettings {
+            multipath_slip,
+            ..self
+        }
+    }
+
+    /// Sets the longest gap in a satellite's measurements within a multipath
+    /// arc
+    pub fn set_max_gap(self, max_gap: Duration) -> QualitySettings {
+        QualitySettings { max_gap, ..self }
+    }
+
+    pub fn elevation_bin_width(&self) -> f64 {
+        self.elevation_bin_width
+    }
+
+    pub fn ionosphere_slip_rate(&self) -> f64 {
+        self.ionosphere_slip_rate
+    }
+
+    pub fn multipath_slip(&self) -> f64 {
+        self.multipath_slip
+    }
+
+    pub fn max_gap(&self) -> Duration {
+        self.max_gap
+    }
+}
+
+impl Default for QualitySettings {
+    fn default() -> QualitySettings {
+        QualitySettings::new()
+    }
+}
+
+/// Running statistics of a value
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+struct Statistics {
+    count: usize,
+    sum: f64,
+    sum_sq: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Statistics {
+    fn new() -> Statistics {
+        Statistics {
+            count: 0,
+            sum: 0.0,
+            sum_sq: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.sum_sq += value * value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    fn mean(&self) -> f64 {
+        self.sum / self.count as f64
+    }
+
+    /// Sum of the squared differences from the mean
+    fn centered_sum_sq(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        (self.sum_sq - self.sum * self.sum / self.count as f64).max(0.0)
+    }
+}
+
+/// Sums of the squared multipath, after removing the mean of each arc
+#[derive(Debug, Copy, Clone, Default, PartialOrd, PartialEq)]
+struct MultipathSums {
+    mp1: (f64, usize),
+    mp2: (f64, usize),
+}
+
+impl MultipathSums {
+    fn add_arc(&mut self, arc: &MultipathArc) {
+        // A single epoch says nothing about the multipath once the mean is
+        // removed
+        if arc.mp1.count > 1 {
+            self.mp1.0 += arc.mp1.centered_sum_sq();
+            self.mp1.1 += arc.mp1.count;
+        }
+        if arc.mp2.count > 1 {
+            self.mp2.0 += arc.mp2.centered_sum_sq();
+            self.mp2.1 += arc.mp2.count;
+        }
+    }
+
+    fn add(&mut self, other: &MultipathSums) {
+        self.mp1.0 += other.mp1.0;
+        self.mp1.1 += other.mp1.1;
+        self.mp2.0 += other.mp2.0;
+        self.mp2.1 += other.mp2.1;
+    }
+
+    fn rms(sum: (f64, usize)) -> Option<f64> {
+        if sum.1 == 0 {
+            None
+        } else {
+            Some((sum.0 / sum.1 as f64).sqrt())
+        }
+    }
+}
+
+/// A continuous arc of multipath values of a satellite
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+struct MultipathArc {
+    signals: (GnssSignal, GnssSignal),
+    /// MP1, MP2 and the geometry free phase of the latest epoch
+    latest: TimeSeries<(f64, f64, f64), 1>,
+    mp1: Statistics,
+    mp2: Statistics,
+}
+
+/// Tracking state of a satellite
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+struct SatelliteState {
+    epochs: usize,
+    slips: usize,
+    lock_times: BTreeMap<GnssSignal, Duration>,
+    arc: Option<MultipathArc>,
+    multipath: MultipathSums,
+}
+
+impl SatelliteState {
+    fn new() -> SatelliteState {
+        SatelliteState {
+            epochs: 0,
+            slips: 0,
+            lock_times: BTreeMap::new(),
+            arc: None,
+            multipath: MultipathSums::default(),
+        }
+    }
+
+    /// Gets the multipath sums including the current arc
+    fn multipath(&self) -> MultipathSums {
+        let mut sums = self.multipath;
+        if let Some(arc) = &self.arc {
+            sums.add_arc(arc);
+        }
+        sums
+    }
+
+    fn end_arc(&mut self) {
+        if let Some(arc) = self.arc.take() {
+            self.multipath.add_arc(&arc);
+        }
+    }
+}
+
+/// The quality metrics of a single satellite
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct SatelliteQuality {
+    constellation: Constellation,
+    sat: u16,
+    epochs: usize,
+    slips: usize,
+    mp1_rms: Option<f64>,
+    mp2_rms: Option<f64>,
+}
+
+impl SatelliteQuality {
+    pub fn constellation(&self) -> Constellation {
+        self.constellation
+    }
+
+    pub fn sat(&self) -> u16 {
+        self.sat
+    }
+
+    /// Gets the number of epochs the satellite was seen in
+    pub fn epochs(&self) -> usize {
+        self.epochs
+    }
+
+    /// Gets the number of cycle slips detected
+    pub fn slips(&self) -> usize {
+        self.slips
+    }
+
+    /// Gets the RMS of the MP1 multipath, in meters
+    ///
+    /// Returns `None` if the satellite wasn't tracked on two frequencies for
+    /// at least two consecutive epochs
+    pub fn mp1_rms(&self) -> Option<f64> {
+        self.mp1_rms
+    }
+
+    /// Gets the RMS of the MP2 multipath, in meters
+    pub fn mp2_rms(&self) -> Option<f64> {
+        self.mp2_rms
+    }
+}
+
+/// The C/N0 statistics of an elevation bin
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct Cn0Bin {
+    min_elevation: f64,
+    max_elevation: f64,
+    count: usize,
+    mean: f64,
+    std_dev: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Cn0Bin {
+    /// Gets the lowest elevation of the bin, in degrees
+    pub fn min_elevation(&self) -> f64 {
+        self.min_elevation
+    }
+
+    /// Gets the highest elevation of the bin, in degrees
+    pub fn max_elevation(&self) -> f64 {
+        self.max_elevation
+    }
+
+    /// Gets the number of C/N0 values in the bin
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Gets the mean C/N0, in dB-Hz
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Gets the standard deviation of the C/N0, in dB-Hz
+    pub fn std_dev(&self) -> f64 {
+        self.std_dev
+    }
+
+    /// Gets the lowest C/N0, in dB-Hz
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    /// Gets the highest C/N0, in dB-Hz
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+}
+
+/// The quality metrics of all of the measurements processed
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub struct QualityReport {
+    epochs: usize,
+    satellites: Vec<SatelliteQuality>,
+    cn0_bins: Vec<Cn0Bin>,
+    mp1_rms: Option<f64>,
+    mp2_rms: Option<f64>,
+}
+
+impl QualityReport {
+    /// Gets the number of epochs processed
+    pub fn epochs(&self) -> usize {
+        self.epochs
+    }
+
+    /// Gets the metrics of each satellite, in order of constellation and
+    /// satellite number
+    pub fn satellites(&self) -> &[SatelliteQuality] {
+        &self.satellites
+    }
+
+    /// Gets the metrics of a satellite, if it was seen
+    pub fn satellite(&self, constellation: Constellation, sat: u16) -> Option<&SatelliteQuality> {
+        self.satellites
+            .iter()
+            .find(|quality| quality.constellation == constellation && quality.sat == sat)
+    }
+
+    /// Gets the C/N0 statistics of the elevation bins with at least one value,
+    /// from the lowest elevation up
+    pub fn cn0_bins(&self) -> &[Cn0Bin] {
+        &self.cn0_bins
+    }
+
+    /// Gets the total number of cycle slips detected
+    pub fn slips(&self) -> usize {
+        self.satellites.iter().map(|quality| quality.slips).sum()
+    }
+
+    /// Gets the RMS of the MP1 multipath of all satellites, in meters
+    pub fn mp1_rms(&self) -> Option<f64> {
+        self.mp1_rms
+    }
+
+    /// Gets the RMS of the MP2 multipath of all satellites, in meters
+    pub fn mp2_rms(&self) -> Option<f64> {
+        self.mp2_rms
+    }
+}
+
+/// Accumulates quality metrics over epochs of measurements
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub struct QualityAnalyzer {
+    settings: QualitySettings,
+    epochs: usize,
+    satellites: BTreeMap<(Constellation, u16), SatelliteState>,
+    cn0_bins: BTreeMap<usize, Statistics>,
+}
+
+impl QualityAnalyzer {
+    pub fn new(settings: QualitySettings) -> QualityAnalyzer {
+        QualityAnalyzer {
+            settings,
+            epochs: 0,
+            satellites: BTreeMap::new(),
+            cn0_bins: BTreeMap::new(),
+        }
+    }
+
+    pub fn settings(&self) -> &QualitySettings {
+        &self.settings
+    }
+
+    /// Adds an epoch of measurements made by a receiver at `receiver`
+    ///
+    /// The receiver position only needs to be approximate, it is used to find
+    /// the elevations of the satellites.
+    pub fn process(&mut self, t: GpsTime, receiver: &ECEF, measurements: &[NavigationMeasurement]) {
+        self.epochs += 1;
+
+        let mut by_satellite: BTreeMap<(Constellation, u16), Vec<&NavigationMeasurement>> =
+            BTreeMap::new();
+        for nm in measurements {
+            let sid = nm.sid();
+            by_satellite
+                .entry((sid.to_constellation(), sid.sat()))
+                .or_default()
+                .push(nm);
+
+            if let Some(cn0) = nm.cn0() {
+                let satellite = nm.satellite_position();
+                if satellite != ECEF::default() {
+                    let elevation = receiver.azel_of(&satellite).el.to_degrees();
+                    if elevation >= 0.0 {
+                        let bin = (elevation / self.settings.elevation_bin_width) as usize;
+                        self.cn0_bins
+                            .entry(bin)
+                            .or_insert_with(Statistics::new)
+                            .add(cn0);
+                    }
+                }
+            }
+        }
+
+        for (key, measurements) in by_satellite {
+            let settings = self.settings;
+            let state = self
+                .satellites
+                .entry(key)
+                .or_insert_with(SatelliteState::new);
+            state.epochs += 1;
+
+            let mut slipped = false;
+            for nm in &measurements {
+                if nm.carrier_phase().is_none() {
+                    continue;
+                }
+                let previous = state.lock_times.insert(nm.sid(), nm.lock_time());
+                if matches!(previous, Some(lock_time) if nm.lock_time() < lock_time) {
+                    slipped = true;
+                }
+            }
+
+            let pair = frequency_pair(&measurements);
+            let values = pair.and_then(|(first, second)| multipath(first, second));
+            match (pair, values) {
+                (Some((first, second)), Some(values)) => {
+                    let signals = (first.sid(), second.sid());
+                    let (mp1, mp2, gf) = values;
+                    let continues = match &state.arc {
+                        Some(arc) if arc.signals == signals => match arc.latest.latest() {
+                            Some((time, (last_mp1, last_mp2, last_gf))) => {
+                                let dt = t.diff(&time);
+                                if dt <= 0.0 || dt > settings.max_gap.as_secs_f64() {
+                                    false
+                                } else {
+                                    if (gf - last_gf).abs() / dt * 60.0
+                                        > settings.ionosphere_slip_rate
+                                        || (mp1 - last_mp1).abs() > settings.multipath_slip
+                                        || (mp2 - last_mp2).abs() > settings.multipath_slip
+                                    {
+                                        slipped = true;
+                                    }
+                                    !slipped
+                                }
+                            }
+                            None => false,
+                        },
+                        _ => false,
+                    };
+                    if !continues {
+                        state.end_arc();
+                    }
+                    let arc = state.arc.get_or_insert(MultipathArc {
+                        signals,
+                        latest: TimeSeries::new(),
+                        mp1: Statistics::new(),
+                        mp2: Statistics::new(),
+                    });
+                    // A continued arc is always older than `t`, and a new one
+                    // is empty, so the value is never out of order
+                    let _ = arc.latest.push(t, values);
+                    arc.mp1.add(mp1);
+                    arc.mp2.add(mp2);
+                }
+                _ => state.end_arc(),
+            }
+
+            if slipped {
+                state.slips += 1;
+            }
+        }
+    }
+
+    /// Makes a report of the metrics of all epochs processed so far
+    pub fn report(&self) -> QualityReport {
+        let mut total = MultipathSums::default();
+        let satellites = self
+            .satellites
+            .iter()
+            .map(|((constellation, sat), state)| {
+                let multipath = state.multipath();
+                total.add(&multipath);
+                SatelliteQuality {
+                    constellation: *constellation,
+                    sat: *sat,
+                    epochs: state.epochs,
+                    slips: state.slips,
+                    mp1_rms: MultipathSums::rms(multipath.mp1),
+                    mp2_rms: MultipathSums::rms(multipath.mp2),
+                }
+            })
+            .collect();
+
+        let width = self.settings.elevation_bin_width;
+        let cn0_bins = self
+            .cn0_bins
+            .iter()
+            .map(|(bin, stats)| Cn0Bin {
+                min_elevation: *bin as f64 * width,
+                max_elevation: ((*bin + 1) as f64 * width).min(90.0),
+                count: stats.count,
+                mean: stats.mean(),
+                std_dev: (stats.centered_sum_sq() / stats.count as f64).sqrt(),
+                min: stats.min,
+                max: stats.max,
+            })
+            .collect();
+
+        QualityReport {
+            epochs: self.epochs,
+            satellites,
+            cn0_bins,
+            mp1_rms: MultipathSums::rms(total.mp1),
+            mp2_rms: MultipathSums::rms(total.mp2),
+        }
+    }
+
+    /// Discards all of the metrics gathered so far
+    pub fn reset(&mut self) {
+        self.epochs = 0;
+        self.satellites.clear();
+        self.cn0_bins.clear();
+    }
+}
+
+impl Default for QualityAnalyzer {
+    fn default() -> QualityAnalyzer {
+        QualityAnalyzer::new(QualitySettings::new())
+    }
+}
+
+/// Picks the measurements on the two highest frequencies with a pseudorange
+/// and carrier phase, highest first
+fn frequency_pair<'a>(
+    measurements: &[&'a NavigationMeasurement],
+) -> Option<(&'a NavigationMeasurement, &'a NavigationMeasurement)> {
+    let mut usable: Vec<&NavigationMeasurement> = measurements
+        .iter()
+        .copied()
+        .filter(|nm| nm.pseudorange().is_some() && nm.carrier_phase().is_some())
+        .collect();
+    usable.sort_by(|a, b| {
+        b.sid()
+            .carrier_frequency()
+            .partial_cmp(&a.sid().carrier_frequency())
+            .unwrap()
+            .then(a.sid().cmp(&b.sid()))
+    });
+    let first = *usable.first()?;
+    let f1 = first.sid().carrier_frequency();
+    let second = usable
+        .into_iter()
+        .find(|nm| f1 - nm.sid().carrier_frequency() > 1.0)?;
+    Some((first, second))
+}
+
+/// Computes MP1, MP2 and the geometry free carrier phase of a pair of
+/// measurements, in meters
+fn multipath(
+    first: &NavigationMeasurement,
+    second: &NavigationMeasurement,
+) -> Option<(f64, f64, f64)> {
+    let (f1, f2) = (
+        first.sid().carrier_frequency(),
+        second.sid().carrier_frequency(),
+    );
+    let p1 = first.pseudorange()?;
+    let p2 = second.pseudorange()?;
+    let l1 = first.carrier_phase()? * SPEED_OF_LIGHT / f1;
+    let l2 = second.carrier_phase()? * SPEED_OF_LIGHT / f2;
+
+    let alpha = (f1 / f2).powi(2);
+    let mp1 = p1 - (1.0 + 2.0 / (alpha - 1.0)) * l1 + 2.0 / (alpha - 1.0) * l2;
+    let mp2 = p2 - 2.0 * alpha / (alpha - 1.0) * l1 + (2.0 * alpha / (alpha - 1.0) - 1.0) * l2;
+    Some((mp1, mp2, l1 - l2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::{LLHDegrees, NED};
+    use crate::ephemeris::SatelliteState as EphemerisState;
+    use crate::signal::Code;
+    use float_eq::assert_float_eq;
+
+    fn satellite_at(receiver: &ECEF, az: f64, el: f64) -> EphemerisState {
+        let (az, el) = (az.to_radians(), el.to_radians());
+        let direction =
+            NED::new(el.cos() * az.cos(), el.cos() * az.sin(), -el.sin()).ecef_vector_at(receiver);
+        EphemerisState {
+            pos: *receiver + 22_000_000.0 * direction,
+            vel: ECEF::default(),
+            acc: ECEF::default(),
+            clock_err: 0.0,
+            clock_rate_err: 0.0,
+            iodc: 0,
+            iode: 0,
+        }
+    }
+
+    /// Measurements of a satellite with a range `range`, an L1 ionospheric
+    /// delay `iono` and code multipath `m1` and `m2`
+    fn measurements(
+        state: &EphemerisState,
+        sat: u16,
+        range: f64,
+        iono: f64,
+        (m1, m2): (f64, f64),
+        lock_time: u64,
+    ) -> Vec<NavigationMeasurement> {
+        let l1 = GnssSignal::new(sat, Code::GpsL1ca).unwrap();
+        let l2 = GnssSignal::new(sat, Code::GpsL2cm).unwrap();
+        let alpha = (l1.carrier_frequency() / l2.carrier_frequency()).powi(2);
+        [(l1, iono, m1, 12.0), (l2, alpha * iono, m2, -7.0)]
+            .iter()
+            .map(|(sid, delay, m, ambiguity)| {
+                let wavelength = SPEED_OF_LIGHT / sid.carrier_frequency();
+                let mut nm = NavigationMeasurement::new();
+                nm.set_sid(*sid);
+                nm.set_pseudorange(range + delay + m);
+                nm.set_carrier_phase((range - delay) / wavelength + ambiguity);
+                nm.set_lock_time(Duration::from_secs(lock_time));
+                nm.set_cn0(if sid.code() == Code::GpsL1ca {
+                    45.0
+                } else {
+                    40.0
+                });
+                nm.set_satellite_state(state);
+                nm
+            })
+            .collect()
+    }
+
+    #[test]
+    fn multipath_and_slips() {
+        let receiver = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let high = satellite_at(&receiver, 45.0, 65.0);
+        let low = satellite_at(&receiver, 200.0, 12.0);
+        let t0 = GpsTime::new(2200, 100_000.0).unwrap();
+
+        let mut analyzer = QualityAnalyzer::default();
+        for i in 0..20u64 {
+            let t = t0 + Duration::from_secs(i);
+            let sign = if i % 2 == 0 { 1.0 } else { -1.0 };
+            let range = 2.2e7 + 300.0 * i as f64;
+            let iono = 3.0 + 0.01 * i as f64;
+            let mut nms = measurements(&high, 3, range, iono, (0.3 * sign, 0.5 * sign), i);
+            if i >= 10 {
+                // A slip of a thousand cycles on L1 starts a new arc
+                let phase = nms[0].carrier_phase().unwrap();
+                nms[0].set_carrier_phase(phase + 1000.0);
+            }
+            // The second satellite loses lock half way through
+            let lock_time = if i < 15 { 100 + i } else { i - 15 };
+            let mut low_nms = measurements(&low, 9, range, iono, (0.0, 0.0), lock_time);
+            nms.append(&mut low_nms);
+            analyzer.process(t, &receiver, &nms);
+        }
+
+        let report = analyzer.report();
+        assert_eq!(report.epochs(), 20);
+        assert_eq!(report.satellites().len(), 2);
+        assert_eq!(report.slips(), 2);
+
+        let high = report.satellite(Constellation::Gps, 3).unwrap();
+        assert_eq!(high.epochs(), 20);
+        assert_eq!(high.slips(), 1);
+        assert_float_eq!(high.mp1_rms().unwrap(), 0.3, abs <= 1e-6);
+        assert_float_eq!(high.mp2_rms().unwrap(), 0.5, abs <= 1e-6);
+
+        let low = report.satellite(Constellation::Gps, 9).unwrap();
+        assert_eq!(low.slips(), 1);
+        assert_float_eq!(low.mp1_rms().unwrap(), 0.0, abs <= 1e-6);
+        assert_float_eq!(report.mp1_rms().unwrap(), 0.3 / 2f64.sqrt(), abs <= 1e-6);
+        assert_eq!(report.satellite(Constellation::Gal, 3), None);
+
+        let bins = report.cn0_bins();
+        assert_eq!(bins.len(), 2);
+        assert_float_eq!(bins[0].min_elevation(), 10.0, abs <= 1e-9);
+        assert_float_eq!(bins[1].max_elevation(), 70.0, abs <= 1e-9);
+        assert_eq!(bins[1].count(), 40);
+        assert_float_eq!(bins[1].mean(), 42.5, abs <= 1e-9);
+        assert_float_eq!(bins[1].std_dev(), 2.5, abs <= 1e-9);
+        assert_float_eq!(bins[1].min(), 40.0, abs <= 1e-9);
+        assert_float_eq!(bins[1].max(), 45.0, abs <= 1e-9);
+
+        analyzer.reset();
+        assert_eq!(analyzer.report().epochs(), 0);
+        assert!(analyzer.report().cn0_bins().is_empty());
+    }
+
+    #[test]
+    fn single_frequency() {
+        let receiver = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let state = satellite_at(&receiver, 0.0, 45.0);
+        let t0 = GpsTime::new(2200, 100_000.0).unwrap();
+
+        let mut analyzer = QualityAnalyzer::default();
+        for i in 0..5u64 {
+            let nms = measurements(&state, 5, 2.2e7, 3.0, (0.0, 0.0), 10);
+            analyzer.process(t0 + Duration::from_secs(i), &receiver, &nms[..1]);
+        }
+        let report = analyzer.report();
+        let quality = report.satellite(Constellation::Gps, 5).unwrap();
+        assert_eq!(quality.epochs(), 5);
+        assert_eq!(quality.slips(), 0);
+        assert_eq!(quality.mp1_rms(), None);
+        assert_eq!(report.mp2_rms(), None);
+        assert_eq!(report.cn0_bins()[0].count(), 5);
+    }
+}
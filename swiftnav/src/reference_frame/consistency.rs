@@ -0,0 +1,327 @@
+// Copyright (c) 2024 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Consistency checks for sets of transformation parameters
+//!
+//! Two checks are performed on a set of transformations, using a handful of
+//! test points spread around the globe:
+//!  * Closure - transforming a point from A to B and back to A should return
+//!    the original point
+//!  * Path agreement - when frames A and B are connected both directly and
+//!    through other frames, both routes should give the same result
+//!
+//! Large errors usually point to a typo in the parameters, a sign convention
+//! mix up, or parameters given in the wrong units.
+
+use super::{params, ReferenceFrame, Transformation, TransformationGraph};
+use crate::coords::{Coordinate, LLHDegrees};
+use crate::time::{GpsTime, UtcTime};
+
+/// Tolerances and test conditions for the consistency checks
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub struct ConsistencySettings {
+    closure_tolerance: f64,
+    path_tolerance: f64,
+    epoch: GpsTime,
+}
+
+impl ConsistencySettings {
+    /// Makes the default settings
+    ///
+    /// Note: The default settings consist of
+    ///  * A closure tolerance of 0.1 mm
+    ///  * A path agreement tolerance of 5 cm
+    ///  * Testing at the 2020.0 epoch
+    pub fn new() -> ConsistencySettings {
+        ConsistencySettings {
+            closure_tolerance: 1e-4,
+            path_tolerance: 0.05,
+            epoch: UtcTime::from_date(2020, 1, 1, 0, 0, 0.).to_gps_hardcoded(),
+        }
+    }
+
+    /// Sets the largest acceptable round trip error, in meters
+    pub fn set_closure_tolerance(self, closure_tolerance: f64) -> ConsistencySettings {
+        ConsistencySettings {
+            closure_tolerance,
+            ..self
+        }
+    }
+
+    /// Sets the largest acceptable difference between paths, in meters
+    pub fn set_path_tolerance(self, path_tolerance: f64) -> ConsistencySettings {
+        ConsistencySettings {
+            path_tolerance,
+            ..self
+        }
+    }
+
+    /// Sets the epoch of the test points
+    pub fn set_epoch(self, epoch: GpsTime) -> ConsistencySettings {
+        ConsistencySettings { epoch, ..self }
+    }
+
+    pub fn closure_tolerance(&self) -> f64 {
+        self.closure_tolerance
+    }
+
+    pub fn path_tolerance(&self) -> f64 {
+        self.path_tolerance
+    }
+
+    pub fn epoch(&self) -> GpsTime {
+        self.epoch
+    }
+}
+
+impl Default for ConsistencySettings {
+    fn default() -> ConsistencySettings {
+        ConsistencySettings::new()
+    }
+}
+
+/// Result of a round trip through a single transformation
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub struct ClosureCheck {
+    from: ReferenceFrame,
+    to: ReferenceFrame,
+    error: f64,
+}
+
+impl ClosureCheck {
+    pub fn from(&self) -> ReferenceFrame {
+        self.from
+    }
+
+    pub fn to(&self) -> ReferenceFrame {
+        self.to
+    }
+
+    /// Gets the largest round trip error over the test points, in meters
+    pub fn error(&self) -> f64 {
+        self.error
+    }
+}
+
+/// Result of comparing a direct transformation with an indirect path between
+/// the same frames
+#[derive(Debug, PartialEq, PartialOrd, Clone)]
+pub struct PathCheck {
+    direct: [ReferenceFrame; 2],
+    alternative: Vec<ReferenceFrame>,
+    difference: f64,
+}
+
+impl PathCheck {
+    /// Gets the frames connected by the direct transformation
+    pub fn direct(&self) -> [ReferenceFrame; 2] {
+        self.direct
+    }
+
+    /// Gets the frames along the alternative path
+    pub fn alternative(&self) -> &[ReferenceFrame] {
+        &self.alternative
+    }
+
+    /// Gets the largest difference between the paths over the test points, in
+    /// meters
+    pub fn difference(&self) -> f64 {
+        self.difference
+    }
+}
+
+/// Outcome of the consistency checks
+///
+/// The checks are sorted from the largest error to the smallest, so the worst
+/// offenders come first.
+#[derive(Debug, PartialEq, PartialOrd, Clone)]
+pub struct ConsistencyReport {
+    settings: ConsistencySettings,
+    closures: Vec<ClosureCheck>,
+    paths: Vec<PathCheck>,
+}
+
+impl ConsistencyReport {
+    pub fn settings(&self) -> &ConsistencySettings {
+        &self.settings
+    }
+
+    /// Gets the closure checks of all transformations
+    pub fn closures(&self) -> &[ClosureCheck] {
+        &self.closures
+    }
+
+    /// Gets the path checks of all transformations with an alternative path
+    pub fn paths(&self) -> &[PathCheck] {
+        &self.paths
+    }
+
+    /// Gets the closure checks which exceed the tolerance
+    pub fn closure_failures(&self) -> &[ClosureCheck] {
+        let count = self
+            .closures
+            .iter()
+            .take_while(|c| c.error > self.settings.closure_tolerance)
+            .count();
+        &self.closures[..count]
+    }
+
+    /// Gets the path checks which exceed the tolerance
+    pub fn path_failures(&self) -> &[PathCheck] {
+        let count = self
+            .paths
+            .iter()
+            .take_while(|p| p.difference > self.settings.path_tolerance)
+            .count();
+        &self.paths[..count]
+    }
+
+    /// Checks if all of the checks are within tolerance
+    pub fn is_consistent(&self) -> bool {
+        self.closure_failures().is_empty() && self.path_failures().is_empty()
+    }
+}
+
+/// Checks the consistency of the built in transformations
+pub fn check_consistency(settings: &ConsistencySettings) -> ConsistencyReport {
+    check_transformations(&params::TRANSFORMATIONS, settings)
+}
+
+/// Checks the consistency of a set of transformations
+pub fn check_transformations(
+    transformations: &[Transformation],
+    settings: &ConsistencySettings,
+) -> ConsistencyReport {
+    let closures = transformations
+        .iter()
+        .map(|t| {
+            let error = test_points(t.from, &settings.epoch)
+                .map(|point| {
+                    let round_trip = t.invert().transform(&t.transform(&point));
+                    (point.position() - round_trip.position()).norm()
+                })
+                .fold(0.0, f64::max);
+            ClosureCheck {
+                from: t.from,
+                to: t.to,
+                error,
+            }
+        })
+        .collect();
+
+    let graph = TransformationGraph::from_transformations(transformations);
+    let mut paths = Vec::new();
+    for t in transformations {
+        let direct = [t.from, t.to];
+        let is_direct_edge = |a: ReferenceFrame, b: ReferenceFrame| {
+            (a == t.from && b == t.to) || (a == t.to && b == t.from)
+        };
+        let alternative = match graph.search(t.from, t.to, |a, b| !is_direct_edge(a, b)) {
+            Some(alternative) => alternative,
+            None => continue,
+        };
+        let difference = test_points(t.from, &settings.epoch)
+            .filter_map(|point| {
+                let via = apply_path(transformations, &alternative, &point)?;
+                Some((t.transform(&point).position() - via.position()).norm())
+            })
+            .fold(0.0, f64::max);
+        paths.push(PathCheck {
+            direct,
+            alternative,
+            difference,
+        });
+    }
+
+    let mut report = ConsistencyReport {
+        settings: *settings,
+        closures,
+        paths,
+    };
+    report.closures.sort_by(|a, b| b.error.total_cmp(&a.error));
+    report
+        .paths
+        .sort_by(|a, b| b.difference.total_cmp(&a.difference));
+    report
+}
+
+/// Points on the surface of the Earth spread over both hemispheres
+fn test_points(frame: ReferenceFrame, epoch: &GpsTime) -> impl Iterator<Item = Coordinate> {
+    const LOCATIONS: [(f64, f64); 6] = [
+        (0.0, 0.0),
+        (0.0, 90.0),
+        (45.0, -120.0),
+        (-35.0, 150.0),
+        (60.0, 10.0),
+        (-80.0, -60.0),
+    ];
+    let epoch = *epoch;
+    LOCATIONS.iter().map(move |(lat, lon)| {
+        Coordinate::without_velocity(frame, LLHDegrees::new(*lat, *lon, 0.0).to_ecef(), epoch)
+    })
+}
+
+fn apply_path(
+    transformations: &[Transformation],
+    path: &[ReferenceFrame],
+    coord: &Coordinate,
+) -> Option<Coordinate> {
+    let mut coord = *coord;
+    for step in path.windows(2) {
+        let transformation = transformations.iter().find_map(|t| {
+            if t.from == step[0] && t.to == step[1] {
+                Some(*t)
+            } else if t.from == step[1] && t.to == step[0] {
+                Some(t.invert())
+            } else {
+                None
+            }
+        })?;
+        coord = transformation.transform(&coord);
+    }
+    Some(coord)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_transformations_are_consistent() {
+        let report = check_consistency(&ConsistencySettings::new());
+        assert_eq!(report.closures().len(), params::TRANSFORMATIONS.len());
+        assert!(!report.paths().is_empty());
+        assert!(report.is_consistent(), "{:?}", report);
+    }
+
+    #[test]
+    fn reports_worst_offenders() {
+        let mut transformations = params::TRANSFORMATIONS.to_vec();
+        // Introduce a 1 m error in the translation of one transformation
+        let bad = transformations
+            .iter_mut()
+            .find(|t| t.from == ReferenceFrame::ITRF2014 && t.to == ReferenceFrame::NAD83_CSRS)
+            .unwrap();
+        bad.params.tx += 1000.0;
+
+        let report = check_transformations(&transformations, &ConsistencySettings::new());
+        assert!(!report.is_consistent());
+        assert!(report.closure_failures().is_empty());
+        let worst = &report.path_failures()[0];
+        let uses_bad = |a: ReferenceFrame, b: ReferenceFrame| {
+            (a == ReferenceFrame::ITRF2014 && b == ReferenceFrame::NAD83_CSRS)
+                || (a == ReferenceFrame::NAD83_CSRS && b == ReferenceFrame::ITRF2014)
+        };
+        assert!(
+            uses_bad(worst.direct()[0], worst.direct()[1])
+                || worst.alternative().windows(2).any(|w| uses_bad(w[0], w[1]))
+        );
+        assert!(worst.difference() > 0.9);
+    }
+}
@@ -0,0 +1,609 @@
+// Copyright (c) 2024 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Grid based datum shifts
+//!
+//! A Helmert transformation only captures the large scale differences between
+//! two reference frames. Regional frames, and older frames in particular, also
+//! contain local distortions from the way they were realized, which national
+//! agencies publish as grids of latitude and longitude shifts. These are
+//! applied on top of the Helmert transformation, see
+//! [`Transformation::transform_with_grid`](super::Transformation::transform_with_grid).
+//!
+//! Grids in the NTv2 format (`.gsb` files) are supported. A file consists of
+//! one or more sub-grids, which may be nested to give a finer resolution in
+//! some areas. The shifts are bilinearly interpolated in the finest sub-grid
+//! containing the point.
+//!
+//! # References
+//!   * Junkins D. R., Farley S. A., "NTv2 Developer's Guide", Geodetic Survey
+//!     Division, Natural Resources Canada, 1995
+
+use crate::coords::LLHRadians;
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+
+/// Length of a header record
+const RECORD_LENGTH: usize = 16;
+/// Number of records in the overview header
+const OVERVIEW_RECORDS: usize = 11;
+/// Number of records in each sub-grid header
+const SUBGRID_RECORDS: usize = 11;
+/// Length of each grid node
+const NODE_LENGTH: usize = 16;
+
+const ARC_SECONDS_TO_RADIANS: f64 = std::f64::consts::PI / (180.0 * 3600.0);
+
+/// Errors which can occur when loading or applying a grid
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq, Eq)]
+pub enum GridError {
+    /// The file ended before all of the grids were read
+    Truncated,
+    /// A header is missing or has an invalid value
+    InvalidHeader,
+    /// The shifts are given in units other than seconds, minutes or degrees
+    UnsupportedUnits,
+    /// The position is outside of the area covered by the grid
+    OutsideGrid,
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GridError::Truncated => write!(f, "Truncated grid file"),
+            GridError::InvalidHeader => write!(f, "Invalid grid file header"),
+            GridError::UnsupportedUnits => write!(f, "Unsupported grid units"),
+            GridError::OutsideGrid => write!(f, "Position outside of the grid"),
+        }
+    }
+}
+
+impl Error for GridError {}
+
+/// A single grid of shifts
+///
+/// As in the file, the bounds are in arc seconds with the longitudes positive
+/// to the west, and the nodes go from the south east corner westwards then
+/// northwards.
+#[derive(Debug, Clone, PartialEq)]
+struct SubGrid {
+    name: String,
+    parent: String,
+    south: f64,
+    north: f64,
+    east: f64,
+    west: f64,
+    lat_inc: f64,
+    lon_inc: f64,
+    rows: usize,
+    columns: usize,
+    /// Latitude and longitude shifts of each node, in arc seconds
+    shifts: Vec<[f64; 2]>,
+}
+
+impl SubGrid {
+    fn contains(&self, lat: f64, lon: f64) -> bool {
+        lat >= self.south && lat <= self.north && lon >= self.east && lon <= self.west
+    }
+
+    /// Bilinearly interpolates the shifts at a point within the grid
+    fn interpolate(&self, lat: f64, lon: f64) -> [f64; 2] {
+        let row = (lat - self.south) / self.lat_inc;
+        let column = (lon - self.east) / self.lon_inc;
+        // Points on the north and west edges use the last cell
+        let r = (row.floor() as usize).min(self.rows.saturating_sub(2));
+        let c = (column.floor() as usize).min(self.columns.saturating_sub(2));
+        let (y, x) = (row - r as f64, column - c as f64);
+
+        let node = |r: usize, c: usize| {
+            self.shifts[r.min(self.rows - 1) * self.columns + c.min(self.columns - 1)]
+        };
+        let (sw, se) = (node(r, c + 1), node(r, c));
+        let (nw, ne) = (node(r + 1, c + 1), node(r + 1, c));
+        let mut shift = [0.0; 2];
+        for (i, value) in shift.iter_mut().enumerate() {
+            *value = se[i] * (1.0 - x) * (1.0 - y)
+                + sw[i] * x * (1.0 - y)
+                + ne[i] * (1.0 - x) * y
+                + nw[i] * x * y;
+        }
+        shift
+    }
+}
+
+/// Reads the header records of an NTv2 file
+struct Records<'a> {
+    data: &'a [u8],
+    offset: usize,
+    big_endian: bool,
+}
+
+impl<'a> Records<'a> {
+    fn record(&mut self, key: &str) -> Result<&'a [u8], GridError> {
+        let record = self
+            .data
+            .get(self.offset..self.offset + RECORD_LENGTH)
+            .ok_or(GridError::Truncated)?;
+        if trim_end(&record[..8]) != key.as_bytes() {
+            return Err(GridError::InvalidHeader);
+        }
+        self.offset += RECORD_LENGTH;
+        Ok(&record[8..])
+    }
+
+    fn int(&mut self, key: &str) -> Result<i32, GridError> {
+        let value: [u8; 4] = self.record(key)?[..4].try_into().unwrap();
+        Ok(if self.big_endian {
+            i32::from_be_bytes(value)
+        } else {
+            i32::from_le_bytes(value)
+        })
+    }
+
+    fn float(&mut self, key: &str) -> Result<f64, GridError> {
+        let value: [u8; 8] = self.record(key)?.try_into().unwrap();
+        let value = if self.big_endian {
+            f64::from_be_bytes(value)
+        } else {
+            f64::from_le_bytes(value)
+        };
+        if value.is_finite() {
+            Ok(value)
+        } else {
+            Err(GridError::InvalidHeader)
+        }
+    }
+
+    fn text(&mut self, key: &str) -> Result<String, GridError> {
+        let value = self.record(key)?;
+        Ok(String::from_utf8_lossy(trim_end(value)).into_owned())
+    }
+
+    fn float32(&self, offset: usize) -> f64 {
+        let value: [u8; 4] = self.data[offset..offset + 4].try_into().unwrap();
+        f64::from(if self.big_endian {
+            f32::from_be_bytes(value)
+        } else {
+            f32::from_le_bytes(value)
+        })
+    }
+}
+
+/// Removes the padding at the end of a text field
+fn trim_end(text: &[u8]) -> &[u8] {
+    let end = text
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace() && *b != 0)
+        .map_or(0, |i| i + 1);
+    &text[..end]
+}
+
+/// A grid of latitude and longitude shifts between two datums
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatumShiftGrid {
+    source: String,
+    target: String,
+    subgrids: Vec<SubGrid>,
+}
+
+impl DatumShiftGrid {
+    /// Loads a grid from the contents of an NTv2 file
+    ///
+    /// Both little and big endian files are supported.
+    pub fn from_ntv2(data: &[u8]) -> Result<DatumShiftGrid, GridError> {
+        if data.len() < RECORD_LENGTH {
+            return Err(GridError::Truncated);
+        }
+        let num_orec: [u8; 4] = data[8..12].try_into().unwrap();
+        let big_endian = if i32::from_le_bytes(num_orec) == OVERVIEW_RECORDS as i32 {
+            false
+        } else if i32::from_be_bytes(num_orec) == OVERVIEW_RECORDS as i32 {
+            true
+        } else {
+            return Err(GridError::InvalidHeader);
+        };
+        let mut records = Records {
+            data,
+            offset: 0,
+            big_endian,
+        };
+
+        records.int("NUM_OREC")?;
+        if records.int("NUM_SREC")? != SUBGRID_RECORDS as i32 {
+            return Err(GridError::InvalidHeader);
+        }
+        let count = records.int("NUM_FILE")?;
+        let scale = match records.text("GS_TYPE")?.as_str() {
+            "SECONDS" => 1.0,
+            "MINUTES" => 60.0,
+            "DEGREES" => 3600.0,
+            _ => return Err(GridError::UnsupportedUnits),
+        };
+        records.text("VERSION")?;
+        let source = records.text("SYSTEM_F")?;
+        let target = records.text("SYSTEM_T")?;
+        for key in ["MAJOR_F", "MINOR_F", "MAJOR_T", "MINOR_T"].iter() {
+            records.float(key)?;
+        }
+        // Each subgrid has at least its header records, the count can't be
+        // more than the rest of the file holds
+        let max_count = (data.len() - records.offset) / (SUBGRID_RECORDS * RECORD_LENGTH);
+        if count < 1 || count as usize > max_count {
+            return Err(GridError::InvalidHeader);
+        }
+
+        let mut subgrids = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let name = records.text("SUB_NAME")?;
+            let parent = records.text("PARENT")?;
+            records.text("CREATED")?;
+            records.text("UPDATED")?;
+            let south = records.float("S_LAT")? * scale;
+            let north = records.float("N_LAT")? * scale;
+            let east = records.float("E_LONG")? * scale;
+            let west = records.float("W_LONG")? * scale;
+            let lat_inc = records.float("LAT_INC")? * scale;
+            let lon_inc = records.float("LONG_INC")? * scale;
+            let nodes = records.int("GS_COUNT")?;
+            if lat_inc <= 0.0 || lon_inc <= 0.0 || north < south || west < east {
+                return Err(GridError::InvalidHeader);
+            }
+            let rows = ((north - south) / lat_inc).round() + 1.0;
+            let columns = ((west - east) / lon_inc).round() + 1.0;
+            if rows * columns != f64::from(nodes) {
+                return Err(GridError::InvalidHeader);
+            }
+            let (rows, columns) = (rows as usize, columns as usize);
+
+            let start = records.offset;
+            let end = start + rows * columns * NODE_LENGTH;
+            if data.len() < end {
+                return Err(GridError::Truncated);
+            }
+            let shifts = (start..end)
+                .step_by(NODE_LENGTH)
+                .map(|offset| {
+                    [
+                        records.float32(offset) * scale,
+                        records.float32(offset + 4) * scale,
+                    ]
+                })
+                .collect();
+            records.offset = end;
+
+            subgrids.push(SubGrid {
+                name,
+                parent,
+                south,
+                north,
+                east,
+                west,
+                lat_inc,
+                lon_inc,
+                rows,
+                columns,
+                shifts,
+            });
+        }
+
+        Ok(DatumShiftGrid {
+            source,
+            target,
+            subgrids,
+        })
+    }
+
+    /// Gets the name of the datum the shifts are from
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Gets the name of the datum the shifts are to
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Gets the names of the sub-grids, along with the names of their parents
+    ///
+    /// Top level sub-grids have a parent named `NONE`.
+    pub fn subgrids(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.subgrids
+            .iter()
+            .map(|grid| (grid.name.as_str(), grid.parent.as_str()))
+    }
+
+    /// Checks if a position is covered by the grid
+    pub fn contains(&self, position: &LLHRadians) -> bool {
+        self.shift(position).is_ok()
+    }
+
+    /// Gets the latitude and longitude shifts at a position, in radians
+    ///
+    /// Unlike in the NTv2 file, the longitude shift is positive to the east.
+    pub fn shift(&self, position: &LLHRadians) -> Result<(f64, f64), GridError> {
+        let lat = position.latitude() / ARC_SECONDS_TO_RADIANS;
+        let lon = -position.longitude() / ARC_SECONDS_TO_RADIANS;
+        let subgrid = self
+            .subgrids
+            .iter()
+            .filter(|grid| grid.contains(lat, lon))
+            .min_by(|a, b| {
+                (a.lat_inc * a.lon_inc)
+                    .partial_cmp(&(b.lat_inc * b.lon_inc))
+                    .unwrap()
+            })
+            .ok_or(GridError::OutsideGrid)?;
+        let [dlat, dlon] = subgrid.interpolate(lat, lon);
+        Ok((
+            dlat * ARC_SECONDS_TO_RADIANS,
+            -dlon * ARC_SECONDS_TO_RADIANS,
+        ))
+    }
+
+    /// Shifts a position from the source datum to the target datum
+    ///
+    /// The height is left unchanged.
+    pub fn apply(&self, position: &LLHRadians) -> Result<LLHRadians, GridError> {
+        let (dlat, dlon) = self.shift(position)?;
+        Ok(LLHRadians::new(
+            position.latitude() + dlat,
+            position.longitude() + dlon,
+            position.height(),
+        ))
+    }
+
+    /// Shifts a position from the target datum back to the source datum
+    ///
+    /// The shifts are given at the source positions, so the inverse is found
+    /// iteratively.
+    pub fn apply_inverse(&self, position: &LLHRadians) -> Result<LLHRadians, GridError> {
+        let mut source = *position;
+        for _ in 0..10 {
+            let (dlat, dlon) = self.shift(&source)?;
+            let next = LLHRadians::new(
+                position.latitude() - dlat,
+                position.longitude() - dlon,
+                position.height(),
+            );
+            let change = (next.latitude() - source.latitude())
+                .abs()
+                .max((next.longitude() - source.longitude()).abs());
+            source = next;
+            if change < 1e-12 {
+                break;
+            }
+        }
+        Ok(source)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+
+    /// A sub-grid for the test files, the bounds are in arc seconds with the
+    /// longitudes positive to the west
+    pub(crate) struct TestGrid<'a> {
+        pub name: &'a str,
+        pub parent: &'a str,
+        pub bounds: [f64; 4],
+        pub increments: [f64; 2],
+        pub shift: fn(f64, f64) -> [f32; 2],
+    }
+
+    /// Writes an NTv2 file
+    pub(crate) fn ntv2(grids: &[TestGrid], big_endian: bool) -> Vec<u8> {
+        let mut data = Vec::new();
+        let key = |data: &mut Vec<u8>, key: &str| {
+            data.extend_from_slice(format!("{:<8}", key).as_bytes());
+        };
+        let int = |data: &mut Vec<u8>, name: &str, value: i32| {
+            key(data, name);
+            if big_endian {
+                data.extend_from_slice(&value.to_be_bytes());
+            } else {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+            data.extend_from_slice(&[0; 4]);
+        };
+        let float = |data: &mut Vec<u8>, name: &str, value: f64| {
+            key(data, name);
+            if big_endian {
+                data.extend_from_slice(&value.to_be_bytes());
+            } else {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+        };
+        let text = |data: &mut Vec<u8>, name: &str, value: &str| {
+            key(data, name);
+            key(data, value);
+        };
+
+        int(&mut data, "NUM_OREC", 11);
+        int(&mut data, "NUM_SREC", 11);
+        int(&mut data, "NUM_FILE", grids.len() as i32);
+        text(&mut data, "GS_TYPE", "SECONDS");
+        text(&mut data, "VERSION", "NTv2.0");
+        text(&mut data, "SYSTEM_F", "NAD83");
+        text(&mut data, "SYSTEM_T", "CSRS");
+        for name in ["MAJOR_F", "MINOR_F", "MAJOR_T", "MINOR_T"].iter() {
+            float(&mut data, name, 6_378_137.0);
+        }
+        for grid in grids {
+            let [south, north, east, west] = grid.bounds;
+            let [lat_inc, lon_inc] = grid.increments;
+            let rows = ((north - south) / lat_inc).round() as usize + 1;
+            let columns = ((west - east) / lon_inc).round() as usize + 1;
+            text(&mut data, "SUB_NAME", grid.name);
+            text(&mut data, "PARENT", grid.parent);
+            text(&mut data, "CREATED", "20240101");
+            text(&mut data, "UPDATED", "20240101");
+            for (name, value) in [
+                ("S_LAT", south),
+                ("N_LAT", north),
+                ("E_LONG", east),
+                ("W_LONG", west),
+                ("LAT_INC", lat_inc),
+                ("LONG_INC", lon_inc),
+            ]
+            .iter()
+            {
+                float(&mut data, name, *value);
+            }
+            int(&mut data, "GS_COUNT", (rows * columns) as i32);
+            for r in 0..rows {
+                for c in 0..columns {
+                    let shift = (grid.shift)(south + r as f64 * lat_inc, east + c as f64 * lon_inc);
+                    for value in [shift[0], shift[1], 0.01, 0.01].iter() {
+                        if big_endian {
+                            data.extend_from_slice(&value.to_be_bytes());
+                        } else {
+                            data.extend_from_slice(&value.to_le_bytes());
+                        }
+                    }
+                }
+            }
+        }
+        key(&mut data, "END");
+        data.extend_from_slice(&[0; 8]);
+        data
+    }
+
+    /// Shifts which vary linearly, so are interpolated exactly
+    fn linear(lat: f64, lon: f64) -> [f32; 2] {
+        [
+            (0.5 + 1e-5 * (lat - 180_000.0)) as f32,
+            (-1.0 + 2e-5 * (lon - 360_000.0)) as f32,
+        ]
+    }
+
+    fn test_grids() -> Vec<TestGrid<'static>> {
+        vec![
+            // 50N to 60N, 100W to 110W in 1 degree steps
+            TestGrid {
+                name: "PARENT",
+                parent: "NONE",
+                bounds: [180_000.0, 216_000.0, 360_000.0, 396_000.0],
+                increments: [3600.0, 3600.0],
+                shift: linear,
+            },
+            // 52N to 53N, 102W to 103W with a constant shift
+            TestGrid {
+                name: "CHILD",
+                parent: "PARENT",
+                bounds: [187_200.0, 190_800.0, 367_200.0, 370_800.0],
+                increments: [900.0, 900.0],
+                shift: |_, _| [1.0, 2.0],
+            },
+        ]
+    }
+
+    fn llh(lat_deg: f64, lon_deg: f64) -> LLHRadians {
+        LLHRadians::new(lat_deg.to_radians(), lon_deg.to_radians(), 100.0)
+    }
+
+    #[test]
+    fn load_ntv2() {
+        for big_endian in [false, true].iter() {
+            let grid = DatumShiftGrid::from_ntv2(&ntv2(&test_grids(), *big_endian)).unwrap();
+            assert_eq!(grid.source(), "NAD83");
+            assert_eq!(grid.target(), "CSRS");
+            assert_eq!(
+                grid.subgrids().collect::<Vec<_>>(),
+                vec![("PARENT", "NONE"), ("CHILD", "PARENT")]
+            );
+
+            // Between the nodes of the parent grid
+            let position = llh(55.25, -105.5);
+            let expected = linear(55.25 * 3600.0, 105.5 * 3600.0);
+            let (dlat, dlon) = grid.shift(&position).unwrap();
+            assert_float_eq!(
+                dlat / ARC_SECONDS_TO_RADIANS,
+                f64::from(expected[0]),
+                abs <= 1e-5
+            );
+            assert_float_eq!(
+                dlon / ARC_SECONDS_TO_RADIANS,
+                -f64::from(expected[1]),
+                abs <= 1e-5
+            );
+
+            // The finer child grid takes precedence
+            let (dlat, dlon) = grid.shift(&llh(52.5, -102.5)).unwrap();
+            assert_float_eq!(dlat / ARC_SECONDS_TO_RADIANS, 1.0, abs <= 1e-9);
+            assert_float_eq!(dlon / ARC_SECONDS_TO_RADIANS, -2.0, abs <= 1e-9);
+
+            // The corners are within the grid
+            assert!(grid.contains(&llh(50.0, -100.0)));
+            assert!(grid.contains(&llh(60.0, -110.0)));
+            assert!(!grid.contains(&llh(49.9, -105.0)));
+            assert!(!grid.contains(&llh(55.0, -99.9)));
+            assert_eq!(grid.apply(&llh(55.0, 105.0)), Err(GridError::OutsideGrid));
+        }
+    }
+
+    #[test]
+    fn apply_and_inverse() {
+        let grid = DatumShiftGrid::from_ntv2(&ntv2(&test_grids(), false)).unwrap();
+        let position = llh(57.3, -107.9);
+        let shifted = grid.apply(&position).unwrap();
+        assert_float_eq!(shifted.height(), position.height(), abs <= 0.0);
+        assert!((shifted.latitude() - position.latitude()).abs() > 1e-7);
+
+        let back = grid.apply_inverse(&shifted).unwrap();
+        assert_float_eq!(back.latitude(), position.latitude(), abs <= 1e-13);
+        assert_float_eq!(back.longitude(), position.longitude(), abs <= 1e-13);
+    }
+
+    #[test]
+    fn invalid_files() {
+        let data = ntv2(&test_grids(), false);
+        assert_eq!(
+            DatumShiftGrid::from_ntv2(&data[..data.len() - 100]),
+            Err(GridError::Truncated)
+        );
+        assert_eq!(
+            DatumShiftGrid::from_ntv2(&data[..100]),
+            Err(GridError::Truncated)
+        );
+        assert_eq!(
+            DatumShiftGrid::from_ntv2(&[0; 64]),
+            Err(GridError::InvalidHeader)
+        );
+
+        let mut radians = data.clone();
+        radians[RECORD_LENGTH * 3 + 8..RECORD_LENGTH * 4].copy_from_slice(b"RADIANS ");
+        assert_eq!(
+            DatumShiftGrid::from_ntv2(&radians),
+            Err(GridError::UnsupportedUnits)
+        );
+
+        // Corrupted subgrid counts
+        for bad_count in [0, -1, i32::MAX].iter() {
+            let mut corrupt = data.clone();
+            corrupt[RECORD_LENGTH * 2 + 8..RECORD_LENGTH * 2 + 12]
+                .copy_from_slice(&bad_count.to_le_bytes());
+            assert_eq!(
+                DatumShiftGrid::from_ntv2(&corrupt),
+                Err(GridError::InvalidHeader)
+            );
+        }
+
+        // The node count doesn't match the bounds
+        let mut count = data;
+        let offset = RECORD_LENGTH * (OVERVIEW_RECORDS + 10) + 8;
+        count[offset..offset + 4].copy_from_slice(&12i32.to_le_bytes());
+        assert_eq!(
+            DatumShiftGrid::from_ntv2(&count),
+            Err(GridError::InvalidHeader)
+        );
+    }
+}
@@ -45,6 +45,9 @@
 //! position and velocity of the coordinate, but it does not the change the epoch of the coordinate.
 //! If you need to change the epoch of the coordinate you will need to use the [`Coordinate::adjust_epoch`](crate::coords::Coordinate::adjust_epoch)
 //! method which uses the velocity of the coordinate to determine the position at the new epoch.
+//! [`TransformationRepository::transform_to_epoch`] does both in a single call, and a
+//! [`PlateMotion`] model can provide the velocity of coordinates which don't have one.
+//! Newer transformation parameters can be installed at run time with the [`update`] module.
 //!
 //! # Example
 //! ```
@@ -75,13 +78,28 @@
 //!
 
 use crate::coords::{Coordinate, ECEF};
+use crate::ellipsoid::{Ellipsoid, GRS80};
+use crate::time::{is_leap_year, GpsTime, UtcTime, DAY};
+use grid::{DatumShiftGrid, GridError};
 use std::{
     collections::{HashMap, HashSet, VecDeque},
     fmt,
+    time::Duration,
 };
 use strum::{Display, EnumIter, EnumString};
 
+pub mod consistency;
+pub mod grid;
 mod params;
+mod plate;
+mod repository;
+pub mod update;
+mod wkt;
+
+pub use plate::PlateMotion;
+pub use repository::{LoadError, TransformationRepository};
+#[cfg(feature = "serde")]
+pub use repository::{TransformationDefinition, TransformationDefinitions};
 
 /// Reference Frames
 #[derive(
@@ -127,6 +145,56 @@ pub enum ReferenceFrame {
     DREF91_R2016,
 }
 
+impl ReferenceFrame {
+    /// Gets the ellipsoid geodetic coordinates in this frame are given on
+    ///
+    /// The ITRF and all of the regional frames derived from it are defined
+    /// with the GRS80 ellipsoid.
+    pub fn ellipsoid(&self) -> &'static dyn Ellipsoid {
+        &GRS80
+    }
+
+    /// Gets the epoch at which the agency maintaining the frame publishes
+    /// coordinates
+    ///
+    /// The NAD83 realizations are published at epoch 2010.0 by the NGS and
+    /// NRCan, and DREF91(R2016) at epoch 2016.456 by the AdV. The ITRF and
+    /// ETRF realizations are used at the epoch of the observations.
+    pub fn epoch_convention(&self) -> EpochConvention {
+        match self {
+            ReferenceFrame::NAD83_2011 | ReferenceFrame::NAD83_CSRS => {
+                EpochConvention::Fixed(2010.0)
+            }
+            ReferenceFrame::DREF91_R2016 => EpochConvention::Fixed(2016.456),
+            _ => EpochConvention::Observation,
+        }
+    }
+}
+
+/// How the epoch of the coordinates of a frame is chosen
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub enum EpochConvention {
+    /// Coordinates are given at the epoch they were observed
+    Observation,
+    /// Coordinates are given at a fixed reference epoch, in fractional years
+    Fixed(f64),
+}
+
+impl EpochConvention {
+    /// Gets the epoch to give a coordinate observed at `observation`
+    pub fn epoch_for(&self, observation: &GpsTime) -> GpsTime {
+        match self {
+            EpochConvention::Observation => *observation,
+            EpochConvention::Fixed(year) => {
+                let start = year.floor();
+                let days = if is_leap_year(start as u16) { 366 } else { 365 };
+                UtcTime::from_date(start as u16, 1, 1, 0, 0, 0.).to_gps_hardcoded()
+                    + Duration::from_secs_f64((year - start) * days as f64 * DAY.as_secs_f64())
+            }
+        }
+    }
+}
+
 /// 15-parameter Helmert transformation parameters
 ///
 /// This transformation consists of a 3 dimensional translation,
@@ -183,6 +251,35 @@ impl TimeDependentHelmertParams {
         self.rz_dot *= -1.0;
     }
 
+    /// Combines this transformation with one applied after it
+    ///
+    /// The parameters of the second transformation are moved to the
+    /// reference epoch of the first one and then summed. This drops the
+    /// second order terms, the products of the parameters of the two
+    /// transformations, which are well below a millimeter for the parameters
+    /// published between the ITRF realizations and the frames derived from
+    /// them.
+    pub fn compose(&self, next: &TimeDependentHelmertParams) -> TimeDependentHelmertParams {
+        let dt = self.epoch - next.epoch;
+        TimeDependentHelmertParams {
+            tx: self.tx + next.tx + next.tx_dot * dt,
+            tx_dot: self.tx_dot + next.tx_dot,
+            ty: self.ty + next.ty + next.ty_dot * dt,
+            ty_dot: self.ty_dot + next.ty_dot,
+            tz: self.tz + next.tz + next.tz_dot * dt,
+            tz_dot: self.tz_dot + next.tz_dot,
+            s: self.s + next.s + next.s_dot * dt,
+            s_dot: self.s_dot + next.s_dot,
+            rx: self.rx + next.rx + next.rx_dot * dt,
+            rx_dot: self.rx_dot + next.rx_dot,
+            ry: self.ry + next.ry + next.ry_dot * dt,
+            ry_dot: self.ry_dot + next.ry_dot,
+            rz: self.rz + next.rz + next.rz_dot * dt,
+            rz_dot: self.rz_dot + next.rz_dot,
+            epoch: self.epoch,
+        }
+    }
+
     /// Apply the transformation on a position at a specific epoch
     pub fn transform_position(&self, position: &ECEF, epoch: f64) -> ECEF {
         let dt = epoch - self.epoch;
@@ -217,6 +314,198 @@ impl TimeDependentHelmertParams {
 
         ECEF::new(x, y, z)
     }
+
+    /// Propagate the covariance of a coordinate through the transformation
+    /// at a specific epoch
+    ///
+    /// The covariance of the parameters themselves isn't included, only the
+    /// effect of the scale and rotation terms on the covariance of the
+    /// coordinate.
+    pub fn transform_covariance(
+        &self,
+        covariance: &CoordinateCovariance,
+        epoch: f64,
+    ) -> CoordinateCovariance {
+        let dt = epoch - self.epoch;
+        let mut position_jacobian = scale_rotation_matrix(
+            (self.s + self.s_dot * dt) * Self::SCALE_SCALE,
+            (self.rx + self.rx_dot * dt) * Self::ROTATE_SCALE,
+            (self.ry + self.ry_dot * dt) * Self::ROTATE_SCALE,
+            (self.rz + self.rz_dot * dt) * Self::ROTATE_SCALE,
+        );
+        for (i, row) in position_jacobian.iter_mut().enumerate() {
+            row[i] += 1.0;
+        }
+
+        match covariance {
+            CoordinateCovariance::Position(covariance) => {
+                CoordinateCovariance::Position(propagate(&position_jacobian, covariance))
+            }
+            CoordinateCovariance::PositionVelocity(covariance) => {
+                // The transformed velocity depends on the position through
+                // the rates of the scale and rotation terms
+                let velocity_jacobian = scale_rotation_matrix(
+                    self.s_dot * Self::SCALE_SCALE,
+                    self.rx_dot * Self::ROTATE_SCALE,
+                    self.ry_dot * Self::ROTATE_SCALE,
+                    self.rz_dot * Self::ROTATE_SCALE,
+                );
+                let mut jacobian = [[0.0; 6]; 6];
+                for i in 0..3 {
+                    for j in 0..3 {
+                        jacobian[i][j] = position_jacobian[i][j];
+                        jacobian[i + 3][j] = velocity_jacobian[i][j];
+                    }
+                    jacobian[i + 3][i + 3] = 1.0;
+                }
+                CoordinateCovariance::PositionVelocity(propagate(&jacobian, covariance))
+            }
+        }
+    }
+}
+
+/// Matrix of the scale and small angle rotation terms of a Helmert transformation
+fn scale_rotation_matrix(s: f64, rx: f64, ry: f64, rz: f64) -> [[f64; 3]; 3] {
+    [[s, -rz, ry], [rz, s, -rx], [-ry, rx, s]]
+}
+
+/// Computes `J C Jᵀ`
+fn propagate<const N: usize>(
+    jacobian: &[[f64; N]; N],
+    covariance: &[[f64; N]; N],
+) -> [[f64; N]; N] {
+    let mut jc = [[0.0; N]; N];
+    for i in 0..N {
+        for j in 0..N {
+            jc[i][j] = (0..N).map(|k| jacobian[i][k] * covariance[k][j]).sum();
+        }
+    }
+    let mut result = [[0.0; N]; N];
+    for i in 0..N {
+        for j in 0..N {
+            result[i][j] = (0..N).map(|k| jc[i][k] * jacobian[j][k]).sum();
+        }
+    }
+    result
+}
+
+/// Covariance of a coordinate
+///
+/// The covariance is either of the ECEF position alone, in meters squared, or
+/// of the ECEF position and velocity together. In the latter case the position
+/// components come first, and the velocity terms are in meters per year like
+/// the velocity of a [`Coordinate`].
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+#[allow(clippy::large_enum_variant)]
+pub enum CoordinateCovariance {
+    Position([[f64; 3]; 3]),
+    PositionVelocity([[f64; 6]; 6]),
+}
+
+impl CoordinateCovariance {
+    /// Gets the covariance of the position
+    pub fn position(&self) -> [[f64; 3]; 3] {
+        match self {
+            CoordinateCovariance::Position(covariance) => *covariance,
+            CoordinateCovariance::PositionVelocity(covariance) => {
+                let mut position = [[0.0; 3]; 3];
+                for (i, row) in position.iter_mut().enumerate() {
+                    row.copy_from_slice(&covariance[i][..3]);
+                }
+                position
+            }
+        }
+    }
+
+    /// Gets the covariance of the velocity, if it is known
+    pub fn velocity(&self) -> Option<[[f64; 3]; 3]> {
+        match self {
+            CoordinateCovariance::Position(_) => None,
+            CoordinateCovariance::PositionVelocity(covariance) => {
+                let mut velocity = [[0.0; 3]; 3];
+                for (i, row) in velocity.iter_mut().enumerate() {
+                    row.copy_from_slice(&covariance[i + 3][3..]);
+                }
+                Some(velocity)
+            }
+        }
+    }
+}
+
+/// The range of epochs over which a set of transformation parameters is valid
+///
+/// The epochs are given as fractional years, either end of the interval may be
+/// unbounded. Both ends are inclusive.
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub struct ValidityInterval {
+    start: Option<f64>,
+    end: Option<f64>,
+}
+
+impl ValidityInterval {
+    /// An interval covering all epochs
+    pub const UNBOUNDED: ValidityInterval = ValidityInterval {
+        start: None,
+        end: None,
+    };
+
+    pub const fn new(start: Option<f64>, end: Option<f64>) -> ValidityInterval {
+        ValidityInterval { start, end }
+    }
+
+    /// Gets the first valid epoch, in fractional years
+    pub fn start(&self) -> Option<f64> {
+        self.start
+    }
+
+    /// Gets the last valid epoch, in fractional years
+    pub fn end(&self) -> Option<f64> {
+        self.end
+    }
+
+    /// Checks if an epoch, in fractional years, is within the interval
+    pub fn contains(&self, epoch: f64) -> bool {
+        let after_start = match self.start {
+            Some(start) => epoch >= start,
+            None => true,
+        };
+        let before_end = match self.end {
+            Some(end) => epoch <= end,
+            None => true,
+        };
+        after_start && before_end
+    }
+
+    /// Gets the epochs which are in both intervals
+    ///
+    /// The intersection of disjoint intervals doesn't contain any epoch.
+    pub fn intersection(&self, other: &ValidityInterval) -> ValidityInterval {
+        let bound = |a: Option<f64>, b: Option<f64>, f: fn(f64, f64) -> f64| match (a, b) {
+            (Some(a), Some(b)) => Some(f(a, b)),
+            (a, b) => a.or(b),
+        };
+        ValidityInterval {
+            start: bound(self.start, other.start, f64::max),
+            end: bound(self.end, other.end, f64::min),
+        }
+    }
+}
+
+impl Default for ValidityInterval {
+    fn default() -> Self {
+        ValidityInterval::UNBOUNDED
+    }
+}
+
+impl fmt::Display for ValidityInterval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => write!(f, "{} to {}", start, end),
+            (Some(start), None) => write!(f, "from {}", start),
+            (None, Some(end)) => write!(f, "until {}", end),
+            (None, None) => write!(f, "all epochs"),
+        }
+    }
 }
 
 /// A transformation from one reference frame to another.
@@ -225,9 +514,34 @@ pub struct Transformation {
     pub from: ReferenceFrame,
     pub to: ReferenceFrame,
     pub params: TimeDependentHelmertParams,
+    validity: ValidityInterval,
 }
 
 impl Transformation {
+    /// Makes a transformation which is valid at all epochs
+    pub const fn new(
+        from: ReferenceFrame,
+        to: ReferenceFrame,
+        params: TimeDependentHelmertParams,
+    ) -> Transformation {
+        Transformation {
+            from,
+            to,
+            params,
+            validity: ValidityInterval::UNBOUNDED,
+        }
+    }
+
+    /// Sets the epochs over which the parameters were published as valid
+    pub const fn set_validity(self, validity: ValidityInterval) -> Transformation {
+        Transformation { validity, ..self }
+    }
+
+    /// Gets the epochs over which the parameters were published as valid
+    pub fn validity(&self) -> ValidityInterval {
+        self.validity
+    }
+
     /// Transform the given coordinate, producing a new coordinate.
     ///
     /// Reference frame transformations do not change the epoch of the
@@ -249,12 +563,103 @@ impl Transformation {
         Coordinate::new(self.to, new_position, new_velocity, coord.epoch())
     }
 
+    /// Transform the given coordinate, then apply the local distortions of
+    /// the destination frame from a datum shift grid
+    ///
+    /// The grid is applied to the geodetic coordinates on the ellipsoid of the
+    /// destination frame, leaving the height and the velocity unchanged. An
+    /// error is returned if the coordinate is outside of the grid.
+    pub fn transform_with_grid(
+        &self,
+        coord: &Coordinate,
+        grid: &DatumShiftGrid,
+    ) -> Result<Coordinate, GridError> {
+        let transformed = self.transform(coord);
+        let ellipsoid = self.to.ellipsoid();
+        let position = grid
+            .apply(&transformed.position().to_llh_on(ellipsoid))?
+            .to_ecef_on(ellipsoid);
+        Ok(Coordinate::new(
+            self.to,
+            position,
+            transformed.velocity(),
+            transformed.epoch(),
+        ))
+    }
+
+    /// Transform the given coordinate along with its covariance
+    ///
+    /// See [`TimeDependentHelmertParams::transform_covariance`] for what is
+    /// included in the transformed covariance.
+    pub fn transform_with_covariance(
+        &self,
+        coord: &Coordinate,
+        covariance: &CoordinateCovariance,
+    ) -> (Coordinate, CoordinateCovariance) {
+        let covariance = self
+            .params
+            .transform_covariance(covariance, coord.epoch().to_fractional_year_hardcoded());
+        (self.transform(coord), covariance)
+    }
+
+    /// Transform the given coordinate, and check that the transformation is
+    /// valid at the epoch of the coordinate
+    ///
+    /// The transformation is always applied, a warning is returned alongside
+    /// the new coordinate if the parameters had to be extrapolated outside of
+    /// their validity interval.
+    pub fn transform_with_warning(
+        &self,
+        coord: &Coordinate,
+    ) -> (Coordinate, Option<ValidityWarning>) {
+        (self.transform(coord), self.validity_warning(&coord.epoch()))
+    }
+
+    /// Checks if the transformation parameters are valid at the given epoch
+    pub fn is_valid_at(&self, epoch: &GpsTime) -> bool {
+        self.validity.contains(epoch.to_fractional_year_hardcoded())
+    }
+
+    /// Gets a warning if the transformation parameters are not valid at the
+    /// given epoch
+    pub fn validity_warning(&self, epoch: &GpsTime) -> Option<ValidityWarning> {
+        let epoch = epoch.to_fractional_year_hardcoded();
+        if self.validity.contains(epoch) {
+            None
+        } else {
+            Some(ValidityWarning {
+                from: self.from,
+                to: self.to,
+                epoch,
+                validity: self.validity,
+            })
+        }
+    }
+
     /// Reverse the transformation
     pub fn invert(mut self) -> Self {
         std::mem::swap(&mut self.from, &mut self.to);
         self.params.invert();
         self
     }
+
+    /// Combines this transformation with one from the frame it transforms
+    /// into, see [`TimeDependentHelmertParams::compose`]
+    ///
+    /// The combined transformation is only valid when both of them are.
+    /// `None` is returned if the second transformation doesn't start from
+    /// the destination frame of this one.
+    pub fn then(&self, next: &Transformation) -> Option<Transformation> {
+        if self.to != next.from {
+            return None;
+        }
+        Some(Transformation {
+            from: self.from,
+            to: next.to,
+            params: self.params.compose(&next.params),
+            validity: self.validity.intersection(&next.validity),
+        })
+    }
 }
 
 /// Error indicating that no transformation was found between two reference frames
@@ -262,7 +667,7 @@ impl Transformation {
 /// This error is returned when trying to find a transformation between two reference frames
 /// and no transformation is found.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
-pub struct TransformationNotFound(ReferenceFrame, ReferenceFrame);
+pub struct TransformationNotFound(pub(crate) ReferenceFrame, pub(crate) ReferenceFrame);
 
 impl fmt::Display for TransformationNotFound {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -272,6 +677,48 @@ impl fmt::Display for TransformationNotFound {
 
 impl std::error::Error for TransformationNotFound {}
 
+/// Warning that a transformation was used outside of its validity interval
+///
+/// The parameters are extrapolated in this case, which may give degraded
+/// results.
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub struct ValidityWarning {
+    from: ReferenceFrame,
+    to: ReferenceFrame,
+    epoch: f64,
+    validity: ValidityInterval,
+}
+
+impl ValidityWarning {
+    pub fn from(&self) -> ReferenceFrame {
+        self.from
+    }
+
+    pub fn to(&self) -> ReferenceFrame {
+        self.to
+    }
+
+    /// Gets the epoch the transformation was used at, in fractional years
+    pub fn epoch(&self) -> f64 {
+        self.epoch
+    }
+
+    /// Gets the validity interval of the transformation
+    pub fn validity(&self) -> ValidityInterval {
+        self.validity
+    }
+}
+
+impl fmt::Display for ValidityWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Transformation from {} to {} used at epoch {} outside of its validity ({})",
+            self.from, self.to, self.epoch, self.validity
+        )
+    }
+}
+
 /// Find a transformation from one reference frame to another
 ///
 /// We currently only support a limited set of transformations.
@@ -280,23 +727,48 @@ pub fn get_transformation(
     from: ReferenceFrame,
     to: ReferenceFrame,
 ) -> Result<Transformation, TransformationNotFound> {
+    find_transformations(from, to)
+        .next()
+        .ok_or(TransformationNotFound(from, to))
+}
+
+/// Find a transformation from one reference frame to another, preferring
+/// parameters which are valid at the given epoch
+///
+/// If parameters valid at the epoch are not available the first available set
+/// is returned, use [`Transformation::validity_warning`] to check for this case.
+pub fn get_transformation_at(
+    from: ReferenceFrame,
+    to: ReferenceFrame,
+    epoch: &GpsTime,
+) -> Result<Transformation, TransformationNotFound> {
+    find_transformations(from, to)
+        .find(|t| t.is_valid_at(epoch))
+        .or_else(|| find_transformations(from, to).next())
+        .ok_or(TransformationNotFound(from, to))
+}
+
+fn find_transformations(
+    from: ReferenceFrame,
+    to: ReferenceFrame,
+) -> impl Iterator<Item = Transformation> {
     params::TRANSFORMATIONS
         .iter()
-        .find(|t| (t.from == from && t.to == to) || (t.from == to && t.to == from))
-        .map(|t| {
+        .filter(move |t| (t.from == from && t.to == to) || (t.from == to && t.to == from))
+        .map(move |t| {
             if t.from == from && t.to == to {
                 *t
             } else {
                 (*t).invert()
             }
         })
-        .ok_or(TransformationNotFound(from, to))
 }
 
 /// A helper type for finding transformations between reference frames that require multiple steps
 ///
 /// This object can be used to determine which calls to [`get_transformation`](crate::reference_frame::get_transformation)
 /// are needed when a single transformation does not exist between two reference frames.
+#[derive(Debug, Clone)]
 pub struct TransformationGraph {
     graph: HashMap<ReferenceFrame, HashSet<ReferenceFrame>>,
 }
@@ -304,18 +776,30 @@ pub struct TransformationGraph {
 impl TransformationGraph {
     /// Create a new transformation graph, fully populated with the known transformations
     pub fn new() -> Self {
-        let mut graph = HashMap::new();
-        for transformation in params::TRANSFORMATIONS.iter() {
-            graph
-                .entry(transformation.from)
-                .or_insert_with(HashSet::new)
-                .insert(transformation.to);
-            graph
-                .entry(transformation.to)
-                .or_insert_with(HashSet::new)
-                .insert(transformation.from);
+        TransformationGraph::from_transformations(&params::TRANSFORMATIONS)
+    }
+
+    /// Create a new transformation graph from a set of transformations
+    pub fn from_transformations(transformations: &[Transformation]) -> Self {
+        let mut graph = TransformationGraph {
+            graph: HashMap::new(),
+        };
+        for transformation in transformations.iter() {
+            graph.add_transformation(transformation);
         }
-        TransformationGraph { graph }
+        graph
+    }
+
+    /// Adds the frames of a transformation to the graph
+    fn add_transformation(&mut self, transformation: &Transformation) {
+        self.graph
+            .entry(transformation.from)
+            .or_default()
+            .insert(transformation.to);
+        self.graph
+            .entry(transformation.to)
+            .or_default()
+            .insert(transformation.from);
     }
 
     /// Get the shortest path between two reference frames, if one exists
@@ -327,26 +811,65 @@ impl TransformationGraph {
         from: ReferenceFrame,
         to: ReferenceFrame,
     ) -> Option<Vec<ReferenceFrame>> {
+        self.search(from, to, |_, _| true)
+    }
+
+    /// Get the shortest path between two reference frames for a coordinate at
+    /// a particular epoch
+    ///
+    /// Paths made only of transformations which are valid at the epoch are
+    /// preferred. If no such path exists the shortest path regardless of
+    /// validity is returned.
+    pub fn get_shortest_path_at(
+        &self,
+        from: ReferenceFrame,
+        to: ReferenceFrame,
+        epoch: &GpsTime,
+    ) -> Option<Vec<ReferenceFrame>> {
+        self.search(from, to, |a, b| {
+            find_transformations(a, b).any(|t| t.is_valid_at(epoch))
+        })
+        .or_else(|| self.get_shortest_path(from, to))
+    }
+
+    /// Breadth-first search only following the allowed edges
+    fn search<F>(
+        &self,
+        from: ReferenceFrame,
+        to: ReferenceFrame,
+        allowed: F,
+    ) -> Option<Vec<ReferenceFrame>>
+    where
+        F: Fn(ReferenceFrame, ReferenceFrame) -> bool,
+    {
         if from == to {
             return None;
         }
 
-        let mut visited: HashSet<ReferenceFrame> = HashSet::new();
-        let mut queue: VecDeque<(ReferenceFrame, Vec<ReferenceFrame>)> = VecDeque::new();
-        queue.push_back((from, vec![from]));
+        // The frame each visited frame was first reached from, the path is
+        // only built once the destination has been found
+        let mut previous: HashMap<ReferenceFrame, ReferenceFrame> = HashMap::new();
+        let mut queue: VecDeque<ReferenceFrame> = VecDeque::new();
+        queue.push_back(from);
 
-        while let Some((current_frame, path)) = queue.pop_front() {
+        while let Some(current_frame) = queue.pop_front() {
             if current_frame == to {
+                let mut path = vec![to];
+                while let Some(frame) = previous.get(path.last()?) {
+                    path.push(*frame);
+                }
+                path.reverse();
                 return Some(path);
             }
 
             if let Some(neighbors) = self.graph.get(&current_frame) {
                 for neighbor in neighbors {
-                    if !visited.contains(neighbor) {
-                        visited.insert(*neighbor);
-                        let mut new_path = path.clone();
-                        new_path.push(*neighbor);
-                        queue.push_back((*neighbor, new_path));
+                    if *neighbor != from
+                        && !previous.contains_key(neighbor)
+                        && allowed(current_frame, *neighbor)
+                    {
+                        previous.insert(*neighbor, current_frame);
+                        queue.push_back(*neighbor);
                     }
                 }
             }
@@ -364,6 +887,7 @@ impl Default for TransformationGraph {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::time::UtcTime;
     use float_eq::assert_float_eq;
     use params::TRANSFORMATIONS;
     use std::str::FromStr;
@@ -754,6 +1278,208 @@ mod tests {
         assert_float_eq!(params.epoch, 2010.0, abs_all <= 1e-4);
     }
 
+    #[test]
+    fn helmert_covariance() {
+        let params = TimeDependentHelmertParams {
+            tx: 10.0,
+            tx_dot: 1.0,
+            ty: -5.0,
+            ty_dot: 0.5,
+            tz: 2.0,
+            tz_dot: -0.2,
+            s: 2.0e6,
+            s_dot: 1.0e5,
+            rx: 5.0e5,
+            rx_dot: 2.0e4,
+            ry: -3.0e5,
+            ry_dot: 4.0e4,
+            rz: 1.0e5,
+            rz_dot: -3.0e4,
+            epoch: 2010.0,
+        };
+        let position = ECEF::new(-2703764.0, -4261273.0, 3887158.0);
+        let velocity = ECEF::new(-0.02, 0.01, 0.005);
+        let epoch = 2020.5;
+
+        // Jacobian of the transformation by central differences
+        let state = |x: &[f64; 6]| {
+            let p = ECEF::new(x[0], x[1], x[2]);
+            let v = ECEF::new(x[3], x[4], x[5]);
+            let p = params.transform_position(&p, epoch);
+            let v = params.transform_velocity(&v, &ECEF::new(x[0], x[1], x[2]));
+            [p.x(), p.y(), p.z(), v.x(), v.y(), v.z()]
+        };
+        let x0 = [
+            position.x(),
+            position.y(),
+            position.z(),
+            velocity.x(),
+            velocity.y(),
+            velocity.z(),
+        ];
+        let mut jacobian = [[0.0; 6]; 6];
+        for j in 0..6 {
+            let mut plus = x0;
+            let mut minus = x0;
+            plus[j] += 1.0;
+            minus[j] -= 1.0;
+            let (plus, minus) = (state(&plus), state(&minus));
+            for i in 0..6 {
+                jacobian[i][j] = (plus[i] - minus[i]) / 2.0;
+            }
+        }
+
+        let mut covariance = [[0.0; 6]; 6];
+        for i in 0..6 {
+            for j in 0..6 {
+                covariance[i][j] = 0.001 * (1.0 + (i.min(j) as f64)) / (1.0 + (i.max(j) as f64));
+            }
+        }
+        let expected = propagate(&jacobian, &covariance);
+
+        let transformed =
+            params.transform_covariance(&CoordinateCovariance::PositionVelocity(covariance), epoch);
+        match transformed {
+            CoordinateCovariance::PositionVelocity(transformed) => {
+                for i in 0..6 {
+                    assert_float_eq!(transformed[i], expected[i], abs_all <= 1e-10);
+                }
+            }
+            _ => panic!("Expected a position and velocity covariance"),
+        }
+        // The scale makes a noticeable difference
+        assert!(transformed.position()[0][0] > covariance[0][0] * (1.0 + 1.0e-3));
+
+        // Without the velocity only the position block is propagated
+        let covariance = CoordinateCovariance::PositionVelocity(covariance);
+        assert_eq!(
+            CoordinateCovariance::Position(covariance.position()).velocity(),
+            None
+        );
+        let transformed = params.transform_covariance(
+            &CoordinateCovariance::Position(covariance.position()),
+            epoch,
+        );
+        match transformed {
+            CoordinateCovariance::Position(transformed) => {
+                for i in 0..3 {
+                    for j in 0..3 {
+                        assert_float_eq!(transformed[i][j], expected[i][j], abs <= 1e-10);
+                    }
+                }
+            }
+            _ => panic!("Expected a position covariance"),
+        }
+    }
+
+    #[test]
+    fn covariance_through_path() {
+        let graph = TransformationGraph::new();
+        let epoch = UtcTime::from_date(2020, 1, 1, 0, 0, 0.).to_gps_hardcoded();
+        let coord = Coordinate::with_velocity(
+            ReferenceFrame::ITRF2020,
+            ECEF::new(4027893.0, 307045.0, 4919475.0),
+            ECEF::new(-0.0135, 0.0178, 0.0101),
+            epoch,
+        );
+        let mut covariance = [[0.0; 6]; 6];
+        for (i, row) in covariance.iter_mut().enumerate() {
+            row[i] = if i < 3 { 1.0e-4 } else { 1.0e-6 };
+        }
+        covariance[0][1] = 2.0e-5;
+        covariance[1][0] = 2.0e-5;
+        let covariance = CoordinateCovariance::PositionVelocity(covariance);
+
+        let (transformed, transformed_covariance) = coord
+            .transform_via_with_covariance(ReferenceFrame::ETRF2000, &graph, &covariance)
+            .unwrap();
+        assert_eq!(
+            transformed,
+            coord
+                .transform_via(ReferenceFrame::ETRF2000, &graph)
+                .unwrap()
+        );
+        assert_ne!(transformed_covariance, covariance);
+
+        let (back, back_covariance) = transformed
+            .transform_via_with_covariance(
+                ReferenceFrame::ITRF2020,
+                &graph,
+                &transformed_covariance,
+            )
+            .unwrap();
+        assert_eq!(back.reference_frame(), ReferenceFrame::ITRF2020);
+        match (back_covariance, covariance) {
+            (
+                CoordinateCovariance::PositionVelocity(back),
+                CoordinateCovariance::PositionVelocity(original),
+            ) => {
+                for i in 0..6 {
+                    assert_float_eq!(back[i], original[i], abs_all <= 1e-15);
+                }
+            }
+            _ => panic!("Expected position and velocity covariances"),
+        }
+
+        // Nothing to do in the same frame
+        let (_, same) = coord
+            .transform_via_with_covariance(ReferenceFrame::ITRF2020, &graph, &covariance)
+            .unwrap();
+        assert_eq!(same, covariance);
+    }
+
+    #[test]
+    fn transform_with_grid() {
+        let grid = grid::tests::ntv2(
+            &[grid::tests::TestGrid {
+                name: "CANADA",
+                parent: "NONE",
+                bounds: [144_000.0, 252_000.0, 180_000.0, 504_000.0],
+                increments: [3600.0, 3600.0],
+                shift: |_, _| [0.036, -0.072],
+            }],
+            false,
+        );
+        let grid = DatumShiftGrid::from_ntv2(&grid).unwrap();
+        let transformation =
+            get_transformation(ReferenceFrame::ITRF2014, ReferenceFrame::NAD83_CSRS).unwrap();
+        let epoch = UtcTime::from_date(2010, 1, 1, 0, 0, 0.).to_gps_hardcoded();
+        let coord = Coordinate::with_velocity(
+            ReferenceFrame::ITRF2014,
+            ECEF::new(-1_270_000.0, -3_570_000.0, 5_100_000.0),
+            ECEF::new(-0.01, 0.002, 0.001),
+            epoch,
+        );
+
+        let helmert = transformation.transform(&coord);
+        let shifted = transformation.transform_with_grid(&coord, &grid).unwrap();
+        assert_eq!(shifted.reference_frame(), ReferenceFrame::NAD83_CSRS);
+        assert_eq!(shifted.velocity(), helmert.velocity());
+        let (helmert_llh, shifted_llh) = (helmert.llh(), shifted.llh());
+        // 0.036" north and 0.072" east
+        assert_float_eq!(
+            (shifted_llh.latitude() - helmert_llh.latitude()).to_degrees() * 3600.0,
+            0.036,
+            abs <= 1e-6
+        );
+        assert_float_eq!(
+            (shifted_llh.longitude() - helmert_llh.longitude()).to_degrees() * 3600.0,
+            0.072,
+            abs <= 1e-6
+        );
+        assert_float_eq!(shifted_llh.height(), helmert_llh.height(), abs <= 1e-6);
+
+        let outside = Coordinate::without_velocity(
+            ReferenceFrame::ITRF2014,
+            ECEF::new(4_027_893.0, 307_045.0, 4_919_475.0),
+            epoch,
+        );
+        assert_eq!(
+            transformation.transform_with_grid(&outside, &grid),
+            Err(GridError::OutsideGrid)
+        );
+    }
+
     #[test]
     fn itrf2020_to_etrf2000_shortest_path() {
         let from = ReferenceFrame::ITRF2020;
@@ -774,6 +1500,73 @@ mod tests {
         assert_eq!(path[2], to);
     }
 
+    #[test]
+    fn validity_intervals() {
+        let interval = ValidityInterval::new(Some(2000.0), Some(2010.0));
+        assert!(interval.contains(2000.0));
+        assert!(interval.contains(2005.5));
+        assert!(interval.contains(2010.0));
+        assert!(!interval.contains(1999.9));
+        assert!(!interval.contains(2010.1));
+        assert!(ValidityInterval::new(Some(2000.0), None).contains(3000.0));
+        assert!(ValidityInterval::UNBOUNDED.contains(0.0));
+        assert_eq!(interval.to_string(), "2000 to 2010");
+
+        assert_eq!(
+            interval.intersection(&ValidityInterval::new(Some(2005.0), None)),
+            ValidityInterval::new(Some(2005.0), Some(2010.0))
+        );
+        assert_eq!(
+            interval.intersection(&ValidityInterval::UNBOUNDED),
+            interval
+        );
+        let disjoint = interval.intersection(&ValidityInterval::new(None, Some(1990.0)));
+        assert!(!disjoint.contains(1990.0));
+        assert!(!disjoint.contains(2000.0));
+    }
+
+    #[test]
+    fn validity_warnings() {
+        let epoch_2020 = UtcTime::from_date(2020, 1, 1, 0, 0, 0.).to_gps_hardcoded();
+        let mut transformation =
+            get_transformation(ReferenceFrame::ITRF2014, ReferenceFrame::NAD83_2011).unwrap();
+        assert!(transformation.is_valid_at(&epoch_2020));
+        assert!(transformation.validity_warning(&epoch_2020).is_none());
+
+        transformation =
+            transformation.set_validity(ValidityInterval::new(Some(1997.0), Some(2015.0)));
+        assert!(!transformation.is_valid_at(&epoch_2020));
+        let coord = Coordinate::without_velocity(
+            ReferenceFrame::ITRF2014,
+            ECEF::new(-2703764.0, -4261273.0, 3887158.0),
+            epoch_2020,
+        );
+        let (transformed, warning) = transformation.transform_with_warning(&coord);
+        assert_eq!(transformed, transformation.transform(&coord));
+        let warning = warning.unwrap();
+        assert_eq!(warning.from(), ReferenceFrame::ITRF2014);
+        assert_eq!(warning.to(), ReferenceFrame::NAD83_2011);
+        assert_float_eq!(warning.epoch(), 2020.0, abs <= 0.01);
+        assert_eq!(warning.validity(), transformation.validity);
+
+        // The inverse keeps the validity interval
+        assert!(!transformation.invert().is_valid_at(&epoch_2020));
+    }
+
+    #[test]
+    fn shortest_path_at_epoch() {
+        let graph = TransformationGraph::new();
+        let epoch = UtcTime::from_date(2020, 1, 1, 0, 0, 0.).to_gps_hardcoded();
+        let path = graph
+            .get_shortest_path_at(ReferenceFrame::ITRF2020, ReferenceFrame::ETRF2000, &epoch)
+            .unwrap();
+        assert_eq!(path.len(), 3);
+        assert_eq!(
+            get_transformation_at(path[0], path[1], &epoch).unwrap(),
+            get_transformation(path[0], path[1]).unwrap()
+        );
+    }
+
     #[test]
     fn fully_traversable_graph() {
         let graph = TransformationGraph::new();
@@ -1,4 +1,11 @@
-use super::{ReferenceFrame, TimeDependentHelmertParams, Transformation};
+use super::{ReferenceFrame, TimeDependentHelmertParams, Transformation, ValidityInterval};
+
+/// ETRS89 coincides with the ITRS at 1989.0, the realizations of ETRS89 are
+/// only defined from that epoch onwards.
+///
+/// The IERS, NGS and NRCan publish their parameters without a validity
+/// interval, so those transformations are valid at all epochs.
+const ETRS89_VALIDITY: ValidityInterval = ValidityInterval::new(Some(1989.0), None);
 
 pub const TRANSFORMATIONS: [Transformation; 31] = [
     Transformation {
@@ -21,6 +28,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: 0.0,
             epoch: 2015.0,
         },
+        validity: ValidityInterval::UNBOUNDED,
     },
     Transformation {
         from: ReferenceFrame::ITRF2020,
@@ -42,6 +50,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: 0.0,
             epoch: 2015.0,
         },
+        validity: ValidityInterval::UNBOUNDED,
     },
     Transformation {
         from: ReferenceFrame::ITRF2020,
@@ -63,6 +72,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: 0.0,
             epoch: 2015.0,
         },
+        validity: ValidityInterval::UNBOUNDED,
     },
     Transformation {
         from: ReferenceFrame::ITRF2020,
@@ -84,6 +94,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: 0.0,
             epoch: 2015.0,
         },
+        validity: ValidityInterval::UNBOUNDED,
     },
     Transformation {
         from: ReferenceFrame::ITRF2020,
@@ -105,6 +116,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: 0.02,
             epoch: 2015.0,
         },
+        validity: ValidityInterval::UNBOUNDED,
     },
     Transformation {
         from: ReferenceFrame::ITRF2020,
@@ -126,6 +138,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: 0.02,
             epoch: 2015.0,
         },
+        validity: ValidityInterval::UNBOUNDED,
     },
     Transformation {
         from: ReferenceFrame::ITRF2020,
@@ -147,6 +160,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: 0.02,
             epoch: 2015.0,
         },
+        validity: ValidityInterval::UNBOUNDED,
     },
     Transformation {
         from: ReferenceFrame::ITRF2020,
@@ -168,6 +182,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: 0.07,
             epoch: 2015.0,
         },
+        validity: ValidityInterval::UNBOUNDED,
     },
     Transformation {
         from: ReferenceFrame::ITRF2020,
@@ -189,6 +204,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: 0.02,
             epoch: 2015.0,
         },
+        validity: ValidityInterval::UNBOUNDED,
     },
     Transformation {
         from: ReferenceFrame::ITRF2020,
@@ -210,6 +226,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: 0.02,
             epoch: 2015.0,
         },
+        validity: ValidityInterval::UNBOUNDED,
     },
     Transformation {
         from: ReferenceFrame::ITRF2020,
@@ -231,6 +248,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: 0.02,
             epoch: 2015.0,
         },
+        validity: ValidityInterval::UNBOUNDED,
     },
     Transformation {
         from: ReferenceFrame::ITRF2020,
@@ -252,6 +270,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: 0.02,
             epoch: 2015.0,
         },
+        validity: ValidityInterval::UNBOUNDED,
     },
     Transformation {
         from: ReferenceFrame::ITRF2020,
@@ -273,6 +292,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: 0.02,
             epoch: 2015.0,
         },
+        validity: ValidityInterval::UNBOUNDED,
     },
     Transformation {
         from: ReferenceFrame::ITRF2020,
@@ -294,6 +314,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: -0.753,
             epoch: 1989.0,
         },
+        validity: ETRS89_VALIDITY,
     },
     Transformation {
         from: ReferenceFrame::ITRF2014,
@@ -315,6 +336,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: -0.770,
             epoch: 1989.0,
         },
+        validity: ETRS89_VALIDITY,
     },
     Transformation {
         from: ReferenceFrame::ITRF2005,
@@ -336,6 +358,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: -0.781,
             epoch: 1989.0,
         },
+        validity: ETRS89_VALIDITY,
     },
     Transformation {
         from: ReferenceFrame::ITRF2000,
@@ -357,6 +380,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: -0.792,
             epoch: 1989.0,
         },
+        validity: ETRS89_VALIDITY,
     },
     Transformation {
         from: ReferenceFrame::ITRF97,
@@ -378,6 +402,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: -0.650,
             epoch: 1989.0,
         },
+        validity: ETRS89_VALIDITY,
     },
     Transformation {
         from: ReferenceFrame::ITRF96,
@@ -399,6 +424,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: -0.650,
             epoch: 1989.0,
         },
+        validity: ETRS89_VALIDITY,
     },
     Transformation {
         from: ReferenceFrame::ITRF94,
@@ -420,6 +446,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: -0.650,
             epoch: 1989.0,
         },
+        validity: ETRS89_VALIDITY,
     },
     Transformation {
         from: ReferenceFrame::ITRF93,
@@ -441,6 +468,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: -0.670,
             epoch: 1989.0,
         },
+        validity: ETRS89_VALIDITY,
     },
     Transformation {
         from: ReferenceFrame::ITRF92,
@@ -462,6 +490,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: -0.680,
             epoch: 1989.0,
         },
+        validity: ETRS89_VALIDITY,
     },
     Transformation {
         from: ReferenceFrame::ITRF91,
@@ -483,6 +512,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: -0.680,
             epoch: 1989.0,
         },
+        validity: ETRS89_VALIDITY,
     },
     Transformation {
         from: ReferenceFrame::ITRF90,
@@ -504,6 +534,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: -0.710,
             epoch: 1989.0,
         },
+        validity: ETRS89_VALIDITY,
     },
     Transformation {
         from: ReferenceFrame::ITRF89,
@@ -525,6 +556,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: -0.710,
             epoch: 1989.0,
         },
+        validity: ETRS89_VALIDITY,
     },
     Transformation {
         from: ReferenceFrame::ITRF2014,
@@ -546,6 +578,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: 0.05133,
             epoch: 2010.0,
         },
+        validity: ValidityInterval::UNBOUNDED,
     },
     Transformation {
         from: ReferenceFrame::ITRF2014,
@@ -567,6 +600,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: -0.770,
             epoch: 1989.0,
         },
+        validity: ETRS89_VALIDITY,
     },
     Transformation {
         from: ReferenceFrame::ITRF2008,
@@ -588,6 +622,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: 0.05133,
             epoch: 2010.0,
         },
+        validity: ValidityInterval::UNBOUNDED,
     },
     Transformation {
         from: ReferenceFrame::ITRF2014,
@@ -609,6 +644,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: 0.05133,
             epoch: 2010.0,
         },
+        validity: ValidityInterval::UNBOUNDED,
     },
     Transformation {
         from: ReferenceFrame::ITRF2020,
@@ -630,6 +666,7 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: 0.05133,
             epoch: 2010.0,
         },
+        validity: ValidityInterval::UNBOUNDED,
     },
     Transformation {
         from: ReferenceFrame::ITRF2020,
@@ -651,5 +688,6 @@ pub const TRANSFORMATIONS: [Transformation; 31] = [
             rz_dot: -0.5284,
             epoch: 2021.0,
         },
+        validity: ETRS89_VALIDITY,
     },
 ];
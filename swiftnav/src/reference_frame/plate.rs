@@ -0,0 +1,73 @@
+// Copyright (c) 2024 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Tectonic plate motion models
+//!
+//! Away from plate boundaries, the velocity of a point on the Earth's crust is
+//! well described by the rotation of its tectonic plate around an Euler pole.
+//! This gives a velocity for coordinates which don't have one, so that they
+//! can still be moved to a different epoch.
+//!
+//! # References
+//!   * Altamimi Z., Métivier L., Rebischung P., Rouby H., Collilieux X.,
+//!     "ITRF2014 plate motion model", Geophysical Journal International 209,
+//!     1906–1912 (2017)
+
+use super::{ReferenceFrame, TimeDependentHelmertParams};
+use crate::coords::ECEF;
+
+/// Rotation of a tectonic plate
+///
+/// The rotation rates are about the ECEF axes of the frame the model is given
+/// in, in milliarcseconds per year.
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub struct PlateMotion {
+    frame: ReferenceFrame,
+    rotation: [f64; 3],
+}
+
+impl PlateMotion {
+    /// The Eurasian plate in the ITRF2014 plate motion model
+    pub const EURASIA_ITRF2014: PlateMotion =
+        PlateMotion::new(ReferenceFrame::ITRF2014, -0.085, -0.531, 0.770);
+
+    /// The North American plate in the ITRF2014 plate motion model
+    pub const NORTH_AMERICA_ITRF2014: PlateMotion =
+        PlateMotion::new(ReferenceFrame::ITRF2014, 0.024, -0.694, -0.063);
+
+    pub const fn new(frame: ReferenceFrame, wx: f64, wy: f64, wz: f64) -> PlateMotion {
+        PlateMotion {
+            frame,
+            rotation: [wx, wy, wz],
+        }
+    }
+
+    /// Gets the frame the model is given in
+    pub fn frame(&self) -> ReferenceFrame {
+        self.frame
+    }
+
+    /// Gets the rotation rates about the X, Y and Z axes, in milliarcseconds
+    /// per year
+    pub fn rotation(&self) -> [f64; 3] {
+        self.rotation
+    }
+
+    /// Gets the velocity of a position on the plate, in meters per year
+    pub fn velocity_at(&self, position: &ECEF) -> ECEF {
+        let [wx, wy, wz] = self
+            .rotation
+            .map(|w| w * TimeDependentHelmertParams::ROTATE_SCALE);
+        ECEF::new(
+            wy * position.z() - wz * position.y(),
+            wz * position.x() - wx * position.z(),
+            wx * position.y() - wy * position.x(),
+        )
+    }
+}
@@ -0,0 +1,1149 @@
+// Copyright (c) 2024 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Collections of transformation parameters
+//!
+//! The parameters built into this crate cover the common global and regional
+//! frames, but organizations often publish their own parameter sets. A
+//! [`TransformationRepository`] holds a set of transformations, which can be
+//! extended at run time with definitions given as EPSG style WKT2 strings or,
+//! with the `serde` feature, read from any format supported by serde such as
+//! JSON or TOML through [`TransformationDefinitions`].
+//!
+//! The frames of the definitions must be one of the [`ReferenceFrame`]s, but
+//! they can be referred to by other names by adding aliases to the repository.
+//!
+//! The transformations found along the path between two frames are cached by
+//! the repository, so that transforming a stream of coordinates doesn't need
+//! to search for the path again for every coordinate.
+
+use super::{
+    params, wkt, Coordinate, CoordinateCovariance, GpsTime, PlateMotion, ReferenceFrame,
+    Transformation, TransformationGraph, TransformationNotFound,
+};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+#[cfg(feature = "serde")]
+use super::{TimeDependentHelmertParams, ValidityInterval};
+#[cfg(feature = "serde")]
+use std::collections::BTreeMap;
+
+/// Errors which can occur when loading transformation definitions
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum LoadError {
+    /// The WKT string is malformed, or isn't a coordinate operation
+    InvalidWkt,
+    /// A frame name is neither a known frame nor an alias of one
+    UnknownFrame(String),
+    /// The transformation method isn't a Helmert transformation
+    UnsupportedMethod(String),
+    /// A required parameter of the transformation is missing
+    MissingParameter(String),
+    /// The unit of a parameter is missing or invalid
+    InvalidUnit(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::InvalidWkt => write!(f, "Invalid WKT coordinate operation"),
+            LoadError::UnknownFrame(name) => write!(f, "Unknown reference frame \"{}\"", name),
+            LoadError::UnsupportedMethod(name) => {
+                write!(f, "Unsupported transformation method \"{}\"", name)
+            }
+            LoadError::MissingParameter(name) => write!(f, "Missing parameter \"{}\"", name),
+            LoadError::InvalidUnit(name) => write!(f, "Invalid unit of parameter \"{}\"", name),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+/// A set of transformations between reference frames
+///
+/// Paths through several frames are found as needed when transforming
+/// coordinates, preferring transformations which are valid at the epoch of
+/// the coordinate. The transformations along each path are cached until a
+/// transformation is added to the repository.
+#[derive(Debug)]
+pub struct TransformationRepository {
+    transformations: Vec<Transformation>,
+    graph: TransformationGraph,
+    aliases: HashMap<String, ReferenceFrame>,
+    chains: Mutex<HashMap<ChainKey, Arc<[Transformation]>>>,
+}
+
+/// The transformations used between two frames only depend on which of the
+/// transformations of the repository are valid at the epoch
+type ChainKey = (ReferenceFrame, ReferenceFrame, Vec<bool>);
+
+impl TransformationRepository {
+    /// Makes a repository of the transformations built into the crate
+    pub fn new() -> TransformationRepository {
+        TransformationRepository::from_transformations(&params::TRANSFORMATIONS)
+    }
+
+    /// Makes a repository of only the given transformations
+    pub fn from_transformations(transformations: &[Transformation]) -> TransformationRepository {
+        TransformationRepository {
+            transformations: transformations.to_vec(),
+            graph: TransformationGraph::from_transformations(transformations),
+            aliases: HashMap::new(),
+            chains: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Adds a transformation to the repository
+    ///
+    /// Transformations already in the repository between the same frames are
+    /// kept, and the first one valid at the epoch of a coordinate is used.
+    pub fn add_transformation(&mut self, transformation: Transformation) {
+        self.graph.add_transformation(&transformation);
+        self.transformations.push(transformation);
+        self.chains
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clear();
+    }
+
+    pub fn transformations(&self) -> &[Transformation] {
+        &self.transformations
+    }
+
+    /// Makes a copy of the repository with more transformations, which are
+    /// preferred over the ones already in the repository
+    pub(super) fn with_preferred(&self, transformations: &[Transformation]) -> Self {
+        let all: Vec<Transformation> = transformations
+            .iter()
+            .chain(self.transformations.iter())
+            .copied()
+            .collect();
+        let mut repository = TransformationRepository::from_transformations(&all);
+        repository.aliases = self.aliases.clone();
+        repository
+    }
+
+    /// Adds another name for a frame, used when loading definitions
+    pub fn add_alias(&mut self, alias: &str, frame: ReferenceFrame) {
+        self.aliases.insert(alias.to_string(), frame);
+    }
+
+    /// Finds the frame with the given name or alias
+    pub fn resolve_frame(&self, name: &str) -> Option<ReferenceFrame> {
+        self.aliases
+            .get(name)
+            .copied()
+            .or_else(|| ReferenceFrame::from_str(name).ok())
+    }
+
+    /// Parses a WKT2 coordinate operation and adds it to the repository
+    ///
+    /// Only Helmert transformations between geocentric frames are supported,
+    /// with the parameters named as in the EPSG dataset. Both the position
+    /// vector and the coordinate frame rotation conventions are accepted. The
+    /// unit conversion factors of the rates are to SI units per second, also
+    /// as in the EPSG dataset.
+    pub fn add_wkt(&mut self, wkt: &str) -> Result<Transformation, LoadError> {
+        let transformation = wkt::parse_transformation(wkt, |name| self.resolve_frame(name))?;
+        self.add_transformation(transformation);
+        Ok(transformation)
+    }
+
+    /// Adds a set of definitions to the repository
+    ///
+    /// The aliases are added first, so they can be used by the
+    /// transformations of the same set. Nothing is added if any of the
+    /// definitions can't be loaded.
+    #[cfg(feature = "serde")]
+    pub fn add_definitions(
+        &mut self,
+        definitions: &TransformationDefinitions,
+    ) -> Result<(), LoadError> {
+        let mut repository = self.clone();
+        // Aliases can refer to other aliases of the same set
+        let mut pending: Vec<_> = definitions.aliases.iter().collect();
+        while !pending.is_empty() {
+            let count = pending.len();
+            pending.retain(|(alias, name)| match repository.resolve_frame(name) {
+                Some(frame) => {
+                    repository.add_alias(alias, frame);
+                    false
+                }
+                None => true,
+            });
+            if pending.len() == count {
+                return Err(LoadError::UnknownFrame(pending[0].1.clone()));
+            }
+        }
+        for definition in definitions.transformations.iter() {
+            let transformation = definition.to_transformation(&repository)?;
+            repository.add_transformation(transformation);
+        }
+        for wkt in definitions.wkt.iter() {
+            repository.add_wkt(wkt)?;
+        }
+        *self = repository;
+        Ok(())
+    }
+
+    /// Finds a transformation from one frame to another, preferring
+    /// parameters which are valid at the given epoch
+    pub fn get_transformation(
+        &self,
+        from: ReferenceFrame,
+        to: ReferenceFrame,
+        epoch: &GpsTime,
+    ) -> Result<Transformation, TransformationNotFound> {
+        self.find_transformations(from, to)
+            .find(|t| t.is_valid_at(epoch))
+            .or_else(|| self.find_transformations(from, to).next())
+            .ok_or(TransformationNotFound(from, to))
+    }
+
+    /// Gets the shortest path between two frames at an epoch
+    ///
+    /// As with [`TransformationGraph::get_shortest_path_at`], paths made only
+    /// of transformations valid at the epoch are preferred.
+    pub fn get_shortest_path(
+        &self,
+        from: ReferenceFrame,
+        to: ReferenceFrame,
+        epoch: &GpsTime,
+    ) -> Option<Vec<ReferenceFrame>> {
+        self.graph
+            .search(from, to, |a, b| {
+                self.find_transformations(a, b)
+                    .any(|t| t.is_valid_at(epoch))
+            })
+            .or_else(|| self.graph.get_shortest_path(from, to))
+    }
+
+    /// Transforms a coordinate into a different frame, going through
+    /// intermediate frames when there is no direct transformation
+    ///
+    /// The epoch of the coordinate isn't changed.
+    pub fn transform(
+        &self,
+        coord: &Coordinate,
+        to: ReferenceFrame,
+    ) -> Result<Coordinate, TransformationNotFound> {
+        let from = coord.reference_frame();
+        if from == to {
+            return Ok(*coord);
+        }
+        let chain = self.resolve_chain(from, to, &coord.epoch())?;
+        Ok(apply_chain(&chain, coord))
+    }
+
+    /// Transforms a coordinate and its covariance into a different frame,
+    /// going through intermediate frames when there is no direct
+    /// transformation
+    ///
+    /// The covariance is propagated through each transformation along the
+    /// path, see [`Transformation::transform_with_covariance`].
+    pub fn transform_with_covariance(
+        &self,
+        coord: &Coordinate,
+        to: ReferenceFrame,
+        covariance: &CoordinateCovariance,
+    ) -> Result<(Coordinate, CoordinateCovariance), TransformationNotFound> {
+        let from = coord.reference_frame();
+        if from == to {
+            return Ok((*coord, *covariance));
+        }
+        let chain = self.resolve_chain(from, to, &coord.epoch())?;
+        Ok(chain.iter().fold(
+            (*coord, *covariance),
+            |(coord, covariance), transformation| {
+                transformation.transform_with_covariance(&coord, &covariance)
+            },
+        ))
+    }
+
+    /// Transforms many coordinates into the same frame
+    ///
+    /// The coordinates can be in different frames and at different epochs.
+    /// The path and the transformations along it are only resolved once for
+    /// all of the coordinates in the same frame at which the same
+    /// transformations are valid, giving the same results as
+    /// [`TransformationRepository::transform`].
+    pub fn transform_batch(
+        &self,
+        coords: &[Coordinate],
+        to: ReferenceFrame,
+    ) -> Result<Vec<Coordinate>, TransformationNotFound> {
+        coords
+            .iter()
+            .map(|coord| self.transform(coord, to))
+            .collect()
+    }
+
+    /// Transforms many coordinates into the same frame, spreading the work
+    /// over the rayon thread pool
+    ///
+    /// Gives the same results as [`TransformationRepository::transform_batch`].
+    #[cfg(feature = "rayon")]
+    pub fn par_transform_batch(
+        &self,
+        coords: &[Coordinate],
+        to: ReferenceFrame,
+    ) -> Result<Vec<Coordinate>, TransformationNotFound> {
+        use rayon::prelude::*;
+        coords
+            .par_iter()
+            .map(|coord| self.transform(coord, to))
+            .collect()
+    }
+
+    /// Combines the transformations along the path between two frames into
+    /// a single transformation, see [`Transformation::then`]
+    ///
+    /// The combined transformation is a first order approximation of the
+    /// transformations along the path, which is cheaper to apply to many
+    /// coordinates at similar epochs. [`TransformationRepository::transform`]
+    /// applies each transformation in turn instead.
+    pub fn get_composed_transformation(
+        &self,
+        from: ReferenceFrame,
+        to: ReferenceFrame,
+        epoch: &GpsTime,
+    ) -> Result<Transformation, TransformationNotFound> {
+        let chain = self.resolve_chain(from, to, epoch)?;
+        chain
+            .iter()
+            .skip(1)
+            .try_fold(chain[0], |composed, next| composed.then(next))
+            .ok_or(TransformationNotFound(from, to))
+    }
+
+    /// Transforms a coordinate into a different frame and moves it to a
+    /// different epoch
+    ///
+    /// The coordinate is moved using its velocity, after the velocity has
+    /// been transformed into the new frame. Coordinates without a velocity
+    /// keep their position, only the epoch is changed, see
+    /// [`TransformationRepository::transform_to_epoch_with_plate`] for moving
+    /// them with a plate motion model instead.
+    pub fn transform_to_epoch(
+        &self,
+        coord: &Coordinate,
+        to: ReferenceFrame,
+        epoch: &GpsTime,
+    ) -> Result<Coordinate, TransformationNotFound> {
+        Ok(self.transform(coord, to)?.adjust_epoch(epoch))
+    }
+
+    /// Transforms a coordinate into a different frame and moves it to a
+    /// different epoch, using a plate motion model for the velocity of
+    /// coordinates which don't have one
+    ///
+    /// The velocity given by the plate motion model is computed in the frame
+    /// of the model, then transformed along with the coordinate. The returned
+    /// coordinate keeps this velocity.
+    pub fn transform_to_epoch_with_plate(
+        &self,
+        coord: &Coordinate,
+        to: ReferenceFrame,
+        epoch: &GpsTime,
+        plate: &PlateMotion,
+    ) -> Result<Coordinate, TransformationNotFound> {
+        if coord.velocity().is_some() {
+            return self.transform_to_epoch(coord, to, epoch);
+        }
+        let on_plate = self.transform(coord, plate.frame())?;
+        let on_plate = Coordinate::with_velocity(
+            on_plate.reference_frame(),
+            on_plate.position(),
+            plate.velocity_at(&on_plate.position()),
+            on_plate.epoch(),
+        );
+        self.transform_to_epoch(&on_plate, to, epoch)
+    }
+
+    /// Transforms a coordinate into a frame and moves it to the epoch
+    /// coordinates of that frame are conventionally given at, see
+    /// [`ReferenceFrame::epoch_convention`]
+    ///
+    /// As with [`TransformationRepository::transform_to_epoch`], coordinates
+    /// without a velocity keep their position.
+    pub fn to_regional_standard(
+        &self,
+        coord: &Coordinate,
+        to: ReferenceFrame,
+    ) -> Result<Coordinate, TransformationNotFound> {
+        let epoch = to.epoch_convention().epoch_for(&coord.epoch());
+        self.transform_to_epoch(coord, to, &epoch)
+    }
+
+    /// Transforms a coordinate into a frame and moves it to the epoch
+    /// coordinates of that frame are conventionally given at, using a plate
+    /// motion model for the velocity of coordinates which don't have one
+    ///
+    /// See [`TransformationRepository::transform_to_epoch_with_plate`].
+    pub fn to_regional_standard_with_plate(
+        &self,
+        coord: &Coordinate,
+        to: ReferenceFrame,
+        plate: &PlateMotion,
+    ) -> Result<Coordinate, TransformationNotFound> {
+        let epoch = to.epoch_convention().epoch_for(&coord.epoch());
+        self.transform_to_epoch_with_plate(coord, to, &epoch, plate)
+    }
+
+    /// Finds the transformations along the path between two frames, using
+    /// the cached ones when possible
+    fn resolve_chain(
+        &self,
+        from: ReferenceFrame,
+        to: ReferenceFrame,
+        epoch: &GpsTime,
+    ) -> Result<Arc<[Transformation]>, TransformationNotFound> {
+        let year = epoch.to_fractional_year_hardcoded();
+        let validity = self
+            .transformations
+            .iter()
+            .map(|t| t.validity().contains(year))
+            .collect();
+        let key = (from, to, validity);
+        if let Some(chain) = self.lock_chains().get(&key) {
+            return Ok(Arc::clone(chain));
+        }
+
+        let chain: Arc<[Transformation]> = self
+            .get_shortest_path(from, to, epoch)
+            .ok_or(TransformationNotFound(from, to))?
+            .windows(2)
+            .map(|step| self.get_transformation(step[0], step[1], epoch))
+            .collect::<Result<Vec<_>, _>>()?
+            .into();
+        self.lock_chains().insert(key, Arc::clone(&chain));
+        Ok(chain)
+    }
+
+    fn lock_chains(&self) -> MutexGuard<'_, HashMap<ChainKey, Arc<[Transformation]>>> {
+        // The cache is always left in a consistent state
+        self.chains
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn find_transformations(
+        &self,
+        from: ReferenceFrame,
+        to: ReferenceFrame,
+    ) -> impl Iterator<Item = Transformation> + '_ {
+        self.transformations
+            .iter()
+            .filter(move |t| (t.from == from && t.to == to) || (t.from == to && t.to == from))
+            .map(move |t| if t.from == from { *t } else { t.invert() })
+    }
+}
+
+fn apply_chain(chain: &[Transformation], coord: &Coordinate) -> Coordinate {
+    chain.iter().fold(*coord, |coord, transformation| {
+        transformation.transform(&coord)
+    })
+}
+
+impl Clone for TransformationRepository {
+    fn clone(&self) -> TransformationRepository {
+        TransformationRepository {
+            transformations: self.transformations.clone(),
+            graph: self.graph.clone(),
+            aliases: self.aliases.clone(),
+            chains: Mutex::new(self.lock_chains().clone()),
+        }
+    }
+}
+
+impl Default for TransformationRepository {
+    fn default() -> TransformationRepository {
+        TransformationRepository::new()
+    }
+}
+
+/// A set of transformation definitions, for loading with serde
+///
+/// The transformations can be given either as parameters or as WKT2 strings,
+/// see [`TransformationRepository::add_wkt`]. The aliases map the alternative
+/// names to the names of the frames.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct TransformationDefinitions {
+    pub aliases: BTreeMap<String, String>,
+    pub transformations: Vec<TransformationDefinition>,
+    pub wkt: Vec<String>,
+}
+
+/// The parameters of a transformation, for loading with serde
+///
+/// The parameters follow the conventions of [`TimeDependentHelmertParams`]:
+/// the translations are in millimeters, the rotations in milliarcseconds, the
+/// scale in parts per billion, and the rates in the same units per year.
+/// Missing parameters are zero. The reference epoch and the validity interval
+/// are in fractional years.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct TransformationDefinition {
+    pub from: String,
+    pub to: String,
+    pub tx: f64,
+    pub tx_dot: f64,
+    pub ty: f64,
+    pub ty_dot: f64,
+    pub tz: f64,
+    pub tz_dot: f64,
+    pub s: f64,
+    pub s_dot: f64,
+    pub rx: f64,
+    pub rx_dot: f64,
+    pub ry: f64,
+    pub ry_dot: f64,
+    pub rz: f64,
+    pub rz_dot: f64,
+    pub epoch: f64,
+    pub valid_from: Option<f64>,
+    pub valid_until: Option<f64>,
+}
+
+#[cfg(feature = "serde")]
+impl TransformationDefinition {
+    fn to_transformation(
+        &self,
+        repository: &TransformationRepository,
+    ) -> Result<Transformation, LoadError> {
+        let resolve = |name: &String| {
+            repository
+                .resolve_frame(name)
+                .ok_or_else(|| LoadError::UnknownFrame(name.clone()))
+        };
+        Ok(Transformation {
+            from: resolve(&self.from)?,
+            to: resolve(&self.to)?,
+            params: TimeDependentHelmertParams {
+                tx: self.tx,
+                tx_dot: self.tx_dot,
+                ty: self.ty,
+                ty_dot: self.ty_dot,
+                tz: self.tz,
+                tz_dot: self.tz_dot,
+                s: self.s,
+                s_dot: self.s_dot,
+                rx: self.rx,
+                rx_dot: self.rx_dot,
+                ry: self.ry,
+                ry_dot: self.ry_dot,
+                rz: self.rz,
+                rz_dot: self.rz_dot,
+                epoch: self.epoch,
+            },
+            validity: ValidityInterval::new(self.valid_from, self.valid_until),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::ECEF;
+    use crate::reference_frame::ValidityInterval;
+    use crate::time::UtcTime;
+    use float_eq::assert_float_eq;
+
+    /// ITRF2014 to ETRF2014 as published by EPSG, with the units EPSG uses
+    const ITRF2014_TO_ETRF2014: &str = r#"COORDINATEOPERATION["ITRF2014 to ETRF2014 (1)",
+    VERSION["EUREF-Eur"],
+    SOURCECRS[GEODCRS["ITRF2014",DYNAMIC[FRAMEEPOCH[2010]],
+        DATUM["International Terrestrial Reference Frame 2014",
+            ELLIPSOID["GRS 1980",6378137,298.257222101,LENGTHUNIT["metre",1]]],
+        CS[Cartesian,3],
+            AXIS["(X)",geocentricX,ORDER[1],LENGTHUNIT["metre",1]],
+            AXIS["(Y)",geocentricY,ORDER[2],LENGTHUNIT["metre",1]],
+            AXIS["(Z)",geocentricZ,ORDER[3],LENGTHUNIT["metre",1]],
+        ID["EPSG",7789]]],
+    TARGETCRS[GEODCRS["ETRF2014",
+        DATUM["European Terrestrial Reference Frame 2014",
+            ELLIPSOID["GRS 1980",6378137,298.257222101,LENGTHUNIT["metre",1]]],
+        CS[Cartesian,3],
+            AXIS["(X)",geocentricX,ORDER[1],LENGTHUNIT["metre",1]],
+            AXIS["(Y)",geocentricY,ORDER[2],LENGTHUNIT["metre",1]],
+            AXIS["(Z)",geocentricZ,ORDER[3],LENGTHUNIT["metre",1]],
+        ID["EPSG",8401]]],
+    METHOD["Time-dependent Position Vector tfm (geocentric)",ID["EPSG",1053]],
+    PARAMETER["X-axis translation",0,LENGTHUNIT["millimetre",0.001]],
+    PARAMETER["Y-axis translation",0,LENGTHUNIT["millimetre",0.001]],
+    PARAMETER["Z-axis translation",0,LENGTHUNIT["millimetre",0.001]],
+    PARAMETER["X-axis rotation",0,ANGLEUNIT["milliarc-second",4.84813681109536E-09]],
+    PARAMETER["Y-axis rotation",0,ANGLEUNIT["milliarc-second",4.84813681109536E-09]],
+    PARAMETER["Z-axis rotation",0,ANGLEUNIT["milliarc-second",4.84813681109536E-09]],
+    PARAMETER["Scale difference",0,SCALEUNIT["parts per billion",1E-09]],
+    PARAMETER["Rate of change of X-axis translation",0,
+        LENGTHUNIT["millimetres per year",3.16887651727315E-11]],
+    PARAMETER["Rate of change of Y-axis translation",0,
+        LENGTHUNIT["millimetres per year",3.16887651727315E-11]],
+    PARAMETER["Rate of change of Z-axis translation",0,
+        LENGTHUNIT["millimetres per year",3.16887651727315E-11]],
+    PARAMETER["Rate of change of X-axis rotation",0.085,
+        ANGLEUNIT["milliarc-seconds per year",1.53631468932076E-16]],
+    PARAMETER["Rate of change of Y-axis rotation",0.531,
+        ANGLEUNIT["milliarc-seconds per year",1.53631468932076E-16]],
+    PARAMETER["Rate of change of Z-axis rotation",-0.77,
+        ANGLEUNIT["milliarc-seconds per year",1.53631468932076E-16]],
+    PARAMETER["Rate of change of Scale difference",0,
+        SCALEUNIT["parts per billion per year",3.16887651727315E-17]],
+    PARAMETER["Parameter reference epoch",1989,TIMEUNIT["year",31556925.445]],
+    OPERATIONACCURACY[0.1],
+    USAGE[SCOPE["Geodesy."],AREA["Europe - onshore and offshore"],
+        BBOX[32.88,-16.1,84.73,40.18]],
+    ID["EPSG",8366]]"#;
+
+    fn epoch_2020() -> GpsTime {
+        UtcTime::from_date(2020, 1, 1, 0, 0, 0.).to_gps_hardcoded()
+    }
+
+    #[test]
+    fn matches_builtin_transformations() {
+        let builtin = TransformationRepository::new();
+        let coord = Coordinate::with_velocity(
+            ReferenceFrame::ITRF2020,
+            ECEF::new(4027893.0, 307045.0, 4919475.0),
+            ECEF::new(-0.0135, 0.0178, 0.0101),
+            epoch_2020(),
+        );
+        assert_eq!(
+            builtin.transform(&coord, ReferenceFrame::ETRF2000),
+            coord.transform_via(ReferenceFrame::ETRF2000, &TransformationGraph::new())
+        );
+        assert_eq!(
+            builtin.transform(&coord, ReferenceFrame::ITRF2020),
+            Ok(coord)
+        );
+
+        let empty = TransformationRepository::from_transformations(&[]);
+        assert_eq!(
+            empty.transform(&coord, ReferenceFrame::ETRF2000),
+            Err(TransformationNotFound(
+                ReferenceFrame::ITRF2020,
+                ReferenceFrame::ETRF2000
+            ))
+        );
+    }
+
+    #[test]
+    fn transform_with_covariance() {
+        let repository = TransformationRepository::new();
+        let coord = Coordinate::with_velocity(
+            ReferenceFrame::ITRF2020,
+            ECEF::new(4027893.0, 307045.0, 4919475.0),
+            ECEF::new(-0.0135, 0.0178, 0.0101),
+            epoch_2020(),
+        );
+        let mut covariance = [[0.0; 6]; 6];
+        for (i, row) in covariance.iter_mut().enumerate() {
+            row[i] = if i < 3 { 1.0e-4 } else { 1.0e-6 };
+        }
+        let covariance = CoordinateCovariance::PositionVelocity(covariance);
+
+        assert_eq!(
+            repository.transform_with_covariance(&coord, ReferenceFrame::ETRF2000, &covariance),
+            coord.transform_via_with_covariance(
+                ReferenceFrame::ETRF2000,
+                &TransformationGraph::new(),
+                &covariance
+            )
+        );
+        assert_eq!(
+            repository.transform_with_covariance(&coord, ReferenceFrame::ITRF2020, &covariance),
+            Ok((coord, covariance))
+        );
+        assert_eq!(
+            TransformationRepository::from_transformations(&[]).transform_with_covariance(
+                &coord,
+                ReferenceFrame::ETRF2000,
+                &covariance
+            ),
+            Err(TransformationNotFound(
+                ReferenceFrame::ITRF2020,
+                ReferenceFrame::ETRF2000
+            ))
+        );
+    }
+
+    #[test]
+    fn transform_to_epoch() {
+        let repository = TransformationRepository::new();
+        let epoch_2010 = UtcTime::from_date(2010, 1, 1, 0, 0, 0.).to_gps_hardcoded();
+        let coord = Coordinate::with_velocity(
+            ReferenceFrame::ITRF2014,
+            ECEF::new(-2703764.0, -4261273.0, 3887158.0),
+            ECEF::new(-0.221, 0.254, 0.122),
+            epoch_2020(),
+        );
+
+        let moved = repository
+            .transform_to_epoch(&coord, ReferenceFrame::NAD83_2011, &epoch_2010)
+            .unwrap();
+        assert_eq!(moved.epoch(), epoch_2010);
+        assert_eq!(moved.reference_frame(), ReferenceFrame::NAD83_2011);
+        // Moving before transforming gives the same position, to within the
+        // second order effects of the Helmert parameters
+        let expected = repository
+            .transform(&coord.adjust_epoch(&epoch_2010), ReferenceFrame::NAD83_2011)
+            .unwrap();
+        assert_float_eq!(
+            moved.position().as_array_ref(),
+            expected.position().as_array_ref(),
+            abs_all <= 1e-6
+        );
+        assert!((moved.position() - coord.position()).as_array_ref()[0].abs() > 2.0);
+
+        // Without a velocity only the epoch changes
+        let still =
+            Coordinate::without_velocity(ReferenceFrame::ITRF2014, coord.position(), epoch_2020());
+        let moved = repository
+            .transform_to_epoch(&still, ReferenceFrame::NAD83_2011, &epoch_2010)
+            .unwrap();
+        assert_eq!(moved.epoch(), epoch_2010);
+        assert_eq!(
+            moved.position(),
+            repository
+                .transform(&still, ReferenceFrame::NAD83_2011)
+                .unwrap()
+                .position()
+        );
+    }
+
+    #[test]
+    fn regional_standard() {
+        let repository = TransformationRepository::new();
+        let epoch_2010 = UtcTime::from_date(2010, 1, 1, 0, 0, 0.).to_gps_hardcoded();
+        let coord = Coordinate::with_velocity(
+            ReferenceFrame::ITRF2014,
+            ECEF::new(-2703764.0, -4261273.0, 3887158.0),
+            ECEF::new(-0.221, 0.254, 0.122),
+            epoch_2020(),
+        );
+
+        let nad83 = repository
+            .to_regional_standard(&coord, ReferenceFrame::NAD83_2011)
+            .unwrap();
+        assert_eq!(nad83.epoch(), epoch_2010);
+        let expected = repository
+            .transform_to_epoch(&coord, ReferenceFrame::NAD83_2011, &epoch_2010)
+            .unwrap();
+        assert_eq!(nad83, expected);
+        assert_eq!(
+            coord.to_regional_standard(ReferenceFrame::NAD83_2011),
+            Ok(expected)
+        );
+
+        // ETRF coordinates stay at the observation epoch
+        let etrf = repository
+            .to_regional_standard(&coord, ReferenceFrame::ETRF2014)
+            .unwrap();
+        assert_eq!(etrf.epoch(), coord.epoch());
+
+        let dref = ReferenceFrame::DREF91_R2016
+            .epoch_convention()
+            .epoch_for(&coord.epoch());
+        assert_float_eq!(
+            dref.to_utc_hardcoded().to_fractional_year(),
+            2016.456,
+            abs <= 0.005
+        );
+    }
+
+    #[test]
+    fn transform_with_plate_motion() {
+        let repository = TransformationRepository::new();
+        let epoch_2030 = UtcTime::from_date(2030, 1, 1, 0, 0, 0.).to_gps_hardcoded();
+        // A point in central Europe
+        let coord = Coordinate::without_velocity(
+            ReferenceFrame::ITRF2020,
+            ECEF::new(4027893.0, 307045.0, 4919475.0),
+            epoch_2020(),
+        );
+        let plate = PlateMotion::EURASIA_ITRF2014;
+        let speed = plate
+            .velocity_at(&coord.position())
+            .as_array_ref()
+            .iter()
+            .map(|v| v * v)
+            .sum::<f64>()
+            .sqrt();
+        assert!(speed > 0.02 && speed < 0.03, "{}", speed);
+
+        // The ETRF is fixed to the Eurasian plate, so points on it don't move
+        let moved = repository
+            .transform_to_epoch_with_plate(&coord, ReferenceFrame::ETRF2014, &epoch_2030, &plate)
+            .unwrap();
+        assert_eq!(moved.epoch(), epoch_2030);
+        let velocity = moved.velocity().unwrap();
+        assert_float_eq!(velocity.as_array_ref(), &[0.0; 3], abs_all <= 1e-3);
+
+        // but they do in the ITRF
+        let moved = repository
+            .transform_to_epoch_with_plate(&coord, ReferenceFrame::ITRF2020, &epoch_2030, &plate)
+            .unwrap();
+        let distance = (moved.position() - coord.position())
+            .as_array_ref()
+            .iter()
+            .map(|v| v * v)
+            .sum::<f64>()
+            .sqrt();
+        assert_float_eq!(distance, speed * 10.0, abs <= 0.01);
+
+        // The velocity of the coordinate takes precedence
+        let with_velocity = Coordinate::with_velocity(
+            coord.reference_frame(),
+            coord.position(),
+            ECEF::default(),
+            coord.epoch(),
+        );
+        let moved = repository
+            .transform_to_epoch_with_plate(
+                &with_velocity,
+                ReferenceFrame::ITRF2020,
+                &epoch_2030,
+                &plate,
+            )
+            .unwrap();
+        assert_eq!(moved.position(), coord.position());
+    }
+
+    #[test]
+    fn transform_batch() {
+        let mut repository = TransformationRepository::new();
+        // A made up set of parameters only valid during 2015, which is
+        // preferred over the built in ones then
+        let builtin = repository
+            .get_transformation(
+                ReferenceFrame::ITRF2014,
+                ReferenceFrame::ETRF2014,
+                &epoch_2020(),
+            )
+            .unwrap();
+        let mut temporary = builtin;
+        temporary.params.tx += 1000.0;
+        temporary = temporary.set_validity(ValidityInterval::new(Some(2015.0), Some(2016.0)));
+        repository.transformations.insert(0, temporary);
+
+        let epoch_2015 = UtcTime::from_date(2015, 6, 1, 0, 0, 0.).to_gps_hardcoded();
+        let position = ECEF::new(4027893.0, 307045.0, 4919475.0);
+        let velocity = ECEF::new(-0.0135, 0.0178, 0.0101);
+        let coords: Vec<Coordinate> = [
+            ReferenceFrame::ITRF2020,
+            ReferenceFrame::ITRF2014,
+            ReferenceFrame::ETRF2014,
+            ReferenceFrame::NAD83_2011,
+        ]
+        .iter()
+        .flat_map(|frame| {
+            vec![
+                Coordinate::with_velocity(*frame, position, velocity, epoch_2020()),
+                Coordinate::with_velocity(*frame, position, velocity, epoch_2015),
+                Coordinate::without_velocity(*frame, position, epoch_2020()),
+            ]
+        })
+        .collect();
+
+        let batch = repository
+            .transform_batch(&coords, ReferenceFrame::ETRF2014)
+            .unwrap();
+        assert_eq!(batch.len(), coords.len());
+        for (coord, transformed) in coords.iter().zip(batch.iter()) {
+            assert_eq!(
+                *transformed,
+                repository
+                    .transform(coord, ReferenceFrame::ETRF2014)
+                    .unwrap()
+            );
+        }
+        #[cfg(feature = "rayon")]
+        assert_eq!(
+            repository.par_transform_batch(&coords, ReferenceFrame::ETRF2014),
+            Ok(batch.clone())
+        );
+        // The parameters valid in 2015 were used for the 2015 coordinate
+        let shifted = batch[4].position() - builtin.transform(&coords[4]).position();
+        assert_float_eq!(shifted.x(), 1.0, abs <= 1e-9);
+
+        assert_eq!(
+            TransformationRepository::from_transformations(&[])
+                .transform_batch(&coords, ReferenceFrame::ETRF2014),
+            Err(TransformationNotFound(
+                ReferenceFrame::ITRF2020,
+                ReferenceFrame::ETRF2014
+            ))
+        );
+        assert_eq!(
+            repository.transform_batch(&[], ReferenceFrame::ETRF2014),
+            Ok(vec![])
+        );
+    }
+
+    #[test]
+    fn cached_paths() {
+        let itrf2014_to_etrf2014 =
+            super::super::get_transformation(ReferenceFrame::ITRF2014, ReferenceFrame::ETRF2014)
+                .unwrap();
+        let itrf2020_to_itrf2014 =
+            super::super::get_transformation(ReferenceFrame::ITRF2020, ReferenceFrame::ITRF2014)
+                .unwrap();
+        let mut repository = TransformationRepository::from_transformations(&[
+            itrf2014_to_etrf2014,
+            itrf2020_to_itrf2014,
+        ]);
+        let coord = Coordinate::without_velocity(
+            ReferenceFrame::ITRF2020,
+            ECEF::new(4027893.0, 307045.0, 4919475.0),
+            epoch_2020(),
+        );
+        let through_itrf2014 = repository
+            .transform(&coord, ReferenceFrame::ETRF2014)
+            .unwrap();
+        assert_eq!(
+            repository.transform(&coord, ReferenceFrame::ETRF2014),
+            Ok(through_itrf2014)
+        );
+        assert_eq!(repository.lock_chains().len(), 1);
+
+        // Adding a direct transformation gives a shorter path
+        let mut direct = itrf2020_to_itrf2014.then(&itrf2014_to_etrf2014).unwrap();
+        direct.params.tx += 1000.0;
+        repository.add_transformation(direct);
+        assert!(repository.lock_chains().is_empty());
+        let direct = repository
+            .transform(&coord, ReferenceFrame::ETRF2014)
+            .unwrap();
+        let shifted = direct.position() - through_itrf2014.position();
+        assert_float_eq!(shifted.x(), 1.0, abs <= 1e-6);
+
+        // The cache is copied along with the repository
+        assert_eq!(repository.clone().lock_chains().len(), 1);
+    }
+
+    #[test]
+    fn composed_transformations() {
+        let repository = TransformationRepository::new();
+        let epoch_2010 = UtcTime::from_date(2010, 1, 1, 0, 0, 0.).to_gps_hardcoded();
+        for (from, to) in [
+            (ReferenceFrame::ITRF2020, ReferenceFrame::ETRF2000),
+            (ReferenceFrame::ITRF2020, ReferenceFrame::NAD83_CSRS),
+            (ReferenceFrame::ETRF2014, ReferenceFrame::NAD83_2011),
+        ]
+        .iter()
+        {
+            let composed = repository
+                .get_composed_transformation(*from, *to, &epoch_2020())
+                .unwrap();
+            assert_eq!(composed.from, *from);
+            assert_eq!(composed.to, *to);
+            for epoch in [epoch_2010, epoch_2020()].iter() {
+                let coord = Coordinate::with_velocity(
+                    *from,
+                    ECEF::new(-2703764.0, -4261273.0, 3887158.0),
+                    ECEF::new(-0.0135, 0.0178, 0.0101),
+                    *epoch,
+                );
+                let expected = repository.transform(&coord, *to).unwrap();
+                let transformed = composed.transform(&coord);
+                assert_float_eq!(
+                    transformed.position().as_array_ref(),
+                    expected.position().as_array_ref(),
+                    abs_all <= 1e-4
+                );
+                assert_float_eq!(
+                    transformed.velocity().unwrap().as_array_ref(),
+                    expected.velocity().unwrap().as_array_ref(),
+                    abs_all <= 1e-6
+                );
+            }
+        }
+
+        assert_eq!(
+            TransformationRepository::from_transformations(&[]).get_composed_transformation(
+                ReferenceFrame::ITRF2020,
+                ReferenceFrame::ETRF2000,
+                &epoch_2020()
+            ),
+            Err(TransformationNotFound(
+                ReferenceFrame::ITRF2020,
+                ReferenceFrame::ETRF2000
+            ))
+        );
+    }
+
+    #[test]
+    fn load_wkt() {
+        let mut repository = TransformationRepository::from_transformations(&[]);
+        let loaded = repository.add_wkt(ITRF2014_TO_ETRF2014).unwrap();
+        let expected = TransformationRepository::new()
+            .get_transformation(
+                ReferenceFrame::ITRF2014,
+                ReferenceFrame::ETRF2014,
+                &epoch_2020(),
+            )
+            .unwrap();
+        assert_eq!(loaded.from, expected.from);
+        assert_eq!(loaded.to, expected.to);
+        let (loaded, expected) = (loaded.params, expected.params);
+        assert_float_eq!(loaded.tx, expected.tx, abs <= 1e-9);
+        assert_float_eq!(loaded.rx_dot, expected.rx_dot, abs <= 1e-9);
+        assert_float_eq!(loaded.ry_dot, expected.ry_dot, abs <= 1e-9);
+        assert_float_eq!(loaded.rz_dot, expected.rz_dot, abs <= 1e-9);
+        assert_float_eq!(loaded.s_dot, expected.s_dot, abs <= 1e-9);
+        assert_float_eq!(loaded.epoch, expected.epoch, abs <= 0.0);
+
+        let coord = Coordinate::without_velocity(
+            ReferenceFrame::ETRF2014,
+            ECEF::new(4027893.0, 307045.0, 4919475.0),
+            epoch_2020(),
+        );
+        assert!(repository
+            .transform(&coord, ReferenceFrame::ITRF2014)
+            .is_ok());
+
+        // The coordinate frame convention has the opposite rotations
+        let coordinate_frame = ITRF2014_TO_ETRF2014.replace(
+            "Time-dependent Position Vector tfm (geocentric)",
+            "Time-dependent Coordinate Frame rotation (geocen)",
+        );
+        let flipped = repository.add_wkt(&coordinate_frame).unwrap().params;
+        assert_float_eq!(flipped.rx_dot, -loaded.rx_dot, abs <= 1e-9);
+        assert_float_eq!(flipped.s_dot, loaded.s_dot, abs <= 1e-9);
+    }
+
+    #[test]
+    fn wkt_frame_aliases() {
+        let mut repository = TransformationRepository::from_transformations(&[]);
+        let renamed = ITRF2014_TO_ETRF2014.replace("GEODCRS[\"ETRF2014\"", "GEODCRS[\"ETRS89\"");
+        assert_eq!(
+            repository.add_wkt(&renamed),
+            Err(LoadError::UnknownFrame("ETRS89".to_string()))
+        );
+        assert!(repository.transformations().is_empty());
+
+        repository.add_alias("ETRS89", ReferenceFrame::ETRF2014);
+        assert_eq!(
+            repository.resolve_frame("ETRS89"),
+            Some(ReferenceFrame::ETRF2014)
+        );
+        assert_eq!(
+            repository.resolve_frame("NAD83(2011)"),
+            Some(ReferenceFrame::NAD83_2011)
+        );
+        assert_eq!(
+            repository.add_wkt(&renamed).unwrap().to,
+            ReferenceFrame::ETRF2014
+        );
+    }
+
+    #[test]
+    fn invalid_wkt() {
+        let mut repository = TransformationRepository::new();
+        assert_eq!(repository.add_wkt(""), Err(LoadError::InvalidWkt));
+        assert_eq!(
+            repository.add_wkt(&ITRF2014_TO_ETRF2014[..200]),
+            Err(LoadError::InvalidWkt)
+        );
+        assert_eq!(
+            repository.add_wkt("GEODCRS[\"ITRF2014\"]"),
+            Err(LoadError::InvalidWkt)
+        );
+        assert_eq!(
+            repository.add_wkt(&ITRF2014_TO_ETRF2014.replace(
+                "Time-dependent Position Vector tfm (geocentric)",
+                "Geocentric translations"
+            )),
+            Err(LoadError::UnsupportedMethod(
+                "Geocentric translations".to_string()
+            ))
+        );
+        assert_eq!(
+            repository
+                .add_wkt(&ITRF2014_TO_ETRF2014.replace("Y-axis rotation\",0,", "Y-axis spin\",0,")),
+            Err(LoadError::MissingParameter("Y-axis rotation".to_string()))
+        );
+        assert_eq!(
+            repository.add_wkt(&ITRF2014_TO_ETRF2014.replace(
+                "PARAMETER[\"Scale difference\",0,SCALEUNIT[\"parts per billion\",1E-09]]",
+                "PARAMETER[\"Scale difference\",0]"
+            )),
+            Err(LoadError::InvalidUnit("Scale difference".to_string()))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn load_definitions() {
+        let json = format!(
+            r#"{{
+                "aliases": {{"MY-FRAME": "ETRF2014", "EUROPE": "MY-FRAME"}},
+                "transformations": [{{
+                    "from": "ITRF2020",
+                    "to": "MY-FRAME",
+                    "tx": 10.0,
+                    "rz_dot": 0.5,
+                    "epoch": 2015.0,
+                    "valid_from": 2015.0
+                }}],
+                "wkt": [{}]
+            }}"#,
+            serde_json::to_string(ITRF2014_TO_ETRF2014).unwrap()
+        );
+        let definitions: TransformationDefinitions = serde_json::from_str(&json).unwrap();
+        let mut repository = TransformationRepository::from_transformations(&[]);
+        repository.add_definitions(&definitions).unwrap();
+        assert_eq!(repository.transformations().len(), 2);
+        assert_eq!(
+            repository.resolve_frame("EUROPE"),
+            Some(ReferenceFrame::ETRF2014)
+        );
+
+        let transformation = repository
+            .get_transformation(
+                ReferenceFrame::ITRF2020,
+                ReferenceFrame::ETRF2014,
+                &epoch_2020(),
+            )
+            .unwrap();
+        assert_float_eq!(transformation.params.tx, 10.0, abs <= 0.0);
+        assert_float_eq!(transformation.params.rz_dot, 0.5, abs <= 0.0);
+        assert_float_eq!(transformation.params.ty, 0.0, abs <= 0.0);
+        assert_eq!(transformation.validity().start(), Some(2015.0));
+
+        // Two steps, through ETRF2014
+        let coord = Coordinate::without_velocity(
+            ReferenceFrame::ITRF2020,
+            ECEF::new(4027893.0, 307045.0, 4919475.0),
+            epoch_2020(),
+        );
+        assert!(repository
+            .transform(&coord, ReferenceFrame::ITRF2014)
+            .is_ok());
+
+        // Nothing is added when a definition fails
+        let mut definitions = definitions;
+        definitions.transformations[0].to = "NOWHERE".to_string();
+        let mut empty = TransformationRepository::from_transformations(&[]);
+        assert_eq!(
+            empty.add_definitions(&definitions),
+            Err(LoadError::UnknownFrame("NOWHERE".to_string()))
+        );
+        assert!(empty.transformations().is_empty());
+        assert_eq!(empty.resolve_frame("EUROPE"), None);
+    }
+}
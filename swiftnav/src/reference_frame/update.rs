@@ -0,0 +1,436 @@
+// Copyright (c) 2024 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Updating transformation parameters at run time
+//!
+//! Transformation parameters are revised and extended from time to time, and
+//! deployed devices may need the new parameters before they get a new version
+//! of this crate. A [`ParameterUpdater`] fetches a [`ParameterSet`] from a
+//! [`ParameterSource`], checks its integrity, and installs it into a
+//! [`TransformationRepository`] when it is newer than the set installed
+//! before. The transformations of the set are preferred over the ones of the
+//! repository between the same frames.
+//!
+//! This crate doesn't include a network client, sources fetching parameter
+//! sets from a server can be made by implementing [`ParameterSource`], which
+//! is also implemented for closures. [`FileSource`] reads a set from a file,
+//! such as a local mirror kept up to date by other means.
+//!
+//! The frames of the transformations must be [`ReferenceFrame`]s known to
+//! this crate, or aliases of them in the repository the set is installed
+//! into.
+//!
+//! # Format
+//!
+//! A parameter set is a text file made of a header and a list of WKT2
+//! coordinate operations, as accepted by
+//! [`TransformationRepository::add_wkt`], separated by an empty line:
+//!
+//! ```text
+//! serial: 3
+//! label: EUREF 2026 update
+//! crc24q: 5e1d0a
+//!
+//! COORDINATEOPERATION["ITRF2020 to ETRF2020", ...]
+//! COORDINATEOPERATION["ITRF2014 to ETRF2014", ...]
+//! ```
+//!
+//! The serial number orders the sets, a set is only installed if its serial
+//! number is larger than the one of the installed set. The label is optional.
+//! The checksum is the CRC-24Q of everything after the empty line, as a
+//! hexadecimal number. It catches corrupted or truncated downloads, but isn't
+//! a signature, so sets should be fetched over an authenticated connection.
+//! Other header fields are ignored.
+//!
+//! [`ReferenceFrame`]: super::ReferenceFrame
+
+use super::repository::LoadError;
+use super::{wkt, Transformation, TransformationRepository};
+use crate::edc::compute_crc24q;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Errors which can occur when updating transformation parameters
+#[derive(Debug)]
+pub enum UpdateError {
+    /// The parameter set couldn't be fetched
+    Fetch(io::Error),
+    /// The header of the parameter set is malformed or incomplete
+    InvalidFormat,
+    /// The checksum of the parameter set doesn't match its contents, with
+    /// the checksum of the header and the one computed returned
+    ChecksumMismatch { expected: u32, computed: u32 },
+    /// A transformation of the parameter set couldn't be loaded
+    Load(LoadError),
+}
+
+impl fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdateError::Fetch(error) => write!(f, "Couldn't fetch parameters: {}", error),
+            UpdateError::InvalidFormat => write!(f, "Invalid parameter set header"),
+            UpdateError::ChecksumMismatch { expected, computed } => write!(
+                f,
+                "Parameter set checksum mismatch (expected {:06x}, computed {:06x})",
+                expected, computed
+            ),
+            UpdateError::Load(error) => write!(f, "Invalid transformation: {}", error),
+        }
+    }
+}
+
+impl Error for UpdateError {}
+
+impl From<LoadError> for UpdateError {
+    fn from(error: LoadError) -> UpdateError {
+        UpdateError::Load(error)
+    }
+}
+
+/// The version of a parameter set
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ParameterVersion {
+    serial: u32,
+    label: String,
+}
+
+impl ParameterVersion {
+    pub fn new(serial: u32, label: &str) -> ParameterVersion {
+        ParameterVersion {
+            serial,
+            label: label.to_string(),
+        }
+    }
+
+    /// Gets the serial number, which increases with each new set
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+
+    /// Gets the description of the set, which may be empty
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+impl fmt::Display for ParameterVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.label.is_empty() {
+            write!(f, "#{}", self.serial)
+        } else {
+            write!(f, "#{} ({})", self.serial, self.label)
+        }
+    }
+}
+
+/// A set of transformations with its version, see the [module
+/// documentation](self) for the format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParameterSet {
+    version: ParameterVersion,
+    operations: Vec<String>,
+}
+
+impl ParameterSet {
+    /// Parses a parameter set and checks its checksum
+    ///
+    /// The operations are only split apart, they are parsed when the set is
+    /// installed since their frames can be aliases of the repository.
+    pub fn from_bytes(bytes: &[u8]) -> Result<ParameterSet, UpdateError> {
+        let text = std::str::from_utf8(bytes).map_err(|_| UpdateError::InvalidFormat)?;
+
+        let mut serial = None;
+        let mut label = "";
+        let mut checksum = None;
+        let mut offset = 0;
+        loop {
+            let end = text[offset..]
+                .find('\n')
+                .map(|i| offset + i + 1)
+                .ok_or(UpdateError::InvalidFormat)?;
+            let line = text[offset..end].trim();
+            offset = end;
+            if line.is_empty() {
+                break;
+            }
+            let (key, value) = line.split_once(':').ok_or(UpdateError::InvalidFormat)?;
+            let value = value.trim();
+            match key.trim() {
+                "serial" => serial = Some(value.parse().map_err(|_| UpdateError::InvalidFormat)?),
+                "label" => label = value,
+                "crc24q" => {
+                    checksum = Some(
+                        u32::from_str_radix(value, 16).map_err(|_| UpdateError::InvalidFormat)?,
+                    )
+                }
+                _ => {}
+            }
+        }
+        let (serial, expected) = match (serial, checksum) {
+            (Some(serial), Some(checksum)) => (serial, checksum),
+            _ => return Err(UpdateError::InvalidFormat),
+        };
+
+        let body = &text[offset..];
+        let computed = compute_crc24q(body.as_bytes(), 0);
+        if computed != expected {
+            return Err(UpdateError::ChecksumMismatch { expected, computed });
+        }
+        let operations = wkt::split(body)?.into_iter().map(str::to_string).collect();
+        Ok(ParameterSet {
+            version: ParameterVersion::new(serial, label),
+            operations,
+        })
+    }
+
+    pub fn version(&self) -> &ParameterVersion {
+        &self.version
+    }
+
+    /// Gets the WKT2 coordinate operations of the set
+    pub fn operations(&self) -> &[String] {
+        &self.operations
+    }
+
+    /// Parses the transformations of the set, resolving the frame names with
+    /// the aliases of the repository
+    pub fn transformations(
+        &self,
+        repository: &TransformationRepository,
+    ) -> Result<Vec<Transformation>, LoadError> {
+        self.operations
+            .iter()
+            .map(|operation| {
+                wkt::parse_transformation(operation, |name| repository.resolve_frame(name))
+            })
+            .collect()
+    }
+}
+
+/// A place parameter sets can be fetched from
+pub trait ParameterSource {
+    /// Fetches the current parameter set
+    fn fetch(&self) -> io::Result<Vec<u8>>;
+}
+
+impl<F> ParameterSource for F
+where
+    F: Fn() -> io::Result<Vec<u8>>,
+{
+    fn fetch(&self) -> io::Result<Vec<u8>> {
+        self()
+    }
+}
+
+/// Reads parameter sets from a file
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileSource {
+    path: PathBuf,
+}
+
+impl FileSource {
+    pub fn new<P: Into<PathBuf>>(path: P) -> FileSource {
+        FileSource { path: path.into() }
+    }
+}
+
+impl ParameterSource for FileSource {
+    fn fetch(&self) -> io::Result<Vec<u8>> {
+        fs::read(&self.path)
+    }
+}
+
+/// Result of a successful update
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateStatus {
+    /// A new parameter set was installed
+    Updated(ParameterVersion),
+    /// The fetched parameter set isn't newer than the installed one
+    UpToDate,
+}
+
+/// Keeps a transformation repository up to date with the parameter sets of a
+/// source
+///
+/// Each new set replaces the previous one, the transformations of the
+/// repository are those of the base repository together with the ones of the
+/// newest set.
+#[derive(Debug, Clone)]
+pub struct ParameterUpdater<S> {
+    source: S,
+    base: TransformationRepository,
+    installed: Option<ParameterVersion>,
+}
+
+impl<S: ParameterSource> ParameterUpdater<S> {
+    /// Makes an updater adding the parameter sets to the transformations
+    /// built into the crate
+    pub fn new(source: S) -> ParameterUpdater<S> {
+        ParameterUpdater {
+            source,
+            base: TransformationRepository::new(),
+            installed: None,
+        }
+    }
+
+    /// Sets the repository the parameter sets are added to
+    pub fn set_base(self, base: TransformationRepository) -> Self {
+        ParameterUpdater { base, ..self }
+    }
+
+    /// Gets the version of the installed parameter set, if any
+    pub fn installed(&self) -> Option<&ParameterVersion> {
+        self.installed.as_ref()
+    }
+
+    /// Fetches the parameter set from the source and installs it if it's
+    /// newer than the installed one
+    ///
+    /// The repository is left untouched if anything fails.
+    pub fn update(
+        &mut self,
+        repository: &mut TransformationRepository,
+    ) -> Result<UpdateStatus, UpdateError> {
+        let bytes = self.source.fetch().map_err(UpdateError::Fetch)?;
+        let set = ParameterSet::from_bytes(&bytes)?;
+        self.install(&set, repository)
+    }
+
+    /// Installs a parameter set into the repository if it's newer than the
+    /// installed one
+    pub fn install(
+        &mut self,
+        set: &ParameterSet,
+        repository: &mut TransformationRepository,
+    ) -> Result<UpdateStatus, UpdateError> {
+        if let Some(installed) = &self.installed {
+            if installed.serial >= set.version.serial {
+                return Ok(UpdateStatus::UpToDate);
+            }
+        }
+        let transformations = set.transformations(&self.base)?;
+        *repository = self.base.with_preferred(&transformations);
+        self.installed = Some(set.version.clone());
+        Ok(UpdateStatus::Updated(set.version.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::{Coordinate, ECEF};
+    use crate::reference_frame::ReferenceFrame;
+    use crate::time::GpsTime;
+    use float_eq::assert_float_eq;
+
+    /// ITRF2014 to ETRF2014 with only a translation along the X axis
+    fn operation(tx: f64) -> String {
+        format!(
+            r#"COORDINATEOPERATION["ITRF2014 to ETRF2014",
+    SOURCECRS[GEODCRS["ITRF2014"]],
+    TARGETCRS[GEODCRS["ETRF2014"]],
+    METHOD["Time-dependent Position Vector tfm (geocentric)"],
+    PARAMETER["X-axis translation",{},LENGTHUNIT["millimetre",0.001]],
+    PARAMETER["Y-axis translation",0,LENGTHUNIT["millimetre",0.001]],
+    PARAMETER["Z-axis translation",0,LENGTHUNIT["millimetre",0.001]],
+    PARAMETER["X-axis rotation",0,ANGLEUNIT["milliarc-second",4.84813681109536E-09]],
+    PARAMETER["Y-axis rotation",0,ANGLEUNIT["milliarc-second",4.84813681109536E-09]],
+    PARAMETER["Z-axis rotation",0,ANGLEUNIT["milliarc-second",4.84813681109536E-09]],
+    PARAMETER["Scale difference",0,SCALEUNIT["parts per billion",1E-09]]]
+"#,
+            tx
+        )
+    }
+
+    fn parameter_set(serial: u32, tx: f64) -> Vec<u8> {
+        let body = format!("{}\n{}", operation(tx), operation(tx));
+        format!(
+            "serial: {}\nlabel: test\ncrc24q: {:06x}\n\n{}",
+            serial,
+            compute_crc24q(body.as_bytes(), 0),
+            body
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn parse_set() {
+        let set = ParameterSet::from_bytes(&parameter_set(7, 1000.0)).unwrap();
+        assert_eq!(set.version(), &ParameterVersion::new(7, "test"));
+        assert_eq!(set.version().to_string(), "#7 (test)");
+        assert_eq!(set.operations().len(), 2);
+
+        let mut corrupted = parameter_set(7, 1000.0);
+        let last = corrupted.len() - 3;
+        corrupted[last] = b')';
+        assert!(matches!(
+            ParameterSet::from_bytes(&corrupted),
+            Err(UpdateError::ChecksumMismatch { .. })
+        ));
+        assert!(matches!(
+            ParameterSet::from_bytes(b"label: no serial\ncrc24q: 0\n\n"),
+            Err(UpdateError::InvalidFormat)
+        ));
+    }
+
+    #[test]
+    fn update_repository() {
+        let serial = std::cell::Cell::new(1);
+        let source = || -> io::Result<Vec<u8>> {
+            Ok(parameter_set(serial.get(), 1000.0 * serial.get() as f64))
+        };
+        let base = TransformationRepository::from_transformations(&[]);
+        let mut updater = ParameterUpdater::new(source).set_base(base);
+        let mut repository = TransformationRepository::from_transformations(&[]);
+
+        let coord = Coordinate::without_velocity(
+            ReferenceFrame::ITRF2014,
+            ECEF::new(4027893.0, 307045.0, 4919475.0),
+            GpsTime::new(2200, 0.0).unwrap(),
+        );
+        assert!(repository
+            .transform(&coord, ReferenceFrame::ETRF2014)
+            .is_err());
+
+        assert_eq!(
+            updater.update(&mut repository).unwrap(),
+            UpdateStatus::Updated(ParameterVersion::new(1, "test"))
+        );
+        let transformed = repository
+            .transform(&coord, ReferenceFrame::ETRF2014)
+            .unwrap();
+        assert_float_eq!(transformed.position().x(), 4027894.0, abs <= 1e-6);
+        assert_eq!(
+            updater.update(&mut repository).unwrap(),
+            UpdateStatus::UpToDate
+        );
+
+        // A newer set replaces the transformations of the previous one
+        serial.set(2);
+        updater.update(&mut repository).unwrap();
+        assert_eq!(updater.installed().map(|v| v.serial()), Some(2));
+        assert_eq!(repository.transformations().len(), 2);
+        let transformed = repository
+            .transform(&coord, ReferenceFrame::ETRF2014)
+            .unwrap();
+        assert_float_eq!(transformed.position().x(), 4027895.0, abs <= 1e-6);
+
+        let mut failing = ParameterUpdater::new(|| -> io::Result<Vec<u8>> {
+            Err(io::Error::from(io::ErrorKind::NotFound))
+        });
+        assert!(matches!(
+            failing.update(&mut repository),
+            Err(UpdateError::Fetch(_))
+        ));
+        assert_eq!(repository.transformations().len(), 2);
+    }
+}
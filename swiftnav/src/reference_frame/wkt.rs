@@ -0,0 +1,373 @@
+// Copyright (c) 2024 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Parsing of WKT2 coordinate operations
+//!
+//! # References
+//!   * ISO 19162:2019, "Geographic information - Well-known text
+//!     representation of coordinate reference systems"
+//!   * IOGP Publication 373-7-2, "Geomatics Guidance Note number 7, part 2",
+//!     Section 4.4.3
+
+use super::repository::LoadError;
+use super::{ReferenceFrame, TimeDependentHelmertParams, Transformation};
+
+/// Length of the year used by the EPSG dataset for rates, in seconds
+const SECONDS_PER_YEAR: f64 = 31_556_925.445;
+/// Deepest nesting of nodes accepted
+const MAX_DEPTH: usize = 32;
+
+/// A value of a WKT node
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Node(Node),
+    Text(String),
+    Number(f64),
+}
+
+/// A WKT node, i.e. a keyword followed by a bracketed list of values
+#[derive(Debug, Clone, PartialEq)]
+struct Node {
+    keyword: String,
+    values: Vec<Value>,
+}
+
+impl Node {
+    fn nodes<'a>(&'a self, keyword: &'a str) -> impl Iterator<Item = &'a Node> + 'a {
+        self.values.iter().filter_map(move |value| match value {
+            Value::Node(node) if node.keyword.eq_ignore_ascii_case(keyword) => Some(node),
+            _ => None,
+        })
+    }
+
+    fn node<'a>(&'a self, keyword: &'a str) -> Option<&'a Node> {
+        self.nodes(keyword).next()
+    }
+
+    fn text(&self) -> Option<&str> {
+        self.values.iter().find_map(|value| match value {
+            Value::Text(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    fn number(&self) -> Option<f64> {
+        self.values.iter().find_map(|value| match value {
+            Value::Number(number) => Some(*number),
+            _ => None,
+        })
+    }
+
+    /// Finds a parameter by name
+    fn parameter(&self, name: &str) -> Option<&Node> {
+        self.nodes("PARAMETER")
+            .find(|p| matches!(p.text(), Some(n) if n.eq_ignore_ascii_case(name)))
+    }
+
+    /// Gets the unit conversion factor of a parameter
+    fn unit_factor(&self) -> Option<f64> {
+        self.values.iter().find_map(|value| match value {
+            Value::Node(node) if node.keyword.to_ascii_uppercase().ends_with("UNIT") => {
+                node.number()
+            }
+            _ => None,
+        })
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.text[self.position..];
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.text[self.position..].chars().next()
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> &'a str {
+        let rest = &self.text[self.position..];
+        let end = rest.find(|c| !f(c)).unwrap_or(rest.len());
+        self.position += end;
+        &rest[..end]
+    }
+
+    fn node(&mut self, keyword: &str) -> Result<Node, LoadError> {
+        // Both square and round brackets are allowed
+        let close = match self.peek() {
+            Some('[') => ']',
+            Some('(') => ')',
+            _ => return Err(LoadError::InvalidWkt),
+        };
+        self.position += 1;
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(LoadError::InvalidWkt);
+        }
+        let mut values = Vec::new();
+        if self.peek() == Some(close) {
+            self.position += 1;
+        } else {
+            loop {
+                values.push(self.value()?);
+                match self.peek() {
+                    Some(',') => self.position += 1,
+                    Some(c) if c == close => {
+                        self.position += 1;
+                        break;
+                    }
+                    _ => return Err(LoadError::InvalidWkt),
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(Node {
+            keyword: keyword.to_string(),
+            values,
+        })
+    }
+
+    fn value(&mut self) -> Result<Value, LoadError> {
+        match self.peek() {
+            Some('"') => {
+                self.position += 1;
+                // Quotes within the text are doubled
+                let mut text = String::new();
+                loop {
+                    let part = self.take_while(|c| c != '"');
+                    text.push_str(part);
+                    if self.position >= self.text.len() {
+                        return Err(LoadError::InvalidWkt);
+                    }
+                    self.position += 1;
+                    if self.text[self.position..].starts_with('"') {
+                        text.push('"');
+                        self.position += 1;
+                    } else {
+                        return Ok(Value::Text(text));
+                    }
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                let number = self.take_while(|c| {
+                    c.is_ascii_digit() || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E'
+                });
+                number
+                    .parse()
+                    .map(Value::Number)
+                    .map_err(|_| LoadError::InvalidWkt)
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let keyword = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                match self.peek() {
+                    Some('[') | Some('(') => Ok(Value::Node(self.node(keyword)?)),
+                    // Enumerated values, such as axis directions
+                    _ => Ok(Value::Text(keyword.to_string())),
+                }
+            }
+            _ => Err(LoadError::InvalidWkt),
+        }
+    }
+}
+
+fn parse(text: &str) -> Result<Node, LoadError> {
+    let mut parser = Parser {
+        text,
+        position: 0,
+        depth: 0,
+    };
+    parser.skip_whitespace();
+    let keyword = parser.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+    let node = parser.node(keyword)?;
+    if parser.peek().is_some() {
+        return Err(LoadError::InvalidWkt);
+    }
+    Ok(node)
+}
+
+/// Splits a text holding several WKT strings one after the other
+pub(super) fn split(text: &str) -> Result<Vec<&str>, LoadError> {
+    let mut parser = Parser {
+        text,
+        position: 0,
+        depth: 0,
+    };
+    let mut parts = Vec::new();
+    while parser.peek().is_some() {
+        let start = parser.position;
+        let keyword = parser.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        if keyword.is_empty() {
+            return Err(LoadError::InvalidWkt);
+        }
+        parser.node(keyword)?;
+        parts.push(&text[start..parser.position]);
+    }
+    Ok(parts)
+}
+
+/// Parses a WKT2 Helmert coordinate operation
+pub(super) fn parse_transformation<F>(
+    text: &str,
+    resolve_frame: F,
+) -> Result<Transformation, LoadError>
+where
+    F: Fn(&str) -> Option<ReferenceFrame>,
+{
+    let operation = parse(text)?;
+    if !operation
+        .keyword
+        .eq_ignore_ascii_case("COORDINATEOPERATION")
+    {
+        return Err(LoadError::InvalidWkt);
+    }
+
+    let frame = |keyword: &str| {
+        let name = operation
+            .node(keyword)
+            .and_then(|crs| crs.values.first())
+            .and_then(|crs| match crs {
+                Value::Node(crs) => crs.text(),
+                _ => None,
+            })
+            .ok_or(LoadError::InvalidWkt)?;
+        resolve_frame(name).ok_or_else(|| LoadError::UnknownFrame(name.to_string()))
+    };
+    let from = frame("SOURCECRS")?;
+    let to = frame("TARGETCRS")?;
+
+    let method = operation
+        .node("METHOD")
+        .and_then(Node::text)
+        .ok_or(LoadError::InvalidWkt)?;
+    let lowercase = method.to_ascii_lowercase();
+    // The crate uses the position vector convention
+    let rotation_sign = if lowercase.contains("position vector") {
+        1.0
+    } else if lowercase.contains("coordinate frame") {
+        -1.0
+    } else {
+        return Err(LoadError::UnsupportedMethod(method.to_string()));
+    };
+
+    // Gets a parameter converted to the units of the crate, or `None` if the
+    // parameter isn't given
+    let parameter = |name: &str, scale: f64| -> Result<Option<f64>, LoadError> {
+        let parameter = match operation.parameter(name) {
+            Some(parameter) => parameter,
+            None => return Ok(None),
+        };
+        let value = parameter
+            .number()
+            .ok_or_else(|| LoadError::MissingParameter(name.to_string()))?;
+        let factor = parameter
+            .unit_factor()
+            .filter(|factor| factor.is_finite() && *factor > 0.0)
+            .ok_or_else(|| LoadError::InvalidUnit(name.to_string()))?;
+        Ok(Some(value * factor / scale))
+    };
+    let required = |name: &str, scale: f64| {
+        parameter(name, scale)?.ok_or_else(|| LoadError::MissingParameter(name.to_string()))
+    };
+    let rate = |name: &str, scale: f64| {
+        Ok::<_, LoadError>(
+            parameter(
+                &format!("Rate of change of {}", name),
+                scale / SECONDS_PER_YEAR,
+            )?
+            .unwrap_or(0.0),
+        )
+    };
+
+    let translation = TimeDependentHelmertParams::TRANSLATE_SCALE;
+    let rotation = TimeDependentHelmertParams::ROTATE_SCALE;
+    let scale = TimeDependentHelmertParams::SCALE_SCALE;
+    let mut params = TimeDependentHelmertParams {
+        tx: required("X-axis translation", translation)?,
+        tx_dot: rate("X-axis translation", translation)?,
+        ty: required("Y-axis translation", translation)?,
+        ty_dot: rate("Y-axis translation", translation)?,
+        tz: required("Z-axis translation", translation)?,
+        tz_dot: rate("Z-axis translation", translation)?,
+        s: required("Scale difference", scale)?,
+        s_dot: rate("Scale difference", scale)?,
+        rx: rotation_sign * required("X-axis rotation", rotation)?,
+        rx_dot: rotation_sign * rate("X-axis rotation", rotation)?,
+        ry: rotation_sign * required("Y-axis rotation", rotation)?,
+        ry_dot: rotation_sign * rate("Y-axis rotation", rotation)?,
+        rz: rotation_sign * required("Z-axis rotation", rotation)?,
+        rz_dot: rotation_sign * rate("Z-axis rotation", rotation)?,
+        epoch: 0.0,
+    };
+    // The epoch is in years whatever the factor of its unit
+    let epoch = operation.parameter("Parameter reference epoch");
+    match epoch.and_then(Node::number) {
+        Some(epoch) => params.epoch = epoch,
+        None => {
+            let rates = [
+                params.tx_dot,
+                params.ty_dot,
+                params.tz_dot,
+                params.s_dot,
+                params.rx_dot,
+                params.ry_dot,
+                params.rz_dot,
+            ];
+            if rates.iter().any(|rate| *rate != 0.0) {
+                return Err(LoadError::MissingParameter(
+                    "Parameter reference epoch".to_string(),
+                ));
+            }
+        }
+    }
+
+    Ok(Transformation::new(from, to, params))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_nodes() {
+        let node = parse(r#" A["x ""y""", 1.5e3, B(-2), c, D[] ] "#).unwrap();
+        assert_eq!(node.keyword, "A");
+        assert_eq!(node.text(), Some("x \"y\""));
+        assert_eq!(node.number(), Some(1500.0));
+        assert_eq!(node.node("b").unwrap().number(), Some(-2.0));
+        assert_eq!(node.values[3], Value::Text("c".to_string()));
+        assert!(node.node("D").unwrap().values.is_empty());
+
+        let nested = format!("{}1{}", "A[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        assert!(parse(&nested).is_ok());
+        let nested = format!("A[{}]", nested);
+        assert_eq!(parse(&nested), Err(LoadError::InvalidWkt));
+
+        for invalid in [
+            "",
+            "A",
+            "A[",
+            "A[1,]",
+            "A[\"x]",
+            "A[1] B[2]",
+            "A[1)",
+            "A[--]",
+        ]
+        .iter()
+        {
+            assert_eq!(parse(invalid), Err(LoadError::InvalidWkt), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,267 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Routes between two points
+//!
+//! Points can be sampled along two kinds of route:
+//!  * Great circle - the shortest route between the points, the heading
+//!    changes along the route
+//!  * Rhumb line - a route with a constant heading, which crosses all meridians
+//!    at the same angle
+//!
+//! Both routes are computed on a sphere with the mean radius of the WGS84
+//! ellipsoid, which is accurate to around 0.5% in distance. This is plenty for
+//! generating test trajectories and planning, but not for surveying. The
+//! height is interpolated linearly along the route.
+//!
+//! # References
+//!   * "Calculate distance, bearing and more between Latitude/Longitude
+//!     points", C. Veness, <https://www.movable-type.co.uk/scripts/latlong.html>
+
+use crate::coords::LLHDegrees;
+use std::f64::consts::{FRAC_PI_4, PI};
+
+/// Mean radius of the WGS84 ellipsoid, in meters
+pub const MEAN_EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// The kind of path a route follows
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum RouteKind {
+    /// The shortest path over the surface of the sphere
+    GreatCircle,
+    /// The path with a constant heading
+    RhumbLine,
+}
+
+/// A route from one point to another
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct Route {
+    start: LLHDegrees,
+    end: LLHDegrees,
+    kind: RouteKind,
+}
+
+impl Route {
+    pub fn new(start: LLHDegrees, end: LLHDegrees, kind: RouteKind) -> Route {
+        Route { start, end, kind }
+    }
+
+    /// Makes a great circle route
+    pub fn great_circle(start: LLHDegrees, end: LLHDegrees) -> Route {
+        Route::new(start, end, RouteKind::GreatCircle)
+    }
+
+    /// Makes a rhumb line route
+    pub fn rhumb_line(start: LLHDegrees, end: LLHDegrees) -> Route {
+        Route::new(start, end, RouteKind::RhumbLine)
+    }
+
+    pub fn start(&self) -> LLHDegrees {
+        self.start
+    }
+
+    pub fn end(&self) -> LLHDegrees {
+        self.end
+    }
+
+    pub fn kind(&self) -> RouteKind {
+        self.kind
+    }
+
+    /// Gets the length of the route over the surface, in meters
+    pub fn distance(&self) -> f64 {
+        let (lat1, lon1, lat2, lon2) = self.radians();
+        match self.kind {
+            RouteKind::GreatCircle => MEAN_EARTH_RADIUS * central_angle(lat1, lon1, lat2, lon2),
+            RouteKind::RhumbLine => {
+                let (dlat, dlon, q) = rhumb_terms(lat1, lon1, lat2, lon2);
+                MEAN_EARTH_RADIUS * (dlat * dlat + q * q * dlon * dlon).sqrt()
+            }
+        }
+    }
+
+    /// Gets the heading at the start of the route, in degrees clockwise from
+    /// north in the range [0, 360)
+    ///
+    /// The heading of a rhumb line is the same along the whole route
+    pub fn initial_bearing(&self) -> f64 {
+        let (lat1, lon1, lat2, lon2) = self.radians();
+        let bearing = match self.kind {
+            RouteKind::GreatCircle => {
+                let dlon = lon2 - lon1;
+                (dlon.sin() * lat2.cos())
+                    .atan2(lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos())
+            }
+            RouteKind::RhumbLine => {
+                let dlon = wrap_longitude(lon2 - lon1);
+                dlon.atan2(mercator(lat2) - mercator(lat1))
+            }
+        };
+        bearing.to_degrees().rem_euclid(360.0)
+    }
+
+    /// Gets the point a fraction of the way along the route
+    ///
+    /// A fraction of 0 gives the start of the route and 1 gives the end
+    pub fn point_at(&self, fraction: f64) -> LLHDegrees {
+        let (lat1, lon1, lat2, lon2) = self.radians();
+        let (lat, lon) = match self.kind {
+            RouteKind::GreatCircle => {
+                let delta = central_angle(lat1, lon1, lat2, lon2);
+                let bearing = self.initial_bearing().to_radians();
+                let d = fraction * delta;
+                let lat = (lat1.sin() * d.cos() + lat1.cos() * d.sin() * bearing.cos()).asin();
+                let lon = lon1
+                    + (bearing.sin() * d.sin() * lat1.cos())
+                        .atan2(d.cos() - lat1.sin() * lat.sin());
+                (lat, lon)
+            }
+            RouteKind::RhumbLine => {
+                let (dlat, dlon, _) = rhumb_terms(lat1, lon1, lat2, lon2);
+                let lat = lat1 + fraction * dlat;
+                let dpsi = mercator(lat2) - mercator(lat1);
+                let lon = if dpsi.abs() > 1e-12 {
+                    lon1 + dlon * (mercator(lat) - mercator(lat1)) / dpsi
+                } else {
+                    lon1 + fraction * dlon
+                };
+                (lat, lon)
+            }
+        };
+        let height = self.start.height() + fraction * (self.end.height() - self.start.height());
+        LLHDegrees::new(lat.to_degrees(), wrap_longitude(lon).to_degrees(), height)
+    }
+
+    /// Samples points along the route, no more than `spacing` meters apart
+    ///
+    /// The points are evenly spaced and include both the start and the end of
+    /// the route.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `spacing` isn't positive
+    pub fn sample(&self, spacing: f64) -> Vec<LLHDegrees> {
+        assert!(spacing > 0.0, "Spacing must be positive");
+        let segments = ((self.distance() / spacing).ceil() as usize).max(1);
+        (0..=segments)
+            .map(|i| self.point_at(i as f64 / segments as f64))
+            .collect()
+    }
+
+    fn radians(&self) -> (f64, f64, f64, f64) {
+        (
+            self.start.latitude().to_radians(),
+            self.start.longitude().to_radians(),
+            self.end.latitude().to_radians(),
+            self.end.longitude().to_radians(),
+        )
+    }
+}
+
+/// Angle between two points as seen from the center of the sphere, using the
+/// haversine formula
+fn central_angle(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    2.0 * a.sqrt().min(1.0).asin()
+}
+
+/// Isometric latitude, the northing of the mercator projection
+fn mercator(lat: f64) -> f64 {
+    (FRAC_PI_4 + lat / 2.0).tan().ln()
+}
+
+/// Gets the latitude and longitude changes along a rhumb line, and the
+/// stretch factor between them
+fn rhumb_terms(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> (f64, f64, f64) {
+    let dlat = lat2 - lat1;
+    let dlon = wrap_longitude(lon2 - lon1);
+    let dpsi = mercator(lat2) - mercator(lat1);
+    let q = if dpsi.abs() > 1e-12 {
+        dlat / dpsi
+    } else {
+        lat1.cos()
+    };
+    (dlat, dlon, q)
+}
+
+/// Wraps a longitude, in radians, into the range [-π, π)
+fn wrap_longitude(lon: f64) -> f64 {
+    (lon + PI).rem_euclid(2.0 * PI) - PI
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn great_circle() {
+        let route = Route::great_circle(
+            LLHDegrees::new(0.0, 0.0, 0.0),
+            LLHDegrees::new(0.0, 1.0, 100.0),
+        );
+        assert_float_eq!(route.distance(), 111_195.08, abs <= 0.01);
+        assert_float_eq!(route.initial_bearing(), 90.0, abs <= 1e-9);
+
+        let midpoint = route.point_at(0.5);
+        assert_float_eq!(midpoint.latitude(), 0.0, abs <= 1e-9);
+        assert_float_eq!(midpoint.longitude(), 0.5, abs <= 1e-9);
+        assert_float_eq!(midpoint.height(), 50.0, abs <= 1e-9);
+
+        // Great circles bulge towards the pole
+        let route = Route::great_circle(
+            LLHDegrees::new(45.0, 0.0, 0.0),
+            LLHDegrees::new(45.0, 90.0, 0.0),
+        );
+        assert_float_eq!(route.point_at(0.5).latitude(), 54.735_610, abs <= 1e-6);
+        assert_float_eq!(route.point_at(1.0).longitude(), 90.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn rhumb_line() {
+        let route = Route::rhumb_line(
+            LLHDegrees::new(45.0, 0.0, 0.0),
+            LLHDegrees::new(45.0, 90.0, 0.0),
+        );
+        assert_float_eq!(route.initial_bearing(), 90.0, abs <= 1e-9);
+        assert_float_eq!(route.point_at(0.5).latitude(), 45.0, abs <= 1e-9);
+        assert_float_eq!(route.point_at(0.5).longitude(), 45.0, abs <= 1e-9);
+        assert!(route.distance() > Route::great_circle(route.start(), route.end()).distance());
+
+        // Crossing the antimeridian takes the short way around
+        let route = Route::rhumb_line(
+            LLHDegrees::new(-10.0, 179.0, 0.0),
+            LLHDegrees::new(10.0, -179.0, 0.0),
+        );
+        let end = route.point_at(1.0);
+        assert_float_eq!(end.latitude(), 10.0, abs <= 1e-9);
+        assert_float_eq!(end.longitude(), -179.0, abs <= 1e-9);
+        assert!(route.distance() < 2_300_000.0);
+    }
+
+    #[test]
+    fn sampling() {
+        let route = Route::great_circle(
+            LLHDegrees::new(0.0, 0.0, 0.0),
+            LLHDegrees::new(0.0, 90.0, 0.0),
+        );
+        let points = route.sample(1_000_000.0);
+        assert_eq!(points.len(), 12);
+        assert_float_eq!(points[0].longitude(), 0.0, abs <= 1e-9);
+        assert_float_eq!(points[11].longitude(), 90.0, abs <= 1e-9);
+        for pair in points.windows(2) {
+            let step = Route::great_circle(pair[0], pair[1]).distance();
+            assert!(step <= 1_000_000.0);
+        }
+
+        let point = LLHDegrees::new(10.0, 10.0, 0.0);
+        assert_eq!(Route::rhumb_line(point, point).sample(10.0).len(), 2);
+    }
+}
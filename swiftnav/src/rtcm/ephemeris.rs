@@ -0,0 +1,800 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Broadcast ephemerides, messages 1019, 1020, 1042, 1045 and 1046
+//!
+//! The messages carry the same terms as the navigation messages broadcast by
+//! the satellites, with the semicircle angles converted to radians and the
+//! GLONASS terms converted to meters when decoded.
+
+use super::msm::nearest_time;
+use super::{BitReader, RtcmError};
+use crate::ephemeris::{Ephemeris, EphemerisTerms};
+use crate::signal::{Code, GnssSignal};
+use crate::time::{nearest_week, GpsTime, DAY, WEEK};
+use std::f64::consts::PI;
+
+/// Offset of Moscow time, used by GLONASS, from UTC in seconds
+const GLO_UTC_OFFSET: f64 = 3.0 * 3600.0;
+/// Offset between the frequency channel number and the frequency slot stored
+/// in a GLONASS ephemeris
+const GLO_FCN_OFFSET: i8 = 8;
+/// Offset of the BeiDou week number from the GPS week number
+const BDS_WEEK_TO_GPS_WEEK: i32 = 1356;
+/// Offset of the Galileo week number from the GPS week number
+const GAL_WEEK_TO_GPS_WEEK: i32 = 1024;
+/// Fit interval of BeiDou ephemerides, in seconds
+const BDS_FIT_INTERVAL: u32 = 3 * 3600;
+/// Fit interval of Galileo ephemerides, in seconds
+const GAL_FIT_INTERVAL: u32 = 4 * 3600;
+
+/// User range accuracy of the GPS and BeiDou URA indices, in meters
+const URA_TABLE: [f32; 16] = [
+    2.4, 3.4, 4.85, 6.85, 9.65, 13.65, 24.0, 48.0, 96.0, 192.0, 384.0, 768.0, 1536.0, 3072.0,
+    6144.0, -1.0,
+];
+/// Accuracy of the GLONASS FT indices, in meters
+const GLO_URA_TABLE: [f32; 16] = [
+    1.0, 2.0, 2.5, 4.0, 5.0, 7.0, 10.0, 12.0, 14.0, 16.0, 32.0, 64.0, 128.0, 256.0, 512.0, -1.0,
+];
+
+/// Orbital terms of a GPS, BeiDou or Galileo ephemeris
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct KeplerTerms {
+    toe: f64,
+    toc: f64,
+    tgd: [f32; 2],
+    crc: f64,
+    crs: f64,
+    cuc: f64,
+    cus: f64,
+    cic: f64,
+    cis: f64,
+    dn: f64,
+    m0: f64,
+    ecc: f64,
+    sqrta: f64,
+    omega0: f64,
+    omegadot: f64,
+    w: f64,
+    inc: f64,
+    inc_dot: f64,
+    af0: f64,
+    af1: f64,
+    af2: f64,
+    iodc: u16,
+    iode: u16,
+}
+
+/// Orbital terms of a GLONASS ephemeris
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct GloTerms {
+    /// Time of day of the terms in Moscow time, in seconds
+    tb: f64,
+    fcn: i8,
+    gamma: f64,
+    tau: f64,
+    d_tau: f64,
+    pos: [f64; 3],
+    vel: [f64; 3],
+    acc: [f64; 3],
+    iod: u8,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Terms {
+    /// The terms and the truncated week number with its modulus, both
+    /// already offset to GPS weeks
+    Kepler {
+        week: i32,
+        modulus: i32,
+        terms: KeplerTerms,
+    },
+    Glo(GloTerms),
+}
+
+/// A decoded broadcast ephemeris message
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EphemerisMessage {
+    message_number: u16,
+    sid: GnssSignal,
+    ura: f32,
+    fit_interval: u32,
+    health_bits: u8,
+    terms: Terms,
+}
+
+impl EphemerisMessage {
+    /// Decodes a message 1019, 1020, 1042, 1045 or 1046 payload
+    pub fn decode(payload: &[u8]) -> Result<EphemerisMessage, RtcmError> {
+        let mut bits = BitReader::new(payload);
+        let number = bits.u(12)? as u16;
+        match number {
+            1019 => decode_gps(&mut bits),
+            1020 => decode_glo(&mut bits),
+            1042 => decode_bds(&mut bits),
+            1045 | 1046 => decode_gal(&mut bits, number == 1046),
+            _ => Err(RtcmError::UnsupportedMessage(number)),
+        }
+    }
+
+    pub fn message_number(&self) -> u16 {
+        self.message_number
+    }
+
+    /// Gets the signal the ephemeris was decoded from
+    pub fn sid(&self) -> GnssSignal {
+        self.sid
+    }
+
+    /// Gets the frequency channel number of a GLONASS satellite, which can be
+    /// passed to [`MsmDecoder::set_glonass_fcn`](super::MsmDecoder::set_glonass_fcn)
+    ///
+    /// Returns `None` for the other constellations
+    pub fn glonass_fcn(&self) -> Option<i8> {
+        match &self.terms {
+            Terms::Glo(terms) => Some(terms.fcn),
+            Terms::Kepler { .. } => None,
+        }
+    }
+
+    /// Makes a complete ephemeris
+    ///
+    /// The messages only contain truncated week numbers, or just the time of
+    /// day for GLONASS, so the ephemeris is placed at the reference time of
+    /// the ephemeris closest to `reference`.
+    pub fn to_ephemeris(&self, reference: &GpsTime) -> Ephemeris {
+        let (toe, terms) = match &self.terms {
+            Terms::Kepler {
+                week,
+                modulus,
+                terms: k,
+            } => {
+                let wn = nearest_week(reference.wn(), *week, *modulus);
+                let toe = GpsTime::new_unchecked(wn, k.toe);
+                // The clock terms can refer to a time in the adjacent week
+                let half_week = WEEK.as_secs_f64() / 2.0;
+                let toc_wn = if k.toc - k.toe > half_week {
+                    wn - 1
+                } else if k.toe - k.toc > half_week {
+                    wn + 1
+                } else {
+                    wn
+                };
+                let terms = EphemerisTerms::new_kepler(
+                    self.sid.to_constellation(),
+                    k.tgd,
+                    k.crc,
+                    k.crs,
+                    k.cuc,
+                    k.cus,
+                    k.cic,
+                    k.cis,
+                    k.dn,
+                    k.m0,
+                    k.ecc,
+                    k.sqrta,
+                    k.omega0,
+                    k.omegadot,
+                    k.w,
+                    k.inc,
+                    k.inc_dot,
+                    k.af0,
+                    k.af1,
+                    k.af2,
+                    GpsTime::new_unchecked(toc_wn, k.toc),
+                    k.iodc,
+                    k.iode,
+                );
+                (toe, terms)
+            }
+            Terms::Glo(g) => {
+                let time_of_day = g.tb - GLO_UTC_OFFSET + reference.utc_offset_hardcoded();
+                let toe = nearest_time(reference, time_of_day, DAY);
+                let terms = EphemerisTerms::new_glo(
+                    g.gamma,
+                    g.tau,
+                    g.d_tau,
+                    g.pos,
+                    g.vel,
+                    g.acc,
+                    (g.fcn + GLO_FCN_OFFSET) as u16,
+                    g.iod,
+                );
+                (toe, terms)
+            }
+        };
+        Ephemeris::new(
+            self.sid,
+            toe,
+            self.ura,
+            self.fit_interval,
+            1,
+            self.health_bits,
+            0,
+            terms,
+        )
+    }
+}
+
+/// Reads a signed field and scales it by a power of two
+fn scaled(bits: &mut BitReader, len: usize, exponent: i32) -> Result<f64, RtcmError> {
+    Ok(bits.s(len)? as f64 * 2f64.powi(exponent))
+}
+
+/// Reads an unsigned field and scales it by a power of two
+fn scaled_u(bits: &mut BitReader, len: usize, exponent: i32) -> Result<f64, RtcmError> {
+    Ok(bits.u(len)? as f64 * 2f64.powi(exponent))
+}
+
+/// Reads a sign-magnitude field and scales it by a power of two
+fn scaled_sm(bits: &mut BitReader, len: usize, exponent: i32) -> Result<f64, RtcmError> {
+    Ok(bits.sign_magnitude(len)? as f64 * 2f64.powi(exponent))
+}
+
+fn signal(sat: u64, code: Code) -> Result<GnssSignal, RtcmError> {
+    // Satellite 0 is used for BeiDou satellite 64
+    let sat = if sat == 0 { 64 } else { sat as u16 };
+    GnssSignal::new(sat, code).map_err(|_| RtcmError::InvalidMessage)
+}
+
+/// Gets the fit interval of a GPS ephemeris from the fit interval flag and
+/// the IODC
+///
+/// # References
+///   * IS-GPS-200, Table 20-XII
+fn gps_fit_interval(fit_flag: bool, iodc: u16) -> u32 {
+    let hours = if !fit_flag {
+        4
+    } else {
+        match iodc {
+            240..=247 => 8,
+            248..=255 | 496 => 14,
+            497..=503 | 1021..=1023 => 26,
+            504..=510 => 50,
+            511 | 752..=756 => 74,
+            757..=763 => 98,
+            764..=767 | 1008..=1010 => 122,
+            1011..=1020 => 146,
+            _ => 6,
+        }
+    };
+    hours * 3600
+}
+
+/// Gets the accuracy of a Galileo SISA index, in meters
+///
+/// # References
+///   * Galileo OS SIS ICD, Issue 2.0, Table 89
+fn gal_sisa(index: u8) -> f32 {
+    let steps = |first: u8, step: f32| f32::from(index - first) * step;
+    match index {
+        0..=49 => steps(0, 0.01),
+        50..=74 => 0.5 + steps(50, 0.02),
+        75..=99 => 1.0 + steps(75, 0.04),
+        100..=125 => 2.0 + steps(100, 0.16),
+        _ => -1.0,
+    }
+}
+
+fn decode_gps(bits: &mut BitReader) -> Result<EphemerisMessage, RtcmError> {
+    let sat = bits.u(6)?;
+    let week = bits.u(10)? as i32;
+    let ura = URA_TABLE[bits.u(4)? as usize];
+    // Codes on L2
+    bits.skip(2)?;
+    let inc_dot = scaled(bits, 14, -43)? * PI;
+    let iode = bits.u(8)? as u16;
+    let toc = bits.u(16)? as f64 * 16.0;
+    let af2 = scaled(bits, 8, -55)?;
+    let af1 = scaled(bits, 16, -43)?;
+    let af0 = scaled(bits, 22, -31)?;
+    let iodc = bits.u(10)? as u16;
+    let crs = scaled(bits, 16, -5)?;
+    let dn = scaled(bits, 16, -43)? * PI;
+    let m0 = scaled(bits, 32, -31)? * PI;
+    let cuc = scaled(bits, 16, -29)?;
+    let ecc = scaled_u(bits, 32, -33)?;
+    let cus = scaled(bits, 16, -29)?;
+    let sqrta = scaled_u(bits, 32, -19)?;
+    let toe = bits.u(16)? as f64 * 16.0;
+    let cic = scaled(bits, 16, -29)?;
+    let omega0 = scaled(bits, 32, -31)? * PI;
+    let cis = scaled(bits, 16, -29)?;
+    let inc = scaled(bits, 32, -31)? * PI;
+    let crc = scaled(bits, 16, -5)?;
+    let w = scaled(bits, 32, -31)? * PI;
+    let omegadot = scaled(bits, 24, -43)? * PI;
+    let tgd = scaled(bits, 8, -31)? as f32;
+    let health_bits = bits.u(6)? as u8;
+    // L2 P data flag
+    bits.skip(1)?;
+    let fit_flag = bits.flag()?;
+
+    Ok(EphemerisMessage {
+        message_number: 1019,
+        sid: signal(sat, Code::GpsL1ca)?,
+        ura,
+        fit_interval: gps_fit_interval(fit_flag, iodc),
+        health_bits,
+        terms: Terms::Kepler {
+            week,
+            modulus: 1024,
+            terms: KeplerTerms {
+                toe,
+                toc,
+                tgd: [tgd, 0.0],
+                crc,
+                crs,
+                cuc,
+                cus,
+                cic,
+                cis,
+                dn,
+                m0,
+                ecc,
+                sqrta,
+                omega0,
+                omegadot,
+                w,
+                inc,
+                inc_dot,
+                af0,
+                af1,
+                af2,
+                iodc,
+                iode,
+            },
+        },
+    })
+}
+
+fn decode_glo(bits: &mut BitReader) -> Result<EphemerisMessage, RtcmError> {
+    let slot = bits.u(6)?;
+    let fcn = bits.u(5)? as i8 - 7;
+    // Almanac health and its availability indicator
+    bits.skip(2)?;
+    let p1 = bits.u(2)?;
+    // tk, the time of the start of the frame
+    bits.skip(12)?;
+    let bn = bits.flag()?;
+    // P2, the parity of tb
+    bits.skip(1)?;
+    let tb = bits.u(7)? as u8;
+    let mut pos = [0.0; 3];
+    let mut vel = [0.0; 3];
+    let mut acc = [0.0; 3];
+    for axis in 0..3 {
+        vel[axis] = scaled_sm(bits, 24, -20)? * 1000.0;
+        pos[axis] = scaled_sm(bits, 27, -11)? * 1000.0;
+        acc[axis] = scaled_sm(bits, 5, -30)? * 1000.0;
+    }
+    // P3, the number of satellites in the almanac
+    bits.skip(1)?;
+    let gamma = scaled_sm(bits, 11, -40)?;
+    // P, the source of the time offsets
+    bits.skip(2)?;
+    let ln_3 = bits.flag()?;
+    let tau = scaled_sm(bits, 22, -30)?;
+    let d_tau = scaled_sm(bits, 5, -30)?;
+    // En, the age of the data, and P4
+    bits.skip(6)?;
+    let ura = GLO_URA_TABLE[bits.u(4)? as usize];
+    // NT, M, the additional data flag, NA, tau_c, N4 and tau_GPS
+    bits.skip(11 + 2 + 1 + 11 + 32 + 5 + 22)?;
+    let ln_5 = bits.flag()?;
+    bits.skip(7)?;
+
+    // P1 is the time between consecutive values of tb
+    let fit_interval = match p1 {
+        2 => 45 * 60,
+        3 => 60 * 60,
+        _ => 30 * 60,
+    };
+    Ok(EphemerisMessage {
+        message_number: 1020,
+        sid: signal(slot, Code::GloL1of)?,
+        ura,
+        fit_interval,
+        health_bits: u8::from(bn) | u8::from(ln_3 || ln_5) << 1,
+        terms: Terms::Glo(GloTerms {
+            tb: f64::from(tb) * 900.0,
+            fcn,
+            gamma,
+            tau,
+            d_tau,
+            pos,
+            vel,
+            acc,
+            iod: tb,
+        }),
+    })
+}
+
+fn decode_bds(bits: &mut BitReader) -> Result<EphemerisMessage, RtcmError> {
+    let sat = bits.u(6)?;
+    let week = bits.u(13)? as i32 + BDS_WEEK_TO_GPS_WEEK;
+    let ura = URA_TABLE[bits.u(4)? as usize];
+    let inc_dot = scaled(bits, 14, -43)? * PI;
+    // AODE
+    bits.skip(5)?;
+    let toc = bits.u(17)? as f64 * 8.0;
+    let af2 = scaled(bits, 11, -66)?;
+    let af1 = scaled(bits, 22, -50)?;
+    let af0 = scaled(bits, 24, -33)?;
+    // AODC
+    bits.skip(5)?;
+    let crs = scaled(bits, 18, -6)?;
+    let dn = scaled(bits, 16, -43)? * PI;
+    let m0 = scaled(bits, 32, -31)? * PI;
+    let cuc = scaled(bits, 18, -31)?;
+    let ecc = scaled_u(bits, 32, -33)?;
+    let cus = scaled(bits, 18, -31)?;
+    let sqrta = scaled_u(bits, 32, -19)?;
+    let toe = bits.u(17)? as f64 * 8.0;
+    let cic = scaled(bits, 18, -31)?;
+    let omega0 = scaled(bits, 32, -31)? * PI;
+    let cis = scaled(bits, 18, -31)?;
+    let inc = scaled(bits, 32, -31)? * PI;
+    let crc = scaled(bits, 18, -6)?;
+    let w = scaled(bits, 32, -31)? * PI;
+    let omegadot = scaled(bits, 24, -43)? * PI;
+    let tgd1 = bits.s(10)? as f32 * 1e-10;
+    let tgd2 = bits.s(10)? as f32 * 1e-10;
+    let health_bits = bits.u(1)? as u8;
+
+    // The issue of data is made from the time of ephemeris, as done when
+    // decoding the D1 navigation message
+    let iod = ((toe as u32 / 720) % 240) as u16;
+    Ok(EphemerisMessage {
+        message_number: 1042,
+        sid: signal(sat, Code::Bds2B1)?,
+        ura,
+        fit_interval: BDS_FIT_INTERVAL,
+        health_bits,
+        terms: Terms::Kepler {
+            week,
+            modulus: 1 << 13,
+            terms: KeplerTerms {
+                toe,
+                toc,
+                tgd: [tgd1, tgd2],
+                crc,
+                crs,
+                cuc,
+                cus,
+                cic,
+                cis,
+                dn,
+                m0,
+                ecc,
+                sqrta,
+                omega0,
+                omegadot,
+                w,
+                inc,
+                inc_dot,
+                af0,
+                af1,
+                af2,
+                iodc: iod,
+                iode: iod,
+            },
+        },
+    })
+}
+
+/// Decodes the F/NAV (1045) or I/NAV (1046) ephemeris
+fn decode_gal(bits: &mut BitReader, inav: bool) -> Result<EphemerisMessage, RtcmError> {
+    let sat = bits.u(6)?;
+    let week = bits.u(12)? as i32 + GAL_WEEK_TO_GPS_WEEK;
+    let iod = bits.u(10)? as u16;
+    let ura = gal_sisa(bits.u(8)? as u8);
+    let inc_dot = scaled(bits, 14, -43)? * PI;
+    let toc = bits.u(14)? as f64 * 60.0;
+    let af2 = scaled(bits, 6, -59)?;
+    let af1 = scaled(bits, 21, -46)?;
+    let af0 = scaled(bits, 31, -34)?;
+    let crs = scaled(bits, 16, -5)?;
+    let dn = scaled(bits, 16, -43)? * PI;
+    let m0 = scaled(bits, 32, -31)? * PI;
+    let cuc = scaled(bits, 16, -29)?;
+    let ecc = scaled_u(bits, 32, -33)?;
+    let cus = scaled(bits, 16, -29)?;
+    let sqrta = scaled_u(bits, 32, -19)?;
+    let toe = bits.u(14)? as f64 * 60.0;
+    let cic = scaled(bits, 16, -29)?;
+    let omega0 = scaled(bits, 32, -31)? * PI;
+    let cis = scaled(bits, 16, -29)?;
+    let inc = scaled(bits, 32, -31)? * PI;
+    let crc = scaled(bits, 16, -5)?;
+    let w = scaled(bits, 32, -31)? * PI;
+    let omegadot = scaled(bits, 24, -43)? * PI;
+    let bgd_e5a = scaled(bits, 10, -32)? as f32;
+
+    // The signal health status and data validity status of each signal are
+    // packed into three bits, E1-B in the low bits and E5b above them for
+    // the I/NAV message
+    let (sid, bgd_e5b, health_bits) = if inav {
+        let bgd_e5b = scaled(bits, 10, -32)? as f32;
+        let e5b = bits.u(3)? as u8;
+        let e1b = bits.u(3)? as u8;
+        bits.skip(2)?;
+        (signal(sat, Code::GalE1b)?, bgd_e5b, e1b | e5b << 3)
+    } else {
+        let e5a = bits.u(3)? as u8;
+        bits.skip(7)?;
+        (signal(sat, Code::GalE5i)?, 0.0, e5a)
+    };
+
+    Ok(EphemerisMessage {
+        message_number: if inav { 1046 } else { 1045 },
+        sid,
+        ura,
+        fit_interval: GAL_FIT_INTERVAL,
+        health_bits,
+        terms: Terms::Kepler {
+            week,
+            modulus: 1 << 12,
+            terms: KeplerTerms {
+                toe,
+                toc,
+                tgd: [bgd_e5a, bgd_e5b],
+                crc,
+                crs,
+                cuc,
+                cus,
+                cic,
+                cis,
+                dn,
+                m0,
+                ecc,
+                sqrta,
+                omega0,
+                omegadot,
+                w,
+                inc,
+                inc_dot,
+                af0,
+                af1,
+                af2,
+                iodc: iod,
+                iode: iod,
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::ECEF;
+    use crate::ephemeris::glonass::GloEphemeris;
+    use crate::ephemeris::kepler::KeplerEphemeris;
+    use crate::rtcm::tests::PayloadBuilder;
+    use crate::rtcm::RtcmMessage;
+    use crate::signal::Constellation;
+    use float_eq::assert_float_eq;
+
+    fn gps_payload(week: u64, toe: u64, toc: u64) -> Vec<u8> {
+        PayloadBuilder::new(1019)
+            .u(12, 6)
+            .u(week, 10)
+            .u(2, 4)
+            .u(1, 2)
+            .s(-1_000, 14)
+            .u(60, 8)
+            .u(toc, 16)
+            .s(0, 8)
+            .s(-80, 16)
+            .s(-250_000, 22)
+            .u(60, 10)
+            .s(1_200, 16)
+            .s(10_000, 16)
+            .s(-1_500_000_000, 32)
+            .s(-300, 16)
+            .u(85_899_346, 32)
+            .s(4_000, 16)
+            .u(2_702_209_434, 32)
+            .u(toe, 16)
+            .s(30, 16)
+            .s(1_000_000_000, 32)
+            .s(-40, 16)
+            .s(644_245_094, 32)
+            .s(6_000, 16)
+            .s(-500_000_000, 32)
+            .s(-20_000, 24)
+            .s(-5, 8)
+            .u(0, 6)
+            .u(0, 1)
+            .u(0, 1)
+            .build()
+    }
+
+    #[test]
+    fn decode_gps() {
+        let payload = gps_payload(2091 % 1024, 25_200, 25_200);
+        let message = match RtcmMessage::decode(&payload).unwrap() {
+            RtcmMessage::Ephemeris(message) => message,
+            other => panic!("Unexpected message {:?}", other),
+        };
+        assert_eq!(message.message_number(), 1019);
+        assert_eq!(message.sid(), GnssSignal::new(12, Code::GpsL1ca).unwrap());
+        assert_eq!(message.glonass_fcn(), None);
+
+        let reference = GpsTime::new(2091, 460_800.0).unwrap();
+        let toe = GpsTime::new(2091, 403_200.0).unwrap();
+        let expected = Ephemeris::new(
+            message.sid(),
+            toe,
+            4.85,
+            4 * 3600,
+            1,
+            0,
+            0,
+            EphemerisTerms::new_kepler(
+                Constellation::Gps,
+                [-5.0 * 2f32.powi(-31), 0.0],
+                6_000.0 * 2f64.powi(-5),
+                1_200.0 * 2f64.powi(-5),
+                -300.0 * 2f64.powi(-29),
+                4_000.0 * 2f64.powi(-29),
+                30.0 * 2f64.powi(-29),
+                -40.0 * 2f64.powi(-29),
+                10_000.0 * 2f64.powi(-43) * PI,
+                -1_500_000_000.0 * 2f64.powi(-31) * PI,
+                85_899_346.0 * 2f64.powi(-33),
+                2_702_209_434.0 * 2f64.powi(-19),
+                1_000_000_000.0 * 2f64.powi(-31) * PI,
+                -20_000.0 * 2f64.powi(-43) * PI,
+                -500_000_000.0 * 2f64.powi(-31) * PI,
+                644_245_094.0 * 2f64.powi(-31) * PI,
+                -1_000.0 * 2f64.powi(-43) * PI,
+                -250_000.0 * 2f64.powi(-31),
+                -80.0 * 2f64.powi(-43),
+                0.0,
+                toe,
+                60,
+                60,
+            ),
+        );
+        let ephemeris = message.to_ephemeris(&reference);
+        assert_eq!(
+            KeplerEphemeris::from_ephemeris(&ephemeris),
+            KeplerEphemeris::from_ephemeris(&expected)
+        );
+        assert_eq!(ephemeris.sid().unwrap().sat(), 12);
+
+        // The truncated week is resolved close to the reference, even across
+        // a rollover
+        let ephemeris = message.to_ephemeris(&GpsTime::new(2091 + 1024, 0.0).unwrap());
+        let kepler = KeplerEphemeris::from_ephemeris(&ephemeris).unwrap();
+        assert_eq!(kepler.toe(), GpsTime::new(2091 + 1024, 403_200.0).unwrap());
+
+        // The clock time can be in the week before the time of ephemeris
+        let payload = gps_payload(2092 % 1024, 0, 37_794);
+        let message = EphemerisMessage::decode(&payload).unwrap();
+        let kepler = KeplerEphemeris::from_ephemeris(&message.to_ephemeris(&reference)).unwrap();
+        assert_eq!(kepler.toe(), GpsTime::new(2092, 0.0).unwrap());
+        assert_eq!(kepler.toc(), GpsTime::new(2091, 604_704.0).unwrap());
+
+        assert_eq!(
+            EphemerisMessage::decode(&payload[..40]),
+            Err(RtcmError::Truncated)
+        );
+    }
+
+    #[test]
+    fn decode_glonass() {
+        let mut builder = PayloadBuilder::new(1020)
+            .u(5, 6)
+            .u(1 + 7, 5)
+            .u(0, 2)
+            .u(1, 2)
+            .u(0, 12)
+            .u(0, 1)
+            .u(0, 1)
+            .u(60, 7);
+        let axes = [
+            (-1_500_000, -5_000_000, 3),
+            (2_000_000, 30_000_000, -2),
+            (500_000, -35_000_000, 0),
+        ];
+        for (vel, pos, acc) in axes.iter() {
+            builder = builder
+                .sign_magnitude(*vel, 24)
+                .sign_magnitude(*pos, 27)
+                .sign_magnitude(*acc, 5);
+        }
+        let payload = builder
+            .u(0, 1)
+            .sign_magnitude(-100, 11)
+            .u(0, 2)
+            .u(0, 1)
+            .sign_magnitude(-50_000, 22)
+            .sign_magnitude(3, 5)
+            .u(0, 5)
+            .u(0, 1)
+            .u(2, 4)
+            .zeros(11 + 2 + 1 + 11 + 32 + 5 + 22)
+            .u(0, 1)
+            .u(0, 7)
+            .build();
+
+        let message = EphemerisMessage::decode(&payload).unwrap();
+        assert_eq!(message.message_number(), 1020);
+        assert_eq!(message.sid(), GnssSignal::new(5, Code::GloL1of).unwrap());
+        assert_eq!(message.glonass_fcn(), Some(1));
+
+        // tb of 15:00 Moscow time is 12:00 UTC
+        let reference = GpsTime::new(2091, 4.0 * 86_400.0 + 10.0 * 3600.0).unwrap();
+        let ephemeris = message.to_ephemeris(&reference);
+        let glo = GloEphemeris::from_ephemeris(&ephemeris).unwrap();
+        let toe = GpsTime::new(2091, 4.0 * 86_400.0 + 12.0 * 3600.0 + 18.0).unwrap();
+        let km = 1000.0 * 2f64.powi(-11);
+        let km_s = 1000.0 * 2f64.powi(-20);
+        let km_s2 = 1000.0 * 2f64.powi(-30);
+        let expected = GloEphemeris::new(
+            toe,
+            -100.0 * 2f64.powi(-40),
+            -50_000.0 * 2f64.powi(-30),
+            ECEF::new(-5_000_000.0 * km, 30_000_000.0 * km, -35_000_000.0 * km),
+            ECEF::new(-1_500_000.0 * km_s, 2_000_000.0 * km_s, 500_000.0 * km_s),
+            ECEF::new(3.0 * km_s2, -2.0 * km_s2, 0.0),
+            60,
+        );
+        assert_eq!(glo, expected);
+    }
+
+    #[test]
+    fn decode_bds_and_gal() {
+        let payload = PayloadBuilder::new(1042)
+            .u(3, 6)
+            .u(735, 13)
+            .u(0, 4)
+            .s(0, 14)
+            .u(0, 5)
+            .u(57_600, 17)
+            .zeros(11 + 22 + 24 + 5 + 18 + 16 + 32 + 18 + 32 + 18 + 32)
+            .u(57_600, 17)
+            .zeros(18 + 32 + 18 + 32 + 18 + 32 + 24)
+            .s(-25, 10)
+            .s(10, 10)
+            .u(0, 1)
+            .build();
+        let message = EphemerisMessage::decode(&payload).unwrap();
+        assert_eq!(message.sid(), GnssSignal::new(3, Code::Bds2B1).unwrap());
+        let reference = GpsTime::new(2091, 0.0).unwrap();
+        let kepler = KeplerEphemeris::from_ephemeris(&message.to_ephemeris(&reference)).unwrap();
+        assert_eq!(kepler.toe(), GpsTime::new(2091, 460_800.0).unwrap());
+        assert!(kepler.is_bds_geo());
+
+        let payload = PayloadBuilder::new(1046)
+            .u(11, 6)
+            .u(1066, 12)
+            .u(97, 10)
+            .u(107, 8)
+            .u(0, 14)
+            .u(2250, 14)
+            .zeros(6 + 21 + 31 + 16 + 16 + 32 + 16 + 32 + 16 + 32)
+            .u(2250, 14)
+            .zeros(16 + 32 + 16 + 32 + 16 + 32 + 24 + 10 + 10)
+            .u(0b001, 3)
+            .u(0b100, 3)
+            .u(0, 2)
+            .build();
+        let message = EphemerisMessage::decode(&payload).unwrap();
+        assert_eq!(message.message_number(), 1046);
+        assert_eq!(message.sid(), GnssSignal::new(11, Code::GalE1b).unwrap());
+        assert_float_eq!(message.ura, 3.12, abs <= 1e-6);
+        assert_eq!(message.health_bits, 0b001_100);
+        let kepler = KeplerEphemeris::from_ephemeris(&message.to_ephemeris(&reference)).unwrap();
+        assert_eq!(kepler.toe(), GpsTime::new(2090, 135_000.0).unwrap());
+    }
+}
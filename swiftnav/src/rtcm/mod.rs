@@ -0,0 +1,364 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! RTCM 3 message decoding
+//!
+//! RTCM 3 is the format used by reference stations and correction networks,
+//! usually delivered over NTRIP. Each message is sent in a frame made of a
+//! preamble byte (`0xD3`), a 10 bit payload length, the payload and a CRC-24Q.
+//! [`decode_frame`] checks a single frame and [`FrameReader`] splits a byte
+//! stream into payloads.
+//!
+//! The messages needed to process network RTK corrections are decoded:
+//!  * 1005 and 1006 - Reference station coordinates
+//!  * MSM4, MSM5 and MSM7 of every constellation - Observations
+//!  * 1019, 1020, 1042, 1045 and 1046 - GPS, GLONASS, BeiDou and Galileo
+//!    broadcast ephemerides
+//!
+//! MSM observations are split into one message per constellation, and
+//! [`MsmDecoder`] gathers them into epochs of
+//! [`NavigationMeasurement`](crate::navmeas::NavigationMeasurement)s.
+//! Ephemeris messages only carry truncated week numbers, so
+//! [`EphemerisMessage::to_ephemeris`] needs an approximate time to make a
+//! complete [`Ephemeris`](crate::ephemeris::Ephemeris).
+//!
+//! # References
+//!   * RTCM Standard 10403.3, Differential GNSS Services - Version 3, 2016
+
+mod ephemeris;
+mod msm;
+mod station;
+
+pub use ephemeris::EphemerisMessage;
+pub use msm::{MsmDecoder, MsmMessage, MsmSatellite, MsmSignal};
+pub use station::StationCoordinates;
+
+use crate::edc::compute_crc24q;
+use std::error::Error;
+use std::fmt;
+
+/// Byte starting every RTCM 3 frame
+pub const PREAMBLE: u8 = 0xD3;
+
+/// Number of bytes in a frame besides the payload
+const FRAME_OVERHEAD: usize = 6;
+
+/// Errors which can occur while decoding RTCM messages
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum RtcmError {
+    /// The frame doesn't start with the preamble
+    InvalidPreamble(u8),
+    /// More bytes are needed to complete the frame
+    Incomplete,
+    /// The CRC of the frame doesn't match its contents
+    CrcMismatch,
+    /// The payload is too short for the fields of the message
+    Truncated,
+    /// The message is of a type which isn't decoded
+    UnsupportedMessage(u16),
+    /// The message contents are inconsistent
+    InvalidMessage,
+}
+
+impl fmt::Display for RtcmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RtcmError::InvalidPreamble(preamble) => {
+                write!(f, "Invalid RTCM preamble ({:#04x})", preamble)
+            }
+            RtcmError::Incomplete => write!(f, "Incomplete RTCM frame"),
+            RtcmError::CrcMismatch => write!(f, "RTCM frame CRC mismatch"),
+            RtcmError::Truncated => write!(f, "RTCM message is truncated"),
+            RtcmError::UnsupportedMessage(number) => {
+                write!(f, "Unsupported RTCM message ({})", number)
+            }
+            RtcmError::InvalidMessage => write!(f, "Invalid RTCM message contents"),
+        }
+    }
+}
+
+impl Error for RtcmError {}
+
+/// A decoded RTCM message
+#[derive(Debug, Clone, PartialEq)]
+pub enum RtcmMessage {
+    /// Message 1005 or 1006
+    StationCoordinates(StationCoordinates),
+    /// An MSM4, MSM5 or MSM7 message of any constellation
+    Msm(MsmMessage),
+    /// Message 1019, 1020, 1042, 1045 or 1046
+    Ephemeris(EphemerisMessage),
+    /// A message type which isn't decoded
+    Unsupported(u16),
+}
+
+impl RtcmMessage {
+    /// Decodes the payload of a frame
+    pub fn decode(payload: &[u8]) -> Result<RtcmMessage, RtcmError> {
+        let number = message_number(payload).ok_or(RtcmError::Truncated)?;
+        match number {
+            1005 | 1006 => StationCoordinates::decode(payload).map(RtcmMessage::StationCoordinates),
+            1019 | 1020 | 1042 | 1045 | 1046 => {
+                EphemerisMessage::decode(payload).map(RtcmMessage::Ephemeris)
+            }
+            _ if MsmMessage::is_supported(number) => {
+                MsmMessage::decode(payload).map(RtcmMessage::Msm)
+            }
+            _ => Ok(RtcmMessage::Unsupported(number)),
+        }
+    }
+}
+
+/// Gets the message number of a payload, its first 12 bits
+pub fn message_number(payload: &[u8]) -> Option<u16> {
+    BitReader::new(payload)
+        .u(12)
+        .ok()
+        .map(|number| number as u16)
+}
+
+/// Checks a frame at the start of `data` and gets its payload
+///
+/// Returns the payload and the total length of the frame, so that the next
+/// frame starts at that offset.
+pub fn decode_frame(data: &[u8]) -> Result<(&[u8], usize), RtcmError> {
+    match data.first() {
+        None => return Err(RtcmError::Incomplete),
+        Some(&PREAMBLE) => {}
+        Some(&byte) => return Err(RtcmError::InvalidPreamble(byte)),
+    }
+    if data.len() < 3 {
+        return Err(RtcmError::Incomplete);
+    }
+    let length = (usize::from(data[1] & 0x03) << 8) | usize::from(data[2]);
+    let frame_length = length + FRAME_OVERHEAD;
+    if data.len() < frame_length {
+        return Err(RtcmError::Incomplete);
+    }
+    let crc = data[frame_length - 3..frame_length]
+        .iter()
+        .fold(0, |acc, byte| (acc << 8) | u32::from(*byte));
+    if compute_crc24q(&data[..length + 3], 0) != crc {
+        return Err(RtcmError::CrcMismatch);
+    }
+    Ok((&data[3..length + 3], frame_length))
+}
+
+/// Splits a stream of bytes into RTCM payloads
+///
+/// Bytes before a preamble and frames with a bad CRC are skipped, so the
+/// reader resynchronizes by itself after corrupted data.
+#[derive(Debug, Clone, Default)]
+pub struct FrameReader {
+    buffer: Vec<u8>,
+}
+
+impl FrameReader {
+    pub fn new() -> FrameReader {
+        FrameReader { buffer: Vec::new() }
+    }
+
+    /// Adds received bytes to the end of the stream
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Gets the payload of the next complete frame
+    ///
+    /// Returns `None` when more bytes are needed
+    pub fn next_payload(&mut self) -> Option<Vec<u8>> {
+        loop {
+            let start = self.buffer.iter().position(|b| *b == PREAMBLE);
+            self.buffer.drain(..start.unwrap_or(self.buffer.len()));
+            match decode_frame(&self.buffer) {
+                Ok((payload, length)) => {
+                    let payload = payload.to_vec();
+                    self.buffer.drain(..length);
+                    return Some(payload);
+                }
+                Err(RtcmError::Incomplete) => return None,
+                // Not a real frame, look for the next preamble
+                Err(_) => {
+                    self.buffer.remove(0);
+                }
+            }
+        }
+    }
+
+    /// Gets the number of bytes waiting to be framed
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+/// Sequential big endian bit field reader
+pub(crate) struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader { data, position: 0 }
+    }
+
+    /// Reads an unsigned field of up to 64 bits
+    pub(crate) fn u(&mut self, len: usize) -> Result<u64, RtcmError> {
+        if self.position + len > 8 * self.data.len() {
+            return Err(RtcmError::Truncated);
+        }
+        let value = (self.position..self.position + len).fold(0, |acc, bit| {
+            (acc << 1) | u64::from((self.data[bit / 8] >> (7 - bit % 8)) & 1)
+        });
+        self.position += len;
+        Ok(value)
+    }
+
+    /// Reads a two's complement signed field of up to 64 bits
+    pub(crate) fn s(&mut self, len: usize) -> Result<i64, RtcmError> {
+        let value = self.u(len)?;
+        if len < 64 && value & (1 << (len - 1)) != 0 {
+            Ok((i128::from(value) - (1i128 << len)) as i64)
+        } else {
+            Ok(value as i64)
+        }
+    }
+
+    /// Reads a sign-magnitude field, where the first bit is the sign, as used
+    /// by the GLONASS messages
+    pub(crate) fn sign_magnitude(&mut self, len: usize) -> Result<i64, RtcmError> {
+        let negative = self.flag()?;
+        let magnitude = self.u(len - 1)? as i64;
+        Ok(if negative { -magnitude } else { magnitude })
+    }
+
+    pub(crate) fn flag(&mut self) -> Result<bool, RtcmError> {
+        Ok(self.u(1)? == 1)
+    }
+
+    pub(crate) fn skip(&mut self, len: usize) -> Result<(), RtcmError> {
+        if self.position + len > 8 * self.data.len() {
+            return Err(RtcmError::Truncated);
+        }
+        self.position += len;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Packs fields into a message payload
+    #[derive(Default)]
+    pub(crate) struct PayloadBuilder {
+        bits: Vec<bool>,
+    }
+
+    impl PayloadBuilder {
+        pub(crate) fn new(number: u16) -> PayloadBuilder {
+            PayloadBuilder::default().u(u64::from(number), 12)
+        }
+
+        pub(crate) fn u(mut self, value: u64, len: usize) -> PayloadBuilder {
+            for k in (0..len).rev() {
+                self.bits.push((value >> k) & 1 == 1);
+            }
+            self
+        }
+
+        pub(crate) fn s(self, value: i64, len: usize) -> PayloadBuilder {
+            let mask = if len == 64 { u64::MAX } else { (1 << len) - 1 };
+            self.u(value as u64 & mask, len)
+        }
+
+        /// Adds a run of zero bits for fields which aren't of interest
+        pub(crate) fn zeros(mut self, len: usize) -> PayloadBuilder {
+            self.bits.resize(self.bits.len() + len, false);
+            self
+        }
+
+        pub(crate) fn sign_magnitude(self, value: i64, len: usize) -> PayloadBuilder {
+            self.u(u64::from(value < 0), 1)
+                .u(value.unsigned_abs(), len - 1)
+        }
+
+        pub(crate) fn build(self) -> Vec<u8> {
+            let mut data = vec![0u8; (self.bits.len() + 7) / 8];
+            for (i, bit) in self.bits.iter().enumerate() {
+                if *bit {
+                    data[i / 8] |= 0x80 >> (i % 8);
+                }
+            }
+            data
+        }
+    }
+
+    /// Wraps a payload into a frame
+    pub(crate) fn frame(payload: &[u8]) -> Vec<u8> {
+        let mut data = vec![PREAMBLE, (payload.len() >> 8) as u8, payload.len() as u8];
+        data.extend_from_slice(payload);
+        let crc = compute_crc24q(&data, 0);
+        data.extend_from_slice(&[(crc >> 16) as u8, (crc >> 8) as u8, crc as u8]);
+        data
+    }
+
+    #[test]
+    fn framing() {
+        let payload = PayloadBuilder::new(1234).u(0xABCDE, 20).build();
+        let data = frame(&payload);
+        assert_eq!(decode_frame(&data), Ok((&payload[..], payload.len() + 6)));
+        assert_eq!(message_number(&payload), Some(1234));
+        assert_eq!(
+            RtcmMessage::decode(&payload),
+            Ok(RtcmMessage::Unsupported(1234))
+        );
+
+        assert_eq!(
+            decode_frame(&data[..data.len() - 1]),
+            Err(RtcmError::Incomplete)
+        );
+        assert_eq!(
+            decode_frame(&data[1..]),
+            Err(RtcmError::InvalidPreamble(0x00))
+        );
+        let mut corrupted = data.clone();
+        corrupted[4] ^= 0x10;
+        assert_eq!(decode_frame(&corrupted), Err(RtcmError::CrcMismatch));
+
+        // The reader skips garbage and corrupted frames
+        let mut reader = FrameReader::new();
+        reader.push(&[0x00, 0x12]);
+        reader.push(&corrupted);
+        reader.push(&data[..5]);
+        assert_eq!(reader.next_payload(), None);
+        reader.push(&data[5..]);
+        reader.push(&data);
+        assert_eq!(reader.next_payload(), Some(payload.clone()));
+        assert_eq!(reader.next_payload(), Some(payload));
+        assert_eq!(reader.next_payload(), None);
+        assert_eq!(reader.buffered(), 0);
+    }
+
+    #[test]
+    fn bit_fields() {
+        let data = PayloadBuilder::default()
+            .u(5, 3)
+            .s(-3, 38)
+            .u(1, 1)
+            .sign_magnitude(-6, 5)
+            .build();
+        let mut reader = BitReader::new(&data);
+        assert_eq!(reader.u(3), Ok(5));
+        assert_eq!(reader.s(38), Ok(-3));
+        assert_eq!(reader.flag(), Ok(true));
+        assert_eq!(reader.sign_magnitude(5), Ok(-6));
+        assert_eq!(reader.u(8), Err(RtcmError::Truncated));
+    }
+}
@@ -0,0 +1,948 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Multiple Signal Messages (MSM)
+//!
+//! Each MSM holds the observations of one constellation at one epoch. The
+//! header has masks of the satellites and signals present, and a cell mask
+//! of which signals are present for each satellite. The satellite data holds
+//! the rough ranges common to all signals of a satellite, and the signal data
+//! holds the fine range of each cell relative to the rough range.
+
+use super::{BitReader, RtcmError};
+use crate::navmeas::NavigationMeasurement;
+use crate::signal::{Code, Constellation, GloSlotMap, GnssSignal};
+use crate::time::{GpsTime, DAY, WEEK};
+use crate::SPEED_OF_LIGHT;
+use std::time::Duration;
+
+/// Meters travelled by light in one millisecond
+const LIGHT_MS: f64 = SPEED_OF_LIGHT / 1000.0;
+/// Offset of BeiDou time from GPS time, in seconds
+const BDS_SECOND_TO_GPS_SECOND: f64 = 14.0;
+/// Offset of Moscow time, used by GLONASS, from UTC in seconds
+const GLO_UTC_OFFSET: f64 = 3.0 * 3600.0;
+/// Largest number of cells a message can have
+const MAX_CELLS: usize = 64;
+
+/// Satellite data of an MSM
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct MsmSatellite {
+    sat: u16,
+    rough_range: Option<f64>,
+    extended_info: Option<u8>,
+    rough_range_rate: Option<f64>,
+}
+
+impl MsmSatellite {
+    /// Gets the satellite number, using the same numbering as [`GnssSignal`]
+    pub fn sat(&self) -> u16 {
+        self.sat
+    }
+
+    /// Gets the rough range to the satellite, in milliseconds
+    pub fn rough_range(&self) -> Option<f64> {
+        self.rough_range
+    }
+
+    /// Gets the constellation specific extended satellite information, only
+    /// present in MSM5 and MSM7
+    ///
+    /// For GLONASS this is the frequency channel number offset by 7.
+    pub fn extended_info(&self) -> Option<u8> {
+        self.extended_info
+    }
+
+    /// Gets the rough phase range rate, in m/s, only present in MSM5 and MSM7
+    pub fn rough_range_rate(&self) -> Option<f64> {
+        self.rough_range_rate
+    }
+}
+
+/// Signal data of an MSM
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct MsmSignal {
+    satellite: usize,
+    signal_id: u8,
+    pseudorange: Option<f64>,
+    phase_range: Option<f64>,
+    lock_time: Duration,
+    half_cycle_ambiguity: bool,
+    cn0: Option<f64>,
+    phase_range_rate: Option<f64>,
+}
+
+impl MsmSignal {
+    /// Gets the index of the satellite of this signal in
+    /// [`MsmMessage::satellites()`]
+    pub fn satellite(&self) -> usize {
+        self.satellite
+    }
+
+    /// Gets the RTCM signal ID, from 1 to 32
+    pub fn signal_id(&self) -> u8 {
+        self.signal_id
+    }
+
+    /// Gets the pseudorange, in meters
+    pub fn pseudorange(&self) -> Option<f64> {
+        self.pseudorange
+    }
+
+    /// Gets the phase range, the carrier phase scaled by the wavelength, in
+    /// meters
+    pub fn phase_range(&self) -> Option<f64> {
+        self.phase_range
+    }
+
+    /// Gets the minimum time the carrier phase has been tracked without
+    /// interruption
+    pub fn lock_time(&self) -> Duration {
+        self.lock_time
+    }
+
+    /// Checks if the carrier phase still has a half cycle ambiguity
+    pub fn half_cycle_ambiguity(&self) -> bool {
+        self.half_cycle_ambiguity
+    }
+
+    /// Gets the carrier to noise density, in dB-Hz
+    pub fn cn0(&self) -> Option<f64> {
+        self.cn0
+    }
+
+    /// Gets the phase range rate, in m/s, only present in MSM5 and MSM7
+    pub fn phase_range_rate(&self) -> Option<f64> {
+        self.phase_range_rate
+    }
+}
+
+/// A decoded MSM4, MSM5 or MSM7 message
+#[derive(Debug, Clone, PartialEq)]
+pub struct MsmMessage {
+    message_number: u16,
+    station_id: u16,
+    epoch_time: u32,
+    multiple_message: bool,
+    iods: u8,
+    satellites: Vec<MsmSatellite>,
+    signals: Vec<MsmSignal>,
+}
+
+impl MsmMessage {
+    /// Checks if a message number is an MSM4, MSM5 or MSM7 message
+    pub fn is_supported(message_number: u16) -> bool {
+        msm_constellation(message_number).is_some() && matches!(message_number % 10, 4 | 5 | 7)
+    }
+
+    /// Decodes an MSM4, MSM5 or MSM7 payload
+    pub fn decode(payload: &[u8]) -> Result<MsmMessage, RtcmError> {
+        let mut bits = BitReader::new(payload);
+        let message_number = bits.u(12)? as u16;
+        if !MsmMessage::is_supported(message_number) {
+            return Err(RtcmError::UnsupportedMessage(message_number));
+        }
+        let msm_type = message_number % 10;
+        let constellation = msm_constellation(message_number).unwrap();
+
+        let station_id = bits.u(12)? as u16;
+        let epoch_time = bits.u(30)? as u32;
+        let multiple_message = bits.flag()?;
+        let iods = bits.u(3)? as u8;
+        // Reserved, clock steering, external clock and smoothing fields
+        bits.skip(7 + 2 + 2 + 1 + 3)?;
+
+        let sat_mask = bits.u(64)?;
+        let sig_mask = bits.u(32)?;
+        let sats: Vec<u16> = (0..64)
+            .filter(|i| sat_mask & (1 << (63 - i)) != 0)
+            .map(|i| i + 1)
+            .collect();
+        let sig_ids: Vec<u8> = (0..32)
+            .filter(|i| sig_mask & (1 << (31 - i)) != 0)
+            .map(|i| i + 1)
+            .collect();
+        if sats.len() * sig_ids.len() > MAX_CELLS {
+            return Err(RtcmError::InvalidMessage);
+        }
+        let mut cells = Vec::new();
+        for satellite in 0..sats.len() {
+            for signal_id in sig_ids.iter() {
+                if bits.flag()? {
+                    cells.push((satellite, *signal_id));
+                }
+            }
+        }
+
+        let n = sats.len();
+        let extended = msm_type != 4;
+        let mut rough_ms = Vec::with_capacity(n);
+        for _ in 0..n {
+            rough_ms.push(bits.u(8)?);
+        }
+        let mut extended_info = vec![None; n];
+        if extended {
+            for info in extended_info.iter_mut() {
+                *info = Some(bits.u(4)? as u8);
+            }
+        }
+        let mut satellites = Vec::with_capacity(n);
+        for (i, sat) in sats.iter().enumerate() {
+            let fraction = bits.u(10)? as f64 / 1024.0;
+            satellites.push(MsmSatellite {
+                sat: rtcm_sat_to_sat(constellation, *sat),
+                rough_range: if rough_ms[i] == 0xFF {
+                    None
+                } else {
+                    Some(rough_ms[i] as f64 + fraction)
+                },
+                extended_info: extended_info[i],
+                rough_range_rate: None,
+            });
+        }
+        if extended {
+            for satellite in satellites.iter_mut() {
+                let rate = bits.s(14)?;
+                if rate != -(1 << 13) {
+                    satellite.rough_range_rate = Some(rate as f64);
+                }
+            }
+        }
+
+        // The signal fields differ in size and resolution between MSM types
+        let (pr_len, pr_scale, phase_len, phase_scale, lock_len, cn0_len, cn0_scale) =
+            if msm_type == 7 {
+                (20, 2f64.powi(-29), 24, 2f64.powi(-31), 10, 10, 1.0 / 16.0)
+            } else {
+                (15, 2f64.powi(-24), 22, 2f64.powi(-29), 4, 6, 1.0)
+            };
+        let read_column = |bits: &mut BitReader, len: usize, signed: bool| {
+            (0..cells.len())
+                .map(|_| {
+                    if signed {
+                        bits.s(len)
+                    } else {
+                        bits.u(len).map(|value| value as i64)
+                    }
+                })
+                .collect::<Result<Vec<i64>, RtcmError>>()
+        };
+        let fine_pr = read_column(&mut bits, pr_len, true)?;
+        let fine_phase = read_column(&mut bits, phase_len, true)?;
+        let lock = read_column(&mut bits, lock_len, false)?;
+        let half_cycle = read_column(&mut bits, 1, false)?;
+        let cn0 = read_column(&mut bits, cn0_len, false)?;
+        let fine_rate = if extended {
+            read_column(&mut bits, 15, true)?
+        } else {
+            Vec::new()
+        };
+
+        let signals = cells
+            .iter()
+            .enumerate()
+            .map(|(i, (satellite, signal_id))| {
+                let rough = satellites[*satellite].rough_range;
+                let fine = |value: i64, len: usize, scale: f64| {
+                    if value == -(1 << (len - 1)) {
+                        None
+                    } else {
+                        rough.map(|rough| (rough + value as f64 * scale) * LIGHT_MS)
+                    }
+                };
+                let phase_range_rate =
+                    match (satellites[*satellite].rough_range_rate, fine_rate.get(i)) {
+                        (Some(rough), Some(fine)) if *fine != -(1 << 14) => {
+                            Some(rough + *fine as f64 * 0.0001)
+                        }
+                        _ => None,
+                    };
+                MsmSignal {
+                    satellite: *satellite,
+                    signal_id: *signal_id,
+                    pseudorange: fine(fine_pr[i], pr_len, pr_scale),
+                    phase_range: fine(fine_phase[i], phase_len, phase_scale),
+                    lock_time: if msm_type == 7 {
+                        extended_lock_time(lock[i] as u16)
+                    } else {
+                        lock_time(lock[i] as u8)
+                    },
+                    half_cycle_ambiguity: half_cycle[i] == 1,
+                    cn0: if cn0[i] == 0 {
+                        None
+                    } else {
+                        Some(cn0[i] as f64 * cn0_scale)
+                    },
+                    phase_range_rate,
+                }
+            })
+            .collect();
+
+        Ok(MsmMessage {
+            message_number,
+            station_id,
+            epoch_time,
+            multiple_message,
+            iods,
+            satellites,
+            signals,
+        })
+    }
+
+    pub fn message_number(&self) -> u16 {
+        self.message_number
+    }
+
+    /// Gets the MSM type, 4, 5 or 7
+    pub fn msm_type(&self) -> u8 {
+        (self.message_number % 10) as u8
+    }
+
+    pub fn constellation(&self) -> Constellation {
+        msm_constellation(self.message_number).unwrap()
+    }
+
+    pub fn station_id(&self) -> u16 {
+        self.station_id
+    }
+
+    /// Gets the raw epoch time field
+    ///
+    /// This is the time of week in milliseconds in the time scale of the
+    /// constellation, except for GLONASS where it is the day of week in the
+    /// top 3 bits followed by the time of day in milliseconds.
+    pub fn epoch_time(&self) -> u32 {
+        self.epoch_time
+    }
+
+    /// Checks if more messages for the same epoch follow this one
+    pub fn multiple_message(&self) -> bool {
+        self.multiple_message
+    }
+
+    /// Gets the issue of data station, which changes when the station
+    /// configuration changes
+    pub fn iods(&self) -> u8 {
+        self.iods
+    }
+
+    pub fn satellites(&self) -> &[MsmSatellite] {
+        &self.satellites
+    }
+
+    pub fn signals(&self) -> &[MsmSignal] {
+        &self.signals
+    }
+
+    /// Gets the epoch of the observations as a GPS time
+    ///
+    /// The epoch time only holds the time of week, so the week (and for
+    /// GLONASS the leap seconds and possibly the day) is taken from the
+    /// closest time to `reference`, which must be within half a week of the
+    /// epoch.
+    pub fn time(&self, reference: &GpsTime) -> GpsTime {
+        let epoch_s = |ms: u32| f64::from(ms) / 1000.0;
+        match self.constellation() {
+            Constellation::Glo => {
+                let day = self.epoch_time >> 27;
+                let time_of_day = epoch_s(self.epoch_time & ((1 << 27) - 1)) - GLO_UTC_OFFSET
+                    + reference.utc_offset_hardcoded();
+                if day < 7 {
+                    nearest_time(
+                        reference,
+                        f64::from(day) * DAY.as_secs_f64() + time_of_day,
+                        WEEK,
+                    )
+                } else {
+                    // The day of week is unknown
+                    nearest_time(reference, time_of_day, DAY)
+                }
+            }
+            Constellation::Bds => nearest_time(
+                reference,
+                epoch_s(self.epoch_time) + BDS_SECOND_TO_GPS_SECOND,
+                WEEK,
+            ),
+            _ => nearest_time(reference, epoch_s(self.epoch_time), WEEK),
+        }
+    }
+
+    /// Gets the GLONASS frequency channel number of a satellite from the
+    /// extended satellite information
+    pub fn glonass_fcn(&self, satellite: &MsmSatellite) -> Option<i8> {
+        match (self.constellation(), satellite.extended_info) {
+            (Constellation::Glo, Some(info)) if info <= 13 => Some(info as i8 - 7),
+            _ => None,
+        }
+    }
+
+    /// Converts the observations into navigation measurements
+    ///
+    /// Signals which don't have an equivalent [`Code`] are left out. The
+    /// carrier phase and doppler of the GLONASS FDMA signals need the
+    /// frequency channel number, so they are only given in MSM5 and MSM7.
+    pub fn measurements(&self) -> Vec<NavigationMeasurement> {
+        self.measurements_with_fcns(&|_| None)
+    }
+
+    /// Converts the observations, using `fcn` to look up the GLONASS
+    /// frequency channel numbers missing from the message
+    fn measurements_with_fcns(
+        &self,
+        lookup: &dyn Fn(u16) -> Option<i8>,
+    ) -> Vec<NavigationMeasurement> {
+        let constellation = self.constellation();
+        self.signals
+            .iter()
+            .filter_map(|signal| {
+                let satellite = &self.satellites[signal.satellite];
+                let code = signal_code(constellation, signal.signal_id)?;
+                let sid = GnssSignal::new(satellite.sat, code).ok()?;
+                let fcn = self
+                    .glonass_fcn(satellite)
+                    .or_else(|| lookup(satellite.sat));
+                let wavelength = carrier_frequency(sid, fcn).map(|f| SPEED_OF_LIGHT / f);
+
+                let mut measurement = NavigationMeasurement::new();
+                measurement.set_sid(sid);
+                if let Some(pseudorange) = signal.pseudorange {
+                    measurement.set_pseudorange(pseudorange);
+                }
+                if let (Some(phase_range), Some(wavelength)) = (signal.phase_range, wavelength) {
+                    measurement.set_carrier_phase(phase_range / wavelength);
+                    measurement.set_half_cycle_known(!signal.half_cycle_ambiguity);
+                }
+                if let (Some(rate), Some(wavelength)) = (signal.phase_range_rate, wavelength) {
+                    measurement.set_measured_doppler(-rate / wavelength);
+                }
+                if let Some(cn0) = signal.cn0 {
+                    measurement.set_cn0(cn0);
+                }
+                measurement.set_lock_time(signal.lock_time);
+                Some(measurement)
+            })
+            .collect()
+    }
+}
+
+/// Gathers the MSMs of all constellations into epochs of measurements
+///
+/// Messages are expected in the order they are sent by a station, with the
+/// multiple message bit set on all but the last message of an epoch. A
+/// partially received epoch is dropped when a message for a later epoch
+/// arrives.
+///
+/// The GLONASS frequency channel numbers found in MSM5 and MSM7 messages are
+/// remembered, so that the carrier phase of stations only sending MSM4 can be
+/// used once they are known. They can also be set from another source, such
+/// as the GLONASS ephemerides.
+#[derive(Debug, Clone)]
+pub struct MsmDecoder {
+    reference: GpsTime,
+    glo_slots: GloSlotMap,
+    pending: Option<(GpsTime, Vec<NavigationMeasurement>)>,
+}
+
+impl MsmDecoder {
+    /// Makes a decoder, with a rough current time used to find the week of
+    /// the first epoch
+    pub fn new(reference: GpsTime) -> MsmDecoder {
+        MsmDecoder {
+            reference,
+            glo_slots: GloSlotMap::new(),
+            pending: None,
+        }
+    }
+
+    /// Sets the frequency channel number of a GLONASS slot
+    ///
+    /// Invalid slots and frequency channel numbers are ignored.
+    pub fn set_glonass_fcn(&mut self, slot: u16, fcn: i8) {
+        let _ = self.glo_slots.set_fcn(slot, fcn);
+    }
+
+    /// Gets the frequency channel number of a GLONASS slot, if known
+    pub fn glonass_fcn(&self, slot: u16) -> Option<i8> {
+        self.glo_slots.fcn(slot)
+    }
+
+    /// Gets the known frequency channel numbers of the GLONASS slots
+    pub fn glo_slots(&self) -> &GloSlotMap {
+        &self.glo_slots
+    }
+
+    /// Adds a message, getting the time and measurements of an epoch once
+    /// its last message has been added
+    ///
+    /// When several messages contain the same signal only the first one is
+    /// kept.
+    pub fn push(&mut self, message: &MsmMessage) -> Option<(GpsTime, Vec<NavigationMeasurement>)> {
+        let time = message.time(&self.reference);
+        for satellite in message.satellites() {
+            if let Some(fcn) = message.glonass_fcn(satellite) {
+                self.set_glonass_fcn(satellite.sat(), fcn);
+            }
+        }
+
+        let stale = match &self.pending {
+            Some((pending_time, _)) => time.diff(pending_time).abs() > 1e-6,
+            None => false,
+        };
+        if stale {
+            self.pending = None;
+        }
+        let slots = self.glo_slots;
+        let lookup = move |slot: u16| slots.fcn(slot);
+        let (_, measurements) = self.pending.get_or_insert_with(|| (time, Vec::new()));
+        for measurement in message.measurements_with_fcns(&lookup) {
+            if !measurements.iter().any(|m| m.sid() == measurement.sid()) {
+                measurements.push(measurement);
+            }
+        }
+
+        if message.multiple_message() {
+            None
+        } else {
+            self.reference = time;
+            self.pending.take()
+        }
+    }
+}
+
+/// Gets the constellation of an MSM message number
+fn msm_constellation(message_number: u16) -> Option<Constellation> {
+    match message_number {
+        1071..=1077 => Some(Constellation::Gps),
+        1081..=1087 => Some(Constellation::Glo),
+        1091..=1097 => Some(Constellation::Gal),
+        1101..=1107 => Some(Constellation::Sbas),
+        1111..=1117 => Some(Constellation::Qzs),
+        1121..=1127 => Some(Constellation::Bds),
+        _ => None,
+    }
+}
+
+/// Converts an RTCM satellite ID, its position in the satellite mask, into a
+/// satellite number
+fn rtcm_sat_to_sat(constellation: Constellation, sat: u16) -> u16 {
+    match constellation {
+        Constellation::Sbas => sat + 119,
+        Constellation::Qzs => sat + 192,
+        _ => sat,
+    }
+}
+
+/// Maps an RTCM signal ID to a code
+///
+/// The GPS L1 and L2 P(Y) signals tracked with Z-tracking (`1W`, `2W`) are
+/// mapped to the P codes.
+fn signal_code(constellation: Constellation, signal_id: u8) -> Option<Code> {
+    let code = match (constellation, signal_id) {
+        (Constellation::Gps, 2) => Code::GpsL1ca,
+        (Constellation::Gps, 3) | (Constellation::Gps, 4) => Code::GpsL1p,
+        (Constellation::Gps, 9) | (Constellation::Gps, 10) => Code::GpsL2p,
+        (Constellation::Gps, 15) => Code::GpsL2cm,
+        (Constellation::Gps, 16) => Code::GpsL2cl,
+        (Constellation::Gps, 17) => Code::GpsL2cx,
+        (Constellation::Gps, 22) => Code::GpsL5i,
+        (Constellation::Gps, 23) => Code::GpsL5q,
+        (Constellation::Gps, 24) => Code::GpsL5x,
+        (Constellation::Gps, 30) => Code::GpsL1ci,
+        (Constellation::Gps, 31) => Code::GpsL1cq,
+        (Constellation::Gps, 32) => Code::GpsL1cx,
+        (Constellation::Glo, 2) => Code::GloL1of,
+        (Constellation::Glo, 3) => Code::GloL1p,
+        (Constellation::Glo, 8) => Code::GloL2of,
+        (Constellation::Glo, 9) => Code::GloL2p,
+        (Constellation::Gal, 2) => Code::GalE1c,
+        (Constellation::Gal, 4) => Code::GalE1b,
+        (Constellation::Gal, 5) => Code::GalE1x,
+        (Constellation::Gal, 8) => Code::GalE6c,
+        (Constellation::Gal, 10) => Code::GalE6b,
+        (Constellation::Gal, 11) => Code::GalE6x,
+        (Constellation::Gal, 14) => Code::GalE7i,
+        (Constellation::Gal, 15) => Code::GalE7q,
+        (Constellation::Gal, 16) => Code::GalE7x,
+        (Constellation::Gal, 18) => Code::GalE8i,
+        (Constellation::Gal, 19) => Code::GalE8q,
+        (Constellation::Gal, 20) => Code::GalE8x,
+        (Constellation::Gal, 22) => Code::GalE5i,
+        (Constellation::Gal, 23) => Code::GalE5q,
+        (Constellation::Gal, 24) => Code::GalE5x,
+        (Constellation::Sbas, 2) => Code::SbasL1ca,
+        (Constellation::Sbas, 22) => Code::SbasL5i,
+        (Constellation::Sbas, 23) => Code::SbasL5q,
+        (Constellation::Sbas, 24) => Code::SbasL5x,
+        (Constellation::Qzs, 2) => Code::QzsL1ca,
+        (Constellation::Qzs, 15) => Code::QzsL2cm,
+        (Constellation::Qzs, 16) => Code::QzsL2cl,
+        (Constellation::Qzs, 17) => Code::QzsL2cx,
+        (Constellation::Qzs, 22) => Code::QzsL5i,
+        (Constellation::Qzs, 23) => Code::QzsL5q,
+        (Constellation::Qzs, 24) => Code::QzsL5x,
+        (Constellation::Qzs, 30) => Code::QzsL1ci,
+        (Constellation::Qzs, 31) => Code::QzsL1cq,
+        (Constellation::Qzs, 32) => Code::QzsL1cx,
+        (Constellation::Bds, 2) => Code::Bds2B1,
+        (Constellation::Bds, 8) => Code::Bds3B3i,
+        (Constellation::Bds, 9) => Code::Bds3B3q,
+        (Constellation::Bds, 10) => Code::Bds3B3x,
+        (Constellation::Bds, 14) => Code::Bds2B2,
+        (Constellation::Bds, 15) => Code::Bds3B7q,
+        (Constellation::Bds, 16) => Code::Bds3B7x,
+        (Constellation::Bds, 22) => Code::Bds3B5i,
+        (Constellation::Bds, 23) => Code::Bds3B5q,
+        (Constellation::Bds, 24) => Code::Bds3B5x,
+        (Constellation::Bds, 30) => Code::Bds3B1ci,
+        (Constellation::Bds, 31) => Code::Bds3B1cq,
+        (Constellation::Bds, 32) => Code::Bds3B1cx,
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Gets the carrier frequency of a signal, which for the GLONASS FDMA signals
+/// depends on the frequency channel number
+fn carrier_frequency(sid: GnssSignal, fcn: Option<i8>) -> Option<f64> {
+    match sid.code() {
+        Code::GloL1of | Code::GloL1p | Code::GloL2of | Code::GloL2p => {
+            fcn.and_then(|k| sid.code().glo_fdma_frequency(k))
+        }
+        _ => Some(sid.carrier_frequency()),
+    }
+}
+
+/// Decodes the 4 bit lock time indicator of MSM4 and MSM5, giving the
+/// minimum lock time
+fn lock_time(indicator: u8) -> Duration {
+    match indicator {
+        0 => Duration::from_millis(0),
+        i => Duration::from_millis(1 << (i + 4)),
+    }
+}
+
+/// Decodes the 10 bit extended lock time indicator of MSM7, giving the
+/// minimum lock time
+fn extended_lock_time(indicator: u16) -> Duration {
+    let indicator = u64::from(indicator.min(704));
+    if indicator < 64 {
+        Duration::from_millis(indicator)
+    } else {
+        let n = indicator / 32 - 1;
+        Duration::from_millis((1 << n) * (indicator - 32 * n))
+    }
+}
+
+/// Finds the time closest to `reference` whose time of week, modulo
+/// `period`, is `time`
+pub(super) fn nearest_time(reference: &GpsTime, time: f64, period: Duration) -> GpsTime {
+    let period = period.as_secs_f64();
+    let mut delta = (time - reference.tow()).rem_euclid(period);
+    if delta > period / 2.0 {
+        delta -= period;
+    }
+    // The epochs have a millisecond resolution
+    let mut tow = ((reference.tow() + delta) * 1000.0).round() / 1000.0;
+    let mut wn = reference.wn();
+    let week = WEEK.as_secs_f64();
+    if tow < 0.0 {
+        tow += week;
+        wn -= 1;
+    } else if tow >= week {
+        tow -= week;
+        wn += 1;
+    }
+    GpsTime::new_unchecked(wn, tow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtcm::tests::PayloadBuilder;
+    use crate::rtcm::RtcmMessage;
+    use float_eq::assert_float_eq;
+
+    /// Observations of one signal used to build a test message
+    struct Cell {
+        range_ms: f64,
+        phase_ms: f64,
+        rate: f64,
+        lock: u64,
+        cn0: f64,
+    }
+
+    /// Builds an MSM with every satellite having every signal
+    fn build_msm(
+        number: u16,
+        epoch: u32,
+        multiple: bool,
+        sats: &[(u64, Option<u64>)],
+        sig_ids: &[u64],
+        cell: impl Fn(usize, usize) -> Cell,
+    ) -> Vec<u8> {
+        let msm_type = number % 10;
+        let mut b = PayloadBuilder::new(number)
+            .u(42, 12)
+            .u(u64::from(epoch), 30)
+            .u(u64::from(multiple), 1)
+            .u(0, 3 + 7 + 2 + 2 + 1 + 3);
+        let sat_mask = sats.iter().fold(0, |acc, (sat, _)| acc | 1 << (64 - sat));
+        let sig_mask = sig_ids.iter().fold(0, |acc, sig| acc | 1 << (32 - sig));
+        b = b.u(sat_mask, 64).u(sig_mask, 32);
+        for _ in 0..sats.len() * sig_ids.len() {
+            b = b.u(1, 1);
+        }
+
+        // The rough values are the first signal of each satellite rounded
+        let rough: Vec<(u64, u64, i64)> = (0..sats.len())
+            .map(|i| {
+                let c = cell(i, 0);
+                let rough = (c.range_ms * 1024.0).round() / 1024.0;
+                let ms = rough.floor();
+                let fraction = (rough - ms) * 1024.0;
+                (ms as u64, fraction as u64, c.rate.round() as i64)
+            })
+            .collect();
+        for (ms, _, _) in rough.iter() {
+            b = b.u(*ms, 8);
+        }
+        if msm_type != 4 {
+            for (_, info) in sats.iter() {
+                b = b.u(info.unwrap_or(0), 4);
+            }
+        }
+        for (_, fraction, _) in rough.iter() {
+            b = b.u(*fraction, 10);
+        }
+        if msm_type != 4 {
+            for (_, _, rate) in rough.iter() {
+                b = b.s(*rate, 14);
+            }
+        }
+
+        let rough_ms = |i: usize| rough[i].0 as f64 + rough[i].1 as f64 / 1024.0;
+        let cells: Vec<(usize, Cell)> = (0..sats.len())
+            .flat_map(|i| (0..sig_ids.len()).map(move |j| (i, j)))
+            .map(|(i, j)| (i, cell(i, j)))
+            .collect();
+        let (pr_len, pr_scale, phase_len, phase_scale) = if msm_type == 7 {
+            (20, 2f64.powi(29), 24, 2f64.powi(31))
+        } else {
+            (15, 2f64.powi(24), 22, 2f64.powi(29))
+        };
+        for (i, c) in cells.iter() {
+            b = b.s(
+                ((c.range_ms - rough_ms(*i)) * pr_scale).round() as i64,
+                pr_len,
+            );
+        }
+        for (i, c) in cells.iter() {
+            b = b.s(
+                ((c.phase_ms - rough_ms(*i)) * phase_scale).round() as i64,
+                phase_len,
+            );
+        }
+        for (_, c) in cells.iter() {
+            b = b.u(c.lock, if msm_type == 7 { 10 } else { 4 });
+        }
+        for _ in cells.iter() {
+            b = b.u(0, 1);
+        }
+        for (_, c) in cells.iter() {
+            if msm_type == 7 {
+                b = b.u((c.cn0 * 16.0).round() as u64, 10);
+            } else {
+                b = b.u(c.cn0.round() as u64, 6);
+            }
+        }
+        if msm_type != 4 {
+            for (i, c) in cells.iter() {
+                b = b.s(
+                    ((c.rate - rough[*i].2 as f64) * 10_000.0).round() as i64,
+                    15,
+                );
+            }
+        }
+        b.build()
+    }
+
+    fn gps_cell(i: usize, j: usize) -> Cell {
+        Cell {
+            range_ms: 70.123_456 + i as f64 + 0.000_01 * j as f64,
+            phase_ms: 70.123_457 + i as f64 + 0.000_02 * j as f64,
+            rate: -512.345 + 100.0 * i as f64,
+            lock: 9,
+            cn0: 41.25 + j as f64,
+        }
+    }
+
+    #[test]
+    fn decode_msm7() {
+        let payload = build_msm(
+            1077,
+            302_400_000,
+            false,
+            &[(5, None), (12, None)],
+            &[2, 15],
+            gps_cell,
+        );
+        let msm = match RtcmMessage::decode(&payload).unwrap() {
+            RtcmMessage::Msm(msm) => msm,
+            other => panic!("Unexpected message {:?}", other),
+        };
+        assert_eq!(msm.msm_type(), 7);
+        assert_eq!(msm.constellation(), Constellation::Gps);
+        assert_eq!(msm.station_id(), 42);
+        assert!(!msm.multiple_message());
+        assert_eq!(msm.satellites().len(), 2);
+        assert_eq!(msm.signals().len(), 4);
+        assert_eq!(msm.satellites()[1].sat(), 12);
+
+        let signal = msm.signals()[3];
+        assert_eq!((signal.satellite(), signal.signal_id()), (1, 15));
+        assert_float_eq!(
+            signal.pseudorange().unwrap(),
+            71.123_466 * LIGHT_MS,
+            abs <= 1e-3
+        );
+        assert_float_eq!(
+            signal.phase_range().unwrap(),
+            71.123_477 * LIGHT_MS,
+            abs <= 1e-3
+        );
+        assert_float_eq!(signal.phase_range_rate().unwrap(), -412.345, abs <= 1e-4);
+        assert_float_eq!(signal.cn0().unwrap(), 42.25, abs <= 1e-9);
+        assert_eq!(signal.lock_time(), Duration::from_millis(9));
+
+        let measurements = msm.measurements();
+        assert_eq!(measurements.len(), 4);
+        let l2 = &measurements[3];
+        assert_eq!(l2.sid(), GnssSignal::new(12, Code::GpsL2cm).unwrap());
+        let wavelength = SPEED_OF_LIGHT / l2.sid().carrier_frequency();
+        assert_float_eq!(
+            l2.carrier_phase().unwrap() * wavelength,
+            71.123_477 * LIGHT_MS,
+            abs <= 1e-3
+        );
+        assert_float_eq!(
+            l2.measured_doppler().unwrap() * wavelength,
+            412.345,
+            abs <= 1e-4
+        );
+        assert!(l2.half_cycle_known());
+
+        let reference = GpsTime::new(2100, 604_000.0).unwrap();
+        let time = msm.time(&reference);
+        assert_eq!((time.wn(), time.tow()), (2100, 302_400.0));
+
+        assert_eq!(
+            MsmMessage::decode(&payload[..payload.len() - 10]),
+            Err(RtcmError::Truncated)
+        );
+    }
+
+    #[test]
+    fn decode_msm4_and_msm5() {
+        let payload = build_msm(1074, 1000, false, &[(5, None)], &[2], gps_cell);
+        let msm = MsmMessage::decode(&payload).unwrap();
+        let signal = msm.signals()[0];
+        assert_float_eq!(
+            signal.pseudorange().unwrap(),
+            70.123_456 * LIGHT_MS,
+            abs <= 0.02
+        );
+        assert_eq!(signal.phase_range_rate(), None);
+        assert_eq!(signal.lock_time(), Duration::from_millis(8192));
+        assert_float_eq!(signal.cn0().unwrap(), 41.0, abs <= 1e-9);
+        assert_eq!(msm.measurements()[0].measured_doppler(), None);
+
+        // GLONASS MSM5 with the frequency channel number
+        let payload = build_msm(1085, 0, false, &[(3, Some(2))], &[2], gps_cell);
+        let msm = MsmMessage::decode(&payload).unwrap();
+        assert_eq!(msm.glonass_fcn(&msm.satellites()[0]), Some(-5));
+        let measurement = &msm.measurements()[0];
+        let wavelength = SPEED_OF_LIGHT / (1.602e9 - 5.0 * 562.5e3);
+        assert_float_eq!(
+            measurement.carrier_phase().unwrap() * wavelength,
+            70.123_457 * LIGHT_MS,
+            abs <= 1e-3
+        );
+    }
+
+    #[test]
+    fn epoch_times() {
+        let reference = GpsTime::new(2000, 100.0).unwrap();
+        let msm = |number, epoch| {
+            MsmMessage::decode(&build_msm(
+                number,
+                epoch,
+                false,
+                &[(1, None)],
+                &[2],
+                gps_cell,
+            ))
+            .unwrap()
+        };
+
+        // The end of the previous week
+        let time = msm(1077, 604_790_000).time(&reference);
+        assert_eq!((time.wn(), time.tow()), (1999, 604_790.0));
+
+        // BeiDou time is 14 seconds behind
+        let time = msm(1127, 86_000).time(&reference);
+        assert_eq!((time.wn(), time.tow()), (2000, 100.0));
+
+        // GLONASS time is UTC + 3 hours, with the day of week
+        let offset = reference.utc_offset_hardcoded();
+        let glo_epoch = |day: u32, seconds: f64| (day << 27) | (seconds * 1000.0) as u32;
+        let time = msm(1087, glo_epoch(0, 10_800.0 + 100.0 - offset)).time(&reference);
+        assert_eq!((time.wn(), time.tow()), (2000, 100.0));
+        let time = msm(1087, glo_epoch(7, 10_800.0 + 100.0 - offset)).time(&reference);
+        assert_eq!((time.wn(), time.tow()), (2000, 100.0));
+        let time = msm(1087, glo_epoch(6, 10_800.0 + 86_000.0 - offset)).time(&reference);
+        assert_eq!((time.wn(), time.tow()), (1999, 604_400.0));
+
+        assert_eq!(lock_time(15), Duration::from_millis(524_288));
+        assert_eq!(
+            extended_lock_time(100),
+            Duration::from_millis(4 * 100 - 256)
+        );
+        assert_eq!(extended_lock_time(704), Duration::from_millis(67_108_864));
+        assert_eq!(extended_lock_time(1000), Duration::from_millis(67_108_864));
+    }
+
+    #[test]
+    fn msm_epochs() {
+        let reference = GpsTime::new(2100, 0.0).unwrap();
+        let offset = reference.utc_offset_hardcoded();
+        let mut decoder = MsmDecoder::new(reference);
+        let decode = |number, epoch, multiple, sat| {
+            MsmMessage::decode(&build_msm(number, epoch, multiple, &[sat], &[2], gps_cell)).unwrap()
+        };
+
+        // An incomplete epoch is dropped
+        assert!(decoder.push(&decode(1077, 1000, true, (5, None))).is_none());
+        let gps = decode(1077, ((1.0 + offset) * 1000.0) as u32, true, (5, None));
+        assert!(decoder.push(&gps).is_none());
+        let glo4 = decode(1084, 10_801_000, false, (3, None));
+        let (time, measurements) = decoder.push(&glo4).unwrap();
+        assert_eq!((time.wn(), time.tow()), (2100, 1.0 + offset));
+        assert_eq!(measurements.len(), 2);
+        assert!(measurements[0].carrier_phase().is_some());
+        assert!(measurements[1].carrier_phase().is_none());
+
+        // The frequency channel number is remembered from MSM5
+        let glo5 = decode(1085, 10_801_000, false, (3, Some(8)));
+        assert!(decoder.push(&glo5).is_some());
+        assert_eq!(decoder.glonass_fcn(3), Some(1));
+        let (_, measurements) = decoder.push(&glo4).unwrap();
+        assert!(measurements[0].carrier_phase().is_some());
+        assert_eq!(decoder.glonass_fcn(0), None);
+    }
+}
@@ -0,0 +1,169 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Reference station coordinates, messages 1005 and 1006
+
+use super::{BitReader, RtcmError};
+use crate::coords::ECEF;
+
+/// Resolution of the station coordinates and antenna height, in meters
+const COORDINATE_SCALE: f64 = 0.0001;
+
+/// Antenna reference point of a reference station
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct StationCoordinates {
+    station_id: u16,
+    itrf_year: u8,
+    gps: bool,
+    glonass: bool,
+    galileo: bool,
+    physical_station: bool,
+    position: ECEF,
+    antenna_height: Option<f64>,
+}
+
+impl StationCoordinates {
+    /// Decodes a message 1005 or 1006 payload
+    pub fn decode(payload: &[u8]) -> Result<StationCoordinates, RtcmError> {
+        let mut bits = BitReader::new(payload);
+        let number = bits.u(12)? as u16;
+        if number != 1005 && number != 1006 {
+            return Err(RtcmError::UnsupportedMessage(number));
+        }
+        let station_id = bits.u(12)? as u16;
+        let itrf_year = bits.u(6)? as u8;
+        let gps = bits.flag()?;
+        let glonass = bits.flag()?;
+        let galileo = bits.flag()?;
+        // The indicator is set for non-physical (virtual) reference stations
+        let physical_station = !bits.flag()?;
+        let x = bits.s(38)?;
+        // Single receiver oscillator indicator and a reserved bit
+        bits.skip(2)?;
+        let y = bits.s(38)?;
+        // Quarter cycle indicator
+        bits.skip(2)?;
+        let z = bits.s(38)?;
+        let antenna_height = if number == 1006 {
+            Some(bits.u(16)? as f64 * COORDINATE_SCALE)
+        } else {
+            None
+        };
+
+        Ok(StationCoordinates {
+            station_id,
+            itrf_year,
+            gps,
+            glonass,
+            galileo,
+            physical_station,
+            position: ECEF::new(
+                x as f64 * COORDINATE_SCALE,
+                y as f64 * COORDINATE_SCALE,
+                z as f64 * COORDINATE_SCALE,
+            ),
+            antenna_height,
+        })
+    }
+
+    pub fn station_id(&self) -> u16 {
+        self.station_id
+    }
+
+    /// Gets the ITRF realization year field, reserved and usually 0
+    pub fn itrf_year(&self) -> u8 {
+        self.itrf_year
+    }
+
+    /// Checks if the station provides GPS observations
+    pub fn gps(&self) -> bool {
+        self.gps
+    }
+
+    /// Checks if the station provides GLONASS observations
+    pub fn glonass(&self) -> bool {
+        self.glonass
+    }
+
+    /// Checks if the station provides Galileo observations
+    pub fn galileo(&self) -> bool {
+        self.galileo
+    }
+
+    /// Checks if the station is a physical station rather than a virtual
+    /// station computed by a network
+    pub fn physical_station(&self) -> bool {
+        self.physical_station
+    }
+
+    /// Gets the ECEF position of the antenna reference point, in meters
+    pub fn position(&self) -> ECEF {
+        self.position
+    }
+
+    /// Gets the height of the antenna reference point above the marker, in
+    /// meters. Only message 1006 contains the height.
+    pub fn antenna_height(&self) -> Option<f64> {
+        self.antenna_height
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rtcm::tests::PayloadBuilder;
+    use crate::rtcm::RtcmMessage;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn decode_station_coordinates() {
+        let payload = PayloadBuilder::new(1006)
+            .u(2003, 12)
+            .u(0, 6)
+            .u(0b1010, 4)
+            .s(-27_031_159_000, 38)
+            .u(0, 2)
+            .s(-42_628_337_000, 38)
+            .u(0, 2)
+            .s(38_850_335_000, 38)
+            .u(15_000, 16)
+            .build();
+
+        let station = match RtcmMessage::decode(&payload).unwrap() {
+            RtcmMessage::StationCoordinates(station) => station,
+            other => panic!("Unexpected message {:?}", other),
+        };
+        assert_eq!(station.station_id(), 2003);
+        assert!(station.gps() && !station.glonass() && station.galileo());
+        assert!(station.physical_station());
+        assert_float_eq!(station.position().x(), -2_703_115.9, abs <= 1e-9);
+        assert_float_eq!(station.position().y(), -4_262_833.7, abs <= 1e-9);
+        assert_float_eq!(station.position().z(), 3_885_033.5, abs <= 1e-9);
+        assert_float_eq!(station.antenna_height().unwrap(), 1.5, abs <= 1e-12);
+
+        // Message 1005 doesn't have the antenna height
+        let payload = PayloadBuilder::new(1005)
+            .u(1, 12)
+            .u(0, 6)
+            .u(0b1111, 4)
+            .s(1, 38)
+            .u(0, 2)
+            .s(2, 38)
+            .u(0, 2)
+            .s(3, 38)
+            .build();
+        let station = StationCoordinates::decode(&payload).unwrap();
+        assert!(!station.physical_station());
+        assert_eq!(station.antenna_height(), None);
+        assert_eq!(
+            StationCoordinates::decode(&payload[..10]),
+            Err(RtcmError::Truncated)
+        );
+    }
+}
@@ -0,0 +1,578 @@
+// Copyright (c) 2024 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Satellite attitude
+//!
+//! GNSS satellites keep their antennas pointed at the Earth, and rotate about
+//! that axis (yaw) to keep their solar panels facing the Sun. The attitude
+//! is needed to apply the satellite antenna offsets and the carrier phase
+//! wind-up in precise positioning.
+//!
+//! Most of the time the satellites follow the nominal yaw steering law, but
+//! when the Sun is close to the orbital plane the nominal yaw rate around
+//! orbit noon and midnight exceeds what the satellite can do. How the
+//! satellites turn instead depends on the satellite type, and is modelled
+//! for the GPS Block IIA, IIR and IIF and the Galileo satellites.
+//!
+//! The body frame follows the IGS conventions: the z axis points at the
+//! center of the Earth, the y axis is along the solar panel axis, and the x
+//! axis completes the frame, pointing to the side of the satellite lit by the
+//! Sun.
+//!
+//! # References
+//!   * Kouba J., "A simplified yaw-attitude model for eclipsing GPS
+//!     satellites", GPS Solutions 13, 1–12 (2009)
+//!   * Dilssner F., "GPS IIF-1 satellite: antenna phase center and attitude
+//!     modeling", Inside GNSS 5(6), 59–64 (2010)
+//!   * European GNSS Service Centre, "Galileo Satellite Metadata"
+//!   * Montenbruck O. et al., "GNSS satellite geometry and attitude models",
+//!     Advances in Space Research 56, 1015–1029 (2015)
+
+use crate::coords::ECEF;
+use crate::ephemeris::SatelliteState;
+use crate::tides::sun_position;
+use crate::time::GpsTime;
+use std::f64::consts::PI;
+
+/// Earth's rotation rate, in rad/s
+const OMEGA_E: f64 = 7.292_115_146_7e-5;
+/// Radius of the Earth's cylindrical shadow, in meters
+const EARTH_RADIUS: f64 = 6_378_137.0;
+/// Smallest magnitude of the beta angle used, the nominal yaw is undefined
+/// when the Sun is in the orbital plane
+const MIN_BETA: f64 = 1e-6;
+
+/// Yaw attitude model of a satellite type
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AttitudeModel {
+    /// Nominal yaw steering at all times
+    Nominal,
+    /// GPS Block IIA, which yaws at its maximum rate through the Earth's
+    /// shadow and recovers the nominal yaw after leaving it
+    GpsIIA,
+    /// GPS Block IIR, which turns at its maximum rate around orbit noon and
+    /// midnight
+    GpsIIR,
+    /// GPS Block IIF, which turns at a constant rate through the Earth's
+    /// shadow and at its maximum rate around orbit noon
+    GpsIIF,
+    /// Galileo IOV, with smoothed yaw steering around orbit noon and
+    /// midnight when the Sun is within 2° of the orbital plane
+    GalileoIov,
+    /// Galileo FOC, with smoothed yaw steering around orbit noon and
+    /// midnight when the Sun is within 4.1° of the orbital plane
+    ///
+    /// The satellites run the smoothing over a fixed time of 5656 seconds,
+    /// which is modelled with the position based smoothing of the IOV
+    /// satellites over the same arc of the orbit.
+    GalileoFoc,
+}
+
+impl AttitudeModel {
+    /// Gets the maximum yaw rate of the satellite type, in degrees per second
+    ///
+    /// These are typical values, individual satellites differ by up to 10%.
+    pub fn max_yaw_rate(&self) -> Option<f64> {
+        match self {
+            AttitudeModel::GpsIIA => Some(0.12),
+            AttitudeModel::GpsIIR => Some(0.2),
+            AttitudeModel::GpsIIF => Some(0.11),
+            _ => None,
+        }
+    }
+
+    /// Calculates the attitude of a satellite at a time, from its ECEF
+    /// position and velocity
+    pub fn attitude(&self, state: &SatelliteState, t: &GpsTime) -> SatelliteAttitude {
+        self.attitude_with_sun(&state.pos, &state.vel, &sun_position(t))
+    }
+
+    /// Calculates the attitude of a satellite from its ECEF position and
+    /// velocity, and the ECEF position of the Sun, all in meters and seconds
+    pub fn attitude_with_sun(&self, pos: &ECEF, vel: &ECEF, sun: &ECEF) -> SatelliteAttitude {
+        let geometry = OrbitGeometry::new(pos, vel, sun);
+        let yaw = match self {
+            AttitudeModel::Nominal => geometry.nominal_yaw(),
+            AttitudeModel::GpsIIA => geometry.gps_iia_yaw(self.max_yaw_rate_rad()),
+            AttitudeModel::GpsIIR => geometry
+                .turning_yaw(geometry.mu, self.max_yaw_rate_rad())
+                .unwrap_or_else(|| geometry.nominal_yaw()),
+            AttitudeModel::GpsIIF => {
+                let rate = self.max_yaw_rate_rad();
+                let midnight = match geometry.shadow_half_arc() {
+                    Some(_) => geometry.shadow_crossing_yaw(),
+                    None => geometry.rate_limited_yaw(geometry.mu, 0.0, rate),
+                };
+                midnight
+                    .or_else(|| geometry.rate_limited_yaw(geometry.mu, PI, rate))
+                    .unwrap_or_else(|| geometry.nominal_yaw())
+            }
+            AttitudeModel::GalileoIov => {
+                geometry.galileo_yaw(2.0f64.to_radians(), 15.0f64.to_radians())
+            }
+            AttitudeModel::GalileoFoc => {
+                geometry.galileo_yaw(4.1f64.to_radians(), 20.1f64.to_radians())
+            }
+        };
+        SatelliteAttitude::new(&geometry, yaw)
+    }
+
+    fn max_yaw_rate_rad(&self) -> f64 {
+        self.max_yaw_rate().unwrap_or(f64::INFINITY).to_radians()
+    }
+}
+
+/// Wraps an angle into (-π, π]
+fn wrap(angle: f64) -> f64 {
+    let wrapped = angle.rem_euclid(2.0 * PI);
+    if wrapped > PI {
+        wrapped - 2.0 * PI
+    } else {
+        wrapped
+    }
+}
+
+/// Geometry of the Sun and the orbit of a satellite
+///
+/// The orbit frame has its x axis along track, its y axis opposite the orbit
+/// normal and its z axis pointing at the center of the Earth.
+struct OrbitGeometry {
+    along_track: ECEF,
+    anti_normal: ECEF,
+    nadir: ECEF,
+    /// Components of the unit vector to the Sun in the orbit frame
+    sun: [f64; 3],
+    /// Elevation of the Sun above the orbital plane, in radians
+    beta: f64,
+    /// Angle of the satellite from orbit midnight, in radians
+    mu: f64,
+    /// Rate of the orbit angle, in rad/s
+    mu_rate: f64,
+    /// Distance of the satellite from the center of the Earth, in meters
+    radius: f64,
+}
+
+impl OrbitGeometry {
+    fn new(pos: &ECEF, vel: &ECEF, sun: &ECEF) -> OrbitGeometry {
+        // The orbital plane is fixed in inertial space, so the velocity due
+        // to the rotation of the ECEF frame is added back
+        let inertial_vel = *vel + ECEF::new(-OMEGA_E * pos.y(), OMEGA_E * pos.x(), 0.0);
+        let momentum = pos.cross(&inertial_vel);
+        let radius = pos.norm();
+
+        let nadir = -1.0 / radius * *pos;
+        let anti_normal = -1.0 * momentum.unit_vector();
+        let along_track = anti_normal.cross(&nadir);
+        let to_sun = (*sun - pos).unit_vector();
+        let sun = [
+            to_sun.dot(&along_track),
+            to_sun.dot(&anti_normal),
+            to_sun.dot(&nadir),
+        ];
+
+        let beta = (-sun[1]).clamp(-1.0, 1.0).asin();
+        let beta = if beta.abs() < MIN_BETA {
+            MIN_BETA.copysign(beta)
+        } else {
+            beta
+        };
+        OrbitGeometry {
+            along_track,
+            anti_normal,
+            nadir,
+            sun,
+            beta,
+            mu: sun[0].atan2(sun[2]),
+            mu_rate: momentum.norm() / (radius * radius),
+            radius,
+        }
+    }
+
+    /// Nominal yaw at an orbit angle, holding the beta angle constant
+    fn nominal_yaw_at(&self, mu: f64) -> f64 {
+        (-self.beta.sin()).atan2(self.beta.cos() * mu.sin())
+    }
+
+    /// Nominal yaw, keeping the x axis pointed towards the Sun
+    fn nominal_yaw(&self) -> f64 {
+        self.sun[1].atan2(self.sun[0])
+    }
+
+    /// Yaw at an orbit angle while the nominal yaw rate around orbit
+    /// midnight (`center` of 0) or noon (`center` of π) exceeds the maximum
+    /// yaw rate
+    ///
+    /// The satellite turns at its maximum rate from when the nominal yaw rate
+    /// first exceeds it, until it catches up with the nominal yaw. Returns
+    /// `None` outside of the turn.
+    fn rate_limited_yaw(&self, mu: f64, center: f64, max_rate: f64) -> Option<f64> {
+        let delta = wrap(mu - center);
+        let tan_beta = self.beta.tan().abs();
+        // The nominal yaw rate is mu_rate * tan_beta * cos(delta) /
+        // (sin²(delta) + tan²(beta)), solved for cos(delta) at the maximum
+        let k = self.mu_rate * tan_beta / max_rate;
+        let cos_start = (-k + (k * k + 4.0 * (1.0 + tan_beta * tan_beta)).sqrt()) / 2.0;
+        if cos_start >= 1.0 || delta.abs() >= PI / 2.0 {
+            return None;
+        }
+        let start = cos_start.acos();
+        if delta < -start {
+            return None;
+        }
+
+        let direction = (self.beta.tan() * center.cos()).signum();
+        let start_yaw = self.nominal_yaw_at(center - start);
+        let yaw = start_yaw + direction * max_rate * (delta + start) / self.mu_rate;
+        if direction * (yaw - self.nominal_yaw_at(mu)) >= 0.0 {
+            None
+        } else {
+            Some(yaw)
+        }
+    }
+
+    /// Yaw at an orbit angle during the rate limited turns around orbit noon
+    /// and midnight, see [`rate_limited_yaw()`](Self::rate_limited_yaw)
+    fn turning_yaw(&self, mu: f64, max_rate: f64) -> Option<f64> {
+        self.rate_limited_yaw(mu, 0.0, max_rate)
+            .or_else(|| self.rate_limited_yaw(mu, PI, max_rate))
+    }
+
+    /// Half of the arc of the orbit in the Earth's shadow, if the orbit
+    /// crosses the shadow
+    fn shadow_half_arc(&self) -> Option<f64> {
+        let cos_arc = (1.0 - (EARTH_RADIUS / self.radius).powi(2)).sqrt() / self.beta.cos();
+        if cos_arc < 1.0 {
+            Some(cos_arc.acos())
+        } else {
+            None
+        }
+    }
+
+    /// Yaw of the GPS Block IIF satellites in the Earth's shadow, turning at
+    /// a constant rate from the nominal yaw at shadow entry to the nominal
+    /// yaw at shadow exit
+    fn shadow_crossing_yaw(&self) -> Option<f64> {
+        let half_arc = self.shadow_half_arc()?;
+        let delta = wrap(self.mu);
+        if delta.abs() > half_arc {
+            return None;
+        }
+        let entry = self.nominal_yaw_at(-half_arc);
+        let exit = self.nominal_yaw_at(half_arc);
+        Some(entry + (exit - entry) * (delta + half_arc) / (2.0 * half_arc))
+    }
+
+    /// Yaw of the GPS Block IIA satellites
+    ///
+    /// In the Earth's shadow the satellites yaw at their maximum rate in the
+    /// direction of their positive yaw bias, and after leaving the shadow
+    /// they turn back to the nominal yaw at their maximum rate.
+    fn gps_iia_yaw(&self, max_rate: f64) -> f64 {
+        let nominal = self
+            .turning_yaw(self.mu, max_rate)
+            .unwrap_or_else(|| self.nominal_yaw());
+        let half_arc = match self.shadow_half_arc() {
+            Some(half_arc) => half_arc,
+            None => return nominal,
+        };
+        let delta = wrap(self.mu);
+        if delta < -half_arc || delta > PI / 2.0 {
+            return nominal;
+        }
+
+        let entry = self
+            .turning_yaw(-half_arc, max_rate)
+            .unwrap_or_else(|| self.nominal_yaw_at(-half_arc));
+        let turn = |from: f64, direction: f64, since: f64| {
+            from + direction * max_rate * (delta - since) / self.mu_rate
+        };
+        if delta <= half_arc {
+            return wrap(turn(entry, 1.0, -half_arc));
+        }
+
+        let exit = entry + max_rate * 2.0 * half_arc / self.mu_rate;
+        let direction = wrap(self.nominal_yaw_at(half_arc) - exit).signum();
+        let recovery = turn(exit, direction, half_arc);
+        if direction * wrap(self.nominal_yaw_at(self.mu) - recovery) <= 0.0 {
+            nominal
+        } else {
+            wrap(recovery)
+        }
+    }
+
+    /// Yaw of the Galileo satellites, which replace the component of the
+    /// Sun vector normal to the orbit with a smoothed one around orbit noon
+    /// and midnight
+    fn galileo_yaw(&self, max_beta: f64, arc: f64) -> f64 {
+        let [sx, sy, _] = self.sun;
+        if self.beta.abs() >= max_beta || sx.abs() >= arc.sin() {
+            return self.nominal_yaw();
+        }
+        let target = max_beta.sin() * (-self.beta).signum();
+        let smoothed =
+            0.5 * (target + sy) + 0.5 * (target - sy) * (PI * sx.abs() / arc.sin()).cos();
+        smoothed.atan2(sx)
+    }
+}
+
+/// Attitude of a satellite, the axes of its body frame in ECEF
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SatelliteAttitude {
+    x: ECEF,
+    y: ECEF,
+    z: ECEF,
+    yaw: f64,
+    beta: f64,
+    orbit_angle: f64,
+}
+
+impl SatelliteAttitude {
+    fn new(geometry: &OrbitGeometry, yaw: f64) -> SatelliteAttitude {
+        let x = yaw.cos() * geometry.along_track + yaw.sin() * geometry.anti_normal;
+        let z = geometry.nadir;
+        SatelliteAttitude {
+            x,
+            y: z.cross(&x),
+            z,
+            yaw: wrap(yaw),
+            beta: geometry.beta,
+            orbit_angle: geometry.mu,
+        }
+    }
+
+    /// Gets the x axis of the body frame, towards the side lit by the Sun
+    pub fn x_axis(&self) -> ECEF {
+        self.x
+    }
+
+    /// Gets the y axis of the body frame, along the solar panel axis
+    pub fn y_axis(&self) -> ECEF {
+        self.y
+    }
+
+    /// Gets the z axis of the body frame, towards the center of the Earth
+    pub fn z_axis(&self) -> ECEF {
+        self.z
+    }
+
+    /// Gets the yaw angle, in radians
+    ///
+    /// The yaw is the angle of the x axis from the along track direction,
+    /// positive towards the opposite of the orbit normal.
+    pub fn yaw(&self) -> f64 {
+        self.yaw
+    }
+
+    /// Gets the elevation of the Sun above the orbital plane, in radians
+    pub fn beta(&self) -> f64 {
+        self.beta
+    }
+
+    /// Gets the angle of the satellite from orbit midnight, the point of the
+    /// orbit furthest from the Sun, in radians
+    pub fn orbit_angle(&self) -> f64 {
+        self.orbit_angle
+    }
+
+    /// Gets the rotation matrix from ECEF to the body frame, the rows being
+    /// the body frame axes
+    pub fn rotation(&self) -> [[f64; 3]; 3] {
+        [*self.x.as_ref(), *self.y.as_ref(), *self.z.as_ref()]
+    }
+
+    /// Converts a vector in the body frame, such as an antenna offset, to
+    /// ECEF
+    pub fn body_to_ecef(&self, v: [f64; 3]) -> ECEF {
+        v[0] * self.x + v[1] * self.y + v[2] * self.z
+    }
+
+    /// Converts an ECEF vector to the body frame
+    pub fn ecef_to_body(&self, v: &ECEF) -> [f64; 3] {
+        [self.x.dot(v), self.y.dot(v), self.z.dot(v)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+
+    /// GPS orbit radius, in meters
+    const GPS_RADIUS: f64 = 26_560e3;
+
+    /// Position and ECEF velocity of a satellite on a circular equatorial
+    /// orbit, with the Sun far enough away to have no parallax at a beta
+    /// angle in degrees
+    fn orbit(mu: f64, beta: f64, radius: f64) -> (ECEF, ECEF, ECEF) {
+        let rate = (3.986_004_418e14 / radius.powi(3)).sqrt();
+        // Orbit midnight is opposite the Sun, along -x
+        let theta = PI + mu;
+        let pos = ECEF::new(radius * theta.cos(), radius * theta.sin(), 0.0);
+        let inertial_vel = ECEF::new(
+            -radius * rate * theta.sin(),
+            radius * rate * theta.cos(),
+            0.0,
+        );
+        let vel = inertial_vel - ECEF::new(-OMEGA_E * pos.y(), OMEGA_E * pos.x(), 0.0);
+        let beta = beta.to_radians();
+        let sun = ECEF::new(beta.cos(), 0.0, beta.sin());
+        (pos, vel, 1e18 * sun)
+    }
+
+    /// Galileo orbit radius, in meters
+    const GAL_RADIUS: f64 = 29_600e3;
+
+    /// Yaw and nominal yaw over a pass of orbit angles, in degrees, in steps
+    /// of about 10 seconds
+    fn yaw_pass(model: AttitudeModel, beta: f64, center: f64, radius: f64) -> Vec<(f64, f64)> {
+        let step = 10.0 * (3.986_004_418e14 / radius.powi(3)).sqrt();
+        (-400..=400)
+            .map(|i| {
+                let mu = center + f64::from(i) * step;
+                let (pos, vel, sun) = orbit(mu, beta, radius);
+                let attitude = model.attitude_with_sun(&pos, &vel, &sun);
+                let nominal = AttitudeModel::Nominal.attitude_with_sun(&pos, &vel, &sun);
+                (attitude.yaw().to_degrees(), nominal.yaw().to_degrees())
+            })
+            .collect()
+    }
+
+    fn max_rate(yaws: &[(f64, f64)]) -> f64 {
+        yaws.windows(2)
+            .map(|pair| {
+                wrap((pair[1].0 - pair[0].0).to_radians())
+                    .to_degrees()
+                    .abs()
+                    / 10.0
+            })
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn nominal_attitude() {
+        for (mu, beta) in [(0.3, 20.0), (2.0, -40.0), (-1.0, 5.0)].iter() {
+            let (pos, vel, sun) = orbit(*mu, *beta, GPS_RADIUS);
+            let attitude = AttitudeModel::Nominal.attitude_with_sun(&pos, &vel, &sun);
+            assert_float_eq!(attitude.beta().to_degrees(), *beta, abs <= 1e-3);
+            assert_float_eq!(attitude.orbit_angle(), *mu, abs <= 1e-4);
+            assert_float_eq!(
+                attitude.yaw(),
+                (-attitude.beta().tan()).atan2(mu.sin()),
+                abs <= 1e-4
+            );
+
+            // z at the Earth, y perpendicular to the Sun, x on the lit side
+            let to_sun = (sun - pos).unit_vector();
+            assert_float_eq!(
+                attitude.ecef_to_body(&pos),
+                [0.0, 0.0, -GPS_RADIUS],
+                abs_all <= 1e-6
+            );
+            let sun_body = attitude.ecef_to_body(&to_sun);
+            assert_float_eq!(sun_body[1], 0.0, abs <= 1e-9);
+            assert!(sun_body[0] > 0.0);
+
+            let rotation = attitude.rotation();
+            for (i, row) in rotation.iter().enumerate() {
+                for (j, other) in rotation.iter().enumerate() {
+                    let expected = if i == j { 1.0 } else { 0.0 };
+                    assert_float_eq!(
+                        row.iter()
+                            .zip(other.iter())
+                            .map(|(a, b)| a * b)
+                            .sum::<f64>(),
+                        expected,
+                        abs <= 1e-12
+                    );
+                }
+            }
+            assert_float_eq!(
+                *attitude.x_axis().cross(&attitude.y_axis()).as_ref(),
+                *attitude.z_axis().as_ref(),
+                abs_all <= 1e-12
+            );
+            let offset = attitude.body_to_ecef([0.1, -0.2, 1.5]);
+            assert_float_eq!(
+                attitude.ecef_to_body(&offset),
+                [0.1, -0.2, 1.5],
+                abs_all <= 1e-12
+            );
+        }
+    }
+
+    #[test]
+    fn rate_limited_turns() {
+        for model in [AttitudeModel::GpsIIR, AttitudeModel::GpsIIF].iter() {
+            let limit = model.max_yaw_rate().unwrap();
+            for center in [0.0, PI].iter() {
+                let yaws = yaw_pass(*model, 0.5, *center, GPS_RADIUS);
+                let nominal: Vec<_> = yaws.iter().map(|(_, nominal)| (*nominal, 0.0)).collect();
+                assert!(max_rate(&nominal) > 2.0 * limit);
+                assert!(max_rate(&yaws) <= limit * 1.01, "{:?}", model);
+                // Back on the nominal yaw at both ends of the pass
+                for (yaw, nominal) in [yaws[0], yaws[yaws.len() - 1]].iter() {
+                    assert_float_eq!(yaw, nominal, abs <= 1e-9);
+                }
+            }
+        }
+        // Far from the Sun's direction the turns aren't needed
+        let yaws = yaw_pass(AttitudeModel::GpsIIR, 40.0, 0.0, GPS_RADIUS);
+        assert!(yaws
+            .iter()
+            .all(|(yaw, nominal)| (yaw - nominal).abs() < 1e-9));
+    }
+
+    #[test]
+    fn shadow_crossings() {
+        // The Block IIF satellites turn at a constant rate through the shadow
+        let yaws = yaw_pass(AttitudeModel::GpsIIF, 3.0, 0.0, GPS_RADIUS);
+        let half_arc = (1.0 - (EARTH_RADIUS / GPS_RADIUS).powi(2)).sqrt().acos() / 1.458e-4;
+        let in_shadow: Vec<_> = (0..yaws.len())
+            .filter(|i| ((*i as f64 - 400.0) * 10.0).abs() < half_arc * 0.95)
+            .map(|i| yaws[i])
+            .collect();
+        let rates: Vec<f64> = in_shadow.windows(2).map(|p| p[1].0 - p[0].0).collect();
+        for rate in rates.iter() {
+            assert_float_eq!(*rate, rates[0], abs <= 1e-6);
+        }
+        assert!(rates[0] > 0.0);
+
+        // The Block IIA satellites turn at their maximum rate, and recover
+        // the nominal yaw after leaving the shadow
+        for beta in [-3.0, 3.0].iter() {
+            let yaws = yaw_pass(AttitudeModel::GpsIIA, *beta, 0.0, GPS_RADIUS);
+            assert!(max_rate(&yaws) <= 0.12 * 1.01);
+            let (yaw, nominal) = yaws[yaws.len() - 1];
+            assert_float_eq!(yaw, nominal, abs <= 1e-9);
+            // Turning positively at the maximum rate in the shadow
+            let step = wrap((yaws[401].0 - yaws[400].0).to_radians()).to_degrees();
+            assert_float_eq!(step, 10.0 * 0.12, abs <= 1e-6);
+        }
+    }
+
+    #[test]
+    fn galileo_smoothing() {
+        for (model, max_beta) in [
+            (AttitudeModel::GalileoIov, 2.0),
+            (AttitudeModel::GalileoFoc, 4.1),
+        ]
+        .iter()
+        {
+            for center in [0.0, PI].iter() {
+                let yaws = yaw_pass(*model, 0.5, *center, GAL_RADIUS);
+                assert!(max_rate(&yaws) < 0.25);
+                let yaws = yaw_pass(*model, max_beta + 0.1, *center, GAL_RADIUS);
+                assert!(yaws
+                    .iter()
+                    .all(|(yaw, nominal)| (yaw - nominal).abs() < 1e-9));
+            }
+        }
+    }
+}
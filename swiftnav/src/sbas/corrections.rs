@@ -0,0 +1,478 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Accumulation and application of SBAS corrections
+
+use super::iono::{igp_band_points, IonosphericGrid};
+use super::{signal_to_prn, udre_variance, LongTermCorrection, PrnMask, SbasMessage};
+use crate::coords::ECEF;
+use crate::ephemeris::SatelliteState;
+use crate::navmeas::NavigationMeasurement;
+use crate::time::GpsTime;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+const GPS_L1_HZ: f64 = 1.57542e9;
+const DAY_SECS: f64 = 86_400.0;
+
+/// Reasons the SBAS corrections can't be applied to a measurement
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum SbasCorrectionError {
+    /// The signal's satellite can't be corrected by SBAS
+    UnsupportedSignal,
+    /// The measurement doesn't have a valid pseudorange
+    NoPseudorange,
+    /// No fast correction has been received for the satellite
+    NoFastCorrection,
+    /// The latest fast correction of the satellite is too old
+    FastCorrectionTimedOut,
+    /// The satellite isn't monitored by the SBAS
+    NotMonitored,
+    /// The SBAS has flagged the satellite as unusable
+    DoNotUse,
+    /// The long term corrections are for a different ephemeris
+    IodeMismatch,
+}
+
+impl fmt::Display for SbasCorrectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SbasCorrectionError::UnsupportedSignal => write!(f, "Signal not supported by SBAS"),
+            SbasCorrectionError::NoPseudorange => write!(f, "No valid pseudorange"),
+            SbasCorrectionError::NoFastCorrection => write!(f, "No fast correction"),
+            SbasCorrectionError::FastCorrectionTimedOut => write!(f, "Fast correction timed out"),
+            SbasCorrectionError::NotMonitored => write!(f, "Satellite not monitored"),
+            SbasCorrectionError::DoNotUse => write!(f, "Satellite flagged as do not use"),
+            SbasCorrectionError::IodeMismatch => {
+                write!(f, "Long term corrections are for a different IODE")
+            }
+        }
+    }
+}
+
+impl Error for SbasCorrectionError {}
+
+/// The corrections applied to a measurement
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct AppliedCorrections {
+    /// Fast correction, including the range rate correction, in meters
+    pub fast: f64,
+    /// Whether long term corrections were applied to the satellite state
+    pub long_term: bool,
+    /// Slant ionospheric delay removed from the pseudorange, in meters
+    pub ionosphere: Option<f64>,
+    /// Variance of the user differential range error, in m²
+    pub udre_variance: f64,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct FastState {
+    time: GpsTime,
+    prc: f64,
+    range_rate: f64,
+    udrei: u8,
+}
+
+/// The SBAS corrections decoded so far
+///
+/// Messages are fed in with [`SbasCorrections::update()`] as they are
+/// received, the corrections can then be applied to measurements with
+/// [`SbasCorrections::apply()`]. The fast correction degradation factors are
+/// decoded but not used, the UDRE variance is reported as broadcast.
+#[derive(Debug, Clone, Default)]
+pub struct SbasCorrections {
+    prn_mask: Option<PrnMask>,
+    fast: BTreeMap<u16, FastState>,
+    long_term: BTreeMap<u16, LongTermCorrection>,
+    igp_masks: BTreeMap<u8, (u8, Vec<(i16, i16)>)>,
+    grid: IonosphericGrid,
+    fast_correction_timeout: f64,
+}
+
+impl SbasCorrections {
+    /// Makes an empty set of corrections
+    ///
+    /// Fast corrections time out after 12 seconds, the precision approach
+    /// timeout of DO-229
+    pub fn new() -> SbasCorrections {
+        SbasCorrections {
+            fast_correction_timeout: 12.0,
+            ..Default::default()
+        }
+    }
+
+    /// Sets how long fast corrections are used for, in seconds
+    pub fn set_fast_correction_timeout(self, fast_correction_timeout: f64) -> SbasCorrections {
+        SbasCorrections {
+            fast_correction_timeout,
+            ..self
+        }
+    }
+
+    pub fn fast_correction_timeout(&self) -> f64 {
+        self.fast_correction_timeout
+    }
+
+    pub fn prn_mask(&self) -> Option<&PrnMask> {
+        self.prn_mask.as_ref()
+    }
+
+    pub fn ionospheric_grid(&self) -> &IonosphericGrid {
+        &self.grid
+    }
+
+    /// Gets the satellite corrected by a PRN mask slot
+    fn slot_prn(&self, iodp: u8, slot: usize) -> Option<u16> {
+        let mask = self.prn_mask.as_ref()?;
+        if mask.iodp != iodp {
+            return None;
+        }
+        mask.prns.get(slot).copied()
+    }
+
+    /// Updates the corrections with a message received at time `t`
+    pub fn update(&mut self, t: GpsTime, message: &SbasMessage) {
+        match message {
+            SbasMessage::DoNotUse => {
+                *self = SbasCorrections::new()
+                    .set_fast_correction_timeout(self.fast_correction_timeout);
+            }
+            SbasMessage::PrnMask(mask) => {
+                let changed = match &self.prn_mask {
+                    Some(current) => current != mask,
+                    None => true,
+                };
+                if changed {
+                    self.fast.clear();
+                    self.long_term.clear();
+                    self.prn_mask = Some(mask.clone());
+                }
+            }
+            SbasMessage::FastCorrections(fast) => self.update_fast(t, fast),
+            SbasMessage::IntegrityInformation(integrity) => {
+                let prns: Vec<Option<u16>> = match &self.prn_mask {
+                    Some(mask) => (0..integrity.udreis.len())
+                        .map(|slot| mask.prns.get(slot).copied())
+                        .collect(),
+                    None => return,
+                };
+                for (prn, udrei) in prns.iter().zip(integrity.udreis.iter()) {
+                    if let Some(state) = prn.and_then(|prn| self.fast.get_mut(&prn)) {
+                        state.udrei = *udrei;
+                    }
+                }
+            }
+            SbasMessage::DegradationFactors(_) => {}
+            SbasMessage::IgpMask(mask) => {
+                let points = igp_band_points(mask.band)
+                    .into_iter()
+                    .zip(mask.mask.iter())
+                    .filter(|(_, used)| **used)
+                    .map(|(point, _)| point)
+                    .collect();
+                self.igp_masks.insert(mask.band, (mask.iodi, points));
+            }
+            SbasMessage::MixedCorrections(fast, long_term) => {
+                self.update_fast(t, fast);
+                self.update_long_term(long_term);
+            }
+            SbasMessage::LongTermCorrections(long_term) => self.update_long_term(long_term),
+            SbasMessage::IonosphericDelays(delays) => {
+                let points = match self.igp_masks.get(&delays.band) {
+                    Some((iodi, points)) if *iodi == delays.iodi => points,
+                    _ => return,
+                };
+                let start = 15 * usize::from(delays.block);
+                for (point, delay) in points.iter().skip(start).zip(delays.delays.iter()) {
+                    self.grid.set(point.0, point.1, *delay);
+                }
+            }
+            SbasMessage::Unsupported(_) => {}
+        }
+    }
+
+    fn update_fast(&mut self, t: GpsTime, fast: &super::FastCorrections) {
+        for (i, correction) in fast.corrections.iter().enumerate() {
+            let prn = match self.slot_prn(fast.iodp, fast.first_slot + i) {
+                Some(prn) => prn,
+                None => continue,
+            };
+            // The range rate correction is the change between consecutive
+            // fast corrections
+            let range_rate = match self.fast.get(&prn) {
+                Some(previous) => {
+                    let dt = t.diff(&previous.time);
+                    if dt > 0.0 && dt <= self.fast_correction_timeout {
+                        (correction.prc - previous.prc) / dt
+                    } else {
+                        0.0
+                    }
+                }
+                None => 0.0,
+            };
+            self.fast.insert(
+                prn,
+                FastState {
+                    time: t,
+                    prc: correction.prc,
+                    range_rate,
+                    udrei: correction.udrei,
+                },
+            );
+        }
+    }
+
+    fn update_long_term(&mut self, long_term: &super::LongTermCorrections) {
+        for correction in &long_term.corrections {
+            if correction.slot == 0 {
+                continue;
+            }
+            if let Some(prn) = self.slot_prn(long_term.iodp, usize::from(correction.slot) - 1) {
+                self.long_term.insert(prn, *correction);
+            }
+        }
+    }
+
+    /// Applies the corrections to a measurement taken at time `t`
+    ///
+    /// The fast correction, and the ionospheric delay when the grid covers the
+    /// signal's pierce point, are applied to the pseudorange. Long term
+    /// corrections are applied to the satellite state, which must already
+    /// have been set from the ephemeris with issue of data `iode`.
+    ///
+    /// The measurement is left untouched if an error is returned.
+    pub fn apply(
+        &self,
+        nm: &mut NavigationMeasurement,
+        t: GpsTime,
+        receiver: &ECEF,
+        iode: u8,
+    ) -> Result<AppliedCorrections, SbasCorrectionError> {
+        let prn = signal_to_prn(nm.sid()).ok_or(SbasCorrectionError::UnsupportedSignal)?;
+        let pseudorange = nm.pseudorange().ok_or(SbasCorrectionError::NoPseudorange)?;
+        let fast = self
+            .fast
+            .get(&prn)
+            .ok_or(SbasCorrectionError::NoFastCorrection)?;
+        let age = t.diff(&fast.time);
+        if age > self.fast_correction_timeout {
+            return Err(SbasCorrectionError::FastCorrectionTimedOut);
+        }
+        let udre_variance = match fast.udrei {
+            14 => return Err(SbasCorrectionError::NotMonitored),
+            15 => return Err(SbasCorrectionError::DoNotUse),
+            udrei => udre_variance(udrei).unwrap_or(f64::INFINITY),
+        };
+        let long_term = match self.long_term.get(&prn) {
+            Some(correction) if correction.iode != iode => {
+                return Err(SbasCorrectionError::IodeMismatch)
+            }
+            correction => correction,
+        };
+
+        if let Some(correction) = long_term {
+            let dt = match correction.time_of_day {
+                Some(t0) => {
+                    let tod = t.tow().rem_euclid(DAY_SECS);
+                    (tod - t0 + DAY_SECS / 2.0).rem_euclid(DAY_SECS) - DAY_SECS / 2.0
+                }
+                None => 0.0,
+            };
+            let offset = ECEF::new(
+                correction.position[0] + correction.velocity[0] * dt,
+                correction.position[1] + correction.velocity[1] * dt,
+                correction.position[2] + correction.velocity[2] * dt,
+            );
+            nm.set_satellite_state(&SatelliteState {
+                pos: nm.satellite_position() + offset,
+                vel: nm.satellite_velocity() + ECEF::from_array(&correction.velocity),
+                acc: nm.satellite_acceleration(),
+                clock_err: nm.satellite_clock_error()
+                    + correction.clock_offset
+                    + correction.clock_drift * dt,
+                clock_rate_err: nm.satellite_clock_error_rate() + correction.clock_drift,
+                iodc: 0,
+                iode,
+            });
+        }
+
+        let ionosphere = if self.grid.is_empty() {
+            None
+        } else {
+            let azel = receiver.azel_of(&nm.satellite_position());
+            self.grid
+                .slant_delay(&receiver.to_llh(), &azel)
+                .map(|delay| delay * (GPS_L1_HZ / nm.sid().carrier_frequency()).powi(2))
+        };
+
+        let correction = fast.prc + fast.range_rate * age;
+        nm.set_pseudorange(pseudorange + correction - ionosphere.unwrap_or(0.0));
+
+        Ok(AppliedCorrections {
+            fast: correction,
+            long_term: long_term.is_some(),
+            ionosphere,
+            udre_variance,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::LLHDegrees;
+    use crate::sbas::{
+        FastCorrection, FastCorrections, GridPointDelay, IgpMask, IntegrityInformation,
+        IonosphericDelays, LongTermCorrections,
+    };
+    use crate::signal::{Code, GnssSignal};
+    use float_eq::assert_float_eq;
+    use std::time::Duration;
+
+    fn fast_corrections(prcs: [f64; 2], udrei: u8) -> SbasMessage {
+        SbasMessage::FastCorrections(FastCorrections {
+            iodf: 0,
+            iodp: 1,
+            first_slot: 0,
+            corrections: prcs
+                .iter()
+                .map(|prc| FastCorrection { prc: *prc, udrei })
+                .collect(),
+        })
+    }
+
+    fn measurement() -> NavigationMeasurement {
+        let mut nm = NavigationMeasurement::new();
+        nm.set_sid(GnssSignal::new(3, Code::GpsL1ca).unwrap());
+        nm.set_pseudorange(20_000_000.0);
+        // Satellite straight overhead
+        let up = LLHDegrees::new(37.5, -122.5, 20_000_000.0).to_ecef();
+        nm.set_satellite_state(&SatelliteState {
+            pos: up,
+            vel: ECEF::default(),
+            acc: ECEF::default(),
+            clock_err: 0.0,
+            clock_rate_err: 0.0,
+            iodc: 0,
+            iode: 0,
+        });
+        nm
+    }
+
+    #[test]
+    fn apply_corrections() {
+        let t0 = GpsTime::new(2100, 3600.0).unwrap();
+        let receiver = LLHDegrees::new(37.5, -122.5, 0.0).to_ecef();
+
+        let mut corrections = SbasCorrections::new();
+        corrections.update(
+            t0,
+            &SbasMessage::PrnMask(PrnMask {
+                iodp: 1,
+                prns: vec![3, 10],
+            }),
+        );
+        corrections.update(t0, &fast_corrections([2.0, -1.0], 3));
+        let t1 = t0 + Duration::from_secs(6);
+        corrections.update(t1, &fast_corrections([2.6, -1.0], 3));
+
+        // Long term corrections for the first slot, at 3600 s time of day
+        corrections.update(
+            t1,
+            &SbasMessage::LongTermCorrections(LongTermCorrections {
+                iodp: 1,
+                corrections: vec![LongTermCorrection {
+                    slot: 1,
+                    iode: 77,
+                    position: [1.0, 2.0, 3.0],
+                    velocity: [0.0, 0.0, 0.01],
+                    clock_offset: 1e-9,
+                    clock_drift: 0.0,
+                    time_of_day: Some(3600.0),
+                }],
+            }),
+        );
+
+        // Four grid points around the receiver in band 1
+        let mut mask = vec![false; 201];
+        for (i, point) in igp_band_points(1).iter().enumerate() {
+            if [(35, -125), (40, -125), (35, -120), (40, -120)].contains(point) {
+                mask[i] = true;
+            }
+        }
+        corrections.update(
+            t1,
+            &SbasMessage::IgpMask(IgpMask {
+                bands: 1,
+                band: 1,
+                iodi: 2,
+                mask,
+            }),
+        );
+        corrections.update(
+            t1,
+            &SbasMessage::IonosphericDelays(IonosphericDelays {
+                band: 1,
+                block: 0,
+                iodi: 2,
+                delays: vec![
+                    GridPointDelay {
+                        delay: Some(4.0),
+                        givei: 3,
+                    };
+                    15
+                ],
+            }),
+        );
+
+        let t2 = t1 + Duration::from_secs(2);
+        let mut nm = measurement();
+        assert_eq!(
+            corrections.apply(&mut nm.clone(), t2, &receiver, 12),
+            Err(SbasCorrectionError::IodeMismatch)
+        );
+        let applied = corrections.apply(&mut nm, t2, &receiver, 77).unwrap();
+        assert_float_eq!(applied.fast, 2.6 + 0.1 * 2.0, abs <= 1e-9);
+        assert!(applied.long_term);
+        assert_float_eq!(applied.ionosphere.unwrap(), 4.0, abs <= 1e-6);
+        assert_float_eq!(applied.udre_variance, 0.2830, abs <= 1e-9);
+        assert_float_eq!(
+            nm.pseudorange().unwrap(),
+            20_000_000.0 + 2.8 - 4.0,
+            abs <= 1e-6
+        );
+        assert_float_eq!(nm.satellite_clock_error(), 1e-9, abs <= 1e-15);
+        let moved = nm.satellite_position() - measurement().satellite_position();
+        assert_float_eq!(moved.z(), 3.0 + 0.01 * 8.0, abs <= 1e-6);
+
+        // The integrity message flags the satellite
+        let mut udreis = vec![3; 51];
+        udreis[0] = 15;
+        corrections.update(
+            t2,
+            &SbasMessage::IntegrityInformation(IntegrityInformation {
+                iodf: [0; 4],
+                udreis,
+            }),
+        );
+        assert_eq!(
+            corrections.apply(&mut measurement(), t2, &receiver, 77),
+            Err(SbasCorrectionError::DoNotUse)
+        );
+
+        let t3 = t2 + Duration::from_secs(30);
+        let mut nm = measurement();
+        nm.set_sid(GnssSignal::new(10, Code::GpsL1ca).unwrap());
+        assert_eq!(
+            corrections.apply(&mut nm, t3, &receiver, 77),
+            Err(SbasCorrectionError::FastCorrectionTimedOut)
+        );
+    }
+}
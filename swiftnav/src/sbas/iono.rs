@@ -0,0 +1,220 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! SBAS ionospheric grid
+
+use super::GridPointDelay;
+use crate::coords::{AzimuthElevation, LLHRadians};
+use std::collections::BTreeMap;
+
+/// Mean earth radius used for the ionospheric pierce point, in meters
+const IONO_EARTH_RADIUS: f64 = 6_378_136.3;
+/// Height of the ionospheric shell, in meters
+const IONO_SHELL_HEIGHT: f64 = 350_000.0;
+
+const LATS_1: [i16; 28] = [
+    -75, -65, -55, -50, -45, -40, -35, -30, -25, -20, -15, -10, -5, 0, 5, 10, 15, 20, 25, 30, 35,
+    40, 45, 50, 55, 65, 75, 85,
+];
+const LATS_2: [i16; 23] = [
+    -55, -50, -45, -40, -35, -30, -25, -20, -15, -10, -5, 0, 5, 10, 15, 20, 25, 30, 35, 40, 45, 50,
+    55,
+];
+const LATS_3: [i16; 27] = [
+    -75, -65, -55, -50, -45, -40, -35, -30, -25, -20, -15, -10, -5, 0, 5, 10, 15, 20, 25, 30, 35,
+    40, 45, 50, 55, 65, 75,
+];
+const LATS_4: [i16; 28] = [
+    -85, -75, -65, -55, -50, -45, -40, -35, -30, -25, -20, -15, -10, -5, 0, 5, 10, 15, 20, 25, 30,
+    35, 40, 45, 50, 55, 65, 75,
+];
+
+/// Gets the latitudes of the grid points along a meridian of bands 0 to 8
+///
+/// Every other meridian only spans ±55°, the others extend to ±75°. Every
+/// 90° from 180°W there is an extra point at 85°N, and every 90° from 140°W
+/// there is one at 85°S.
+fn meridian_latitudes(lon: i16) -> &'static [i16] {
+    if lon.rem_euclid(10) != 0 {
+        &LATS_2
+    } else if (lon + 180).rem_euclid(90) == 0 {
+        &LATS_1
+    } else if (lon + 140).rem_euclid(90) == 0 {
+        &LATS_4
+    } else {
+        &LATS_3
+    }
+}
+
+/// Gets the latitude and longitude, in degrees, of the grid points of an IGP
+/// band, in the order of the band's mask
+///
+/// Bands 0 to 8 are 40° wide strips of longitude starting at 180°W, bands 9
+/// and 10 cover the northern and southern polar caps. Returns an empty list
+/// for any other band.
+pub fn igp_band_points(band: u8) -> Vec<(i16, i16)> {
+    match band {
+        0..=8 => {
+            let start = -180 + 40 * i16::from(band);
+            (0..8)
+                .flat_map(|column| {
+                    let lon = start + 5 * column;
+                    meridian_latitudes(lon).iter().map(move |lat| (*lat, lon))
+                })
+                .collect()
+        }
+        9 | 10 => {
+            let sign = if band == 9 { 1 } else { -1 };
+            let mut points: Vec<(i16, i16)> = (0..72).map(|i| (sign * 60, -180 + 5 * i)).collect();
+            for lat in [65, 70, 75].iter() {
+                points.extend((0..36).map(|i| (sign * lat, -180 + 10 * i)));
+            }
+            let offset = if band == 9 { -180 } else { -170 };
+            points.extend((0..12).map(|i| (sign * 85, offset + 30 * i)));
+            points
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// The vertical ionospheric delays at the grid points received so far
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IonosphericGrid {
+    points: BTreeMap<(i16, i16), GridPointDelay>,
+}
+
+impl IonosphericGrid {
+    pub fn new() -> IonosphericGrid {
+        IonosphericGrid::default()
+    }
+
+    /// Sets the delay at a grid point, given in degrees
+    pub fn set(&mut self, lat: i16, lon: i16, delay: GridPointDelay) {
+        self.points.insert((lat, lon), delay);
+    }
+
+    /// Gets the delay at a grid point, given in degrees
+    pub fn get(&self, lat: i16, lon: i16) -> Option<&GridPointDelay> {
+        self.points.get(&(lat, lon))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    /// Interpolates the vertical delay on L1, in meters, at a point given in
+    /// degrees
+    ///
+    /// The four grid points around the point on the 5° grid are used, falling
+    /// back on the 10° grid when they aren't all available. Returns `None` if
+    /// neither grid has four usable points.
+    pub fn vertical_delay(&self, lat: f64, lon: f64) -> Option<f64> {
+        [5, 10]
+            .iter()
+            .find_map(|spacing| self.interpolate(lat, lon, *spacing))
+    }
+
+    /// Computes the slant delay on L1, in meters, of a signal received at
+    /// `receiver` from the direction `azel`
+    pub fn slant_delay(&self, receiver: &LLHRadians, azel: &AzimuthElevation) -> Option<f64> {
+        let ratio = IONO_EARTH_RADIUS / (IONO_EARTH_RADIUS + IONO_SHELL_HEIGHT) * azel.el.cos();
+        let psi = std::f64::consts::FRAC_PI_2 - azel.el - ratio.asin();
+        let lat_u = receiver.latitude();
+        let lat_pp = (lat_u.sin() * psi.cos() + lat_u.cos() * psi.sin() * azel.az.cos()).asin();
+        let lon_pp = receiver.longitude() + (psi.sin() * azel.az.sin() / lat_pp.cos()).asin();
+
+        let obliquity = 1.0 / (1.0 - ratio * ratio).sqrt();
+        let vertical = self.vertical_delay(lat_pp.to_degrees(), lon_pp.to_degrees())?;
+        Some(obliquity * vertical)
+    }
+
+    fn interpolate(&self, lat: f64, lon: f64, spacing: i16) -> Option<f64> {
+        let step = f64::from(spacing);
+        let lon = (lon + 180.0).rem_euclid(360.0) - 180.0;
+        let lat0 = (lat / step).floor() * step;
+        let lon0 = (lon / step).floor() * step;
+        let wrap = |lon: f64| ((lon + 180.0).rem_euclid(360.0) - 180.0) as i16;
+
+        let corner = |dlat: f64, dlon: f64| {
+            self.get((lat0 + dlat) as i16, wrap(lon0 + dlon))
+                .and_then(|point| point.delay)
+        };
+        let d00 = corner(0.0, 0.0)?;
+        let d01 = corner(0.0, step)?;
+        let d10 = corner(step, 0.0)?;
+        let d11 = corner(step, step)?;
+
+        let x = (lon - lon0) / step;
+        let y = (lat - lat0) / step;
+        Some((1.0 - x) * (1.0 - y) * d00 + x * (1.0 - y) * d01 + (1.0 - x) * y * d10 + x * y * d11)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn band_layout() {
+        // Band sizes from DO-229 Table A-15
+        let sizes: Vec<usize> = (0..=10).map(|band| igp_band_points(band).len()).collect();
+        assert_eq!(
+            sizes,
+            vec![201, 201, 201, 201, 201, 201, 201, 201, 200, 192, 192]
+        );
+        let band0 = igp_band_points(0);
+        assert_eq!(band0[0], (-75, -180));
+        assert_eq!(band0[27], (85, -180));
+        assert_eq!(band0[28], (-55, -175));
+        assert_eq!(igp_band_points(1)[0], (-85, -140));
+        assert_eq!(igp_band_points(10)[191], (-85, 160));
+        assert!(igp_band_points(11).is_empty());
+    }
+
+    #[test]
+    fn interpolation() {
+        let mut grid = IonosphericGrid::new();
+        let point = |delay| GridPointDelay {
+            delay: Some(delay),
+            givei: 5,
+        };
+        grid.set(35, -125, point(2.0));
+        grid.set(35, -120, point(4.0));
+        grid.set(40, -125, point(6.0));
+        grid.set(40, -120, point(8.0));
+
+        assert_float_eq!(
+            grid.vertical_delay(37.5, -122.5).unwrap(),
+            5.0,
+            abs <= 1e-12
+        );
+        assert_float_eq!(
+            grid.vertical_delay(35.0, -125.0).unwrap(),
+            2.0,
+            abs <= 1e-12
+        );
+        assert_eq!(grid.vertical_delay(42.0, -122.5), None);
+
+        // Straight up the slant delay is the vertical delay
+        let receiver = LLHRadians::new(37.5f64.to_radians(), -122.5f64.to_radians(), 0.0);
+        let zenith = AzimuthElevation::new(0.0, std::f64::consts::FRAC_PI_2);
+        assert_float_eq!(
+            grid.slant_delay(&receiver, &zenith).unwrap(),
+            5.0,
+            abs <= 1e-9
+        );
+        let low = AzimuthElevation::new(0.0, 20f64.to_radians());
+        assert!(grid.slant_delay(&receiver, &low).is_none());
+    }
+}
@@ -0,0 +1,591 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! SBAS message decoding
+//!
+//! Satellite based augmentation systems (WAAS, EGNOS, MSAS, GAGAN, ...)
+//! broadcast corrections for the GPS and GLONASS satellites on L1. Each 250 bit
+//! message is made of an 8 bit preamble, a 6 bit message type, 212 bits of
+//! data and a 24 bit CRC. The messages needed for a DGNSS style correction of
+//! the pseudoranges are decoded:
+//!  * Type 1 - PRN mask, assigning the satellites to correction slots
+//!  * Types 2 to 5 - Fast corrections
+//!  * Type 6 - Integrity information
+//!  * Type 7 - Fast correction degradation factors
+//!  * Type 18 - Ionospheric grid point mask
+//!  * Type 24 - Mixed fast and long term corrections
+//!  * Type 25 - Long term satellite error corrections
+//!  * Type 26 - Ionospheric delays
+//!
+//! Decoded messages are accumulated in [`SbasCorrections`], which can then be
+//! applied to [`NavigationMeasurement`](crate::navmeas::NavigationMeasurement)s.
+//!
+//! # References
+//!   * RTCA DO-229E, Appendix A
+
+mod corrections;
+mod iono;
+
+pub use corrections::{AppliedCorrections, SbasCorrectionError, SbasCorrections};
+pub use iono::{igp_band_points, IonosphericGrid};
+
+use crate::edc::compute_crc24q;
+use crate::signal::{Code, GnssSignal};
+use std::error::Error;
+use std::fmt;
+
+/// Number of bytes needed to hold a 250 bit SBAS message
+pub const SBAS_MESSAGE_BYTES: usize = 32;
+
+const PREAMBLES: [u8; 3] = [0x53, 0x9A, 0xC6];
+
+/// Errors which can occur while decoding an SBAS message
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum SbasDecodeError {
+    /// The preamble isn't one of the three SBAS preambles
+    InvalidPreamble(u8),
+    /// The CRC of the message doesn't match its contents
+    CrcMismatch,
+}
+
+impl fmt::Display for SbasDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SbasDecodeError::InvalidPreamble(preamble) => {
+                write!(f, "Invalid SBAS preamble ({:#04x})", preamble)
+            }
+            SbasDecodeError::CrcMismatch => write!(f, "SBAS message CRC mismatch"),
+        }
+    }
+}
+
+impl Error for SbasDecodeError {}
+
+/// Gets the satellite signal of an SBAS PRN number
+///
+/// SBAS numbers GPS satellites 1 to 37, GLONASS slots as 38 to 61 and the SBAS
+/// satellites themselves as 120 to 158. Returns `None` for any other number or
+/// for satellites this library doesn't support.
+pub fn prn_to_signal(prn: u16) -> Option<GnssSignal> {
+    match prn {
+        1..=37 => GnssSignal::new(prn, Code::GpsL1ca).ok(),
+        38..=61 => GnssSignal::new(prn - 37, Code::GloL1of).ok(),
+        120..=158 => GnssSignal::new(prn, Code::SbasL1ca).ok(),
+        _ => None,
+    }
+}
+
+/// Gets the SBAS PRN number of a satellite, see [`prn_to_signal()`]
+pub fn signal_to_prn(sid: GnssSignal) -> Option<u16> {
+    let sat = sid.sat();
+    if sid.code().is_gps() {
+        Some(sat)
+    } else if sid.code().is_glo() {
+        Some(sat + 37)
+    } else if sid.code().is_sbas() {
+        Some(sat)
+    } else {
+        None
+    }
+}
+
+/// Gets the variance of the user differential range error, in m², of a UDRE
+/// indicator
+///
+/// Returns `None` for the "not monitored" and "do not use" indicators
+pub fn udre_variance(udrei: u8) -> Option<f64> {
+    const VARIANCES: [f64; 14] = [
+        0.0520, 0.0924, 0.1444, 0.2830, 0.4678, 0.8315, 1.2992, 1.8709, 2.5465, 3.3260, 5.1968,
+        20.7870, 230.9661, 2078.695,
+    ];
+    VARIANCES.get(usize::from(udrei)).copied()
+}
+
+/// Gets the variance of the grid ionospheric vertical error, in m², of a GIVE
+/// indicator
+///
+/// Returns `None` for the "not monitored" indicator
+pub fn give_variance(givei: u8) -> Option<f64> {
+    const VARIANCES: [f64; 15] = [
+        0.0084, 0.0333, 0.0749, 0.1331, 0.2079, 0.2994, 0.4075, 0.5322, 0.6735, 0.8315, 1.1974,
+        1.8709, 3.3260, 20.7870, 187.0826,
+    ];
+    VARIANCES.get(usize::from(givei)).copied()
+}
+
+/// The satellites assigned to the correction slots, message type 1
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PrnMask {
+    /// Issue of data of the PRN mask
+    pub iodp: u8,
+    /// SBAS PRN numbers, in slot order
+    pub prns: Vec<u16>,
+}
+
+/// A single fast correction
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct FastCorrection {
+    /// Pseudorange correction, in meters
+    pub prc: f64,
+    /// User differential range error indicator
+    pub udrei: u8,
+}
+
+/// A block of fast corrections, message types 2 to 5 and the first half of 24
+#[derive(Debug, Clone, PartialEq)]
+pub struct FastCorrections {
+    /// Issue of data of the fast corrections
+    pub iodf: u8,
+    /// Issue of data of the PRN mask the corrections refer to
+    pub iodp: u8,
+    /// Index in the PRN mask of the first correction
+    pub first_slot: usize,
+    pub corrections: Vec<FastCorrection>,
+}
+
+/// UDRE indicators of all slots, message type 6
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IntegrityInformation {
+    /// Issue of data of the fast corrections in message types 2 to 5
+    pub iodf: [u8; 4],
+    /// UDRE indicators, in slot order
+    pub udreis: Vec<u8>,
+}
+
+/// Fast correction degradation factors, message type 7
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DegradationFactors {
+    /// System latency, in seconds
+    pub system_latency: u8,
+    pub iodp: u8,
+    /// Degradation factor indicators, in slot order
+    pub ai: Vec<u8>,
+}
+
+/// Corrections to the broadcast orbit and clock of one satellite
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct LongTermCorrection {
+    /// One based index in the PRN mask, zero if the entry is unused
+    pub slot: u8,
+    /// Issue of data of the ephemeris being corrected
+    pub iode: u8,
+    /// ECEF position correction, in meters
+    pub position: [f64; 3],
+    /// ECEF velocity correction, in m/s
+    pub velocity: [f64; 3],
+    /// Clock offset correction, in seconds
+    pub clock_offset: f64,
+    /// Clock drift correction, in s/s
+    pub clock_drift: f64,
+    /// Time of day the corrections apply at, in seconds. Only present with
+    /// velocity corrections.
+    pub time_of_day: Option<f64>,
+}
+
+/// Long term corrections, message type 25 and the second half of 24
+#[derive(Debug, Clone, PartialEq)]
+pub struct LongTermCorrections {
+    pub iodp: u8,
+    pub corrections: Vec<LongTermCorrection>,
+}
+
+/// Ionospheric grid points which have delays broadcast for one band,
+/// message type 18
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IgpMask {
+    /// Number of bands being broadcast
+    pub bands: u8,
+    pub band: u8,
+    /// Issue of data of the ionosphere mask
+    pub iodi: u8,
+    /// One entry per grid point of the band, see [`igp_band_points()`]
+    pub mask: Vec<bool>,
+}
+
+/// The vertical delay at a single ionospheric grid point
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct GridPointDelay {
+    /// Vertical delay on L1, in meters, `None` if it shouldn't be used
+    pub delay: Option<f64>,
+    /// Grid ionospheric vertical error indicator
+    pub givei: u8,
+}
+
+/// Ionospheric delays of a block of grid points, message type 26
+#[derive(Debug, Clone, PartialEq)]
+pub struct IonosphericDelays {
+    pub band: u8,
+    /// Block of 15 grid points, in the order of the band's mask
+    pub block: u8,
+    pub iodi: u8,
+    pub delays: Vec<GridPointDelay>,
+}
+
+/// A decoded SBAS message
+#[derive(Debug, Clone, PartialEq)]
+pub enum SbasMessage {
+    /// Type 0, the system is being tested and shouldn't be used
+    DoNotUse,
+    PrnMask(PrnMask),
+    FastCorrections(FastCorrections),
+    IntegrityInformation(IntegrityInformation),
+    DegradationFactors(DegradationFactors),
+    IgpMask(IgpMask),
+    MixedCorrections(FastCorrections, LongTermCorrections),
+    LongTermCorrections(LongTermCorrections),
+    IonosphericDelays(IonosphericDelays),
+    /// A message type which isn't decoded
+    Unsupported(u8),
+}
+
+impl SbasMessage {
+    /// Decodes a 250 bit SBAS message
+    ///
+    /// The bits are packed most significant bit first starting with the
+    /// preamble, the last 6 bits of `data` are ignored.
+    pub fn decode(data: &[u8; SBAS_MESSAGE_BYTES]) -> Result<SbasMessage, SbasDecodeError> {
+        let bits = Bits(data);
+
+        let preamble = bits.u(0, 8) as u8;
+        if !PREAMBLES.contains(&preamble) {
+            return Err(SbasDecodeError::InvalidPreamble(preamble));
+        }
+
+        // The CRC covers the first 226 bits, shifting them down by 6 bits
+        // aligns the end of the covered data with a byte boundary
+        let mut aligned = [0u8; 29];
+        for (i, byte) in aligned.iter_mut().enumerate() {
+            let start = 8 * i as isize - 6;
+            *byte = (0..8).fold(0, |acc, k| {
+                let bit = start + k;
+                let value = if bit < 0 { 0 } else { bits.u(bit as usize, 1) };
+                (acc << 1) | value as u8
+            });
+        }
+        if compute_crc24q(&aligned, 0) != bits.u(226, 24) {
+            return Err(SbasDecodeError::CrcMismatch);
+        }
+
+        Ok(match bits.u(8, 6) as u8 {
+            0 => SbasMessage::DoNotUse,
+            1 => SbasMessage::PrnMask(PrnMask {
+                iodp: bits.u(224, 2) as u8,
+                prns: (0..210)
+                    .filter(|i| bits.u(14 + i, 1) == 1)
+                    .map(|i| i as u16 + 1)
+                    .collect(),
+            }),
+            message_type @ 2..=5 => SbasMessage::FastCorrections(FastCorrections {
+                iodf: bits.u(14, 2) as u8,
+                iodp: bits.u(16, 2) as u8,
+                first_slot: 13 * (usize::from(message_type) - 2),
+                corrections: (0..13)
+                    .map(|i| FastCorrection {
+                        prc: 0.125 * f64::from(bits.s(18 + 12 * i, 12)),
+                        udrei: bits.u(174 + 4 * i, 4) as u8,
+                    })
+                    .collect(),
+            }),
+            6 => SbasMessage::IntegrityInformation(IntegrityInformation {
+                iodf: [
+                    bits.u(14, 2) as u8,
+                    bits.u(16, 2) as u8,
+                    bits.u(18, 2) as u8,
+                    bits.u(20, 2) as u8,
+                ],
+                udreis: (0..51).map(|i| bits.u(22 + 4 * i, 4) as u8).collect(),
+            }),
+            7 => SbasMessage::DegradationFactors(DegradationFactors {
+                system_latency: bits.u(14, 4) as u8,
+                iodp: bits.u(18, 2) as u8,
+                ai: (0..51).map(|i| bits.u(22 + 4 * i, 4) as u8).collect(),
+            }),
+            18 => SbasMessage::IgpMask(IgpMask {
+                bands: bits.u(14, 4) as u8,
+                band: bits.u(18, 4) as u8,
+                iodi: bits.u(22, 2) as u8,
+                mask: (0..201).map(|i| bits.u(24 + i, 1) == 1).collect(),
+            }),
+            24 => SbasMessage::MixedCorrections(
+                FastCorrections {
+                    iodf: bits.u(114, 2) as u8,
+                    iodp: bits.u(110, 2) as u8,
+                    first_slot: 13 * bits.u(112, 2) as usize,
+                    corrections: (0..6)
+                        .map(|i| FastCorrection {
+                            prc: 0.125 * f64::from(bits.s(14 + 12 * i, 12)),
+                            udrei: bits.u(86 + 4 * i, 4) as u8,
+                        })
+                        .collect(),
+                },
+                decode_long_term_half(&bits, 120),
+            ),
+            25 => {
+                let mut first = decode_long_term_half(&bits, 14);
+                let second = decode_long_term_half(&bits, 120);
+                first.corrections.extend(second.corrections);
+                SbasMessage::LongTermCorrections(first)
+            }
+            26 => SbasMessage::IonosphericDelays(IonosphericDelays {
+                band: bits.u(14, 4) as u8,
+                block: bits.u(18, 4) as u8,
+                iodi: bits.u(217, 2) as u8,
+                delays: (0..15)
+                    .map(|i| {
+                        let delay = bits.u(22 + 13 * i, 9);
+                        GridPointDelay {
+                            delay: if delay == 0x1FF {
+                                None
+                            } else {
+                                Some(0.125 * f64::from(delay))
+                            },
+                            givei: bits.u(31 + 13 * i, 4) as u8,
+                        }
+                    })
+                    .collect(),
+            }),
+            message_type => SbasMessage::Unsupported(message_type),
+        })
+    }
+}
+
+/// Decodes one 106 bit half of a long term correction message
+fn decode_long_term_half(bits: &Bits, start: usize) -> LongTermCorrections {
+    let velocity_code = bits.u(start, 1);
+    let p = start + 1;
+    if velocity_code == 0 {
+        let decode = |p: usize| LongTermCorrection {
+            slot: bits.u(p, 6) as u8,
+            iode: bits.u(p + 6, 8) as u8,
+            position: [
+                0.125 * f64::from(bits.s(p + 14, 9)),
+                0.125 * f64::from(bits.s(p + 23, 9)),
+                0.125 * f64::from(bits.s(p + 32, 9)),
+            ],
+            velocity: [0.0; 3],
+            clock_offset: f64::from(bits.s(p + 41, 10)) * 2f64.powi(-31),
+            clock_drift: 0.0,
+            time_of_day: None,
+        };
+        LongTermCorrections {
+            iodp: bits.u(p + 102, 2) as u8,
+            corrections: vec![decode(p), decode(p + 51)],
+        }
+    } else {
+        LongTermCorrections {
+            iodp: bits.u(p + 103, 2) as u8,
+            corrections: vec![LongTermCorrection {
+                slot: bits.u(p, 6) as u8,
+                iode: bits.u(p + 6, 8) as u8,
+                position: [
+                    0.125 * f64::from(bits.s(p + 14, 11)),
+                    0.125 * f64::from(bits.s(p + 25, 11)),
+                    0.125 * f64::from(bits.s(p + 36, 11)),
+                ],
+                velocity: [
+                    f64::from(bits.s(p + 58, 8)) * 2f64.powi(-11),
+                    f64::from(bits.s(p + 66, 8)) * 2f64.powi(-11),
+                    f64::from(bits.s(p + 74, 8)) * 2f64.powi(-11),
+                ],
+                clock_offset: f64::from(bits.s(p + 47, 11)) * 2f64.powi(-31),
+                clock_drift: f64::from(bits.s(p + 82, 8)) * 2f64.powi(-39),
+                time_of_day: Some(16.0 * f64::from(bits.u(p + 90, 13))),
+            }],
+        }
+    }
+}
+
+/// Big endian bit field access
+struct Bits<'a>(&'a [u8]);
+
+impl Bits<'_> {
+    /// Gets an unsigned field of up to 32 bits
+    fn u(&self, start: usize, len: usize) -> u32 {
+        (start..start + len).fold(0, |acc, bit| {
+            (acc << 1) | u32::from((self.0[bit / 8] >> (7 - bit % 8)) & 1)
+        })
+    }
+
+    /// Gets a two's complement signed field of up to 32 bits
+    fn s(&self, start: usize, len: usize) -> i32 {
+        let value = self.u(start, len);
+        if len < 32 && value & (1 << (len - 1)) != 0 {
+            (value as i64 - (1i64 << len)) as i32
+        } else {
+            value as i32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+
+    /// Packs fields into a message and appends the CRC
+    struct MessageBuilder {
+        bits: Vec<bool>,
+    }
+
+    impl MessageBuilder {
+        fn new(message_type: u8) -> MessageBuilder {
+            let builder = MessageBuilder { bits: Vec::new() };
+            builder.u(0x53, 8).u(u32::from(message_type), 6)
+        }
+
+        fn u(mut self, value: u32, len: usize) -> MessageBuilder {
+            for k in (0..len).rev() {
+                self.bits.push((value >> k) & 1 == 1);
+            }
+            self
+        }
+
+        fn s(self, value: i32, len: usize) -> MessageBuilder {
+            let mask = if len == 32 { u32::MAX } else { (1 << len) - 1 };
+            self.u(value as u32 & mask, len)
+        }
+
+        fn build(mut self) -> [u8; SBAS_MESSAGE_BYTES] {
+            assert!(self.bits.len() <= 226);
+            self.bits.resize(226, false);
+            let mut data = [0u8; SBAS_MESSAGE_BYTES];
+            let mut aligned = [0u8; 29];
+            for (i, bit) in self.bits.iter().enumerate() {
+                if *bit {
+                    data[i / 8] |= 0x80 >> (i % 8);
+                    aligned[(i + 6) / 8] |= 0x80 >> ((i + 6) % 8);
+                }
+            }
+            let crc = compute_crc24q(&aligned, 0);
+            for k in 0..24 {
+                if (crc >> (23 - k)) & 1 == 1 {
+                    let i = 226 + k;
+                    data[i / 8] |= 0x80 >> (i % 8);
+                }
+            }
+            data
+        }
+    }
+
+    #[test]
+    fn decode_prn_mask_and_fast_corrections() {
+        let mut mask = MessageBuilder::new(1);
+        for prn in 1..=210 {
+            mask = mask.u(u32::from(prn == 3 || prn == 10 || prn == 40), 1);
+        }
+        let data = mask.u(2, 2).build();
+        assert_eq!(
+            SbasMessage::decode(&data),
+            Ok(SbasMessage::PrnMask(PrnMask {
+                iodp: 2,
+                prns: vec![3, 10, 40]
+            }))
+        );
+
+        let mut fast = MessageBuilder::new(3).u(1, 2).u(2, 2);
+        for i in 0..13 {
+            fast = fast.s(if i == 0 { -20 } else { i }, 12);
+        }
+        for i in 0..13 {
+            fast = fast.u(if i == 0 { 15 } else { 5 }, 4);
+        }
+        match SbasMessage::decode(&fast.build()).unwrap() {
+            SbasMessage::FastCorrections(fast) => {
+                assert_eq!((fast.iodf, fast.iodp, fast.first_slot), (1, 2, 13));
+                assert_float_eq!(fast.corrections[0].prc, -2.5, abs <= 1e-12);
+                assert_eq!(fast.corrections[0].udrei, 15);
+                assert_float_eq!(fast.corrections[12].prc, 1.5, abs <= 1e-12);
+                assert_eq!(fast.corrections[12].udrei, 5);
+            }
+            message => panic!("Unexpected message {:?}", message),
+        }
+    }
+
+    #[test]
+    fn decode_long_term_corrections() {
+        // One half with velocity code 1, one half with velocity code 0
+        let data = MessageBuilder::new(25)
+            .u(1, 1)
+            .u(2, 6)
+            .u(77, 8)
+            .s(-8, 11)
+            .s(16, 11)
+            .s(0, 11)
+            .s(-2, 11)
+            .s(4, 8)
+            .s(0, 8)
+            .s(-4, 8)
+            .s(1, 8)
+            .u(100, 13)
+            .u(2, 2)
+            .u(0, 1)
+            .u(1, 6)
+            .u(12, 8)
+            .s(8, 9)
+            .s(0, 9)
+            .s(-1, 9)
+            .s(3, 10)
+            .u(0, 6)
+            .u(0, 8)
+            .u(0, 27)
+            .u(0, 10)
+            .u(2, 2)
+            .build();
+
+        match SbasMessage::decode(&data).unwrap() {
+            SbasMessage::LongTermCorrections(long_term) => {
+                assert_eq!(long_term.iodp, 2);
+                assert_eq!(long_term.corrections.len(), 3);
+                let first = long_term.corrections[0];
+                assert_eq!((first.slot, first.iode), (2, 77));
+                assert_float_eq!(first.position[0], -1.0, abs <= 1e-12);
+                assert_float_eq!(first.position[1], 2.0, abs <= 1e-12);
+                assert_float_eq!(first.velocity[2], -4.0 / 2048.0, abs <= 1e-12);
+                assert_float_eq!(first.clock_offset, -2.0 * 2f64.powi(-31), abs <= 1e-20);
+                assert_eq!(first.time_of_day, Some(1600.0));
+                let second = long_term.corrections[1];
+                assert_eq!((second.slot, second.iode), (1, 12));
+                assert_float_eq!(second.position[2], -0.125, abs <= 1e-12);
+                assert_eq!(long_term.corrections[2].slot, 0);
+            }
+            message => panic!("Unexpected message {:?}", message),
+        }
+    }
+
+    #[test]
+    fn decode_errors() {
+        let mut data = MessageBuilder::new(0).build();
+        assert_eq!(SbasMessage::decode(&data), Ok(SbasMessage::DoNotUse));
+        data[10] ^= 0x10;
+        assert_eq!(
+            SbasMessage::decode(&data),
+            Err(SbasDecodeError::CrcMismatch)
+        );
+        data[0] = 0x12;
+        assert_eq!(
+            SbasMessage::decode(&data),
+            Err(SbasDecodeError::InvalidPreamble(0x12))
+        );
+    }
+
+    #[test]
+    fn prn_numbering() {
+        assert_eq!(
+            prn_to_signal(40),
+            Some(GnssSignal::new(3, Code::GloL1of).unwrap())
+        );
+        assert_eq!(prn_to_signal(100), None);
+        assert_eq!(
+            signal_to_prn(GnssSignal::new(131, Code::SbasL1ca).unwrap()),
+            Some(131)
+        );
+        assert_eq!(udre_variance(15), None);
+        assert_eq!(give_variance(0), Some(0.0084));
+    }
+}
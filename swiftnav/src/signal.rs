@@ -13,6 +13,7 @@
 //! identified by it's assigned number and the constellation it belongs to. Each
 //! satellite can send out multiple signals.
 
+use crate::ephemeris::Ephemeris;
 use std::borrow::Cow;
 use std::error::Error;
 use std::ffi;
@@ -21,6 +22,7 @@ use std::str::FromStr;
 
 /// GNSS satellite constellations
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Constellation {
     /// GPS
     Gps,
@@ -88,6 +90,40 @@ impl Constellation {
         };
         c_str.to_string_lossy()
     }
+
+    /// Gets the letter identifying the constellation in RINEX 3 files
+    pub fn to_rinex_char(&self) -> char {
+        match self {
+            Constellation::Gps => 'G',
+            Constellation::Sbas => 'S',
+            Constellation::Glo => 'R',
+            Constellation::Bds => 'C',
+            Constellation::Qzs => 'J',
+            Constellation::Gal => 'E',
+        }
+    }
+
+    /// Gets the constellation identified by a RINEX 3 letter
+    pub fn from_rinex_char(letter: char) -> Option<Constellation> {
+        match letter {
+            'G' => Some(Constellation::Gps),
+            'S' => Some(Constellation::Sbas),
+            'R' => Some(Constellation::Glo),
+            'C' => Some(Constellation::Bds),
+            'J' => Some(Constellation::Qzs),
+            'E' => Some(Constellation::Gal),
+            _ => None,
+        }
+    }
+
+    /// Gets the offset from the satellite number in RINEX files to the PRN
+    fn rinex_sat_offset(&self) -> u16 {
+        match self {
+            Constellation::Sbas => 100,
+            Constellation::Qzs => 192,
+            _ => 0,
+        }
+    }
 }
 
 impl FromStr for Constellation {
@@ -115,6 +151,7 @@ impl std::convert::TryFrom<u8> for Constellation {
 
 /// Code identifiers
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Code {
     /// GPS L1CA: BPSK(1)
     GpsL1ca,
@@ -226,6 +263,79 @@ impl fmt::Display for InvalidCode {
 
 impl Error for InvalidCode {}
 
+/// RINEX 3 band and attribute of the codes
+///
+/// When a code has more than one entry, the first is used when writing RINEX
+/// and the others are only accepted when reading. The GPS P(Y) codes are
+/// written as tracked with Z-tracking, as most receivers do.
+const RINEX_CODES: &[(Constellation, &str, Code)] = &[
+    (Constellation::Gps, "1C", Code::GpsL1ca),
+    (Constellation::Gps, "1S", Code::GpsL1ci),
+    (Constellation::Gps, "1L", Code::GpsL1cq),
+    (Constellation::Gps, "1X", Code::GpsL1cx),
+    (Constellation::Gps, "1W", Code::GpsL1p),
+    (Constellation::Gps, "1P", Code::GpsL1p),
+    (Constellation::Gps, "1Y", Code::GpsL1p),
+    (Constellation::Gps, "2S", Code::GpsL2cm),
+    (Constellation::Gps, "2L", Code::GpsL2cl),
+    (Constellation::Gps, "2X", Code::GpsL2cx),
+    (Constellation::Gps, "2W", Code::GpsL2p),
+    (Constellation::Gps, "2P", Code::GpsL2p),
+    (Constellation::Gps, "2Y", Code::GpsL2p),
+    (Constellation::Gps, "2D", Code::GpsL2p),
+    (Constellation::Gps, "5I", Code::GpsL5i),
+    (Constellation::Gps, "5Q", Code::GpsL5q),
+    (Constellation::Gps, "5X", Code::GpsL5x),
+    (Constellation::Sbas, "1C", Code::SbasL1ca),
+    (Constellation::Sbas, "5I", Code::SbasL5i),
+    (Constellation::Sbas, "5Q", Code::SbasL5q),
+    (Constellation::Sbas, "5X", Code::SbasL5x),
+    (Constellation::Glo, "1C", Code::GloL1of),
+    (Constellation::Glo, "1P", Code::GloL1p),
+    (Constellation::Glo, "2C", Code::GloL2of),
+    (Constellation::Glo, "2P", Code::GloL2p),
+    (Constellation::Gal, "1B", Code::GalE1b),
+    (Constellation::Gal, "1C", Code::GalE1c),
+    (Constellation::Gal, "1X", Code::GalE1x),
+    (Constellation::Gal, "6B", Code::GalE6b),
+    (Constellation::Gal, "6C", Code::GalE6c),
+    (Constellation::Gal, "6X", Code::GalE6x),
+    (Constellation::Gal, "7I", Code::GalE7i),
+    (Constellation::Gal, "7Q", Code::GalE7q),
+    (Constellation::Gal, "7X", Code::GalE7x),
+    (Constellation::Gal, "8I", Code::GalE8i),
+    (Constellation::Gal, "8Q", Code::GalE8q),
+    (Constellation::Gal, "8X", Code::GalE8x),
+    (Constellation::Gal, "5I", Code::GalE5i),
+    (Constellation::Gal, "5Q", Code::GalE5q),
+    (Constellation::Gal, "5X", Code::GalE5x),
+    (Constellation::Qzs, "1C", Code::QzsL1ca),
+    (Constellation::Qzs, "1S", Code::QzsL1ci),
+    (Constellation::Qzs, "1L", Code::QzsL1cq),
+    (Constellation::Qzs, "1X", Code::QzsL1cx),
+    (Constellation::Qzs, "2S", Code::QzsL2cm),
+    (Constellation::Qzs, "2L", Code::QzsL2cl),
+    (Constellation::Qzs, "2X", Code::QzsL2cx),
+    (Constellation::Qzs, "5I", Code::QzsL5i),
+    (Constellation::Qzs, "5Q", Code::QzsL5q),
+    (Constellation::Qzs, "5X", Code::QzsL5x),
+    (Constellation::Bds, "2I", Code::Bds2B1),
+    // RINEX 3.01 put B1I in band 1
+    (Constellation::Bds, "1I", Code::Bds2B1),
+    (Constellation::Bds, "7I", Code::Bds2B2),
+    (Constellation::Bds, "7Q", Code::Bds3B7q),
+    (Constellation::Bds, "7X", Code::Bds3B7x),
+    (Constellation::Bds, "6I", Code::Bds3B3i),
+    (Constellation::Bds, "6Q", Code::Bds3B3q),
+    (Constellation::Bds, "6X", Code::Bds3B3x),
+    (Constellation::Bds, "5D", Code::Bds3B5i),
+    (Constellation::Bds, "5P", Code::Bds3B5q),
+    (Constellation::Bds, "5X", Code::Bds3B5x),
+    (Constellation::Bds, "1D", Code::Bds3B1ci),
+    (Constellation::Bds, "1P", Code::Bds3B1cq),
+    (Constellation::Bds, "1X", Code::Bds3B1cx),
+];
+
 impl Code {
     pub(crate) fn from_code_t(value: swiftnav_sys::code_t) -> Result<Code, InvalidCode> {
         match value {
@@ -418,6 +528,42 @@ impl Code {
     pub fn is_qzss(&self) -> bool {
         unsafe { swiftnav_sys::is_qzss(self.to_code_t()) }
     }
+
+    /// Gets the RINEX 3 band and attribute of the code, such as `1C`
+    ///
+    /// `None` is returned for the auxiliary antenna codes, and for the BDS3
+    /// B2b I code which has no descriptor of its own.
+    pub fn to_rinex(&self) -> Option<&'static str> {
+        RINEX_CODES
+            .iter()
+            .find(|(_, _, code)| code == self)
+            .map(|(_, descriptor, _)| *descriptor)
+    }
+
+    /// Gets the code from a RINEX 3 band and attribute
+    ///
+    /// Either the band and attribute alone (`1C`) or a full observation type
+    /// (`C1C`, `L1C`, `D1C` or `S1C`) can be given.
+    pub fn from_rinex(constellation: Constellation, descriptor: &str) -> Option<Code> {
+        let descriptor = match descriptor.len() {
+            3 if descriptor.starts_with(&['C', 'L', 'D', 'S'][..]) => &descriptor[1..],
+            _ => descriptor,
+        };
+        RINEX_CODES
+            .iter()
+            .find(|(c, d, _)| *c == constellation && *d == descriptor)
+            .map(|(_, _, code)| *code)
+    }
+
+    /// Gets the carrier frequency of a GLONASS FDMA code on a frequency
+    /// channel, `None` for other codes
+    pub fn glo_fdma_frequency(&self, fcn: i8) -> Option<f64> {
+        match self {
+            Code::GloL1of | Code::GloL1p => Some(GLO_L1_HZ + f64::from(fcn) * GLO_L1_DELTA_HZ),
+            Code::GloL2of | Code::GloL2p => Some(GLO_L2_HZ + f64::from(fcn) * GLO_L2_DELTA_HZ),
+            _ => None,
+        }
+    }
 }
 
 impl FromStr for Code {
@@ -533,6 +679,41 @@ impl GnssSignal {
             str.to_string_lossy().to_string()
         }
     }
+
+    /// Get the carrier frequency of the signal, looking up the frequency
+    /// channel of GLONASS FDMA signals in `slots`
+    ///
+    /// Returns `None` if the frequency channel of a GLONASS satellite isn't
+    /// known.
+    pub fn glo_channel_frequency(&self, slots: &GloSlotMap) -> Option<f64> {
+        let code = self.code();
+        match code {
+            Code::GloL1of | Code::GloL1p | Code::GloL2of | Code::GloL2p => slots
+                .fcn(self.sat())
+                .and_then(|fcn| code.glo_fdma_frequency(fcn)),
+            _ => Some(self.carrier_frequency()),
+        }
+    }
+
+    /// Makes the RINEX 3 satellite identifier, such as `G05` or `S23`
+    pub fn to_rinex_sat(&self) -> String {
+        let constellation = self.to_constellation();
+        format!(
+            "{}{:02}",
+            constellation.to_rinex_char(),
+            self.sat() - constellation.rinex_sat_offset()
+        )
+    }
+
+    /// Makes a signal from a RINEX 3 satellite identifier and observation
+    /// code, see [`Code::from_rinex()`]
+    pub fn from_rinex(sat: &str, descriptor: &str) -> Option<GnssSignal> {
+        let mut chars = sat.trim().chars();
+        let constellation = Constellation::from_rinex_char(chars.next()?)?;
+        let sat: u16 = chars.as_str().trim().parse().ok()?;
+        let code = Code::from_rinex(constellation, descriptor)?;
+        GnssSignal::new(sat + constellation.rinex_sat_offset(), code).ok()
+    }
 }
 
 impl fmt::Display for GnssSignal {
@@ -541,6 +722,152 @@ impl fmt::Display for GnssSignal {
     }
 }
 
+/// GLONASS L1 center frequency, in Hz
+const GLO_L1_HZ: f64 = 1.602e9;
+/// GLONASS L1 frequency channel spacing, in Hz
+const GLO_L1_DELTA_HZ: f64 = 562.5e3;
+/// GLONASS L2 center frequency, in Hz
+const GLO_L2_HZ: f64 = 1.246e9;
+/// GLONASS L2 frequency channel spacing, in Hz
+const GLO_L2_DELTA_HZ: f64 = 437.5e3;
+
+/// Number of GLONASS orbital slots
+const GLO_SLOTS: usize = 28;
+
+/// Invalid GLONASS slot or frequency channel given to a [`GloSlotMap`]
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum InvalidGloChannel {
+    /// The orbital slot is not between 1 and 28
+    InvalidSlot(u16),
+    /// The frequency channel number is not between -7 and 6
+    InvalidFcn(i8),
+}
+
+impl fmt::Display for InvalidGloChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidGloChannel::InvalidSlot(slot) => {
+                write!(f, "Invalid GLONASS orbital slot: {}", slot)
+            }
+            InvalidGloChannel::InvalidFcn(fcn) => {
+                write!(f, "Invalid GLONASS frequency channel number: {}", fcn)
+            }
+        }
+    }
+}
+
+impl Error for InvalidGloChannel {}
+
+/// The frequency channel numbers of the GLONASS orbital slots
+///
+/// GLONASS satellites are identified by their orbital slot, but each
+/// transmits its FDMA signals on a frequency channel which isn't fixed to the
+/// slot. Satellites in antipodal slots share a frequency channel. The
+/// channels can be learned from the ephemerides, or from any of the other
+/// sources which give them, such as the RTCM MSM and UBX-RXM-RAWX messages.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct GloSlotMap {
+    fcns: [Option<i8>; GLO_SLOTS],
+}
+
+impl GloSlotMap {
+    /// Makes a map with no known frequency channels
+    pub fn new() -> GloSlotMap {
+        GloSlotMap {
+            fcns: [None; GLO_SLOTS],
+        }
+    }
+
+    /// Sets the frequency channel number of a slot, replacing any previous
+    /// one
+    pub fn set_fcn(&mut self, slot: u16, fcn: i8) -> Result<(), InvalidGloChannel> {
+        if !(-7..=6).contains(&fcn) {
+            return Err(InvalidGloChannel::InvalidFcn(fcn));
+        }
+        let entry = self
+            .fcns
+            .get_mut(usize::from(slot).wrapping_sub(1))
+            .ok_or(InvalidGloChannel::InvalidSlot(slot))?;
+        *entry = Some(fcn);
+        Ok(())
+    }
+
+    /// Gets the frequency channel number of a slot, if known
+    pub fn fcn(&self, slot: u16) -> Option<i8> {
+        self.fcns
+            .get(usize::from(slot).wrapping_sub(1))
+            .copied()
+            .flatten()
+    }
+
+    /// Forgets the frequency channel number of a slot, returning it if it
+    /// was known
+    pub fn remove(&mut self, slot: u16) -> Option<i8> {
+        self.fcns
+            .get_mut(usize::from(slot).wrapping_sub(1))
+            .and_then(Option::take)
+    }
+
+    /// Sets the frequency channel number of the slot of a GLONASS ephemeris
+    ///
+    /// Returns `false` if the ephemeris isn't for a GLONASS satellite, or
+    /// doesn't have a valid frequency channel.
+    pub fn update_from_ephemeris(&mut self, ephemeris: &Ephemeris) -> bool {
+        match (ephemeris.sid(), ephemeris.glo_fcn()) {
+            (Ok(sid), Some(fcn)) => self.set_fcn(sid.sat(), fcn).is_ok(),
+            _ => false,
+        }
+    }
+
+    /// Gets the slots known to be on a frequency channel
+    pub fn slots_on(&self, fcn: i8) -> impl Iterator<Item = u16> + '_ {
+        self.iter()
+            .filter(move |(_, slot_fcn)| *slot_fcn == fcn)
+            .map(|(slot, _)| slot)
+    }
+
+    /// Iterates over the slots with a known frequency channel number, along
+    /// with the channel number
+    pub fn iter(&self) -> impl Iterator<Item = (u16, i8)> + '_ {
+        self.fcns
+            .iter()
+            .enumerate()
+            .filter_map(|(i, fcn)| fcn.map(|fcn| (i as u16 + 1, fcn)))
+    }
+
+    /// Gets the number of slots with a known frequency channel number
+    pub fn len(&self) -> usize {
+        self.fcns.iter().filter(|fcn| fcn.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for GloSlotMap {
+    fn default() -> GloSlotMap {
+        GloSlotMap::new()
+    }
+}
+
+/// Invalid slots and frequency channels are skipped
+impl Extend<(u16, i8)> for GloSlotMap {
+    fn extend<T: IntoIterator<Item = (u16, i8)>>(&mut self, iter: T) {
+        for (slot, fcn) in iter {
+            let _ = self.set_fcn(slot, fcn);
+        }
+    }
+}
+
+impl std::iter::FromIterator<(u16, i8)> for GloSlotMap {
+    fn from_iter<T: IntoIterator<Item = (u16, i8)>>(iter: T) -> GloSlotMap {
+        let mut slots = GloSlotMap::new();
+        slots.extend(iter);
+        slots
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1262,4 +1589,125 @@ mod tests {
             "BDS B1 32"
         );
     }
+
+    #[test]
+    fn rinex_codes() {
+        for (constellation, descriptor, code) in RINEX_CODES.iter() {
+            assert_eq!(Code::from_rinex(*constellation, descriptor), Some(*code));
+            let written = code.to_rinex().unwrap();
+            assert_eq!(Code::from_rinex(*constellation, written), Some(*code));
+        }
+        assert_eq!(Code::GpsL1p.to_rinex(), Some("1W"));
+        assert_eq!(Code::Bds2B1.to_rinex(), Some("2I"));
+        assert_eq!(Code::Bds3B7i.to_rinex(), None);
+        assert_eq!(Code::AuxGps.to_rinex(), None);
+
+        assert_eq!(
+            Code::from_rinex(Constellation::Gal, "1C"),
+            Some(Code::GalE1c)
+        );
+        assert_eq!(
+            Code::from_rinex(Constellation::Glo, "L2C"),
+            Some(Code::GloL2of)
+        );
+        assert_eq!(
+            Code::from_rinex(Constellation::Bds, "C1I"),
+            Some(Code::Bds2B1)
+        );
+        assert_eq!(Code::from_rinex(Constellation::Glo, "1B"), None);
+        assert_eq!(Code::from_rinex(Constellation::Gps, "X1C"), None);
+        assert_eq!(Code::from_rinex(Constellation::Gps, ""), None);
+
+        for constellation in [
+            Constellation::Gps,
+            Constellation::Sbas,
+            Constellation::Glo,
+            Constellation::Bds,
+            Constellation::Qzs,
+            Constellation::Gal,
+        ]
+        .iter()
+        {
+            assert_eq!(
+                Constellation::from_rinex_char(constellation.to_rinex_char()),
+                Some(*constellation)
+            );
+        }
+        assert_eq!(Constellation::from_rinex_char('I'), None);
+    }
+
+    #[test]
+    fn rinex_sats() {
+        let sid = GnssSignal::from_rinex("G05", "C1C").unwrap();
+        assert_eq!(sid, GnssSignal::new(5, Code::GpsL1ca).unwrap());
+        assert_eq!(sid.to_rinex_sat(), "G05");
+
+        let sid = GnssSignal::from_rinex("S23", "1C").unwrap();
+        assert_eq!(sid, GnssSignal::new(123, Code::SbasL1ca).unwrap());
+        assert_eq!(sid.to_rinex_sat(), "S23");
+
+        let sid = GnssSignal::from_rinex("J 2", "L5Q").unwrap();
+        assert_eq!(sid, GnssSignal::new(194, Code::QzsL5q).unwrap());
+        assert_eq!(sid.to_rinex_sat(), "J02");
+
+        assert_eq!(GnssSignal::from_rinex("G05", "7Q"), None);
+        assert_eq!(GnssSignal::from_rinex("X05", "1C"), None);
+        assert_eq!(GnssSignal::from_rinex("G", "1C"), None);
+    }
+
+    #[test]
+    fn glo_slot_map() {
+        let mut slots = GloSlotMap::new();
+        assert!(slots.is_empty());
+        assert_eq!(slots.fcn(1), None);
+        slots.set_fcn(1, 1).unwrap();
+        slots.set_fcn(5, 1).unwrap();
+        slots.set_fcn(2, -4).unwrap();
+        assert_eq!(slots.fcn(1), Some(1));
+        assert_eq!(slots.fcn(2), Some(-4));
+        assert_eq!(slots.len(), 3);
+        assert_eq!(slots.slots_on(1).collect::<Vec<_>>(), vec![1, 5]);
+        assert_eq!(
+            slots.iter().collect::<Vec<_>>(),
+            vec![(1, 1), (2, -4), (5, 1)]
+        );
+
+        assert_eq!(slots.set_fcn(0, 1), Err(InvalidGloChannel::InvalidSlot(0)));
+        assert_eq!(
+            slots.set_fcn(29, 1),
+            Err(InvalidGloChannel::InvalidSlot(29))
+        );
+        assert_eq!(slots.set_fcn(3, 7), Err(InvalidGloChannel::InvalidFcn(7)));
+        assert_eq!(slots.set_fcn(3, -8), Err(InvalidGloChannel::InvalidFcn(-8)));
+        assert_eq!(slots.fcn(29), None);
+
+        assert_eq!(slots.remove(5), Some(1));
+        assert_eq!(slots.remove(5), None);
+        assert_eq!(slots.remove(40), None);
+
+        let collected: GloSlotMap = vec![(3, 6), (4, -7), (30, 0), (6, 9)].into_iter().collect();
+        assert_eq!(collected.iter().collect::<Vec<_>>(), vec![(3, 6), (4, -7)]);
+    }
+
+    #[test]
+    fn glo_channel_frequency() {
+        let mut slots = GloSlotMap::new();
+        slots.set_fcn(3, -2).unwrap();
+
+        let l1 = GnssSignal::new(3, Code::GloL1of).unwrap();
+        assert_eq!(
+            l1.glo_channel_frequency(&slots),
+            Some(1.602e9 - 2.0 * 562.5e3)
+        );
+        let l2 = GnssSignal::new(3, Code::GloL2p).unwrap();
+        assert_eq!(
+            l2.glo_channel_frequency(&slots),
+            Some(1.246e9 - 2.0 * 437.5e3)
+        );
+        let unknown = GnssSignal::new(4, Code::GloL1of).unwrap();
+        assert_eq!(unknown.glo_channel_frequency(&slots), None);
+
+        assert_eq!(Code::GloL1of.glo_fdma_frequency(0), Some(1.602e9));
+        assert_eq!(Code::GpsL1ca.glo_fdma_frequency(0), None);
+    }
 }
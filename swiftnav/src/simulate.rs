@@ -0,0 +1,580 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Synthetic measurement generation
+//!
+//! A [`MeasurementSimulator`] generates the measurements a receiver following
+//! a known [trajectory](ReceiverTrajectory) would make of the satellites
+//! described by a set of ephemerides. Since the truth is known, algorithms
+//! can be tested end to end, from the measurements to the position solution.
+//!
+//! The observables of each signal are simulated as
+//!  * Pseudorange - `ρ + c(dtr - dts) + I + T + εP`
+//!  * Carrier phase - `(ρ + c(dtr - dts) - I + T) / λ + N + εΦ`
+//!  * Doppler - `-(ρ' + c(dtr' - dts')) / λ + εD`
+//!
+//! where `ρ` is the geometric range from the satellite at the time of
+//! transmission to the receiver, including the Earth rotation correction,
+//! `dtr` and `dts` the receiver and satellite clock errors, `I` the Klobuchar
+//! ionospheric delay scaled to the signal frequency, `T` the tropospheric
+//! delay, `λ` the carrier wavelength and `N` a random integer ambiguity which
+//! stays the same for as long as the satellite is above the elevation mask.
+//! The noise terms `ε` are white and gaussian, drawn from a seeded generator
+//! so that a simulation is repeatable.
+//!
+//! Group delays, multipath, cycle slips and antenna phase center offsets are
+//! not simulated. The C/N0 grows with the elevation, from 30 dB-Hz at the
+//! horizon to 50 dB-Hz at the zenith.
+
+use crate::coords::ECEF;
+use crate::ephemeris::Ephemeris;
+use crate::ionosphere::Ionosphere;
+use crate::navmeas::NavigationMeasurement;
+use crate::signal::{Code, GnssSignal};
+use crate::solver::filter::geometry;
+use crate::solver::pvt::TroposphereCorrection;
+use crate::time::{GpsTime, TimeDelta};
+use crate::troposphere::TroposphereModel;
+use crate::SPEED_OF_LIGHT;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::time::Duration;
+
+const GPS_L1_HZ: f64 = 1.57542e9;
+const EARTH_ROTATION_RATE: f64 = 7.292_115_146_7e-5;
+/// Number of light time iterations, enough for a micrometer level range
+const LIGHT_TIME_ITERATIONS: usize = 3;
+
+/// The true motion of a simulated receiver
+pub trait ReceiverTrajectory {
+    /// Gets the ECEF position and velocity of the receiver at a time
+    ///
+    /// Returns `None` if the trajectory doesn't cover the time
+    fn state_at(&self, time: &GpsTime) -> Option<(ECEF, ECEF)>;
+}
+
+/// A static receiver
+impl ReceiverTrajectory for ECEF {
+    fn state_at(&self, _time: &GpsTime) -> Option<(ECEF, ECEF)> {
+        Some((*self, ECEF::default()))
+    }
+}
+
+impl<F> ReceiverTrajectory for F
+where
+    F: Fn(&GpsTime) -> Option<(ECEF, ECEF)>,
+{
+    fn state_at(&self, time: &GpsTime) -> Option<(ECEF, ECEF)> {
+        self(time)
+    }
+}
+
+/// Settings of a [`MeasurementSimulator`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationSettings {
+    codes: Option<Vec<Code>>,
+    elevation_mask: f64,
+    ionosphere: Option<Ionosphere>,
+    troposphere: Option<TroposphereCorrection>,
+    clock_bias: f64,
+    clock_drift: f64,
+    pseudorange_sigma: f64,
+    carrier_phase_sigma: f64,
+    doppler_sigma: f64,
+    seed: u64,
+}
+
+impl SimulationSettings {
+    /// Makes the default settings
+    ///
+    /// Note: The default settings consist of
+    ///  * Simulating the signal of each ephemeris
+    ///  * An elevation mask of 0 degrees
+    ///  * No ionospheric or tropospheric delay
+    ///  * A perfect receiver clock
+    ///  * No measurement noise
+    pub fn new() -> SimulationSettings {
+        SimulationSettings {
+            codes: None,
+            elevation_mask: 0.0,
+            ionosphere: None,
+            troposphere: None,
+            clock_bias: 0.0,
+            clock_drift: 0.0,
+            pseudorange_sigma: 0.0,
+            carrier_phase_sigma: 0.0,
+            doppler_sigma: 0.0,
+            seed: 0,
+        }
+    }
+
+    /// Simulates the signals with the given codes of each satellite, instead
+    /// of only the signal of its ephemeris
+    ///
+    /// Codes of other constellations than the ephemeris' are skipped
+    pub fn set_codes(self, codes: &[Code]) -> SimulationSettings {
+        SimulationSettings {
+            codes: Some(codes.to_vec()),
+            ..self
+        }
+    }
+
+    /// Sets the lowest elevation of the simulated satellites, in radians
+    pub fn set_elevation_mask(self, elevation_mask: f64) -> SimulationSettings {
+        SimulationSettings {
+            elevation_mask,
+            ..self
+        }
+    }
+
+    /// Delays the signals with the Klobuchar model of the given parameters
+    pub fn set_ionosphere(self, ionosphere: Ionosphere) -> SimulationSettings {
+        SimulationSettings {
+            ionosphere: Some(ionosphere),
+            ..self
+        }
+    }
+
+    /// Delays the signals with the given tropospheric model
+    pub fn set_troposphere(self, troposphere: TroposphereCorrection) -> SimulationSettings {
+        SimulationSettings {
+            troposphere: Some(troposphere),
+            ..self
+        }
+    }
+
+    /// Sets the receiver clock error at the first simulated epoch, in
+    /// seconds, and its drift in seconds per second
+    pub fn set_receiver_clock(self, clock_bias: f64, clock_drift: f64) -> SimulationSettings {
+        SimulationSettings {
+            clock_bias,
+            clock_drift,
+            ..self
+        }
+    }
+
+    /// Sets the standard deviations of the measurement noise
+    ///
+    /// The pseudorange noise is in meters, the carrier phase noise in cycles
+    /// and the doppler noise in Hertz
+    pub fn set_noise(
+        self,
+        pseudorange_sigma: f64,
+        carrier_phase_sigma: f64,
+        doppler_sigma: f64,
+    ) -> SimulationSettings {
+        SimulationSettings {
+            pseudorange_sigma,
+            carrier_phase_sigma,
+            doppler_sigma,
+            ..self
+        }
+    }
+
+    /// Sets the seed of the noise and ambiguity generator
+    pub fn set_seed(self, seed: u64) -> SimulationSettings {
+        SimulationSettings { seed, ..self }
+    }
+
+    pub fn codes(&self) -> Option<&[Code]> {
+        self.codes.as_deref()
+    }
+
+    pub fn elevation_mask(&self) -> f64 {
+        self.elevation_mask
+    }
+
+    pub fn ionosphere(&self) -> Option<&Ionosphere> {
+        self.ionosphere.as_ref()
+    }
+
+    pub fn troposphere(&self) -> Option<&TroposphereCorrection> {
+        self.troposphere.as_ref()
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Default for SimulationSettings {
+    fn default() -> SimulationSettings {
+        SimulationSettings::new()
+    }
+}
+
+/// The SplitMix64 generator, with Box-Muller gaussian samples
+struct Random(u64);
+
+impl Random {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform sample in (0, 1]
+    fn uniform(&mut self) -> f64 {
+        ((self.next_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+
+    fn gaussian(&mut self, sigma: f64) -> f64 {
+        if sigma == 0.0 {
+            return 0.0;
+        }
+        let (u1, u2) = (self.uniform(), self.uniform());
+        sigma * (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+    }
+
+    /// Integer ambiguity between -2^20 and 2^20
+    fn ambiguity(&mut self) -> f64 {
+        (self.next_u64() >> 43) as f64 - (1u64 << 20) as f64
+    }
+}
+
+struct Track {
+    since: GpsTime,
+    ambiguity: f64,
+}
+
+/// Generates the measurements of a receiver following a trajectory
+///
+/// See the [module documentation](self) for the measurement model. The
+/// simulator remembers the carrier phase ambiguities and lock times of the
+/// signals between epochs, so the epochs should be simulated in order.
+pub struct MeasurementSimulator<'a> {
+    settings: SimulationSettings,
+    ephemerides: &'a [Ephemeris],
+    random: Random,
+    tracks: HashMap<GnssSignal, Track>,
+    clock_epoch: Option<GpsTime>,
+}
+
+impl<'a> MeasurementSimulator<'a> {
+    pub fn new(settings: SimulationSettings, ephemerides: &'a [Ephemeris]) -> Self {
+        let random = Random(settings.seed);
+        MeasurementSimulator {
+            settings,
+            ephemerides,
+            random,
+            tracks: HashMap::new(),
+            clock_epoch: None,
+        }
+    }
+
+    pub fn settings(&self) -> &SimulationSettings {
+        &self.settings
+    }
+
+    /// Gets the true receiver clock error at a time, in seconds
+    pub fn clock_bias_at(&self, time: &GpsTime) -> f64 {
+        let elapsed = match &self.clock_epoch {
+            Some(epoch) => time.diff(epoch),
+            None => 0.0,
+        };
+        self.settings.clock_bias + self.settings.clock_drift * elapsed
+    }
+
+    /// Simulates the measurements of an epoch, ordered by signal
+    ///
+    /// Returns `None` if the trajectory doesn't cover the time. Satellites
+    /// whose ephemeris isn't valid or healthy at the time are left out.
+    pub fn simulate<T>(
+        &mut self,
+        trajectory: &T,
+        time: GpsTime,
+    ) -> Option<Vec<NavigationMeasurement>>
+    where
+        T: ReceiverTrajectory + ?Sized,
+    {
+        let (position, velocity) = trajectory.state_at(&time)?;
+        if self.clock_epoch.is_none() {
+            self.clock_epoch = Some(time);
+        }
+        let clock_bias = SPEED_OF_LIGHT * self.clock_bias_at(&time);
+        let clock_drift = SPEED_OF_LIGHT * self.settings.clock_drift;
+        let llh = position.to_llh();
+        let doy = time.to_utc_hardcoded().day_of_year() as f64;
+
+        let mut measurements = Vec::new();
+        for ephemeris in self.ephemerides {
+            let eph_sid = match ephemeris.sid() {
+                Ok(sid) => sid,
+                Err(_) => continue,
+            };
+
+            let mut state = None;
+            let mut light_time = 0.0;
+            for _ in 0..LIGHT_TIME_ITERATIONS {
+                let transmission = time - TimeDelta::from_secs_f64(light_time);
+                match ephemeris.calc_satellite_state(transmission) {
+                    Ok(s) => {
+                        light_time = geometry(&s.pos, &position).0 / SPEED_OF_LIGHT;
+                        state = Some(s);
+                    }
+                    Err(_) => {
+                        state = None;
+                        break;
+                    }
+                }
+            }
+            let state = match state {
+                Some(state) => state,
+                None => continue,
+            };
+            let azel = position.azel_of(&state.pos);
+            if azel.el < self.settings.elevation_mask {
+                continue;
+            }
+
+            let (range, los) = geometry(&state.pos, &position);
+            let dot =
+                |v: &ECEF| -> f64 { los.iter().zip(v.as_array_ref()).map(|(a, b)| a * b).sum() };
+            let satellite_rate = dot(&state.vel);
+            // The transmission time moves with the range, and the Earth
+            // rotation correction with the satellite and the receiver
+            let sagnac_rate = EARTH_ROTATION_RATE / SPEED_OF_LIGHT
+                * (state.vel.x() * position.y() - state.vel.y() * position.x()
+                    + state.pos.x() * velocity.y()
+                    - state.pos.y() * velocity.x());
+            let range_rate = (satellite_rate - dot(&velocity))
+                / (1.0 + satellite_rate / SPEED_OF_LIGHT)
+                + sagnac_rate;
+            let clock = clock_bias - SPEED_OF_LIGHT * state.clock_err;
+            let clock_rate = clock_drift - SPEED_OF_LIGHT * state.clock_rate_err;
+            let ionosphere = self.settings.ionosphere.as_ref().map_or(0.0, |ionosphere| {
+                ionosphere.calc_delay(&time, llh.latitude(), llh.longitude(), azel.az, azel.el)
+            });
+            let troposphere = self.settings.troposphere.map_or(0.0, |troposphere| {
+                troposphere.calc_delay(doy, llh.latitude(), llh.height(), azel.el)
+            });
+            let cn0 = 30.0 + 20.0 * azel.el.max(0.0).sin();
+
+            let codes = match &self.settings.codes {
+                Some(codes) => codes
+                    .iter()
+                    .filter(|code| code.to_constellation() == eph_sid.to_constellation())
+                    .copied()
+                    .collect(),
+                None => vec![eph_sid.code()],
+            };
+            for code in codes {
+                let sid = match GnssSignal::new(eph_sid.sat(), code) {
+                    Ok(sid) => sid,
+                    Err(_) => continue,
+                };
+                if !ephemeris.is_healthy(&code) {
+                    continue;
+                }
+                let frequency = sid.carrier_frequency();
+                let wavelength = SPEED_OF_LIGHT / frequency;
+                let ionosphere = ionosphere * (GPS_L1_HZ / frequency).powi(2);
+
+                let random = &mut self.random;
+                let track = self.tracks.entry(sid).or_insert_with(|| Track {
+                    since: time,
+                    ambiguity: random.ambiguity(),
+                });
+                if time.diff(&track.since) < 0.0 {
+                    track.since = time;
+                }
+                let lock_time = time.diff(&track.since);
+                let ambiguity = track.ambiguity;
+
+                let mut nm = NavigationMeasurement::new();
+                nm.set_sid(sid);
+                nm.set_pseudorange(
+                    range
+                        + clock
+                        + ionosphere
+                        + troposphere
+                        + random.gaussian(self.settings.pseudorange_sigma),
+                );
+                nm.set_carrier_phase(
+                    (range + clock - ionosphere + troposphere) / wavelength
+                        + ambiguity
+                        + random.gaussian(self.settings.carrier_phase_sigma),
+                );
+                nm.set_half_cycle_known(true);
+                nm.set_measured_doppler(
+                    -(range_rate + clock_rate) / wavelength
+                        + random.gaussian(self.settings.doppler_sigma),
+                );
+                nm.set_cn0(cn0);
+                nm.set_lock_time(Duration::from_secs_f64(lock_time));
+                nm.set_satellite_state(&state);
+                measurements.push(nm);
+            }
+        }
+
+        // Signals which aren't visible anymore are reacquired with a new
+        // ambiguity
+        self.tracks
+            .retain(|sid, _| measurements.iter().any(|nm| nm.sid() == *sid));
+        measurements.sort_by_key(|nm| nm.sid());
+        Some(measurements)
+    }
+
+    /// Simulates the measurements of several epochs, in order
+    ///
+    /// Times which the trajectory doesn't cover are skipped
+    pub fn simulate_epochs<T>(
+        &mut self,
+        trajectory: &T,
+        times: &[GpsTime],
+    ) -> Vec<(GpsTime, Vec<NavigationMeasurement>)>
+    where
+        T: ReceiverTrajectory + ?Sized,
+    {
+        times
+            .iter()
+            .filter_map(|time| {
+                self.simulate(trajectory, *time)
+                    .map(|measurements| (*time, measurements))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ephemeris::{calc_satellite_states_at, EphemerisTerms};
+    use crate::signal::Constellation;
+    use crate::solver::wls::{solve_wls, Weighting};
+    use float_eq::assert_float_eq;
+
+    fn ephemerides(toe: GpsTime) -> Vec<Ephemeris> {
+        (1..=8)
+            .map(|sat| {
+                Ephemeris::new(
+                    GnssSignal::new(sat, Code::GpsL1ca).unwrap(),
+                    toe,
+                    2.0,
+                    14400,
+                    1,
+                    0,
+                    0,
+                    EphemerisTerms::new_kepler(
+                        Constellation::Gps,
+                        [0.0, 0.0],
+                        200.0,
+                        -20.0,
+                        -1e-6,
+                        1e-5,
+                        -4e-8,
+                        1e-7,
+                        4e-9,
+                        0.1 * f64::from(sat),
+                        0.01,
+                        5153.6,
+                        0.3 * f64::from(sat),
+                        -8e-9,
+                        0.5,
+                        0.96,
+                        0.0,
+                        1e-5,
+                        0.0,
+                        0.0,
+                        toe,
+                        30,
+                        30,
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    /// A point on the ground below the satellites, so that they are all
+    /// visible
+    fn receiver_below(ephemerides: &[Ephemeris], time: GpsTime) -> ECEF {
+        let states = calc_satellite_states_at(ephemerides, time);
+        let mut centroid = [0.0; 3];
+        for state in states.iter().flatten() {
+            for (c, p) in centroid.iter_mut().zip(state.pos.as_array_ref()) {
+                *c += p;
+            }
+        }
+        let norm = centroid.iter().map(|c| c * c).sum::<f64>().sqrt();
+        let scale = 6_371_000.0 / norm;
+        ECEF::new(
+            centroid[0] * scale,
+            centroid[1] * scale,
+            centroid[2] * scale,
+        )
+    }
+
+    #[test]
+    fn solve_simulated_epoch() {
+        let toe = GpsTime::new(2100, 7200.0).unwrap();
+        let ephemerides = ephemerides(toe);
+        let time = GpsTime::new(2100, 7800.0).unwrap();
+        let receiver = receiver_below(&ephemerides, time);
+
+        let settings = SimulationSettings::new().set_receiver_clock(1e-4, 1e-8);
+        let mut simulator = MeasurementSimulator::new(settings, &ephemerides);
+        let measurements = simulator.simulate(&receiver, time).unwrap();
+        assert_eq!(measurements.len(), 8);
+
+        let solution = solve_wls(&measurements, &Weighting::Uniform).unwrap();
+        assert_float_eq!(solution.position().x(), receiver.x(), abs <= 1e-3);
+        assert_float_eq!(solution.position().y(), receiver.y(), abs <= 1e-3);
+        assert_float_eq!(solution.position().z(), receiver.z(), abs <= 1e-3);
+        assert_float_eq!(solution.clock_biases()[0].1, 1e-4, abs <= 1e-11);
+
+        // The carrier phase follows the pseudorange, up to the ambiguity, and
+        // the ambiguity is kept from epoch to epoch
+        let later = GpsTime::new(2100, 7801.0).unwrap();
+        let next = simulator.simulate(&receiver, later).unwrap();
+        let wavelength = SPEED_OF_LIGHT / GPS_L1_HZ;
+        for (first, second) in measurements.iter().zip(&next) {
+            let ambiguity = |nm: &NavigationMeasurement| {
+                nm.carrier_phase().unwrap() - nm.pseudorange().unwrap() / wavelength
+            };
+            assert_float_eq!(ambiguity(first), ambiguity(first).round(), abs <= 1e-4);
+            assert_float_eq!(ambiguity(first), ambiguity(second), abs <= 1e-4);
+            assert_eq!(second.lock_time(), Duration::from_secs(1));
+
+            // The doppler matches the change of the pseudorange
+            let doppler =
+                -(second.pseudorange().unwrap() - first.pseudorange().unwrap()) / wavelength;
+            let mean =
+                (first.measured_doppler().unwrap() + second.measured_doppler().unwrap()) / 2.0;
+            assert_float_eq!(doppler, mean, abs <= 1e-3);
+        }
+        assert_float_eq!(simulator.clock_bias_at(&later), 1e-4 + 1e-8, abs <= 1e-15);
+    }
+
+    #[test]
+    fn noise_is_repeatable() {
+        let toe = GpsTime::new(2100, 7200.0).unwrap();
+        let ephemerides = ephemerides(toe);
+        let time = GpsTime::new(2100, 7800.0).unwrap();
+        let receiver = receiver_below(&ephemerides, time);
+        let settings = SimulationSettings::new()
+            .set_noise(0.5, 0.01, 0.05)
+            .set_seed(42);
+
+        let simulate = |settings: SimulationSettings| {
+            MeasurementSimulator::new(settings, &ephemerides)
+                .simulate(&receiver, time)
+                .unwrap()
+        };
+        let first = simulate(settings.clone());
+        assert_eq!(first, simulate(settings.clone()));
+        assert_ne!(first, simulate(settings.set_seed(7)));
+
+        // Outside of the trajectory
+        let trajectory = |_: &GpsTime| None;
+        let mut simulator = MeasurementSimulator::new(SimulationSettings::new(), &ephemerides);
+        assert!(simulator.simulate(&trajectory, time).is_none());
+    }
+}
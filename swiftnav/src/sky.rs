@@ -0,0 +1,334 @@
+// Copyright (c) 2024 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Sky masks and sky plots
+//!
+//! Buildings, trees and terrain block different parts of the sky seen from a
+//! site, so a single elevation mask is often either too strict or too loose.
+//! A [`SkyMask`] gives the lowest usable elevation as a function of azimuth,
+//! interpolated between points measured around the horizon.
+//!
+//! [`SkyPlotBins`] divides the sky into cells of equal azimuth and elevation
+//! spans, to accumulate statistics such as signal strength or the number of
+//! observations in each part of the sky.
+
+use crate::coords::{AzimuthElevation, ECEF};
+use crate::navmeas::NavigationMeasurement;
+use crate::signal::GnssSignal;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+/// Invalid sky mask or sky plot definition
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct InvalidSkyMask(&'static str);
+
+impl fmt::Display for InvalidSkyMask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid sky mask: {}", self.0)
+    }
+}
+
+impl Error for InvalidSkyMask {}
+
+/// An azimuth dependent elevation mask
+///
+/// The mask elevation is interpolated linearly in azimuth between the points
+/// defining the mask, wrapping around north.
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub struct SkyMask {
+    /// Azimuth and elevation pairs in degrees, sorted by azimuth in [0, 360)
+    points: Vec<(f64, f64)>,
+}
+
+impl SkyMask {
+    /// Makes a mask with the same elevation, in degrees, at all azimuths
+    pub fn constant(elevation: f64) -> SkyMask {
+        SkyMask {
+            points: vec![(0.0, elevation)],
+        }
+    }
+
+    /// Makes a mask from azimuth and elevation pairs, in degrees
+    ///
+    /// The points can be given in any order, and azimuths outside of
+    /// [0, 360) are wrapped. Returns an error if there are no points, if any
+    /// of the values isn't finite, or if two points have the same azimuth.
+    pub fn from_points(points: &[(f64, f64)]) -> Result<SkyMask, InvalidSkyMask> {
+        if points.is_empty() {
+            return Err(InvalidSkyMask("no points"));
+        }
+        if points
+            .iter()
+            .any(|(az, el)| !az.is_finite() || !el.is_finite())
+        {
+            return Err(InvalidSkyMask("values must be finite"));
+        }
+        let mut points: Vec<(f64, f64)> = points
+            .iter()
+            .map(|(az, el)| (az.rem_euclid(360.0), *el))
+            .collect();
+        points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        if points.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return Err(InvalidSkyMask("azimuths must be different"));
+        }
+        Ok(SkyMask { points })
+    }
+
+    /// Gets the points of the mask, sorted by azimuth
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    /// Gets the mask elevation at an azimuth, both in degrees
+    pub fn elevation_at(&self, azimuth: f64) -> f64 {
+        let azimuth = azimuth.rem_euclid(360.0);
+        let (first, last) = (self.points[0], self.points[self.points.len() - 1]);
+        let (before, after) = match self.points.iter().position(|p| p.0 > azimuth) {
+            // Between the last point and the first one, across north
+            None => (last, (first.0 + 360.0, first.1)),
+            Some(0) => ((last.0 - 360.0, last.1), first),
+            Some(i) => (self.points[i - 1], self.points[i]),
+        };
+        let fraction = (azimuth - before.0) / (after.0 - before.0);
+        before.1 + fraction * (after.1 - before.1)
+    }
+
+    /// Checks if a direction is above the mask
+    pub fn is_visible(&self, direction: &AzimuthElevation) -> bool {
+        direction.el.to_degrees() >= self.elevation_at(direction.az.to_degrees())
+    }
+
+    /// Checks if a satellite is above the mask of a receiver
+    pub fn is_visible_from(&self, receiver: &ECEF, satellite: &ECEF) -> bool {
+        self.is_visible(&receiver.azel_of(satellite))
+    }
+
+    /// Keeps the measurements whose satellites are above the mask of a
+    /// receiver
+    ///
+    /// The satellite states of the measurements must have been set.
+    pub fn filter_measurements(
+        &self,
+        receiver: &ECEF,
+        measurements: &[NavigationMeasurement],
+    ) -> Vec<NavigationMeasurement> {
+        measurements
+            .iter()
+            .filter(|nm| self.is_visible_from(receiver, &nm.satellite_position()))
+            .cloned()
+            .collect()
+    }
+}
+
+/// A cell of a sky plot, see [`SkyPlotBins`]
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct SkyCell {
+    azimuth: usize,
+    elevation: usize,
+}
+
+impl SkyCell {
+    /// Gets the index of the cell along the azimuth, counting clockwise from
+    /// north
+    pub fn azimuth_index(&self) -> usize {
+        self.azimuth
+    }
+
+    /// Gets the index of the cell along the elevation, counting up from the
+    /// horizon
+    pub fn elevation_index(&self) -> usize {
+        self.elevation
+    }
+}
+
+/// Division of the sky above the horizon into cells
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct SkyPlotBins {
+    azimuth_step: f64,
+    elevation_step: f64,
+}
+
+impl SkyPlotBins {
+    /// Makes cells with the given spans of azimuth and elevation, in degrees
+    ///
+    /// The spans must evenly divide 360 and 90 degrees respectively.
+    pub fn new(azimuth_step: f64, elevation_step: f64) -> Result<SkyPlotBins, InvalidSkyMask> {
+        let divides = |step: f64, range: f64| {
+            step > 0.0 && step <= range && ((range / step) - (range / step).round()).abs() < 1e-9
+        };
+        if !divides(azimuth_step, 360.0) {
+            return Err(InvalidSkyMask("azimuth step must divide 360 degrees"));
+        }
+        if !divides(elevation_step, 90.0) {
+            return Err(InvalidSkyMask("elevation step must divide 90 degrees"));
+        }
+        Ok(SkyPlotBins {
+            azimuth_step,
+            elevation_step,
+        })
+    }
+
+    /// Gets the number of cells around the horizon
+    pub fn azimuth_cells(&self) -> usize {
+        (360.0 / self.azimuth_step).round() as usize
+    }
+
+    /// Gets the number of cells from the horizon to the zenith
+    pub fn elevation_cells(&self) -> usize {
+        (90.0 / self.elevation_step).round() as usize
+    }
+
+    /// Gets the cell containing a direction, `None` if it is below the
+    /// horizon
+    ///
+    /// The zenith is in the highest row of cells.
+    pub fn cell(&self, direction: &AzimuthElevation) -> Option<SkyCell> {
+        let direction = direction.normalized();
+        let elevation = direction.el.to_degrees();
+        if elevation < 0.0 {
+            return None;
+        }
+        let index = |value: f64, step: f64, cells: usize| ((value / step) as usize).min(cells - 1);
+        Some(SkyCell {
+            azimuth: index(
+                direction.az.to_degrees(),
+                self.azimuth_step,
+                self.azimuth_cells(),
+            ),
+            elevation: index(elevation, self.elevation_step, self.elevation_cells()),
+        })
+    }
+
+    /// Gets the direction of the center of a cell
+    pub fn center(&self, cell: &SkyCell) -> AzimuthElevation {
+        AzimuthElevation::new(
+            ((cell.azimuth as f64 + 0.5) * self.azimuth_step).to_radians(),
+            ((cell.elevation as f64 + 0.5) * self.elevation_step).to_radians(),
+        )
+    }
+
+    /// Groups satellites by the cell they are in
+    ///
+    /// Satellites below the horizon are left out.
+    pub fn bin<I>(&self, satellites: I) -> BTreeMap<SkyCell, Vec<GnssSignal>>
+    where
+        I: IntoIterator<Item = (GnssSignal, AzimuthElevation)>,
+    {
+        let mut cells: BTreeMap<SkyCell, Vec<GnssSignal>> = BTreeMap::new();
+        for (sid, direction) in satellites {
+            if let Some(cell) = self.cell(&direction) {
+                cells.entry(cell).or_default().push(sid);
+            }
+        }
+        cells
+    }
+
+    /// Groups measurements by the cell their satellites are in, as seen from
+    /// a receiver
+    ///
+    /// The satellite states of the measurements must have been set.
+    pub fn bin_measurements(
+        &self,
+        receiver: &ECEF,
+        measurements: &[NavigationMeasurement],
+    ) -> BTreeMap<SkyCell, Vec<GnssSignal>> {
+        self.bin(
+            measurements
+                .iter()
+                .map(|nm| (nm.sid(), receiver.azel_of(&nm.satellite_position()))),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::LLHDegrees;
+    use crate::signal::Code;
+    use float_eq::assert_float_eq;
+
+    fn azel(az: f64, el: f64) -> AzimuthElevation {
+        AzimuthElevation::new(az.to_radians(), el.to_radians())
+    }
+
+    #[test]
+    fn interpolated_mask() {
+        let mask = SkyMask::from_points(&[(90.0, 30.0), (0.0, 10.0), (-90.0, 20.0)]).unwrap();
+        assert_eq!(mask.points(), &[(0.0, 10.0), (90.0, 30.0), (270.0, 20.0)]);
+        assert_float_eq!(mask.elevation_at(0.0), 10.0, abs <= 1e-12);
+        assert_float_eq!(mask.elevation_at(45.0), 20.0, abs <= 1e-12);
+        assert_float_eq!(mask.elevation_at(180.0), 25.0, abs <= 1e-12);
+        assert_float_eq!(mask.elevation_at(315.0), 15.0, abs <= 1e-12);
+        assert_float_eq!(mask.elevation_at(-45.0), 15.0, abs <= 1e-12);
+        assert_float_eq!(mask.elevation_at(360.0), 10.0, abs <= 1e-12);
+
+        assert!(mask.is_visible(&azel(90.0, 31.0)));
+        assert!(!mask.is_visible(&azel(90.0, 29.0)));
+        assert!(mask.is_visible(&azel(0.0, 11.0)));
+
+        let constant = SkyMask::constant(15.0);
+        assert_float_eq!(constant.elevation_at(123.0), 15.0, abs <= 1e-12);
+        assert!(!constant.is_visible(&azel(200.0, 14.0)));
+        let single = SkyMask::from_points(&[(100.0, 5.0)]).unwrap();
+        assert_float_eq!(single.elevation_at(300.0), 5.0, abs <= 1e-12);
+    }
+
+    #[test]
+    fn invalid_masks() {
+        assert!(SkyMask::from_points(&[]).is_err());
+        assert!(SkyMask::from_points(&[(0.0, f64::NAN)]).is_err());
+        assert!(SkyMask::from_points(&[(0.0, 10.0), (360.0, 20.0)]).is_err());
+        assert!(SkyPlotBins::new(7.0, 10.0).is_err());
+        assert!(SkyPlotBins::new(30.0, 0.0).is_err());
+        assert!(SkyPlotBins::new(30.0, 100.0).is_err());
+    }
+
+    #[test]
+    fn visible_from_receiver() {
+        let receiver = LLHDegrees::new(0.0, 0.0, 0.0).to_ecef();
+        // Up is along X at the equator and prime meridian
+        let overhead = ECEF::new(26_000_000.0, 0.0, 0.0);
+        let below = ECEF::new(-26_000_000.0, 0.0, 0.0);
+        let mask = SkyMask::constant(10.0);
+        assert!(mask.is_visible_from(&receiver, &overhead));
+        assert!(!mask.is_visible_from(&receiver, &below));
+    }
+
+    #[test]
+    fn sky_plot_cells() {
+        let bins = SkyPlotBins::new(30.0, 15.0).unwrap();
+        assert_eq!(bins.azimuth_cells(), 12);
+        assert_eq!(bins.elevation_cells(), 6);
+
+        let cell = bins.cell(&azel(100.0, 20.0)).unwrap();
+        assert_eq!(cell.azimuth_index(), 3);
+        assert_eq!(cell.elevation_index(), 1);
+        let center = bins.center(&cell);
+        assert_float_eq!(center.az.to_degrees(), 105.0, abs <= 1e-9);
+        assert_float_eq!(center.el.to_degrees(), 22.5, abs <= 1e-9);
+
+        let zenith = bins.cell(&azel(0.0, 90.0)).unwrap();
+        assert_eq!(zenith.elevation_index(), 5);
+        let wrapped = bins.cell(&azel(-10.0, 0.0)).unwrap();
+        assert_eq!(wrapped.azimuth_index(), 11);
+        assert_eq!(bins.cell(&azel(0.0, -1.0)), None);
+
+        let gps = |sat| GnssSignal::new(sat, Code::GpsL1ca).unwrap();
+        let cells = bins.bin(vec![
+            (gps(1), azel(100.0, 20.0)),
+            (gps(2), azel(110.0, 25.0)),
+            (gps(3), azel(200.0, 60.0)),
+            (gps(4), azel(300.0, -5.0)),
+        ]);
+        assert_eq!(cells.len(), 2);
+        assert_eq!(cells[&cell], vec![gps(1), gps(2)]);
+        assert_eq!(cells.values().map(Vec::len).sum::<usize>(), 3);
+    }
+}
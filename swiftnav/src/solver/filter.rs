@@ -0,0 +1,1602 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Filter based PVT estimation
+//!
+//! Unlike the [single epoch solver](crate::solver::calc_pvt) a filter carries
+//! its state from one epoch to the next. This makes it possible to apply
+//! knowledge about how the receiver moves, which helps to limit drift when
+//! GNSS conditions are poor.
+//!
+//! [`KalmanPvt`] is an extended Kalman filter which estimates the receiver
+//! position, velocity and clock from pseudorange and doppler measurements.
+//! The receiver clock is estimated against the time of a reference
+//! constellation, with the offset of each other constellation, the
+//! inter-system bias, estimated as an additional state.
+//!
+//! Two optional vehicle motion constraints are provided:
+//!  * Zero velocity updates (ZUPT) - when the receiver is detected as being
+//!    stationary the velocity is constrained to zero
+//!  * Non-holonomic constraints (NHC) - a land vehicle does not move sideways
+//!    or vertically relative to its direction of travel, so the lateral and
+//!    vertical velocity components are constrained to zero
+//!
+//! Each constraint is expressed as a [`VelocityConstraint`], a scalar
+//! pseudo-measurement of the ECEF velocity which can be applied as a regular
+//! measurement update. [`KalmanPvt`] applies them automatically when they are
+//! enabled in its settings.
+//!
+//! The filter can also estimate the zenith tropospheric delay left over after
+//! the measurements have been corrected with a tropospheric model, see
+//! [`ZtdSettings`]. Over long sessions this absorbs the error of the model,
+//! which would otherwise bias the estimated height.
+
+use crate::coords::{ECEF, NED};
+use crate::navmeas::NavigationMeasurement;
+use crate::signal::Constellation;
+use crate::solver::hint::CoarseHint;
+use crate::solver::linalg::Matrix;
+use crate::solver::protection::{ProtectionLevelSettings, ProtectionLevels};
+use crate::solver::wls::{reference_constellation, solve_wls, Weighting};
+use crate::time::GpsTime;
+use crate::troposphere::MappingFunction;
+use crate::SPEED_OF_LIGHT;
+use std::error::Error;
+use std::fmt;
+
+/// Settings for zero velocity updates
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ZuptSettings {
+    speed_threshold: f64,
+    min_stationary_epochs: u32,
+    velocity_sigma: f64,
+}
+
+impl ZuptSettings {
+    /// Creates a default set of ZUPT settings
+    ///
+    /// Note: The default settings consist of
+    ///  * A speed threshold of 0.05 m/s
+    ///  * Requiring 3 consecutive epochs below the threshold
+    ///  * A velocity standard deviation of 0.01 m/s
+    pub fn new() -> ZuptSettings {
+        ZuptSettings {
+            speed_threshold: 0.05,
+            min_stationary_epochs: 3,
+            velocity_sigma: 0.01,
+        }
+    }
+
+    /// Sets the speed, in meters per second, below which the receiver may be
+    /// considered stationary
+    pub fn set_speed_threshold(self, speed_threshold: f64) -> ZuptSettings {
+        ZuptSettings {
+            speed_threshold,
+            ..self
+        }
+    }
+
+    /// Sets the number of consecutive epochs the speed must stay below the
+    /// threshold before the receiver is considered stationary
+    pub fn set_min_stationary_epochs(self, min_stationary_epochs: u32) -> ZuptSettings {
+        ZuptSettings {
+            min_stationary_epochs,
+            ..self
+        }
+    }
+
+    /// Sets the standard deviation, in meters per second, of the zero velocity
+    /// pseudo-measurements
+    pub fn set_velocity_sigma(self, velocity_sigma: f64) -> ZuptSettings {
+        ZuptSettings {
+            velocity_sigma,
+            ..self
+        }
+    }
+
+    pub fn speed_threshold(&self) -> f64 {
+        self.speed_threshold
+    }
+
+    pub fn min_stationary_epochs(&self) -> u32 {
+        self.min_stationary_epochs
+    }
+
+    pub fn velocity_sigma(&self) -> f64 {
+        self.velocity_sigma
+    }
+}
+
+impl Default for ZuptSettings {
+    fn default() -> ZuptSettings {
+        ZuptSettings::new()
+    }
+}
+
+/// Settings for non-holonomic constraints
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NhcSettings {
+    lateral_sigma: f64,
+    vertical_sigma: f64,
+    min_speed: f64,
+}
+
+impl NhcSettings {
+    /// Creates a default set of NHC settings
+    ///
+    /// Note: The default settings consist of
+    ///  * A lateral velocity standard deviation of 0.1 m/s
+    ///  * A vertical velocity standard deviation of 0.1 m/s
+    ///  * A minimum horizontal speed of 1 m/s for the heading to be updated
+    pub fn new() -> NhcSettings {
+        NhcSettings {
+            lateral_sigma: 0.1,
+            vertical_sigma: 0.1,
+            min_speed: 1.0,
+        }
+    }
+
+    /// Sets the standard deviation, in meters per second, of the lateral
+    /// velocity pseudo-measurement
+    pub fn set_lateral_sigma(self, lateral_sigma: f64) -> NhcSettings {
+        NhcSettings {
+            lateral_sigma,
+            ..self
+        }
+    }
+
+    /// Sets the standard deviation, in meters per second, of the vertical
+    /// velocity pseudo-measurement
+    pub fn set_vertical_sigma(self, vertical_sigma: f64) -> NhcSettings {
+        NhcSettings {
+            vertical_sigma,
+            ..self
+        }
+    }
+
+    /// Sets the horizontal speed, in meters per second, above which the
+    /// direction of travel is considered to be observable
+    pub fn set_min_speed(self, min_speed: f64) -> NhcSettings {
+        NhcSettings { min_speed, ..self }
+    }
+
+    pub fn lateral_sigma(&self) -> f64 {
+        self.lateral_sigma
+    }
+
+    pub fn vertical_sigma(&self) -> f64 {
+        self.vertical_sigma
+    }
+
+    pub fn min_speed(&self) -> f64 {
+        self.min_speed
+    }
+}
+
+impl Default for NhcSettings {
+    fn default() -> NhcSettings {
+        NhcSettings::new()
+    }
+}
+
+/// Selects which motion constraints are applied by the filter
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConstraintSettings {
+    zupt: Option<ZuptSettings>,
+    nhc: Option<NhcSettings>,
+}
+
+impl ConstraintSettings {
+    /// Creates a set of settings with all constraints disabled
+    pub fn new() -> ConstraintSettings {
+        ConstraintSettings {
+            zupt: None,
+            nhc: None,
+        }
+    }
+
+    /// Enables zero velocity updates
+    pub fn enable_zupt(self, settings: ZuptSettings) -> ConstraintSettings {
+        ConstraintSettings {
+            zupt: Some(settings),
+            nhc: self.nhc,
+        }
+    }
+
+    /// Disables zero velocity updates
+    pub fn disable_zupt(self) -> ConstraintSettings {
+        ConstraintSettings {
+            zupt: None,
+            nhc: self.nhc,
+        }
+    }
+
+    /// Enables non-holonomic constraints
+    ///
+    /// Note: these constraints are only valid for wheeled land vehicles
+    pub fn enable_nhc(self, settings: NhcSettings) -> ConstraintSettings {
+        ConstraintSettings {
+            zupt: self.zupt,
+            nhc: Some(settings),
+        }
+    }
+
+    /// Disables non-holonomic constraints
+    pub fn disable_nhc(self) -> ConstraintSettings {
+        ConstraintSettings {
+            zupt: self.zupt,
+            nhc: None,
+        }
+    }
+
+    pub fn zupt(&self) -> Option<&ZuptSettings> {
+        self.zupt.as_ref()
+    }
+
+    pub fn nhc(&self) -> Option<&NhcSettings> {
+        self.nhc.as_ref()
+    }
+}
+
+/// A scalar pseudo-measurement of the ECEF velocity
+///
+/// The constraint states that `h · v = value`, with the given variance, where
+/// `v` is the ECEF velocity.
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct VelocityConstraint {
+    /// Sensitivity of the constraint to the ECEF velocity
+    pub h: ECEF,
+    /// Expected value of the constraint
+    pub value: f64,
+    /// Variance of the constraint, in (m/s)^2
+    pub variance: f64,
+}
+
+impl VelocityConstraint {
+    /// Gets the residual of the constraint for a given ECEF velocity
+    pub fn residual(&self, velocity: &ECEF) -> f64 {
+        self.value - self.h.dot(velocity)
+    }
+}
+
+/// Keeps track of the state needed to generate motion constraints
+#[derive(Debug, Clone, PartialEq)]
+pub struct MotionConstraints {
+    settings: ConstraintSettings,
+    stationary_epochs: u32,
+    heading: Option<f64>,
+}
+
+impl MotionConstraints {
+    pub fn new(settings: ConstraintSettings) -> MotionConstraints {
+        MotionConstraints {
+            settings,
+            stationary_epochs: 0,
+            heading: None,
+        }
+    }
+
+    pub fn settings(&self) -> &ConstraintSettings {
+        &self.settings
+    }
+
+    /// Checks if the receiver is currently considered stationary
+    pub fn is_stationary(&self) -> bool {
+        match self.settings.zupt {
+            Some(zupt) => self.stationary_epochs >= zupt.min_stationary_epochs,
+            None => false,
+        }
+    }
+
+    /// Gets the last observed direction of travel, in radians clockwise from
+    /// north
+    pub fn heading(&self) -> Option<f64> {
+        self.heading
+    }
+
+    /// Forgets any accumulated motion state
+    pub fn reset(&mut self) {
+        self.stationary_epochs = 0;
+        self.heading = None;
+    }
+
+    /// Updates the motion state with the latest position and velocity
+    /// estimates, and returns the constraints to apply at this epoch
+    ///
+    /// When the receiver is stationary the zero velocity update takes
+    /// precedence over the non-holonomic constraints.
+    pub fn update(&mut self, position: &ECEF, velocity: &ECEF) -> Vec<VelocityConstraint> {
+        if let Some(zupt) = self.settings.zupt {
+            let speed = velocity.norm();
+            if speed < zupt.speed_threshold {
+                self.stationary_epochs = self.stationary_epochs.saturating_add(1);
+            } else {
+                self.stationary_epochs = 0;
+            }
+
+            if self.is_stationary() {
+                let variance = zupt.velocity_sigma * zupt.velocity_sigma;
+                return [
+                    ECEF::new(1.0, 0.0, 0.0),
+                    ECEF::new(0.0, 1.0, 0.0),
+                    ECEF::new(0.0, 0.0, 1.0),
+                ]
+                .iter()
+                .map(|h| VelocityConstraint {
+                    h: *h,
+                    value: 0.0,
+                    variance,
+                })
+                .collect();
+            }
+        }
+
+        let nhc = match self.settings.nhc {
+            Some(nhc) => nhc,
+            None => return Vec::new(),
+        };
+
+        let vel_ned = velocity.ned_vector_at(position);
+        if vel_ned.n().hypot(vel_ned.e()) >= nhc.min_speed {
+            self.heading = Some(vel_ned.e().atan2(vel_ned.n()));
+        }
+
+        let heading = match self.heading {
+            Some(heading) => heading,
+            None => return Vec::new(),
+        };
+
+        let lateral = NED::new(-heading.sin(), heading.cos(), 0.0).ecef_vector_at(position);
+        let vertical = NED::new(0.0, 0.0, 1.0).ecef_vector_at(position);
+
+        vec![
+            VelocityConstraint {
+                h: lateral,
+                value: 0.0,
+                variance: nhc.lateral_sigma * nhc.lateral_sigma,
+            },
+            VelocityConstraint {
+                h: vertical,
+                value: 0.0,
+                variance: nhc.vertical_sigma * nhc.vertical_sigma,
+            },
+        ]
+    }
+}
+
+/// Rotation rate of the Earth, in radians per second
+const EARTH_ROTATION_RATE: f64 = 7.292_115_146_7e-5;
+
+/// Number of states besides the inter-system biases: ECEF position, ECEF
+/// velocity, clock bias and clock drift
+const STATE_COUNT: usize = 8;
+const POSITION: usize = 0;
+const VELOCITY: usize = 3;
+const CLOCK_BIAS: usize = 6;
+const CLOCK_DRIFT: usize = 7;
+/// Index of the zenith tropospheric delay state, when it is estimated
+const ZTD: usize = STATE_COUNT;
+/// Standard deviation of an inter-system bias when it is first estimated
+const INITIAL_ISB_SIGMA: f64 = 100.0;
+/// Lowest elevation the tropospheric mapping is evaluated at, in radians
+const MIN_MAPPING_ELEVATION: f64 = 0.05;
+
+/// Settings for the estimation of the residual zenith tropospheric delay
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ZtdSettings {
+    mapping: MappingFunction,
+    initial_sigma: f64,
+    psd: f64,
+}
+
+impl ZtdSettings {
+    /// Creates a default set of zenith delay settings
+    ///
+    /// Note: The default settings consist of
+    ///  * The wet Niell mapping function
+    ///  * An initial standard deviation of 0.2 m
+    ///  * A random walk noise density of 1e-8 m²/s, or about 6 mm/√h
+    pub fn new() -> ZtdSettings {
+        ZtdSettings {
+            mapping: MappingFunction::Niell,
+            initial_sigma: 0.2,
+            psd: 1e-8,
+        }
+    }
+
+    /// Sets the function mapping the zenith delay to the satellite elevation
+    ///
+    /// The wet factor of the mapping function is used, as most of the error
+    /// of a model is in the wet delay. [`MappingFunction::Model`] maps the
+    /// delay with the inverse of the sine of the elevation.
+    pub fn set_mapping_function(self, mapping: MappingFunction) -> ZtdSettings {
+        ZtdSettings { mapping, ..self }
+    }
+
+    /// Sets the standard deviation of the zenith delay when the filter is
+    /// initialized, in meters
+    pub fn set_initial_sigma(self, initial_sigma: f64) -> ZtdSettings {
+        ZtdSettings {
+            initial_sigma,
+            ..self
+        }
+    }
+
+    /// Sets the power spectral density of the zenith delay, in m²/s
+    pub fn set_psd(self, psd: f64) -> ZtdSettings {
+        ZtdSettings { psd, ..self }
+    }
+
+    pub fn mapping_function(&self) -> MappingFunction {
+        self.mapping
+    }
+
+    pub fn initial_sigma(&self) -> f64 {
+        self.initial_sigma
+    }
+
+    pub fn psd(&self) -> f64 {
+        self.psd
+    }
+
+    /// Gets the mapping factor of a satellite at elevation `el`, in radians,
+    /// seen from latitude `lat`, in radians, and height `h`, in meters
+    fn factor(&self, doy: f64, lat: f64, h: f64, el: f64) -> f64 {
+        let el = el.max(MIN_MAPPING_ELEVATION);
+        self.mapping
+            .factors(doy, lat, h, el)
+            .map_or(1.0 / el.sin(), |factors| factors.wet)
+    }
+}
+
+impl Default for ZtdSettings {
+    fn default() -> ZtdSettings {
+        ZtdSettings::new()
+    }
+}
+
+/// Settings for the Kalman filter PVT engine
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KalmanSettings {
+    acceleration_psd: f64,
+    clock_bias_psd: f64,
+    clock_drift_psd: f64,
+    inter_system_bias_psd: f64,
+    code_sigma: f64,
+    doppler_sigma: f64,
+    initial_position_sigma: f64,
+    initial_velocity_sigma: f64,
+    max_gap: f64,
+    constraints: ConstraintSettings,
+    #[cfg_attr(feature = "serde", serde(default))]
+    ztd: Option<ZtdSettings>,
+}
+
+impl KalmanSettings {
+    /// Creates a default set of filter settings
+    ///
+    /// Note: The default settings consist of
+    ///  * An acceleration noise density of 1 m²/s³, suitable for a land vehicle
+    ///  * Clock noise densities of 0.01 m²/s (bias) and 0.04 m²/s³ (drift),
+    ///    typical of a TCXO
+    ///  * An inter-system bias noise density of 1e-4 m²/s
+    ///  * Pseudorange and doppler standard deviations of 2 m and 0.1 m/s
+    ///  * Re-initialization after a 10 second gap in measurements
+    ///  * No motion constraints
+    ///  * No zenith tropospheric delay estimation
+    pub fn new() -> KalmanSettings {
+        KalmanSettings {
+            acceleration_psd: 1.0,
+            clock_bias_psd: 0.01,
+            clock_drift_psd: 0.04,
+            inter_system_bias_psd: 1e-4,
+            code_sigma: 2.0,
+            doppler_sigma: 0.1,
+            initial_position_sigma: 100.0,
+            initial_velocity_sigma: 50.0,
+            max_gap: 10.0,
+            constraints: ConstraintSettings::new(),
+            ztd: None,
+        }
+    }
+
+    /// Sets the power spectral density of the receiver acceleration, in m²/s³
+    pub fn set_acceleration_psd(self, acceleration_psd: f64) -> KalmanSettings {
+        KalmanSettings {
+            acceleration_psd,
+            ..self
+        }
+    }
+
+    /// Sets the power spectral densities of the receiver clock bias, in m²/s,
+    /// and of the clock drift, in m²/s³
+    pub fn set_clock_psd(self, clock_bias_psd: f64, clock_drift_psd: f64) -> KalmanSettings {
+        KalmanSettings {
+            clock_bias_psd,
+            clock_drift_psd,
+            ..self
+        }
+    }
+
+    /// Sets the power spectral density of the inter-system biases, in m²/s
+    pub fn set_inter_system_bias_psd(self, inter_system_bias_psd: f64) -> KalmanSettings {
+        KalmanSettings {
+            inter_system_bias_psd,
+            ..self
+        }
+    }
+
+    /// Sets the pseudorange standard deviation, in meters
+    pub fn set_code_sigma(self, code_sigma: f64) -> KalmanSettings {
+        KalmanSettings { code_sigma, ..self }
+    }
+
+    /// Sets the doppler standard deviation, in meters per second
+    pub fn set_doppler_sigma(self, doppler_sigma: f64) -> KalmanSettings {
+        KalmanSettings {
+            doppler_sigma,
+            ..self
+        }
+    }
+
+    /// Sets the largest gap between epochs, in seconds, before the filter is
+    /// re-initialized
+    pub fn set_max_gap(self, max_gap: f64) -> KalmanSettings {
+        KalmanSettings { max_gap, ..self }
+    }
+
+    /// Sets the motion constraints to apply after each measurement update
+    pub fn set_constraints(self, constraints: ConstraintSettings) -> KalmanSettings {
+        KalmanSettings {
+            constraints,
+            ..self
+        }
+    }
+
+    /// Enables the estimation of the residual zenith tropospheric delay
+    pub fn enable_ztd(self, settings: ZtdSettings) -> KalmanSettings {
+        KalmanSettings {
+            ztd: Some(settings),
+            ..self
+        }
+    }
+
+    /// Disables the estimation of the residual zenith tropospheric delay
+    pub fn disable_ztd(self) -> KalmanSettings {
+        KalmanSettings { ztd: None, ..self }
+    }
+
+    pub fn acceleration_psd(&self) -> f64 {
+        self.acceleration_psd
+    }
+
+    pub fn clock_bias_psd(&self) -> f64 {
+        self.clock_bias_psd
+    }
+
+    pub fn clock_drift_psd(&self) -> f64 {
+        self.clock_drift_psd
+    }
+
+    pub fn inter_system_bias_psd(&self) -> f64 {
+        self.inter_system_bias_psd
+    }
+
+    pub fn code_sigma(&self) -> f64 {
+        self.code_sigma
+    }
+
+    pub fn doppler_sigma(&self) -> f64 {
+        self.doppler_sigma
+    }
+
+    pub fn max_gap(&self) -> f64 {
+        self.max_gap
+    }
+
+    pub fn constraints(&self) -> &ConstraintSettings {
+        &self.constraints
+    }
+
+    pub fn ztd(&self) -> Option<&ZtdSettings> {
+        self.ztd.as_ref()
+    }
+}
+
+impl Default for KalmanSettings {
+    fn default() -> KalmanSettings {
+        KalmanSettings::new()
+    }
+}
+
+/// Reasons a filter update can fail
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum KalmanError {
+    /// Fewer than four valid pseudoranges, or none when the filter has been
+    /// seeded, are available to initialize the filter
+    NotEnoughMeasurements,
+    /// The initial position could not be computed
+    InitializationFailed,
+    /// The measurements are older than the current filter state
+    TimeReversed,
+}
+
+impl fmt::Display for KalmanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KalmanError::NotEnoughMeasurements => {
+                write!(f, "Not enough measurements to initialize the filter")
+            }
+            KalmanError::InitializationFailed => write!(f, "Filter initialization failed"),
+            KalmanError::TimeReversed => write!(f, "Measurements are older than the filter"),
+        }
+    }
+}
+
+impl Error for KalmanError {}
+
+/// The filter estimate at a particular epoch
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub struct KalmanSolution {
+    time: GpsTime,
+    position: ECEF,
+    velocity: ECEF,
+    clock_bias: f64,
+    clock_drift: f64,
+    reference: Constellation,
+    inter_system_biases: Vec<(Constellation, f64)>,
+    position_covariance: [[f64; 3]; 3],
+    velocity_covariance: [[f64; 3]; 3],
+    zenith_delay: Option<(f64, f64)>,
+    measurements_used: usize,
+    constraints_applied: usize,
+}
+
+impl KalmanSolution {
+    pub fn time(&self) -> GpsTime {
+        self.time
+    }
+
+    pub fn position(&self) -> ECEF {
+        self.position
+    }
+
+    pub fn velocity(&self) -> ECEF {
+        self.velocity
+    }
+
+    /// Gets the receiver clock bias against the time of the
+    /// [reference constellation](KalmanSolution::reference_constellation), in
+    /// seconds
+    pub fn clock_bias(&self) -> f64 {
+        self.clock_bias
+    }
+
+    /// Gets the constellation the receiver clock bias refers to
+    ///
+    /// This is GPS when GPS measurements are present when the filter is
+    /// initialized, or otherwise the first constellation present in the order
+    /// of [`Constellation`].
+    pub fn reference_constellation(&self) -> Constellation {
+        self.reference
+    }
+
+    /// Gets the inter-system bias of each other constellation seen since
+    /// initialization, in seconds
+    ///
+    /// The receiver clock bias of a constellation is the reference clock bias
+    /// plus its inter-system bias.
+    pub fn inter_system_biases(&self) -> &[(Constellation, f64)] {
+        &self.inter_system_biases
+    }
+
+    /// Gets the receiver clock bias against the time of a constellation, in
+    /// seconds
+    ///
+    /// Returns `None` if the constellation hasn't been seen since the filter
+    /// was initialized
+    pub fn constellation_clock_bias(&self, constellation: Constellation) -> Option<f64> {
+        if constellation == self.reference {
+            return Some(self.clock_bias);
+        }
+        self.inter_system_biases
+            .iter()
+            .find(|(c, _)| *c == constellation)
+            .map(|(_, isb)| self.clock_bias + isb)
+    }
+
+    /// Gets the receiver clock drift, in seconds per second
+    pub fn clock_drift(&self) -> f64 {
+        self.clock_drift
+    }
+
+    /// Gets the ECEF position covariance, in meters squared
+    pub fn position_covariance(&self) -> &[[f64; 3]; 3] {
+        &self.position_covariance
+    }
+
+    /// Computes the protection levels of the position from its covariance
+    pub fn protection_levels(&self, settings: &ProtectionLevelSettings) -> ProtectionLevels {
+        ProtectionLevels::from_covariance(&self.position, &self.position_covariance, settings)
+    }
+
+    /// Gets the ECEF velocity covariance, in (m/s)²
+    pub fn velocity_covariance(&self) -> &[[f64; 3]; 3] {
+        &self.velocity_covariance
+    }
+
+    /// Gets the estimated residual zenith tropospheric delay, in meters
+    ///
+    /// This is the zenith delay left after the tropospheric correction of the
+    /// measurements, or the whole zenith delay if they weren't corrected.
+    /// Returns `None` if the delay isn't estimated.
+    pub fn zenith_delay(&self) -> Option<f64> {
+        self.zenith_delay.map(|(delay, _)| delay)
+    }
+
+    /// Gets the variance of the residual zenith tropospheric delay, in meters
+    /// squared
+    pub fn zenith_delay_variance(&self) -> Option<f64> {
+        self.zenith_delay.map(|(_, variance)| variance)
+    }
+
+    /// Gets the number of pseudorange and doppler measurements used in the
+    /// last update
+    pub fn measurements_used(&self) -> usize {
+        self.measurements_used
+    }
+
+    /// Gets the number of motion constraints applied in the last update
+    pub fn constraints_applied(&self) -> usize {
+        self.constraints_applied
+    }
+}
+
+/// A stateful PVT engine based on an extended Kalman filter
+///
+/// The filter estimates the receiver position, velocity, clock bias and clock
+/// drift from the same [`NavigationMeasurement`]s used by the
+/// [single epoch solver](crate::solver::calc_pvt). The satellite states must
+/// be set on the measurements, and the pseudoranges are expected to be
+/// corrected for atmospheric delays already.
+///
+/// The receiver motion is modelled as a constant velocity driven by white
+/// noise acceleration, and the clock as a bias and drift driven by white noise.
+/// An inter-system bias state is added for each constellation other than the
+/// reference one as it is first seen, modelled as a random walk. The residual
+/// zenith tropospheric delay is also modelled as a random walk when it is
+/// estimated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KalmanPvt {
+    settings: KalmanSettings,
+    time: Option<GpsTime>,
+    x: Vec<f64>,
+    p: Matrix,
+    reference: Constellation,
+    /// Constellation of each inter-system bias state, in state order
+    isb_states: Vec<Constellation>,
+    constraints: MotionConstraints,
+    hint: Option<CoarseHint>,
+}
+
+impl KalmanPvt {
+    pub fn new(settings: KalmanSettings) -> KalmanPvt {
+        let n = STATE_COUNT + usize::from(settings.ztd.is_some());
+        KalmanPvt {
+            settings,
+            time: None,
+            x: vec![0.0; n],
+            p: Matrix::zeros(n, n),
+            reference: Constellation::Gps,
+            isb_states: Vec::new(),
+            constraints: MotionConstraints::new(settings.constraints),
+            hint: None,
+        }
+    }
+
+    pub fn settings(&self) -> &KalmanSettings {
+        &self.settings
+    }
+
+    /// Checks if the filter has been initialized
+    pub fn is_initialized(&self) -> bool {
+        self.time.is_some()
+    }
+
+    /// Provides a coarse position to initialize the filter from
+    ///
+    /// The hint is used the next time the filter initializes, in place of a
+    /// least squares position, with the hint covariance as the initial
+    /// position uncertainty. A seeded filter can initialize from a single
+    /// pseudorange, which only needs to determine the clock bias. The hint
+    /// time is not used, the filter takes its time from the measurements.
+    pub fn seed(&mut self, hint: &CoarseHint) {
+        self.hint = Some(*hint);
+    }
+
+    /// Gets the hint which will be used for the next initialization, if any
+    pub fn pending_hint(&self) -> Option<&CoarseHint> {
+        self.hint.as_ref()
+    }
+
+    /// Discards the filter state, the next update will re-initialize it
+    pub fn reset(&mut self) {
+        self.time = None;
+        self.constraints.reset();
+    }
+
+    /// Processes a new epoch of measurements
+    ///
+    /// Measurements without a valid pseudorange are ignored, dopplers are used
+    /// when they are valid. The filter is initialized from the first epoch
+    /// with at least four pseudoranges, or one if it has been
+    /// [seeded](KalmanPvt::seed), and re-initialized when the gap since
+    /// the previous epoch is larger than the configured maximum.
+    pub fn update(
+        &mut self,
+        t: GpsTime,
+        measurements: &[NavigationMeasurement],
+    ) -> Result<KalmanSolution, KalmanError> {
+        let measurements: Vec<&NavigationMeasurement> = measurements
+            .iter()
+            .filter(|nm| nm.pseudorange().is_some())
+            .collect();
+
+        match self.time {
+            Some(previous) => {
+                let dt = t.diff(&previous);
+                if dt < 0.0 {
+                    return Err(KalmanError::TimeReversed);
+                } else if dt > self.settings.max_gap {
+                    self.reset();
+                    self.initialize(&measurements)?;
+                } else {
+                    self.predict(dt);
+                }
+            }
+            None => self.initialize(&measurements)?,
+        }
+        self.time = Some(t);
+
+        let mut measurements_used = 0;
+        for nm in &measurements {
+            if let Some(pseudorange) = nm.pseudorange() {
+                self.pseudorange_update(nm, pseudorange);
+                measurements_used += 1;
+            }
+            if let Some(doppler) = nm.measured_doppler() {
+                self.doppler_update(nm, doppler);
+                measurements_used += 1;
+            }
+        }
+
+        let position = self.position();
+        let velocity = self.velocity();
+        let constraints = self.constraints.update(&position, &velocity);
+        for constraint in &constraints {
+            let mut h = vec![0.0; self.x.len()];
+            h[VELOCITY..VELOCITY + 3].copy_from_slice(constraint.h.as_array_ref());
+            let residual = constraint.residual(&self.velocity());
+            self.scalar_update(&h, residual, constraint.variance);
+        }
+
+        let mut solution = self.solution().expect("Filter is initialized");
+        solution.measurements_used = measurements_used;
+        solution.constraints_applied = constraints.len();
+        Ok(solution)
+    }
+
+    /// Gets the current filter estimate
+    ///
+    /// Returns `None` if the filter hasn't been initialized
+    pub fn solution(&self) -> Option<KalmanSolution> {
+        let block = |offset: usize| {
+            let mut cov = [[0.0; 3]; 3];
+            for (i, row) in cov.iter_mut().enumerate() {
+                for (j, value) in row.iter_mut().enumerate() {
+                    *value = self.p[(offset + i, offset + j)];
+                }
+            }
+            cov
+        };
+
+        Some(KalmanSolution {
+            time: self.time?,
+            position: self.position(),
+            velocity: self.velocity(),
+            clock_bias: self.x[CLOCK_BIAS] / SPEED_OF_LIGHT,
+            clock_drift: self.x[CLOCK_DRIFT] / SPEED_OF_LIGHT,
+            reference: self.reference,
+            inter_system_biases: self
+                .isb_states
+                .iter()
+                .enumerate()
+                .map(|(k, c)| (*c, self.x[self.isb_start() + k] / SPEED_OF_LIGHT))
+                .collect(),
+            position_covariance: block(POSITION),
+            velocity_covariance: block(VELOCITY),
+            zenith_delay: self.settings.ztd.map(|_| (self.x[ZTD], self.p[(ZTD, ZTD)])),
+            measurements_used: 0,
+            constraints_applied: 0,
+        })
+    }
+
+    fn position(&self) -> ECEF {
+        ECEF::new(self.x[POSITION], self.x[POSITION + 1], self.x[POSITION + 2])
+    }
+
+    fn velocity(&self) -> ECEF {
+        ECEF::new(self.x[VELOCITY], self.x[VELOCITY + 1], self.x[VELOCITY + 2])
+    }
+
+    /// Sets the initial state from the seeded hint or a least squares
+    /// position, with a loose prior so the first measurement update isn't
+    /// double counted
+    fn initialize(&mut self, measurements: &[&NavigationMeasurement]) -> Result<(), KalmanError> {
+        if let Some(hint) = self.hint {
+            return self.initialize_from_hint(&hint, measurements);
+        }
+        if measurements.len() < 4 {
+            return Err(KalmanError::NotEnoughMeasurements);
+        }
+
+        // Start with a clock for each constellation when there are enough
+        // measurements, otherwise all of them share a single clock
+        let owned: Vec<NavigationMeasurement> =
+            measurements.iter().map(|nm| (*nm).clone()).collect();
+        let (position, clock_biases) = match solve_wls(&owned, &Weighting::Uniform) {
+            Ok(solution) => (
+                solution.position(),
+                solution
+                    .clock_biases()
+                    .iter()
+                    .map(|(c, bias)| (*c, bias * SPEED_OF_LIGHT))
+                    .collect(),
+            ),
+            Err(_) => {
+                let (position, clock_bias) = least_squares_position(measurements)
+                    .ok_or(KalmanError::InitializationFailed)?;
+                (
+                    position,
+                    constellations(measurements)
+                        .map(|c| (c, clock_bias))
+                        .collect::<Vec<_>>(),
+                )
+            }
+        };
+        let reference = reference_constellation(clock_biases.iter().map(|(c, _)| *c))
+            .ok_or(KalmanError::InitializationFailed)?;
+        let clock_bias = clock_biases
+            .iter()
+            .find(|(c, _)| *c == reference)
+            .map(|(_, bias)| *bias)
+            .ok_or(KalmanError::InitializationFailed)?;
+
+        let position_var = self.settings.initial_position_sigma.powi(2);
+        let mut position_cov = [[0.0; 3]; 3];
+        for (i, row) in position_cov.iter_mut().enumerate() {
+            row[i] = position_var;
+        }
+        self.set_initial_state(
+            &position,
+            &position_cov,
+            reference,
+            clock_bias,
+            position_var,
+        );
+        for (constellation, bias) in clock_biases {
+            if constellation != reference {
+                self.add_isb_state(constellation, bias - clock_bias, position_var);
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts from the hint position, the clock bias of each constellation is
+    /// taken as the mean of its pseudorange residuals from there
+    fn initialize_from_hint(
+        &mut self,
+        hint: &CoarseHint,
+        measurements: &[&NavigationMeasurement],
+    ) -> Result<(), KalmanError> {
+        let reference = reference_constellation(constellations(measurements))
+            .ok_or(KalmanError::NotEnoughMeasurements)?;
+        let position = hint.position();
+        let mut clock_biases: Vec<(Constellation, f64)> = Vec::new();
+        for constellation in constellations(measurements) {
+            let mut residual_sum = 0.0;
+            let mut count = 0;
+            for nm in measurements
+                .iter()
+                .filter(|nm| nm.sid().to_constellation() == constellation)
+            {
+                let (range, _) = geometry(&nm.satellite_position(), &position);
+                let pseudorange = nm.pseudorange().ok_or(KalmanError::InitializationFailed)?;
+                residual_sum += pseudorange + SPEED_OF_LIGHT * nm.satellite_clock_error() - range;
+                count += 1;
+            }
+            clock_biases.push((constellation, residual_sum / count as f64));
+        }
+        let clock_bias = clock_biases
+            .iter()
+            .find(|(c, _)| *c == reference)
+            .map(|(_, bias)| *bias)
+            .ok_or(KalmanError::InitializationFailed)?;
+
+        // The position error maps directly into the clock bias estimate
+        let clock_var = self.settings.initial_position_sigma.powi(2)
+            + hint.horizontal_sigma().powi(2)
+            + hint.vertical_sigma().powi(2);
+        self.set_initial_state(
+            &position,
+            &hint.covariance(),
+            reference,
+            clock_bias,
+            clock_var,
+        );
+        for (constellation, bias) in clock_biases {
+            if constellation != reference {
+                self.add_isb_state(constellation, bias - clock_bias, clock_var);
+            }
+        }
+        self.hint = None;
+        Ok(())
+    }
+
+    fn set_initial_state(
+        &mut self,
+        position: &ECEF,
+        position_cov: &[[f64; 3]; 3],
+        reference: Constellation,
+        clock_bias: f64,
+        clock_var: f64,
+    ) {
+        self.reference = reference;
+        self.isb_states.clear();
+        let n = self.isb_start();
+        self.x = vec![0.0; n];
+        self.x[POSITION..POSITION + 3].copy_from_slice(position.as_array_ref());
+        self.x[CLOCK_BIAS] = clock_bias;
+
+        let velocity_var = self.settings.initial_velocity_sigma.powi(2);
+        self.p = Matrix::zeros(n, n);
+        for (i, row) in position_cov.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                self.p[(POSITION + i, POSITION + j)] = *value;
+            }
+            self.p[(VELOCITY + i, VELOCITY + i)] = velocity_var;
+        }
+        self.p[(CLOCK_BIAS, CLOCK_BIAS)] = clock_var;
+        // Allow for a drift of up to ~100 ppm
+        self.p[(CLOCK_DRIFT, CLOCK_DRIFT)] = (1e-4 * SPEED_OF_LIGHT).powi(2);
+        if let Some(ztd) = &self.settings.ztd {
+            self.p[(ZTD, ZTD)] = ztd.initial_sigma.powi(2);
+        }
+    }
+
+    /// Gets the index of the first inter-system bias state, after the zenith
+    /// delay state if there is one
+    fn isb_start(&self) -> usize {
+        STATE_COUNT + usize::from(self.settings.ztd.is_some())
+    }
+
+    /// Adds an inter-system bias state, uncorrelated with the other states
+    fn add_isb_state(&mut self, constellation: Constellation, bias: f64, variance: f64) {
+        let n = self.x.len();
+        let mut p = Matrix::zeros(n + 1, n + 1);
+        for i in 0..n {
+            for j in 0..n {
+                p[(i, j)] = self.p[(i, j)];
+            }
+        }
+        p[(n, n)] = variance;
+        self.p = p;
+        self.x.push(bias);
+        self.isb_states.push(constellation);
+    }
+
+    /// Gets the index of the inter-system bias state of a constellation, or
+    /// `None` for the reference constellation
+    fn isb_index(&self, constellation: Constellation) -> Option<usize> {
+        self.isb_states
+            .iter()
+            .position(|c| *c == constellation)
+            .map(|k| self.isb_start() + k)
+    }
+
+    fn predict(&mut self, dt: f64) {
+        let n = self.x.len();
+        let mut f = Matrix::identity(n);
+        for i in 0..3 {
+            f[(POSITION + i, VELOCITY + i)] = dt;
+        }
+        f[(CLOCK_BIAS, CLOCK_DRIFT)] = dt;
+
+        let (dt2, dt3) = (dt * dt, dt * dt * dt);
+        let qa = self.settings.acceleration_psd;
+        let mut q = Matrix::zeros(n, n);
+        for i in 0..3 {
+            q[(POSITION + i, POSITION + i)] = qa * dt3 / 3.0;
+            q[(POSITION + i, VELOCITY + i)] = qa * dt2 / 2.0;
+            q[(VELOCITY + i, POSITION + i)] = qa * dt2 / 2.0;
+            q[(VELOCITY + i, VELOCITY + i)] = qa * dt;
+        }
+        let (qb, qd) = (self.settings.clock_bias_psd, self.settings.clock_drift_psd);
+        q[(CLOCK_BIAS, CLOCK_BIAS)] = qb * dt + qd * dt3 / 3.0;
+        q[(CLOCK_BIAS, CLOCK_DRIFT)] = qd * dt2 / 2.0;
+        q[(CLOCK_DRIFT, CLOCK_BIAS)] = qd * dt2 / 2.0;
+        q[(CLOCK_DRIFT, CLOCK_DRIFT)] = qd * dt;
+        if let Some(ztd) = &self.settings.ztd {
+            q[(ZTD, ZTD)] = ztd.psd * dt;
+        }
+        for i in self.isb_start()..n {
+            q[(i, i)] = self.settings.inter_system_bias_psd * dt;
+        }
+
+        self.x = f.mul_vec(&self.x);
+        self.p = f.mul(&self.p).mul(&f.transpose()).add(&q);
+    }
+
+    fn pseudorange_update(&mut self, nm: &NavigationMeasurement, pseudorange: f64) {
+        let (range, los) = geometry(&nm.satellite_position(), &self.position());
+        let corrected = pseudorange + SPEED_OF_LIGHT * nm.satellite_clock_error();
+        let constellation = nm.sid().to_constellation();
+        if constellation != self.reference && self.isb_index(constellation).is_none() {
+            let bias = corrected - (range + self.x[CLOCK_BIAS]);
+            self.add_isb_state(constellation, bias, INITIAL_ISB_SIGMA.powi(2));
+        }
+        let isb = self.isb_index(constellation);
+        let mapping = self.ztd_mapping(&los);
+        let residual = corrected
+            - (range
+                + self.x[CLOCK_BIAS]
+                + isb.map_or(0.0, |i| self.x[i])
+                + mapping.map_or(0.0, |m| m * self.x[ZTD]));
+
+        let mut h = vec![0.0; self.x.len()];
+        for i in 0..3 {
+            h[POSITION + i] = -los[i];
+        }
+        h[CLOCK_BIAS] = 1.0;
+        if let Some(i) = isb {
+            h[i] = 1.0;
+        }
+        if let Some(m) = mapping {
+            h[ZTD] = m;
+        }
+        self.scalar_update(&h, residual, self.settings.code_sigma.powi(2));
+    }
+
+    /// Gets the mapping factor of the zenith delay for a satellite in the
+    /// direction `los`, or `None` if the delay isn't estimated
+    fn ztd_mapping(&self, los: &[f64; 3]) -> Option<f64> {
+        let ztd = self.settings.ztd.as_ref()?;
+        let llh = self.position().to_llh();
+        let (lat, lon) = (llh.latitude(), llh.longitude());
+        let up = [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()];
+        let el = (los[0] * up[0] + los[1] * up[1] + los[2] * up[2]).asin();
+        let doy = self
+            .time
+            .map_or(0.0, |t| t.to_utc_hardcoded().day_of_year() as f64);
+        Some(ztd.factor(doy, lat, llh.height(), el))
+    }
+
+    fn doppler_update(&mut self, nm: &NavigationMeasurement, doppler: f64) {
+        let (_, los) = geometry(&nm.satellite_position(), &self.position());
+        let wavelength = SPEED_OF_LIGHT / nm.sid().carrier_frequency();
+        let range_rate = -doppler * wavelength + SPEED_OF_LIGHT * nm.satellite_clock_error_rate();
+
+        let sat_vel = nm.satellite_velocity();
+        let relative = sat_vel - self.velocity();
+        let rel = relative.as_array_ref();
+        let predicted = los[0] * rel[0] + los[1] * rel[1] + los[2] * rel[2] + self.x[CLOCK_DRIFT];
+
+        let mut h = vec![0.0; self.x.len()];
+        for i in 0..3 {
+            h[VELOCITY + i] = -los[i];
+        }
+        h[CLOCK_DRIFT] = 1.0;
+        self.scalar_update(
+            &h,
+            range_rate - predicted,
+            self.settings.doppler_sigma.powi(2),
+        );
+    }
+
+    /// Applies a scalar measurement with sensitivity `h`
+    fn scalar_update(&mut self, h: &[f64], residual: f64, variance: f64) {
+        let ph = self.p.mul_vec(h);
+        let s = h.iter().zip(ph.iter()).map(|(a, b)| a * b).sum::<f64>() + variance;
+        for i in 0..self.x.len() {
+            self.x[i] += ph[i] / s * residual;
+            for j in 0..self.x.len() {
+                self.p[(i, j)] -= ph[i] * ph[j] / s;
+            }
+        }
+    }
+}
+
+/// Computes the range to a satellite, including the Earth rotation correction,
+/// and the unit vector from the receiver to the satellite
+pub(crate) fn geometry(satellite: &ECEF, receiver: &ECEF) -> (f64, [f64; 3]) {
+    let delta = satellite - receiver;
+    let d = delta.as_array_ref();
+    let distance = delta.norm();
+    let sagnac = EARTH_ROTATION_RATE / SPEED_OF_LIGHT
+        * (satellite.x() * receiver.y() - satellite.y() * receiver.x());
+    (
+        distance + sagnac,
+        [d[0] / distance, d[1] / distance, d[2] / distance],
+    )
+}
+
+/// Gets the constellations of the measurements, in order
+fn constellations(measurements: &[&NavigationMeasurement]) -> impl Iterator<Item = Constellation> {
+    let mut constellations: Vec<Constellation> = measurements
+        .iter()
+        .map(|nm| nm.sid().to_constellation())
+        .collect();
+    constellations.sort();
+    constellations.dedup();
+    constellations.into_iter()
+}
+
+/// Computes a position and clock bias, in meters, from pseudoranges alone
+/// starting from the center of the Earth
+fn least_squares_position(measurements: &[&NavigationMeasurement]) -> Option<(ECEF, f64)> {
+    let mut x = [0.0; 4];
+    for _ in 0..20 {
+        let receiver = ECEF::new(x[0], x[1], x[2]);
+        let mut normal = Matrix::zeros(4, 4);
+        let mut rhs = [0.0; 4];
+        for nm in measurements {
+            let (range, los) = geometry(&nm.satellite_position(), &receiver);
+            let corrected = nm.pseudorange()? + SPEED_OF_LIGHT * nm.satellite_clock_error();
+            let residual = corrected - (range + x[3]);
+            let row = [-los[0], -los[1], -los[2], 1.0];
+            for i in 0..4 {
+                rhs[i] += row[i] * residual;
+                for j in 0..4 {
+                    normal[(i, j)] += row[i] * row[j];
+                }
+            }
+        }
+
+        let dx = normal.inverse()?.mul_vec(&rhs);
+        for (state, delta) in x.iter_mut().zip(dx.iter()) {
+            *state += delta;
+        }
+        if (dx[0] * dx[0] + dx[1] * dx[1] + dx[2] * dx[2]).sqrt() < 1e-4 {
+            return Some((ECEF::new(x[0], x[1], x[2]), x[3]));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::LLHDegrees;
+    use crate::ephemeris::SatelliteState;
+    use crate::signal::{Code, GnssSignal};
+    use crate::solver::fixtures::satellites;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn disabled_constraints() {
+        let mut constraints = MotionConstraints::new(ConstraintSettings::new());
+        let position = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+
+        assert!(constraints
+            .update(&position, &ECEF::new(0.0, 0.0, 0.0))
+            .is_empty());
+        assert!(!constraints.is_stationary());
+    }
+
+    #[test]
+    fn zupt_requires_consecutive_epochs() {
+        let settings =
+            ConstraintSettings::new().enable_zupt(ZuptSettings::new().set_min_stationary_epochs(2));
+        let mut constraints = MotionConstraints::new(settings);
+        let position = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let still = ECEF::new(0.01, 0.0, 0.0);
+        let moving = ECEF::new(1.0, 0.0, 0.0);
+
+        assert!(constraints.update(&position, &still).is_empty());
+        assert_eq!(constraints.update(&position, &still).len(), 3);
+        assert!(constraints.is_stationary());
+        assert!(constraints.update(&position, &moving).is_empty());
+        assert!(!constraints.is_stationary());
+        assert!(constraints.update(&position, &still).is_empty());
+    }
+
+    #[test]
+    fn nhc_constraints_are_perpendicular_to_travel() {
+        let settings = ConstraintSettings::new().enable_nhc(NhcSettings::new());
+        let mut constraints = MotionConstraints::new(settings);
+        let position = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let velocity = NED::new(10.0, 5.0, 0.0).ecef_vector_at(&position);
+
+        let result = constraints.update(&position, &velocity);
+        assert_eq!(result.len(), 2);
+        for constraint in &result {
+            assert_float_eq!(constraint.residual(&velocity), 0.0, abs <= 1e-9);
+        }
+        assert_float_eq!(
+            constraints.heading().unwrap(),
+            5.0f64.atan2(10.0),
+            abs <= 1e-9
+        );
+
+        // A sideways slip shows up in the lateral residual
+        let slip = NED::new(10.0, 6.0, 0.0).ecef_vector_at(&position);
+        assert!(result[0].residual(&slip).abs() > 0.5);
+        assert_float_eq!(result[1].residual(&slip), 0.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn nhc_holds_heading_at_low_speed() {
+        let settings = ConstraintSettings::new().enable_nhc(NhcSettings::new());
+        let mut constraints = MotionConstraints::new(settings);
+        let position = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+
+        let slow = NED::new(0.1, 0.1, 0.0).ecef_vector_at(&position);
+        assert!(constraints.update(&position, &slow).is_empty());
+
+        let north = NED::new(5.0, 0.0, 0.0).ecef_vector_at(&position);
+        assert_eq!(constraints.update(&position, &north).len(), 2);
+        assert_eq!(constraints.update(&position, &slow).len(), 2);
+        assert_float_eq!(constraints.heading().unwrap(), 0.0, abs <= 1e-9);
+    }
+
+    fn simulate_epoch(
+        receiver: &ECEF,
+        velocity: &ECEF,
+        clock_bias: f64,
+        clock_drift: f64,
+    ) -> Vec<NavigationMeasurement> {
+        let satellites = satellites();
+
+        satellites
+            .iter()
+            .map(|(sat, pos)| {
+                let sid = GnssSignal::new(*sat, Code::GpsL1ca).unwrap();
+                let (range, los) = geometry(pos, receiver);
+                let v = velocity.as_array_ref();
+                let range_rate = -(los[0] * v[0] + los[1] * v[1] + los[2] * v[2]) + clock_drift;
+                let wavelength = SPEED_OF_LIGHT / sid.carrier_frequency();
+
+                let mut nm = NavigationMeasurement::new();
+                nm.set_sid(sid);
+                nm.set_pseudorange(range + clock_bias);
+                nm.set_measured_doppler(-range_rate / wavelength);
+                nm.set_satellite_state(&SatelliteState {
+                    pos: *pos,
+                    vel: ECEF::default(),
+                    acc: ECEF::default(),
+                    clock_err: 0.0,
+                    clock_rate_err: 0.0,
+                    iodc: 0,
+                    iode: 0,
+                });
+                nm
+            })
+            .collect()
+    }
+
+    #[test]
+    fn kalman_tracks_constant_velocity() {
+        let start = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let velocity = NED::new(10.0, -5.0, 0.0).ecef_vector_at(&start);
+        let mut filter = KalmanPvt::new(KalmanSettings::new());
+        let t0 = GpsTime::new(2200, 100_000.0).unwrap();
+
+        let mut solution = None;
+        for i in 0..10 {
+            let dt = i as f64;
+            let truth = start + dt * velocity;
+            let clock_bias = 3000.0 + 2.0 * dt;
+            let nms = simulate_epoch(&truth, &velocity, clock_bias, 2.0);
+            let t = t0 + std::time::Duration::from_secs(i);
+            solution = Some((filter.update(t, &nms).unwrap(), truth));
+        }
+
+        let (solution, truth) = solution.unwrap();
+        assert!(filter.is_initialized());
+        assert_eq!(solution.measurements_used(), 12);
+        assert_float_eq!(solution.position().x(), truth.x(), abs <= 0.1);
+        assert_float_eq!(solution.position().y(), truth.y(), abs <= 0.1);
+        assert_float_eq!(solution.position().z(), truth.z(), abs <= 0.1);
+        assert_float_eq!(solution.velocity().x(), velocity.x(), abs <= 0.01);
+        assert_float_eq!(solution.velocity().y(), velocity.y(), abs <= 0.01);
+        assert_float_eq!(solution.velocity().z(), velocity.z(), abs <= 0.01);
+        assert_float_eq!(solution.clock_bias() * SPEED_OF_LIGHT, 3018.0, abs <= 0.1);
+        assert_float_eq!(solution.clock_drift() * SPEED_OF_LIGHT, 2.0, abs <= 0.01);
+    }
+
+    #[test]
+    fn kalman_inter_system_biases() {
+        let position = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let galileo = [
+            (3, ECEF::new(-23_000_000.0, -12_000_000.0, 5_000_000.0)),
+            (8, ECEF::new(-3_000_000.0, -16_000_000.0, 19_000_000.0)),
+        ];
+        let isb = 60.0;
+        let mut filter = KalmanPvt::new(KalmanSettings::new());
+        let t0 = GpsTime::new(2200, 100_000.0).unwrap();
+
+        let mut solution = None;
+        for i in 0..10 {
+            let mut nms = simulate_epoch(&position, &ECEF::default(), 1000.0, 0.0);
+            // Galileo only shows up after the filter is initialized
+            if i >= 3 {
+                for (sat, pos) in &galileo {
+                    let (range, _) = geometry(pos, &position);
+                    let mut nm = nms[0].clone();
+                    nm.set_sid(GnssSignal::new(*sat, Code::GalE1b).unwrap());
+                    nm.set_pseudorange(range + 1000.0 + isb);
+                    nm.invalidate_measured_doppler();
+                    nm.set_satellite_state(&SatelliteState {
+                        pos: *pos,
+                        vel: ECEF::default(),
+                        acc: ECEF::default(),
+                        clock_err: 0.0,
+                        clock_rate_err: 0.0,
+                        iodc: 0,
+                        iode: 0,
+                    });
+                    nms.push(nm);
+                }
+            }
+            let t = t0 + std::time::Duration::from_secs(i);
+            solution = Some(filter.update(t, &nms).unwrap());
+        }
+
+        let solution = solution.unwrap();
+        assert_eq!(solution.reference_constellation(), Constellation::Gps);
+        assert_eq!(solution.inter_system_biases().len(), 1);
+        assert_eq!(solution.inter_system_biases()[0].0, Constellation::Gal);
+        assert_float_eq!(
+            solution.inter_system_biases()[0].1 * SPEED_OF_LIGHT,
+            isb,
+            abs <= 0.5
+        );
+        assert_float_eq!(
+            solution
+                .constellation_clock_bias(Constellation::Gal)
+                .unwrap()
+                * SPEED_OF_LIGHT,
+            1000.0 + isb,
+            abs <= 0.5
+        );
+        assert_eq!(solution.constellation_clock_bias(Constellation::Bds), None);
+        assert_float_eq!(solution.position().x(), position.x(), abs <= 0.5);
+        assert_float_eq!(solution.position().z(), position.z(), abs <= 0.5);
+    }
+
+    #[test]
+    fn kalman_errors() {
+        let position = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let nms = simulate_epoch(&position, &ECEF::default(), 0.0, 0.0);
+        let mut filter = KalmanPvt::new(KalmanSettings::new());
+        let t = GpsTime::new(2200, 100_000.0).unwrap();
+
+        assert_eq!(
+            filter.update(t, &nms[..3]),
+            Err(KalmanError::NotEnoughMeasurements)
+        );
+        assert!(!filter.is_initialized());
+
+        filter.update(t, &nms).unwrap();
+        let earlier = t - std::time::Duration::from_secs(1);
+        assert_eq!(filter.update(earlier, &nms), Err(KalmanError::TimeReversed));
+
+        filter.reset();
+        assert!(filter.solution().is_none());
+    }
+
+    #[test]
+    fn kalman_zenith_delay() {
+        let truth = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let llh = truth.to_llh();
+        let ztd = 0.3;
+        let ztd_settings = ZtdSettings::new();
+        let t0 = GpsTime::new(2200, 100_000.0).unwrap();
+        let doy = t0.to_utc_hardcoded().day_of_year() as f64;
+
+        // The delay can only be told apart from the height and clock with
+        // satellites at a range of elevations
+        let mut nms = simulate_epoch(&truth, &ECEF::default(), 1000.0, 0.0);
+        for (sat, az, el) in [(7, 30.0f64, 10.0f64), (9, 150.0, 15.0), (21, 270.0, 20.0)] {
+            let (az, el) = (az.to_radians(), el.to_radians());
+            let direction = NED::new(el.cos() * az.cos(), el.cos() * az.sin(), -el.sin())
+                .ecef_vector_at(&truth);
+            let pos = truth + 22_000_000.0 * direction;
+            let (range, _) = geometry(&pos, &truth);
+            let mut nm = nms[0].clone();
+            nm.set_sid(GnssSignal::new(sat, Code::GpsL1ca).unwrap());
+            nm.set_pseudorange(range + 1000.0);
+            nm.invalidate_measured_doppler();
+            nm.set_satellite_state(&SatelliteState {
+                pos,
+                vel: ECEF::default(),
+                acc: ECEF::default(),
+                clock_err: 0.0,
+                clock_rate_err: 0.0,
+                iodc: 0,
+                iode: 0,
+            });
+            nms.push(nm);
+        }
+        for nm in nms.iter_mut() {
+            let el = truth.azel_of(&nm.satellite_position()).el;
+            let delay = ztd * ztd_settings.factor(doy, llh.latitude(), llh.height(), el);
+            nm.set_pseudorange(nm.pseudorange().unwrap() + delay);
+        }
+
+        let settings = KalmanSettings::new()
+            .set_acceleration_psd(1e-6)
+            .set_code_sigma(0.5);
+        let mut estimating = KalmanPvt::new(settings.enable_ztd(ztd_settings));
+        let mut fixed = KalmanPvt::new(settings);
+        let mut solutions = None;
+        for i in 0..60 {
+            let t = t0 + std::time::Duration::from_secs(i);
+            solutions = Some((
+                estimating.update(t, &nms).unwrap(),
+                fixed.update(t, &nms).unwrap(),
+            ));
+        }
+
+        let (estimated, unestimated) = solutions.unwrap();
+        assert_eq!(unestimated.zenith_delay(), None);
+        assert_float_eq!(estimated.zenith_delay().unwrap(), ztd, abs <= 0.05);
+        assert!(estimated.zenith_delay_variance().unwrap() < 0.2 * 0.2);
+
+        let height_error = |solution: &KalmanSolution| {
+            (solution.position() - truth)
+                .ned_vector_at(&truth)
+                .d()
+                .abs()
+        };
+        assert!(height_error(&estimated) < 0.5 * height_error(&unestimated));
+    }
+
+    #[test]
+    fn seeded_kalman() {
+        let truth = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let nms = simulate_epoch(&truth, &ECEF::default(), 1000.0, 0.0);
+        let offset = NED::new(30.0, -40.0, 0.0).ecef_vector_at(&truth);
+        let hint = CoarseHint::new(truth + offset, 100.0);
+        let mut filter = KalmanPvt::new(KalmanSettings::new());
+        let t = GpsTime::new(2200, 100_000.0).unwrap();
+
+        filter.seed(&hint);
+        assert!(filter.pending_hint().is_some());
+        assert_eq!(
+            filter.update(t, &[]),
+            Err(KalmanError::NotEnoughMeasurements)
+        );
+        let solution = filter.update(t, &nms[..2]).unwrap();
+        assert!(filter.pending_hint().is_none());
+        assert_eq!(solution.measurements_used(), 4);
+
+        // Two satellites improve the position along their lines of sight,
+        // the rest of the hint error stays within its uncertainty
+        let error = (solution.position() - truth).ned_vector_at(&truth);
+        assert!(error.n().hypot(error.e()) < 50.0);
+        let cov = solution.position_covariance();
+        assert!(cov[0][0] + cov[1][1] + cov[2][2] < 3.0 * 100.0 * 100.0);
+    }
+}
@@ -0,0 +1,29 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Satellite geometry shared by the solver tests
+
+use crate::coords::ECEF;
+
+/// GPS satellites, by PRN, spread over the sky of San Francisco
+pub(super) fn satellites() -> [(u16, ECEF); 6] {
+    [
+        (1, ECEF::new(-11_000_000.0, -18_000_000.0, 16_000_000.0)),
+        (5, ECEF::new(5_000_000.0, -20_000_000.0, 16_000_000.0)),
+        (12, ECEF::new(-20_000_000.0, -5_000_000.0, 17_000_000.0)),
+        (17, ECEF::new(-8_000_000.0, -25_000_000.0, 2_000_000.0)),
+        (24, ECEF::new(-15_000_000.0, -10_000_000.0, 20_000_000.0)),
+        (30, ECEF::new(2_000_000.0, -14_000_000.0, 22_000_000.0)),
+    ]
+}
+
+/// Position of a seventh satellite, for the tests needing more redundancy
+pub(super) fn extra_satellite() -> ECEF {
+    ECEF::new(-23_000_000.0, -12_000_000.0, 5_000_000.0)
+}
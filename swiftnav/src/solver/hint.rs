@@ -0,0 +1,411 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Coarse position and time hints
+//!
+//! A rough idea of where the receiver is and what time it is lets a receiver
+//! narrow its signal search, and lets a filter start before enough satellites
+//! are tracked for a standalone fix. A [`CoarseHint`] carries such a position,
+//! and optionally a time, along with their uncertainties. Hints can come from
+//! an NMEA GGA sentence, be entered manually, or come from any other source
+//! such as a cell tower database.
+//!
+//! Hints are consumed by [`KalmanPvt::seed()`](crate::solver::filter::KalmanPvt::seed)
+//! and can be used to compute doppler search windows with
+//! [`CoarseHint::doppler_window()`].
+
+use crate::coords::{LLHDegrees, ECEF, NED};
+use crate::ephemeris::SatelliteState;
+use crate::signal::GnssSignal;
+use crate::time::GpsTime;
+use crate::SPEED_OF_LIGHT;
+use std::error::Error;
+use std::fmt;
+
+/// Typical user range errors, in meters, used to scale the HDOP of a GGA
+/// sentence into a position uncertainty, indexed by the GGA fix quality
+const GGA_RANGE_ERRORS: [Option<f64>; 7] = [
+    None,
+    Some(5.0),
+    Some(1.0),
+    Some(5.0),
+    Some(0.05),
+    Some(0.5),
+    Some(50.0),
+];
+
+/// Where a hint came from
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HintSource {
+    /// Parsed from an NMEA sentence
+    Nmea,
+    /// Entered by the user
+    Manual,
+    /// Provided by some other positioning source
+    External,
+}
+
+/// Errors which can occur when parsing a hint
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum HintError {
+    /// The sentence is not a well formed GGA sentence
+    InvalidSentence,
+    /// The sentence checksum doesn't match its contents
+    ChecksumMismatch,
+    /// The sentence doesn't contain a position fix
+    NoFix,
+}
+
+impl fmt::Display for HintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HintError::InvalidSentence => write!(f, "Invalid GGA sentence"),
+            HintError::ChecksumMismatch => write!(f, "NMEA checksum mismatch"),
+            HintError::NoFix => write!(f, "GGA sentence has no fix"),
+        }
+    }
+}
+
+impl Error for HintError {}
+
+/// A coarse position, and optionally time, with explicit uncertainties
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct CoarseHint {
+    position: ECEF,
+    horizontal_sigma: f64,
+    vertical_sigma: f64,
+    time: Option<GpsTime>,
+    time_sigma: f64,
+    source: HintSource,
+}
+
+impl CoarseHint {
+    /// Makes a manual hint with the same standard deviation, in meters, in
+    /// all directions and no time
+    pub fn new(position: ECEF, sigma: f64) -> CoarseHint {
+        CoarseHint {
+            position,
+            horizontal_sigma: sigma,
+            vertical_sigma: sigma,
+            time: None,
+            time_sigma: 0.0,
+            source: HintSource::Manual,
+        }
+    }
+
+    /// Makes a hint from the position in a GGA sentence
+    ///
+    /// The checksum is verified when present. The horizontal uncertainty is
+    /// the HDOP scaled by a typical range error for the fix quality, and the
+    /// vertical uncertainty is twice the horizontal. The GGA altitude and
+    /// geoid separation are summed to get the ellipsoidal height.
+    pub fn from_gga(sentence: &str) -> Result<CoarseHint, HintError> {
+        let sentence = sentence.trim();
+        let body = sentence
+            .strip_prefix('$')
+            .ok_or(HintError::InvalidSentence)?;
+        let body = match body.split_once('*') {
+            Some((body, checksum)) => {
+                let expected =
+                    u8::from_str_radix(checksum, 16).map_err(|_| HintError::InvalidSentence)?;
+                if body.bytes().fold(0, |acc, b| acc ^ b) != expected {
+                    return Err(HintError::ChecksumMismatch);
+                }
+                body
+            }
+            None => body,
+        };
+
+        let fields: Vec<&str> = body.split(',').collect();
+        if fields.len() < 12 || fields[0].len() != 5 || !fields[0].ends_with("GGA") {
+            return Err(HintError::InvalidSentence);
+        }
+        let quality: usize = fields[6].parse().map_err(|_| HintError::InvalidSentence)?;
+        let range_error = GGA_RANGE_ERRORS
+            .get(quality)
+            .copied()
+            .flatten()
+            .ok_or(HintError::NoFix)?;
+
+        let latitude = parse_degrees_minutes(fields[2], fields[3], 'N', 'S')?;
+        let longitude = parse_degrees_minutes(fields[4], fields[5], 'E', 'W')?;
+        let number = |field: &str| field.parse::<f64>().map_err(|_| HintError::InvalidSentence);
+        let hdop = if fields[8].is_empty() {
+            1.0
+        } else {
+            number(fields[8])?
+        };
+        let altitude = number(fields[9])?;
+        let separation = if fields[11].is_empty() {
+            0.0
+        } else {
+            number(fields[11])?
+        };
+
+        let position = LLHDegrees::new(latitude, longitude, altitude + separation).to_ecef();
+        let sigma = hdop * range_error;
+        Ok(CoarseHint::new(position, sigma)
+            .set_vertical_sigma(2.0 * sigma)
+            .set_source(HintSource::Nmea))
+    }
+
+    /// Sets the horizontal standard deviation, in meters
+    pub fn set_horizontal_sigma(self, horizontal_sigma: f64) -> CoarseHint {
+        CoarseHint {
+            horizontal_sigma,
+            ..self
+        }
+    }
+
+    /// Sets the vertical standard deviation, in meters
+    pub fn set_vertical_sigma(self, vertical_sigma: f64) -> CoarseHint {
+        CoarseHint {
+            vertical_sigma,
+            ..self
+        }
+    }
+
+    /// Sets the time of the hint, with its standard deviation in seconds
+    pub fn set_time(self, time: GpsTime, time_sigma: f64) -> CoarseHint {
+        CoarseHint {
+            time: Some(time),
+            time_sigma,
+            ..self
+        }
+    }
+
+    pub fn set_source(self, source: HintSource) -> CoarseHint {
+        CoarseHint { source, ..self }
+    }
+
+    pub fn position(&self) -> ECEF {
+        self.position
+    }
+
+    pub fn horizontal_sigma(&self) -> f64 {
+        self.horizontal_sigma
+    }
+
+    pub fn vertical_sigma(&self) -> f64 {
+        self.vertical_sigma
+    }
+
+    pub fn time(&self) -> Option<GpsTime> {
+        self.time
+    }
+
+    /// Gets the standard deviation of the time, in seconds
+    pub fn time_sigma(&self) -> f64 {
+        self.time_sigma
+    }
+
+    pub fn source(&self) -> HintSource {
+        self.source
+    }
+
+    /// Gets the ECEF covariance of the position, in meters squared
+    pub fn covariance(&self) -> [[f64; 3]; 3] {
+        let axes = [
+            (NED::new(1.0, 0.0, 0.0), self.horizontal_sigma),
+            (NED::new(0.0, 1.0, 0.0), self.horizontal_sigma),
+            (NED::new(0.0, 0.0, 1.0), self.vertical_sigma),
+        ];
+        let mut covariance = [[0.0; 3]; 3];
+        for (axis, sigma) in axes.iter() {
+            let u = axis.ecef_vector_at(&self.position);
+            let u = u.as_array_ref();
+            for (i, row) in covariance.iter_mut().enumerate() {
+                for (j, value) in row.iter_mut().enumerate() {
+                    *value += sigma * sigma * u[i] * u[j];
+                }
+            }
+        }
+        covariance
+    }
+
+    /// Checks if a position is consistent with the hint, i.e. within `k`
+    /// standard deviations horizontally and vertically
+    pub fn contains(&self, position: &ECEF, k: f64) -> bool {
+        let ned = (*position - self.position).ned_vector_at(&self.position);
+        ned.n().hypot(ned.e()) <= k * self.horizontal_sigma
+            && ned.d().abs() <= k * self.vertical_sigma
+    }
+
+    /// Computes the doppler search window of a signal, in Hz, as a center and
+    /// a half width
+    ///
+    /// The satellite state should be evaluated at the hint time. The window is
+    /// `k` standard deviations wide on each side, covering the position and
+    /// time uncertainties of the hint, plus the uncertainty of the receiver
+    /// clock drift, in seconds per second.
+    pub fn doppler_window(
+        &self,
+        sid: GnssSignal,
+        satellite: &SatelliteState,
+        clock_drift_sigma: f64,
+        k: f64,
+    ) -> (f64, f64) {
+        let wavelength = SPEED_OF_LIGHT / sid.carrier_frequency();
+        let line_of_sight = satellite.pos - self.position;
+        let range = line_of_sight.norm();
+        let los = (1.0 / range) * line_of_sight;
+        let range_rate = satellite.vel.dot(&los) - SPEED_OF_LIGHT * satellite.clock_rate_err;
+        let center = -range_rate / wavelength;
+
+        // Moving the receiver across the line of sight turns it by the
+        // displacement over the range, changing the projected velocity
+        let tangential_velocity = {
+            let along = satellite.vel.dot(&los);
+            (satellite.vel.dot(&satellite.vel) - along * along)
+                .max(0.0)
+                .sqrt()
+        };
+        let position_sigma = self.horizontal_sigma.hypot(self.vertical_sigma);
+        let position_term = tangential_velocity * position_sigma / range;
+        // An error in time moves the satellite along its orbit
+        let acceleration = satellite.acc.norm();
+        let time_term = acceleration * self.time_sigma;
+        let clock_term = SPEED_OF_LIGHT * clock_drift_sigma;
+
+        let sigma = (position_term.powi(2) + time_term.powi(2) + clock_term.powi(2)).sqrt();
+        (center, k * sigma / wavelength)
+    }
+}
+
+/// Parses a `(d)ddmm.mmmm` NMEA angle and hemisphere into signed degrees
+fn parse_degrees_minutes(
+    value: &str,
+    hemisphere: &str,
+    positive: char,
+    negative: char,
+) -> Result<f64, HintError> {
+    let point = value.find('.').unwrap_or(value.len());
+    if point < 3 {
+        return Err(HintError::InvalidSentence);
+    }
+    // The split point can fall within a multi-byte character of a corrupted
+    // field
+    let number = |field: Option<&str>| {
+        field
+            .and_then(|field| field.parse::<f64>().ok())
+            .ok_or(HintError::InvalidSentence)
+    };
+    let degrees = number(value.get(..point - 2))?;
+    let minutes = number(value.get(point - 2..))?;
+    let angle = degrees + minutes / 60.0;
+    match hemisphere.chars().next() {
+        Some(c) if c == positive => Ok(angle),
+        Some(c) if c == negative => Ok(-angle),
+        _ => Err(HintError::InvalidSentence),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::Code;
+    use float_eq::assert_float_eq;
+
+    const GGA: &str = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47";
+
+    #[test]
+    fn gga_hints() {
+        let hint = CoarseHint::from_gga(GGA).unwrap();
+        let llh: LLHDegrees = hint.position().to_llh().into();
+        assert_float_eq!(llh.latitude(), 48.0 + 7.038 / 60.0, abs <= 1e-9);
+        assert_float_eq!(llh.longitude(), 11.0 + 31.0 / 60.0, abs <= 1e-9);
+        assert_float_eq!(llh.height(), 545.4 + 46.9, abs <= 1e-6);
+        assert_float_eq!(hint.horizontal_sigma(), 4.5, abs <= 1e-12);
+        assert_float_eq!(hint.vertical_sigma(), 9.0, abs <= 1e-12);
+        assert_eq!(hint.source(), HintSource::Nmea);
+        assert!(hint.time().is_none());
+
+        let southern = "$GNGGA,000000,3351.000,S,15112.000,W,4,12,1.0,10.0,M,,M,,";
+        let hint = CoarseHint::from_gga(southern).unwrap();
+        let llh: LLHDegrees = hint.position().to_llh().into();
+        assert_float_eq!(llh.latitude(), -33.85, abs <= 1e-9);
+        assert_float_eq!(llh.longitude(), -151.2, abs <= 1e-9);
+        assert_float_eq!(hint.horizontal_sigma(), 0.05, abs <= 1e-12);
+
+        let corrupted = GGA.replace("4807", "4808");
+        assert_eq!(
+            CoarseHint::from_gga(&corrupted),
+            Err(HintError::ChecksumMismatch)
+        );
+        let no_fix = "$GPGGA,123519,4807.038,N,01131.000,E,0,00,,,M,,M,,";
+        assert_eq!(CoarseHint::from_gga(no_fix), Err(HintError::NoFix));
+        assert_eq!(
+            CoarseHint::from_gga("$GPRMC,123519,A"),
+            Err(HintError::InvalidSentence)
+        );
+        let non_ascii = "$GPGGA,123519,1é2.0,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,";
+        assert_eq!(
+            CoarseHint::from_gga(non_ascii),
+            Err(HintError::InvalidSentence)
+        );
+    }
+
+    #[test]
+    fn hint_uncertainty() {
+        let position = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let hint = CoarseHint::new(position, 100.0).set_vertical_sigma(20.0);
+        let covariance = hint.covariance();
+        // The trace is invariant to the rotation
+        let trace = covariance[0][0] + covariance[1][1] + covariance[2][2];
+        assert_float_eq!(trace, 2.0 * 100.0 * 100.0 + 20.0 * 20.0, rmax <= 1e-12);
+        for (i, row) in covariance.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                assert_float_eq!(*value, covariance[j][i], abs <= 1e-9);
+            }
+        }
+
+        let east = NED::new(0.0, 250.0, 0.0).ecef_vector_at(&position);
+        let up = NED::new(0.0, 0.0, -50.0).ecef_vector_at(&position);
+        assert!(hint.contains(&(position + east), 3.0));
+        assert!(!hint.contains(&(position + east), 2.0));
+        assert!(!hint.contains(&(position + up), 2.0));
+    }
+
+    #[test]
+    fn doppler_windows() {
+        let position = LLHDegrees::new(0.0, 0.0, 0.0).to_ecef();
+        let sid = GnssSignal::new(1, Code::GpsL1ca).unwrap();
+        // Satellite straight overhead, moving across the line of sight
+        let satellite = SatelliteState {
+            pos: ECEF::new(26_560_000.0, 0.0, 0.0),
+            vel: ECEF::new(0.0, 3_000.0, 1_000.0),
+            acc: ECEF::new(-0.56, 0.0, 0.0),
+            clock_err: 0.0,
+            clock_rate_err: 0.0,
+            iodc: 0,
+            iode: 0,
+        };
+
+        let exact = CoarseHint::new(position, 0.0);
+        let (center, width) = exact.doppler_window(sid, &satellite, 0.0, 3.0);
+        assert_float_eq!(center, 0.0, abs <= 1e-6);
+        assert_float_eq!(width, 0.0, abs <= 1e-12);
+
+        // A 1 ppm clock and a 100 km position uncertainty
+        let wavelength = SPEED_OF_LIGHT / sid.carrier_frequency();
+        let (_, width) = exact.doppler_window(sid, &satellite, 1e-6, 1.0);
+        assert_float_eq!(width, sid.carrier_frequency() * 1e-6, rmax <= 1e-12);
+        let coarse = CoarseHint::new(position, 100_000.0).set_vertical_sigma(0.0);
+        let (_, width) = coarse.doppler_window(sid, &satellite, 0.0, 1.0);
+        let tangential = (3_000.0_f64.powi(2) + 1_000.0_f64.powi(2)).sqrt();
+        let expected = tangential * 100_000.0 / (26_560_000.0 - 6_378_137.0) / wavelength;
+        assert_float_eq!(width, expected, rmax <= 1e-6);
+
+        // A 10 s time uncertainty
+        let timed = exact.set_time(GpsTime::new(2200, 0.0).unwrap(), 10.0);
+        let (_, width) = timed.doppler_window(sid, &satellite, 0.0, 1.0);
+        assert_float_eq!(width, 5.6 / wavelength, rmax <= 1e-9);
+    }
+}
@@ -0,0 +1,281 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! GLONASS inter-frequency code biases
+//!
+//! Each GLONASS FDMA satellite transmits on its own frequency, and the
+//! receiver's front end delays each frequency by a slightly different amount.
+//! These inter-frequency biases (IFBs) can reach several meters and aren't
+//! absorbed by the GLONASS receiver clock bias, so they degrade solutions
+//! mixing GLONASS pseudoranges with other constellations.
+//!
+//! A [`GloIfbTable`] holds the bias of each GLONASS signal, either supplied by
+//! the user or estimated with a [`GloIfbEstimator`] from the measurements of
+//! the receiver at a known position. Only the differences between the biases
+//! matter, as any common part is absorbed by the receiver clock bias.
+
+use crate::coords::ECEF;
+use crate::navmeas::NavigationMeasurement;
+use crate::signal::{Code, Constellation, GnssSignal};
+use crate::solver::filter::geometry;
+use crate::SPEED_OF_LIGHT;
+use std::collections::HashMap;
+
+/// Pseudorange bias of each GLONASS signal, in meters
+///
+/// The biases are subtracted from the pseudoranges. Signals without a bias
+/// in the table are left uncorrected.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GloIfbTable {
+    biases: HashMap<GnssSignal, f64>,
+}
+
+impl GloIfbTable {
+    /// Makes an empty table
+    pub fn new() -> GloIfbTable {
+        GloIfbTable {
+            biases: HashMap::new(),
+        }
+    }
+
+    /// Sets the bias of a signal, in meters
+    ///
+    /// Returns `false`, and leaves the table unchanged, if the signal isn't a
+    /// GLONASS signal
+    pub fn set_bias(&mut self, sid: GnssSignal, bias: f64) -> bool {
+        if sid.to_constellation() != Constellation::Glo {
+            return false;
+        }
+        self.biases.insert(sid, bias);
+        true
+    }
+
+    /// Gets the bias of a signal, in meters
+    pub fn bias(&self, sid: GnssSignal) -> Option<f64> {
+        self.biases.get(&sid).copied()
+    }
+
+    /// Removes the bias of a signal
+    pub fn remove(&mut self, sid: GnssSignal) -> Option<f64> {
+        self.biases.remove(&sid)
+    }
+
+    pub fn len(&self) -> usize {
+        self.biases.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.biases.is_empty()
+    }
+
+    /// Iterates over the signals and their biases, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (GnssSignal, f64)> + '_ {
+        self.biases.iter().map(|(sid, bias)| (*sid, *bias))
+    }
+}
+
+impl Extend<(GnssSignal, f64)> for GloIfbTable {
+    /// Adds the biases, skipping any non GLONASS signal
+    fn extend<T: IntoIterator<Item = (GnssSignal, f64)>>(&mut self, iter: T) {
+        for (sid, bias) in iter {
+            self.set_bias(sid, bias);
+        }
+    }
+}
+
+impl std::iter::FromIterator<(GnssSignal, f64)> for GloIfbTable {
+    fn from_iter<T: IntoIterator<Item = (GnssSignal, f64)>>(iter: T) -> GloIfbTable {
+        let mut table = GloIfbTable::new();
+        table.extend(iter);
+        table
+    }
+}
+
+/// Estimates the GLONASS inter-frequency biases from the measurements of a
+/// receiver at a known position
+///
+/// In each epoch the residual of each GLONASS pseudorange against the known
+/// position is taken relative to the mean residual of the signals with the
+/// same code, which removes the receiver clock bias. The bias of a signal is
+/// the mean of these relative residuals over all of the epochs added.
+/// Uncorrected atmospheric delays leak into the estimates, so the
+/// measurements should either be corrected for them or be collected over a
+/// long enough period for them to average out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GloIfbEstimator {
+    sums: HashMap<GnssSignal, (f64, usize)>,
+}
+
+impl GloIfbEstimator {
+    pub fn new() -> GloIfbEstimator {
+        GloIfbEstimator {
+            sums: HashMap::new(),
+        }
+    }
+
+    /// Adds the measurements of an epoch taken at `position`
+    ///
+    /// Codes with fewer than two GLONASS pseudoranges in the epoch are
+    /// skipped, as there is nothing to compare them to.
+    pub fn add(&mut self, measurements: &[NavigationMeasurement], position: &ECEF) {
+        let residuals: Vec<(GnssSignal, f64)> = measurements
+            .iter()
+            .filter(|nm| nm.sid().to_constellation() == Constellation::Glo)
+            .filter_map(|nm| {
+                let (range, _) = geometry(&nm.satellite_position(), position);
+                let pseudorange = nm.pseudorange()? + SPEED_OF_LIGHT * nm.satellite_clock_error();
+                Some((nm.sid(), pseudorange - range))
+            })
+            .collect();
+
+        let mut codes: Vec<Code> = residuals.iter().map(|(sid, _)| sid.code()).collect();
+        codes.sort();
+        codes.dedup();
+        for code in codes {
+            let same_code = || residuals.iter().filter(|(sid, _)| sid.code() == code);
+            let count = same_code().count();
+            if count < 2 {
+                continue;
+            }
+            let mean = same_code().map(|(_, r)| r).sum::<f64>() / count as f64;
+            for (sid, residual) in same_code() {
+                let entry = self.sums.entry(*sid).or_insert((0.0, 0));
+                entry.0 += residual - mean;
+                entry.1 += 1;
+            }
+        }
+    }
+
+    /// Gets the number of epochs added for a signal
+    pub fn count(&self, sid: GnssSignal) -> usize {
+        self.sums.get(&sid).map_or(0, |(_, count)| *count)
+    }
+
+    /// Makes a table of the biases estimated so far
+    pub fn table(&self) -> GloIfbTable {
+        self.sums
+            .iter()
+            .map(|(sid, (sum, count))| (*sid, sum / *count as f64))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::LLHDegrees;
+    use crate::ephemeris::SatelliteState;
+    use crate::solver::pvt::PvtSolver;
+    use crate::time::GpsTime;
+    use float_eq::assert_float_eq;
+
+    fn glo(slot: u16) -> GnssSignal {
+        GnssSignal::new(slot, Code::GloL1of).unwrap()
+    }
+
+    fn simulate_epoch(receiver: &ECEF, ifbs: &[f64; 4]) -> Vec<NavigationMeasurement> {
+        let satellites = [
+            (
+                GnssSignal::new(1, Code::GpsL1ca).unwrap(),
+                ECEF::new(-11e6, -18e6, 16e6),
+            ),
+            (
+                GnssSignal::new(5, Code::GpsL1ca).unwrap(),
+                ECEF::new(5e6, -20e6, 16e6),
+            ),
+            (
+                GnssSignal::new(12, Code::GpsL1ca).unwrap(),
+                ECEF::new(-20e6, -5e6, 17e6),
+            ),
+            (
+                GnssSignal::new(24, Code::GpsL1ca).unwrap(),
+                ECEF::new(-15e6, -10e6, 20e6),
+            ),
+            (
+                GnssSignal::new(30, Code::GpsL1ca).unwrap(),
+                ECEF::new(2e6, -14e6, 22e6),
+            ),
+            (glo(1), ECEF::new(-8e6, -25e6, 2e6)),
+            (glo(2), ECEF::new(-23e6, -12e6, 5e6)),
+            (glo(3), ECEF::new(-3e6, -16e6, 19e6)),
+            (glo(4), ECEF::new(-18e6, -16e6, 10e6)),
+        ];
+
+        satellites
+            .iter()
+            .enumerate()
+            .map(|(i, (sid, pos))| {
+                let (range, _) = geometry(pos, receiver);
+                let bias = if i >= 5 { 50.0 + ifbs[i - 5] } else { 0.0 };
+                let mut nm = NavigationMeasurement::new();
+                nm.set_sid(*sid);
+                nm.set_pseudorange(range + bias);
+                nm.set_satellite_state(&SatelliteState {
+                    pos: *pos,
+                    vel: ECEF::default(),
+                    acc: ECEF::default(),
+                    clock_err: 0.0,
+                    clock_rate_err: 0.0,
+                    iodc: 0,
+                    iode: 0,
+                });
+                nm
+            })
+            .collect()
+    }
+
+    #[test]
+    fn table() {
+        let mut table = GloIfbTable::new();
+        assert!(table.set_bias(glo(3), 1.5));
+        assert!(!table.set_bias(GnssSignal::new(3, Code::GpsL1ca).unwrap(), 1.5));
+        assert_eq!(table.bias(glo(3)), Some(1.5));
+        assert_eq!(table.bias(glo(4)), None);
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.remove(glo(3)), Some(1.5));
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn correct_and_estimate() {
+        let receiver = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let time = GpsTime::new(2200, 300_000.0).unwrap();
+        let ifbs = [3.0, -2.0, 1.0, -2.0];
+        let measurements = simulate_epoch(&receiver, &ifbs);
+        let error = |position: ECEF| (position - receiver).norm();
+
+        let uncorrected = PvtSolver::default().solve(&measurements, &time).unwrap();
+        assert!(error(uncorrected.position()) > 0.1);
+
+        let mut estimator = GloIfbEstimator::new();
+        estimator.add(&measurements, &receiver);
+        estimator.add(&measurements, &receiver);
+        assert_eq!(estimator.count(glo(1)), 2);
+        assert_eq!(
+            estimator.count(GnssSignal::new(1, Code::GpsL1ca).unwrap()),
+            0
+        );
+        let table = estimator.table();
+        assert_float_eq!(table.bias(glo(1)).unwrap(), 3.0, abs <= 1e-6);
+        assert_float_eq!(table.bias(glo(2)).unwrap(), -2.0, abs <= 1e-6);
+
+        let solver = PvtSolver::builder().set_glo_biases(table).build();
+        let (corrected, report) = solver.solve_with_report(&measurements, &time).unwrap();
+        assert_float_eq!(error(corrected.position()), 0.0, abs <= 1e-3);
+        assert_float_eq!(
+            report
+                .signal(glo(1))
+                .unwrap()
+                .corrections()
+                .inter_frequency_bias,
+            3.0,
+            abs <= 1e-6
+        );
+    }
+}
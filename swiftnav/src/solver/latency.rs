@@ -0,0 +1,254 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Solution latency management
+//!
+//! A solution is only available some time after the measurements it was
+//! computed from were taken. Real-time integrations usually need to know both
+//! the measurement epoch of a solution and when it was produced, and often
+//! want to propagate the solution forward to the current time. The
+//! [`TimestampedSolution`] type keeps track of both times and can extrapolate
+//! the position using the estimated velocity, refusing to do so when the
+//! solution has become too old.
+
+use crate::coords::ECEF;
+use crate::solver::GnssSolution;
+use crate::time::GpsTime;
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// MJD of the Unix epoch, 1970-01-01
+const UNIX_EPOCH_MJD: f64 = 40587.0;
+
+/// Reasons a solution can't be extrapolated
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub enum ExtrapolationError {
+    /// The solution doesn't have a velocity estimate
+    NoVelocity,
+    /// The extrapolation interval, in seconds, is larger than allowed
+    TooOld(f64),
+}
+
+impl fmt::Display for ExtrapolationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtrapolationError::NoVelocity => write!(f, "Solution has no velocity"),
+            ExtrapolationError::TooOld(age) => {
+                write!(f, "Solution is too old to extrapolate ({} s)", age)
+            }
+        }
+    }
+}
+
+impl Error for ExtrapolationError {}
+
+/// A position solution tagged with its measurement epoch and the wall-clock
+/// time it was produced at
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct TimestampedSolution {
+    epoch: GpsTime,
+    produced_at: SystemTime,
+    position: ECEF,
+    velocity: Option<ECEF>,
+}
+
+impl TimestampedSolution {
+    pub fn new(
+        epoch: GpsTime,
+        produced_at: SystemTime,
+        position: ECEF,
+        velocity: Option<ECEF>,
+    ) -> TimestampedSolution {
+        TimestampedSolution {
+            epoch,
+            produced_at,
+            position,
+            velocity,
+        }
+    }
+
+    /// Tags a solution from the [single epoch solver](crate::solver::calc_pvt)
+    ///
+    /// Returns `None` if the solution doesn't contain a valid position
+    pub fn from_solution(
+        solution: &GnssSolution,
+        produced_at: SystemTime,
+    ) -> Option<TimestampedSolution> {
+        Some(TimestampedSolution {
+            epoch: solution.time(),
+            produced_at,
+            position: solution.pos_ecef()?,
+            velocity: solution.vel_ecef(),
+        })
+    }
+
+    /// Gets the time of the measurements the solution was computed from
+    pub fn epoch(&self) -> GpsTime {
+        self.epoch
+    }
+
+    /// Gets the wall-clock time the solution was produced at
+    pub fn produced_at(&self) -> SystemTime {
+        self.produced_at
+    }
+
+    pub fn position(&self) -> &ECEF {
+        &self.position
+    }
+
+    pub fn velocity(&self) -> Option<&ECEF> {
+        self.velocity.as_ref()
+    }
+
+    /// Gets the measurement epoch as a wall-clock time
+    ///
+    /// Note: The hard coded leap second table is used to convert from GPS time
+    pub fn epoch_system_time(&self) -> SystemTime {
+        gps_to_system_time(&self.epoch)
+    }
+
+    /// Gets the time taken between the measurement epoch and the production of
+    /// the solution
+    ///
+    /// A zero duration is returned if the solution was seemingly produced
+    /// before the measurements were taken, e.g. because of a misaligned
+    /// system clock.
+    pub fn latency(&self) -> Duration {
+        self.produced_at
+            .duration_since(self.epoch_system_time())
+            .unwrap_or_default()
+    }
+
+    /// Propagates the solution to a different epoch using its velocity
+    ///
+    /// The extrapolation is refused if the interval between the solution epoch
+    /// and the requested epoch is larger than `max_age`.
+    pub fn extrapolate_to(
+        &self,
+        epoch: GpsTime,
+        max_age: Duration,
+    ) -> Result<TimestampedSolution, ExtrapolationError> {
+        let velocity = self.velocity.ok_or(ExtrapolationError::NoVelocity)?;
+        let dt = epoch.diff(&self.epoch);
+        if dt.abs() > max_age.as_secs_f64() {
+            return Err(ExtrapolationError::TooOld(dt));
+        }
+
+        Ok(TimestampedSolution {
+            epoch,
+            produced_at: self.produced_at,
+            position: self.position + dt * velocity,
+            velocity: self.velocity,
+        })
+    }
+
+    /// Propagates the solution to the given wall-clock time
+    ///
+    /// See [`TimestampedSolution::extrapolate_to()`] for more details
+    pub fn extrapolate_to_system_time(
+        &self,
+        now: SystemTime,
+        max_age: Duration,
+    ) -> Result<TimestampedSolution, ExtrapolationError> {
+        let dt = match now.duration_since(self.epoch_system_time()) {
+            Ok(ahead) => ahead.as_secs_f64(),
+            Err(behind) => -behind.duration().as_secs_f64(),
+        };
+        if dt.abs() > max_age.as_secs_f64() {
+            return Err(ExtrapolationError::TooOld(dt));
+        }
+
+        let mut epoch = self.epoch;
+        if dt >= 0.0 {
+            epoch.add_duration(&Duration::from_secs_f64(dt));
+        } else {
+            epoch.subtract_duration(&Duration::from_secs_f64(-dt));
+        }
+        self.extrapolate_to(epoch, max_age)
+    }
+
+    /// Propagates the solution by its own latency, i.e. to the epoch at which
+    /// it was produced
+    pub fn compensate_latency(
+        &self,
+        max_age: Duration,
+    ) -> Result<TimestampedSolution, ExtrapolationError> {
+        self.extrapolate_to_system_time(self.produced_at, max_age)
+    }
+}
+
+fn gps_to_system_time(t: &GpsTime) -> SystemTime {
+    let mjd = t.to_utc_hardcoded().to_mjd().as_f64();
+    let unix_seconds = (mjd - UNIX_EPOCH_MJD) * 86400.0;
+    if unix_seconds >= 0.0 {
+        UNIX_EPOCH + Duration::from_secs_f64(unix_seconds)
+    } else {
+        UNIX_EPOCH - Duration::from_secs_f64(-unix_seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::UtcTime;
+    use float_eq::assert_float_eq;
+
+    fn make_solution(velocity: Option<ECEF>) -> TimestampedSolution {
+        let epoch = UtcTime::from_date(2021, 6, 1, 12, 0, 0.0).to_gps_hardcoded();
+        // 2021-06-01T12:00:00.25Z
+        let produced_at = UNIX_EPOCH + Duration::from_millis(1_622_548_800_250);
+        TimestampedSolution::new(
+            epoch,
+            produced_at,
+            ECEF::new(-2703115.0, -4262360.0, 3885092.0),
+            velocity,
+        )
+    }
+
+    #[test]
+    fn latency() {
+        let solution = make_solution(None);
+        assert_float_eq!(solution.latency().as_secs_f64(), 0.25, abs <= 1e-6);
+    }
+
+    #[test]
+    fn extrapolation() {
+        let solution = make_solution(Some(ECEF::new(1.0, -2.0, 4.0)));
+
+        let compensated = solution.compensate_latency(Duration::from_secs(1)).unwrap();
+        assert_float_eq!(compensated.position().x(), -2703115.0 + 0.25, abs <= 1e-4);
+        assert_float_eq!(compensated.position().y(), -4262360.0 - 0.5, abs <= 1e-4);
+        assert_float_eq!(compensated.position().z(), 3885092.0 + 1.0, abs <= 1e-4);
+        assert_float_eq!(compensated.latency().as_secs_f64(), 0.0, abs <= 1e-6);
+
+        let later = solution.epoch() + Duration::from_secs(2);
+        let extrapolated = solution
+            .extrapolate_to(later, Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(extrapolated.epoch(), later);
+        assert_float_eq!(extrapolated.position().x(), -2703113.0, abs <= 1e-6);
+    }
+
+    #[test]
+    fn extrapolation_limits() {
+        let solution = make_solution(Some(ECEF::new(1.0, -2.0, 4.0)));
+        let later = solution.epoch() + Duration::from_secs(10);
+        assert_eq!(
+            solution.extrapolate_to(later, Duration::from_secs(5)),
+            Err(ExtrapolationError::TooOld(10.0))
+        );
+
+        let no_velocity = make_solution(None);
+        assert_eq!(
+            no_velocity.compensate_latency(Duration::from_secs(1)),
+            Err(ExtrapolationError::NoVelocity)
+        );
+    }
+}
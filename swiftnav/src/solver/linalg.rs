@@ -0,0 +1,210 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Small dense matrix helpers shared by the estimators
+//!
+//! Only the handful of operations needed by the solvers are provided, the
+//! matrices involved are small enough that the straightforward algorithms are
+//! good enough.
+
+use std::ops::{Index, IndexMut};
+
+/// A dense, row major matrix
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Matrix {
+    rows: usize,
+    cols: usize,
+    data: Vec<f64>,
+}
+
+impl Matrix {
+    /// Makes a matrix filled with zeros
+    pub(crate) fn zeros(rows: usize, cols: usize) -> Matrix {
+        Matrix {
+            rows,
+            cols,
+            data: vec![0.0; rows * cols],
+        }
+    }
+
+    /// Makes a square identity matrix
+    pub(crate) fn identity(size: usize) -> Matrix {
+        let mut m = Matrix::zeros(size, size);
+        for i in 0..size {
+            m[(i, i)] = 1.0;
+        }
+        m
+    }
+
+    pub(crate) fn transpose(&self) -> Matrix {
+        let mut t = Matrix::zeros(self.cols, self.rows);
+        for i in 0..self.rows {
+            for j in 0..self.cols {
+                t[(j, i)] = self[(i, j)];
+            }
+        }
+        t
+    }
+
+    /// Computes the matrix product `self * rhs`
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the dimensions don't agree
+    pub(crate) fn mul(&self, rhs: &Matrix) -> Matrix {
+        assert_eq!(self.cols, rhs.rows, "Matrix dimensions don't agree");
+        let mut out = Matrix::zeros(self.rows, rhs.cols);
+        for i in 0..self.rows {
+            for k in 0..self.cols {
+                let a = self[(i, k)];
+                if a == 0.0 {
+                    continue;
+                }
+                for j in 0..rhs.cols {
+                    out[(i, j)] += a * rhs[(k, j)];
+                }
+            }
+        }
+        out
+    }
+
+    /// Computes the sum `self + rhs`
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the dimensions don't agree
+    pub(crate) fn add(&self, rhs: &Matrix) -> Matrix {
+        assert_eq!(
+            (self.rows, self.cols),
+            (rhs.rows, rhs.cols),
+            "Matrix dimensions don't agree"
+        );
+        Matrix {
+            rows: self.rows,
+            cols: self.cols,
+            data: self
+                .data
+                .iter()
+                .zip(rhs.data.iter())
+                .map(|(a, b)| a + b)
+                .collect(),
+        }
+    }
+
+    /// Computes the product with a vector, `self * v`
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the dimensions don't agree
+    pub(crate) fn mul_vec(&self, v: &[f64]) -> Vec<f64> {
+        assert_eq!(self.cols, v.len(), "Matrix dimensions don't agree");
+        (0..self.rows)
+            .map(|i| (0..self.cols).map(|j| self[(i, j)] * v[j]).sum())
+            .collect()
+    }
+
+    /// Inverts a square matrix using Gauss-Jordan elimination with partial
+    /// pivoting
+    ///
+    /// Returns `None` if the matrix isn't square or is numerically singular
+    pub(crate) fn inverse(&self) -> Option<Matrix> {
+        if self.rows != self.cols {
+            return None;
+        }
+        let n = self.rows;
+        let mut m = self.clone();
+        let mut inv = Matrix::identity(n);
+        let max_abs = self.data.iter().fold(0.0_f64, |acc, a| acc.max(a.abs()));
+        let tolerance = 1e-12 * max_abs.max(f64::MIN_POSITIVE);
+
+        for col in 0..n {
+            let pivot =
+                (col..n).max_by(|&a, &b| m[(a, col)].abs().total_cmp(&m[(b, col)].abs()))?;
+            if m[(pivot, col)].abs() <= tolerance {
+                return None;
+            }
+            m.swap_rows(col, pivot);
+            inv.swap_rows(col, pivot);
+
+            let scale = m[(col, col)];
+            for j in 0..n {
+                m[(col, j)] /= scale;
+                inv[(col, j)] /= scale;
+            }
+            for row in 0..n {
+                if row == col {
+                    continue;
+                }
+                let factor = m[(row, col)];
+                if factor == 0.0 {
+                    continue;
+                }
+                for j in 0..n {
+                    m[(row, j)] -= factor * m[(col, j)];
+                    inv[(row, j)] -= factor * inv[(col, j)];
+                }
+            }
+        }
+
+        Some(inv)
+    }
+
+    fn swap_rows(&mut self, a: usize, b: usize) {
+        if a != b {
+            for j in 0..self.cols {
+                self.data.swap(a * self.cols + j, b * self.cols + j);
+            }
+        }
+    }
+}
+
+impl Index<(usize, usize)> for Matrix {
+    type Output = f64;
+    fn index(&self, (row, col): (usize, usize)) -> &f64 {
+        debug_assert!(row < self.rows && col < self.cols);
+        &self.data[row * self.cols + col]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Matrix {
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut f64 {
+        debug_assert!(row < self.rows && col < self.cols);
+        &mut self.data[row * self.cols + col]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn inverse() {
+        let mut m = Matrix::zeros(3, 3);
+        let values = [[0.0, 2.0, 1.0], [1.0, 1.0, 0.0], [3.0, 0.0, 4.0]];
+        for (i, row) in values.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                m[(i, j)] = *value;
+            }
+        }
+
+        let inv = m.inverse().unwrap();
+        for i in 0..3 {
+            for j in 0..3 {
+                let product: f64 = (0..3).map(|k| m[(i, k)] * inv[(k, j)]).sum();
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert_float_eq!(product, expected, abs <= 1e-12);
+            }
+        }
+
+        let singular = Matrix::zeros(2, 2);
+        assert!(singular.inverse().is_none());
+        assert!(Matrix::zeros(2, 3).inverse().is_none());
+    }
+}
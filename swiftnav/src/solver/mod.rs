@@ -13,9 +13,28 @@
 //! same point in time can be processed to get an estimated PVT (position,
 //! velocity, and time) solution.
 
+pub mod filter;
+#[cfg(test)]
+mod fixtures;
+pub mod hint;
+pub mod ifb;
+pub mod latency;
+pub(crate) mod linalg;
+pub mod protection;
+pub mod pvt;
+pub mod raim;
+pub mod residuals;
+pub mod rtk;
+pub mod solution;
+pub(crate) mod stats;
+pub mod velocity;
+pub mod weighting;
+pub mod wls;
+
 use crate::coords::{LLHRadians, ECEF, NED};
 use crate::navmeas::NavigationMeasurement;
 use crate::signal::GnssSignal;
+use crate::solver::linalg::Matrix;
 use crate::time::GpsTime;
 use std::borrow::Cow;
 use std::ffi;
@@ -90,6 +109,24 @@ impl GnssSolution {
         }
     }
 
+    /// Computes the protection levels of the position solution from its
+    /// covariance
+    ///
+    /// Returns `None` if the position isn't valid
+    pub fn protection_levels(
+        &self,
+        settings: &protection::ProtectionLevelSettings,
+    ) -> Option<protection::ProtectionLevels> {
+        let position = self.pos_ecef()?;
+        let c = self.err_cov()?;
+        let covariance = [[c[0], c[1], c[2]], [c[1], c[3], c[4]], [c[2], c[4], c[5]]];
+        Some(protection::ProtectionLevels::from_covariance(
+            &position,
+            &covariance,
+            settings,
+        ))
+    }
+
     /// Gets the receiver velocity covariance matrix
     ///
     /// See [`GnssSolution::err_cov`] for representation, minus the DOP element
@@ -149,6 +186,19 @@ impl Dops {
         unsafe { std::mem::zeroed::<Dops>() }
     }
 
+    /// Computes the DOPs of a set of satellites as seen from a receiver
+    ///
+    /// All satellites are assumed to share a single receiver clock term.
+    /// Returns `None` if fewer than four satellites are given or if their
+    /// geometry doesn't allow a solution.
+    pub fn from_geometry(receiver: &ECEF, satellites: &[ECEF]) -> Option<Dops> {
+        let directions: Vec<NED> = satellites
+            .iter()
+            .map(|sat| (sat - receiver).ned_vector_at(receiver))
+            .collect();
+        compute_dops(&directions)
+    }
+
     /// Gets the position (3D) dilution of precision
     pub fn pdop(&self) -> f64 {
         self.0.pdop
@@ -175,8 +225,50 @@ impl Dops {
     }
 }
 
+/// Computes the DOPs of a set of satellites from their directions
+///
+/// The directions are either [`AzimuthElevation`](crate::coords::AzimuthElevation)s
+/// or line of sight vectors in the local north, east, down frame, of any
+/// non-zero length. This allows evaluating the geometry of a set of
+/// satellites without measurements, e.g. from an almanac. All satellites are assumed to share a single receiver
+/// clock term.
+///
+/// Returns `None` if fewer than four directions are given or if their
+/// geometry doesn't allow a solution.
+pub fn compute_dops<D>(directions: &[D]) -> Option<Dops>
+where
+    D: Copy + Into<NED>,
+{
+    if directions.len() < 4 {
+        return None;
+    }
+
+    // Normal matrix of the geometry
+    let mut normal = Matrix::zeros(4, 4);
+    for direction in directions {
+        let los: NED = (*direction).into();
+        let norm = (los.n() * los.n() + los.e() * los.e() + los.d() * los.d()).sqrt();
+        let row = [-los.n() / norm, -los.e() / norm, -los.d() / norm, 1.0];
+        for i in 0..4 {
+            for j in 0..4 {
+                normal[(i, j)] += row[i] * row[j];
+            }
+        }
+    }
+    let q = normal.inverse()?;
+
+    let mut dops = Dops::new();
+    dops.0.hdop = (q[(0, 0)] + q[(1, 1)]).sqrt();
+    dops.0.vdop = q[(2, 2)].sqrt();
+    dops.0.pdop = (q[(0, 0)] + q[(1, 1)] + q[(2, 2)]).sqrt();
+    dops.0.tdop = q[(3, 3)].sqrt();
+    dops.0.gdop = (q[(0, 0)] + q[(1, 1)] + q[(2, 2)] + q[(3, 3)]).sqrt();
+    Some(dops)
+}
+
 /// Different strategies of how to choose which measurements to use in a solution
 #[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ProcessingStrategy {
     GpsOnly,
     AllConstellations,
@@ -201,6 +293,7 @@ impl ProcessingStrategy {
 
 /// Holds the settings to customize how the GNSS solution is calculated
 #[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PvtSettings {
     strategy: ProcessingStrategy,
     disable_raim: bool,
@@ -391,6 +484,8 @@ impl PvtStatus {
 }
 
 /// Try to calculate a single point GNSS solution
+///
+/// See [`pvt::PvtSolver`] for a solver with more configuration options
 pub fn calc_pvt(
     measurements: &[NavigationMeasurement],
     tor: GpsTime,
@@ -434,11 +529,42 @@ pub fn calc_pvt(
     }
 }
 
+/// Try to calculate a single point GNSS solution, using the RAIM fault
+/// detection and exclusion in [`raim`] in place of the built in RAIM check
+///
+/// Any signals excluded by the RAIM check are removed before the solution is
+/// calculated. The report of the check, including the excluded signals and
+/// the protection levels, is returned alongside the solution. The RAIM setting
+/// in `settings` is ignored.
+pub fn calc_pvt_with_fde(
+    measurements: &[NavigationMeasurement],
+    tor: GpsTime,
+    settings: PvtSettings,
+    raim_settings: &raim::RaimSettings,
+) -> Result<(PvtStatus, GnssSolution, Dops, SidSet, raim::RaimReport), PvtError> {
+    let report = raim::fault_detection_exclusion(measurements, raim_settings)?;
+    let used: Vec<NavigationMeasurement> = measurements
+        .iter()
+        .filter(|nm| !report.excluded().contains(&nm.sid()))
+        .cloned()
+        .collect();
+
+    let (_, solution, dops, sidset) = calc_pvt(&used, tor, settings.disable_raim())?;
+    let status = if report.is_repaired() {
+        PvtStatus::RepairedSolution
+    } else {
+        PvtStatus::RaimPassed
+    };
+    Ok((status, solution, dops, sidset, report))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::coords::AzimuthElevation;
     use crate::ephemeris::SatelliteState;
     use crate::signal::Code;
+    use float_eq::assert_float_eq;
     use std::time::Duration;
 
     fn make_tor() -> GpsTime {
@@ -1263,6 +1389,43 @@ mod tests {
         );
     }
 
+    #[test]
+    fn dops_from_directions() {
+        // One satellite at the zenith and three on the horizon
+        let directions: Vec<AzimuthElevation> =
+            [(0.0, 90.0), (0.0, 0.0), (120.0, 0.0), (240.0, 0.0)]
+                .iter()
+                .map(|(az, el): &(f64, f64)| {
+                    AzimuthElevation::new(az.to_radians(), el.to_radians())
+                })
+                .collect();
+        let dops = compute_dops(&directions).unwrap();
+        assert_float_eq!(dops.hdop(), (4.0_f64 / 3.0).sqrt(), abs <= 1e-9);
+        assert_float_eq!(dops.vdop(), (4.0_f64 / 3.0).sqrt(), abs <= 1e-9);
+        assert_float_eq!(dops.pdop(), (8.0_f64 / 3.0).sqrt(), abs <= 1e-9);
+        assert_float_eq!(dops.tdop(), (1.0_f64 / 3.0).sqrt(), abs <= 1e-9);
+        assert_float_eq!(dops.gdop(), 3.0_f64.sqrt(), abs <= 1e-9);
+
+        // Line of sight vectors of any length give the same DOPs
+        let vectors: Vec<NED> = directions
+            .iter()
+            .map(|d| {
+                let ned = d.to_ned();
+                NED::new(20e6 * ned.n(), 20e6 * ned.e(), 20e6 * ned.d())
+            })
+            .collect();
+        assert_float_eq!(
+            compute_dops(&vectors).unwrap().gdop(),
+            dops.gdop(),
+            abs <= 1e-12
+        );
+
+        assert!(compute_dops(&directions[..3]).is_none());
+        // All on the horizon the vertical and clock terms can't be separated
+        let horizon = [directions[1], directions[2], directions[3], directions[1]];
+        assert!(compute_dops(&horizon).is_none());
+    }
+
     #[test]
     fn dops() {
         let truedops = Dops(swiftnav_sys::dops_t {
@@ -0,0 +1,176 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Protection levels from the solution covariance
+//!
+//! The horizontal and vertical protection levels (HPL/VPL) bound the position
+//! error with a given integrity risk, assuming the errors are zero mean and
+//! gaussian with the covariance of the solution. As in SBAS (RTCA DO-229) the
+//! horizontal level scales the semi-major axis of the horizontal error
+//! ellipse, and the vertical level scales the vertical standard deviation.
+//!
+//! These are complementary to the fault based protection levels computed by
+//! [RAIM](crate::solver::raim), which bound the error caused by an
+//! undetected single satellite fault.
+
+use crate::coords::{ecef_to_ned_covariance, PositionAccuracy, ECEF};
+use crate::solver::stats::normal_isf;
+
+/// Settings for computing protection levels
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProtectionLevelSettings {
+    horizontal_integrity_risk: f64,
+    vertical_integrity_risk: f64,
+}
+
+impl ProtectionLevelSettings {
+    /// Makes the default settings
+    ///
+    /// Note: The default settings consist of
+    ///  * A horizontal integrity risk of 1e-9
+    ///  * A vertical integrity risk of 1e-7
+    pub fn new() -> ProtectionLevelSettings {
+        ProtectionLevelSettings {
+            horizontal_integrity_risk: 1e-9,
+            vertical_integrity_risk: 1e-7,
+        }
+    }
+
+    /// Sets the probability of the horizontal error exceeding the HPL
+    pub fn set_horizontal_integrity_risk(
+        self,
+        horizontal_integrity_risk: f64,
+    ) -> ProtectionLevelSettings {
+        ProtectionLevelSettings {
+            horizontal_integrity_risk,
+            ..self
+        }
+    }
+
+    /// Sets the probability of the vertical error exceeding the VPL
+    pub fn set_vertical_integrity_risk(
+        self,
+        vertical_integrity_risk: f64,
+    ) -> ProtectionLevelSettings {
+        ProtectionLevelSettings {
+            vertical_integrity_risk,
+            ..self
+        }
+    }
+
+    pub fn horizontal_integrity_risk(&self) -> f64 {
+        self.horizontal_integrity_risk
+    }
+
+    pub fn vertical_integrity_risk(&self) -> f64 {
+        self.vertical_integrity_risk
+    }
+
+    /// Gets the multiplier applied to the semi-major axis of the horizontal
+    /// error ellipse
+    pub fn horizontal_k(&self) -> f64 {
+        normal_isf(0.5 * self.horizontal_integrity_risk)
+    }
+
+    /// Gets the multiplier applied to the vertical standard deviation
+    pub fn vertical_k(&self) -> f64 {
+        normal_isf(0.5 * self.vertical_integrity_risk)
+    }
+}
+
+impl Default for ProtectionLevelSettings {
+    fn default() -> ProtectionLevelSettings {
+        ProtectionLevelSettings::new()
+    }
+}
+
+/// Horizontal and vertical protection levels of a solution
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct ProtectionLevels {
+    hpl: f64,
+    vpl: f64,
+}
+
+impl ProtectionLevels {
+    /// Computes the protection levels of a position from its ECEF covariance,
+    /// in meters squared
+    pub fn from_covariance(
+        position: &ECEF,
+        covariance: &[[f64; 3]; 3],
+        settings: &ProtectionLevelSettings,
+    ) -> ProtectionLevels {
+        let ned = ecef_to_ned_covariance(covariance, &position.to_llh());
+        let accuracy = PositionAccuracy::from_ned_covariance(&ned);
+
+        ProtectionLevels {
+            hpl: settings.horizontal_k() * accuracy.ellipse().semi_major(),
+            vpl: settings.vertical_k() * accuracy.vertical_sigma(),
+        }
+    }
+
+    /// Gets the horizontal protection level, in meters
+    pub fn hpl(&self) -> f64 {
+        self.hpl
+    }
+
+    /// Gets the vertical protection level, in meters
+    pub fn vpl(&self) -> f64 {
+        self.vpl
+    }
+
+    /// Checks if the protection levels are within the given alert limits
+    ///
+    /// A solution whose protection levels exceed the alert limits shouldn't be
+    /// used for applications requiring integrity.
+    pub fn is_available(&self, horizontal_alert_limit: f64, vertical_alert_limit: f64) -> bool {
+        self.hpl <= horizontal_alert_limit && self.vpl <= vertical_alert_limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::{LLHDegrees, NED};
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn k_factors() {
+        let settings = ProtectionLevelSettings::new();
+        assert_float_eq!(settings.vertical_k(), 5.33, abs <= 0.01);
+        assert_float_eq!(settings.horizontal_k(), 6.11, abs <= 0.01);
+    }
+
+    #[test]
+    fn levels_from_covariance() {
+        let position = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        // Build an ECEF covariance from a known NED covariance, 2 m north,
+        // 1 m east and 3 m down standard deviations
+        let sigmas = [2.0, 1.0, 3.0];
+        let mut covariance = [[0.0; 3]; 3];
+        for (k, sigma) in sigmas.iter().enumerate() {
+            let mut ned = [0.0; 3];
+            ned[k] = 1.0;
+            let axis = NED::from_array(&ned).ecef_vector_at(&position);
+            let axis = axis.as_array_ref();
+            for i in 0..3 {
+                for j in 0..3 {
+                    covariance[i][j] += sigma * sigma * axis[i] * axis[j];
+                }
+            }
+        }
+
+        let settings = ProtectionLevelSettings::new();
+        let levels = ProtectionLevels::from_covariance(&position, &covariance, &settings);
+        assert_float_eq!(levels.hpl(), 2.0 * settings.horizontal_k(), abs <= 1e-6);
+        assert_float_eq!(levels.vpl(), 3.0 * settings.vertical_k(), abs <= 1e-6);
+        assert!(levels.is_available(40.0, 50.0));
+        assert!(!levels.is_available(10.0, 50.0));
+    }
+}
@@ -0,0 +1,422 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Configurable single epoch position solver
+//!
+//! Where [`calc_pvt`](crate::solver::calc_pvt) takes a fixed set of settings
+//! on every call, a [`PvtSolver`] is configured once through a
+//! [`PvtSolverBuilder`] and then reused for every epoch:
+//!
+//! ```
+//! use swiftnav::solver::pvt::{PvtSolver, TroposphereCorrection};
+//! use swiftnav::signal::Constellation;
+//!
+//! let solver = PvtSolver::builder()
+//!     .set_elevation_mask(10.0_f64.to_radians())
+//!     .set_constellations(&[Constellation::Gps, Constellation::Gal])
+//!     .set_troposphere(TroposphereCorrection::Unb3m)
+//!     .enable_raim()
+//!     .build();
+//! ```
+//!
+//! The solution is computed by the [weighted least squares](super::wls)
+//! solver, with the corrections and exclusions of the
+//! [residual report](super::residuals).
+
+use crate::coords::ECEF;
+use crate::ionosphere::Ionosphere;
+use crate::navmeas::NavigationMeasurement;
+use crate::signal::{Code, Constellation, GnssSignal};
+use crate::solver::ifb::GloIfbTable;
+use crate::solver::raim::RaimSettings;
+use crate::solver::residuals::{solve_screened, CorrectionSettings, ResidualReport, Screening};
+use crate::solver::wls::{Iteration, Weighting, WlsError, WlsSolution};
+use crate::time::GpsTime;
+use crate::troposphere::{self, Hopfield, Saastamoinen, TroposphereModel};
+
+/// Tropospheric model applied by a [`PvtSolver`]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TroposphereCorrection {
+    /// The UNB3m model, see [`troposphere::calc_delay()`]
+    Unb3m,
+    Saastamoinen(Saastamoinen),
+    Hopfield(Hopfield),
+}
+
+impl TroposphereModel for TroposphereCorrection {
+    fn calc_delay(&self, doy: f64, lat: f64, h: f64, el: f64) -> f64 {
+        match self {
+            TroposphereCorrection::Unb3m => troposphere::Unb3m.calc_delay(doy, lat, h, el),
+            TroposphereCorrection::Saastamoinen(model) => model.calc_delay(doy, lat, h, el),
+            TroposphereCorrection::Hopfield(model) => model.calc_delay(doy, lat, h, el),
+        }
+    }
+}
+
+/// Builds a configured [`PvtSolver`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PvtSolverBuilder {
+    solver: PvtSolver,
+}
+
+impl PvtSolverBuilder {
+    /// Makes a builder with the default settings
+    ///
+    /// Note: The default settings consist of
+    ///  * Using all constellations and codes
+    ///  * An elevation mask of 0 degrees
+    ///  * No ionospheric, tropospheric or GLONASS inter-frequency bias
+    ///    corrections
+    ///  * At most 20 iterations, stopping once the position changes by less
+    ///    than 0.1 mm
+    ///  * Disabling RAIM
+    ///  * No a priori position, the iteration starts at the center of the
+    ///    Earth
+    pub fn new() -> PvtSolverBuilder {
+        let iteration = Iteration::default();
+        PvtSolverBuilder {
+            solver: PvtSolver {
+                constellations: None,
+                codes: None,
+                elevation_mask: 0.0,
+                ionosphere: None,
+                troposphere: None,
+                glo_biases: None,
+                max_iterations: iteration.max_iterations,
+                convergence_threshold: iteration.convergence_threshold,
+                raim: None,
+                a_priori_position: None,
+            },
+        }
+    }
+
+    /// Only uses signals from the given constellations
+    pub fn set_constellations(mut self, constellations: &[Constellation]) -> PvtSolverBuilder {
+        self.solver.constellations = Some(constellations.to_vec());
+        self
+    }
+
+    /// Only uses signals with the given codes
+    pub fn set_codes(mut self, codes: &[Code]) -> PvtSolverBuilder {
+        self.solver.codes = Some(codes.to_vec());
+        self
+    }
+
+    /// Sets the lowest satellite elevation used, in radians
+    pub fn set_elevation_mask(mut self, elevation_mask: f64) -> PvtSolverBuilder {
+        self.solver.elevation_mask = elevation_mask;
+        self
+    }
+
+    /// Corrects the ionospheric delay with the given Klobuchar parameters
+    pub fn set_ionosphere(mut self, ionosphere: Ionosphere) -> PvtSolverBuilder {
+        self.solver.ionosphere = Some(ionosphere);
+        self
+    }
+
+    /// Corrects the tropospheric delay with the given model
+    pub fn set_troposphere(mut self, troposphere: TroposphereCorrection) -> PvtSolverBuilder {
+        self.solver.troposphere = Some(troposphere);
+        self
+    }
+
+    /// Corrects the GLONASS inter-frequency biases with the given table
+    pub fn set_glo_biases(mut self, glo_biases: GloIfbTable) -> PvtSolverBuilder {
+        self.solver.glo_biases = Some(glo_biases);
+        self
+    }
+
+    /// Sets the maximum number of least squares iterations
+    pub fn set_max_iterations(mut self, max_iterations: usize) -> PvtSolverBuilder {
+        self.solver.max_iterations = max_iterations;
+        self
+    }
+
+    /// Sets the size of the position update, in meters, below which the
+    /// iteration is considered converged
+    pub fn set_convergence_threshold(mut self, convergence_threshold: f64) -> PvtSolverBuilder {
+        self.solver.convergence_threshold = convergence_threshold;
+        self
+    }
+
+    /// Enables RAIM fault detection and exclusion with the default settings
+    ///
+    /// See [`raim`](super::raim) for more details
+    pub fn enable_raim(self) -> PvtSolverBuilder {
+        self.set_raim(RaimSettings::new())
+    }
+
+    /// Enables RAIM fault detection and exclusion with the given settings
+    pub fn set_raim(mut self, raim: RaimSettings) -> PvtSolverBuilder {
+        self.solver.raim = Some(raim);
+        self
+    }
+
+    /// Disables RAIM
+    pub fn disable_raim(mut self) -> PvtSolverBuilder {
+        self.solver.raim = None;
+        self
+    }
+
+    /// Sets an a priori position of the receiver
+    ///
+    /// The iteration starts from it, and the elevations and atmospheric
+    /// corrections are computed at it instead of at a first uncorrected
+    /// solution. The pre-fit residuals are taken against it, with the clock
+    /// bias of each constellation being the mean of its residuals.
+    pub fn set_a_priori_position(mut self, position: ECEF) -> PvtSolverBuilder {
+        self.solver.a_priori_position = Some(position);
+        self
+    }
+
+    /// Makes the configured solver
+    pub fn build(self) -> PvtSolver {
+        self.solver
+    }
+}
+
+impl Default for PvtSolverBuilder {
+    fn default() -> PvtSolverBuilder {
+        PvtSolverBuilder::new()
+    }
+}
+
+/// A configured single epoch position solver, see [`PvtSolverBuilder`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PvtSolver {
+    constellations: Option<Vec<Constellation>>,
+    codes: Option<Vec<Code>>,
+    elevation_mask: f64,
+    ionosphere: Option<Ionosphere>,
+    troposphere: Option<TroposphereCorrection>,
+    glo_biases: Option<GloIfbTable>,
+    max_iterations: usize,
+    convergence_threshold: f64,
+    raim: Option<RaimSettings>,
+    a_priori_position: Option<ECEF>,
+}
+
+impl PvtSolver {
+    /// Makes a builder to configure a solver
+    pub fn builder() -> PvtSolverBuilder {
+        PvtSolverBuilder::new()
+    }
+
+    /// Updates the Klobuchar parameters used to correct the ionospheric delay
+    pub fn set_ionosphere(&mut self, ionosphere: Ionosphere) {
+        self.ionosphere = Some(ionosphere);
+    }
+
+    /// Updates the GLONASS inter-frequency biases, e.g. with a new estimate
+    pub fn set_glo_biases(&mut self, glo_biases: GloIfbTable) {
+        self.glo_biases = Some(glo_biases);
+    }
+
+    /// Updates the a priori position of the receiver, e.g. with the position
+    /// of the previous epoch
+    pub fn set_a_priori_position(&mut self, position: Option<ECEF>) {
+        self.a_priori_position = position;
+    }
+
+    pub fn constellations(&self) -> Option<&[Constellation]> {
+        self.constellations.as_deref()
+    }
+
+    pub fn codes(&self) -> Option<&[Code]> {
+        self.codes.as_deref()
+    }
+
+    pub fn elevation_mask(&self) -> f64 {
+        self.elevation_mask
+    }
+
+    pub fn ionosphere(&self) -> Option<&Ionosphere> {
+        self.ionosphere.as_ref()
+    }
+
+    pub fn troposphere(&self) -> Option<&TroposphereCorrection> {
+        self.troposphere.as_ref()
+    }
+
+    pub fn glo_biases(&self) -> Option<&GloIfbTable> {
+        self.glo_biases.as_ref()
+    }
+
+    pub fn max_iterations(&self) -> usize {
+        self.max_iterations
+    }
+
+    pub fn convergence_threshold(&self) -> f64 {
+        self.convergence_threshold
+    }
+
+    pub fn raim(&self) -> Option<&RaimSettings> {
+        self.raim.as_ref()
+    }
+
+    pub fn a_priori_position(&self) -> Option<ECEF> {
+        self.a_priori_position
+    }
+
+    /// Checks if a signal passes the constellation and code allow-lists
+    pub fn is_allowed(&self, sid: GnssSignal) -> bool {
+        let constellation = match &self.constellations {
+            Some(constellations) => constellations.contains(&sid.to_constellation()),
+            None => true,
+        };
+        let code = match &self.codes {
+            Some(codes) => codes.contains(&sid.code()),
+            None => true,
+        };
+        constellation && code
+    }
+
+    /// Computes a position solution from the measurements of an epoch
+    pub fn solve(
+        &self,
+        measurements: &[NavigationMeasurement],
+        time: &GpsTime,
+    ) -> Result<WlsSolution, WlsError> {
+        self.solve_with_report(measurements, time)
+            .map(|(solution, _)| solution)
+    }
+
+    /// Computes a position solution along with a report of the residuals and
+    /// exclusions of every measurement
+    pub fn solve_with_report(
+        &self,
+        measurements: &[NavigationMeasurement],
+        time: &GpsTime,
+    ) -> Result<(WlsSolution, ResidualReport), WlsError> {
+        let mut settings = CorrectionSettings::new().set_elevation_mask(self.elevation_mask);
+        if let Some(ionosphere) = &self.ionosphere {
+            settings = settings.set_ionosphere(ionosphere);
+        }
+        if let Some(troposphere) = &self.troposphere {
+            settings = settings.set_troposphere(troposphere);
+        }
+        if let Some(glo_biases) = &self.glo_biases {
+            settings = settings.set_glo_biases(glo_biases);
+        }
+        if let Some(raim) = self.raim {
+            settings = settings.set_raim(raim);
+        }
+
+        let allowed = |sid: GnssSignal| self.is_allowed(sid);
+        let screening = Screening {
+            allowed: Some(&allowed),
+            a_priori_position: self.a_priori_position,
+            iteration: Iteration {
+                initial_position: ECEF::default(),
+                max_iterations: self.max_iterations,
+                convergence_threshold: self.convergence_threshold,
+            },
+        };
+        solve_screened(
+            measurements,
+            &Weighting::Uniform,
+            time,
+            &settings,
+            &screening,
+        )
+    }
+}
+
+impl Default for PvtSolver {
+    fn default() -> PvtSolver {
+        PvtSolverBuilder::new().build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::LLHDegrees;
+    use crate::ephemeris::SatelliteState;
+    use crate::solver::filter::geometry;
+    use crate::solver::fixtures::{extra_satellite, satellites};
+    use crate::solver::residuals::Exclusion;
+    use float_eq::assert_float_eq;
+
+    fn simulate_epoch(receiver: &ECEF, time: &GpsTime) -> Vec<NavigationMeasurement> {
+        let mut satellites: Vec<(u16, Code, ECEF)> = satellites()
+            .iter()
+            .map(|&(sat, pos)| (sat, Code::GpsL1ca, pos))
+            .collect();
+        satellites.push((3, Code::GalE1b, extra_satellite()));
+        let llh = receiver.to_llh();
+        let doy = time.to_utc_hardcoded().day_of_year() as f64;
+        let troposphere = Saastamoinen::new();
+
+        satellites
+            .iter()
+            .map(|(sat, code, pos)| {
+                let (range, _) = geometry(pos, receiver);
+                let el = receiver.azel_of(pos).el;
+                let delay = troposphere.calc_delay(doy, llh.latitude(), llh.height(), el);
+                let mut nm = NavigationMeasurement::new();
+                nm.set_sid(GnssSignal::new(*sat, *code).unwrap());
+                // The Galileo measurement is far off, to check it is left out
+                let offset = if *code == Code::GalE1b { 5000.0 } else { 0.0 };
+                nm.set_pseudorange(range + 100.0 + delay + offset);
+                nm.set_satellite_state(&SatelliteState {
+                    pos: *pos,
+                    vel: ECEF::default(),
+                    acc: ECEF::default(),
+                    clock_err: 0.0,
+                    clock_rate_err: 0.0,
+                    iodc: 0,
+                    iode: 0,
+                });
+                nm
+            })
+            .collect()
+    }
+
+    #[test]
+    fn configured_solver() {
+        let receiver = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let time = GpsTime::new(2200, 300_000.0).unwrap();
+        let measurements = simulate_epoch(&receiver, &time);
+
+        let mut solver = PvtSolver::builder()
+            .set_constellations(&[Constellation::Gps])
+            .set_troposphere(TroposphereCorrection::Saastamoinen(Saastamoinen::new()))
+            .set_convergence_threshold(1e-6)
+            .build();
+        assert!(solver.raim().is_none());
+        assert!(!solver.is_allowed(measurements[6].sid()));
+
+        let (solution, report) = solver.solve_with_report(&measurements, &time).unwrap();
+        assert_float_eq!(solution.position().x(), receiver.x(), abs <= 1e-2);
+        assert_float_eq!(solution.position().y(), receiver.y(), abs <= 1e-2);
+        assert_float_eq!(solution.position().z(), receiver.z(), abs <= 1e-2);
+        assert_eq!(solution.clock_biases().len(), 1);
+        assert_eq!(report.signals()[6].exclusion(), Some(Exclusion::NotAllowed));
+        assert_eq!(report.used().count(), 6);
+
+        // Starting from the previous solution takes fewer iterations
+        let first = solution.iterations();
+        solver.set_a_priori_position(Some(solution.position()));
+        let solution = solver.solve(&measurements, &time).unwrap();
+        assert!(solution.iterations() < first);
+        assert_float_eq!(solution.position().z(), receiver.z(), abs <= 1e-2);
+
+        // The iteration gives up once it runs out of iterations
+        let solver = PvtSolver::builder()
+            .set_codes(&[Code::GpsL1ca])
+            .set_max_iterations(2)
+            .build();
+        assert_eq!(
+            solver.solve(&measurements, &time),
+            Err(WlsError::FailedToConverge)
+        );
+    }
+}
@@ -0,0 +1,480 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Receiver autonomous integrity monitoring (RAIM)
+//!
+//! Implements residual based fault detection and exclusion (FDE) for single
+//! epoch pseudorange solutions. The sum of squared residuals of a least squares
+//! fit, normalized by the measurement variance, follows a chi-square
+//! distribution when all measurements are fault free. A fault is declared
+//! when it exceeds the threshold for the configured probability of false
+//! alarm, and satellites are then excluded one at a time, keeping the
+//! exclusion which best explains the residuals, until the test passes.
+//!
+//! Alongside the exclusions the horizontal and vertical protection levels of
+//! the final geometry are computed from the slopes of the individual
+//! measurements, i.e. the position error a bias on each measurement causes
+//! relative to its effect on the test statistic.
+//!
+//! # References
+//!   * "Global Positioning System: Theory and Applications, Volume II",
+//!     Chapter 5, B. Parkinson and J. Spilker (1996)
+//!   * "Integrity Monitoring", R. G. Brown, in "Understanding GPS: Principles
+//!     and Applications", E. Kaplan (2006)
+
+use crate::coords::ECEF;
+use crate::navmeas::NavigationMeasurement;
+use crate::signal::{Constellation, GnssSignal};
+use crate::solver::filter::geometry;
+use crate::solver::linalg::Matrix;
+use crate::solver::stats::{chi_square_isf, normal_isf};
+use crate::solver::PvtError;
+use crate::SPEED_OF_LIGHT;
+
+/// Settings for the RAIM fault detection and exclusion
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RaimSettings {
+    probability_false_alarm: f64,
+    probability_missed_detection: f64,
+    code_sigma: f64,
+    max_exclusions: usize,
+}
+
+impl RaimSettings {
+    /// Makes the default settings
+    ///
+    /// Note: The default settings consist of
+    ///  * A probability of false alarm of 1e-5
+    ///  * A probability of missed detection of 1e-3
+    ///  * A pseudorange standard deviation of 3 meters
+    ///  * Excluding at most one satellite
+    pub fn new() -> RaimSettings {
+        RaimSettings {
+            probability_false_alarm: 1e-5,
+            probability_missed_detection: 1e-3,
+            code_sigma: 3.0,
+            max_exclusions: 1,
+        }
+    }
+
+    /// Sets the probability of the detection test failing on fault free
+    /// measurements
+    pub fn set_probability_false_alarm(self, probability_false_alarm: f64) -> RaimSettings {
+        RaimSettings {
+            probability_false_alarm,
+            ..self
+        }
+    }
+
+    /// Sets the probability of a fault large enough to exceed the protection
+    /// levels going undetected
+    pub fn set_probability_missed_detection(
+        self,
+        probability_missed_detection: f64,
+    ) -> RaimSettings {
+        RaimSettings {
+            probability_missed_detection,
+            ..self
+        }
+    }
+
+    /// Sets the standard deviation of the pseudorange measurements, in meters
+    pub fn set_code_sigma(self, code_sigma: f64) -> RaimSettings {
+        RaimSettings { code_sigma, ..self }
+    }
+
+    /// Sets the maximum number of satellites which may be excluded
+    pub fn set_max_exclusions(self, max_exclusions: usize) -> RaimSettings {
+        RaimSettings {
+            max_exclusions,
+            ..self
+        }
+    }
+
+    pub fn probability_false_alarm(&self) -> f64 {
+        self.probability_false_alarm
+    }
+
+    pub fn probability_missed_detection(&self) -> f64 {
+        self.probability_missed_detection
+    }
+
+    pub fn code_sigma(&self) -> f64 {
+        self.code_sigma
+    }
+
+    pub fn max_exclusions(&self) -> usize {
+        self.max_exclusions
+    }
+}
+
+impl Default for RaimSettings {
+    fn default() -> RaimSettings {
+        RaimSettings::new()
+    }
+}
+
+/// Outcome of a successful RAIM check
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub struct RaimReport {
+    position: ECEF,
+    test_statistic: f64,
+    threshold: f64,
+    degrees_of_freedom: usize,
+    excluded: Vec<GnssSignal>,
+    hpl: f64,
+    vpl: f64,
+}
+
+impl RaimReport {
+    /// Gets the position estimated from the remaining measurements
+    pub fn position(&self) -> ECEF {
+        self.position
+    }
+
+    /// Gets the normalized sum of squared residuals of the final solution
+    pub fn test_statistic(&self) -> f64 {
+        self.test_statistic
+    }
+
+    /// Gets the detection threshold the test statistic was compared against
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// Gets the redundancy of the final solution
+    pub fn degrees_of_freedom(&self) -> usize {
+        self.degrees_of_freedom
+    }
+
+    /// Gets the signals which were excluded from the solution
+    pub fn excluded(&self) -> &[GnssSignal] {
+        &self.excluded
+    }
+
+    /// Checks if any measurements had to be excluded
+    pub fn is_repaired(&self) -> bool {
+        !self.excluded.is_empty()
+    }
+
+    /// Gets the horizontal protection level, in meters
+    pub fn hpl(&self) -> f64 {
+        self.hpl
+    }
+
+    /// Gets the vertical protection level, in meters
+    pub fn vpl(&self) -> f64 {
+        self.vpl
+    }
+}
+
+/// Least squares fit of a set of pseudoranges
+struct Fit {
+    position: ECEF,
+    test_statistic: f64,
+    degrees_of_freedom: usize,
+    /// Horizontal and vertical slope of each measurement
+    slopes: Vec<(f64, f64)>,
+}
+
+/// Runs fault detection and exclusion on a set of measurements
+///
+/// Only measurements with a valid pseudorange are considered. When a fault is
+/// detected all of the signals from the satellite whose exclusion best
+/// explains the residuals are removed, this is repeated up to the configured
+/// maximum number of exclusions.
+///
+/// # Errors
+///
+/// * [`PvtError::NotEnoughMeasurements`] if there are too few measurements
+///   for a solution
+/// * [`PvtError::FailedToConverge`] if the least squares fit fails
+/// * [`PvtError::RaimRepairImpossible`] if there is no redundancy to detect or
+///   exclude a fault
+/// * [`PvtError::RaimRepairFailed`] if no allowed exclusion passes the test
+pub fn fault_detection_exclusion(
+    measurements: &[NavigationMeasurement],
+    settings: &RaimSettings,
+) -> Result<RaimReport, PvtError> {
+    let mut remaining: Vec<&NavigationMeasurement> = measurements
+        .iter()
+        .filter(|nm| nm.pseudorange().is_some())
+        .collect();
+    if remaining.len() < 4 {
+        return Err(PvtError::NotEnoughMeasurements);
+    }
+
+    let mut fit = least_squares(&remaining, settings.code_sigma)?;
+    if fit.degrees_of_freedom == 0 {
+        return Err(PvtError::RaimRepairImpossible);
+    }
+
+    let mut excluded = Vec::new();
+    loop {
+        let threshold = chi_square_isf(settings.probability_false_alarm, fit.degrees_of_freedom);
+        if fit.test_statistic <= threshold {
+            let pbias = settings.code_sigma
+                * (threshold.sqrt() + normal_isf(settings.probability_missed_detection));
+            let hpl = fit.slopes.iter().fold(0.0_f64, |acc, s| acc.max(s.0)) * pbias;
+            let vpl = fit.slopes.iter().fold(0.0_f64, |acc, s| acc.max(s.1)) * pbias;
+            return Ok(RaimReport {
+                position: fit.position,
+                test_statistic: fit.test_statistic,
+                threshold,
+                degrees_of_freedom: fit.degrees_of_freedom,
+                excluded,
+                hpl,
+                vpl,
+            });
+        }
+        if excluded_satellites(&excluded) >= settings.max_exclusions {
+            return Err(PvtError::RaimRepairFailed);
+        }
+
+        // Try removing each satellite in turn, keeping the candidate with the
+        // smallest test statistic relative to its threshold
+        let mut satellites: Vec<(Constellation, u16)> =
+            remaining.iter().map(|nm| satellite(&nm.sid())).collect();
+        satellites.sort();
+        satellites.dedup();
+
+        let mut best: Option<(f64, (Constellation, u16), Fit)> = None;
+        for candidate in satellites {
+            let subset: Vec<&NavigationMeasurement> = remaining
+                .iter()
+                .copied()
+                .filter(|nm| satellite(&nm.sid()) != candidate)
+                .collect();
+            let candidate_fit = match least_squares(&subset, settings.code_sigma) {
+                Ok(candidate_fit) if candidate_fit.degrees_of_freedom > 0 => candidate_fit,
+                _ => continue,
+            };
+            let ratio = candidate_fit.test_statistic
+                / chi_square_isf(
+                    settings.probability_false_alarm,
+                    candidate_fit.degrees_of_freedom,
+                );
+            let better = match &best {
+                Some((best_ratio, _, _)) => ratio < *best_ratio,
+                None => true,
+            };
+            if better {
+                best = Some((ratio, candidate, candidate_fit));
+            }
+        }
+
+        let (_, satellite_excluded, best_fit) = best.ok_or(PvtError::RaimRepairImpossible)?;
+        remaining.retain(|nm| {
+            if satellite(&nm.sid()) == satellite_excluded {
+                excluded.push(nm.sid());
+                false
+            } else {
+                true
+            }
+        });
+        fit = best_fit;
+    }
+}
+
+fn satellite(sid: &GnssSignal) -> (Constellation, u16) {
+    (sid.to_constellation(), sid.sat())
+}
+
+fn excluded_satellites(excluded: &[GnssSignal]) -> usize {
+    let mut satellites: Vec<(Constellation, u16)> = excluded.iter().map(satellite).collect();
+    satellites.sort();
+    satellites.dedup();
+    satellites.len()
+}
+
+/// Fits a position and one clock bias per constellation to the pseudoranges
+fn least_squares(measurements: &[&NavigationMeasurement], sigma: f64) -> Result<Fit, PvtError> {
+    let mut constellations: Vec<Constellation> = measurements
+        .iter()
+        .map(|nm| nm.sid().to_constellation())
+        .collect();
+    constellations.sort();
+    constellations.dedup();
+
+    let states = 3 + constellations.len();
+    if measurements.len() < states {
+        return Err(PvtError::NotEnoughMeasurements);
+    }
+    let clock_index = |nm: &NavigationMeasurement| {
+        3 + constellations
+            .iter()
+            .position(|c| *c == nm.sid().to_constellation())
+            .expect("Constellation is present")
+    };
+
+    let mut x = vec![0.0; states];
+    let mut converged = false;
+    for _ in 0..20 {
+        let receiver = ECEF::new(x[0], x[1], x[2]);
+        let mut normal = Matrix::zeros(states, states);
+        let mut rhs = vec![0.0; states];
+        for nm in measurements {
+            let (range, los) = geometry(&nm.satellite_position(), &receiver);
+            let residual = corrected_pseudorange(nm) - (range + x[clock_index(nm)]);
+            let mut row = vec![0.0; states];
+            row[..3].copy_from_slice(&[-los[0], -los[1], -los[2]]);
+            row[clock_index(nm)] = 1.0;
+            for i in 0..states {
+                rhs[i] += row[i] * residual;
+                for j in 0..states {
+                    normal[(i, j)] += row[i] * row[j];
+                }
+            }
+        }
+
+        let dx = normal
+            .inverse()
+            .ok_or(PvtError::FailedToConverge)?
+            .mul_vec(&rhs);
+        for (state, delta) in x.iter_mut().zip(dx.iter()) {
+            *state += delta;
+        }
+        if (dx[0] * dx[0] + dx[1] * dx[1] + dx[2] * dx[2]).sqrt() < 1e-4 {
+            converged = true;
+            break;
+        }
+    }
+    if !converged {
+        return Err(PvtError::FailedToConverge);
+    }
+
+    // Geometry at the solution, with the position rotated into the local
+    // north, east, down frame so the slopes split into horizontal and
+    // vertical components
+    let receiver = ECEF::new(x[0], x[1], x[2]);
+    let mut h = Matrix::zeros(measurements.len(), states);
+    let mut test_statistic = 0.0;
+    for (k, nm) in measurements.iter().enumerate() {
+        let (range, _) = geometry(&nm.satellite_position(), &receiver);
+        let residual = corrected_pseudorange(nm) - (range + x[clock_index(nm)]);
+        test_statistic += residual * residual / (sigma * sigma);
+
+        let los = (nm.satellite_position() - receiver).ned_vector_at(&receiver);
+        let norm = (los.n() * los.n() + los.e() * los.e() + los.d() * los.d()).sqrt();
+        h[(k, 0)] = -los.n() / norm;
+        h[(k, 1)] = -los.e() / norm;
+        h[(k, 2)] = -los.d() / norm;
+        h[(k, clock_index(nm))] = 1.0;
+    }
+    let ht = h.transpose();
+    let a = ht
+        .mul(&h)
+        .inverse()
+        .ok_or(PvtError::FailedToConverge)?
+        .mul(&ht);
+
+    let slopes = (0..measurements.len())
+        .map(|k| {
+            let hat: f64 = (0..states).map(|s| h[(k, s)] * a[(s, k)]).sum();
+            let redundancy = (1.0 - hat).max(0.0).sqrt();
+            if redundancy < 1e-9 {
+                (f64::INFINITY, f64::INFINITY)
+            } else {
+                (
+                    (a[(0, k)].powi(2) + a[(1, k)].powi(2)).sqrt() / redundancy,
+                    a[(2, k)].abs() / redundancy,
+                )
+            }
+        })
+        .collect();
+
+    Ok(Fit {
+        position: receiver,
+        test_statistic,
+        degrees_of_freedom: measurements.len() - states,
+        slopes,
+    })
+}
+
+fn corrected_pseudorange(nm: &NavigationMeasurement) -> f64 {
+    nm.pseudorange().unwrap_or_default() + SPEED_OF_LIGHT * nm.satellite_clock_error()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::LLHDegrees;
+    use crate::ephemeris::SatelliteState;
+    use crate::signal::Code;
+    use crate::solver::fixtures::{extra_satellite, satellites};
+    use float_eq::assert_float_eq;
+
+    fn simulate_epoch(receiver: &ECEF, clock_bias: f64) -> Vec<NavigationMeasurement> {
+        let mut satellites = satellites().to_vec();
+        satellites.push((31, extra_satellite()));
+        // Small deterministic noise so the test statistic isn't zero
+        let noise = [0.5, -1.0, 0.8, -0.3, 1.2, -0.7, 0.2];
+
+        satellites
+            .iter()
+            .zip(noise.iter())
+            .map(|((sat, pos), noise)| {
+                let (range, _) = geometry(pos, receiver);
+                let mut nm = NavigationMeasurement::new();
+                nm.set_sid(GnssSignal::new(*sat, Code::GpsL1ca).unwrap());
+                nm.set_pseudorange(range + clock_bias + noise);
+                nm.set_satellite_state(&SatelliteState {
+                    pos: *pos,
+                    vel: ECEF::default(),
+                    acc: ECEF::default(),
+                    clock_err: 0.0,
+                    clock_rate_err: 0.0,
+                    iodc: 0,
+                    iode: 0,
+                });
+                nm
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fault_free() {
+        let receiver = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let measurements = simulate_epoch(&receiver, 1000.0);
+
+        let report = fault_detection_exclusion(&measurements, &RaimSettings::new()).unwrap();
+        assert!(!report.is_repaired());
+        assert_eq!(report.degrees_of_freedom(), 3);
+        assert!(report.test_statistic() < report.threshold());
+        assert_float_eq!(report.position().x(), receiver.x(), abs <= 5.0);
+        assert!(report.hpl() > 0.0 && report.hpl().is_finite());
+        assert!(report.vpl() > 0.0 && report.vpl().is_finite());
+    }
+
+    #[test]
+    fn excludes_faulty_satellite() {
+        let receiver = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let mut measurements = simulate_epoch(&receiver, 1000.0);
+        let faulty = measurements[2].sid();
+        let pseudorange = measurements[2].pseudorange().unwrap();
+        measurements[2].set_pseudorange(pseudorange + 200.0);
+
+        let report = fault_detection_exclusion(&measurements, &RaimSettings::new()).unwrap();
+        assert!(report.is_repaired());
+        assert_eq!(report.excluded(), &[faulty]);
+        assert_eq!(report.degrees_of_freedom(), 2);
+        assert_float_eq!(report.position().z(), receiver.z(), abs <= 5.0);
+
+        let settings = RaimSettings::new().set_max_exclusions(0);
+        assert_eq!(
+            fault_detection_exclusion(&measurements, &settings),
+            Err(PvtError::RaimRepairFailed)
+        );
+        assert_eq!(
+            fault_detection_exclusion(&measurements[..4], &settings),
+            Err(PvtError::RaimRepairImpossible)
+        );
+    }
+}
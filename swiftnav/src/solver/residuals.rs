@@ -0,0 +1,617 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Measurement residual analysis
+//!
+//! [`solve_wls_with_report`] runs the [weighted least squares](super::wls)
+//! solver with atmospheric corrections, an elevation mask and optionally RAIM
+//! applied, and reports what happened to every measurement along the way:
+//! the corrections applied to it, whether and why it was excluded, and its
+//! residuals before and after the fit.
+//!
+//! The pre-fit residuals are taken against an a priori solution which only
+//! corrects for the satellite clocks, while the post-fit residuals are taken
+//! against the final solution. Excluded measurements still get residuals when
+//! possible, which makes it easy to see how far off they were.
+
+use crate::coords::ECEF;
+use crate::ionosphere::Ionosphere;
+use crate::navmeas::NavigationMeasurement;
+use crate::signal::{Constellation, GnssSignal};
+use crate::solver::filter::geometry;
+use crate::solver::ifb::GloIfbTable;
+use crate::solver::raim::{fault_detection_exclusion, RaimReport, RaimSettings};
+use crate::solver::wls::{solve_wls_iterated, Iteration, Weighting, WlsError, WlsSolution};
+use crate::solver::PvtError;
+use crate::time::GpsTime;
+use crate::troposphere::TroposphereModel;
+use crate::SPEED_OF_LIGHT;
+
+const GPS_L1_HZ: f64 = 1.57542e9;
+
+/// Corrections and screening applied by [`solve_wls_with_report`]
+#[derive(Clone, Copy)]
+pub struct CorrectionSettings<'a> {
+    ionosphere: Option<&'a Ionosphere>,
+    troposphere: Option<&'a dyn TroposphereModel>,
+    glo_biases: Option<&'a GloIfbTable>,
+    elevation_mask: f64,
+    raim: Option<RaimSettings>,
+}
+
+impl<'a> CorrectionSettings<'a> {
+    /// Makes the default settings
+    ///
+    /// Note: The default settings consist of
+    ///  * No ionospheric, tropospheric or GLONASS inter-frequency bias
+    ///    corrections
+    ///  * An elevation mask of 0 degrees
+    ///  * No RAIM
+    pub fn new() -> CorrectionSettings<'a> {
+        CorrectionSettings {
+            ionosphere: None,
+            troposphere: None,
+            glo_biases: None,
+            elevation_mask: 0.0,
+            raim: None,
+        }
+    }
+
+    /// Sets the Klobuchar parameters used to correct the ionospheric delay
+    pub fn set_ionosphere(self, ionosphere: &'a Ionosphere) -> CorrectionSettings<'a> {
+        CorrectionSettings {
+            ionosphere: Some(ionosphere),
+            ..self
+        }
+    }
+
+    /// Sets the model used to correct the tropospheric delay
+    pub fn set_troposphere(self, troposphere: &'a dyn TroposphereModel) -> CorrectionSettings<'a> {
+        CorrectionSettings {
+            troposphere: Some(troposphere),
+            ..self
+        }
+    }
+
+    /// Sets the GLONASS inter-frequency biases to correct
+    pub fn set_glo_biases(self, glo_biases: &'a GloIfbTable) -> CorrectionSettings<'a> {
+        CorrectionSettings {
+            glo_biases: Some(glo_biases),
+            ..self
+        }
+    }
+
+    /// Sets the lowest satellite elevation used, in radians
+    pub fn set_elevation_mask(self, elevation_mask: f64) -> CorrectionSettings<'a> {
+        CorrectionSettings {
+            elevation_mask,
+            ..self
+        }
+    }
+
+    /// Sets the RAIM settings, enabling fault detection and exclusion
+    pub fn set_raim(self, raim: RaimSettings) -> CorrectionSettings<'a> {
+        CorrectionSettings {
+            raim: Some(raim),
+            ..self
+        }
+    }
+
+    pub fn ionosphere(&self) -> Option<&'a Ionosphere> {
+        self.ionosphere
+    }
+
+    pub fn troposphere(&self) -> Option<&'a dyn TroposphereModel> {
+        self.troposphere
+    }
+
+    pub fn glo_biases(&self) -> Option<&'a GloIfbTable> {
+        self.glo_biases
+    }
+
+    pub fn elevation_mask(&self) -> f64 {
+        self.elevation_mask
+    }
+
+    pub fn raim(&self) -> Option<&RaimSettings> {
+        self.raim.as_ref()
+    }
+}
+
+impl<'a> Default for CorrectionSettings<'a> {
+    fn default() -> CorrectionSettings<'a> {
+        CorrectionSettings::new()
+    }
+}
+
+/// Corrections applied to a pseudorange, in meters
+///
+/// The satellite clock correction is added to the pseudorange, while the
+/// atmospheric delays and receiver biases are subtracted from it.
+#[derive(Debug, Copy, Clone, Default, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SignalCorrections {
+    pub satellite_clock: f64,
+    pub ionosphere: f64,
+    pub troposphere: f64,
+    pub inter_frequency_bias: f64,
+}
+
+/// Reason a measurement wasn't used in the solution
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Exclusion {
+    /// The measurement has no valid pseudorange
+    NoPseudorange,
+    /// The signal isn't one the solver is configured to use
+    NotAllowed,
+    /// The satellite is below the elevation mask
+    ElevationMask,
+    /// RAIM excluded the satellite as faulty
+    Raim,
+}
+
+/// Residual analysis of a single measurement
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct SignalResidual {
+    sid: GnssSignal,
+    elevation: f64,
+    corrections: SignalCorrections,
+    pre_fit: Option<f64>,
+    post_fit: Option<f64>,
+    exclusion: Option<Exclusion>,
+}
+
+impl SignalResidual {
+    pub fn sid(&self) -> GnssSignal {
+        self.sid
+    }
+
+    /// Gets the satellite elevation seen from the a priori position, in
+    /// radians
+    pub fn elevation(&self) -> f64 {
+        self.elevation
+    }
+
+    /// Gets the corrections applied to the pseudorange
+    pub fn corrections(&self) -> &SignalCorrections {
+        &self.corrections
+    }
+
+    /// Gets the corrected pseudorange minus the range and clock bias of the a
+    /// priori solution, in meters
+    pub fn pre_fit(&self) -> Option<f64> {
+        self.pre_fit
+    }
+
+    /// Gets the corrected pseudorange minus the range and clock bias of the
+    /// final solution, in meters
+    ///
+    /// Returns `None` if there is no pseudorange, or if the solution has no
+    /// clock bias for the constellation because all of its measurements were
+    /// excluded
+    pub fn post_fit(&self) -> Option<f64> {
+        self.post_fit
+    }
+
+    /// Gets the reason the measurement was excluded, if it was
+    pub fn exclusion(&self) -> Option<Exclusion> {
+        self.exclusion
+    }
+
+    /// Checks if the measurement was used in the solution
+    pub fn is_used(&self) -> bool {
+        self.exclusion.is_none()
+    }
+}
+
+/// Residual analysis of a solution
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub struct ResidualReport {
+    a_priori_position: ECEF,
+    signals: Vec<SignalResidual>,
+    raim: Option<Result<RaimReport, PvtError>>,
+}
+
+impl ResidualReport {
+    /// Gets the position of the a priori solution, which the corrections and
+    /// pre-fit residuals are computed at
+    pub fn a_priori_position(&self) -> ECEF {
+        self.a_priori_position
+    }
+
+    /// Gets the analysis of every measurement, in the order they were given
+    pub fn signals(&self) -> &[SignalResidual] {
+        &self.signals
+    }
+
+    /// Gets the analysis of a signal
+    pub fn signal(&self, sid: GnssSignal) -> Option<&SignalResidual> {
+        self.signals.iter().find(|s| s.sid == sid)
+    }
+
+    /// Gets the measurements used in the solution
+    pub fn used(&self) -> impl Iterator<Item = &SignalResidual> {
+        self.signals.iter().filter(|s| s.is_used())
+    }
+
+    /// Gets the measurements excluded from the solution
+    pub fn excluded(&self) -> impl Iterator<Item = &SignalResidual> {
+        self.signals.iter().filter(|s| !s.is_used())
+    }
+
+    /// Gets the outcome of RAIM, if it was enabled
+    ///
+    /// When RAIM fails no measurements are excluded by it and the solution
+    /// uses all of the remaining measurements.
+    pub fn raim(&self) -> Option<&Result<RaimReport, PvtError>> {
+        self.raim.as_ref()
+    }
+}
+
+/// Computes a position solution along with a report of the residuals
+///
+/// An a priori solution correcting only the satellite clocks is computed
+/// first, the satellite elevations and atmospheric delays are evaluated at its
+/// position. Measurements below the elevation mask are then excluded, RAIM is
+/// run on the corrected measurements if enabled, and the final solution is
+/// computed from what remains. The weighting must line up with
+/// `measurements`, as for [`solve_wls`](super::wls::solve_wls).
+///
+/// # Panics
+///
+/// This function will panic if a tropospheric model is set and `time` is not
+/// valid
+pub fn solve_wls_with_report(
+    measurements: &[NavigationMeasurement],
+    weighting: &Weighting,
+    time: &GpsTime,
+    settings: &CorrectionSettings,
+) -> Result<(WlsSolution, ResidualReport), WlsError> {
+    solve_screened(
+        measurements,
+        weighting,
+        time,
+        settings,
+        &Screening::default(),
+    )
+}
+
+/// Additional screening and iteration options of the solution
+#[derive(Default)]
+pub(crate) struct Screening<'a> {
+    /// Signals which may be used, all signals if `None`
+    pub(crate) allowed: Option<&'a dyn Fn(GnssSignal) -> bool>,
+    /// Position the corrections are computed at, in place of the a priori
+    /// solution
+    pub(crate) a_priori_position: Option<ECEF>,
+    pub(crate) iteration: Iteration,
+}
+
+/// Computes a position solution with a report of the residuals, see
+/// [`solve_wls_with_report`]
+pub(crate) fn solve_screened(
+    measurements: &[NavigationMeasurement],
+    weighting: &Weighting,
+    time: &GpsTime,
+    settings: &CorrectionSettings,
+    screening: &Screening,
+) -> Result<(WlsSolution, ResidualReport), WlsError> {
+    let mut screened = measurements.to_vec();
+    let mut exclusions: Vec<Option<Exclusion>> = measurements
+        .iter()
+        .map(|nm| {
+            if nm.pseudorange().is_none() {
+                Some(Exclusion::NoPseudorange)
+            } else if matches!(screening.allowed, Some(allowed) if !allowed(nm.sid())) {
+                Some(Exclusion::NotAllowed)
+            } else {
+                None
+            }
+        })
+        .collect();
+    for (nm, exclusion) in screened.iter_mut().zip(exclusions.iter()) {
+        if exclusion.is_some() {
+            nm.invalidate_pseudorange();
+        }
+    }
+
+    let (receiver, a_priori) = match screening.a_priori_position {
+        Some(position) => (position, None),
+        None => {
+            let iteration = Iteration {
+                initial_position: ECEF::default(),
+                ..screening.iteration
+            };
+            let solution = solve_wls_iterated(&screened, weighting, &iteration)?;
+            (solution.position(), Some(solution))
+        }
+    };
+    let llh = receiver.to_llh();
+    let doy = settings
+        .troposphere
+        .map(|_| time.to_utc_hardcoded().day_of_year() as f64);
+
+    let mut corrected = measurements.to_vec();
+    let mut signals: Vec<SignalResidual> = Vec::with_capacity(measurements.len());
+    for ((nm, corrected), exclusion) in measurements
+        .iter()
+        .zip(corrected.iter_mut())
+        .zip(exclusions.iter_mut())
+    {
+        let azel = receiver.azel_of(&nm.satellite_position());
+        let ionosphere = settings.ionosphere.map_or(0.0, |ionosphere| {
+            ionosphere.calc_delay(time, llh.latitude(), llh.longitude(), azel.az, azel.el)
+                * (GPS_L1_HZ / nm.sid().carrier_frequency()).powi(2)
+        });
+        let troposphere = settings.troposphere.map_or(0.0, |troposphere| {
+            troposphere.calc_delay(
+                doy.unwrap_or_default(),
+                llh.latitude(),
+                llh.height(),
+                azel.el,
+            )
+        });
+        let inter_frequency_bias = settings
+            .glo_biases
+            .and_then(|biases| biases.bias(nm.sid()))
+            .unwrap_or_default();
+        if let Some(pseudorange) = nm.pseudorange() {
+            corrected
+                .set_pseudorange(pseudorange - ionosphere - troposphere - inter_frequency_bias);
+        }
+        if exclusion.is_none() && azel.el < settings.elevation_mask {
+            *exclusion = Some(Exclusion::ElevationMask);
+        }
+
+        signals.push(SignalResidual {
+            sid: nm.sid(),
+            elevation: azel.el,
+            corrections: SignalCorrections {
+                satellite_clock: SPEED_OF_LIGHT * nm.satellite_clock_error(),
+                ionosphere,
+                troposphere,
+                inter_frequency_bias,
+            },
+            pre_fit: None,
+            post_fit: None,
+            exclusion: *exclusion,
+        });
+    }
+
+    let a_priori_clocks = match &a_priori {
+        Some(solution) => solution.clock_biases().to_vec(),
+        None => mean_clock_biases(&corrected, &exclusions, &receiver),
+    };
+    for (signal, nm) in signals.iter_mut().zip(corrected.iter()) {
+        signal.pre_fit = residual(nm, &receiver, &a_priori_clocks);
+    }
+
+    let mut screened = corrected.clone();
+    for (nm, signal) in screened.iter_mut().zip(signals.iter()) {
+        if !signal.is_used() {
+            nm.invalidate_pseudorange();
+        }
+    }
+
+    let raim = settings.raim.map(|raim| {
+        let outcome = fault_detection_exclusion(&screened, &raim);
+        if let Ok(report) = &outcome {
+            for (nm, signal) in screened.iter_mut().zip(signals.iter_mut()) {
+                if report.excluded().contains(&signal.sid) && signal.is_used() {
+                    signal.exclusion = Some(Exclusion::Raim);
+                    nm.invalidate_pseudorange();
+                }
+            }
+        }
+        outcome
+    });
+
+    let iteration = Iteration {
+        initial_position: receiver,
+        ..screening.iteration
+    };
+    let solution = solve_wls_iterated(&screened, weighting, &iteration)?;
+    for (signal, nm) in signals.iter_mut().zip(corrected.iter()) {
+        signal.post_fit = residual(nm, &solution.position(), solution.clock_biases());
+    }
+
+    Ok((
+        solution,
+        ResidualReport {
+            a_priori_position: receiver,
+            signals,
+            raim,
+        },
+    ))
+}
+
+/// Estimates the clock bias of each constellation, in seconds, as the mean
+/// residual of its usable measurements at a position
+fn mean_clock_biases(
+    measurements: &[NavigationMeasurement],
+    exclusions: &[Option<Exclusion>],
+    receiver: &ECEF,
+) -> Vec<(Constellation, f64)> {
+    let mut sums: Vec<(Constellation, f64, usize)> = Vec::new();
+    for (nm, _) in measurements
+        .iter()
+        .zip(exclusions.iter())
+        .filter(|(_, exclusion)| exclusion.is_none())
+    {
+        let constellation = nm.sid().to_constellation();
+        let offset = residual(nm, receiver, &[(constellation, 0.0)]).unwrap_or_default();
+        match sums.iter_mut().find(|(c, _, _)| *c == constellation) {
+            Some((_, sum, count)) => {
+                *sum += offset;
+                *count += 1;
+            }
+            None => sums.push((constellation, offset, 1)),
+        }
+    }
+    sums.into_iter()
+        .map(|(c, sum, count)| (c, sum / count as f64 / SPEED_OF_LIGHT))
+        .collect()
+}
+
+/// Computes the residual of a corrected measurement against a solution
+fn residual(
+    nm: &NavigationMeasurement,
+    receiver: &ECEF,
+    clock_biases: &[(Constellation, f64)],
+) -> Option<f64> {
+    let pseudorange = nm.pseudorange()?;
+    let clock_bias = clock_biases
+        .iter()
+        .find(|(c, _)| *c == nm.sid().to_constellation())?
+        .1;
+    let (range, _) = geometry(&nm.satellite_position(), receiver);
+    Some(
+        pseudorange + SPEED_OF_LIGHT * nm.satellite_clock_error()
+            - (range + SPEED_OF_LIGHT * clock_bias),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::LLHDegrees;
+    use crate::ephemeris::SatelliteState;
+    use crate::signal::Code;
+    use crate::solver::fixtures::{extra_satellite, satellites};
+    use crate::troposphere::Saastamoinen;
+    use float_eq::assert_float_eq;
+
+    const D2R: f64 = std::f64::consts::PI / 180.0;
+
+    fn simulate_epoch(
+        receiver: &ECEF,
+        time: &GpsTime,
+        clock_bias: f64,
+        troposphere: &dyn TroposphereModel,
+    ) -> Vec<NavigationMeasurement> {
+        let mut satellites = satellites().to_vec();
+        satellites.push((31, extra_satellite()));
+        let llh = receiver.to_llh();
+        let doy = time.to_utc_hardcoded().day_of_year() as f64;
+
+        satellites
+            .iter()
+            .map(|(sat, pos)| {
+                let (range, _) = geometry(pos, receiver);
+                let el = receiver.azel_of(pos).el;
+                let delay = troposphere.calc_delay(doy, llh.latitude(), llh.height(), el);
+                let mut nm = NavigationMeasurement::new();
+                nm.set_sid(GnssSignal::new(*sat, Code::GpsL1ca).unwrap());
+                nm.set_pseudorange(range + clock_bias + delay - 20.0);
+                nm.set_satellite_state(&SatelliteState {
+                    pos: *pos,
+                    vel: ECEF::default(),
+                    acc: ECEF::default(),
+                    clock_err: -20.0 / SPEED_OF_LIGHT,
+                    clock_rate_err: 0.0,
+                    iodc: 0,
+                    iode: 0,
+                });
+                nm
+            })
+            .collect()
+    }
+
+    #[test]
+    fn corrections_and_residuals() {
+        let receiver = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let time = GpsTime::new(2200, 300_000.0).unwrap();
+        let troposphere = Saastamoinen::new();
+        let mut measurements = simulate_epoch(&receiver, &time, 300.0, &troposphere);
+        measurements[0].invalidate_pseudorange();
+
+        let settings = CorrectionSettings::new().set_troposphere(&troposphere);
+        let (solution, report) =
+            solve_wls_with_report(&measurements, &Weighting::Uniform, &time, &settings).unwrap();
+        assert_float_eq!(solution.position().x(), receiver.x(), abs <= 1e-2);
+        assert_float_eq!(solution.position().y(), receiver.y(), abs <= 1e-2);
+        assert_float_eq!(solution.position().z(), receiver.z(), abs <= 1e-2);
+
+        assert_eq!(report.signals().len(), measurements.len());
+        assert_eq!(report.used().count(), 6);
+        let missing = &report.signals()[0];
+        assert_eq!(missing.exclusion(), Some(Exclusion::NoPseudorange));
+        assert_eq!(missing.pre_fit(), None);
+        assert_eq!(missing.post_fit(), None);
+        assert!(report.raim().is_none());
+
+        let mut pre_fit = 0.0;
+        for signal in report.used() {
+            assert_float_eq!(signal.corrections().satellite_clock, -20.0, abs <= 1e-6);
+            assert_eq!(signal.corrections().ionosphere, 0.0);
+            assert!(signal.corrections().troposphere > 2.0);
+            assert_float_eq!(signal.post_fit().unwrap(), 0.0, abs <= 1e-2);
+            pre_fit += signal.pre_fit().unwrap().abs();
+        }
+        // The a priori solution doesn't model the troposphere, which shows in
+        // the pre-fit residuals
+        assert!(pre_fit > 0.1);
+    }
+
+    #[test]
+    fn exclusions() {
+        let receiver = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let time = GpsTime::new(2200, 300_000.0).unwrap();
+        let troposphere = Saastamoinen::new();
+        let mut measurements = simulate_epoch(&receiver, &time, 300.0, &troposphere);
+        let lowest = measurements
+            .iter()
+            .map(|nm| (receiver.azel_of(&nm.satellite_position()).el, nm.sid()))
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+            .unwrap();
+        let faulty = measurements[2].sid();
+        let pseudorange = measurements[2].pseudorange().unwrap();
+        measurements[2].set_pseudorange(pseudorange + 200.0);
+
+        let settings = CorrectionSettings::new()
+            .set_troposphere(&troposphere)
+            .set_elevation_mask(lowest.0 + 0.1 * D2R)
+            .set_raim(RaimSettings::new());
+        let (solution, report) =
+            solve_wls_with_report(&measurements, &Weighting::Uniform, &time, &settings).unwrap();
+        // The fault pulls the a priori position the delays are computed at
+        assert_float_eq!(solution.position().z(), receiver.z(), abs <= 0.5);
+
+        let masked = report.signal(lowest.1).unwrap();
+        assert_eq!(masked.exclusion(), Some(Exclusion::ElevationMask));
+        assert!(masked.post_fit().unwrap().abs() < 0.5);
+
+        let raim = report.raim().unwrap().as_ref().unwrap();
+        assert_eq!(raim.excluded(), &[faulty]);
+        let excluded = report.signal(faulty).unwrap();
+        assert_eq!(excluded.exclusion(), Some(Exclusion::Raim));
+        assert_float_eq!(excluded.post_fit().unwrap(), 200.0, abs <= 0.5);
+        assert_eq!(report.used().count(), 5);
+        assert_eq!(report.excluded().count(), 2);
+
+        // Without RAIM the fault shows in the residuals of the solution
+        let settings = settings.set_elevation_mask(0.0);
+        let settings = CorrectionSettings {
+            raim: None,
+            ..settings
+        };
+        let (_, report) =
+            solve_wls_with_report(&measurements, &Weighting::Uniform, &time, &settings).unwrap();
+        assert_eq!(report.excluded().count(), 0);
+        let largest = report
+            .used()
+            .max_by(|a, b| {
+                let size = |s: &SignalResidual| s.post_fit().unwrap().abs();
+                size(a).partial_cmp(&size(b)).unwrap()
+            })
+            .unwrap();
+        assert_eq!(largest.sid(), faulty);
+    }
+}
@@ -0,0 +1,547 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Single epoch RTK float solution
+//!
+//! Estimates the baseline between a base station with a known position and a
+//! rover from a single epoch of
+//! [double differenced](crate::navmeas::differences) pseudorange and carrier
+//! phase observations. The carrier phase ambiguities are estimated as real
+//! valued (float) parameters alongside the baseline, no attempt is made at
+//! fixing them to integers.
+//!
+//! The observations are weighted using the full double difference covariance,
+//! which accounts for the correlation introduced by sharing a reference
+//! satellite. The carrier phase is expected to be in cycles, increasing with
+//! the range to the satellite. GLONASS observations are ignored since their
+//! double differences include inter-frequency biases.
+
+use crate::coords::ECEF;
+use crate::navmeas::differences::{DifferencedEpoch, ReferenceSelection};
+use crate::navmeas::NavigationMeasurement;
+use crate::signal::{Code, Constellation, GnssSignal};
+use crate::solver::linalg::Matrix;
+use crate::SPEED_OF_LIGHT;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+/// Settings for the RTK float solver
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RtkSettings {
+    code_sigma: f64,
+    phase_sigma: f64,
+    max_iterations: usize,
+    convergence_threshold: f64,
+    reference_selection: ReferenceSelection,
+}
+
+impl RtkSettings {
+    /// Makes the default settings
+    ///
+    /// The default undifferenced noise is 0.3 m for pseudoranges and 3 mm for
+    /// carrier phases.
+    pub fn new() -> RtkSettings {
+        RtkSettings {
+            code_sigma: 0.3,
+            phase_sigma: 0.003,
+            max_iterations: 10,
+            convergence_threshold: 1e-4,
+            reference_selection: ReferenceSelection::default(),
+        }
+    }
+
+    /// Sets the undifferenced pseudorange noise, in meters
+    pub fn set_code_sigma(self, code_sigma: f64) -> RtkSettings {
+        RtkSettings { code_sigma, ..self }
+    }
+
+    /// Sets the undifferenced carrier phase noise, in meters
+    pub fn set_phase_sigma(self, phase_sigma: f64) -> RtkSettings {
+        RtkSettings {
+            phase_sigma,
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of linearization iterations
+    pub fn set_max_iterations(self, max_iterations: usize) -> RtkSettings {
+        RtkSettings {
+            max_iterations,
+            ..self
+        }
+    }
+
+    /// Sets the size of the baseline update, in meters, below which the
+    /// solution is considered converged
+    pub fn set_convergence_threshold(self, convergence_threshold: f64) -> RtkSettings {
+        RtkSettings {
+            convergence_threshold,
+            ..self
+        }
+    }
+
+    /// Sets how the reference satellites are chosen when forming double
+    /// differences from raw measurements
+    pub fn set_reference_selection(self, reference_selection: ReferenceSelection) -> RtkSettings {
+        RtkSettings {
+            reference_selection,
+            ..self
+        }
+    }
+
+    pub fn code_sigma(&self) -> f64 {
+        self.code_sigma
+    }
+
+    pub fn phase_sigma(&self) -> f64 {
+        self.phase_sigma
+    }
+
+    pub fn max_iterations(&self) -> usize {
+        self.max_iterations
+    }
+
+    pub fn convergence_threshold(&self) -> f64 {
+        self.convergence_threshold
+    }
+
+    pub fn reference_selection(&self) -> ReferenceSelection {
+        self.reference_selection
+    }
+}
+
+impl Default for RtkSettings {
+    fn default() -> RtkSettings {
+        RtkSettings::new()
+    }
+}
+
+/// Reasons the RTK float solution can fail
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum RtkError {
+    /// Fewer than three double differenced pseudoranges are available
+    NotEnoughObservations,
+    /// The satellite geometry doesn't allow the baseline to be estimated
+    SingularGeometry,
+    /// The solution didn't converge within the maximum number of iterations
+    NotConverged,
+}
+
+impl fmt::Display for RtkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RtkError::NotEnoughObservations => write!(f, "Not enough double differences"),
+            RtkError::SingularGeometry => write!(f, "Singular satellite geometry"),
+            RtkError::NotConverged => write!(f, "Solution did not converge"),
+        }
+    }
+}
+
+impl Error for RtkError {}
+
+/// A float estimate of a double differenced carrier phase ambiguity
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct FloatAmbiguity {
+    reference: GnssSignal,
+    value: f64,
+    variance: f64,
+}
+
+impl FloatAmbiguity {
+    /// Gets the reference signal of the double difference
+    pub fn reference(&self) -> GnssSignal {
+        self.reference
+    }
+
+    /// Gets the estimated ambiguity, in cycles
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Gets the variance of the estimated ambiguity, in cycles squared
+    pub fn variance(&self) -> f64 {
+        self.variance
+    }
+}
+
+/// A single epoch RTK float solution
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloatSolution {
+    base_position: ECEF,
+    baseline: ECEF,
+    covariance: [[f64; 3]; 3],
+    ambiguities: BTreeMap<GnssSignal, FloatAmbiguity>,
+    code_observations: usize,
+    phase_observations: usize,
+    iterations: usize,
+}
+
+impl FloatSolution {
+    /// Gets the estimated vector from the base to the rover, in ECEF meters
+    pub fn baseline(&self) -> ECEF {
+        self.baseline
+    }
+
+    /// Gets the covariance of the baseline, in ECEF meters squared
+    pub fn baseline_covariance(&self) -> &[[f64; 3]; 3] {
+        &self.covariance
+    }
+
+    /// Gets the base position the baseline is relative to
+    pub fn base_position(&self) -> ECEF {
+        self.base_position
+    }
+
+    /// Gets the estimated rover position
+    pub fn rover_position(&self) -> ECEF {
+        self.base_position + self.baseline
+    }
+
+    /// Gets the estimated ambiguities, keyed by the non-reference signal of
+    /// each double difference
+    pub fn ambiguities(&self) -> &BTreeMap<GnssSignal, FloatAmbiguity> {
+        &self.ambiguities
+    }
+
+    /// Gets the number of double differenced pseudoranges used
+    pub fn code_observations(&self) -> usize {
+        self.code_observations
+    }
+
+    /// Gets the number of double differenced carrier phases used
+    pub fn phase_observations(&self) -> usize {
+        self.phase_observations
+    }
+
+    /// Gets the number of linearization iterations performed
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+}
+
+/// A double difference as used by the estimator
+struct Observation {
+    sid: GnssSignal,
+    reference: GnssSignal,
+    satellite: ECEF,
+    reference_satellite: ECEF,
+    pseudorange: Option<f64>,
+    /// Carrier phase scaled to meters, and the index of its ambiguity state
+    carrier_phase: Option<(f64, usize)>,
+    wavelength: f64,
+}
+
+/// Computes the RTK float solution from raw rover and base measurements
+///
+/// The satellite positions are taken from the
+/// [rover measurements](NavigationMeasurement::satellite_position), so the
+/// satellite states must have been set. See [`solve_float_differenced()`] for
+/// more details.
+pub fn solve_float(
+    rover: &[NavigationMeasurement],
+    base: &[NavigationMeasurement],
+    base_position: &ECEF,
+    settings: &RtkSettings,
+) -> Result<FloatSolution, RtkError> {
+    let epoch = DifferencedEpoch::new(rover, base, settings.reference_selection);
+    let satellites: BTreeMap<GnssSignal, ECEF> = rover
+        .iter()
+        .map(|nm| (nm.sid(), nm.satellite_position()))
+        .collect();
+    solve_float_differenced(&epoch, &satellites, base_position, settings)
+}
+
+/// Computes the RTK float solution from already formed double differences
+///
+/// `satellites` must contain the position of each satellite, keyed by any of
+/// its signals. Double differences whose satellites aren't found are ignored.
+/// The solution is iterated starting from a zero length baseline, which is
+/// suitable for baselines up to several tens of kilometers.
+pub fn solve_float_differenced(
+    epoch: &DifferencedEpoch,
+    satellites: &BTreeMap<GnssSignal, ECEF>,
+    base_position: &ECEF,
+    settings: &RtkSettings,
+) -> Result<FloatSolution, RtkError> {
+    let find_satellite = |sid: GnssSignal| {
+        satellites.get(&sid).copied().or_else(|| {
+            satellites
+                .iter()
+                .find(|(other, _)| {
+                    other.to_constellation() == sid.to_constellation() && other.sat() == sid.sat()
+                })
+                .map(|(_, pos)| *pos)
+        })
+    };
+
+    // Group the observations by code, since only double differences sharing
+    // a reference are correlated
+    let mut groups: BTreeMap<Code, Vec<Observation>> = BTreeMap::new();
+    let mut ambiguity_count = 0;
+    for dd in epoch.double_differences().values() {
+        if dd.sid().to_constellation() == Constellation::Glo {
+            continue;
+        }
+        let (satellite, reference_satellite) =
+            match (find_satellite(dd.sid()), find_satellite(dd.reference())) {
+                (Some(sat), Some(reference)) => (sat, reference),
+                _ => continue,
+            };
+        let wavelength = SPEED_OF_LIGHT / dd.sid().carrier_frequency();
+        let carrier_phase = dd.carrier_phase().map(|cycles| {
+            ambiguity_count += 1;
+            (cycles * wavelength, ambiguity_count - 1)
+        });
+        groups
+            .entry(dd.sid().code())
+            .or_default()
+            .push(Observation {
+                sid: dd.sid(),
+                reference: dd.reference(),
+                satellite,
+                reference_satellite,
+                pseudorange: dd.pseudorange(),
+                carrier_phase,
+                wavelength,
+            });
+    }
+
+    let code_observations = groups
+        .values()
+        .flatten()
+        .filter(|obs| obs.pseudorange.is_some())
+        .count();
+    if code_observations < 3 {
+        return Err(RtkError::NotEnoughObservations);
+    }
+
+    let states = 3 + ambiguity_count;
+    let mut x = vec![0.0; states];
+    let code_variance = 2.0 * settings.code_sigma * settings.code_sigma;
+    let phase_variance = 2.0 * settings.phase_sigma * settings.phase_sigma;
+
+    for iteration in 1..=settings.max_iterations.max(1) {
+        let rover = *base_position + ECEF::new(x[0], x[1], x[2]);
+        let mut normal = Matrix::zeros(states, states);
+        let mut rhs = vec![0.0; states];
+
+        for observations in groups.values() {
+            let mut code_rows = Vec::new();
+            let mut phase_rows = Vec::new();
+            for obs in observations {
+                let (range, h) = double_difference_geometry(obs, base_position, &rover);
+                if let Some(pseudorange) = obs.pseudorange {
+                    let mut row = vec![0.0; states];
+                    row[..3].copy_from_slice(&h);
+                    code_rows.push((row, pseudorange - range));
+                }
+                if let Some((phase, index)) = obs.carrier_phase {
+                    let mut row = vec![0.0; states];
+                    row[..3].copy_from_slice(&h);
+                    row[3 + index] = obs.wavelength;
+                    let residual = phase - range - obs.wavelength * x[3 + index];
+                    phase_rows.push((row, residual));
+                }
+            }
+            accumulate(&mut normal, &mut rhs, &code_rows, code_variance);
+            accumulate(&mut normal, &mut rhs, &phase_rows, phase_variance);
+        }
+
+        let covariance = normal.inverse().ok_or(RtkError::SingularGeometry)?;
+        let dx = covariance.mul_vec(&rhs);
+        for (state, delta) in x.iter_mut().zip(dx.iter()) {
+            *state += delta;
+        }
+
+        let step = (dx[0] * dx[0] + dx[1] * dx[1] + dx[2] * dx[2]).sqrt();
+        if step < settings.convergence_threshold {
+            let mut ambiguities = BTreeMap::new();
+            for obs in groups.values().flatten() {
+                if let Some((_, index)) = obs.carrier_phase {
+                    ambiguities.insert(
+                        obs.sid,
+                        FloatAmbiguity {
+                            reference: obs.reference,
+                            value: x[3 + index],
+                            variance: covariance[(3 + index, 3 + index)],
+                        },
+                    );
+                }
+            }
+
+            let mut baseline_covariance = [[0.0; 3]; 3];
+            for (i, row) in baseline_covariance.iter_mut().enumerate() {
+                for (j, value) in row.iter_mut().enumerate() {
+                    *value = covariance[(i, j)];
+                }
+            }
+
+            return Ok(FloatSolution {
+                base_position: *base_position,
+                baseline: ECEF::new(x[0], x[1], x[2]),
+                covariance: baseline_covariance,
+                ambiguities,
+                code_observations,
+                phase_observations: ambiguity_count,
+                iterations: iteration,
+            });
+        }
+    }
+
+    Err(RtkError::NotConverged)
+}
+
+/// Computes the double differenced geometric range and its partial
+/// derivatives with respect to the rover position
+fn double_difference_geometry(obs: &Observation, base: &ECEF, rover: &ECEF) -> (f64, [f64; 3]) {
+    let to_sat = obs.satellite - rover;
+    let to_reference = obs.reference_satellite - rover;
+    let sat_range = to_sat.norm();
+    let reference_range = to_reference.norm();
+    let range = (sat_range - (obs.satellite - base).norm())
+        - (reference_range - (obs.reference_satellite - base).norm());
+
+    let h = [
+        -to_sat.x() / sat_range + to_reference.x() / reference_range,
+        -to_sat.y() / sat_range + to_reference.y() / reference_range,
+        -to_sat.z() / sat_range + to_reference.z() / reference_range,
+    ];
+    (range, h)
+}
+
+/// Adds a group of double differences sharing a reference to the normal
+/// equations
+///
+/// The covariance of `n` double differences sharing a reference is
+/// `σ² (I + 11ᵀ)`, whose inverse is `(I - 11ᵀ / (n + 1)) / σ²`. This allows
+/// the weighting to be applied without forming the covariance matrix.
+fn accumulate(normal: &mut Matrix, rhs: &mut [f64], rows: &[(Vec<f64>, f64)], variance: f64) {
+    if rows.is_empty() {
+        return;
+    }
+    let states = rhs.len();
+    let n = rows.len() as f64;
+
+    let mut row_sum = vec![0.0; states];
+    let mut residual_sum = 0.0;
+    for (row, residual) in rows {
+        for i in 0..states {
+            row_sum[i] += row[i];
+            rhs[i] += row[i] * residual / variance;
+            for j in 0..states {
+                normal[(i, j)] += row[i] * row[j] / variance;
+            }
+        }
+        residual_sum += residual;
+    }
+
+    let correction = 1.0 / ((n + 1.0) * variance);
+    for i in 0..states {
+        rhs[i] -= row_sum[i] * residual_sum * correction;
+        for j in 0..states {
+            normal[(i, j)] -= row_sum[i] * row_sum[j] * correction;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::LLHDegrees;
+    use crate::ephemeris::SatelliteState;
+    use crate::solver::fixtures::satellites;
+    use float_eq::assert_float_eq;
+    use std::time::Duration;
+
+    fn make_measurement(
+        sid: GnssSignal,
+        receiver: &ECEF,
+        satellite: &ECEF,
+        clock: f64,
+        ambiguity: f64,
+    ) -> NavigationMeasurement {
+        let range = (satellite - receiver).norm() + clock;
+        let wavelength = SPEED_OF_LIGHT / sid.carrier_frequency();
+        let mut nm = NavigationMeasurement::new();
+        nm.set_sid(sid);
+        nm.set_pseudorange(range);
+        nm.set_carrier_phase(range / wavelength + ambiguity);
+        nm.set_half_cycle_known(true);
+        nm.set_lock_time(Duration::from_secs(10));
+        nm.set_cn0(40.0 + sid.sat() as f64);
+        nm.set_satellite_state(&SatelliteState {
+            pos: *satellite,
+            vel: ECEF::default(),
+            acc: ECEF::default(),
+            clock_err: 0.0,
+            clock_rate_err: 0.0,
+            iodc: 0,
+            iode: 0,
+        });
+        nm
+    }
+
+    fn make_epoch(
+        base: &ECEF,
+        rover: &ECEF,
+    ) -> (Vec<NavigationMeasurement>, Vec<NavigationMeasurement>) {
+        let satellites = satellites();
+
+        let mut rover_nms = Vec::new();
+        let mut base_nms = Vec::new();
+        for (sat, pos) in satellites.iter() {
+            let sid = GnssSignal::new(*sat, Code::GpsL1ca).unwrap();
+            rover_nms.push(make_measurement(sid, rover, pos, 1500.0, *sat as f64 * 3.0));
+            base_nms.push(make_measurement(sid, base, pos, -200.0, -(*sat as f64)));
+        }
+        (rover_nms, base_nms)
+    }
+
+    #[test]
+    fn float_baseline() {
+        let base = LLHDegrees::new(37.77, -122.42, 10.0).to_ecef();
+        let truth = ECEF::new(120.0, -340.0, 75.0);
+        let (rover, base_nms) = make_epoch(&base, &(base + truth));
+
+        let solution = solve_float(&rover, &base_nms, &base, &RtkSettings::new()).unwrap();
+        assert_float_eq!(solution.baseline().x(), truth.x(), abs <= 1e-4);
+        assert_float_eq!(solution.baseline().y(), truth.y(), abs <= 1e-4);
+        assert_float_eq!(solution.baseline().z(), truth.z(), abs <= 1e-4);
+        assert_eq!(solution.code_observations(), 5);
+        assert_eq!(solution.phase_observations(), 5);
+
+        // The float ambiguities should match the simulated integer values
+        for (sid, ambiguity) in solution.ambiguities() {
+            let reference = ambiguity.reference().sat() as f64;
+            let expected = 4.0 * sid.sat() as f64 - 4.0 * reference;
+            assert_float_eq!(ambiguity.value(), expected, abs <= 1e-3);
+            assert!(ambiguity.variance() > 0.0);
+        }
+
+        let cov = solution.baseline_covariance();
+        for i in 0..3 {
+            assert!(cov[i][i] > 0.0);
+            for j in 0..3 {
+                assert_float_eq!(cov[i][j], cov[j][i], abs <= 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn not_enough_observations() {
+        let base = LLHDegrees::new(37.77, -122.42, 10.0).to_ecef();
+        let (rover, base_nms) = make_epoch(&base, &base);
+        assert_eq!(
+            solve_float(&rover[..3], &base_nms[..3], &base, &RtkSettings::new()),
+            Err(RtkError::NotEnoughObservations)
+        );
+    }
+}
@@ -0,0 +1,270 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Position solution output
+//!
+//! The solvers each have their own solution type carrying what is particular
+//! to them. A [`PvtSolution`] gathers the parts common to all of them, along
+//! with the reference frame the position is in, and converts them into the
+//! forms used by the rest of the crate: geodetic coordinates, a
+//! [`Coordinate`] for reference frame transformations, and NMEA sentences.
+//!
+//! Solutions computed with broadcast ephemerides are in the WGS84 frame of
+//! the ephemerides. The recent WGS84 realizations agree with the ITRF to a few
+//! centimeters, well below the accuracy of such solutions, so they are
+//! usually tagged with the current ITRF.
+
+use crate::coords::{Coordinate, CoordinateEstimate, LLHRadians, ECEF};
+use crate::geoid::get_geoid_offset;
+use crate::nmea::{FixQuality, NmeaFix, NmeaFormat};
+use crate::reference_frame::ReferenceFrame;
+use crate::signal::GnssSignal;
+use crate::solver::filter::KalmanSolution;
+use crate::solver::wls::WlsSolution;
+use crate::solver::{Dops, GnssSolution};
+use crate::time::GpsTime;
+
+/// A position, and optionally velocity, solution at a time
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub struct PvtSolution {
+    time: GpsTime,
+    reference_frame: ReferenceFrame,
+    position: ECEF,
+    velocity: Option<ECEF>,
+    covariance: Option<[[f64; 3]; 3]>,
+    dops: Option<Dops>,
+    used_signals: Vec<GnssSignal>,
+}
+
+impl PvtSolution {
+    /// Makes a solution with only a position
+    pub fn new(time: GpsTime, reference_frame: ReferenceFrame, position: ECEF) -> PvtSolution {
+        PvtSolution {
+            time,
+            reference_frame,
+            position,
+            velocity: None,
+            covariance: None,
+            dops: None,
+            used_signals: Vec::new(),
+        }
+    }
+
+    /// Makes a solution from a weighted least squares solution at a time
+    ///
+    /// The used signals are those with a residual in the solution.
+    pub fn from_wls(
+        solution: &WlsSolution,
+        time: GpsTime,
+        reference_frame: ReferenceFrame,
+    ) -> PvtSolution {
+        let used_signals: Vec<GnssSignal> =
+            solution.residuals().iter().map(|(sid, _)| *sid).collect();
+        let mut pvt = PvtSolution::new(time, reference_frame, solution.position())
+            .set_covariance(*solution.covariance())
+            .set_used_signals(&used_signals);
+        pvt.dops = solution.dops().cloned();
+        pvt
+    }
+
+    /// Makes a solution from a Kalman filter solution
+    ///
+    /// The filter doesn't keep track of the signals it used, they can be set
+    /// with [`PvtSolution::set_used_signals()`].
+    pub fn from_kalman(solution: &KalmanSolution, reference_frame: ReferenceFrame) -> PvtSolution {
+        PvtSolution::new(solution.time(), reference_frame, solution.position())
+            .set_velocity(solution.velocity())
+            .set_covariance(*solution.position_covariance())
+    }
+
+    /// Makes a solution from the output of [`calc_pvt()`](crate::solver::calc_pvt)
+    ///
+    /// Returns `None` if the solution has no valid position. The signals used
+    /// can be set with [`PvtSolution::set_used_signals()`], checking the
+    /// measurements against the returned [`SidSet`](crate::solver::SidSet).
+    pub fn from_gnss_solution(
+        solution: &GnssSolution,
+        dops: &Dops,
+        reference_frame: ReferenceFrame,
+    ) -> Option<PvtSolution> {
+        let position = solution.pos_ecef()?;
+        let cov = solution.err_cov()?;
+        let mut pvt = PvtSolution::new(solution.time(), reference_frame, position)
+            .set_covariance([
+                [cov[0], cov[1], cov[2]],
+                [cov[1], cov[3], cov[4]],
+                [cov[2], cov[4], cov[5]],
+            ])
+            .set_dops(dops.clone());
+        pvt.velocity = solution.vel_ecef();
+        Some(pvt)
+    }
+
+    /// Sets the ECEF velocity, in m/s
+    pub fn set_velocity(self, velocity: ECEF) -> PvtSolution {
+        PvtSolution {
+            velocity: Some(velocity),
+            ..self
+        }
+    }
+
+    /// Sets the ECEF position covariance, in meters squared
+    pub fn set_covariance(self, covariance: [[f64; 3]; 3]) -> PvtSolution {
+        PvtSolution {
+            covariance: Some(covariance),
+            ..self
+        }
+    }
+
+    pub fn set_dops(self, dops: Dops) -> PvtSolution {
+        PvtSolution {
+            dops: Some(dops),
+            ..self
+        }
+    }
+
+    pub fn set_used_signals(self, used_signals: &[GnssSignal]) -> PvtSolution {
+        PvtSolution {
+            used_signals: used_signals.to_vec(),
+            ..self
+        }
+    }
+
+    pub fn time(&self) -> GpsTime {
+        self.time
+    }
+
+    pub fn reference_frame(&self) -> ReferenceFrame {
+        self.reference_frame
+    }
+
+    pub fn position(&self) -> ECEF {
+        self.position
+    }
+
+    /// Gets the ECEF velocity, in m/s
+    pub fn velocity(&self) -> Option<ECEF> {
+        self.velocity
+    }
+
+    /// Gets the ECEF position covariance, in meters squared
+    pub fn covariance(&self) -> Option<&[[f64; 3]; 3]> {
+        self.covariance.as_ref()
+    }
+
+    pub fn dops(&self) -> Option<&Dops> {
+        self.dops.as_ref()
+    }
+
+    pub fn used_signals(&self) -> &[GnssSignal] {
+        &self.used_signals
+    }
+
+    /// Gets the geodetic coordinates of the position, on the ellipsoid of
+    /// the solution's reference frame
+    pub fn to_llh(&self) -> LLHRadians {
+        self.to_coordinate().llh()
+    }
+
+    /// Makes a coordinate of the position at the solution's time, ready to be
+    /// transformed into other reference frames
+    ///
+    /// The velocity of a [`Coordinate`] is the slow motion of a station, in
+    /// meters per year, so the velocity of the solution isn't included.
+    pub fn to_coordinate(&self) -> Coordinate {
+        Coordinate::without_velocity(self.reference_frame, self.position, self.time)
+    }
+
+    /// Makes a coordinate estimate of the position and its covariance
+    ///
+    /// Returns `None` if the solution has no covariance
+    pub fn to_coordinate_estimate(&self) -> Option<CoordinateEstimate> {
+        self.covariance
+            .map(|covariance| CoordinateEstimate::new(self.to_coordinate(), covariance))
+    }
+
+    /// Makes an NMEA fix of the solution
+    ///
+    /// The fix has a [`FixQuality::Single`] position with its height above
+    /// the WGS84 ellipsoid, and the geoid separation of the built in geoid
+    /// model. The time is converted to UTC with the hardcoded leap seconds.
+    pub fn to_nmea_fix(&self) -> NmeaFix {
+        let llh = self.position.to_llh();
+        let mut fix = NmeaFix::new(self.time.to_utc_hardcoded())
+            .set_position(llh, FixQuality::Single)
+            .set_geoid_separation(get_geoid_offset(llh) as f64)
+            .set_used_signals(&self.used_signals);
+        if let Some(velocity) = self.velocity {
+            fix = fix.set_velocity(velocity.ned_vector_at(&self.position));
+        }
+        if let Some(dops) = &self.dops {
+            fix = fix.set_dops(dops);
+        }
+        fix
+    }
+
+    /// Makes an NMEA GGA sentence of the solution with the default
+    /// formatting, see [`PvtSolution::to_nmea_fix()`]
+    pub fn to_nmea_gga(&self) -> String {
+        NmeaFormat::default().gga(&self.to_nmea_fix())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::LLHDegrees;
+    use crate::signal::Code;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn conversions() {
+        let llh = LLHDegrees::new(37.77, -122.39, 10.0).to_radians();
+        let position = llh.to_ecef();
+        let time = GpsTime::new(2200, 300_000.0).unwrap();
+        let sids = [
+            GnssSignal::new(5, Code::GpsL1ca).unwrap(),
+            GnssSignal::new(12, Code::GpsL1ca).unwrap(),
+            GnssSignal::new(11, Code::GalE1b).unwrap(),
+        ];
+        let solution = PvtSolution::new(time, ReferenceFrame::ITRF2020, position)
+            .set_covariance([[4.0, 0.0, 0.0], [0.0, 4.0, 0.0], [0.0, 0.0, 9.0]])
+            .set_used_signals(&sids);
+
+        let converted = solution.to_llh();
+        assert_float_eq!(converted.latitude(), llh.latitude(), abs <= 1e-9);
+        assert_float_eq!(converted.longitude(), llh.longitude(), abs <= 1e-9);
+        assert_float_eq!(converted.height(), 10.0, abs <= 1e-3);
+
+        let coordinate = solution.to_coordinate();
+        assert_eq!(coordinate.reference_frame(), ReferenceFrame::ITRF2020);
+        assert_eq!(coordinate.epoch(), time);
+        assert_eq!(coordinate.velocity(), None);
+        let moving = solution.clone().set_velocity(ECEF::new(1.0, 2.0, 3.0));
+        assert_eq!(moving.to_coordinate().velocity(), None);
+        let estimate = solution.to_coordinate_estimate().unwrap();
+        assert_float_eq!(estimate.covariance()[2][2], 9.0, abs <= 1e-12);
+        assert!(PvtSolution::new(time, ReferenceFrame::ITRF2020, position)
+            .to_coordinate_estimate()
+            .is_none());
+
+        let fix = solution.to_nmea_fix();
+        assert_eq!(fix.quality(), FixQuality::Single);
+        assert_eq!(fix.sats_used(), 3);
+        assert!(fix.velocity().is_none());
+        let gga = solution.to_nmea_gga();
+        assert!(gga.starts_with("$GNGGA,"));
+        let fields: Vec<&str> = gga.split(',').collect();
+        assert_eq!(fields[2], "3746.20000");
+        assert_eq!(fields[3], "N");
+        assert_eq!(fields[5], "W");
+        assert_eq!(fields[6], "1");
+        assert_eq!(fields[7], "03");
+    }
+}
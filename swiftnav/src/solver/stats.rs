@@ -0,0 +1,149 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Statistical distribution helpers used by the integrity algorithms
+//!
+//! The quantile functions are found by bisection of the distribution
+//! functions, which is plenty fast for the handful of evaluations needed per
+//! epoch.
+
+/// Natural log of the gamma function, using the Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.001_208_650_973_866_179,
+        -0.000_005_395_239_384_953,
+    ];
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut series = 1.000_000_000_190_015;
+    for (i, c) in COEFFICIENTS.iter().enumerate() {
+        series += c / (x + 1.0 + i as f64);
+    }
+    -tmp + (2.506_628_274_631_000_5 * series / x).ln()
+}
+
+/// Regularized upper incomplete gamma function, Q(a, x)
+fn gamma_q(a: f64, x: f64) -> f64 {
+    const EPSILON: f64 = 1e-15;
+    const MAX_ITERATIONS: usize = 500;
+
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let prefix = (-x + a * x.ln() - ln_gamma(a)).exp();
+    if x < a + 1.0 {
+        // Series representation of P(a, x)
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        for n in 1..MAX_ITERATIONS {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term.abs() < sum.abs() * EPSILON {
+                break;
+            }
+        }
+        1.0 - sum * prefix
+    } else {
+        // Continued fraction representation of Q(a, x), using Lentz's method
+        let tiny = f64::MIN_POSITIVE / EPSILON;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for n in 1..MAX_ITERATIONS {
+            let an = -(n as f64) * (n as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < EPSILON {
+                break;
+            }
+        }
+        prefix * h
+    }
+}
+
+/// Finds the root of a decreasing function between the bounds
+fn bisect<F: Fn(f64) -> f64>(f: F, mut low: f64, mut high: f64) -> f64 {
+    for _ in 0..200 {
+        let mid = 0.5 * (low + high);
+        if f(mid) > 0.0 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+        if high - low <= 1e-12 * high.abs().max(1.0) {
+            break;
+        }
+    }
+    0.5 * (low + high)
+}
+
+/// Probability that a chi-square variable exceeds `x`
+pub(crate) fn chi_square_sf(x: f64, degrees_of_freedom: usize) -> f64 {
+    gamma_q(0.5 * degrees_of_freedom as f64, 0.5 * x)
+}
+
+/// Value a chi-square variable exceeds with the given probability
+pub(crate) fn chi_square_isf(probability: f64, degrees_of_freedom: usize) -> f64 {
+    let mut high = degrees_of_freedom as f64 + 10.0;
+    while chi_square_sf(high, degrees_of_freedom) > probability {
+        high *= 2.0;
+    }
+    bisect(
+        |x| chi_square_sf(x, degrees_of_freedom) - probability,
+        0.0,
+        high,
+    )
+}
+
+/// Probability that a standard normal variable exceeds `x`
+pub(crate) fn normal_sf(x: f64) -> f64 {
+    let tail = 0.5 * gamma_q(0.5, 0.5 * x * x);
+    if x >= 0.0 {
+        tail
+    } else {
+        1.0 - tail
+    }
+}
+
+/// Value a standard normal variable exceeds with the given probability
+pub(crate) fn normal_isf(probability: f64) -> f64 {
+    bisect(|x| normal_sf(x) - probability, -40.0, 40.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn quantiles() {
+        assert_float_eq!(normal_isf(0.5), 0.0, abs <= 1e-9);
+        assert_float_eq!(normal_isf(0.025), 1.959_963_985, abs <= 1e-6);
+        assert_float_eq!(normal_isf(1e-7), 5.199_337_582, abs <= 1e-6);
+
+        assert_float_eq!(chi_square_isf(0.05, 1), 3.841_458_821, abs <= 1e-6);
+        assert_float_eq!(chi_square_isf(0.01, 4), 13.276_704_13, abs <= 1e-6);
+        assert_float_eq!(chi_square_isf(1e-5, 3), 25.901_749_1, abs <= 1e-4);
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Doppler based velocity solver
+//!
+//! Given a receiver position, the doppler measurements of four or more
+//! satellites determine the receiver velocity and clock drift. The problem is
+//! linear so no iteration is needed, and since the position only enters
+//! through the line of sight vectors it can be tens of meters off without
+//! degrading the velocity noticeably. This makes the solver usable on its own,
+//! e.g. to feed velocity updates into a dead reckoning filter.
+
+use crate::coords::{ECEF, NED};
+use crate::navmeas::NavigationMeasurement;
+use crate::signal::GnssSignal;
+use crate::solver::filter::geometry;
+use crate::solver::linalg::Matrix;
+use crate::SPEED_OF_LIGHT;
+use std::error::Error;
+use std::fmt;
+
+/// Errors which can occur when solving for velocity
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum VelocityError {
+    /// Fewer than four measurements had a valid doppler
+    NotEnoughMeasurements,
+    /// The satellite geometry doesn't allow a solution
+    SingularGeometry,
+}
+
+impl fmt::Display for VelocityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VelocityError::NotEnoughMeasurements => write!(f, "Not enough doppler measurements"),
+            VelocityError::SingularGeometry => write!(f, "Singular satellite geometry"),
+        }
+    }
+}
+
+impl Error for VelocityError {}
+
+/// A velocity and clock drift solution
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub struct VelocitySolution {
+    position: ECEF,
+    velocity: ECEF,
+    clock_drift: f64,
+    velocity_covariance: [[f64; 3]; 3],
+    clock_drift_variance: f64,
+    residuals: Vec<(GnssSignal, f64)>,
+}
+
+impl VelocitySolution {
+    /// Gets the ECEF velocity, in m/s
+    pub fn velocity(&self) -> ECEF {
+        self.velocity
+    }
+
+    /// Gets the velocity in the local north, east, down frame of the receiver
+    /// position, in m/s
+    pub fn velocity_ned(&self) -> NED {
+        self.velocity.ned_vector_at(&self.position)
+    }
+
+    /// Gets the receiver clock drift, in s/s
+    pub fn clock_drift(&self) -> f64 {
+        self.clock_drift
+    }
+
+    /// Gets the ECEF velocity covariance, in (m/s)²
+    pub fn velocity_covariance(&self) -> &[[f64; 3]; 3] {
+        &self.velocity_covariance
+    }
+
+    /// Gets the variance of the clock drift, in (s/s)²
+    pub fn clock_drift_variance(&self) -> f64 {
+        self.clock_drift_variance
+    }
+
+    /// Gets the post-fit range rate residual of each measurement used, in m/s
+    pub fn residuals(&self) -> &[(GnssSignal, f64)] {
+        &self.residuals
+    }
+}
+
+/// Computes the receiver velocity and clock drift from doppler measurements
+///
+/// Measurements without a valid doppler are skipped. `doppler_sigma` is the
+/// standard deviation of the range rates derived from the dopplers, in m/s,
+/// and only scales the covariance of the solution.
+pub fn solve_velocity(
+    position: &ECEF,
+    measurements: &[NavigationMeasurement],
+    doppler_sigma: f64,
+) -> Result<VelocitySolution, VelocityError> {
+    let used: Vec<(&NavigationMeasurement, f64)> = measurements
+        .iter()
+        .filter_map(|nm| nm.measured_doppler().map(|doppler| (nm, doppler)))
+        .collect();
+    if used.len() < 4 {
+        return Err(VelocityError::NotEnoughMeasurements);
+    }
+
+    // Observed range rate minus the part due to the satellite motion
+    let mut h = Matrix::zeros(used.len(), 4);
+    let mut y = Vec::with_capacity(used.len());
+    for (row, (nm, doppler)) in used.iter().enumerate() {
+        let (_, los) = geometry(&nm.satellite_position(), position);
+        let wavelength = SPEED_OF_LIGHT / nm.sid().carrier_frequency();
+        let range_rate = -doppler * wavelength + SPEED_OF_LIGHT * nm.satellite_clock_error_rate();
+        let sat_vel = nm.satellite_velocity();
+        let sat_vel = sat_vel.as_array_ref();
+        let sat_motion = los[0] * sat_vel[0] + los[1] * sat_vel[1] + los[2] * sat_vel[2];
+
+        y.push(range_rate - sat_motion);
+        for (k, value) in los.iter().enumerate() {
+            h[(row, k)] = -value;
+        }
+        h[(row, 3)] = 1.0;
+    }
+
+    let ht = h.transpose();
+    let q = ht
+        .mul(&h)
+        .inverse()
+        .ok_or(VelocityError::SingularGeometry)?;
+    let x = q.mul_vec(&ht.mul_vec(&y));
+    let fitted = h.mul_vec(&x);
+
+    let variance = doppler_sigma * doppler_sigma;
+    let mut velocity_covariance = [[0.0; 3]; 3];
+    for (i, row) in velocity_covariance.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = variance * q[(i, j)];
+        }
+    }
+
+    Ok(VelocitySolution {
+        position: *position,
+        velocity: ECEF::new(x[0], x[1], x[2]),
+        clock_drift: x[3] / SPEED_OF_LIGHT,
+        velocity_covariance,
+        clock_drift_variance: variance * q[(3, 3)] / (SPEED_OF_LIGHT * SPEED_OF_LIGHT),
+        residuals: used
+            .iter()
+            .zip(y.iter().zip(fitted.iter()))
+            .map(|((nm, _), (y, fitted))| (nm.sid(), y - fitted))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::LLHDegrees;
+    use crate::ephemeris::SatelliteState;
+    use crate::signal::Code;
+    use crate::solver::fixtures::satellites;
+    use float_eq::assert_float_eq;
+
+    fn simulate_dopplers(
+        receiver: &ECEF,
+        velocity: &ECEF,
+        clock_drift: f64,
+    ) -> Vec<NavigationMeasurement> {
+        let satellites = satellites();
+        let sat_vel = ECEF::new(1000.0, -2000.0, 500.0);
+
+        satellites[..5]
+            .iter()
+            .map(|(sat, pos)| {
+                let sid = GnssSignal::new(*sat, Code::GpsL1ca).unwrap();
+                let (_, los) = geometry(pos, receiver);
+                let relative = sat_vel - *velocity;
+                let r = relative.as_array_ref();
+                let range_rate =
+                    los[0] * r[0] + los[1] * r[1] + los[2] * r[2] + clock_drift * SPEED_OF_LIGHT;
+                let wavelength = SPEED_OF_LIGHT / sid.carrier_frequency();
+
+                let mut nm = NavigationMeasurement::new();
+                nm.set_sid(sid);
+                nm.set_measured_doppler(-range_rate / wavelength);
+                nm.set_satellite_state(&SatelliteState {
+                    pos: *pos,
+                    vel: sat_vel,
+                    acc: ECEF::default(),
+                    clock_err: 0.0,
+                    clock_rate_err: 0.0,
+                    iodc: 0,
+                    iode: 0,
+                });
+                nm
+            })
+            .collect()
+    }
+
+    #[test]
+    fn velocity_from_dopplers() {
+        let receiver = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let velocity = NED::new(3.0, -4.0, 0.5).ecef_vector_at(&receiver);
+        let measurements = simulate_dopplers(&receiver, &velocity, 1e-7);
+
+        // A position error of 50 m barely changes the velocity
+        let approximate = receiver + ECEF::new(30.0, -30.0, 30.0);
+        let solution = solve_velocity(&approximate, &measurements, 0.1).unwrap();
+        let ned = solution.velocity_ned();
+        assert_float_eq!(ned.n(), 3.0, abs <= 0.01);
+        assert_float_eq!(ned.e(), -4.0, abs <= 0.01);
+        assert_float_eq!(ned.d(), 0.5, abs <= 0.01);
+        assert_float_eq!(solution.clock_drift(), 1e-7, abs <= 1e-10);
+        assert_eq!(solution.residuals().len(), 5);
+        assert!(solution.velocity_covariance()[0][0] > 0.0);
+        assert!(solution.clock_drift_variance() > 0.0);
+
+        assert_eq!(
+            solve_velocity(&receiver, &measurements[..3], 0.1),
+            Err(VelocityError::NotEnoughMeasurements)
+        );
+    }
+}
@@ -0,0 +1,225 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Measurement variance models
+//!
+//! The noise of a pseudorange grows as the signal gets weaker, which happens
+//! both at low elevations, where the signal travels through more atmosphere
+//! and is more affected by multipath, and whenever the signal is attenuated.
+//! A [`VarianceModel`] turns these into a variance for each measurement,
+//! which can then be used to [weight](crate::solver::wls::Weighting) the
+//! measurements in the least squares solution.
+//!
+//! Two common models are provided, one based on the
+//! [satellite elevation](ElevationModel) and one based on the
+//! [signal C/N0](Cn0Model). Any other model can be used by implementing the
+//! trait, which is also implemented for closures.
+//!
+//! # References
+//!   * Hartinger H., Brunner F. K., "Variances of GPS Phase Observations: The
+//!     SIGMA-ε Model", GPS Solutions 2, 35–43 (1999)
+//!   * Takasu T., "RTKLIB ver. 2.4.2 Manual", Appendix E.6
+
+use crate::navmeas::NavigationMeasurement;
+
+/// Lowest elevation used by the models, to keep the variance finite
+const MIN_ELEVATION: f64 = 1.0 * std::f64::consts::PI / 180.0;
+
+/// Model of the pseudorange noise of a measurement
+pub trait VarianceModel {
+    /// Gets the variance of the pseudorange of a measurement, in m²
+    ///
+    /// `elevation` is the elevation of the satellite seen from the receiver,
+    /// in radians.
+    fn variance(&self, measurement: &NavigationMeasurement, elevation: f64) -> f64;
+}
+
+impl<F: Fn(&NavigationMeasurement, f64) -> f64> VarianceModel for F {
+    fn variance(&self, measurement: &NavigationMeasurement, elevation: f64) -> f64 {
+        self(measurement, elevation)
+    }
+}
+
+/// Elevation dependent variance model
+///
+/// The standard deviation is `a + b / sin(el)`, combined as a sum of
+/// squares: `σ² = a² + b² / sin²(el)`.
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ElevationModel {
+    a: f64,
+    b: f64,
+}
+
+impl ElevationModel {
+    /// Makes the default elevation model
+    ///
+    /// Note: The default settings consist of
+    ///  * An elevation independent standard deviation of 0.3 m
+    ///  * An elevation dependent standard deviation of 0.3 m at zenith
+    pub fn new() -> ElevationModel {
+        ElevationModel { a: 0.3, b: 0.3 }
+    }
+
+    /// Sets the elevation independent standard deviation, in meters
+    pub fn set_a(self, a: f64) -> ElevationModel {
+        ElevationModel { a, ..self }
+    }
+
+    /// Sets the elevation dependent standard deviation at zenith, in meters
+    pub fn set_b(self, b: f64) -> ElevationModel {
+        ElevationModel { b, ..self }
+    }
+
+    pub fn a(&self) -> f64 {
+        self.a
+    }
+
+    pub fn b(&self) -> f64 {
+        self.b
+    }
+}
+
+impl Default for ElevationModel {
+    fn default() -> ElevationModel {
+        ElevationModel::new()
+    }
+}
+
+impl VarianceModel for ElevationModel {
+    fn variance(&self, _measurement: &NavigationMeasurement, elevation: f64) -> f64 {
+        let sin_el = elevation.max(MIN_ELEVATION).sin();
+        self.a * self.a + self.b * self.b / (sin_el * sin_el)
+    }
+}
+
+/// C/N0 dependent variance model, also known as the SIGMA-ε model
+///
+/// The variance is `σ² = floor² + coefficient · 10^(-C/N0 / 10)`, with the
+/// C/N0 in dB-Hz. Measurements without a C/N0 use the
+/// [default C/N0](Cn0Model::set_default_cn0).
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cn0Model {
+    floor: f64,
+    coefficient: f64,
+    default_cn0: f64,
+}
+
+impl Cn0Model {
+    /// Makes the default C/N0 model
+    ///
+    /// Note: The default settings consist of
+    ///  * A minimum standard deviation of 0.3 m
+    ///  * A coefficient of 1.61e4 m²·Hz, suitable for GPS L1 C/A code
+    ///  * Using 30 dB-Hz for measurements without a C/N0
+    pub fn new() -> Cn0Model {
+        Cn0Model {
+            floor: 0.3,
+            coefficient: 1.61e4,
+            default_cn0: 30.0,
+        }
+    }
+
+    /// Sets the minimum standard deviation, in meters
+    pub fn set_floor(self, floor: f64) -> Cn0Model {
+        Cn0Model { floor, ..self }
+    }
+
+    /// Sets the coefficient of the C/N0 term, in m²·Hz
+    pub fn set_coefficient(self, coefficient: f64) -> Cn0Model {
+        Cn0Model {
+            coefficient,
+            ..self
+        }
+    }
+
+    /// Sets the C/N0 used for measurements without one, in dB-Hz
+    pub fn set_default_cn0(self, default_cn0: f64) -> Cn0Model {
+        Cn0Model {
+            default_cn0,
+            ..self
+        }
+    }
+
+    pub fn floor(&self) -> f64 {
+        self.floor
+    }
+
+    pub fn coefficient(&self) -> f64 {
+        self.coefficient
+    }
+
+    pub fn default_cn0(&self) -> f64 {
+        self.default_cn0
+    }
+}
+
+impl Default for Cn0Model {
+    fn default() -> Cn0Model {
+        Cn0Model::new()
+    }
+}
+
+impl VarianceModel for Cn0Model {
+    fn variance(&self, measurement: &NavigationMeasurement, _elevation: f64) -> f64 {
+        let cn0 = measurement.cn0().unwrap_or(self.default_cn0);
+        self.floor * self.floor + self.coefficient * 10f64.powf(-cn0 / 10.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn elevation_model() {
+        let model = ElevationModel::new();
+        let nm = NavigationMeasurement::new();
+        assert_float_eq!(
+            model.variance(&nm, std::f64::consts::FRAC_PI_2),
+            0.18,
+            abs <= 1e-12
+        );
+        assert_float_eq!(
+            model.variance(&nm, 30f64.to_radians()),
+            0.09 + 0.36,
+            abs <= 1e-12
+        );
+        // Satellites below the horizon get the largest variance, not an
+        // infinite or negative one
+        let lowest = model.variance(&nm, MIN_ELEVATION);
+        assert_float_eq!(model.variance(&nm, 0.0), lowest, abs <= 1e-12);
+        assert_float_eq!(model.variance(&nm, -0.1), lowest, abs <= 1e-12);
+
+        let model = model.set_a(0.0).set_b(1.0);
+        assert_float_eq!(model.variance(&nm, 30f64.to_radians()), 4.0, abs <= 1e-12);
+    }
+
+    #[test]
+    fn cn0_model() {
+        let model = Cn0Model::new().set_floor(0.0).set_coefficient(1e4);
+        let mut nm = NavigationMeasurement::new();
+        assert_float_eq!(model.variance(&nm, 0.5), 10.0, abs <= 1e-12);
+        nm.set_cn0(40.0);
+        assert_float_eq!(model.variance(&nm, 0.5), 1.0, abs <= 1e-12);
+        nm.set_cn0(50.0);
+        assert_float_eq!(model.variance(&nm, 0.5), 0.1, abs <= 1e-12);
+
+        let custom = |nm: &NavigationMeasurement, el: f64| {
+            ElevationModel::new().variance(nm, el) + model.variance(nm, el)
+        };
+        assert_float_eq!(
+            custom.variance(&nm, 0.5),
+            0.1 + ElevationModel::new().variance(&nm, 0.5),
+            abs <= 1e-12
+        );
+    }
+}
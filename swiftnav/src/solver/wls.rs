@@ -0,0 +1,565 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Weighted least squares position solver
+//!
+//! Unlike [`calc_pvt`](crate::solver::calc_pvt), whose weighting is fixed, this
+//! solver lets the caller describe the measurement noise, either as a weight
+//! per measurement or as a full covariance matrix when the measurement errors
+//! are correlated. The solution exposes the a-posteriori covariance, the DOPs
+//! of the geometry, and the post-fit residuals of every measurement.
+//!
+//! One receiver clock bias is estimated for each constellation present, so
+//! inter-system biases don't leak into the position.
+//!
+//! The weights can also come from a [`VarianceModel`], see
+//! [`solve_wls_with_model`].
+
+use crate::coords::ECEF;
+use crate::navmeas::NavigationMeasurement;
+use crate::signal::{Constellation, GnssSignal};
+use crate::solver::filter::geometry;
+use crate::solver::linalg::Matrix;
+use crate::solver::weighting::VarianceModel;
+use crate::solver::Dops;
+use crate::SPEED_OF_LIGHT;
+use std::error::Error;
+use std::fmt;
+
+const MAX_ITERATIONS: usize = 20;
+const CONVERGENCE_THRESHOLD: f64 = 1e-4;
+
+/// Description of the measurement noise used to weight the measurements
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub enum Weighting {
+    /// All measurements have the same, unit, variance
+    Uniform,
+    /// A weight, the inverse of the variance in 1/m², for each measurement
+    Weights(Vec<f64>),
+    /// A row major covariance matrix of the measurements, in m²
+    Covariance(Vec<f64>),
+}
+
+impl Weighting {
+    /// Makes the weights of the measurements from a variance model
+    ///
+    /// The satellite elevations are computed from the receiver position,
+    /// which only needs to be approximate.
+    pub fn from_model<M: VarianceModel + ?Sized>(
+        model: &M,
+        measurements: &[NavigationMeasurement],
+        receiver: &ECEF,
+    ) -> Weighting {
+        Weighting::Weights(
+            measurements
+                .iter()
+                .map(|nm| {
+                    let elevation = receiver.azel_of(&nm.satellite_position()).el;
+                    1.0 / model.variance(nm, elevation)
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Errors which can occur in the weighted least squares solution
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum WlsError {
+    /// There were not enough measurements for a solution
+    NotEnoughMeasurements,
+    /// The weights don't match the measurements, aren't positive, or the
+    /// covariance isn't invertible
+    InvalidWeighting,
+    /// The geometry is singular or the iteration didn't converge
+    FailedToConverge,
+}
+
+impl fmt::Display for WlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WlsError::NotEnoughMeasurements => write!(f, "Not enough measurements"),
+            WlsError::InvalidWeighting => write!(f, "Invalid measurement weighting"),
+            WlsError::FailedToConverge => write!(f, "Least squares failed to converge"),
+        }
+    }
+}
+
+impl Error for WlsError {}
+
+/// A weighted least squares position solution
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub struct WlsSolution {
+    position: ECEF,
+    clock_biases: Vec<(Constellation, f64)>,
+    covariance: [[f64; 3]; 3],
+    variance_factor: f64,
+    dops: Option<Dops>,
+    residuals: Vec<(GnssSignal, f64)>,
+    iterations: usize,
+}
+
+impl WlsSolution {
+    pub fn position(&self) -> ECEF {
+        self.position
+    }
+
+    /// Gets the receiver clock bias of each constellation, in seconds
+    pub fn clock_biases(&self) -> &[(Constellation, f64)] {
+        &self.clock_biases
+    }
+
+    /// Gets the constellation the
+    /// [inter-system biases](WlsSolution::inter_system_biases) are relative to
+    ///
+    /// This is GPS when present, otherwise the first constellation in the
+    /// order of [`Constellation`].
+    pub fn reference_constellation(&self) -> Constellation {
+        reference_constellation(self.clock_biases.iter().map(|(c, _)| *c))
+            .expect("A solution has at least one clock bias")
+    }
+
+    /// Gets the receiver clock bias of each constellation relative to that of
+    /// the [reference constellation](WlsSolution::reference_constellation),
+    /// in seconds
+    ///
+    /// The reference constellation itself isn't included.
+    pub fn inter_system_biases(&self) -> Vec<(Constellation, f64)> {
+        let reference = self.reference_constellation();
+        let reference_bias = self
+            .clock_biases
+            .iter()
+            .find(|(c, _)| *c == reference)
+            .map(|(_, bias)| *bias)
+            .unwrap_or_default();
+        self.clock_biases
+            .iter()
+            .filter(|(c, _)| *c != reference)
+            .map(|(c, bias)| (*c, bias - reference_bias))
+            .collect()
+    }
+
+    /// Gets the a-posteriori ECEF position covariance, in meters squared
+    ///
+    /// This is the a-priori covariance from the weighting scaled by the
+    /// [variance factor](WlsSolution::variance_factor)
+    pub fn covariance(&self) -> &[[f64; 3]; 3] {
+        &self.covariance
+    }
+
+    /// Gets the a-posteriori variance of unit weight
+    ///
+    /// Values much larger than one indicate the weighting underestimates the
+    /// measurement noise. It is one when there is no redundancy.
+    pub fn variance_factor(&self) -> f64 {
+        self.variance_factor
+    }
+
+    /// Gets the DOPs of the measurement geometry
+    ///
+    /// Returns `None` if the geometry doesn't allow computing DOPs with a
+    /// single receiver clock
+    pub fn dops(&self) -> Option<&Dops> {
+        self.dops.as_ref()
+    }
+
+    /// Gets the post-fit residual of each measurement used, in meters
+    pub fn residuals(&self) -> &[(GnssSignal, f64)] {
+        &self.residuals
+    }
+
+    /// Gets the number of iterations taken to converge
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+}
+
+/// Computes a position solution from pseudoranges with the given weighting
+///
+/// Measurements without a valid pseudorange are skipped. The weights, or
+/// the rows and columns of the covariance, must line up with `measurements`
+/// including any skipped measurements.
+pub fn solve_wls(
+    measurements: &[NavigationMeasurement],
+    weighting: &Weighting,
+) -> Result<WlsSolution, WlsError> {
+    solve_wls_iterated(measurements, weighting, &Iteration::default())
+}
+
+/// Starting point and limits of the least squares iteration
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub(crate) struct Iteration {
+    pub(crate) initial_position: ECEF,
+    pub(crate) max_iterations: usize,
+    /// Size of the last position update at which the iteration stops, in
+    /// meters
+    pub(crate) convergence_threshold: f64,
+}
+
+impl Default for Iteration {
+    fn default() -> Iteration {
+        Iteration {
+            initial_position: ECEF::default(),
+            max_iterations: MAX_ITERATIONS,
+            convergence_threshold: CONVERGENCE_THRESHOLD,
+        }
+    }
+}
+
+/// Computes a position solution, see [`solve_wls`], with the given starting
+/// point and limits of the iteration
+pub(crate) fn solve_wls_iterated(
+    measurements: &[NavigationMeasurement],
+    weighting: &Weighting,
+    iteration: &Iteration,
+) -> Result<WlsSolution, WlsError> {
+    let used: Vec<usize> = (0..measurements.len())
+        .filter(|i| measurements[*i].pseudorange().is_some())
+        .collect();
+    let weight = weight_matrix(measurements.len(), &used, weighting)?;
+
+    let mut constellations: Vec<Constellation> = used
+        .iter()
+        .map(|i| measurements[*i].sid().to_constellation())
+        .collect();
+    constellations.sort();
+    constellations.dedup();
+    let states = 3 + constellations.len();
+    if used.len() < states {
+        return Err(WlsError::NotEnoughMeasurements);
+    }
+    let clock_index = |nm: &NavigationMeasurement| {
+        3 + constellations
+            .iter()
+            .position(|c| *c == nm.sid().to_constellation())
+            .expect("Constellation is present")
+    };
+
+    let mut x = vec![0.0; states];
+    x[..3].copy_from_slice(iteration.initial_position.as_array_ref());
+    let mut iterations = 0;
+    let h = loop {
+        iterations += 1;
+        let receiver = ECEF::new(x[0], x[1], x[2]);
+        let mut h = Matrix::zeros(used.len(), states);
+        let mut residuals = Vec::with_capacity(used.len());
+        for (row, i) in used.iter().enumerate() {
+            let nm = &measurements[*i];
+            let (range, los) = geometry(&nm.satellite_position(), &receiver);
+            let corrected =
+                nm.pseudorange().unwrap_or_default() + SPEED_OF_LIGHT * nm.satellite_clock_error();
+            residuals.push(corrected - (range + x[clock_index(nm)]));
+            for (k, value) in los.iter().enumerate() {
+                h[(row, k)] = -value;
+            }
+            h[(row, clock_index(nm))] = 1.0;
+        }
+
+        let ht_w = h.transpose().mul(&weight);
+        let dx = ht_w
+            .mul(&h)
+            .inverse()
+            .ok_or(WlsError::FailedToConverge)?
+            .mul_vec(&ht_w.mul_vec(&residuals));
+        for (state, delta) in x.iter_mut().zip(dx.iter()) {
+            *state += delta;
+        }
+        if (dx[0] * dx[0] + dx[1] * dx[1] + dx[2] * dx[2]).sqrt() < iteration.convergence_threshold
+        {
+            break h;
+        }
+        if iterations >= iteration.max_iterations {
+            return Err(WlsError::FailedToConverge);
+        }
+    };
+
+    let receiver = ECEF::new(x[0], x[1], x[2]);
+    let post_fit: Vec<f64> = used
+        .iter()
+        .map(|i| {
+            let nm = &measurements[*i];
+            let (range, _) = geometry(&nm.satellite_position(), &receiver);
+            nm.pseudorange().unwrap_or_default() + SPEED_OF_LIGHT * nm.satellite_clock_error()
+                - (range + x[clock_index(nm)])
+        })
+        .collect();
+
+    let degrees_of_freedom = used.len() - states;
+    let variance_factor = if degrees_of_freedom > 0 {
+        let weighted = weight.mul_vec(&post_fit);
+        post_fit
+            .iter()
+            .zip(weighted.iter())
+            .map(|(a, b)| a * b)
+            .sum::<f64>()
+            / degrees_of_freedom as f64
+    } else {
+        1.0
+    };
+
+    let q = h
+        .transpose()
+        .mul(&weight)
+        .mul(&h)
+        .inverse()
+        .ok_or(WlsError::FailedToConverge)?;
+    let mut covariance = [[0.0; 3]; 3];
+    for (i, row) in covariance.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = variance_factor * q[(i, j)];
+        }
+    }
+
+    let satellites: Vec<ECEF> = used
+        .iter()
+        .map(|i| measurements[*i].satellite_position())
+        .collect();
+
+    Ok(WlsSolution {
+        position: receiver,
+        clock_biases: constellations
+            .iter()
+            .enumerate()
+            .map(|(k, c)| (*c, x[3 + k] / SPEED_OF_LIGHT))
+            .collect(),
+        covariance,
+        variance_factor,
+        dops: Dops::from_geometry(&receiver, &satellites),
+        residuals: used
+            .iter()
+            .zip(post_fit.iter())
+            .map(|(i, r)| (measurements[*i].sid(), *r))
+            .collect(),
+        iterations,
+    })
+}
+
+/// Computes a position solution weighting the measurements with a variance
+/// model
+///
+/// The satellite elevations aren't known before there is a position, so a
+/// first solution with uniform weights is used to compute them. The
+/// returned solution is the one with the model weights.
+pub fn solve_wls_with_model<M: VarianceModel + ?Sized>(
+    measurements: &[NavigationMeasurement],
+    model: &M,
+) -> Result<WlsSolution, WlsError> {
+    let initial = solve_wls(measurements, &Weighting::Uniform)?;
+    let weighting = Weighting::from_model(model, measurements, &initial.position());
+    solve_wls(measurements, &weighting)
+}
+
+/// Picks the constellation other receiver clocks are referred to: GPS when
+/// present, otherwise the first constellation in order
+pub(crate) fn reference_constellation<I: IntoIterator<Item = Constellation>>(
+    constellations: I,
+) -> Option<Constellation> {
+    let constellations: Vec<Constellation> = constellations.into_iter().collect();
+    if constellations.contains(&Constellation::Gps) {
+        Some(Constellation::Gps)
+    } else {
+        constellations.into_iter().min()
+    }
+}
+
+/// Builds the weight matrix of the used measurements
+fn weight_matrix(count: usize, used: &[usize], weighting: &Weighting) -> Result<Matrix, WlsError> {
+    let n = used.len();
+    match weighting {
+        Weighting::Uniform => Ok(Matrix::identity(n)),
+        Weighting::Weights(weights) => {
+            if weights.len() != count {
+                return Err(WlsError::InvalidWeighting);
+            }
+            let mut w = Matrix::zeros(n, n);
+            for (row, i) in used.iter().enumerate() {
+                if !(weights[*i] > 0.0 && weights[*i].is_finite()) {
+                    return Err(WlsError::InvalidWeighting);
+                }
+                w[(row, row)] = weights[*i];
+            }
+            Ok(w)
+        }
+        Weighting::Covariance(covariance) => {
+            if covariance.len() != count * count {
+                return Err(WlsError::InvalidWeighting);
+            }
+            let mut c = Matrix::zeros(n, n);
+            for (row, i) in used.iter().enumerate() {
+                for (col, j) in used.iter().enumerate() {
+                    c[(row, col)] = covariance[i * count + j];
+                }
+            }
+            c.inverse().ok_or(WlsError::InvalidWeighting)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::LLHDegrees;
+    use crate::ephemeris::SatelliteState;
+    use crate::signal::Code;
+    use crate::solver::fixtures::satellites;
+    use float_eq::assert_float_eq;
+
+    fn simulate_epoch(
+        receiver: &ECEF,
+        clock_bias: f64,
+        noise: &[f64],
+    ) -> Vec<NavigationMeasurement> {
+        let satellites = satellites();
+
+        satellites
+            .iter()
+            .zip(noise.iter())
+            .map(|((sat, pos), noise)| {
+                let (range, _) = geometry(pos, receiver);
+                let mut nm = NavigationMeasurement::new();
+                nm.set_sid(GnssSignal::new(*sat, Code::GpsL1ca).unwrap());
+                nm.set_pseudorange(range + clock_bias + noise);
+                nm.set_satellite_state(&SatelliteState {
+                    pos: *pos,
+                    vel: ECEF::default(),
+                    acc: ECEF::default(),
+                    clock_err: 0.0,
+                    clock_rate_err: 0.0,
+                    iodc: 0,
+                    iode: 0,
+                });
+                nm
+            })
+            .collect()
+    }
+
+    #[test]
+    fn uniform_weighting() {
+        let receiver = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let measurements = simulate_epoch(&receiver, 300.0, &[0.0; 6]);
+
+        let solution = solve_wls(&measurements, &Weighting::Uniform).unwrap();
+        assert_float_eq!(solution.position().x(), receiver.x(), abs <= 1e-3);
+        assert_float_eq!(solution.position().y(), receiver.y(), abs <= 1e-3);
+        assert_float_eq!(solution.position().z(), receiver.z(), abs <= 1e-3);
+        assert_eq!(solution.clock_biases().len(), 1);
+        assert_float_eq!(
+            solution.clock_biases()[0].1,
+            300.0 / SPEED_OF_LIGHT,
+            abs <= 1e-11
+        );
+        assert_eq!(solution.residuals().len(), 6);
+        for (_, residual) in solution.residuals() {
+            assert_float_eq!(*residual, 0.0, abs <= 1e-3);
+        }
+        assert!(solution.dops().unwrap().pdop() > 1.0);
+    }
+
+    #[test]
+    fn inter_system_biases() {
+        let receiver = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let mut measurements = simulate_epoch(&receiver, 300.0, &[0.0; 6]);
+        for nm in &mut measurements[4..] {
+            nm.set_sid(GnssSignal::new(nm.sid().sat(), Code::GalE1b).unwrap());
+            nm.set_pseudorange(nm.pseudorange().unwrap() + 40.0);
+        }
+
+        let solution = solve_wls(&measurements, &Weighting::Uniform).unwrap();
+        assert_float_eq!(solution.position().x(), receiver.x(), abs <= 1e-3);
+        assert_eq!(solution.clock_biases().len(), 2);
+        assert_eq!(solution.reference_constellation(), Constellation::Gps);
+        let isbs = solution.inter_system_biases();
+        assert_eq!(isbs.len(), 1);
+        assert_eq!(isbs[0].0, Constellation::Gal);
+        assert_float_eq!(isbs[0].1 * SPEED_OF_LIGHT, 40.0, abs <= 1e-3);
+
+        assert_eq!(
+            reference_constellation([Constellation::Gal, Constellation::Bds]),
+            Some(Constellation::Bds)
+        );
+        assert_eq!(reference_constellation(Vec::new()), None);
+    }
+
+    #[test]
+    fn weights_down_weight_outlier() {
+        let receiver = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let measurements = simulate_epoch(&receiver, 0.0, &[0.0, 0.0, 0.0, 0.0, 0.0, 30.0]);
+        let error = |solution: &WlsSolution| (solution.position() - receiver).norm();
+
+        let uniform = solve_wls(&measurements, &Weighting::Uniform).unwrap();
+        let weights = vec![1.0, 1.0, 1.0, 1.0, 1.0, 1e-4];
+        let weighted = solve_wls(&measurements, &Weighting::Weights(weights.clone())).unwrap();
+        assert!(error(&weighted) < error(&uniform));
+
+        // An equivalent diagonal covariance gives the same solution
+        let mut covariance = vec![0.0; 36];
+        for (i, w) in weights.iter().enumerate() {
+            covariance[i * 6 + i] = 1.0 / w;
+        }
+        let from_covariance = solve_wls(&measurements, &Weighting::Covariance(covariance)).unwrap();
+        assert_float_eq!(
+            from_covariance.position().x(),
+            weighted.position().x(),
+            abs <= 1e-6
+        );
+        assert_float_eq!(
+            from_covariance.covariance()[0][0],
+            weighted.covariance()[0][0],
+            abs <= 1e-6
+        );
+
+        assert_eq!(
+            solve_wls(&measurements, &Weighting::Weights(vec![1.0; 5])),
+            Err(WlsError::InvalidWeighting)
+        );
+        assert_eq!(
+            solve_wls(&measurements[..3], &Weighting::Uniform),
+            Err(WlsError::NotEnoughMeasurements)
+        );
+    }
+
+    #[test]
+    fn model_weighting() {
+        use crate::solver::weighting::{Cn0Model, ElevationModel};
+
+        let receiver = LLHDegrees::new(37.77, -122.39, 10.0).to_ecef();
+        let error = |solution: &WlsSolution| (solution.position() - receiver).norm();
+        let lowest = |measurements: &[NavigationMeasurement]| {
+            (0..measurements.len())
+                .min_by(|a, b| {
+                    let el =
+                        |i: &usize| receiver.azel_of(&measurements[*i].satellite_position()).el;
+                    el(a).partial_cmp(&el(b)).unwrap()
+                })
+                .unwrap()
+        };
+
+        // An error on the lowest satellite is down weighted by the elevation
+        let mut noise = [0.0; 6];
+        noise[lowest(&simulate_epoch(&receiver, 0.0, &noise))] = 20.0;
+        let measurements = simulate_epoch(&receiver, 0.0, &noise);
+        let uniform = solve_wls(&measurements, &Weighting::Uniform).unwrap();
+        let weighted = solve_wls_with_model(&measurements, &ElevationModel::new()).unwrap();
+        assert!(error(&weighted) < error(&uniform));
+
+        // An error on a weak signal is down weighted by the C/N0
+        let mut measurements = simulate_epoch(&receiver, 0.0, &[0.0, 20.0, 0.0, 0.0, 0.0, 0.0]);
+        for (i, nm) in measurements.iter_mut().enumerate() {
+            nm.set_cn0(if i == 1 { 25.0 } else { 45.0 });
+        }
+        let uniform = solve_wls(&measurements, &Weighting::Uniform).unwrap();
+        let weighted = solve_wls_with_model(&measurements, &Cn0Model::new()).unwrap();
+        assert!(error(&weighted) < error(&uniform));
+
+        // Models which don't give a positive variance are rejected
+        assert_eq!(
+            solve_wls_with_model(&measurements, &|_: &NavigationMeasurement, _: f64| 0.0),
+            Err(WlsError::InvalidWeighting)
+        );
+    }
+}
@@ -0,0 +1,530 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Asynchronous decoding of receiver and correction streams
+//!
+//! Receivers and NTRIP casters are usually read from asynchronous serial
+//! ports and sockets. A [`DecodeStream`] turns a [`Stream`] of byte chunks
+//! into a stream of decoded items without blocking a thread, feeding the
+//! chunks to a [`Decoder`] as they arrive. Any runtime can drive it, with
+//! tokio an `AsyncRead` is turned into such a stream by
+//! `tokio_util::io::ReaderStream`.
+//!
+//! The binary decoders come in two levels:
+//!  * [`RtcmMessageDecoder`] and [`UbxMessageDecoder`] give the individual
+//!    [`RtcmMessage`]s and [`UbxMessage`]s
+//!  * [`RtcmDecoder`] and [`UbxDecoder`] gather the messages into observation
+//!    epochs and ephemerides, see [`Decoded`]
+//!
+//! NMEA streams are read line by line by an [`NmeaDecoder`], which turns the
+//! GGA sentences into [`CoarseHint`]s.
+//!
+//! Corrupted data is skipped by the framing, so decoding errors only concern
+//! a single message and the stream carries on after them.
+//!
+//! This module is only available with the `futures` feature.
+
+use crate::ephemeris::Ephemeris;
+use crate::navmeas::NavigationMeasurement;
+use crate::rtcm::{self, MsmDecoder, RtcmError, RtcmMessage, StationCoordinates};
+use crate::solver::hint::{CoarseHint, HintError};
+use crate::time::{GpsTime, UtcParams};
+use crate::ubx::{self, NavigationData, SubframeDecoder, UbxError, UbxMessage};
+use futures::stream::Stream;
+use std::error::Error;
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Incremental decoding of a byte stream
+pub trait Decoder {
+    type Item;
+    type Error;
+
+    /// Adds received bytes to the end of the stream
+    fn push(&mut self, data: &[u8]);
+
+    /// Gets the next decoded item
+    ///
+    /// Returns `None` when more bytes are needed
+    fn next_item(&mut self) -> Option<Result<Self::Item, Self::Error>>;
+}
+
+/// Errors which can occur in a [`DecodeStream`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StreamError<E, D> {
+    /// The underlying byte stream returned an error
+    Source(E),
+    /// A message couldn't be decoded
+    Decode(D),
+}
+
+impl<E: fmt::Display, D: fmt::Display> fmt::Display for StreamError<E, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamError::Source(error) => write!(f, "Stream source error ({})", error),
+            StreamError::Decode(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl<E, D> Error for StreamError<E, D>
+where
+    E: Error + 'static,
+    D: Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StreamError::Source(error) => Some(error),
+            StreamError::Decode(error) => Some(error),
+        }
+    }
+}
+
+/// Decodes a stream of byte chunks into a stream of items
+///
+/// The stream ends when the byte stream does, any incomplete message left
+/// over is dropped. Errors of the byte stream are passed on, and the byte
+/// stream is polled again afterwards.
+#[derive(Debug)]
+pub struct DecodeStream<S, D> {
+    source: S,
+    decoder: D,
+    finished: bool,
+}
+
+impl<S, D> DecodeStream<S, D> {
+    pub fn new(source: S, decoder: D) -> DecodeStream<S, D> {
+        DecodeStream {
+            source,
+            decoder,
+            finished: false,
+        }
+    }
+
+    pub fn decoder(&self) -> &D {
+        &self.decoder
+    }
+
+    pub fn decoder_mut(&mut self) -> &mut D {
+        &mut self.decoder
+    }
+
+    /// Gets back the byte stream and the decoder
+    pub fn into_inner(self) -> (S, D) {
+        (self.source, self.decoder)
+    }
+}
+
+impl<S, B, E, D> Stream for DecodeStream<S, D>
+where
+    S: Stream<Item = Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+    D: Decoder + Unpin,
+{
+    type Item = Result<D::Item, StreamError<E, D::Error>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(item) = this.decoder.next_item() {
+                return Poll::Ready(Some(item.map_err(StreamError::Decode)));
+            }
+            if this.finished {
+                return Poll::Ready(None);
+            }
+            match Pin::new(&mut this.source).poll_next(cx) {
+                Poll::Ready(Some(Ok(data))) => this.decoder.push(data.as_ref()),
+                Poll::Ready(Some(Err(error))) => {
+                    return Poll::Ready(Some(Err(StreamError::Source(error))))
+                }
+                Poll::Ready(None) => this.finished = true,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Decodes a byte stream into RTCM messages
+#[derive(Debug, Clone, Default)]
+pub struct RtcmMessageDecoder {
+    frames: rtcm::FrameReader,
+}
+
+impl RtcmMessageDecoder {
+    pub fn new() -> RtcmMessageDecoder {
+        RtcmMessageDecoder::default()
+    }
+}
+
+impl Decoder for RtcmMessageDecoder {
+    type Item = RtcmMessage;
+    type Error = RtcmError;
+
+    fn push(&mut self, data: &[u8]) {
+        self.frames.push(data);
+    }
+
+    fn next_item(&mut self) -> Option<Result<RtcmMessage, RtcmError>> {
+        self.frames
+            .next_payload()
+            .map(|payload| RtcmMessage::decode(&payload))
+    }
+}
+
+/// Decodes a byte stream into UBX messages
+#[derive(Debug, Clone, Default)]
+pub struct UbxMessageDecoder {
+    frames: ubx::FrameReader,
+}
+
+impl UbxMessageDecoder {
+    pub fn new() -> UbxMessageDecoder {
+        UbxMessageDecoder::default()
+    }
+}
+
+impl Decoder for UbxMessageDecoder {
+    type Item = UbxMessage;
+    type Error = UbxError;
+
+    fn push(&mut self, data: &[u8]) {
+        self.frames.push(data);
+    }
+
+    fn next_item(&mut self) -> Option<Result<UbxMessage, UbxError>> {
+        self.frames
+            .next_frame()
+            .map(|frame| UbxMessage::decode(&frame))
+    }
+}
+
+/// Data gathered from the messages of a receiver or correction stream
+#[derive(Debug, Clone)]
+pub enum Decoded {
+    /// The time and measurements of an observation epoch
+    Epoch(GpsTime, Vec<NavigationMeasurement>),
+    Ephemeris(Ephemeris),
+    /// UTC parameters, only decoded from UBX subframes
+    UtcParams(UtcParams),
+    /// Reference station coordinates, only decoded from RTCM
+    StationCoordinates(StationCoordinates),
+}
+
+/// Decodes a byte stream of RTCM messages into epochs, ephemerides and
+/// station coordinates
+///
+/// Ephemeris messages are placed at the week closest to the latest epoch
+/// decoded, or to the reference time before the first epoch. The GLONASS
+/// frequency channel numbers of the GLONASS ephemerides are passed on to the
+/// [`MsmDecoder`].
+#[derive(Debug, Clone)]
+pub struct RtcmDecoder {
+    messages: RtcmMessageDecoder,
+    msm: MsmDecoder,
+    reference: GpsTime,
+}
+
+impl RtcmDecoder {
+    /// Makes a decoder, with a rough current time used to find the week of
+    /// the first messages
+    pub fn new(reference: GpsTime) -> RtcmDecoder {
+        RtcmDecoder {
+            messages: RtcmMessageDecoder::new(),
+            msm: MsmDecoder::new(reference),
+            reference,
+        }
+    }
+
+    pub fn msm_decoder(&self) -> &MsmDecoder {
+        &self.msm
+    }
+
+    pub fn msm_decoder_mut(&mut self) -> &mut MsmDecoder {
+        &mut self.msm
+    }
+}
+
+impl Decoder for RtcmDecoder {
+    type Item = Decoded;
+    type Error = RtcmError;
+
+    fn push(&mut self, data: &[u8]) {
+        self.messages.push(data);
+    }
+
+    fn next_item(&mut self) -> Option<Result<Decoded, RtcmError>> {
+        loop {
+            let message = match self.messages.next_item()? {
+                Ok(message) => message,
+                Err(error) => return Some(Err(error)),
+            };
+            match message {
+                RtcmMessage::Msm(msm) => {
+                    if let Some((time, measurements)) = self.msm.push(&msm) {
+                        self.reference = time;
+                        return Some(Ok(Decoded::Epoch(time, measurements)));
+                    }
+                }
+                RtcmMessage::Ephemeris(message) => {
+                    if let Some(fcn) = message.glonass_fcn() {
+                        self.msm.set_glonass_fcn(message.sid().sat(), fcn);
+                    }
+                    let ephemeris = message.to_ephemeris(&self.reference);
+                    return Some(Ok(Decoded::Ephemeris(ephemeris)));
+                }
+                RtcmMessage::StationCoordinates(coordinates) => {
+                    return Some(Ok(Decoded::StationCoordinates(coordinates)));
+                }
+                RtcmMessage::Unsupported(_) => {}
+            }
+        }
+    }
+}
+
+/// Decodes a byte stream of UBX messages into epochs, ephemerides and UTC
+/// parameters
+///
+/// Observation epochs come from RXM-RAWX messages, and the RXM-SFRBX
+/// subframes are gathered by a [`SubframeDecoder`]. The time of the latest
+/// epoch is used to resolve the week numbers of the subframes.
+#[derive(Debug, Clone, Default)]
+pub struct UbxDecoder {
+    messages: UbxMessageDecoder,
+    subframes: SubframeDecoder,
+}
+
+impl UbxDecoder {
+    pub fn new() -> UbxDecoder {
+        UbxDecoder::default()
+    }
+}
+
+impl Decoder for UbxDecoder {
+    type Item = Decoded;
+    type Error = UbxError;
+
+    fn push(&mut self, data: &[u8]) {
+        self.messages.push(data);
+    }
+
+    fn next_item(&mut self) -> Option<Result<Decoded, UbxError>> {
+        loop {
+            let message = match self.messages.next_item()? {
+                Ok(message) => message,
+                Err(error) => return Some(Err(error)),
+            };
+            match message {
+                UbxMessage::Rawx(rawx) => {
+                    let time = rawx.time();
+                    self.subframes.set_time(time);
+                    return Some(Ok(Decoded::Epoch(time, rawx.into_measurements())));
+                }
+                UbxMessage::Sfrbx(sfrbx) => match self.subframes.push(&sfrbx) {
+                    Some(NavigationData::Ephemeris(ephemeris)) => {
+                        return Some(Ok(Decoded::Ephemeris(ephemeris)))
+                    }
+                    Some(NavigationData::UtcParams(params)) => {
+                        return Some(Ok(Decoded::UtcParams(params)))
+                    }
+                    None => {}
+                },
+                UbxMessage::Unsupported(_, _) => {}
+            }
+        }
+    }
+}
+
+/// Longest line kept by an [`NmeaDecoder`], longer lines are dropped
+///
+/// NMEA 0183 limits sentences to 82 characters, this leaves room for
+/// proprietary sentences which don't always keep to the limit.
+const NMEA_MAX_LINE: usize = 1024;
+
+/// Decodes a byte stream of NMEA sentences into position hints
+///
+/// The stream is split into lines, each GGA sentence with a fix gives a
+/// [`CoarseHint`], see [`CoarseHint::from_gga()`]. Other sentences, GGA
+/// sentences without a fix and any bytes before the `$` of a line are
+/// skipped.
+#[derive(Debug, Clone, Default)]
+pub struct NmeaDecoder {
+    buffer: Vec<u8>,
+}
+
+impl NmeaDecoder {
+    pub fn new() -> NmeaDecoder {
+        NmeaDecoder::default()
+    }
+}
+
+impl Decoder for NmeaDecoder {
+    type Item = CoarseHint;
+    type Error = HintError;
+
+    fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    fn next_item(&mut self) -> Option<Result<CoarseHint, HintError>> {
+        loop {
+            let end = match self.buffer.iter().position(|&b| b == b'\n') {
+                Some(end) => end,
+                None => {
+                    if self.buffer.len() > NMEA_MAX_LINE {
+                        self.buffer.clear();
+                    }
+                    return None;
+                }
+            };
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            if line.len() > NMEA_MAX_LINE {
+                continue;
+            }
+            let line = match line.iter().position(|&b| b == b'$') {
+                Some(start) => String::from_utf8_lossy(&line[start..]),
+                None => continue,
+            };
+            let is_gga = line.get(3..6) == Some("GGA");
+            if !is_gga {
+                continue;
+            }
+            match CoarseHint::from_gga(&line) {
+                Err(HintError::NoFix) => {}
+                result => return Some(result),
+            }
+        }
+    }
+}
+
+/// Decodes a byte stream into RTCM epochs, ephemerides and station
+/// coordinates, see [`RtcmDecoder`]
+pub fn decode_rtcm<S>(source: S, reference: GpsTime) -> DecodeStream<S, RtcmDecoder> {
+    DecodeStream::new(source, RtcmDecoder::new(reference))
+}
+
+/// Decodes a byte stream into UBX epochs, ephemerides and UTC parameters,
+/// see [`UbxDecoder`]
+pub fn decode_ubx<S>(source: S) -> DecodeStream<S, UbxDecoder> {
+    DecodeStream::new(source, UbxDecoder::new())
+}
+
+/// Decodes a byte stream of NMEA sentences into position hints, see
+/// [`NmeaDecoder`]
+pub fn decode_nmea<S>(source: S) -> DecodeStream<S, NmeaDecoder> {
+    DecodeStream::new(source, NmeaDecoder::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::LLHDegrees;
+    use crate::edc::compute_crc24q;
+    use float_eq::assert_float_eq;
+    use futures::executor::block_on;
+    use futures::stream::{self, StreamExt};
+    use std::io;
+
+    fn rtcm_frame(payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![rtcm::PREAMBLE, 0, payload.len() as u8];
+        frame.extend_from_slice(payload);
+        let crc = compute_crc24q(&frame, 0);
+        frame.extend_from_slice(&crc.to_be_bytes()[1..]);
+        frame
+    }
+
+    fn ubx_frame(class: u8, id: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = ubx::SYNC.to_vec();
+        frame.extend_from_slice(&[class, id]);
+        frame.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        frame.extend_from_slice(payload);
+        let (a, b) = frame[2..].iter().fold((0u8, 0u8), |(a, b), byte| {
+            let a = a.wrapping_add(*byte);
+            (a, b.wrapping_add(a))
+        });
+        frame.extend_from_slice(&[a, b]);
+        frame
+    }
+
+    #[test]
+    fn rtcm_messages() {
+        // Message 1230, split across chunks and surrounded by garbage
+        let mut data = vec![0x55, 0xAA];
+        data.extend(rtcm_frame(&[0x4C, 0xE0, 0x00]));
+        data.extend(rtcm_frame(&[0x4C, 0xE0, 0x00]));
+        let chunks: Vec<Result<Vec<u8>, io::Error>> =
+            data.chunks(4).map(|chunk| Ok(chunk.to_vec())).collect();
+
+        let source = stream::iter(chunks);
+        let messages: Vec<_> =
+            block_on(DecodeStream::new(source, RtcmMessageDecoder::new()).collect());
+        assert_eq!(messages.len(), 2);
+        for message in messages {
+            assert!(matches!(message, Ok(RtcmMessage::Unsupported(1230))));
+        }
+    }
+
+    #[test]
+    fn ubx_epochs() {
+        let mut rawx = vec![0u8; 16];
+        rawx[0..8].copy_from_slice(&345_600.5f64.to_le_bytes());
+        rawx[8..10].copy_from_slice(&2200u16.to_le_bytes());
+        let frame = ubx_frame(0x02, 0x15, &rawx);
+        let chunks: Vec<Result<Vec<u8>, io::Error>> = vec![
+            Ok(ubx_frame(0x01, 0x07, &[0; 4])),
+            Ok(frame[..10].to_vec()),
+            Err(io::Error::new(io::ErrorKind::Other, "timeout")),
+            Ok(frame[10..].to_vec()),
+        ];
+
+        let mut decoded = decode_ubx(stream::iter(chunks));
+        assert!(matches!(
+            block_on(decoded.next()),
+            Some(Err(StreamError::Source(_)))
+        ));
+        match block_on(decoded.next()) {
+            Some(Ok(Decoded::Epoch(time, measurements))) => {
+                assert_eq!(time, GpsTime::new(2200, 345_600.5).unwrap());
+                assert!(measurements.is_empty());
+            }
+            _ => panic!("Expected an observation epoch"),
+        }
+        assert!(block_on(decoded.next()).is_none());
+        assert!(block_on(decoded.next()).is_none());
+    }
+
+    #[test]
+    fn nmea_hints() {
+        let data = concat!(
+            "\x00garbage$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n",
+            "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A\r\n",
+            "$GPGGA,123520,4807.038,N,01131.000,E,0,00,,,M,,M,,\r\n",
+            "$GPGGA,123521,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*00\r\n",
+            "$GNGGA,123522,3351.000,S,15112.000,E,4,12,1.0,10.0,M,,M,,\r\n",
+            "$GPGGA,123523,4807.038"
+        );
+        let chunks: Vec<Result<Vec<u8>, io::Error>> = data
+            .as_bytes()
+            .chunks(7)
+            .map(|chunk| Ok(chunk.to_vec()))
+            .collect();
+
+        let hints: Vec<_> = block_on(decode_nmea(stream::iter(chunks)).collect());
+        assert_eq!(hints.len(), 3);
+        let hint = hints[0].as_ref().unwrap();
+        assert_float_eq!(hint.horizontal_sigma(), 4.5, abs <= 1e-12);
+        assert!(matches!(
+            hints[1],
+            Err(StreamError::Decode(HintError::ChecksumMismatch))
+        ));
+        let llh: LLHDegrees = hints[2].as_ref().unwrap().position().to_llh().into();
+        assert_float_eq!(llh.latitude(), -33.85, abs <= 1e-9);
+        assert_float_eq!(llh.longitude(), 151.2, abs <= 1e-9);
+    }
+}
@@ -0,0 +1,492 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Tidal site displacements
+//!
+//! The gravitational pull of the Sun and Moon periodically deforms the earth,
+//! moving a site by up to 40 cm over a day. The ocean tides add a further
+//! loading displacement of up to several centimeters at coastal sites.
+//! Precise point positioning must model both to reach centimeter accuracy.
+//!
+//! The solid earth tide is computed with the degree 2 and 3 terms of the IERS
+//! 2010 model, including the latitude dependence of the Love numbers and the
+//! largest of the frequency dependent corrections, from the K1 constituent.
+//! The displacements are in the conventional tide free system, so the
+//! permanent part of the tide is included. The Sun and Moon positions come
+//! from low precision analytical series, accurate to around 0.1°, which is
+//! well within what the model needs.
+//!
+//! Ocean loading is computed from the 11 main tidal constituents, with the
+//! site coefficients given in the BLQ format provided by the ocean loading
+//! service at <http://holt.oso.chalmers.se/loading/>.
+//!
+//! # References
+//!   * IERS Conventions (2010), IERS Technical Note 36, Chapter 7.1
+//!   * Satellite Orbits, O Montenbruck and E Gill, 2000, Section 3.3.2
+
+use crate::coords::{ECEF, NED};
+use crate::time::GpsTime;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fmt;
+
+/// Equatorial radius of the earth used by the IERS, in meters
+const EARTH_RADIUS: f64 = 6_378_136.6;
+/// Ratio of the gravitational parameters of the Moon and the earth
+const MOON_MASS_RATIO: f64 = 0.012_300_037_1;
+/// Ratio of the gravitational parameters of the Sun and the earth
+const SUN_MASS_RATIO: f64 = 332_946.048_2;
+/// Obliquity of the ecliptic at J2000, in radians
+const OBLIQUITY: f64 = 23.439_291_11 * PI / 180.0;
+/// Arc seconds to radians
+const AS2R: f64 = PI / (180.0 * 3600.0);
+/// MJD of the start of GPS time
+const GPS_EPOCH_MJD: f64 = 44_244.0;
+/// Difference between terrestrial time and GPS time, in seconds
+const TT_GPS_OFFSET: f64 = 51.184;
+
+/// Number of ocean loading tidal constituents
+pub const OCEAN_LOADING_CONSTITUENTS: usize = 11;
+
+/// Gets the MJD of a GPS time, offset by a number of seconds
+fn mjd(t: &GpsTime, offset: f64) -> f64 {
+    GPS_EPOCH_MJD + (f64::from(t.wn()) * 604_800.0 + t.tow() + offset) / 86_400.0
+}
+
+/// Greenwich mean sidereal time, in radians
+fn gmst(t: &GpsTime) -> f64 {
+    let d_ut = mjd(t, -t.utc_offset_hardcoded()) - 51_544.5;
+    let centuries = d_ut / 36_525.0;
+    let degrees =
+        280.460_618_37 + 360.985_647_366_29 * d_ut + 0.000_387_933 * centuries * centuries;
+    degrees.to_radians().rem_euclid(2.0 * PI)
+}
+
+/// Rotates an inertial vector of date into the earth fixed frame
+///
+/// Precession since the epoch of date, nutation and polar motion are ignored.
+fn inertial_to_ecef(v: [f64; 3], gmst: f64) -> ECEF {
+    let (sin_g, cos_g) = gmst.sin_cos();
+    ECEF::new(
+        cos_g * v[0] + sin_g * v[1],
+        -sin_g * v[0] + cos_g * v[1],
+        v[2],
+    )
+}
+
+/// Converts ecliptic longitude, latitude and distance into equatorial
+/// cartesian coordinates
+fn ecliptic_to_equatorial(longitude: f64, latitude: f64, distance: f64) -> [f64; 3] {
+    let (sin_e, cos_e) = OBLIQUITY.sin_cos();
+    let x = distance * latitude.cos() * longitude.cos();
+    let y = distance * latitude.cos() * longitude.sin();
+    let z = distance * latitude.sin();
+    [x, cos_e * y - sin_e * z, sin_e * y + cos_e * z]
+}
+
+/// Gets the approximate position of the Sun in ECEF, in meters
+pub fn sun_position(t: &GpsTime) -> ECEF {
+    let centuries = (mjd(t, TT_GPS_OFFSET) - 51_544.5) / 36_525.0;
+    let mean_anomaly = (357.525_6 + 35_999.049 * centuries).to_radians();
+    // The 1.3972° per century moves the longitude to the equinox of date
+    let longitude = (282.94 + 1.397_2 * centuries).to_radians()
+        + mean_anomaly
+        + (6_892.0 * mean_anomaly.sin() + 72.0 * (2.0 * mean_anomaly).sin()) * AS2R;
+    let distance =
+        (149.619 - 2.499 * mean_anomaly.cos() - 0.021 * (2.0 * mean_anomaly).cos()) * 1e9;
+    inertial_to_ecef(ecliptic_to_equatorial(longitude, 0.0, distance), gmst(t))
+}
+
+/// Gets the approximate position of the Moon in ECEF, in meters
+pub fn moon_position(t: &GpsTime) -> ECEF {
+    let centuries = (mjd(t, TT_GPS_OFFSET) - 51_544.5) / 36_525.0;
+    let mean_longitude = (218.316_17 + 481_267.880_88 * centuries).to_radians();
+    let l = (134.962_92 + 477_198.867_53 * centuries).to_radians();
+    let lp = (357.525_43 + 35_999.049_44 * centuries).to_radians();
+    let f = (93.272_83 + 483_202.018_73 * centuries).to_radians();
+    let d = (297.850_27 + 445_267.111_35 * centuries).to_radians();
+
+    let longitude = mean_longitude
+        + (22_640.0 * l.sin() + 769.0 * (2.0 * l).sin() - 4_586.0 * (l - 2.0 * d).sin()
+            + 2_370.0 * (2.0 * d).sin()
+            - 668.0 * lp.sin()
+            - 412.0 * (2.0 * f).sin()
+            - 212.0 * (2.0 * l - 2.0 * d).sin()
+            - 206.0 * (l + lp - 2.0 * d).sin()
+            + 192.0 * (l + 2.0 * d).sin()
+            - 165.0 * (lp - 2.0 * d).sin()
+            + 148.0 * (l - lp).sin()
+            - 125.0 * d.sin()
+            - 110.0 * (l + lp).sin()
+            - 55.0 * (2.0 * f - 2.0 * d).sin())
+            * AS2R;
+    let latitude = (18_520.0
+        * (f + longitude - mean_longitude + (412.0 * (2.0 * f).sin() + 541.0 * lp.sin()) * AS2R)
+            .sin()
+        - 526.0 * (f - 2.0 * d).sin()
+        + 44.0 * (l + f - 2.0 * d).sin()
+        - 31.0 * (-l + f - 2.0 * d).sin()
+        - 25.0 * (-2.0 * l + f).sin()
+        - 23.0 * (lp + f - 2.0 * d).sin()
+        + 21.0 * (-l + f).sin()
+        + 11.0 * (-lp + f - 2.0 * d).sin())
+        * AS2R;
+    let distance = (385_000.0
+        - 20_905.0 * l.cos()
+        - 3_699.0 * (2.0 * d - l).cos()
+        - 2_956.0 * (2.0 * d).cos()
+        - 570.0 * (2.0 * l).cos()
+        + 246.0 * (2.0 * l - 2.0 * d).cos()
+        - 205.0 * (lp - 2.0 * d).cos()
+        - 171.0 * (l + 2.0 * d).cos()
+        - 152.0 * (l + lp - 2.0 * d).cos())
+        * 1e3;
+    inertial_to_ecef(
+        ecliptic_to_equatorial(longitude, latitude, distance),
+        gmst(t),
+    )
+}
+
+/// Displacement of a site caused by the tide of a single body
+fn body_tide(site: &ECEF, body: &ECEF, mass_ratio: f64, h2: f64, l2: f64) -> ECEF {
+    const H3: f64 = 0.292;
+    const L3: f64 = 0.015;
+
+    let r = site.unit_vector();
+    let rb = body.unit_vector();
+    let distance = body.norm();
+    let cos_angle = r.dot(&rb);
+    let transverse = rb - cos_angle * r;
+
+    let scale2 = mass_ratio * EARTH_RADIUS.powi(4) / distance.powi(3);
+    let degree2 =
+        (h2 * (1.5 * cos_angle * cos_angle - 0.5)) * r + (3.0 * l2 * cos_angle) * transverse;
+
+    let scale3 = mass_ratio * EARTH_RADIUS.powi(5) / distance.powi(4);
+    let degree3 = (H3 * (2.5 * cos_angle.powi(3) - 1.5 * cos_angle)) * r
+        + (L3 * (7.5 * cos_angle * cos_angle - 1.5)) * transverse;
+
+    scale2 * degree2 + scale3 * degree3
+}
+
+/// Computes the solid earth tide displacement of a site at a time
+///
+/// The site is given in ECEF and the displacement is returned in ECEF, in
+/// meters.
+pub fn solid_earth_tide(site: &ECEF, t: &GpsTime) -> ECEF {
+    let llh = site.to_llh();
+    let (sin_lat, cos_lat) = llh.latitude().sin_cos();
+    let p2 = 1.5 * sin_lat * sin_lat - 0.5;
+    let h2 = 0.6078 - 0.0006 * p2;
+    let l2 = 0.0847 + 0.0002 * p2;
+
+    let moon = body_tide(site, &moon_position(t), MOON_MASS_RATIO, h2, l2);
+    let sun = body_tide(site, &sun_position(t), SUN_MASS_RATIO, h2, l2);
+
+    // Frequency dependent correction from the K1 constituent, in the radial
+    // direction
+    let k1 = -0.025 * sin_lat * cos_lat * (gmst(t) + llh.longitude()).sin();
+    let up = NED::new(0.0, 0.0, -k1).ecef_vector_at(site);
+
+    moon + sun + up
+}
+
+/// Errors which can occur when parsing BLQ ocean loading coefficients
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlqError {
+    /// A coefficient line could not be parsed
+    InvalidLine(usize),
+    /// A site has fewer than the six lines of coefficients
+    MissingCoefficients(String),
+}
+
+impl fmt::Display for BlqError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlqError::InvalidLine(line) => write!(f, "Invalid BLQ coefficients on line {}", line),
+            BlqError::MissingCoefficients(site) => {
+                write!(f, "Missing BLQ coefficients for site {}", site)
+            }
+        }
+    }
+}
+
+impl Error for BlqError {}
+
+/// Ocean loading coefficients of a site
+///
+/// The coefficients are for the M2, S2, N2, K2, K1, O1, P1, Q1, Mf, Mm and Ssa
+/// constituents, in that order. The components are in the radial, west and
+/// south directions, matching the BLQ format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OceanLoading {
+    amplitudes: [[f64; OCEAN_LOADING_CONSTITUENTS]; 3],
+    phases: [[f64; OCEAN_LOADING_CONSTITUENTS]; 3],
+}
+
+impl OceanLoading {
+    /// Makes the coefficients from amplitudes, in meters, and Greenwich phase
+    /// lags, in degrees
+    pub fn new(
+        amplitudes: [[f64; OCEAN_LOADING_CONSTITUENTS]; 3],
+        phases: [[f64; OCEAN_LOADING_CONSTITUENTS]; 3],
+    ) -> OceanLoading {
+        OceanLoading { amplitudes, phases }
+    }
+
+    /// Gets the amplitudes, in meters, of the radial, west and south
+    /// components
+    pub fn amplitudes(&self) -> &[[f64; OCEAN_LOADING_CONSTITUENTS]; 3] {
+        &self.amplitudes
+    }
+
+    /// Gets the phase lags, in degrees, of the radial, west and south
+    /// components
+    pub fn phases(&self) -> &[[f64; OCEAN_LOADING_CONSTITUENTS]; 3] {
+        &self.phases
+    }
+
+    /// Computes the ocean loading displacement of a site at a time
+    ///
+    /// The site is given in ECEF and the displacement is returned in ECEF, in
+    /// meters.
+    pub fn displacement(&self, site: &ECEF, t: &GpsTime) -> ECEF {
+        // Angular velocity (rad/s) and multiples of the mean longitudes of
+        // the Sun and Moon, the longitude of the lunar perigee and a phase
+        // offset in cycles for each constituent
+        const ARGUMENTS: [[f64; 5]; OCEAN_LOADING_CONSTITUENTS] = [
+            [1.40519e-4, 2.0, -2.0, 0.0, 0.0],
+            [1.45444e-4, 0.0, 0.0, 0.0, 0.0],
+            [1.37880e-4, 2.0, -3.0, 1.0, 0.0],
+            [1.45842e-4, 2.0, 0.0, 0.0, 0.0],
+            [0.72921e-4, 1.0, 0.0, 0.0, 0.25],
+            [0.67598e-4, 1.0, -2.0, 0.0, -0.25],
+            [0.72523e-4, -1.0, 0.0, 0.0, -0.25],
+            [0.64959e-4, 1.0, -3.0, 1.0, -0.25],
+            [0.53234e-5, 0.0, 2.0, 0.0, 0.0],
+            [0.26392e-5, 0.0, 1.0, -1.0, 0.0],
+            [0.03982e-5, 2.0, 0.0, 0.0, 0.0],
+        ];
+        /// MJD of 1975-01-01, the epoch of the astronomical arguments
+        const EPOCH_MJD: f64 = 42_413.0;
+
+        let mjd_ut = mjd(t, -t.utc_offset_hardcoded());
+        let day = mjd_ut.floor();
+        let seconds_of_day = (mjd_ut - day) * 86_400.0;
+        let days = day - EPOCH_MJD + 1.0;
+        let centuries = (27_392.500_528 + 1.000_000_035 * days) / 36_525.0;
+        let c2 = centuries * centuries;
+        let c3 = c2 * centuries;
+        let sun_longitude =
+            (279.696_68 + 36_000.768_930_485 * centuries + 3.03e-4 * c2).to_radians();
+        let moon_longitude = (270.434_358 + 481_267.883_141_37 * centuries - 0.001_133 * c2
+            + 1.9e-6 * c3)
+            .to_radians();
+        let lunar_perigee =
+            (334.329_653 + 4_069.034_032_957_7 * centuries - 0.010_325 * c2 - 1.2e-5 * c3)
+                .to_radians();
+        let values = [
+            seconds_of_day,
+            sun_longitude,
+            moon_longitude,
+            lunar_perigee,
+            2.0 * PI,
+        ];
+
+        let mut radial_west_south = [0.0; 3];
+        for (i, arguments) in ARGUMENTS.iter().enumerate() {
+            let angle: f64 = arguments
+                .iter()
+                .zip(values.iter())
+                .map(|(a, v)| a * v)
+                .sum();
+            for (j, component) in radial_west_south.iter_mut().enumerate() {
+                *component +=
+                    self.amplitudes[j][i] * (angle - self.phases[j][i].to_radians()).cos();
+            }
+        }
+
+        let [radial, west, south] = radial_west_south;
+        NED::new(-south, -west, -radial).ecef_vector_at(site)
+    }
+}
+
+/// Parses the sites of a BLQ ocean loading file
+///
+/// Each site is made of a line with its name followed by six lines of 11
+/// values: the radial, west and south amplitudes followed by the radial, west
+/// and south phases. Comment lines start with `$$`.
+pub fn parse_blq(text: &str) -> Result<Vec<(String, OceanLoading)>, BlqError> {
+    let mut sites = Vec::new();
+    let mut current: Option<(String, Vec<[f64; OCEAN_LOADING_CONSTITUENTS]>)> = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("$$") {
+            continue;
+        }
+        let values: Option<Vec<f64>> = line
+            .split_whitespace()
+            .map(|value| value.parse::<f64>().ok())
+            .collect();
+        match (&mut current, values) {
+            (Some((_, rows)), Some(values)) if rows.len() < 6 => {
+                if values.len() != OCEAN_LOADING_CONSTITUENTS {
+                    return Err(BlqError::InvalidLine(index + 1));
+                }
+                let mut row = [0.0; OCEAN_LOADING_CONSTITUENTS];
+                row.copy_from_slice(&values);
+                rows.push(row);
+            }
+            (Some((name, rows)), _) if rows.len() < 6 => {
+                return Err(BlqError::MissingCoefficients(name.clone()));
+            }
+            (None, Some(_)) => return Err(BlqError::InvalidLine(index + 1)),
+            (_, _) => {
+                if let Some(site) = current.take() {
+                    sites.push(site);
+                }
+                current = Some((line.to_string(), Vec::new()));
+            }
+        }
+    }
+    if let Some(site) = current {
+        sites.push(site);
+    }
+
+    sites
+        .into_iter()
+        .map(|(name, rows)| {
+            if rows.len() < 6 {
+                return Err(BlqError::MissingCoefficients(name));
+            }
+            let loading =
+                OceanLoading::new([rows[0], rows[1], rows[2]], [rows[3], rows[4], rows[5]]);
+            Ok((name, loading))
+        })
+        .collect()
+}
+
+/// Computes the total tidal displacement of a site at a time
+///
+/// This is the solid earth tide plus the ocean loading, if coefficients are
+/// given for the site. The site is given in ECEF and the displacement is
+/// returned in ECEF, in meters.
+pub fn tidal_displacement(site: &ECEF, t: &GpsTime, ocean_loading: Option<&OceanLoading>) -> ECEF {
+    let solid = solid_earth_tide(site, t);
+    match ocean_loading {
+        Some(loading) => solid + loading.displacement(site, t),
+        None => solid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::LLHDegrees;
+    use crate::time::UtcTime;
+    use float_eq::assert_float_eq;
+    use std::time::Duration;
+
+    const BLQ: &str = "$$ Ocean loading displacement
+$$ Test site with only an M2 radial term
+  TEST
+  .01000 .00000 .00000 .00000 .00000 .00000 .00000 .00000 .00000 .00000 .00000
+  .00000 .00000 .00000 .00000 .00000 .00000 .00000 .00000 .00000 .00000 .00000
+  .00000 .00000 .00000 .00000 .00000 .00000 .00000 .00000 .00000 .00000 .00000
+   -30.0    0.0    0.0    0.0    0.0    0.0    0.0    0.0    0.0    0.0    0.0
+     0.0    0.0    0.0    0.0    0.0    0.0    0.0    0.0    0.0    0.0    0.0
+     0.0    0.0    0.0    0.0    0.0    0.0    0.0    0.0    0.0    0.0    0.0
+$$ END TABLE
+";
+
+    #[test]
+    fn sun_and_moon() {
+        // Close to the March equinox the Sun is over the equator, and at noon
+        // UTC it is near the Greenwich meridian
+        let t = UtcTime::from_date(2020, 3, 20, 12, 0, 0.0).to_gps_hardcoded();
+        let sun = sun_position(&t);
+        assert_float_eq!(sun.norm(), 1.496e11, rmax <= 0.02);
+        let llh = LLHDegrees::from(sun.to_llh());
+        assert_float_eq!(llh.latitude(), 0.0, abs <= 0.5);
+        // The equation of time is around -7.5 minutes
+        assert_float_eq!(llh.longitude(), 1.9, abs <= 0.5);
+
+        for hours in 0..48 {
+            let moon = moon_position(&(t + Duration::from_secs(3600 * hours)));
+            let distance = moon.norm();
+            assert!(distance > 3.56e8 && distance < 4.07e8);
+        }
+    }
+
+    #[test]
+    fn solid_tide() {
+        let site = LLHDegrees::new(0.0, 10.0, 0.0).to_ecef();
+        let start = UtcTime::from_date(2021, 6, 10, 0, 0, 0.0).to_gps_hardcoded();
+        let mut min_up = f64::MAX;
+        let mut max_up = f64::MIN;
+        for hour in 0..25 {
+            let t = start + Duration::from_secs(3600 * hour);
+            let displacement = solid_earth_tide(&site, &t);
+            let ned = displacement.ned_vector_at(&site);
+            assert!(ned.n().abs() < 0.1 && ned.e().abs() < 0.1);
+            min_up = min_up.min(-ned.d());
+            max_up = max_up.max(-ned.d());
+        }
+        // At new moon the radial tide at the equator spans a few decimeters
+        // over a day
+        assert!(max_up < 0.5 && min_up > -0.3);
+        assert!(max_up - min_up > 0.2);
+
+        assert_eq!(
+            tidal_displacement(&site, &start, None),
+            solid_earth_tide(&site, &start)
+        );
+    }
+
+    #[test]
+    fn ocean_loading() {
+        let sites = parse_blq(BLQ).unwrap();
+        assert_eq!(sites.len(), 1);
+        let (name, loading) = &sites[0];
+        assert_eq!(name, "TEST");
+        assert_float_eq!(loading.amplitudes()[0][0], 0.01, abs <= 1e-12);
+        assert_float_eq!(loading.phases()[0][0], -30.0, abs <= 1e-12);
+
+        let site = LLHDegrees::new(45.0, 10.0, 0.0).to_ecef();
+        let start = UtcTime::from_date(2021, 6, 1, 0, 0, 0.0).to_gps_hardcoded();
+        let mut max_up = 0.0_f64;
+        for minute in 0..(12 * 60) {
+            let t = start + Duration::from_secs(60 * minute);
+            let ned = loading.displacement(&site, &t).ned_vector_at(&site);
+            assert_float_eq!(ned.n(), 0.0, abs <= 1e-12);
+            assert_float_eq!(ned.e(), 0.0, abs <= 1e-12);
+            max_up = max_up.max(-ned.d());
+        }
+        assert_float_eq!(max_up, 0.01, abs <= 1e-5);
+
+        // The M2 tide repeats after 12.42 hours
+        let t = start + Duration::from_secs(3600);
+        let period = Duration::from_secs_f64(2.0 * PI / 1.40519e-4);
+        assert_float_eq!(
+            *loading.displacement(&site, &t).as_array_ref(),
+            *loading.displacement(&site, &(t + period)).as_array_ref(),
+            abs_all <= 2e-4
+        );
+
+        assert_eq!(
+            parse_blq("  SITE\n  .1 .2\n"),
+            Err(BlqError::InvalidLine(2))
+        );
+        assert_eq!(
+            parse_blq("  SITE\n$$ END TABLE\n"),
+            Err(BlqError::MissingCoefficients("SITE".to_string()))
+        );
+    }
+}
@@ -12,7 +12,8 @@
 //! along with conversions for all of these to and from [`GpsTime`].
 //! Not all functionality is available in these other representations, so it's
 //! intended that all times are to converted to [`GpsTime`] before use with
-//! swiftnav.
+//! swiftnav. [`PreciseGpsTime`] is available for timing work which needs more
+//! than the double precision of [`GpsTime`].
 //!
 //! # ⚠️  🦘  ⏱  ⚠️  - Leap Seconds
 //! UTC time occasinally adds additional seconds to keep it synchronized with the
@@ -28,7 +29,9 @@
 //! information, and [`UtcParams::decode()`] is provided for decoding the raw GPS
 //! navigation subframe with this information. This is the prefered method since it
 //! is usually available when processing raw GNSS data and ensures that the right
-//! offset is applied at the right time.
+//! offset is applied at the right time. A [`UtcParamsStore`] keeps the newest
+//! parameters decoded from each constellation and picks the right set for each
+//! conversion.
 //!
 //! The second way is to use a table of historical leap seconds that is compiled
 //! in to swftnav-rs. This list is kept up to date in the source code as new leap
@@ -44,10 +47,15 @@
 //! [`UtcParams`] object to handle the leap second conversion and one which doesn't
 //! take a [`UtcParams`] object but has `_hardcoded` appended to the function name.
 
+use crate::signal::Constellation;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use std::error::Error;
 use std::fmt;
-use std::ops::{Add, AddAssign, Sub, SubAssign};
-use std::time::Duration;
+use std::num::TryFromIntError;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub const MINUTE: Duration = Duration::from_secs(swiftnav_sys::MINUTE_SECS as u64);
 pub const HOUR: Duration = Duration::from_secs(swiftnav_sys::HOUR_SECS as u64);
@@ -72,6 +80,20 @@ pub const GLO_TIME_START: GpsTime = GpsTime::new_unchecked(
     swiftnav_sys::GLO_EPOCH_TOW,
 );
 
+/// Offset of TAI from GPS time, which is constant since neither counts leap
+/// seconds
+pub const TAI_GPS_OFFSET: Duration = Duration::from_secs(19);
+/// Number of distinct week numbers in the 10 bit GPS week of the legacy
+/// navigation messages
+pub const GPS_WEEK_MODULUS: u16 = 1 << 10;
+/// Number of distinct week numbers in the 12 bit Galileo week
+pub const GAL_WEEK_MODULUS: u16 = 1 << 12;
+/// Number of distinct week numbers in the 13 bit Beidou week
+pub const BDS_WEEK_MODULUS: u16 = 1 << 13;
+/// Number of seconds from the Unix epoch to the start of GPS time, Jan 6th
+/// 1980, not counting leap seconds
+const GPS_EPOCH_UNIX_SECS: f64 = 315_964_800.0;
+
 /// Error type when a given GPS time is not valid
 #[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
 pub enum InvalidGpsTime {
@@ -92,6 +114,26 @@ impl fmt::Display for InvalidGpsTime {
 
 impl Error for InvalidGpsTime {}
 
+/// Error type when a time string can't be parsed
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum ParseTimeError {
+    /// The string doesn't have the expected layout
+    InvalidFormat,
+    /// One of the fields of the string is out of range
+    InvalidValue,
+}
+
+impl fmt::Display for ParseTimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseTimeError::InvalidFormat => write!(f, "Invalid time format"),
+            ParseTimeError::InvalidValue => write!(f, "Time value out of range"),
+        }
+    }
+}
+
+impl Error for ParseTimeError {}
+
 impl GpsTime {
     const JIFFY: f64 = swiftnav_sys::FLOAT_EQUALITY_EPS;
 
@@ -165,6 +207,12 @@ impl GpsTime {
         unsafe { swiftnav_sys::gpsdifftime(&self.0, &other.0) }
     }
 
+    /// Gets the signed difference between this and another time value, the
+    /// same as subtracting `other` from this time
+    pub fn signed_diff(&self, other: &Self) -> TimeDelta {
+        TimeDelta::from_secs_f64(self.diff(other))
+    }
+
     /// Converts the GPS time into UTC time
     ///
     /// # Panics
@@ -281,7 +329,7 @@ impl GpsTime {
     pub fn to_glo(self, utc_params: &UtcParams) -> GloTime {
         assert!(self.is_valid());
         assert!(self >= GLO_TIME_START);
-        GloTime(unsafe { swiftnav_sys::gps2glo(self.c_ptr(), utc_params.c_ptr()) })
+        gps_to_glo(&self, Some(utc_params))
     }
 
     /// Converts a GPS time into a Glonass time using the hardcoded list of leap
@@ -297,7 +345,253 @@ impl GpsTime {
     pub fn to_glo_hardcoded(self) -> GloTime {
         assert!(self.is_valid());
         assert!(self >= GLO_TIME_START);
-        GloTime(unsafe { swiftnav_sys::gps2glo(self.c_ptr(), std::ptr::null()) })
+        gps_to_glo(&self, None)
+    }
+
+    /// Makes a RINEX 3 epoch string, such as `2020 04 05 00 00  0.0000000`
+    ///
+    /// The date and time are in the GPS time scale, as used by the epochs of
+    /// RINEX observation files. The seconds are rounded to 100 ns.
+    pub fn rinex_epoch(&self) -> String {
+        const TICKS_PER_SECOND: u64 = 10_000_000;
+        const TICKS_PER_DAY: u64 = swiftnav_sys::DAY_SECS as u64 * TICKS_PER_SECOND;
+        let day_secs = DAY.as_secs_f64();
+        let mut days = i64::from(self.wn()) * 7 + (self.tow() / day_secs).floor() as i64;
+        let mut ticks = ((self.tow() % day_secs) * TICKS_PER_SECOND as f64).round() as u64;
+        if ticks >= TICKS_PER_DAY {
+            days += 1;
+            ticks -= TICKS_PER_DAY;
+        }
+        let (year, month, day) = civil_from_days(days + days_from_civil(1980, 1, 6));
+        let seconds = ticks / TICKS_PER_SECOND;
+        format!(
+            "{:4} {:02} {:02} {:02} {:02} {:2}.{:07}",
+            year,
+            month,
+            day,
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            ticks % TICKS_PER_SECOND
+        )
+    }
+
+    /// Parses a RINEX epoch, in the GPS time scale
+    ///
+    /// Both the RINEX 3 layout with four digit years and the RINEX 2 layout
+    /// with two digit years are accepted, with any amount of whitespace
+    /// between the fields.
+    pub fn from_rinex_epoch(epoch: &str) -> Result<GpsTime, ParseTimeError> {
+        let fields: Vec<&str> = epoch.split_whitespace().collect();
+        if fields.len() != 6 {
+            return Err(ParseTimeError::InvalidFormat);
+        }
+        let number = |field: &str| {
+            field
+                .parse::<u16>()
+                .map_err(|_| ParseTimeError::InvalidFormat)
+        };
+        let year = match number(fields[0])? {
+            year if year < 80 => year + 2000,
+            year if year < 100 => year + 1900,
+            year => year,
+        };
+        let (month, day) = (number(fields[1])?, number(fields[2])?);
+        let (hour, minute) = (number(fields[3])?, number(fields[4])?);
+        let seconds: f64 = fields[5]
+            .parse()
+            .map_err(|_| ParseTimeError::InvalidFormat)?;
+        if !valid_date(year, month, day)
+            || hour >= 24
+            || minute >= 60
+            || !(0.0..60.0).contains(&seconds)
+        {
+            return Err(ParseTimeError::InvalidValue);
+        }
+
+        let days = days_from_civil(year, month, day) - days_from_civil(1980, 1, 6);
+        if days < 0 {
+            return Err(ParseTimeError::InvalidValue);
+        }
+        let tow = (days % 7) as f64 * DAY.as_secs_f64()
+            + f64::from(hour) * HOUR.as_secs_f64()
+            + f64::from(minute) * MINUTE.as_secs_f64()
+            + seconds;
+        GpsTime::new((days / 7) as i16, tow).map_err(|_| ParseTimeError::InvalidValue)
+    }
+
+    /// Parses a SINEX epoch, `YYYY:DDD:SSSSS`, in the GPS time scale
+    ///
+    /// Two digit years are accepted as well. The all zero epoch, which SINEX
+    /// files use for an open ended interval, isn't a valid time.
+    pub fn from_sinex_epoch(epoch: &str) -> Result<GpsTime, ParseTimeError> {
+        let fields: Vec<&str> = epoch.trim().split(':').collect();
+        if fields.len() != 3 {
+            return Err(ParseTimeError::InvalidFormat);
+        }
+        let year = match fields[0]
+            .parse::<u16>()
+            .map_err(|_| ParseTimeError::InvalidFormat)?
+        {
+            year if fields[0].len() > 2 => year,
+            year if year < 50 => year + 2000,
+            year => year + 1900,
+        };
+        let day_of_year: i64 = fields[1]
+            .parse()
+            .map_err(|_| ParseTimeError::InvalidFormat)?;
+        let seconds: f64 = fields[2]
+            .parse()
+            .map_err(|_| ParseTimeError::InvalidFormat)?;
+        let days_in_year = if valid_date(year, 2, 29) { 366 } else { 365 };
+        if !(1..=days_in_year).contains(&day_of_year)
+            || !(0.0..=DAY.as_secs_f64()).contains(&seconds)
+        {
+            return Err(ParseTimeError::InvalidValue);
+        }
+
+        let days = days_from_civil(year, 1, 1) + day_of_year - 1 - days_from_civil(1980, 1, 6);
+        if days < 0 {
+            return Err(ParseTimeError::InvalidValue);
+        }
+        GpsTime::from_seconds(days as f64 * DAY.as_secs_f64() + seconds)
+            .map_err(|_| ParseTimeError::InvalidValue)
+    }
+
+    /// Makes a GPS time from a 10 bit week number, resolving the week
+    /// rollovers with a reference time
+    ///
+    /// The week closest to the week of `reference` is chosen, so the
+    /// reference needs to be within 512 weeks of the actual time. A clock,
+    /// the time of a file, or a previous solution all make good references.
+    pub fn from_truncated_wn(
+        wn10: u16,
+        tow: f64,
+        reference: &GpsTime,
+    ) -> Result<GpsTime, InvalidGpsTime> {
+        if wn10 >= GPS_WEEK_MODULUS {
+            return Err(InvalidGpsTime::InvalidWN(wn10 as i16));
+        }
+        let wn = nearest_week(reference.wn(), i32::from(wn10), i32::from(GPS_WEEK_MODULUS));
+        GpsTime::new(wn, tow)
+    }
+
+    /// Makes a GPS time from the number of seconds since the start of GPS
+    /// time
+    fn from_seconds(seconds: f64) -> Result<GpsTime, InvalidGpsTime> {
+        if !seconds.is_finite() {
+            return Err(InvalidGpsTime::InvalidTOW(seconds));
+        }
+        let week = WEEK.as_secs_f64();
+        let wn = (seconds / week).floor();
+        if wn < 0.0 || wn > f64::from(i16::MAX) {
+            return Err(InvalidGpsTime::InvalidWN(wn as i16));
+        }
+        let tow = (seconds - wn * week).max(0.0);
+        // Rounding can leave a full week in the time of week
+        if tow >= week {
+            GpsTime::new(wn as i16 + 1, 0.0)
+        } else {
+            GpsTime::new(wn as i16, tow)
+        }
+    }
+
+    /// Gets the number of seconds since the start of GPS time
+    fn seconds(&self) -> f64 {
+        f64::from(self.wn()) * WEEK.as_secs_f64() + self.tow()
+    }
+
+    /// Converts the GPS time into a Unix timestamp
+    ///
+    /// Unix time follows UTC without counting leap seconds, so a leap second
+    /// repeats the same timestamp twice.
+    pub fn to_unix(&self, utc_params: &UtcParams) -> SystemTime {
+        let seconds = self.seconds() + GPS_EPOCH_UNIX_SECS - self.utc_offset(utc_params);
+        UNIX_EPOCH + Duration::from_secs_f64(seconds)
+    }
+
+    /// Converts the GPS time into a Unix timestamp using the hardcoded list
+    /// of leap seconds
+    ///
+    /// # ⚠️  🦘  ⏱  ⚠️  - Leap Seconds
+    /// The hard coded list of leap seconds will get out of date, it is
+    /// preferable to use [`GpsTime::to_unix()`] with the newest set of UTC
+    /// parameters
+    pub fn to_unix_hardcoded(&self) -> SystemTime {
+        let seconds = self.seconds() + GPS_EPOCH_UNIX_SECS - self.utc_offset_hardcoded();
+        UNIX_EPOCH + Duration::from_secs_f64(seconds)
+    }
+
+    /// Makes a GPS time from a Unix timestamp, such as [`SystemTime::now()`]
+    ///
+    /// Returns an error if the timestamp is before the start of GPS time
+    pub fn from_unix(time: SystemTime, utc_params: &UtcParams) -> Result<GpsTime, InvalidGpsTime> {
+        GpsTime::from_unix_with(time, |t| t.utc_offset(utc_params))
+    }
+
+    /// Makes a GPS time from a Unix timestamp using the hardcoded list of
+    /// leap seconds
+    ///
+    /// # ⚠️  🦘  ⏱  ⚠️  - Leap Seconds
+    /// The hard coded list of leap seconds will get out of date, it is
+    /// preferable to use [`GpsTime::from_unix()`] with the newest set of UTC
+    /// parameters
+    pub fn from_unix_hardcoded(time: SystemTime) -> Result<GpsTime, InvalidGpsTime> {
+        GpsTime::from_unix_with(time, |t| t.utc_offset_hardcoded())
+    }
+
+    fn from_unix_with<F: Fn(&GpsTime) -> f64>(
+        time: SystemTime,
+        utc_offset: F,
+    ) -> Result<GpsTime, InvalidGpsTime> {
+        let unix = match time.duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_secs_f64(),
+            Err(before) => -before.duration().as_secs_f64(),
+        };
+        // The offset depends on the GPS time being computed, start from the
+        // time without leap seconds and iterate
+        let seconds = unix - GPS_EPOCH_UNIX_SECS;
+        let mut gps = GpsTime::from_seconds(seconds)?;
+        for _ in 0..2 {
+            gps = GpsTime::from_seconds(seconds + utc_offset(&gps))?;
+        }
+        Ok(gps)
+    }
+
+    /// Converts the GPS time into a TAI timestamp, counted from Jan 1st 1970
+    /// TAI like the `CLOCK_TAI` clock of Linux
+    ///
+    /// TAI doesn't have leap seconds so no UTC parameters are needed.
+    pub fn to_tai(&self) -> SystemTime {
+        let seconds = self.seconds() + GPS_EPOCH_UNIX_SECS + TAI_GPS_OFFSET.as_secs_f64();
+        UNIX_EPOCH + Duration::from_secs_f64(seconds)
+    }
+
+    /// Makes a GPS time from a TAI timestamp, counted from Jan 1st 1970 TAI
+    ///
+    /// Returns an error if the timestamp is before the start of GPS time
+    pub fn from_tai(time: SystemTime) -> Result<GpsTime, InvalidGpsTime> {
+        let tai = match time.duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_secs_f64(),
+            Err(before) => -before.duration().as_secs_f64(),
+        };
+        GpsTime::from_seconds(tai - GPS_EPOCH_UNIX_SECS - TAI_GPS_OFFSET.as_secs_f64())
+    }
+
+    /// Gets the number of seconds difference between TAI and UTC
+    pub fn tai_utc_offset(&self, utc_params: &UtcParams) -> f64 {
+        self.utc_offset(utc_params) + TAI_GPS_OFFSET.as_secs_f64()
+    }
+
+    /// Gets the number of seconds difference between TAI and UTC using the
+    /// hardcoded list of leap seconds
+    ///
+    /// # ⚠️  🦘  ⏱  ⚠️  - Leap Seconds
+    /// The hard coded list of leap seconds will get out of date, it is
+    /// preferable to use [`GpsTime::tai_utc_offset()`] with the newest set
+    /// of UTC parameters
+    pub fn tai_utc_offset_hardcoded(&self) -> f64 {
+        self.utc_offset_hardcoded() + TAI_GPS_OFFSET.as_secs_f64()
     }
 
     #[rustversion::since(1.62)]
@@ -333,6 +627,31 @@ impl fmt::Debug for GpsTime {
     }
 }
 
+/// Formats the time in the `WN:TOW` notation, such as `2100:345600.5`
+impl fmt::Display for GpsTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match f.precision() {
+            Some(precision) => write!(f, "{}:{:.*}", self.wn(), precision, self.tow()),
+            None => write!(f, "{}:{}", self.wn(), self.tow()),
+        }
+    }
+}
+
+/// Parses a time in the `WN:TOW` notation
+impl FromStr for GpsTime {
+    type Err = ParseTimeError;
+
+    fn from_str(s: &str) -> Result<GpsTime, ParseTimeError> {
+        let (wn, tow) = s
+            .trim()
+            .split_once(':')
+            .ok_or(ParseTimeError::InvalidFormat)?;
+        let wn = wn.parse().map_err(|_| ParseTimeError::InvalidFormat)?;
+        let tow = tow.parse().map_err(|_| ParseTimeError::InvalidFormat)?;
+        GpsTime::new(wn, tow).map_err(|_| ParseTimeError::InvalidValue)
+    }
+}
+
 impl PartialEq for GpsTime {
     fn eq(&self, other: &Self) -> bool {
         let diff_seconds = self.diff(other).abs();
@@ -368,19 +687,19 @@ impl AddAssign<Duration> for GpsTime {
     }
 }
 
+/// Subtracting a later time panics, use [`GpsTime::signed_diff()`] when the
+/// order of the times isn't known
 impl Sub<GpsTime> for GpsTime {
-    type Output = Duration;
-    fn sub(self, rhs: GpsTime) -> Duration {
-        let diff_seconds = self.diff(&rhs);
-        Duration::from_secs_f64(diff_seconds)
+    type Output = TimeDelta;
+    fn sub(self, rhs: GpsTime) -> TimeDelta {
+        self.signed_diff(&rhs)
     }
 }
 
 impl Sub<&GpsTime> for GpsTime {
-    type Output = Duration;
-    fn sub(self, rhs: &GpsTime) -> Duration {
-        let diff_seconds = self.diff(rhs);
-        Duration::from_secs_f64(diff_seconds)
+    type Output = TimeDelta;
+    fn sub(self, rhs: &GpsTime) -> TimeDelta {
+        self.signed_diff(rhs)
     }
 }
 
@@ -398,6 +717,23 @@ impl SubAssign<Duration> for GpsTime {
     }
 }
 
+impl Add<TimeDelta> for GpsTime {
+    type Output = Self;
+    fn add(mut self, rhs: TimeDelta) -> Self {
+        unsafe {
+            swiftnav_sys::add_secs(&mut self.0, rhs.as_secs_f64());
+        }
+        self
+    }
+}
+
+impl Sub<TimeDelta> for GpsTime {
+    type Output = Self;
+    fn sub(self, rhs: TimeDelta) -> Self {
+        self + (-rhs)
+    }
+}
+
 impl From<GalTime> for GpsTime {
     fn from(gal: GalTime) -> Self {
         gal.to_gps()
@@ -410,6 +746,368 @@ impl From<BdsTime> for GpsTime {
     }
 }
 
+/// Number of nanoseconds in a week
+const WEEK_NS: u64 = swiftnav_sys::WEEK_SECS as u64 * 1_000_000_000;
+
+/// High precision representation of GPS time
+///
+/// [`GpsTime`] stores the time of week as a double, which has a resolution of
+/// about 0.1 ns at the end of the week and loses more with every operation.
+/// This representation keeps the time of week as an integer number of
+/// nanoseconds plus a fraction of a nanosecond, so adding durations, which
+/// have a nanosecond resolution, and comparing times are exact.
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct PreciseGpsTime {
+    // The field order gives the chronological order to the derived traits
+    wn: i16,
+    tow_ns: u64,
+    sub_ns: f64,
+}
+
+impl PreciseGpsTime {
+    /// Makes a new precise GPS time, checking the validity of the values
+    ///
+    /// `sub_ns` is a fraction of a nanosecond to add to `tow_ns`, in the
+    /// range [0, 1).
+    pub fn new(wn: i16, tow_ns: u64, sub_ns: f64) -> Result<PreciseGpsTime, InvalidGpsTime> {
+        if wn < 0 {
+            Err(InvalidGpsTime::InvalidWN(wn))
+        } else if tow_ns >= WEEK_NS || !(0.0..1.0).contains(&sub_ns) {
+            Err(InvalidGpsTime::InvalidTOW(
+                tow_ns as f64 * 1e-9 + sub_ns * 1e-9,
+            ))
+        } else {
+            Ok(PreciseGpsTime { wn, tow_ns, sub_ns })
+        }
+    }
+
+    /// Makes a normalized time from a number of nanoseconds since the start
+    /// of GPS time
+    fn from_nanos(nanos: i128, sub_ns: f64) -> PreciseGpsTime {
+        let carry = sub_ns.floor();
+        let nanos = nanos + carry as i128;
+        let week = i128::from(WEEK_NS);
+        PreciseGpsTime {
+            wn: nanos.div_euclid(week) as i16,
+            tow_ns: nanos.rem_euclid(week) as u64,
+            sub_ns: sub_ns - carry,
+        }
+    }
+
+    /// Gets the number of whole nanoseconds since the start of GPS time
+    fn nanos(&self) -> i128 {
+        i128::from(self.wn) * i128::from(WEEK_NS) + i128::from(self.tow_ns)
+    }
+
+    pub fn wn(&self) -> i16 {
+        self.wn
+    }
+
+    /// Gets the whole nanoseconds of the time of week
+    pub fn tow_ns(&self) -> u64 {
+        self.tow_ns
+    }
+
+    /// Gets the fraction of a nanosecond of the time of week
+    pub fn sub_ns(&self) -> f64 {
+        self.sub_ns
+    }
+
+    /// Gets the time of week in seconds, rounded to double precision
+    pub fn tow(&self) -> f64 {
+        (self.tow_ns as f64 + self.sub_ns) * 1e-9
+    }
+
+    /// Gets the difference between this and another time in seconds
+    ///
+    /// The whole nanoseconds are subtracted exactly before converting to
+    /// double, so small differences keep their full precision.
+    pub fn diff(&self, other: &PreciseGpsTime) -> f64 {
+        self.diff_ns(other) * 1e-9
+    }
+
+    /// Gets the difference between this and another time in nanoseconds
+    pub fn diff_ns(&self, other: &PreciseGpsTime) -> f64 {
+        (self.nanos() - other.nanos()) as f64 + (self.sub_ns - other.sub_ns)
+    }
+
+    /// Gets the signed difference between this and another time, keeping
+    /// the full precision of both
+    pub fn signed_diff(&self, other: &PreciseGpsTime) -> TimeDelta {
+        TimeDelta::from_parts(
+            (self.nanos() - other.nanos()) as i64,
+            self.sub_ns - other.sub_ns,
+        )
+    }
+
+    /// Adds a number of seconds, which may be negative, to the time
+    pub fn add_seconds(self, seconds: f64) -> PreciseGpsTime {
+        let ns = seconds * 1e9;
+        let whole = ns.floor();
+        PreciseGpsTime::from_nanos(self.nanos() + whole as i128, self.sub_ns + (ns - whole))
+    }
+
+    /// Converts to the double precision representation
+    pub fn to_gps_time(&self) -> GpsTime {
+        GpsTime::new_unchecked(self.wn, self.tow())
+    }
+}
+
+impl From<GpsTime> for PreciseGpsTime {
+    fn from(gps: GpsTime) -> PreciseGpsTime {
+        let ns = gps.tow() * 1e9;
+        let whole = ns.floor();
+        PreciseGpsTime::from_nanos(
+            i128::from(gps.wn()) * i128::from(WEEK_NS) + whole as i128,
+            ns - whole,
+        )
+    }
+}
+
+impl From<PreciseGpsTime> for GpsTime {
+    fn from(precise: PreciseGpsTime) -> GpsTime {
+        precise.to_gps_time()
+    }
+}
+
+impl Add<Duration> for PreciseGpsTime {
+    type Output = Self;
+    fn add(self, rhs: Duration) -> Self {
+        PreciseGpsTime::from_nanos(self.nanos() + rhs.as_nanos() as i128, self.sub_ns)
+    }
+}
+
+impl AddAssign<Duration> for PreciseGpsTime {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub<Duration> for PreciseGpsTime {
+    type Output = Self;
+    fn sub(self, rhs: Duration) -> Self {
+        PreciseGpsTime::from_nanos(self.nanos() - rhs.as_nanos() as i128, self.sub_ns)
+    }
+}
+
+impl SubAssign<Duration> for PreciseGpsTime {
+    fn sub_assign(&mut self, rhs: Duration) {
+        *self = *self - rhs;
+    }
+}
+
+impl Add<TimeDelta> for PreciseGpsTime {
+    type Output = Self;
+    fn add(self, rhs: TimeDelta) -> Self {
+        PreciseGpsTime::from_nanos(
+            self.nanos() + i128::from(rhs.nanos),
+            self.sub_ns + rhs.sub_ns,
+        )
+    }
+}
+
+impl Sub<TimeDelta> for PreciseGpsTime {
+    type Output = Self;
+    fn sub(self, rhs: TimeDelta) -> Self {
+        self + (-rhs)
+    }
+}
+
+/// A GPS time with exact equality, ordering and hashing, for use as the key
+/// of epoch indexed maps and sets
+///
+/// [`GpsTime`] treats times within a small tolerance as equal, which isn't
+/// transitive, so it can't be used as a key. The key is the time rounded to
+/// the nearest nanosecond, stored as the week number and whole nanoseconds of
+/// the week, and compares these exactly. Times differing only by rounding
+/// errors of their arithmetic get the same key, unless they happen to fall
+/// either side of a half nanosecond.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GpsTimeKey {
+    // The field order gives the chronological order to the derived traits
+    wn: i16,
+    tow_ns: u64,
+}
+
+impl GpsTimeKey {
+    /// Makes a key from a week number and nanoseconds of the week, checking
+    /// the validity of the values
+    pub fn new(wn: i16, tow_ns: u64) -> Result<GpsTimeKey, InvalidGpsTime> {
+        if wn < 0 {
+            Err(InvalidGpsTime::InvalidWN(wn))
+        } else if tow_ns >= WEEK_NS {
+            Err(InvalidGpsTime::InvalidTOW(tow_ns as f64 * 1e-9))
+        } else {
+            Ok(GpsTimeKey { wn, tow_ns })
+        }
+    }
+
+    /// Makes a normalized key from a number of nanoseconds since the start
+    /// of GPS time
+    fn from_nanos(nanos: i128) -> GpsTimeKey {
+        let week = i128::from(WEEK_NS);
+        GpsTimeKey {
+            wn: nanos.div_euclid(week) as i16,
+            tow_ns: nanos.rem_euclid(week) as u64,
+        }
+    }
+
+    pub fn wn(&self) -> i16 {
+        self.wn
+    }
+
+    /// Gets the whole nanoseconds of the time of week
+    pub fn tow_ns(&self) -> u64 {
+        self.tow_ns
+    }
+
+    pub fn to_gps_time(&self) -> GpsTime {
+        GpsTime::new_unchecked(self.wn, self.tow_ns as f64 * 1e-9)
+    }
+}
+
+impl From<GpsTime> for GpsTimeKey {
+    fn from(gps: GpsTime) -> GpsTimeKey {
+        GpsTimeKey::from_nanos(
+            i128::from(gps.wn()) * i128::from(WEEK_NS) + (gps.tow() * 1e9).round() as i128,
+        )
+    }
+}
+
+impl From<PreciseGpsTime> for GpsTimeKey {
+    fn from(precise: PreciseGpsTime) -> GpsTimeKey {
+        GpsTimeKey::from_nanos(precise.nanos() + precise.sub_ns.round() as i128)
+    }
+}
+
+impl From<GpsTimeKey> for GpsTime {
+    fn from(key: GpsTimeKey) -> GpsTime {
+        key.to_gps_time()
+    }
+}
+
+impl From<GpsTimeKey> for PreciseGpsTime {
+    fn from(key: GpsTimeKey) -> PreciseGpsTime {
+        PreciseGpsTime {
+            wn: key.wn,
+            tow_ns: key.tow_ns,
+            sub_ns: 0.0,
+        }
+    }
+}
+
+/// A signed difference between two times
+///
+/// [`Duration`] can't be negative, so it can't represent the difference
+/// between two times without knowing which one comes first. The delta is
+/// stored as whole nanoseconds and a fraction of a nanosecond, like
+/// [`PreciseGpsTime`], covering about ±292 years.
+#[derive(Debug, Copy, Clone, Default, PartialOrd, PartialEq)]
+pub struct TimeDelta {
+    // The field order gives the numerical order to the derived traits
+    nanos: i64,
+    sub_ns: f64,
+}
+
+impl TimeDelta {
+    pub const ZERO: TimeDelta = TimeDelta {
+        nanos: 0,
+        sub_ns: 0.0,
+    };
+
+    /// Makes a normalized delta, with the fraction in the range [0, 1)
+    fn from_parts(nanos: i64, sub_ns: f64) -> TimeDelta {
+        let carry = sub_ns.floor();
+        TimeDelta {
+            nanos: nanos + carry as i64,
+            sub_ns: sub_ns - carry,
+        }
+    }
+
+    /// Makes a delta from a number of seconds
+    pub fn from_secs_f64(seconds: f64) -> TimeDelta {
+        let ns = seconds * 1e9;
+        let whole = ns.floor();
+        TimeDelta::from_parts(whole as i64, ns - whole)
+    }
+
+    /// Makes a delta from a whole number of nanoseconds
+    pub fn from_nanos(nanos: i64) -> TimeDelta {
+        TimeDelta { nanos, sub_ns: 0.0 }
+    }
+
+    /// Gets the delta in seconds
+    pub fn as_secs_f64(&self) -> f64 {
+        self.as_nanos_f64() * 1e-9
+    }
+
+    /// Gets the delta in nanoseconds
+    pub fn as_nanos_f64(&self) -> f64 {
+        self.nanos as f64 + self.sub_ns
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.nanos < 0
+    }
+
+    /// Gets the magnitude of the delta, rounded down to whole nanoseconds
+    pub fn unsigned_abs(&self) -> Duration {
+        let magnitude = if self.is_negative() { -*self } else { *self };
+        Duration::from_nanos(magnitude.nanos as u64)
+    }
+
+    /// Converts the delta to a [`Duration`], rounded down to whole
+    /// nanoseconds, or `None` if it is negative
+    pub fn to_duration(&self) -> Option<Duration> {
+        if self.is_negative() {
+            None
+        } else {
+            Some(Duration::from_nanos(self.nanos as u64))
+        }
+    }
+}
+
+impl TryFrom<Duration> for TimeDelta {
+    type Error = TryFromIntError;
+
+    /// Converts a duration, failing if it is too long for a delta
+    fn try_from(duration: Duration) -> Result<TimeDelta, TryFromIntError> {
+        i64::try_from(duration.as_nanos()).map(TimeDelta::from_nanos)
+    }
+}
+
+impl Neg for TimeDelta {
+    type Output = Self;
+    fn neg(self) -> Self {
+        TimeDelta::from_parts(-self.nanos, -self.sub_ns)
+    }
+}
+
+impl Add for TimeDelta {
+    type Output = Self;
+    fn add(self, rhs: TimeDelta) -> Self {
+        TimeDelta::from_parts(self.nanos + rhs.nanos, self.sub_ns + rhs.sub_ns)
+    }
+}
+
+impl Sub for TimeDelta {
+    type Output = Self;
+    fn sub(self, rhs: TimeDelta) -> Self {
+        self + (-rhs)
+    }
+}
+
+/// Finds the week closest to the `reference` week which is equal to `week`
+/// modulo `modulus`
+pub(crate) fn nearest_week(reference: i16, week: i32, modulus: i32) -> i16 {
+    let reference = i32::from(reference);
+    let mut delta = (week - reference).rem_euclid(modulus);
+    if delta > modulus / 2 {
+        delta -= modulus;
+    }
+    (reference + delta) as i16
+}
+
 /// Representation of Galileo Time
 #[derive(Debug, Copy, Clone)]
 pub struct GalTime {
@@ -428,6 +1126,28 @@ impl GalTime {
         }
     }
 
+    /// Makes a Galileo time from a 12 bit week number, resolving the week
+    /// rollovers with a reference time
+    ///
+    /// The week closest to the week of `reference` is chosen, see
+    /// [`GpsTime::from_truncated_wn()`]
+    pub fn from_truncated_wn(
+        wn12: u16,
+        tow: f64,
+        reference: &GpsTime,
+    ) -> Result<GalTime, InvalidGpsTime> {
+        if wn12 >= GAL_WEEK_MODULUS {
+            return Err(InvalidGpsTime::InvalidWN(wn12 as i16));
+        }
+        let offset = swiftnav_sys::GAL_WEEK_TO_GPS_WEEK as i32;
+        let wn = nearest_week(
+            reference.wn(),
+            i32::from(wn12) + offset,
+            i32::from(GAL_WEEK_MODULUS),
+        );
+        GalTime::new(wn - offset as i16, tow)
+    }
+
     pub fn wn(&self) -> i16 {
         self.wn
     }
@@ -478,6 +1198,28 @@ impl BdsTime {
         }
     }
 
+    /// Makes a Beidou time from a 13 bit week number, resolving the week
+    /// rollovers with a reference time
+    ///
+    /// The week closest to the week of `reference` is chosen, see
+    /// [`GpsTime::from_truncated_wn()`]
+    pub fn from_truncated_wn(
+        wn13: u16,
+        tow: f64,
+        reference: &GpsTime,
+    ) -> Result<BdsTime, InvalidGpsTime> {
+        if wn13 >= BDS_WEEK_MODULUS {
+            return Err(InvalidGpsTime::InvalidWN(wn13 as i16));
+        }
+        let offset = swiftnav_sys::BDS_WEEK_TO_GPS_WEEK as i32;
+        let wn = nearest_week(
+            reference.wn(),
+            i32::from(wn13) + offset,
+            i32::from(BDS_WEEK_MODULUS),
+        );
+        BdsTime::new(wn - offset as i16, tow)
+    }
+
     pub fn wn(&self) -> i16 {
         self.wn
     }
@@ -512,14 +1254,23 @@ impl From<GalTime> for BdsTime {
 }
 
 /// Representation of Glonass Time
-#[derive(Copy, Clone)]
-pub struct GloTime(swiftnav_sys::glo_time_t);
+///
+/// Glonass time follows UTC(SU), which is three hours ahead of UTC, so unlike
+/// the other GNSS time bases it includes leap seconds. The day is counted
+/// within four year intervals starting from January 1st 1996.
+///
+/// The fields are in chronological order, so the derived ordering compares
+/// the four year interval first.
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct GloTime {
+    n4: u8,
+    nt: u16,
+    h: u8,
+    m: u8,
+    s: f64,
+}
 
 impl GloTime {
-    pub(crate) fn c_ptr(&self) -> *const swiftnav_sys::glo_time_t {
-        &self.0
-    }
-
     /// Creates a new GloTime
     /// nt - Day number within the four-year interval [1-1461].
     ///      Comes from the field NT in the GLO string 4.
@@ -533,32 +1284,32 @@ impl GloTime {
     /// m - Minutes [0-59]
     /// s - Seconds [0-60]
     pub fn new(nt: u16, n4: u8, h: u8, m: u8, s: f64) -> GloTime {
-        GloTime(swiftnav_sys::glo_time_t { nt, n4, h, m, s })
+        GloTime { nt, n4, h, m, s }
     }
 
     pub fn nt(&self) -> u16 {
-        self.0.nt
+        self.nt
     }
 
     pub fn n4(&self) -> u8 {
-        self.0.n4
+        self.n4
     }
 
     pub fn h(&self) -> u8 {
-        self.0.h
+        self.h
     }
 
     pub fn m(&self) -> u8 {
-        self.0.m
+        self.m
     }
 
     pub fn s(&self) -> f64 {
-        self.0.s
+        self.s
     }
 
     /// Converts a Glonass time into a GPS time
     pub fn to_gps(self, utc_params: &UtcParams) -> GpsTime {
-        GpsTime(unsafe { swiftnav_sys::glo2gps(self.c_ptr(), utc_params.c_ptr()) })
+        glo_to_gps(&self, Some(utc_params))
     }
 
     /// Converts a Glonass time into a GPS time using the hardcoded list of leap
@@ -567,39 +1318,168 @@ impl GloTime {
     /// Note: The hard coded list of leap seconds will get out of date, it is
     /// preferable to use [`GloTime::to_gps()`] with the newest set of UTC parameters
     pub fn to_gps_hardcoded(self) -> GpsTime {
-        GpsTime(unsafe { swiftnav_sys::glo2gps(self.c_ptr(), std::ptr::null()) })
+        glo_to_gps(&self, None)
+    }
+
+    /// Gets the number of seconds since the start of Glonass time, not
+    /// counting leap seconds
+    fn seconds_since_epoch(&self) -> f64 {
+        let days = u32::from(self.n4.saturating_sub(1)) * GLO_DAYS_IN_4_YEARS
+            + u32::from(self.nt.saturating_sub(1));
+        f64::from(days) * DAY.as_secs_f64()
+            + f64::from(self.h) * HOUR.as_secs_f64()
+            + f64::from(self.m) * MINUTE.as_secs_f64()
+            + self.s
+    }
+}
+
+/// Number of days in a Glonass four year interval
+const GLO_DAYS_IN_4_YEARS: u32 = 1461;
+
+/// Gets the GPS-UTC offset, from the UTC parameters if given or from the
+/// hardcoded list of leap seconds otherwise
+fn gps_utc_offset(t: &GpsTime, utc_params: Option<&UtcParams>) -> f64 {
+    match utc_params {
+        Some(utc_params) => t.utc_offset(utc_params),
+        None => t.utc_offset_hardcoded(),
+    }
+}
+
+/// Converts a GPS time into Glonass time
+///
+/// Glonass time is counted from [`GLO_TIME_START`] like GPS time, except the
+/// leap seconds inserted since then are removed. During a leap second the
+/// seconds of the Glonass time reach 60.
+fn gps_to_glo(gps: &GpsTime, utc_params: Option<&UtcParams>) -> GloTime {
+    let leap_second = match utc_params {
+        Some(utc_params) => gps.is_leap_second_event(utc_params),
+        None => gps.is_leap_second_event_hardcoded(),
+    };
+    // Count the leap second as the last second of the previous minute
+    let gps = if leap_second {
+        *gps - Duration::from_secs(1)
+    } else {
+        *gps
+    };
+    let epoch_offset = GLO_TIME_START.utc_offset_hardcoded();
+    let elapsed = gps.diff(&GLO_TIME_START) - (gps_utc_offset(&gps, utc_params) - epoch_offset);
+
+    let day = DAY.as_secs_f64();
+    let days = (elapsed / day).floor();
+    let mut seconds = elapsed - days * day;
+    let days = days as u32;
+    let h = (seconds / HOUR.as_secs_f64()).floor();
+    seconds -= h * HOUR.as_secs_f64();
+    let m = (seconds / MINUTE.as_secs_f64()).floor();
+    seconds -= m * MINUTE.as_secs_f64();
+    if leap_second {
+        seconds += 1.0;
+    }
+    GloTime {
+        nt: (days % GLO_DAYS_IN_4_YEARS + 1) as u16,
+        n4: (days / GLO_DAYS_IN_4_YEARS + 1) as u8,
+        h: h as u8,
+        m: m as u8,
+        s: seconds,
     }
 }
 
+/// Converts a Glonass time into GPS time
+///
+/// The leap seconds to add back depend on the GPS time, so the conversion is
+/// iterated from the time without them.
+fn glo_to_gps(glo: &GloTime, utc_params: Option<&UtcParams>) -> GpsTime {
+    let elapsed = glo.seconds_since_epoch();
+    let epoch_offset = GLO_TIME_START.utc_offset_hardcoded();
+    let mut gps = GLO_TIME_START + Duration::from_secs_f64(elapsed);
+    for _ in 0..2 {
+        let leap_seconds = gps_utc_offset(&gps, utc_params) - epoch_offset;
+        gps = GLO_TIME_START + Duration::from_secs_f64(elapsed + leap_seconds);
+    }
+    gps
+}
+
+/// Data ID of the LNAV almanac pages
+const LNAV_DATA_ID: u32 = 1;
+
+/// SV ID of the LNAV page holding the UTC parameters, page 18 of subframe 4
+const LNAV_UTC_SV_ID: u32 = 56;
+
+/// Gets bits `first` to `first + len - 1` of word `n` of an LNAV subframe,
+/// from `words` holding words 3 to 10, with bit 1 being the MSB
+fn lnav_field(words: &[u32; 8], n: usize, first: u32, len: u32) -> u32 {
+    (words[n - 3] >> (31 - first - len)) & ((1 << len) - 1)
+}
+
 /// GPS UTC correction parameters
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct UtcParams(swiftnav_sys::utc_params_t);
 
 impl UtcParams {
-    pub(crate) fn mut_c_ptr(&mut self) -> *mut swiftnav_sys::utc_params_t {
-        &mut self.0
-    }
-
     pub(crate) fn c_ptr(&self) -> *const swiftnav_sys::utc_params_t {
         &self.0
     }
 
     /// Decodes UTC parameters from GPS LNAV message subframe 4 words 6-10.
     ///
-    /// Note: Fills out the full time of week from current gps week cycle. Also
-    /// sets t_lse to the exact GPS time at the start of the leap second event.
+    /// `words` are words 3 to 10 of subframe 4 page 18, with each word in the
+    /// 30 LSBs of the u32. Returns `None` if the words aren't from page 18.
+    ///
+    /// Note: Only the 8 LSBs of the week numbers are broadcast. The reference
+    /// time of the parameters is resolved to the week closest to the week of
+    /// `reference`, e.g. the time the subframe was received, and the week of
+    /// the leap second event to the week closest to the reference time of the
+    /// parameters. Also sets t_lse to the exact GPS time at the start of the
+    /// leap second event.
     ///
     /// # References
     ///   * IS-GPS-200H, Section 20.3.3.5.1.6
-    pub fn decode(words: &[u32; 8]) -> Option<Self> {
-        let mut params = UtcParams::default();
-        let result = unsafe { swiftnav_sys::decode_utc_parameters(words, params.mut_c_ptr()) };
+    pub fn decode(words: &[u32; 8], reference: &GpsTime) -> Option<Self> {
+        let field = |n: usize, first: u32, len: u32| lnav_field(words, n, first, len);
+        let signed = |n: usize, first: u32, len: u32| {
+            ((field(n, first, len) << (32 - len)) as i32) >> (32 - len)
+        };
+
+        if field(3, 1, 2) != LNAV_DATA_ID || field(3, 3, 6) != LNAV_UTC_SV_ID {
+            return None;
+        }
 
-        if result {
-            Some(params)
-        } else {
-            None
+        let a1 = f64::from(signed(6, 1, 24)) * 2f64.powi(-50);
+        let a0 = f64::from(((field(7, 1, 24) << 8) | field(8, 1, 8)) as i32) * 2f64.powi(-30);
+        let tot = f64::from(field(8, 9, 8)) * 4096.0;
+        let wn_t = nearest_week(reference.wn(), field(8, 17, 8) as i32, 256);
+        let dt_ls = signed(9, 1, 8) as i8;
+        let wn_lsf = nearest_week(wn_t, field(9, 9, 8) as i32, 256);
+        let dn = field(9, 17, 8);
+        let dt_lsf = signed(10, 1, 8) as i8;
+
+        let tot = GpsTime::new(wn_t, tot).ok()?;
+        // The leap second is inserted at the end of day DN in UTC, with
+        // Sunday being day 1. The time of week is kept apart from the week so
+        // the sub-nanosecond terms of the correction aren't lost.
+        let mut wn_lse = wn_lsf;
+        let mut tow_lse = f64::from(dn) * DAY.as_secs_f64() + f64::from(dt_ls);
+        let dt = f64::from(wn_lse - tot.wn()) * WEEK.as_secs_f64() + tow_lse - tot.tow();
+        tow_lse += a0 + a1 * dt;
+        while tow_lse >= WEEK.as_secs_f64() {
+            tow_lse -= WEEK.as_secs_f64();
+            wn_lse += 1;
         }
+        while tow_lse < 0.0 {
+            tow_lse += WEEK.as_secs_f64();
+            wn_lse -= 1;
+        }
+        let t_lse = GpsTime::new(wn_lse, tow_lse).ok()?;
+
+        Some(UtcParams(swiftnav_sys::utc_params_t {
+            a0,
+            a1,
+            a2: 0.0,
+            tot: tot.to_gps_time_t(),
+            t_lse: t_lse.to_gps_time_t(),
+            dt_ls,
+            dt_lsf,
+        }))
     }
 
     /// Build the UTC parameters from the already decoded parameters
@@ -666,6 +1546,327 @@ impl Default for UtcParams {
     }
 }
 
+/// Offset between Galileo system time and GPS time, as broadcast by Galileo
+///
+/// The offset is modelled as a first order polynomial, it stays within a few
+/// tens of nanoseconds.
+///
+/// # References
+///   * Galileo OS SIS ICD Issue 2.0, Section 5.1.8
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct GgtoParams {
+    a0g: f64,
+    a1g: f64,
+    t0g: GpsTime,
+}
+
+impl GgtoParams {
+    /// Makes the parameters from the already decoded terms
+    pub fn new(a0g: f64, a1g: f64, t0g: GpsTime) -> GgtoParams {
+        GgtoParams { a0g, a1g, t0g }
+    }
+
+    /// Decodes the parameters from a Galileo I/NAV word of type 10
+    ///
+    /// The 6 bit week number of the reference time is resolved to the week
+    /// closest to the week of `reference`. Returns `None` if the word isn't of
+    /// type 10, or if the parameters are marked as unavailable.
+    pub fn decode_inav(
+        word: &[u8; crate::ephemeris::GAL_INAV_CONTENT_BYTE],
+        reference: &GpsTime,
+    ) -> Option<GgtoParams> {
+        use crate::ephemeris::{inav_field, inav_signed_field};
+
+        if inav_field(word, 0, 6) != 10 {
+            return None;
+        }
+        // All of the GGTO bits are set when the offset isn't available
+        if inav_field(word, 86, 42) == (1 << 42) - 1 {
+            return None;
+        }
+        let a0g = inav_signed_field(word, 86, 16) as f64 * 2f64.powi(-35);
+        let a1g = inav_signed_field(word, 102, 12) as f64 * 2f64.powi(-51);
+        let t0g = inav_field(word, 114, 8) as f64 * 3600.0;
+        // The GST weeks are offset from the GPS weeks by a multiple of 64
+        let wn0g = nearest_week(reference.wn(), inav_field(word, 122, 6) as i32, 64);
+        Some(GgtoParams {
+            a0g,
+            a1g,
+            t0g: GpsTime::new(wn0g, t0g).ok()?,
+        })
+    }
+
+    /// Constant term of the offset \[s\]
+    pub fn a0g(&self) -> f64 {
+        self.a0g
+    }
+
+    /// Rate of change of the offset \[s/s\]
+    pub fn a1g(&self) -> f64 {
+        self.a1g
+    }
+
+    /// Reference time of the parameters
+    pub fn t0g(&self) -> GpsTime {
+        self.t0g
+    }
+
+    /// Gets Galileo system time minus GPS time at a time, in seconds
+    pub fn offset(&self, t: &GpsTime) -> f64 {
+        self.a0g + self.a1g * t.diff(&self.t0g)
+    }
+
+    /// Converts a Galileo time into GPS time, accounting for the offset
+    /// between the two time scales
+    pub fn gal_to_gps(&self, gal: GalTime) -> GpsTime {
+        let gps = gal.to_gps();
+        gps - TimeDelta::from_secs_f64(self.offset(&gps))
+    }
+
+    /// Converts a GPS time into Galileo time, accounting for the offset
+    /// between the two time scales
+    pub fn gps_to_gal(&self, gps: GpsTime) -> GalTime {
+        (gps + TimeDelta::from_secs_f64(self.offset(&gps))).to_gal()
+    }
+}
+
+/// Settings of a [`UtcParamsStore`]
+#[derive(Debug, Clone, PartialOrd, PartialEq, Eq, Hash)]
+pub struct UtcStoreSettings {
+    priorities: Vec<Constellation>,
+    max_age: Duration,
+}
+
+impl UtcStoreSettings {
+    /// Makes the default settings
+    ///
+    /// Note: The default settings consist of
+    ///  * Parameters from GPS, Galileo, QZSS, BeiDou and GLONASS are used, in
+    ///    that order of priority
+    ///  * Parameters are used up to 7 days from their reference time
+    pub fn new() -> UtcStoreSettings {
+        UtcStoreSettings {
+            priorities: vec![
+                Constellation::Gps,
+                Constellation::Gal,
+                Constellation::Qzs,
+                Constellation::Bds,
+                Constellation::Glo,
+            ],
+            max_age: Duration::from_secs(7 * 24 * 3600),
+        }
+    }
+
+    /// Sets the constellations whose parameters are used, highest priority
+    /// first
+    pub fn set_priorities(self, priorities: Vec<Constellation>) -> UtcStoreSettings {
+        UtcStoreSettings { priorities, ..self }
+    }
+
+    /// Sets how far from their reference time parameters are used
+    pub fn set_max_age(self, max_age: Duration) -> UtcStoreSettings {
+        UtcStoreSettings { max_age, ..self }
+    }
+
+    pub fn priorities(&self) -> &[Constellation] {
+        &self.priorities
+    }
+
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+}
+
+impl Default for UtcStoreSettings {
+    fn default() -> UtcStoreSettings {
+        UtcStoreSettings::new()
+    }
+}
+
+/// The newest UTC parameters decoded from each constellation, and the newest
+/// GPS to Galileo time offset
+///
+/// The parameters are stored in the GPS to UTC form of [`UtcParams`], and
+/// must be converted to that form before being added, e.g. by accounting for
+/// the 14 second offset between BeiDou time and GPS time. Time conversions
+/// use the freshest set of parameters valid at the time being converted,
+/// when sets from several constellations have the same reference time the
+/// constellation priorities decide. The hardcoded list of leap seconds is used
+/// when there are no valid parameters.
+#[derive(Clone)]
+pub struct UtcParamsStore {
+    settings: UtcStoreSettings,
+    params: BTreeMap<Constellation, UtcParams>,
+    ggto: Option<GgtoParams>,
+}
+
+impl UtcParamsStore {
+    pub fn new(settings: UtcStoreSettings) -> UtcParamsStore {
+        UtcParamsStore {
+            settings,
+            params: BTreeMap::new(),
+            ggto: None,
+        }
+    }
+
+    pub fn settings(&self) -> &UtcStoreSettings {
+        &self.settings
+    }
+
+    /// Adds parameters decoded from a constellation
+    ///
+    /// Returns `false` if the parameters aren't newer than the ones already
+    /// stored for the constellation, in which case they are ignored.
+    pub fn insert(&mut self, constellation: Constellation, params: UtcParams) -> bool {
+        if !params.tot().is_valid() {
+            return false;
+        }
+        match self.params.get(&constellation) {
+            Some(existing) if existing.tot() >= params.tot() => false,
+            _ => {
+                self.params.insert(constellation, params);
+                true
+            }
+        }
+    }
+
+    /// Gets the newest parameters decoded from a constellation
+    pub fn get(&self, constellation: Constellation) -> Option<&UtcParams> {
+        self.params.get(&constellation)
+    }
+
+    /// Gets the parameters to use at a time, if any are valid
+    pub fn params_at(&self, t: &GpsTime) -> Option<&UtcParams> {
+        let max_age = self.settings.max_age.as_secs_f64();
+        self.settings
+            .priorities
+            .iter()
+            .enumerate()
+            .filter_map(|(priority, constellation)| {
+                self.params
+                    .get(constellation)
+                    .map(|params| (priority, params))
+            })
+            .filter(|(_, params)| t.diff(&params.tot()).abs() <= max_age)
+            .max_by(|(priority_a, a), (priority_b, b)| {
+                a.tot()
+                    .partial_cmp(&b.tot())
+                    .unwrap()
+                    .then(priority_b.cmp(priority_a))
+            })
+            .map(|(_, params)| params)
+    }
+
+    /// Gets the number of seconds difference between GPS and UTC times
+    pub fn utc_offset(&self, t: &GpsTime) -> f64 {
+        match self.params_at(t) {
+            Some(params) => t.utc_offset(params),
+            None => t.utc_offset_hardcoded(),
+        }
+    }
+
+    /// Checks to see if a point in time is a UTC leap second event
+    pub fn is_leap_second_event(&self, t: &GpsTime) -> bool {
+        match self.params_at(t) {
+            Some(params) => t.is_leap_second_event(params),
+            None => t.is_leap_second_event_hardcoded(),
+        }
+    }
+
+    /// Converts a GPS time into UTC time
+    ///
+    /// # Panics
+    /// This function will panic if the GPS time is not valid
+    pub fn gps_to_utc(&self, t: GpsTime) -> UtcTime {
+        match self.params_at(&t) {
+            Some(params) => t.to_utc(params),
+            None => t.to_utc_hardcoded(),
+        }
+    }
+
+    /// Converts a UTC time into GPS time
+    pub fn utc_to_gps(&self, utc: &UtcTime) -> GpsTime {
+        // The parameters are picked using the approximate GPS time, they are
+        // valid for days so the leap seconds don't matter
+        match self.params_at(&utc.to_gps_hardcoded()) {
+            Some(params) => utc.to_gps(params),
+            None => utc.to_gps_hardcoded(),
+        }
+    }
+
+    /// Converts a GPS time into a Glonass time
+    pub fn gps_to_glo(&self, t: GpsTime) -> GloTime {
+        match self.params_at(&t) {
+            Some(params) => t.to_glo(params),
+            None => t.to_glo_hardcoded(),
+        }
+    }
+
+    /// Converts a Glonass time into a GPS time
+    pub fn glo_to_gps(&self, glo: GloTime) -> GpsTime {
+        match self.params_at(&glo.to_gps_hardcoded()) {
+            Some(params) => glo.to_gps(params),
+            None => glo.to_gps_hardcoded(),
+        }
+    }
+
+    /// Adds the GPS to Galileo time offset parameters
+    ///
+    /// Returns `false` if the parameters aren't newer than the ones already
+    /// stored, in which case they are ignored.
+    pub fn insert_ggto(&mut self, ggto: GgtoParams) -> bool {
+        match &self.ggto {
+            Some(existing) if existing.t0g() >= ggto.t0g() => false,
+            _ => {
+                self.ggto = Some(ggto);
+                true
+            }
+        }
+    }
+
+    /// Gets the GPS to Galileo time offset parameters to use at a time, if
+    /// they are valid
+    pub fn ggto_at(&self, t: &GpsTime) -> Option<&GgtoParams> {
+        self.ggto
+            .as_ref()
+            .filter(|ggto| t.diff(&ggto.t0g()).abs() <= self.settings.max_age.as_secs_f64())
+    }
+
+    /// Converts a Galileo time into GPS time
+    ///
+    /// The nominal alignment of the two time scales is used when there are no
+    /// valid GPS to Galileo time offset parameters.
+    pub fn gal_to_gps(&self, gal: GalTime) -> GpsTime {
+        match self.ggto_at(&gal.to_gps()) {
+            Some(ggto) => ggto.gal_to_gps(gal),
+            None => gal.to_gps(),
+        }
+    }
+
+    /// Converts a GPS time into Galileo time
+    pub fn gps_to_gal(&self, gps: GpsTime) -> GalTime {
+        match self.ggto_at(&gps) {
+            Some(ggto) => ggto.gps_to_gal(gps),
+            None => gps.to_gal(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.params.clear();
+        self.ggto = None;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty() && self.ggto.is_none()
+    }
+}
+
+impl Default for UtcParamsStore {
+    fn default() -> UtcParamsStore {
+        UtcParamsStore::new(UtcStoreSettings::new())
+    }
+}
+
 /// Representation of UTC time
 ///
 /// Note: This implementation does not aim to be able to represent arbitrary dates and times.
@@ -801,6 +2002,89 @@ impl Default for UtcTime {
     }
 }
 
+/// Formats the time as an ISO8601 string, such as `2021-08-01T00:11:00.000Z`
+///
+/// The seconds have three decimals unless another precision is given, and
+/// are truncated rather than rounded so the other fields never change.
+impl fmt::Display for UtcTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year(),
+            self.month(),
+            self.day_of_month(),
+            self.hour(),
+            self.minute(),
+            self.0.second_int
+        )?;
+        let precision = f.precision().unwrap_or(3).min(9);
+        if precision > 0 {
+            let scale = 10u64.pow(precision as u32);
+            let fraction = ((self.0.second_frac * scale as f64) as u64).min(scale - 1);
+            write!(f, ".{:0width$}", fraction, width = precision)?;
+        }
+        write!(f, "Z")
+    }
+}
+
+/// Parses an ISO8601 date and time in UTC, such as
+/// `2021-08-01T00:11:00.250Z`
+///
+/// The seconds may have any number of decimals, a space can be used instead
+/// of the `T`, and the `Z` is optional.
+impl FromStr for UtcTime {
+    type Err = ParseTimeError;
+
+    fn from_str(s: &str) -> Result<UtcTime, ParseTimeError> {
+        let s = s.trim();
+        let s = s.strip_suffix('Z').unwrap_or(s);
+        let (date, time) = s
+            .split_once('T')
+            .or_else(|| s.split_once(' '))
+            .ok_or(ParseTimeError::InvalidFormat)?;
+        let number = |field: &str, digits: usize| {
+            if field.len() == digits && field.bytes().all(|b| b.is_ascii_digit()) {
+                field
+                    .parse::<u16>()
+                    .map_err(|_| ParseTimeError::InvalidFormat)
+            } else {
+                Err(ParseTimeError::InvalidFormat)
+            }
+        };
+
+        let date: Vec<&str> = date.split('-').collect();
+        let time: Vec<&str> = time.split(':').collect();
+        if date.len() != 3 || time.len() != 3 {
+            return Err(ParseTimeError::InvalidFormat);
+        }
+        let (year, month, day) = (
+            number(date[0], 4)?,
+            number(date[1], 2)?,
+            number(date[2], 2)?,
+        );
+        let (hour, minute) = (number(time[0], 2)?, number(time[1], 2)?);
+        let (whole, fraction) = time[2].split_once('.').unwrap_or((time[2], ""));
+        number(whole, 2)?;
+        if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseTimeError::InvalidFormat);
+        }
+        let seconds: f64 = time[2].parse().map_err(|_| ParseTimeError::InvalidFormat)?;
+        // Leap seconds are numbered 60
+        if !valid_date(year, month, day) || hour >= 24 || minute >= 60 || seconds >= 61.0 {
+            return Err(ParseTimeError::InvalidValue);
+        }
+        Ok(UtcTime::from_date(
+            year,
+            month as u8,
+            day as u8,
+            hour as u8,
+            minute as u8,
+            seconds,
+        ))
+    }
+}
+
 impl From<MJD> for UtcTime {
     fn from(mjd: MJD) -> UtcTime {
         mjd.to_utc()
@@ -897,9 +2181,53 @@ pub fn is_leap_year(year: u16) -> bool {
     ((year % 4 == 0) && (year % 100 != 0)) || (year % 400 == 0)
 }
 
+/// Checks a calendar date exists
+fn valid_date(year: u16, month: u16, day: u16) -> bool {
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => return false,
+    };
+    (1..=days_in_month).contains(&day)
+}
+
+/// Gets the number of days from Jan 1st 1970 to a date of the proleptic
+/// Gregorian calendar
+///
+/// # References
+///   * H. Hinnant, "chrono-Compatible Low-Level Date Algorithms"
+fn days_from_civil(year: u16, month: u16, day: u16) -> i64 {
+    let year = i64::from(year) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Gets the date of the proleptic Gregorian calendar a number of days after
+/// Jan 1st 1970, the inverse of [`days_from_civil`]
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month as u8, day as u8)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use float_eq::assert_float_eq;
 
     #[test]
     fn validity() {
@@ -1147,6 +2475,94 @@ mod tests {
         )
     }
 
+    /// Encodes subframe 4 page 18 words 3-10 with the given UTC parameters
+    fn encode_utc_words(
+        a0: i32,
+        a1: i32,
+        tot: u8,
+        wn_t: u8,
+        dt_ls: i8,
+        wn_lsf: u8,
+        dn: u8,
+        dt_lsf: i8,
+    ) -> [u32; 8] {
+        // Data bits 1-24 of a word, leaving the parity bits cleared
+        let word = |data: u32| (data & 0xFF_FFFF) << 6;
+        let a0 = a0 as u32;
+        [
+            word((LNAV_DATA_ID << 22) | (LNAV_UTC_SV_ID << 16)),
+            0,
+            0,
+            word(a1 as u32),
+            word(a0 >> 8),
+            word(((a0 & 0xFF) << 16) | (u32::from(tot) << 8) | u32::from(wn_t)),
+            word((u32::from(dt_ls as u8) << 16) | (u32::from(wn_lsf) << 8) | u32::from(dn)),
+            word(u32::from(dt_lsf as u8) << 16),
+        ]
+    }
+
+    #[test]
+    fn decode_utc_params() {
+        // The leap second at the end of 31 Dec 2016, the last day of week 1929
+        let words = encode_utc_words(
+            -12,
+            -5,
+            36,
+            (1925 % 256) as u8,
+            17,
+            (1929 % 256) as u8,
+            7,
+            18,
+        );
+        let params = UtcParams::decode(&words, &GpsTime::new_unchecked(2000, 0.0)).unwrap();
+
+        let a0 = -12.0 * 2f64.powi(-30);
+        let a1 = -5.0 * 2f64.powi(-50);
+        assert_eq!(params.a0(), a0);
+        assert_eq!(params.a1(), a1);
+        assert_eq!(params.a2(), 0.0);
+        assert_eq!(params.tot(), GpsTime::new_unchecked(1925, 36.0 * 4096.0));
+        assert_eq!(params.dt_ls(), 17);
+        assert_eq!(params.dt_lsf(), 18);
+
+        let dt = GpsTime::new_unchecked(1930, 17.0).diff(&params.tot());
+        assert_eq!(params.t_lse().wn(), 1930);
+        assert_float_eq!(params.t_lse().tow(), 17.0 + a0 + a1 * dt, abs <= 1e-9);
+
+        // The weeks are resolved in the cycle of the reference time
+        let params = UtcParams::decode(&words, &GpsTime::new_unchecked(2200, 0.0)).unwrap();
+        assert_eq!(params.tot().wn(), 2181);
+        assert_eq!(params.t_lse().wn(), 2186);
+
+        let mut wrong_page = words;
+        wrong_page[0] = (LNAV_DATA_ID << 28) | (55 << 22);
+        assert!(UtcParams::decode(&wrong_page, &GpsTime::new_unchecked(2000, 0.0)).is_none());
+    }
+
+    #[test]
+    fn decode_utc_params_matches_c() {
+        let cases = [
+            encode_utc_words(-12, -5, 36, 133, 17, 137, 7, 18),
+            encode_utc_words(0x1234_5678, 0x7F_FFFF, 255, 0, -3, 255, 1, -2),
+            encode_utc_words(i32::MIN, -0x80_0000, 0, 255, 127, 0, 4, -128),
+        ];
+        for words in cases.iter() {
+            let mut c = UtcParams::default();
+            assert!(unsafe { swiftnav_sys::decode_utc_parameters(words, &mut c.0) });
+            let rust = UtcParams::decode(words, &c.tot()).unwrap();
+
+            assert_eq!(rust.a0(), c.a0());
+            assert_eq!(rust.a1(), c.a1());
+            assert_eq!(rust.a2(), c.a2());
+            assert_eq!(rust.dt_ls(), c.dt_ls());
+            assert_eq!(rust.dt_lsf(), c.dt_lsf());
+            assert_eq!(rust.tot().tow(), c.tot().tow());
+            assert_eq!(rust.tot().wn(), c.tot().wn());
+            assert_eq!(rust.t_lse().wn(), c.t_lse().wn());
+            assert_float_eq!(rust.t_lse().tow(), c.t_lse().tow(), abs <= 1e-6);
+        }
+    }
+
     #[test]
     fn utc_params() {
         struct TestCase {
@@ -1602,11 +3018,7 @@ mod tests {
         for (test_case, expectation) in test_cases.iter().zip(expectations.iter()) {
             let rounded = test_case.round_to_epoch(soln_freq);
 
-            let diff = if &rounded >= expectation {
-                rounded - expectation
-            } else {
-                *expectation - rounded
-            };
+            let diff = (rounded - expectation).unsigned_abs();
             assert!(diff < epsilon);
         }
     }
@@ -1630,7 +3042,7 @@ mod tests {
 
         for (test_case, expectation) in test_cases.iter().zip(expectations.iter()) {
             let rounded = test_case.floor_to_epoch(soln_freq);
-            assert!((rounded - expectation) < epsilon);
+            assert!((rounded - expectation).unsigned_abs() < epsilon);
         }
     }
 
@@ -1697,6 +3109,401 @@ mod tests {
         let gps = glo.to_gps_hardcoded();
         assert_eq!(gps.wn(), swiftnav_sys::GLO_EPOCH_WN as i16);
         assert!((gps.tow() - swiftnav_sys::GLO_EPOCH_TOW as f64).abs() < 1e-9);
+
+        // 2020-04-05 02:59:42 in Moscow, with 7 leap seconds since 1996
+        let gps = GpsTime::new(2100, 0.0).unwrap();
+        let glo = gps.to_glo_hardcoded();
+        assert_eq!(glo.n4(), 7);
+        assert_eq!(glo.nt(), 96);
+        assert_eq!(glo.h(), 2);
+        assert_eq!(glo.m(), 59);
+        assert!((glo.s() - 42.0).abs() < 1e-6);
+        assert_eq!(glo.to_gps_hardcoded(), gps);
+
+        // The four year interval comes before the day in the ordering
+        let end_of_cycle = GloTime::new(1461, 7, 23, 59, 59.0);
+        let start_of_cycle = GloTime::new(1, 8, 0, 0, 0.0);
+        assert!(end_of_cycle < start_of_cycle);
+        assert!(GloTime::new(1000, 7, 0, 0, 0.0) < GloTime::new(1, 8, 0, 0, 0.0));
+        assert!(GloTime::new(96, 7, 2, 59, 42.5) > GloTime::new(96, 7, 2, 59, 42.0));
+
+        let glo = GloTime::new(96, 7, 2, 59, 42.5);
+        let gps = glo.to_gps_hardcoded();
+        assert_eq!(gps.wn(), 2100);
+        assert!((gps.tow() - 0.5).abs() < 1e-6);
+
+        let utc_params = UtcParams::from_components(
+            0.0,
+            0.0,
+            0.0,
+            &GpsTime::new(2100, 0.0).unwrap(),
+            &GpsTime::new(1930, 0.0).unwrap(),
+            18,
+            18,
+        );
+        let gps = GpsTime::new(2100, 3600.0).unwrap();
+        let glo = gps.to_glo(&utc_params);
+        assert_eq!(glo.h(), 3);
+        assert_eq!(glo.to_gps(&utc_params), gps);
+    }
+
+    #[test]
+    fn unix_and_tai() {
+        // 2020-04-05 00:00:00 GPS, 18 leap seconds ahead of UTC
+        let gps = GpsTime::new(2100, 0.0).unwrap();
+        let unix = UNIX_EPOCH + Duration::from_secs(1_586_044_782);
+        assert_eq!(gps.to_unix_hardcoded(), unix);
+        assert_eq!(GpsTime::from_unix_hardcoded(unix).unwrap(), gps);
+        assert!((gps.tai_utc_offset_hardcoded() - 37.0).abs() < 1e-9);
+
+        let utc_params = UtcParams::from_components(
+            0.0,
+            0.0,
+            0.0,
+            &GpsTime::new(2100, 0.0).unwrap(),
+            &GpsTime::new(1930, 0.0).unwrap(),
+            18,
+            18,
+        );
+        assert_eq!(gps.to_unix(&utc_params), unix);
+        assert_eq!(GpsTime::from_unix(unix, &utc_params).unwrap(), gps);
+
+        let tai = UNIX_EPOCH + Duration::from_secs(1_586_044_819);
+        assert_eq!(gps.to_tai(), tai);
+        assert_eq!(GpsTime::from_tai(tai).unwrap(), gps);
+
+        let fraction = gps + Duration::from_millis(250);
+        let converted = GpsTime::from_unix_hardcoded(fraction.to_unix_hardcoded()).unwrap();
+        assert!((converted.diff(&fraction)).abs() < 1e-6);
+
+        assert!(GpsTime::from_unix_hardcoded(UNIX_EPOCH).is_err());
+        assert!(GpsTime::from_tai(UNIX_EPOCH).is_err());
+    }
+
+    #[test]
+    fn truncated_week_numbers() {
+        let reference = GpsTime::new(2100, 0.0).unwrap();
+        let gps = GpsTime::from_truncated_wn(2100 % 1024, 10.0, &reference).unwrap();
+        assert_eq!(gps.wn(), 2100);
+        assert!((gps.tow() - 10.0).abs() < 1e-9);
+
+        // Across the next rollover in either direction
+        let reference = GpsTime::new(3071, 0.0).unwrap();
+        let gps = GpsTime::from_truncated_wn(1, 0.0, &reference).unwrap();
+        assert_eq!(gps.wn(), 3073);
+        let reference = GpsTime::new(3073, 0.0).unwrap();
+        let gps = GpsTime::from_truncated_wn(1023, 0.0, &reference).unwrap();
+        assert_eq!(gps.wn(), 3071);
+
+        assert!(GpsTime::from_truncated_wn(1024, 0.0, &reference).is_err());
+        assert!(GpsTime::from_truncated_wn(1, -1.0, &reference).is_err());
+
+        let reference = GpsTime::new(2100, 0.0).unwrap();
+        let gal = GalTime::from_truncated_wn(2100 - 1024, 5.0, &reference).unwrap();
+        assert_eq!(gal.wn(), 1076);
+        let gal = GalTime::from_truncated_wn(4095, 5.0, &GpsTime::new(5120, 0.0).unwrap());
+        assert_eq!(gal.unwrap().wn(), 4095);
+        assert!(GalTime::from_truncated_wn(4096, 0.0, &reference).is_err());
+
+        let bds = BdsTime::from_truncated_wn(744, 5.0, &reference).unwrap();
+        assert_eq!(bds.wn(), 744);
+        let bds = BdsTime::from_truncated_wn(2, 5.0, &GpsTime::new(1356 + 8190, 0.0).unwrap());
+        assert_eq!(bds.unwrap().wn(), 8194);
+        assert!(BdsTime::from_truncated_wn(8192, 0.0, &reference).is_err());
+    }
+
+    #[test]
+    fn precise_time() {
+        let t = PreciseGpsTime::new(2100, WEEK_NS - 1, 0.25).unwrap();
+        assert!((t.tow() - (WEEK.as_secs_f64() - 1e-9)).abs() < 1e-9);
+
+        // Crossing the week boundary is exact
+        let next = t + Duration::from_nanos(2);
+        assert_eq!(next.wn(), 2101);
+        assert_eq!(next.tow_ns(), 1);
+        assert!((next.sub_ns() - 0.25).abs() < 1e-12);
+        assert!((next.diff_ns(&t) - 2.0).abs() < 1e-12);
+        assert_eq!(next - Duration::from_nanos(2), t);
+        assert!(next > t);
+
+        // Many small steps don't accumulate any error
+        let mut stepped = t;
+        for _ in 0..1_000_000 {
+            stepped += Duration::from_nanos(1_001);
+        }
+        assert_eq!(stepped, t + Duration::from_nanos(1_001_000_000));
+
+        let shifted = t.add_seconds(-0.5e-9);
+        assert_eq!(shifted.tow_ns(), WEEK_NS - 2);
+        assert!((shifted.sub_ns() - 0.75).abs() < 1e-6);
+        assert!((t.diff_ns(&shifted) - 0.5).abs() < 1e-6);
+
+        let gps = GpsTime::new(2100, 12.5).unwrap();
+        let precise = PreciseGpsTime::from(gps);
+        assert_eq!(precise.tow_ns(), 12_500_000_000);
+        assert_eq!(precise.to_gps_time(), gps);
+
+        assert!(PreciseGpsTime::new(-1, 0, 0.0).is_err());
+        assert!(PreciseGpsTime::new(0, WEEK_NS, 0.0).is_err());
+        assert!(PreciseGpsTime::new(0, 0, 1.0).is_err());
+    }
+
+    #[test]
+    fn time_keys() {
+        use std::collections::{BTreeSet, HashMap};
+
+        // Rounding errors of the arithmetic don't change the key
+        let a = GpsTime::new(2100, 100.0 + 0.1 + 0.2).unwrap();
+        let b = GpsTime::new(2100, 100.3).unwrap();
+        let mut epochs = HashMap::new();
+        epochs.insert(GpsTimeKey::from(a), 1);
+        *epochs.entry(GpsTimeKey::from(b)).or_insert(0) += 1;
+        assert_eq!(epochs.len(), 1);
+        assert_eq!(epochs[&GpsTimeKey::from(b)], 2);
+
+        let key = GpsTimeKey::from(b);
+        assert_eq!(key.wn(), 2100);
+        assert_eq!(key.tow_ns(), 100_300_000_000);
+        assert_eq!(key.to_gps_time(), b);
+
+        // Rounding up can carry into the next week
+        let end = GpsTimeKey::from(PreciseGpsTime::new(2100, WEEK_NS - 1, 0.75).unwrap());
+        assert_eq!(end, GpsTimeKey::new(2101, 0).unwrap());
+        let precise = PreciseGpsTime::from(GpsTimeKey::new(2100, 5).unwrap());
+        assert_eq!(precise.tow_ns(), 5);
+        assert_eq!(precise.sub_ns(), 0.0);
+
+        let ordered: BTreeSet<GpsTimeKey> = [
+            GpsTimeKey::new(2101, 0).unwrap(),
+            GpsTimeKey::new(2100, WEEK_NS - 1).unwrap(),
+            GpsTimeKey::new(2100, 1).unwrap(),
+        ]
+        .iter()
+        .copied()
+        .collect();
+        let tows: Vec<(i16, u64)> = ordered.iter().map(|k| (k.wn(), k.tow_ns())).collect();
+        assert_eq!(tows, vec![(2100, 1), (2100, WEEK_NS - 1), (2101, 0)]);
+
+        assert!(GpsTimeKey::new(-1, 0).is_err());
+        assert!(GpsTimeKey::new(0, WEEK_NS).is_err());
+    }
+
+    #[test]
+    fn signed_differences() {
+        let a = GpsTime::new(2100, 10.0).unwrap();
+        let b = GpsTime::new(2100, 12.5).unwrap();
+        let delta = a.signed_diff(&b);
+        assert_eq!(a - b, delta);
+        assert!(delta.is_negative());
+        assert!((delta.as_secs_f64() + 2.5).abs() < 1e-9);
+        assert_eq!(delta.to_duration(), None);
+        assert_eq!(delta.unsigned_abs(), Duration::from_millis(2500));
+        assert_eq!(
+            b.signed_diff(&a).to_duration(),
+            Some(Duration::from_millis(2500))
+        );
+        assert_eq!(b + delta, a);
+        assert_eq!(a - delta, b);
+
+        let t = PreciseGpsTime::new(2100, 5, 0.75).unwrap();
+        let u = PreciseGpsTime::new(2100, 7, 0.25).unwrap();
+        let delta = t.signed_diff(&u);
+        assert!((delta.as_nanos_f64() + 1.5).abs() < 1e-12);
+        assert_eq!(u + delta, t);
+        assert_eq!(t - delta, u);
+        assert_eq!(-(-delta), delta);
+
+        let sum =
+            TimeDelta::try_from(Duration::from_secs(1)).unwrap() + TimeDelta::from_secs_f64(-1.5);
+        assert!((sum.as_secs_f64() + 0.5).abs() < 1e-12);
+        assert!(sum < TimeDelta::ZERO);
+        assert_eq!(sum - sum, TimeDelta::ZERO);
+        assert!(TimeDelta::try_from(Duration::MAX).is_err());
+    }
+
+    #[test]
+    fn gps_time_strings() {
+        let t = GpsTime::new(2100, 345_600.5).unwrap();
+        assert_eq!(t.to_string(), "2100:345600.5");
+        assert_eq!(format!("{:.3}", t), "2100:345600.500");
+        assert_eq!("2100:345600.5".parse::<GpsTime>().unwrap(), t);
+        assert_eq!(
+            " 2100:345600.5 ".parse::<GpsTime>().unwrap().to_string(),
+            "2100:345600.5"
+        );
+        assert_eq!(
+            "2100".parse::<GpsTime>(),
+            Err(ParseTimeError::InvalidFormat)
+        );
+        assert_eq!(
+            "2100:604800".parse::<GpsTime>(),
+            Err(ParseTimeError::InvalidValue)
+        );
+
+        // 2020-04-09 00:00:00 GPS
+        assert_eq!(t.rinex_epoch(), "2020 04 09 00 00  0.5000000");
+        assert_eq!(GpsTime::from_rinex_epoch(&t.rinex_epoch()).unwrap(), t);
+        let t = GpsTime::new(2100, 86_399.99999999).unwrap();
+        assert_eq!(t.rinex_epoch(), "2020 04 06 00 00  0.0000000");
+        let t = GpsTime::new(2100, 3_723.25).unwrap();
+        assert_eq!(t.rinex_epoch(), "2020 04 05 01 02  3.2500000");
+        assert_eq!(
+            GpsTime::from_rinex_epoch(" 20  4  5  1  2  3.2500000").unwrap(),
+            t
+        );
+        assert_eq!(
+            GpsTime::from_rinex_epoch("2020 02 30 00 00  0.0"),
+            Err(ParseTimeError::InvalidValue)
+        );
+        assert_eq!(
+            GpsTime::from_rinex_epoch("2020 02 28 00 00"),
+            Err(ParseTimeError::InvalidFormat)
+        );
+
+        assert_eq!(
+            GpsTime::from_sinex_epoch("2020:096:03723").unwrap(),
+            GpsTime::new(2100, 3_723.0).unwrap()
+        );
+        assert_eq!(
+            GpsTime::from_sinex_epoch("20:096:03723").unwrap(),
+            GpsTime::new(2100, 3_723.0).unwrap()
+        );
+        assert_eq!(
+            GpsTime::from_sinex_epoch("2020:366:86400").unwrap(),
+            GpsTime::from_sinex_epoch("2021:001:00000").unwrap()
+        );
+        assert_eq!(
+            GpsTime::from_sinex_epoch("0000:000:00000"),
+            Err(ParseTimeError::InvalidValue)
+        );
+        assert_eq!(
+            GpsTime::from_sinex_epoch("2020:096"),
+            Err(ParseTimeError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn utc_time_strings() {
+        let utc: UtcTime = "2021-08-01T00:11:02.250Z".parse().unwrap();
+        assert_eq!(utc.year(), 2021);
+        assert_eq!(utc.month(), 8);
+        assert_eq!(utc.day_of_month(), 1);
+        assert_eq!(utc.hour(), 0);
+        assert_eq!(utc.minute(), 11);
+        assert!((utc.seconds() - 2.25).abs() < 1e-9);
+        assert_eq!(utc.to_string(), "2021-08-01T00:11:02.250Z");
+        assert_eq!(format!("{:.0}", utc), "2021-08-01T00:11:02Z");
+        assert_eq!(format!("{:.6}", utc), "2021-08-01T00:11:02.250000Z");
+
+        // Leap seconds are accepted
+        assert!("2016-12-31 23:59:60".parse::<UtcTime>().is_ok());
+
+        assert!("2021-08-01".parse::<UtcTime>().is_err());
+        assert!("2021-8-1T00:11:02Z".parse::<UtcTime>().is_err());
+        assert!("2021-02-29T00:00:00Z".parse::<UtcTime>().is_err());
+        assert!("2021-08-01T24:00:00Z".parse::<UtcTime>().is_err());
+        assert!("2021-08-01T00:00:0aZ".parse::<UtcTime>().is_err());
+    }
+
+    #[test]
+    fn civil_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(1980, 1, 6), 3657);
+        assert_eq!(civil_from_days(3657), (1980, 1, 6));
+        for days in (-1000..30_000).step_by(37) {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(
+                days_from_civil(year as u16, u16::from(month), u16::from(day)),
+                days
+            );
+        }
+    }
+
+    #[test]
+    fn utc_params_store() {
+        let params = |a0, tot_wn| {
+            UtcParams::from_components(
+                a0,
+                0.0,
+                0.0,
+                &GpsTime::new_unchecked(tot_wn, 0.0),
+                &GpsTime::new_unchecked(2086, 259218.0),
+                18,
+                19,
+            )
+        };
+        let t = GpsTime::new_unchecked(2081, 0.0);
+
+        let mut store = UtcParamsStore::default();
+        assert!(store.params_at(&t).is_none());
+        assert_eq!(store.utc_offset(&t), t.utc_offset_hardcoded());
+
+        assert!(store.insert(Constellation::Gal, params(0.25, 2080)));
+        assert!(store.insert(Constellation::Gps, params(0.125, 2080)));
+        assert!(!store.insert(Constellation::Gps, params(0.5, 2079)));
+        assert_eq!(store.get(Constellation::Gps).unwrap().a0(), 0.125);
+        // Equally fresh sets are picked by priority
+        assert_eq!(store.params_at(&t).unwrap().a0(), 0.125);
+        assert_eq!(store.utc_offset(&t), t.utc_offset(&params(0.125, 2080)));
+
+        // Fresher sets are preferred, regardless of priority
+        assert!(store.insert(Constellation::Bds, params(0.375, 2081)));
+        assert_eq!(store.params_at(&t).unwrap().a0(), 0.375);
+
+        let mut store = UtcParamsStore::new(
+            UtcStoreSettings::new().set_priorities(vec![Constellation::Gal, Constellation::Gps]),
+        );
+        store.insert(Constellation::Gps, params(0.125, 2080));
+        store.insert(Constellation::Gal, params(0.25, 2080));
+        store.insert(Constellation::Bds, params(0.375, 2081));
+        assert_eq!(store.params_at(&t).unwrap().a0(), 0.25);
+
+        // Parameters are only used close to their reference time
+        let later = GpsTime::new_unchecked(2090, 0.0);
+        assert!(store.params_at(&later).is_none());
+        assert_eq!(store.utc_offset(&later), later.utc_offset_hardcoded());
+    }
+
+    #[test]
+    fn ggto() {
+        let mut word = [0u8; crate::ephemeris::GAL_INAV_CONTENT_BYTE];
+        fn set(word: &mut [u8], start: usize, len: usize, value: i64) {
+            for k in 0..len {
+                let bit = start + k;
+                if (value >> (len - 1 - k)) & 1 == 1 {
+                    word[bit / 8] |= 1 << (7 - bit % 8);
+                }
+            }
+        }
+        set(&mut word, 0, 6, 10);
+        set(&mut word, 86, 16, -640);
+        set(&mut word, 102, 12, 100);
+        set(&mut word, 114, 8, 36);
+        set(&mut word, 122, 6, 2200 % 64);
+
+        let reference = GpsTime::new_unchecked(2210, 0.0);
+        let ggto = GgtoParams::decode_inav(&word, &reference).unwrap();
+        assert_eq!(ggto.t0g(), GpsTime::new_unchecked(2200, 129600.0));
+        assert!((ggto.a0g() + 640.0 * 2f64.powi(-35)).abs() < 1e-20);
+        assert!((ggto.a1g() - 100.0 * 2f64.powi(-51)).abs() < 1e-24);
+
+        let t = GpsTime::new_unchecked(2200, 129600.0 + 1e5);
+        let offset = ggto.offset(&t);
+        assert!((offset - (ggto.a0g() + ggto.a1g() * 1e5)).abs() < 1e-18);
+        let gal = ggto.gps_to_gal(t);
+        assert!((gal.tow() - (t.tow() + offset)).abs() < 1e-9);
+        assert!(ggto.gal_to_gps(gal).diff(&t).abs() < 1e-9);
+
+        let mut store = UtcParamsStore::default();
+        assert!(store.gps_to_gal(t).tow() == t.tow());
+        assert!(store.insert_ggto(ggto));
+        assert!(!store.insert_ggto(ggto));
+        assert!((store.gps_to_gal(t).tow() - gal.tow()).abs() < 1e-9);
+        assert!(store.ggto_at(&GpsTime::new_unchecked(2202, 0.0)).is_none());
+
+        // All bits set marks the parameters as unavailable
+        set(&mut word, 86, 42, (1 << 42) - 1);
+        assert_eq!(GgtoParams::decode_inav(&word, &reference), None);
     }
 
     #[test]
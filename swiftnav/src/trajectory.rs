@@ -0,0 +1,473 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Time indexed receiver trajectories
+//!
+//! A [`Trajectory`] is a sequence of positions in a single reference frame,
+//! ordered by time, each with an optional velocity. It can hold the truth of
+//! a [simulation](crate::simulate) or the output of a solver, and is
+//! interpolated between its points in one of two ways:
+//!  * [`Interpolation::Linear`] - straight lines between the points, the
+//!    velocity being constant between two points
+//!  * [`Interpolation::Hermite`] - cubic Hermite splines matching the
+//!    positions and velocities of the points, which follows a vehicle's motion
+//!    much more closely at low rates. Segments without both velocities fall
+//!    back to linear interpolation.
+//!
+//! The velocities are the motion of the receiver in meters per second, unlike
+//! the velocities of [`Coordinate`]s which are station velocities in meters
+//! per year. Coordinates taken from or given to a trajectory don't have a
+//! velocity.
+
+use crate::coords::{Coordinate, ECEF};
+use crate::reference_frame::ReferenceFrame;
+use crate::simulate::ReceiverTrajectory;
+use crate::solver::solution::PvtSolution;
+use crate::time::{GpsTime, TimeDelta};
+use std::error::Error;
+use std::fmt;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Largest time difference, in seconds, at which a point is considered to be
+/// at a time
+const TIME_TOLERANCE: f64 = 1e-9;
+
+/// How a [`Trajectory`] is interpolated between its points
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Interpolation {
+    Linear,
+    Hermite,
+}
+
+/// Errors which can occur while building a [`Trajectory`]
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub enum TrajectoryError {
+    /// The point is in a different reference frame than the trajectory
+    FrameMismatch(ReferenceFrame, ReferenceFrame),
+    /// The point isn't after the last point of the trajectory
+    OutOfOrder(GpsTime),
+}
+
+impl fmt::Display for TrajectoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrajectoryError::FrameMismatch(expected, actual) => {
+                write!(f, "Trajectory point in {} instead of {}", actual, expected)
+            }
+            TrajectoryError::OutOfOrder(time) => write!(
+                f,
+                "Trajectory point at week {} TOW {} is out of order",
+                time.wn(),
+                time.tow()
+            ),
+        }
+    }
+}
+
+impl Error for TrajectoryError {}
+
+/// A position, and optionally velocity, of a trajectory at a time
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct TrajectoryPoint {
+    time: GpsTime,
+    position: ECEF,
+    velocity: Option<ECEF>,
+}
+
+impl TrajectoryPoint {
+    pub fn new(time: GpsTime, position: ECEF) -> TrajectoryPoint {
+        TrajectoryPoint {
+            time,
+            position,
+            velocity: None,
+        }
+    }
+
+    /// Sets the ECEF velocity, in m/s
+    pub fn set_velocity(self, velocity: ECEF) -> TrajectoryPoint {
+        TrajectoryPoint {
+            velocity: Some(velocity),
+            ..self
+        }
+    }
+
+    pub fn time(&self) -> GpsTime {
+        self.time
+    }
+
+    pub fn position(&self) -> ECEF {
+        self.position
+    }
+
+    /// Gets the ECEF velocity, in m/s
+    pub fn velocity(&self) -> Option<ECEF> {
+        self.velocity
+    }
+}
+
+/// A sequence of receiver positions ordered by time
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub struct Trajectory {
+    reference_frame: ReferenceFrame,
+    points: Vec<TrajectoryPoint>,
+}
+
+impl Trajectory {
+    /// Makes an empty trajectory in a reference frame
+    pub fn new(reference_frame: ReferenceFrame) -> Trajectory {
+        Trajectory {
+            reference_frame,
+            points: Vec::new(),
+        }
+    }
+
+    /// Makes a trajectory from the positions and velocities of solutions
+    ///
+    /// The solutions must be in the given reference frame and in time order
+    pub fn from_solutions(
+        reference_frame: ReferenceFrame,
+        solutions: &[PvtSolution],
+    ) -> Result<Trajectory, TrajectoryError> {
+        let mut trajectory = Trajectory::new(reference_frame);
+        for solution in solutions {
+            if solution.reference_frame() != reference_frame {
+                return Err(TrajectoryError::FrameMismatch(
+                    reference_frame,
+                    solution.reference_frame(),
+                ));
+            }
+            let mut point = TrajectoryPoint::new(solution.time(), solution.position());
+            if let Some(velocity) = solution.velocity() {
+                point = point.set_velocity(velocity);
+            }
+            trajectory.push(point)?;
+        }
+        Ok(trajectory)
+    }
+
+    /// Makes a trajectory from the positions of coordinates, placed at their
+    /// epochs
+    ///
+    /// The coordinates must be in the given reference frame and in time order
+    pub fn from_coordinates(
+        reference_frame: ReferenceFrame,
+        coordinates: &[Coordinate],
+    ) -> Result<Trajectory, TrajectoryError> {
+        let mut trajectory = Trajectory::new(reference_frame);
+        for coordinate in coordinates {
+            trajectory.push_coordinate(coordinate)?;
+        }
+        Ok(trajectory)
+    }
+
+    /// Adds a point to the end of the trajectory
+    pub fn push(&mut self, point: TrajectoryPoint) -> Result<(), TrajectoryError> {
+        if let Some(last) = self.points.last() {
+            if point.time.diff(&last.time) <= TIME_TOLERANCE {
+                return Err(TrajectoryError::OutOfOrder(point.time));
+            }
+        }
+        self.points.push(point);
+        Ok(())
+    }
+
+    /// Adds the position of a coordinate to the end of the trajectory
+    pub fn push_coordinate(&mut self, coordinate: &Coordinate) -> Result<(), TrajectoryError> {
+        if coordinate.reference_frame() != self.reference_frame {
+            return Err(TrajectoryError::FrameMismatch(
+                self.reference_frame,
+                coordinate.reference_frame(),
+            ));
+        }
+        self.push(TrajectoryPoint::new(
+            coordinate.epoch(),
+            coordinate.position(),
+        ))
+    }
+
+    pub fn reference_frame(&self) -> ReferenceFrame {
+        self.reference_frame
+    }
+
+    pub fn points(&self) -> &[TrajectoryPoint] {
+        &self.points
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Gets the time of the first point
+    pub fn start(&self) -> Option<GpsTime> {
+        self.points.first().map(|point| point.time)
+    }
+
+    /// Gets the time of the last point
+    pub fn end(&self) -> Option<GpsTime> {
+        self.points.last().map(|point| point.time)
+    }
+
+    /// Gets the points as coordinates
+    pub fn coordinates(&self) -> impl Iterator<Item = Coordinate> + '_ {
+        self.points.iter().map(move |point| {
+            Coordinate::without_velocity(self.reference_frame, point.position, point.time)
+        })
+    }
+
+    /// Interpolates the position and velocity at a time
+    ///
+    /// Returns `None` if the time is outside of the trajectory
+    pub fn interpolate(
+        &self,
+        time: &GpsTime,
+        interpolation: Interpolation,
+    ) -> Option<TrajectoryPoint> {
+        let after = self
+            .points
+            .partition_point(|point| time.diff(&point.time) > TIME_TOLERANCE);
+        let next = self.points.get(after)?;
+        if next.time.diff(time).abs() <= TIME_TOLERANCE {
+            return Some(*next);
+        }
+        if after == 0 {
+            return None;
+        }
+        let previous = &self.points[after - 1];
+
+        let h = next.time.diff(&previous.time);
+        let s = time.diff(&previous.time) / h;
+        let (p0, p1) = (previous.position, next.position);
+        let (position, velocity) = match (interpolation, previous.velocity, next.velocity) {
+            (Interpolation::Hermite, Some(v0), Some(v1)) => {
+                let (s2, s3) = (s * s, s * s * s);
+                let position = (2.0 * s3 - 3.0 * s2 + 1.0) * p0
+                    + ((s3 - 2.0 * s2 + s) * h) * v0
+                    + (-2.0 * s3 + 3.0 * s2) * p1
+                    + ((s3 - s2) * h) * v1;
+                let velocity = ((6.0 * s2 - 6.0 * s) / h) * p0
+                    + (3.0 * s2 - 4.0 * s + 1.0) * v0
+                    + ((6.0 * s - 6.0 * s2) / h) * p1
+                    + (3.0 * s2 - 2.0 * s) * v1;
+                (position, velocity)
+            }
+            _ => {
+                let velocity = (1.0 / h) * (p1 - p0);
+                (p0 + (s * h) * velocity, velocity)
+            }
+        };
+        Some(TrajectoryPoint::new(*time, position).set_velocity(velocity))
+    }
+
+    /// Interpolates the position at a time, as a coordinate
+    ///
+    /// Returns `None` if the time is outside of the trajectory
+    pub fn coordinate_at(
+        &self,
+        time: &GpsTime,
+        interpolation: Interpolation,
+    ) -> Option<Coordinate> {
+        self.interpolate(time, interpolation).map(|point| {
+            Coordinate::without_velocity(self.reference_frame, point.position, point.time)
+        })
+    }
+
+    /// Resamples the trajectory at a fixed interval
+    ///
+    /// The new points are at whole multiples of the interval in GPS time, for
+    /// example on the second for a 1 s interval, from the start to the end of
+    /// the trajectory. All of the new points have a velocity.
+    ///
+    /// # Panics
+    /// This function will panic if `interval` is zero
+    pub fn resample(&self, interval: Duration, interpolation: Interpolation) -> Trajectory {
+        assert!(!interval.is_zero(), "Resampling interval must not be zero");
+        let mut resampled = Trajectory::new(self.reference_frame);
+        let (start, end) = match (self.start(), self.end()) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return resampled,
+        };
+        let interval = interval.as_secs_f64();
+        let first = ((start.tow() - TIME_TOLERANCE) / interval).ceil() * interval - start.tow();
+        let count = ((end.diff(&start) - first + TIME_TOLERANCE) / interval).floor();
+        let mut k = 0.0;
+        while k <= count {
+            let time = start + TimeDelta::from_secs_f64(first + k * interval);
+            if let Some(point) = self.interpolate(&time, interpolation) {
+                resampled.points.push(point);
+            }
+            k += 1.0;
+        }
+        resampled
+    }
+
+    /// Formats the trajectory as comma separated values
+    ///
+    /// Each point is a line of GPS week, time of week, ECEF position, ECEF
+    /// velocity, and latitude and longitude in degrees and height on the
+    /// ellipsoid of the reference frame. The velocity is left empty for
+    /// points without one. The first line is a header naming the columns.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("week,tow,x,y,z,vx,vy,vz,latitude,longitude,height\n");
+        for (point, coordinate) in self.points.iter().zip(self.coordinates()) {
+            let llh = coordinate.llh().to_degrees();
+            let [x, y, z] = *point.position.as_array_ref();
+            let velocity = match point.velocity {
+                Some(v) => format!("{:.4},{:.4},{:.4}", v.x(), v.y(), v.z()),
+                None => String::from(",,"),
+            };
+            writeln!(
+                csv,
+                "{},{:.3},{:.4},{:.4},{:.4},{},{:.9},{:.9},{:.4}",
+                point.time.wn(),
+                point.time.tow(),
+                x,
+                y,
+                z,
+                velocity,
+                llh.latitude(),
+                llh.longitude(),
+                llh.height()
+            )
+            .unwrap();
+        }
+        csv
+    }
+}
+
+/// The trajectory is interpolated with [`Interpolation::Hermite`]
+impl ReceiverTrajectory for Trajectory {
+    fn state_at(&self, time: &GpsTime) -> Option<(ECEF, ECEF)> {
+        self.interpolate(time, Interpolation::Hermite)
+            .map(|point| (point.position, point.velocity.unwrap_or_default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_eq::assert_float_eq;
+
+    fn time(tow: f64) -> GpsTime {
+        GpsTime::new(2200, tow).unwrap()
+    }
+
+    /// A receiver accelerating along the x axis, sampled every 2 seconds
+    fn accelerating() -> Trajectory {
+        let mut trajectory = Trajectory::new(ReferenceFrame::ITRF2020);
+        for i in 0..5 {
+            let t = 2.0 * f64::from(i);
+            let position = ECEF::new(6_378_137.0 + 0.5 * t * t, 0.0, 0.0);
+            let point = TrajectoryPoint::new(time(1000.5 + t), position)
+                .set_velocity(ECEF::new(t, 0.0, 0.0));
+            trajectory.push(point).unwrap();
+        }
+        trajectory
+    }
+
+    #[test]
+    fn interpolation() {
+        let trajectory = accelerating();
+        assert_eq!(trajectory.len(), 5);
+        assert_eq!(trajectory.start(), Some(time(1000.5)));
+        assert_eq!(trajectory.end(), Some(time(1008.5)));
+
+        // Hermite interpolation is exact for a constant acceleration
+        let point = trajectory
+            .interpolate(&time(1003.5), Interpolation::Hermite)
+            .unwrap();
+        assert_float_eq!(point.position().x(), 6_378_137.0 + 4.5, abs <= 1e-6);
+        assert_float_eq!(point.velocity().unwrap().x(), 3.0, abs <= 1e-9);
+
+        let point = trajectory
+            .interpolate(&time(1003.5), Interpolation::Linear)
+            .unwrap();
+        assert_float_eq!(point.position().x(), 6_378_137.0 + 5.0, abs <= 1e-6);
+        assert_float_eq!(point.velocity().unwrap().x(), 3.0, abs <= 1e-9);
+
+        // The points themselves are returned as is
+        let point = trajectory
+            .interpolate(&time(1004.5), Interpolation::Linear)
+            .unwrap();
+        assert_eq!(point, trajectory.points()[2]);
+
+        assert!(trajectory
+            .interpolate(&time(1000.0), Interpolation::Hermite)
+            .is_none());
+        assert!(trajectory
+            .interpolate(&time(1009.0), Interpolation::Hermite)
+            .is_none());
+        let (position, _) = trajectory.state_at(&time(1008.5)).unwrap();
+        assert_float_eq!(position.x(), 6_378_137.0 + 32.0, abs <= 1e-6);
+    }
+
+    #[test]
+    fn building() {
+        let mut trajectory = accelerating();
+        let point = TrajectoryPoint::new(time(1008.5), ECEF::default());
+        assert_eq!(
+            trajectory.push(point),
+            Err(TrajectoryError::OutOfOrder(time(1008.5)))
+        );
+
+        let coordinate =
+            Coordinate::without_velocity(ReferenceFrame::ITRF2014, ECEF::default(), time(1010.0));
+        assert_eq!(
+            trajectory.push_coordinate(&coordinate),
+            Err(TrajectoryError::FrameMismatch(
+                ReferenceFrame::ITRF2020,
+                ReferenceFrame::ITRF2014
+            ))
+        );
+
+        let coordinates: Vec<Coordinate> = trajectory.coordinates().collect();
+        assert_eq!(coordinates.len(), 5);
+        assert_eq!(coordinates[1].epoch(), time(1002.5));
+        assert_eq!(coordinates[1].velocity(), None);
+        let rebuilt = Trajectory::from_coordinates(ReferenceFrame::ITRF2020, &coordinates).unwrap();
+        assert_eq!(
+            rebuilt.points()[1].position(),
+            trajectory.points()[1].position()
+        );
+        assert_eq!(rebuilt.points()[1].velocity(), None);
+    }
+
+    #[test]
+    fn resampling() {
+        let trajectory = accelerating();
+        let resampled = trajectory.resample(Duration::from_secs(1), Interpolation::Hermite);
+        assert_eq!(resampled.len(), 8);
+        assert_eq!(resampled.start(), Some(time(1001.0)));
+        assert_eq!(resampled.end(), Some(time(1008.0)));
+        for point in resampled.points() {
+            let t = point.time().diff(&time(1000.5));
+            assert_float_eq!(point.position().x(), 6_378_137.0 + 0.5 * t * t, abs <= 1e-6);
+            assert_float_eq!(point.velocity().unwrap().x(), t, abs <= 1e-9);
+        }
+
+        let resampled = trajectory.resample(Duration::from_millis(500), Interpolation::Linear);
+        assert_eq!(resampled.len(), 17);
+
+        let csv = accelerating().to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(
+            lines[0],
+            "week,tow,x,y,z,vx,vy,vz,latitude,longitude,height"
+        );
+        assert!(
+            lines[2].starts_with("2200,1002.500,6378139.0000,0.0000,0.0000,2.0000,0.0000,0.0000,")
+        );
+    }
+}
@@ -12,9 +12,33 @@
 //! Tropospheric delays are typically modeled with the UNM3m model. The model
 //! parameters are hardcoded into the library, unlike the ionosphere model.
 //!
+//! The Saastamoinen and Hopfield models are also available, to match the
+//! corrections used by other processing software. All of the models implement
+//! the [`TroposphereModel`] trait so they can be selected at run time. The
+//! zenith delays of the Saastamoinen and Hopfield models are mapped to the
+//! satellite elevation either with the model's own mapping, or with one of the
+//! [`MappingFunction`]s.
+//!
 //! # References
 //!   * UNB Neutral Atmosphere Models: Development and Performance. R Leandro,
 //!      M Santos, and R B Langley
+//!   * Atmospheric Correction for the Troposphere and Stratosphere in Radio
+//!      Ranging of Satellites. J Saastamoinen, 1972
+//!   * Two-quartic Tropospheric Refractivity Profile for Correcting Satellite
+//!      Data. H S Hopfield, 1969
+//!   * GNSS - Global Navigation Satellite Systems, Section 5.3.3.
+//!      B Hofmann-Wellenhof, H Lichtenegger and E Wasle, 2008
+//!   * Global Mapping Functions for the Atmosphere Delay at Radio Wavelengths.
+//!      A E Niell, 1996
+//!   * A Model for Tropospheric Calibration from Daily Surface and Radiosonde
+//!      Balloon Measurement. C C Chao, 1972
+
+use std::f64::consts::PI;
+
+/// Lowest height, in meters, the standard atmosphere is evaluated at
+const MIN_HEIGHT: f64 = -1000.0;
+/// Highest height, in meters, the standard atmosphere is evaluated at
+const MAX_HEIGHT: f64 = 10_000.0;
 
 ///  Calculate tropospheric delay using UNM3m model.
 ///
@@ -24,9 +48,342 @@ pub fn calc_delay(doy: f64, lat: f64, h: f64, el: f64) -> f64 {
     unsafe { swiftnav_sys::calc_troposphere(doy, lat, h, el) }
 }
 
+/// A model of the tropospheric delay of a signal
+pub trait TroposphereModel {
+    /// Calculate the tropospheric delay, in meters
+    ///
+    /// Requires the day of year of the delay, the latitude (rad) and height (m)
+    /// of the receiver, and the elevation of the satellite (rad)
+    fn calc_delay(&self, doy: f64, lat: f64, h: f64, el: f64) -> f64;
+}
+
+/// The UNM3m model, see [`calc_delay()`]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Unb3m;
+
+impl TroposphereModel for Unb3m {
+    fn calc_delay(&self, doy: f64, lat: f64, h: f64, el: f64) -> f64 {
+        calc_delay(doy, lat, h, el)
+    }
+}
+
+/// Functions mapping the zenith delays to the satellite elevation
+#[derive(Debug, Copy, Clone, Default, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MappingFunction {
+    /// The mapping originally published with the delay model
+    #[default]
+    Model,
+    /// The Niell mapping functions, with seasonal and height dependency
+    Niell,
+    /// The Chao mapping functions
+    Chao,
+}
+
+/// Hydrostatic and wet mapping factors
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct MappingFactors {
+    pub hydrostatic: f64,
+    pub wet: f64,
+}
+
+impl MappingFunction {
+    /// Calculate the hydrostatic and wet mapping factors
+    ///
+    /// Requires the day of year, the latitude (rad) and height (m) of the
+    /// receiver, and the elevation of the satellite (rad). Returns `None` for
+    /// [`MappingFunction::Model`], which depends on the delay model.
+    pub fn factors(&self, doy: f64, lat: f64, h: f64, el: f64) -> Option<MappingFactors> {
+        match self {
+            MappingFunction::Model => None,
+            MappingFunction::Niell => Some(niell(doy, lat, h, el)),
+            MappingFunction::Chao => Some(MappingFactors {
+                hydrostatic: 1.0 / (el.sin() + 0.00143 / (el.tan() + 0.0445)),
+                wet: 1.0 / (el.sin() + 0.00035 / (el.tan() + 0.017)),
+            }),
+        }
+    }
+}
+
+/// Marini's continued fraction, normalized to one at zenith
+fn continued_fraction(el: f64, a: f64, b: f64, c: f64) -> f64 {
+    let sin_el = el.sin();
+    (1.0 + a / (1.0 + b / (1.0 + c))) / (sin_el + a / (sin_el + b / (sin_el + c)))
+}
+
+/// Linearly interpolates the Niell coefficient tables, given at 15° to 75° of
+/// latitude in 15° steps
+fn interpolate_latitude(table: &[f64; 5], lat_deg: f64) -> f64 {
+    let x = (lat_deg.abs() / 15.0 - 1.0).clamp(0.0, 4.0);
+    let i = (x.floor() as usize).min(3);
+    table[i] + (table[i + 1] - table[i]) * (x - i as f64)
+}
+
+fn niell(doy: f64, lat: f64, h: f64, el: f64) -> MappingFactors {
+    const HYDRO_MEAN: [[f64; 5]; 3] = [
+        [
+            1.2769934e-3,
+            1.2683230e-3,
+            1.2465397e-3,
+            1.2196049e-3,
+            1.2045996e-3,
+        ],
+        [
+            2.9153695e-3,
+            2.9152299e-3,
+            2.9288445e-3,
+            2.9022565e-3,
+            2.9024912e-3,
+        ],
+        [
+            62.610505e-3,
+            62.837393e-3,
+            63.721774e-3,
+            63.824265e-3,
+            64.258455e-3,
+        ],
+    ];
+    const HYDRO_AMPLITUDE: [[f64; 5]; 3] = [
+        [0.0, 1.2709626e-5, 2.6523662e-5, 3.4000452e-5, 4.1202191e-5],
+        [0.0, 2.1414979e-5, 3.0160779e-5, 7.2562722e-5, 11.723375e-5],
+        [0.0, 9.0128400e-5, 4.3497037e-5, 84.795348e-5, 170.37206e-5],
+    ];
+    const HEIGHT_CORRECTION: [f64; 3] = [2.53e-5, 5.49e-3, 1.14e-3];
+    const WET: [[f64; 5]; 3] = [
+        [
+            5.8021897e-4,
+            5.6794847e-4,
+            5.8118019e-4,
+            5.9727542e-4,
+            6.1641693e-4,
+        ],
+        [
+            1.4275268e-3,
+            1.5138625e-3,
+            1.4572752e-3,
+            1.5007428e-3,
+            1.7599082e-3,
+        ],
+        [
+            4.3472961e-2,
+            4.6729510e-2,
+            4.3908931e-2,
+            4.4626982e-2,
+            5.4736038e-2,
+        ],
+    ];
+
+    let lat_deg = lat.to_degrees();
+    // The seasonal variation peaks on day 28 in the northern hemisphere
+    let phase_doy = if lat < 0.0 { doy + 365.25 / 2.0 } else { doy };
+    let season = (2.0 * PI * (phase_doy - 28.0) / 365.25).cos();
+
+    let coefficient = |i: usize| {
+        interpolate_latitude(&HYDRO_MEAN[i], lat_deg)
+            - interpolate_latitude(&HYDRO_AMPLITUDE[i], lat_deg) * season
+    };
+    let hydrostatic = continued_fraction(el, coefficient(0), coefficient(1), coefficient(2));
+    let height_correction = (1.0 / el.sin()
+        - continued_fraction(
+            el,
+            HEIGHT_CORRECTION[0],
+            HEIGHT_CORRECTION[1],
+            HEIGHT_CORRECTION[2],
+        ))
+        * h
+        / 1000.0;
+
+    let wet = continued_fraction(
+        el,
+        interpolate_latitude(&WET[0], lat_deg),
+        interpolate_latitude(&WET[1], lat_deg),
+        interpolate_latitude(&WET[2], lat_deg),
+    );
+
+    MappingFactors {
+        hydrostatic: hydrostatic + height_correction,
+        wet,
+    }
+}
+
+/// Surface meteorological values of a standard atmosphere at a height (m)
+///
+/// Returns the pressure (hPa), temperature (K) and partial water vapour
+/// pressure (hPa) for the given relative humidity, in the range [0, 1]
+fn standard_atmosphere(h: f64, relative_humidity: f64) -> (f64, f64, f64) {
+    let h = h.clamp(MIN_HEIGHT, MAX_HEIGHT);
+    let pressure = 1013.25 * (1.0 - 2.2557e-5 * h).powf(5.2568);
+    let temperature = 15.0 - 6.5e-3 * h + 273.16;
+    let vapour_pressure =
+        6.108 * relative_humidity * ((17.15 * temperature - 4684.0) / (temperature - 38.45)).exp();
+    (pressure, temperature, vapour_pressure)
+}
+
+/// The Saastamoinen model, evaluated with a standard atmosphere
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Saastamoinen {
+    relative_humidity: f64,
+    mapping: MappingFunction,
+}
+
+impl Saastamoinen {
+    /// Makes the model with the default settings
+    ///
+    /// Note: The default settings consist of
+    ///  * A relative humidity of 70%
+    ///  * The model's own mapping, the inverse of the sine of the elevation
+    pub fn new() -> Saastamoinen {
+        Saastamoinen {
+            relative_humidity: 0.7,
+            mapping: MappingFunction::Model,
+        }
+    }
+
+    /// Sets the relative humidity, in the range [0, 1]
+    pub fn set_relative_humidity(self, relative_humidity: f64) -> Saastamoinen {
+        Saastamoinen {
+            relative_humidity,
+            ..self
+        }
+    }
+
+    pub fn set_mapping_function(self, mapping: MappingFunction) -> Saastamoinen {
+        Saastamoinen { mapping, ..self }
+    }
+
+    pub fn relative_humidity(&self) -> f64 {
+        self.relative_humidity
+    }
+
+    pub fn mapping_function(&self) -> MappingFunction {
+        self.mapping
+    }
+
+    /// Calculate the hydrostatic and wet zenith delays, in meters
+    ///
+    /// Requires the latitude (rad) and height (m) of the receiver
+    pub fn zenith_delays(&self, lat: f64, h: f64) -> (f64, f64) {
+        let (pressure, temperature, vapour_pressure) =
+            standard_atmosphere(h, self.relative_humidity);
+        let h = h.clamp(MIN_HEIGHT, MAX_HEIGHT);
+        let hydrostatic =
+            0.0022768 * pressure / (1.0 - 0.00266 * (2.0 * lat).cos() - 0.00028 * h / 1000.0);
+        let wet = 0.002277 * (1255.0 / temperature + 0.05) * vapour_pressure;
+        (hydrostatic, wet)
+    }
+}
+
+impl Default for Saastamoinen {
+    fn default() -> Self {
+        Saastamoinen::new()
+    }
+}
+
+impl TroposphereModel for Saastamoinen {
+    /// Satellites below the horizon have no delay
+    fn calc_delay(&self, doy: f64, lat: f64, h: f64, el: f64) -> f64 {
+        if el <= 0.0 {
+            return 0.0;
+        }
+        let (hydrostatic, wet) = self.zenith_delays(lat, h);
+        let factors = self
+            .mapping
+            .factors(doy, lat, h, el)
+            .unwrap_or(MappingFactors {
+                hydrostatic: 1.0 / el.sin(),
+                wet: 1.0 / el.sin(),
+            });
+        hydrostatic * factors.hydrostatic + wet * factors.wet
+    }
+}
+
+/// The modified Hopfield model, evaluated with a standard atmosphere
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hopfield {
+    relative_humidity: f64,
+    mapping: MappingFunction,
+}
+
+impl Hopfield {
+    /// Makes the model with the default settings
+    ///
+    /// Note: The default settings consist of
+    ///  * A relative humidity of 70%
+    ///  * The model's own mapping, with the elevation offset by 2.5° for the
+    ///    dry delay and 1.5° for the wet delay
+    pub fn new() -> Hopfield {
+        Hopfield {
+            relative_humidity: 0.7,
+            mapping: MappingFunction::Model,
+        }
+    }
+
+    /// Sets the relative humidity, in the range [0, 1]
+    pub fn set_relative_humidity(self, relative_humidity: f64) -> Hopfield {
+        Hopfield {
+            relative_humidity,
+            ..self
+        }
+    }
+
+    pub fn set_mapping_function(self, mapping: MappingFunction) -> Hopfield {
+        Hopfield { mapping, ..self }
+    }
+
+    pub fn relative_humidity(&self) -> f64 {
+        self.relative_humidity
+    }
+
+    pub fn mapping_function(&self) -> MappingFunction {
+        self.mapping
+    }
+
+    /// Calculate the dry and wet zenith delays, in meters
+    ///
+    /// Requires the height (m) of the receiver
+    pub fn zenith_delays(&self, h: f64) -> (f64, f64) {
+        let (pressure, temperature, vapour_pressure) =
+            standard_atmosphere(h, self.relative_humidity);
+        let h = h.clamp(MIN_HEIGHT, MAX_HEIGHT);
+        // Heights of the top of the dry and wet layers
+        let dry_height = 40_136.0 + 148.72 * (temperature - 273.16);
+        let wet_height = 11_000.0;
+        let dry = 77.64e-6 * pressure / temperature * (dry_height - h) / 5.0;
+        let wet = 0.373 * vapour_pressure / (temperature * temperature) * (wet_height - h) / 5.0;
+        (dry, wet.max(0.0))
+    }
+}
+
+impl Default for Hopfield {
+    fn default() -> Self {
+        Hopfield::new()
+    }
+}
+
+impl TroposphereModel for Hopfield {
+    /// Satellites below the horizon have no delay
+    fn calc_delay(&self, doy: f64, lat: f64, h: f64, el: f64) -> f64 {
+        if el <= 0.0 {
+            return 0.0;
+        }
+        let (dry, wet) = self.zenith_delays(h);
+        let factors = self.mapping.factors(doy, lat, h, el).unwrap_or_else(|| {
+            let el_deg = el.to_degrees();
+            MappingFactors {
+                hydrostatic: 1.0 / (el_deg * el_deg + 6.25).sqrt().to_radians().sin(),
+                wet: 1.0 / (el_deg * el_deg + 2.25).sqrt().to_radians().sin(),
+            }
+        });
+        dry * factors.hydrostatic + wet * factors.wet
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::troposphere::calc_delay;
+    use crate::troposphere::*;
+    use float_eq::assert_float_eq;
 
     const D2R: f64 = std::f64::consts::PI / 180.0;
 
@@ -119,4 +476,91 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn mapping_functions() {
+        let lat = 45.0 * D2R;
+        for mapping in [MappingFunction::Niell, MappingFunction::Chao].iter() {
+            let zenith = mapping.factors(32.5, lat, 0.0, 90.0 * D2R).unwrap();
+            assert_float_eq!(zenith.hydrostatic, 1.0, abs <= 1e-3);
+            assert_float_eq!(zenith.wet, 1.0, abs <= 1e-3);
+        }
+        assert!(MappingFunction::Model
+            .factors(32.5, lat, 0.0, 0.5)
+            .is_none());
+
+        // The mapping functions agree closely down to low elevations
+        for el in [30.0, 10.0].iter() {
+            let el = el * D2R;
+            let niell = MappingFunction::Niell.factors(32.5, lat, 0.0, el).unwrap();
+            let chao = MappingFunction::Chao.factors(32.5, lat, 0.0, el).unwrap();
+            assert_float_eq!(niell.hydrostatic, chao.hydrostatic, rmax <= 0.01);
+            assert_float_eq!(niell.wet, chao.wet, rmax <= 0.01);
+        }
+
+        // Seasons are swapped between the hemispheres
+        let north = MappingFunction::Niell.factors(32.5, lat, 0.0, 5.0 * D2R);
+        let south = MappingFunction::Niell.factors(32.5 + 365.25 / 2.0, -lat, 0.0, 5.0 * D2R);
+        assert_float_eq!(
+            north.unwrap().hydrostatic,
+            south.unwrap().hydrostatic,
+            abs <= 1e-9
+        );
+    }
+
+    #[test]
+    fn troposphere_models() {
+        let lat = 45.0 * D2R;
+        let zenith = 90.0 * D2R;
+        let saastamoinen = Saastamoinen::new();
+        let hopfield = Hopfield::new();
+
+        // Sea level standard atmosphere at 45° latitude
+        let (hydrostatic, wet) = saastamoinen.zenith_delays(lat, 0.0);
+        assert_float_eq!(hydrostatic, 0.0022768 * 1013.25, abs <= 1e-9);
+        assert!(wet > 0.05 && wet < 0.2);
+        let (dry, hopfield_wet) = hopfield.zenith_delays(0.0);
+        assert_float_eq!(dry, hydrostatic, abs <= 0.01);
+        assert_float_eq!(hopfield_wet, wet, abs <= 0.05);
+
+        // The standard atmosphere gives delays within a few percent of the
+        // UNB3m reference values
+        let models: [&dyn TroposphereModel; 3] = [&saastamoinen, &hopfield, &Unb3m];
+        for model in models.iter() {
+            let delay = model.calc_delay(32.5, 40.0 * D2R, 1300.0, 45.0 * D2R);
+            assert_float_eq!(delay, 2.8567, rmax <= 0.07);
+            let delay = model.calc_delay(180.5, -10.0 * D2R, 0.0, 20.0 * D2R);
+            assert_float_eq!(delay, 7.4942, rmax <= 0.07);
+        }
+        assert_float_eq!(
+            saastamoinen.calc_delay(32.5, lat, 0.0, zenith),
+            hydrostatic + wet,
+            abs <= 1e-9
+        );
+
+        // The delay shrinks with height and humidity
+        assert!(
+            saastamoinen.calc_delay(32.5, lat, 2000.0, zenith)
+                < saastamoinen.calc_delay(32.5, lat, 0.0, zenith)
+        );
+        let dry = hopfield.set_relative_humidity(0.0);
+        assert_float_eq!(dry.zenith_delays(0.0).1, 0.0, abs <= 1e-12);
+
+        let niell = saastamoinen.set_mapping_function(MappingFunction::Niell);
+        assert_eq!(niell.mapping_function(), MappingFunction::Niell);
+        // Bending makes the path shorter than the flat earth approximation
+        let mapped = niell.calc_delay(32.5, lat, 0.0, 10.0 * D2R);
+        let flat = saastamoinen.calc_delay(32.5, lat, 0.0, 10.0 * D2R);
+        assert!(mapped < flat);
+        assert_float_eq!(mapped, flat, rmax <= 0.05);
+
+        for el in [0.0, -0.1].iter() {
+            assert_float_eq!(
+                saastamoinen.calc_delay(32.5, lat, 0.0, *el),
+                0.0,
+                abs <= 0.0
+            );
+            assert_float_eq!(hopfield.calc_delay(32.5, lat, 0.0, *el), 0.0, abs <= 0.0);
+        }
+    }
 }
@@ -0,0 +1,337 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! u-blox UBX message decoding
+//!
+//! UBX is the binary protocol of u-blox receivers. Each message is sent in a
+//! frame made of two sync bytes (`0xB5 0x62`), the message class and ID, a
+//! little endian 16 bit payload length, the payload and an 8 bit Fletcher
+//! checksum. [`decode_frame`] checks a single frame and [`FrameReader`] splits
+//! a byte stream into frames.
+//!
+//! Only the raw data messages are decoded:
+//!  * RXM-RAWX - Observations, converted into
+//!    [`NavigationMeasurement`](crate::navmeas::NavigationMeasurement)s
+//!  * RXM-SFRBX - Navigation message subframes, which [`SubframeDecoder`]
+//!    gathers and passes to the ephemeris and UTC parameter decoders
+//!
+//! # References
+//!   * u-blox 8 / u-blox M8 Receiver description, UBX-13003221, Section 32
+
+mod rawx;
+mod sfrbx;
+
+pub use rawx::RawxMessage;
+pub use sfrbx::{NavigationData, SfrbxMessage, SubframeDecoder};
+
+use crate::signal::{Code, GnssSignal};
+use std::error::Error;
+use std::fmt;
+
+/// Bytes starting every UBX frame
+pub const SYNC: [u8; 2] = [0xB5, 0x62];
+
+/// Class of the receiver manager messages
+const CLASS_RXM: u8 = 0x02;
+const ID_RXM_SFRBX: u8 = 0x13;
+const ID_RXM_RAWX: u8 = 0x15;
+
+/// Number of bytes in a frame besides the payload
+const FRAME_OVERHEAD: usize = 8;
+
+/// Errors which can occur while decoding UBX messages
+#[derive(Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum UbxError {
+    /// The frame doesn't start with the sync bytes
+    InvalidSync,
+    /// More bytes are needed to complete the frame
+    Incomplete,
+    /// The checksum of the frame doesn't match its contents
+    ChecksumMismatch,
+    /// The payload is too short for the fields of the message
+    Truncated,
+    /// The message is of a class and ID which isn't decoded
+    UnsupportedMessage(u8, u8),
+    /// The GNSS and signal identifiers don't match a known signal
+    UnknownSignal(u8, u8),
+    /// The message contents are inconsistent
+    InvalidMessage,
+}
+
+impl fmt::Display for UbxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UbxError::InvalidSync => write!(f, "Invalid UBX sync bytes"),
+            UbxError::Incomplete => write!(f, "Incomplete UBX frame"),
+            UbxError::ChecksumMismatch => write!(f, "UBX frame checksum mismatch"),
+            UbxError::Truncated => write!(f, "UBX message is truncated"),
+            UbxError::UnsupportedMessage(class, id) => {
+                write!(f, "Unsupported UBX message ({:#04x} {:#04x})", class, id)
+            }
+            UbxError::UnknownSignal(gnss_id, sig_id) => {
+                write!(
+                    f,
+                    "Unknown UBX signal (gnssId {}, sigId {})",
+                    gnss_id, sig_id
+                )
+            }
+            UbxError::InvalidMessage => write!(f, "Invalid UBX message contents"),
+        }
+    }
+}
+
+impl Error for UbxError {}
+
+/// A checked UBX frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    class: u8,
+    id: u8,
+    payload: Vec<u8>,
+}
+
+impl Frame {
+    pub fn class(&self) -> u8 {
+        self.class
+    }
+
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+}
+
+/// A decoded UBX message
+#[derive(Debug, Clone, PartialEq)]
+pub enum UbxMessage {
+    /// RXM-RAWX observations
+    Rawx(RawxMessage),
+    /// RXM-SFRBX navigation message subframe
+    Sfrbx(SfrbxMessage),
+    /// A message class and ID which isn't decoded
+    Unsupported(u8, u8),
+}
+
+impl UbxMessage {
+    /// Decodes the payload of a frame
+    pub fn decode(frame: &Frame) -> Result<UbxMessage, UbxError> {
+        match (frame.class, frame.id) {
+            (CLASS_RXM, ID_RXM_RAWX) => RawxMessage::decode(&frame.payload).map(UbxMessage::Rawx),
+            (CLASS_RXM, ID_RXM_SFRBX) => {
+                SfrbxMessage::decode(&frame.payload).map(UbxMessage::Sfrbx)
+            }
+            (class, id) => Ok(UbxMessage::Unsupported(class, id)),
+        }
+    }
+}
+
+/// Computes the 8 bit Fletcher checksum of the class, ID, length and payload
+fn checksum(data: &[u8]) -> [u8; 2] {
+    data.iter().fold([0u8, 0u8], |[a, b], byte| {
+        let a = a.wrapping_add(*byte);
+        [a, b.wrapping_add(a)]
+    })
+}
+
+/// Checks a frame at the start of `data`
+///
+/// Returns the frame and its total length, so that the next frame starts at
+/// that offset.
+pub fn decode_frame(data: &[u8]) -> Result<(Frame, usize), UbxError> {
+    if data.len() < 2 {
+        return Err(UbxError::Incomplete);
+    }
+    if data[..2] != SYNC {
+        return Err(UbxError::InvalidSync);
+    }
+    if data.len() < 6 {
+        return Err(UbxError::Incomplete);
+    }
+    let length = usize::from(u16::from_le_bytes([data[4], data[5]]));
+    let frame_length = length + FRAME_OVERHEAD;
+    if data.len() < frame_length {
+        return Err(UbxError::Incomplete);
+    }
+    if checksum(&data[2..length + 6]) != data[length + 6..frame_length] {
+        return Err(UbxError::ChecksumMismatch);
+    }
+    let frame = Frame {
+        class: data[2],
+        id: data[3],
+        payload: data[6..length + 6].to_vec(),
+    };
+    Ok((frame, frame_length))
+}
+
+/// Splits a stream of bytes into UBX frames
+///
+/// Bytes before the sync bytes and frames with a bad checksum are skipped,
+/// so the reader resynchronizes by itself after corrupted data or when UBX
+/// messages are interleaved with NMEA sentences.
+#[derive(Debug, Clone, Default)]
+pub struct FrameReader {
+    buffer: Vec<u8>,
+}
+
+impl FrameReader {
+    pub fn new() -> FrameReader {
+        FrameReader { buffer: Vec::new() }
+    }
+
+    /// Adds received bytes to the end of the stream
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Gets the next complete frame
+    ///
+    /// Returns `None` when more bytes are needed
+    pub fn next_frame(&mut self) -> Option<Frame> {
+        loop {
+            let start = self.buffer.iter().position(|b| *b == SYNC[0]);
+            self.buffer.drain(..start.unwrap_or(self.buffer.len()));
+            match decode_frame(&self.buffer) {
+                Ok((frame, length)) => {
+                    self.buffer.drain(..length);
+                    return Some(frame);
+                }
+                Err(UbxError::Incomplete) => return None,
+                // Not a real frame, look for the next sync byte
+                Err(_) => {
+                    self.buffer.remove(0);
+                }
+            }
+        }
+    }
+
+    /// Gets the number of bytes waiting to be framed
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+/// Gets the signal of a UBX GNSS ID, satellite ID and signal ID
+fn ubx_signal(gnss_id: u8, sv_id: u8, sig_id: u8) -> Result<GnssSignal, UbxError> {
+    let code = match (gnss_id, sig_id) {
+        (0, 0) => Code::GpsL1ca,
+        (0, 3) => Code::GpsL2cl,
+        (0, 4) => Code::GpsL2cm,
+        (0, 6) => Code::GpsL5i,
+        (0, 7) => Code::GpsL5q,
+        (1, 0) => Code::SbasL1ca,
+        (2, 0) => Code::GalE1c,
+        (2, 1) => Code::GalE1b,
+        (2, 3) => Code::GalE5i,
+        (2, 4) => Code::GalE5q,
+        (2, 5) => Code::GalE7i,
+        (2, 6) => Code::GalE7q,
+        // Signals with the D1 and D2 navigation messages
+        (3, 0) | (3, 1) => Code::Bds2B1,
+        (3, 2) | (3, 3) => Code::Bds2B2,
+        (3, 5) => Code::Bds3B1cq,
+        (3, 6) => Code::Bds3B1ci,
+        (3, 7) => Code::Bds3B5q,
+        (3, 8) => Code::Bds3B5i,
+        (5, 0) => Code::QzsL1ca,
+        (5, 4) => Code::QzsL2cm,
+        (5, 5) => Code::QzsL2cl,
+        (5, 8) => Code::QzsL5i,
+        (5, 9) => Code::QzsL5q,
+        (6, 0) => Code::GloL1of,
+        (6, 2) => Code::GloL2of,
+        _ => return Err(UbxError::UnknownSignal(gnss_id, sig_id)),
+    };
+    // QZSS satellites are numbered from 1 rather than by their PRN
+    let sat = if gnss_id == 5 {
+        u16::from(sv_id) + 192
+    } else {
+        u16::from(sv_id)
+    };
+    GnssSignal::new(sat, code).map_err(|_| UbxError::InvalidMessage)
+}
+
+/// Gets the GLONASS frequency channel number from the UBX frequency ID
+fn glonass_fcn(gnss_id: u8, freq_id: u8) -> Option<i8> {
+    if gnss_id == 6 && freq_id <= 13 {
+        Some(freq_id as i8 - 7)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Wraps a payload into a frame
+    pub(crate) fn frame(class: u8, id: u8, payload: &[u8]) -> Vec<u8> {
+        let mut data = SYNC.to_vec();
+        data.extend_from_slice(&[class, id]);
+        data.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        data.extend_from_slice(payload);
+        let ck = checksum(&data[2..]);
+        data.extend_from_slice(&ck);
+        data
+    }
+
+    #[test]
+    fn framing() {
+        // UBX-MON-VER poll
+        let poll = [0xB5, 0x62, 0x0A, 0x04, 0x00, 0x00, 0x0E, 0x34];
+        let (poll_frame, length) = decode_frame(&poll).unwrap();
+        assert_eq!(
+            (poll_frame.class(), poll_frame.id(), length),
+            (0x0A, 0x04, 8)
+        );
+        assert!(poll_frame.payload().is_empty());
+        assert_eq!(
+            UbxMessage::decode(&poll_frame),
+            Ok(UbxMessage::Unsupported(0x0A, 0x04))
+        );
+
+        let data = frame(0x01, 0x07, &[1, 2, 3, 4, 5]);
+        assert_eq!(decode_frame(&data[..9]), Err(UbxError::Incomplete));
+        assert_eq!(decode_frame(&data[1..]), Err(UbxError::InvalidSync));
+        let mut corrupted = data.clone();
+        corrupted[7] ^= 0x01;
+        assert_eq!(decode_frame(&corrupted), Err(UbxError::ChecksumMismatch));
+
+        // The reader skips NMEA sentences and corrupted frames
+        let mut reader = FrameReader::new();
+        reader.push(b"$GPTXT,01,01,02,ANTSTATUS=OK*3B\r\n");
+        reader.push(&corrupted);
+        reader.push(&data[..4]);
+        assert_eq!(reader.next_frame(), None);
+        reader.push(&data[4..]);
+        reader.push(&poll);
+        assert_eq!(reader.next_frame().unwrap().payload(), &[1, 2, 3, 4, 5]);
+        assert_eq!(reader.next_frame(), Some(poll_frame));
+        assert_eq!(reader.next_frame(), None);
+        assert_eq!(reader.buffered(), 0);
+    }
+
+    #[test]
+    fn signals() {
+        assert_eq!(
+            ubx_signal(5, 2, 0),
+            Ok(GnssSignal::new(194, Code::QzsL1ca).unwrap())
+        );
+        assert_eq!(
+            ubx_signal(2, 11, 5),
+            Ok(GnssSignal::new(11, Code::GalE7i).unwrap())
+        );
+        assert_eq!(ubx_signal(5, 1, 1), Err(UbxError::UnknownSignal(5, 1)));
+        assert_eq!(ubx_signal(0, 40, 0), Err(UbxError::InvalidMessage));
+        assert_eq!(glonass_fcn(6, 0), Some(-7));
+        assert_eq!(glonass_fcn(0, 0), None);
+    }
+}
@@ -0,0 +1,245 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Multi-GNSS raw measurements, message RXM-RAWX
+
+use super::{glonass_fcn, ubx_signal, UbxError};
+use crate::navmeas::NavigationMeasurement;
+use crate::time::GpsTime;
+use std::convert::TryInto;
+use std::time::Duration;
+
+/// Length of the message header
+const HEADER_LENGTH: usize = 16;
+/// Length of each measurement block
+const MEASUREMENT_LENGTH: usize = 32;
+
+/// Tracking status bits
+const TRK_PR_VALID: u8 = 1 << 0;
+const TRK_CP_VALID: u8 = 1 << 1;
+const TRK_HALF_CYCLE_VALID: u8 = 1 << 2;
+
+/// Receiver status bits
+const REC_LEAP_SECONDS_VALID: u8 = 1 << 0;
+const REC_CLOCK_RESET: u8 = 1 << 1;
+
+/// A decoded RXM-RAWX message
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawxMessage {
+    time: GpsTime,
+    leap_seconds: Option<i8>,
+    clock_reset: bool,
+    glonass_fcns: Vec<(u16, i8)>,
+    measurements: Vec<NavigationMeasurement>,
+}
+
+impl RawxMessage {
+    /// Decodes an RXM-RAWX payload
+    ///
+    /// Measurements of signals which can't be represented are skipped.
+    pub fn decode(payload: &[u8]) -> Result<RawxMessage, UbxError> {
+        if payload.len() < HEADER_LENGTH {
+            return Err(UbxError::Truncated);
+        }
+        let tow = f64::from_le_bytes(payload[0..8].try_into().unwrap());
+        let week = u16::from_le_bytes([payload[8], payload[9]]);
+        let leap_seconds = payload[10] as i8;
+        let count = usize::from(payload[11]);
+        let status = payload[12];
+        if payload.len() < HEADER_LENGTH + count * MEASUREMENT_LENGTH {
+            return Err(UbxError::Truncated);
+        }
+        let time = GpsTime::new(week as i16, tow).map_err(|_| UbxError::InvalidMessage)?;
+
+        let mut glonass_fcns = Vec::new();
+        let mut measurements = Vec::with_capacity(count);
+        for block in payload[HEADER_LENGTH..]
+            .chunks_exact(MEASUREMENT_LENGTH)
+            .take(count)
+        {
+            let sid = match ubx_signal(block[20], block[21], block[22]) {
+                Ok(sid) => sid,
+                Err(_) => continue,
+            };
+            if let Some(fcn) = glonass_fcn(block[20], block[23]) {
+                if !glonass_fcns.contains(&(sid.sat(), fcn)) {
+                    glonass_fcns.push((sid.sat(), fcn));
+                }
+            }
+            let pseudorange = f64::from_le_bytes(block[0..8].try_into().unwrap());
+            let carrier_phase = f64::from_le_bytes(block[8..16].try_into().unwrap());
+            let doppler = f32::from_le_bytes(block[16..20].try_into().unwrap());
+            let lock_time = u16::from_le_bytes([block[24], block[25]]);
+            let cn0 = block[26];
+            let tracking = block[30];
+
+            let mut measurement = NavigationMeasurement::new();
+            measurement.set_sid(sid);
+            if tracking & TRK_PR_VALID != 0 && pseudorange.is_finite() {
+                measurement.set_pseudorange(pseudorange);
+            }
+            if tracking & TRK_CP_VALID != 0 && carrier_phase.is_finite() {
+                measurement.set_carrier_phase(carrier_phase);
+                measurement.set_half_cycle_known(tracking & TRK_HALF_CYCLE_VALID != 0);
+            }
+            if doppler.is_finite() {
+                measurement.set_measured_doppler(f64::from(doppler));
+            }
+            measurement.set_cn0(f64::from(cn0));
+            measurement.set_lock_time(Duration::from_millis(u64::from(lock_time)));
+            measurements.push(measurement);
+        }
+
+        Ok(RawxMessage {
+            time,
+            leap_seconds: if status & REC_LEAP_SECONDS_VALID != 0 {
+                Some(leap_seconds)
+            } else {
+                None
+            },
+            clock_reset: status & REC_CLOCK_RESET != 0,
+            glonass_fcns,
+            measurements,
+        })
+    }
+
+    /// Gets the receiver time of the measurements
+    pub fn time(&self) -> GpsTime {
+        self.time
+    }
+
+    /// Gets the offset of GPS time from UTC, if the receiver knows it
+    pub fn leap_seconds(&self) -> Option<i8> {
+        self.leap_seconds
+    }
+
+    /// Checks if the receiver clock was reset, in which case the carrier
+    /// phase measurements aren't continuous with the previous epoch
+    pub fn clock_reset(&self) -> bool {
+        self.clock_reset
+    }
+
+    /// Gets the frequency channel numbers of the tracked GLONASS satellites,
+    /// as pairs of slot and channel number
+    pub fn glonass_fcns(&self) -> &[(u16, i8)] {
+        &self.glonass_fcns
+    }
+
+    pub fn measurements(&self) -> &[NavigationMeasurement] {
+        &self.measurements
+    }
+
+    /// Takes the measurements out of the message
+    pub fn into_measurements(self) -> Vec<NavigationMeasurement> {
+        self.measurements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::{Code, GnssSignal};
+    use crate::ubx::tests::frame;
+    use crate::ubx::{decode_frame, UbxMessage};
+    use float_eq::assert_float_eq;
+
+    #[allow(clippy::too_many_arguments)]
+    fn block(
+        pseudorange: f64,
+        carrier_phase: f64,
+        doppler: f32,
+        ids: [u8; 4],
+        lock_time: u16,
+        cn0: u8,
+        tracking: u8,
+    ) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&pseudorange.to_le_bytes());
+        data.extend_from_slice(&carrier_phase.to_le_bytes());
+        data.extend_from_slice(&doppler.to_le_bytes());
+        data.extend_from_slice(&ids);
+        data.extend_from_slice(&lock_time.to_le_bytes());
+        data.extend_from_slice(&[cn0, 5, 3, 7, tracking, 0]);
+        data
+    }
+
+    #[test]
+    fn decode_rawx() {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&345_600.5f64.to_le_bytes());
+        payload.extend_from_slice(&2091u16.to_le_bytes());
+        payload.extend_from_slice(&[18, 4, 0b01, 1, 0, 0]);
+        payload.extend(block(
+            21_000_123.25,
+            110_359_010.5,
+            -1_250.5,
+            [0, 12, 0, 0],
+            64_500,
+            45,
+            0b0111,
+        ));
+        payload.extend(block(
+            20_000_000.0,
+            0.0,
+            300.0,
+            [6, 4, 0, 6],
+            1_000,
+            40,
+            0b0001,
+        ));
+        // L1S isn't a supported signal
+        payload.extend(block(0.0, 0.0, 0.0, [5, 1, 1, 0], 0, 0, 0));
+        payload.extend(block(
+            22_000_000.0,
+            115_000_000.0,
+            0.0,
+            [2, 11, 1, 0],
+            0,
+            30,
+            0b0011,
+        ));
+
+        let data = frame(0x02, 0x15, &payload);
+        let (frame, _) = decode_frame(&data).unwrap();
+        let rawx = match UbxMessage::decode(&frame).unwrap() {
+            UbxMessage::Rawx(rawx) => rawx,
+            other => panic!("Unexpected message {:?}", other),
+        };
+        assert_eq!(rawx.time(), GpsTime::new(2091, 345_600.5).unwrap());
+        assert_eq!(rawx.leap_seconds(), Some(18));
+        assert!(!rawx.clock_reset());
+        assert_eq!(rawx.glonass_fcns(), &[(4, -1)]);
+
+        let measurements = rawx.measurements();
+        assert_eq!(measurements.len(), 3);
+        let gps = &measurements[0];
+        assert_eq!(gps.sid(), GnssSignal::new(12, Code::GpsL1ca).unwrap());
+        assert_float_eq!(gps.pseudorange().unwrap(), 21_000_123.25, abs <= 0.0);
+        assert_float_eq!(gps.carrier_phase().unwrap(), 110_359_010.5, abs <= 0.0);
+        assert!(gps.half_cycle_known());
+        assert_float_eq!(gps.measured_doppler().unwrap(), -1_250.5, abs <= 0.0);
+        assert_float_eq!(gps.cn0().unwrap(), 45.0, abs <= 0.0);
+        assert_eq!(gps.lock_time(), Duration::from_millis(64_500));
+
+        let glo = &measurements[1];
+        assert_eq!(glo.sid(), GnssSignal::new(4, Code::GloL1of).unwrap());
+        assert!(glo.pseudorange().is_some());
+        assert_eq!(glo.carrier_phase(), None);
+
+        let gal = &measurements[2];
+        assert_eq!(gal.sid(), GnssSignal::new(11, Code::GalE1b).unwrap());
+        assert!(gal.carrier_phase().is_some());
+        assert!(!gal.half_cycle_known());
+
+        assert_eq!(
+            RawxMessage::decode(&payload[..HEADER_LENGTH + 40]),
+            Err(UbxError::Truncated)
+        );
+    }
+}
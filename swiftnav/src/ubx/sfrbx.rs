@@ -0,0 +1,438 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Broadcast navigation data subframes, message RXM-SFRBX
+//!
+//! The receiver reports each subframe, or page for Galileo, as it is
+//! received. GPS and BeiDou words are reported as 30 bit words including
+//! the parity bits. Galileo I/NAV pages are reported as the even and odd
+//! page parts, 4 words each.
+//!
+//! # References
+//!   * IS-GPS-200H, Section 20.3.2
+//!   * BeiDou SIS ICD, Version 2.1, Section 5.2
+//!   * Galileo OS SIS ICD, Issue 2.0, Section 4.3.2
+
+use super::{glonass_fcn, ubx_signal, UbxError};
+use crate::ephemeris::{Ephemeris, GAL_INAV_CONTENT_BYTE};
+use crate::signal::{Code, GnssSignal};
+use crate::time::{GpsTime, UtcParams};
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+/// Length of the message header
+const HEADER_LENGTH: usize = 8;
+/// Number of words in a GPS LNAV or BeiDou D1 subframe
+const SUBFRAME_WORDS: usize = 10;
+/// Number of words in a Galileo I/NAV page
+const GAL_PAGE_WORDS: usize = 8;
+/// Mask of the 30 bits of a GPS or BeiDou word
+const WORD_MASK: u32 = 0x3FFF_FFFF;
+/// GPS telemetry word preamble
+const GPS_PREAMBLE: u32 = 0x8B;
+/// BeiDou D1 preamble
+const BDS_PREAMBLE: u32 = 0x712;
+/// Page ID of the GPS subframe 4 page with the UTC parameters
+const GPS_UTC_PAGE_ID: u32 = 56;
+/// Number of bits of data in the even part of a Galileo I/NAV page
+const GAL_EVEN_DATA_BITS: usize = 112;
+
+/// A decoded RXM-SFRBX message
+#[derive(Debug, Clone, PartialEq)]
+pub struct SfrbxMessage {
+    sid: GnssSignal,
+    fcn: Option<i8>,
+    words: Vec<u32>,
+}
+
+impl SfrbxMessage {
+    /// Decodes an RXM-SFRBX payload
+    pub fn decode(payload: &[u8]) -> Result<SfrbxMessage, UbxError> {
+        if payload.len() < HEADER_LENGTH {
+            return Err(UbxError::Truncated);
+        }
+        let count = usize::from(payload[4]);
+        if payload.len() < HEADER_LENGTH + 4 * count {
+            return Err(UbxError::Truncated);
+        }
+        let words = payload[HEADER_LENGTH..HEADER_LENGTH + 4 * count]
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        Ok(SfrbxMessage {
+            sid: ubx_signal(payload[0], payload[1], payload[2])?,
+            fcn: glonass_fcn(payload[0], payload[3]),
+            words,
+        })
+    }
+
+    /// Gets the signal the data was received on
+    pub fn sid(&self) -> GnssSignal {
+        self.sid
+    }
+
+    /// Gets the frequency channel number of a GLONASS satellite
+    pub fn glonass_fcn(&self) -> Option<i8> {
+        self.fcn
+    }
+
+    /// Gets the raw words, in the layout used by the receiver
+    pub fn words(&self) -> &[u32] {
+        &self.words
+    }
+}
+
+/// Navigation data decoded from a set of subframes
+pub enum NavigationData {
+    Ephemeris(Ephemeris),
+    UtcParams(UtcParams),
+}
+
+/// Gathers the subframes of each satellite until a complete ephemeris, or
+/// another set of parameters, can be decoded
+///
+/// The supported navigation messages are the GPS L1 C/A LNAV message, the
+/// BeiDou B1I D1 message and the Galileo E1-B and E5b I/NAV message. The
+/// subframes of an ephemeris have to agree on the issue of data, or be
+/// consecutive for BeiDou, so that subframes from before and after an update
+/// aren't mixed.
+///
+/// The GPS UTC parameters only carry the 8 LSBs of their week numbers, they
+/// are resolved with the time given to [`SubframeDecoder::set_time()`], e.g.
+/// the time of the RXM-RAWX messages. The UTC parameters are skipped until a
+/// time is known.
+#[derive(Debug, Clone, Default)]
+pub struct SubframeDecoder {
+    gps: HashMap<u16, GpsFrames>,
+    bds: HashMap<u16, BdsFrames>,
+    gal: HashMap<u16, GalPages>,
+    time: Option<GpsTime>,
+}
+
+impl SubframeDecoder {
+    pub fn new() -> SubframeDecoder {
+        SubframeDecoder::default()
+    }
+
+    /// Sets the current time, used to resolve truncated week numbers
+    pub fn set_time(&mut self, time: GpsTime) {
+        self.time = Some(time);
+    }
+
+    /// Gets the current time, if one has been set
+    pub fn time(&self) -> Option<GpsTime> {
+        self.time
+    }
+
+    /// Adds a subframe, returning the decoded data when it completes a set
+    pub fn push(&mut self, message: &SfrbxMessage) -> Option<NavigationData> {
+        let sid = message.sid();
+        let sat = sid.sat();
+        match sid.code() {
+            Code::GpsL1ca => {
+                let words = subframe_words(message)?;
+                if field(words[0], 1, 8) != GPS_PREAMBLE {
+                    return None;
+                }
+                if field(words[1], 20, 3) == 4 && field(words[2], 3, 6) == GPS_UTC_PAGE_ID {
+                    let page: &[u32; 8] = words[2..].try_into().unwrap();
+                    let params = UtcParams::decode(page, self.time.as_ref()?)?;
+                    return Some(NavigationData::UtcParams(params));
+                }
+                let (frames, tot_tow) = self.gps.entry(sat).or_default().push(&words)?;
+                let mut ephemeris = Ephemeris::decode_gps(&frames, tot_tow);
+                ephemeris.set_sid(sid);
+                Some(NavigationData::Ephemeris(ephemeris))
+            }
+            // Only the MEO and IGSO satellites broadcast the D1 message
+            Code::Bds2B1 if (6..=58).contains(&sat) => {
+                let words = subframe_words(message)?;
+                if field(words[0], 1, 11) != BDS_PREAMBLE {
+                    return None;
+                }
+                let frames = self.bds.entry(sat).or_default().push(&words)?;
+                Some(NavigationData::Ephemeris(Ephemeris::decode_bds(
+                    &frames, sid,
+                )))
+            }
+            Code::GalE1b | Code::GalE7i => {
+                let words: &[u32; GAL_PAGE_WORDS] = message.words().try_into().ok()?;
+                let content = gal_page_content(words)?;
+                let pages = self.gal.entry(sat).or_default().push(&content)?;
+                let mut ephemeris = Ephemeris::decode_gal(&pages);
+                ephemeris.set_sid(GnssSignal::new(sat, Code::GalE1b).ok()?);
+                ephemeris.set_valid(true);
+                Some(NavigationData::Ephemeris(ephemeris))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Gets the 30 bit words of a GPS or BeiDou subframe
+fn subframe_words(message: &SfrbxMessage) -> Option<[u32; SUBFRAME_WORDS]> {
+    let words: &[u32; SUBFRAME_WORDS] = message.words().try_into().ok()?;
+    Some(words.map(|word| word & WORD_MASK))
+}
+
+/// Gets a field of a 30 bit word, with the bits numbered from 1 at the most
+/// significant bit as done by the ICDs
+fn field(word: u32, first: u32, len: u32) -> u32 {
+    (word >> (31 - first - len)) & ((1 << len) - 1)
+}
+
+/// Subframes 1 to 3 of a GPS satellite
+#[derive(Debug, Clone, Default)]
+struct GpsFrames {
+    subframes: [Option<[u32; SUBFRAME_WORDS]>; 3],
+}
+
+impl GpsFrames {
+    /// Stores a subframe, giving words 3 to 10 of the three subframes and
+    /// the time of transmission of subframe 1 once they are complete
+    fn push(&mut self, words: &[u32; SUBFRAME_WORDS]) -> Option<([[u32; 8]; 3], f64)> {
+        let id = field(words[1], 20, 3) as usize;
+        *self.subframes.get_mut(id.checked_sub(1)?)? = Some(*words);
+
+        let [sf1, sf2, sf3] = self.subframes;
+        let (sf1, sf2, sf3) = (sf1?, sf2?, sf3?);
+        // The 8 LSBs of the IODC and both copies of the IODE must match
+        let iode = field(sf2[2], 1, 8);
+        if field(sf1[7], 1, 8) != iode || field(sf3[9], 1, 8) != iode {
+            return None;
+        }
+        self.subframes = Default::default();
+        // The HOW holds the time of week of the next subframe
+        let tot_tow = f64::from(field(sf1[1], 1, 17)) * 6.0 - 6.0;
+        let frames = [sf1, sf2, sf3].map(|sf| sf[2..].try_into().unwrap());
+        Some((frames, tot_tow))
+    }
+}
+
+/// Subframes 1 to 3 of a BeiDou satellite
+#[derive(Debug, Clone, Default)]
+struct BdsFrames {
+    subframes: [Option<[u32; SUBFRAME_WORDS]>; 3],
+}
+
+impl BdsFrames {
+    /// Stores a subframe, giving the three subframes once they are complete
+    fn push(&mut self, words: &[u32; SUBFRAME_WORDS]) -> Option<[[u32; SUBFRAME_WORDS]; 3]> {
+        let id = field(words[0], 16, 3) as usize;
+        *self.subframes.get_mut(id.checked_sub(1)?)? = Some(*words);
+
+        let [sf1, sf2, sf3] = self.subframes;
+        let frames = [sf1?, sf2?, sf3?];
+        // The subframes are sent every 6 seconds, they have to be from the
+        // same frame
+        let sow = |sf: &[u32; SUBFRAME_WORDS]| (field(sf[0], 19, 8) << 12) | field(sf[1], 1, 12);
+        if sow(&frames[1]) != sow(&frames[0]) + 6 || sow(&frames[2]) != sow(&frames[1]) + 6 {
+            return None;
+        }
+        self.subframes = Default::default();
+        Some(frames)
+    }
+}
+
+/// Words 1 to 5 of a Galileo satellite
+#[derive(Debug, Clone, Default)]
+struct GalPages {
+    words: [Option<[u8; GAL_INAV_CONTENT_BYTE]>; 5],
+}
+
+impl GalPages {
+    /// Stores a word, giving words 1 to 5 once they are complete
+    fn push(
+        &mut self,
+        content: &[u8; GAL_INAV_CONTENT_BYTE],
+    ) -> Option<[[u8; GAL_INAV_CONTENT_BYTE]; 5]> {
+        let word_type = usize::from(content[0] >> 2);
+        *self.words.get_mut(word_type.checked_sub(1)?)? = Some(*content);
+
+        let [w1, w2, w3, w4, w5] = self.words;
+        let words = [w1?, w2?, w3?, w4?, w5?];
+        // Words 1 to 4 all carry the issue of data of the ephemeris
+        let iod = |word: &[u8; GAL_INAV_CONTENT_BYTE]| {
+            (u16::from(word[0] & 0x03) << 8) | u16::from(word[1])
+        };
+        if words[1..4].iter().any(|word| iod(word) != iod(&words[0])) {
+            return None;
+        }
+        self.words = Default::default();
+        Some(words)
+    }
+}
+
+/// Extracts the 128 bit word from the even and odd parts of a Galileo I/NAV
+/// page, returning `None` for alert pages
+fn gal_page_content(words: &[u32; GAL_PAGE_WORDS]) -> Option<[u8; GAL_INAV_CONTENT_BYTE]> {
+    let bit = |n: usize| (words[n / 32] >> (31 - n % 32)) & 1 == 1;
+    // Each part starts with the even/odd bit and the page type bit
+    if bit(0) || bit(1) || !bit(128) || bit(129) {
+        return None;
+    }
+    let mut content = [0u8; GAL_INAV_CONTENT_BYTE];
+    for (k, byte) in content.iter_mut().enumerate() {
+        for i in 8 * k..8 * (k + 1) {
+            let source = if i < GAL_EVEN_DATA_BITS {
+                2 + i
+            } else {
+                128 + 2 + (i - GAL_EVEN_DATA_BITS)
+            };
+            if bit(source) {
+                *byte |= 0x80 >> (i % 8);
+            }
+        }
+    }
+    Some(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ubx::tests::frame;
+    use crate::ubx::{decode_frame, UbxMessage};
+
+    fn sfrbx_payload(ids: [u8; 4], words: &[u32]) -> Vec<u8> {
+        let mut payload = vec![ids[0], ids[1], ids[2], ids[3], words.len() as u8, 0, 2, 0];
+        for word in words {
+            payload.extend_from_slice(&word.to_le_bytes());
+        }
+        payload
+    }
+
+    /// Makes a GPS subframe with the given HOW fields and a word holding an
+    /// issue of data in bits 1 to 8
+    fn gps_subframe(id: u32, tow_count: u32, iod_word: usize, iod: u32) -> [u32; 10] {
+        let mut words = [0u32; 10];
+        words[0] = GPS_PREAMBLE << 22;
+        words[1] = (tow_count << 13) | (id << 8);
+        words[iod_word] = iod << 22;
+        words
+    }
+
+    #[test]
+    fn decode_sfrbx() {
+        let words = gps_subframe(1, 1000, 7, 45);
+        let data = frame(0x02, 0x13, &sfrbx_payload([0, 7, 0, 0], &words));
+        let (frame, _) = decode_frame(&data).unwrap();
+        let sfrbx = match UbxMessage::decode(&frame).unwrap() {
+            UbxMessage::Sfrbx(sfrbx) => sfrbx,
+            _ => panic!("Unexpected message"),
+        };
+        assert_eq!(sfrbx.sid(), GnssSignal::new(7, Code::GpsL1ca).unwrap());
+        assert_eq!(sfrbx.words(), &words);
+        assert_eq!(sfrbx.glonass_fcn(), None);
+
+        let glo = SfrbxMessage::decode(&sfrbx_payload([6, 3, 0, 8], &[1, 2, 3, 4])).unwrap();
+        assert_eq!(glo.glonass_fcn(), Some(1));
+        assert_eq!(
+            SfrbxMessage::decode(&sfrbx_payload([0, 7, 0, 0], &words)[..40]),
+            Err(UbxError::Truncated)
+        );
+    }
+
+    #[test]
+    fn gps_subframes() {
+        let mut frames = GpsFrames::default();
+        assert!(frames.push(&gps_subframe(1, 1000, 7, 45)).is_none());
+        assert!(frames.push(&gps_subframe(2, 1001, 2, 45)).is_none());
+        // Subframe 3 of the next issue of data
+        assert!(frames.push(&gps_subframe(3, 1002, 9, 46)).is_none());
+        assert!(frames.push(&gps_subframe(1, 1005, 7, 46)).is_none());
+        let (words, tot_tow) = frames.push(&gps_subframe(2, 1006, 2, 46)).unwrap();
+        assert_eq!(tot_tow, 6024.0);
+        assert_eq!(words[0][5], 46 << 22);
+        assert_eq!(words[1][0], 46 << 22);
+        assert_eq!(words[2][7], 46 << 22);
+        assert!(frames.subframes.iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn gps_utc_page() {
+        // Subframe 4 page 18, with the reference time of the UTC parameters
+        // in week 2435 and a leap second at the end of week 2437
+        let mut words = gps_subframe(4, 1000, 2, (1 << 6) | GPS_UTC_PAGE_ID);
+        words[7] = ((2435 % 256) << 6) | (36 << 14);
+        words[8] = (18 << 22) | ((2437 % 256) << 14) | (7 << 6);
+        words[9] = 19 << 22;
+        let message = SfrbxMessage {
+            sid: GnssSignal::new(7, Code::GpsL1ca).unwrap(),
+            fcn: None,
+            words: words.to_vec(),
+        };
+
+        // The weeks can't be resolved before the time is known
+        let mut decoder = SubframeDecoder::new();
+        assert!(decoder.push(&message).is_none());
+
+        decoder.set_time(GpsTime::new(2440, 0.0).unwrap());
+        let params = match decoder.push(&message) {
+            Some(NavigationData::UtcParams(params)) => params,
+            _ => panic!("Expected UTC parameters"),
+        };
+        assert_eq!(params.tot(), GpsTime::new(2435, 36.0 * 4096.0).unwrap());
+        assert_eq!(params.t_lse(), GpsTime::new(2438, 18.0).unwrap());
+        assert_eq!(params.dt_lsf(), 19);
+    }
+
+    #[test]
+    fn bds_subframes() {
+        let subframe = |id: u32, sow: u32| {
+            let mut words = [0u32; 10];
+            words[0] = (BDS_PREAMBLE << 19) | (id << 12) | ((sow >> 12) << 4);
+            words[1] = (sow & 0xFFF) << 18;
+            words
+        };
+        let mut frames = BdsFrames::default();
+        assert!(frames.push(&subframe(1, 345_600)).is_none());
+        assert!(frames.push(&subframe(2, 345_606)).is_none());
+        assert!(frames.push(&subframe(3, 345_642)).is_none());
+        let words = frames.push(&subframe(3, 345_612)).unwrap();
+        assert_eq!(field(words[2][0], 16, 3), 3);
+    }
+
+    #[test]
+    fn galileo_pages() {
+        // Word type 1 with IODnav 97, split into the page parts
+        let content: [u8; 16] = [
+            0x4, 0x61, 0x23, 0x28, 0xBF, 0x30, 0x9B, 0xA0, 0x0, 0x71, 0xC8, 0x6A, 0xA8, 0x14, 0x16,
+            0x7,
+        ];
+        let mut bits = [false; 256];
+        bits[128] = true;
+        for i in 0..128 {
+            let value = content[i / 8] & (0x80 >> (i % 8)) != 0;
+            if i < 112 {
+                bits[2 + i] = value;
+            } else {
+                bits[130 + i - 112] = value;
+            }
+        }
+        let mut words = [0u32; 8];
+        for (i, bit) in bits.iter().enumerate() {
+            if *bit {
+                words[i / 32] |= 1 << (31 - i % 32);
+            }
+        }
+        assert_eq!(gal_page_content(&words), Some(content));
+
+        // Alert pages are skipped
+        words[0] |= 1 << 30;
+        assert_eq!(gal_page_content(&words), None);
+
+        let mut pages = GalPages::default();
+        for word_type in 1..=5u8 {
+            let mut word = content;
+            word[0] = (word_type << 2) | (word[0] & 0x03);
+            let result = pages.push(&word);
+            assert_eq!(result.is_some(), word_type == 5);
+        }
+    }
+}
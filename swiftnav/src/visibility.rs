@@ -0,0 +1,485 @@
+// Copyright (c) 2020-2021 Swift Navigation Inc.
+// Contact: Swift Navigation <dev@swiftnav.com>
+//
+// This source is subject to the license found in the file 'LICENSE' which must
+// be be distributed together with this source. All other rights reserved.
+//
+// THIS CODE AND INFORMATION IS PROVIDED "AS IS" WITHOUT WARRANTY OF ANY KIND,
+// EITHER EXPRESSED OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND/OR FITNESS FOR A PARTICULAR PURPOSE.
+//! Satellite visibility prediction
+//!
+//! Predicts when satellites rise above and set below an elevation mask at a
+//! location, and how high they get in between. This is what survey planning
+//! tools use to pick the times with the most satellites in view.
+//!
+//! The satellite orbits can come from any [`Orbit`], such as broadcast
+//! [ephemerides](Ephemeris), which are only valid for a few hours, or
+//! [almanacs](Almanac), which can be used for predictions weeks ahead. The
+//! elevation is sampled at a regular step, and the rise, set and highest
+//! points of each pass are then refined between the samples. Passes shorter
+//! than the step can be missed.
+
+use crate::coords::ECEF;
+use crate::ephemeris::almanac::Almanac;
+use crate::ephemeris::Ephemeris;
+use crate::signal::{Constellation, GnssSignal};
+use crate::time::GpsTime;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Number of bisections used to refine the rise and set times
+const REFINE_ITERATIONS: usize = 30;
+
+/// Source of the positions of a satellite
+pub trait Orbit {
+    /// Gets the satellite of the orbit, `None` if it isn't known
+    fn sid(&self) -> Option<GnssSignal>;
+
+    /// Gets the position of the satellite at a time, `None` if the orbit
+    /// can't be used at that time
+    fn position(&self, t: GpsTime) -> Option<ECEF>;
+}
+
+impl Orbit for Ephemeris {
+    fn sid(&self) -> Option<GnssSignal> {
+        Ephemeris::sid(self).ok()
+    }
+
+    fn position(&self, t: GpsTime) -> Option<ECEF> {
+        self.calc_satellite_state(t).ok().map(|state| state.pos)
+    }
+}
+
+impl Orbit for Almanac {
+    fn sid(&self) -> Option<GnssSignal> {
+        Some(Almanac::sid(self))
+    }
+
+    fn position(&self, t: GpsTime) -> Option<ECEF> {
+        Some(self.calc_satellite_state(t).pos)
+    }
+}
+
+impl<T: Orbit + ?Sized> Orbit for &T {
+    fn sid(&self) -> Option<GnssSignal> {
+        (**self).sid()
+    }
+
+    fn position(&self, t: GpsTime) -> Option<ECEF> {
+        (**self).position(t)
+    }
+}
+
+/// Settings of the visibility prediction
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VisibilitySettings {
+    elevation_mask: f64,
+    step: Duration,
+}
+
+impl VisibilitySettings {
+    /// Makes the default settings
+    ///
+    /// Note: The default settings consist of
+    ///  * An elevation mask of 10 degrees
+    ///  * Sampling the elevation every 60 seconds
+    pub fn new() -> VisibilitySettings {
+        VisibilitySettings {
+            elevation_mask: 10.0,
+            step: Duration::from_secs(60),
+        }
+    }
+
+    /// Sets the minimum elevation of visible satellites, in degrees
+    pub fn set_elevation_mask(self, elevation_mask: f64) -> VisibilitySettings {
+        VisibilitySettings {
+            elevation_mask,
+            ..self
+        }
+    }
+
+    /// Sets the time between elevation samples
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `step` is zero
+    pub fn set_step(self, step: Duration) -> VisibilitySettings {
+        assert!(step > Duration::ZERO, "Time step must be larger than zero");
+        VisibilitySettings { step, ..self }
+    }
+
+    pub fn elevation_mask(&self) -> f64 {
+        self.elevation_mask
+    }
+
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+}
+
+impl Default for VisibilitySettings {
+    fn default() -> VisibilitySettings {
+        VisibilitySettings::new()
+    }
+}
+
+/// A period during which a satellite is above the elevation mask
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+pub struct SatellitePass {
+    sid: GnssSignal,
+    rise: Option<GpsTime>,
+    set: Option<GpsTime>,
+    max_elevation: f64,
+    max_elevation_time: GpsTime,
+}
+
+impl SatellitePass {
+    /// Gets the satellite, as the signal of the first orbit given for it
+    pub fn sid(&self) -> GnssSignal {
+        self.sid
+    }
+
+    /// Gets the time the satellite rises above the mask, `None` if it is
+    /// already visible at the start of the prediction
+    pub fn rise(&self) -> Option<GpsTime> {
+        self.rise
+    }
+
+    /// Gets the time the satellite sets below the mask, `None` if it is
+    /// still visible at the end of the prediction
+    pub fn set(&self) -> Option<GpsTime> {
+        self.set
+    }
+
+    /// Gets the highest elevation of the pass, in degrees
+    pub fn max_elevation(&self) -> f64 {
+        self.max_elevation
+    }
+
+    /// Gets the time of the highest elevation of the pass
+    pub fn max_elevation_time(&self) -> GpsTime {
+        self.max_elevation_time
+    }
+}
+
+/// Predicts the passes of the satellites over a location between `start`
+/// and `end`
+///
+/// Orbits of the same satellite are combined, at each time the first orbit
+/// which can be used is taken. The passes are sorted by the time the
+/// satellites become visible.
+pub fn predict_passes<O: Orbit>(
+    orbits: &[O],
+    location: &ECEF,
+    start: GpsTime,
+    end: GpsTime,
+    settings: &VisibilitySettings,
+) -> Vec<SatellitePass> {
+    let mut satellites: BTreeMap<(Constellation, u16), (GnssSignal, Vec<&O>)> = BTreeMap::new();
+    for orbit in orbits {
+        if let Some(sid) = orbit.sid() {
+            satellites
+                .entry((sid.to_constellation(), sid.sat()))
+                .or_insert_with(|| (sid, Vec::new()))
+                .1
+                .push(orbit);
+        }
+    }
+
+    let mut passes: Vec<SatellitePass> = satellites
+        .into_values()
+        .flat_map(|(sid, orbits)| {
+            let elevation = |t: GpsTime| {
+                orbits
+                    .iter()
+                    .find_map(|orbit| orbit.position(t))
+                    .map_or(-90.0, |pos| location.azel_of(&pos).el.to_degrees())
+            };
+            satellite_passes(sid, &elevation, start, end, settings)
+        })
+        .collect();
+    passes.sort_by(|a, b| {
+        let a_time = a.rise.unwrap_or(start);
+        let b_time = b.rise.unwrap_or(start);
+        a_time
+            .diff(&b_time)
+            .partial_cmp(&0.0)
+            .unwrap()
+            .then(a.sid.cmp(&b.sid))
+    });
+    passes
+}
+
+/// Finds the passes of a single satellite
+fn satellite_passes(
+    sid: GnssSignal,
+    elevation: &dyn Fn(GpsTime) -> f64,
+    start: GpsTime,
+    end: GpsTime,
+    settings: &VisibilitySettings,
+) -> Vec<SatellitePass> {
+    let mask = settings.elevation_mask;
+    let step = settings.step;
+    let mut passes = Vec::new();
+
+    let mut previous = (start, elevation(start));
+    // Start of the current pass and the highest sample so far
+    let mut current = if previous.1 >= mask {
+        Some((None, previous))
+    } else {
+        None
+    };
+    let mut t = start;
+    while t < end {
+        t += step;
+        if t > end {
+            t = end;
+        }
+        let sample = (t, elevation(t));
+        match current {
+            None if sample.1 >= mask => {
+                let rise = crossing(elevation, mask, previous.0, t);
+                current = Some((Some(rise), sample));
+            }
+            Some((rise, highest)) if sample.1 < mask => {
+                let set = crossing(elevation, mask, previous.0, t);
+                passes.push(make_pass(
+                    sid,
+                    elevation,
+                    rise,
+                    Some(set),
+                    highest,
+                    (rise.unwrap_or(start), set),
+                    step,
+                ));
+                current = None;
+            }
+            Some((rise, highest)) if sample.1 > highest.1 => {
+                current = Some((rise, sample));
+            }
+            _ => {}
+        }
+        previous = sample;
+    }
+    if let Some((rise, highest)) = current {
+        passes.push(make_pass(
+            sid,
+            elevation,
+            rise,
+            None,
+            highest,
+            (rise.unwrap_or(start), end),
+            step,
+        ));
+    }
+    passes
+}
+
+/// Finds the time the elevation crosses the mask between two samples on
+/// either side of it
+fn crossing(elevation: &dyn Fn(GpsTime) -> f64, mask: f64, a: GpsTime, b: GpsTime) -> GpsTime {
+    let above_at_a = elevation(a) >= mask;
+    let (mut low, mut high) = (0.0, b.diff(&a));
+    for _ in 0..REFINE_ITERATIONS {
+        let middle = (low + high) / 2.0;
+        let above = elevation(a + Duration::from_secs_f64(middle)) >= mask;
+        if above == above_at_a {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    a + Duration::from_secs_f64(high)
+}
+
+/// Makes a pass, refining the time of the highest elevation around the
+/// highest sample
+fn make_pass(
+    sid: GnssSignal,
+    elevation: &dyn Fn(GpsTime) -> f64,
+    rise: Option<GpsTime>,
+    set: Option<GpsTime>,
+    highest: (GpsTime, f64),
+    bounds: (GpsTime, GpsTime),
+    step: Duration,
+) -> SatellitePass {
+    // Golden section search over the samples next to the highest one
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let origin = bounds.0;
+    let at = |offset: f64| origin + Duration::from_secs_f64(offset);
+    let offset = highest.0.diff(&origin);
+    let step = step.as_secs_f64();
+    let mut low = (offset - step).max(0.0);
+    let mut high = (offset + step).min(bounds.1.diff(&origin));
+    for _ in 0..REFINE_ITERATIONS {
+        let x1 = high - ratio * (high - low);
+        let x2 = low + ratio * (high - low);
+        if elevation(at(x1)) < elevation(at(x2)) {
+            low = x1;
+        } else {
+            high = x2;
+        }
+    }
+    let refined = (at((low + high) / 2.0), elevation(at((low + high) / 2.0)));
+    let (max_elevation_time, max_elevation) = if refined.1 > highest.1 {
+        refined
+    } else {
+        highest
+    };
+    SatellitePass {
+        sid,
+        rise,
+        set,
+        max_elevation,
+        max_elevation_time,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::{LLHDegrees, NED};
+    use crate::signal::Code;
+    use float_eq::assert_float_eq;
+    use std::f64::consts::PI;
+
+    /// A satellite crossing the sky of a location along a meridian, with its
+    /// elevation following a sine wave
+    struct SineOrbit {
+        sid: GnssSignal,
+        location: ECEF,
+        start: GpsTime,
+        period: f64,
+        peak: f64,
+    }
+
+    impl Orbit for SineOrbit {
+        fn sid(&self) -> Option<GnssSignal> {
+            Some(self.sid)
+        }
+
+        fn position(&self, t: GpsTime) -> Option<ECEF> {
+            let phase = 2.0 * PI * t.diff(&self.start) / self.period;
+            let el = (self.peak * phase.sin()).to_radians();
+            let range = 20_000_000.0;
+            let ned = NED::new(range * el.cos(), 0.0, -range * el.sin());
+            Some(self.location + ned.ecef_vector_at(&self.location))
+        }
+    }
+
+    #[test]
+    fn sine_passes() {
+        let location = LLHDegrees::new(0.0, 0.0, 0.0).to_ecef();
+        let start = GpsTime::new(2100, 0.0).unwrap();
+        let end = GpsTime::new(2100, 9000.0).unwrap();
+        let orbits = [
+            // Above the horizon from 15 minutes before the start for an
+            // hour, every 2 hours
+            SineOrbit {
+                sid: GnssSignal::new(3, Code::GpsL1ca).unwrap(),
+                location,
+                start: start - Duration::from_secs(900),
+                period: 7200.0,
+                peak: 60.0,
+            },
+            // Never gets above the mask
+            SineOrbit {
+                sid: GnssSignal::new(4, Code::GpsL1ca).unwrap(),
+                location,
+                start,
+                period: 7200.0,
+                peak: 8.0,
+            },
+        ];
+        let settings = VisibilitySettings::new().set_step(Duration::from_secs(300));
+        let passes = predict_passes(&orbits, &location, start, end, &settings);
+        assert_eq!(passes.len(), 2);
+        assert_eq!(passes[0].sid().sat(), 3);
+        assert_eq!(passes[1].sid().sat(), 3);
+
+        // Time from the horizon to the 10 degree mask
+        let below_mask = (10.0f64 / 60.0).asin() / (2.0 * PI) * 7200.0;
+
+        // Visible from the start
+        let pass = passes[0];
+        assert!(pass.rise().is_none());
+        assert_float_eq!(
+            pass.set().unwrap().diff(&start),
+            2700.0 - below_mask,
+            abs <= 1e-3
+        );
+        assert_float_eq!(pass.max_elevation(), 60.0, abs <= 1e-6);
+        assert_float_eq!(pass.max_elevation_time().diff(&start), 900.0, abs <= 1.0);
+
+        // Still visible at the end
+        let pass = passes[1];
+        assert_float_eq!(
+            pass.rise().unwrap().diff(&start),
+            6300.0 + below_mask,
+            abs <= 1e-3
+        );
+        assert!(pass.set().is_none());
+        assert_float_eq!(pass.max_elevation(), 60.0, abs <= 1e-6);
+        assert_float_eq!(pass.max_elevation_time().diff(&start), 8100.0, abs <= 1.0);
+    }
+
+    #[test]
+    fn almanac_passes() {
+        let toa = GpsTime::new(2100, 405_504.0).unwrap();
+        let almanacs: Vec<Almanac> = (0..6)
+            .map(|i| {
+                Almanac::new(
+                    GnssSignal::new(i + 1, Code::GpsL1ca).unwrap(),
+                    toa,
+                    5_153.6,
+                    0.005,
+                    55.0_f64.to_radians(),
+                    f64::from(i) * PI / 3.0,
+                    -8.0e-9,
+                    0.9,
+                    f64::from(i) * 1.1,
+                    0.0,
+                    0.0,
+                )
+            })
+            .collect();
+        let location = LLHDegrees::new(0.0, 0.0, 0.0).to_ecef();
+        let end = toa + Duration::from_secs(86_400);
+        let settings = VisibilitySettings::new();
+        let passes = predict_passes(&almanacs, &location, toa, end, &settings);
+        assert!(!passes.is_empty());
+
+        let mut previous = toa;
+        for pass in &passes {
+            let rise = pass.rise().unwrap_or(toa);
+            let set = pass.set().unwrap_or(end);
+            assert!(rise.diff(&previous) >= 0.0);
+            assert!(set.diff(&rise) > 0.0);
+            previous = rise;
+
+            // The pass bounds are on the mask, with the satellite above it
+            // in between
+            let almanac = &almanacs[usize::from(pass.sid().sat() - 1)];
+            let elevation = |t: GpsTime| {
+                location
+                    .azel_of(&almanac.calc_satellite_state(t).pos)
+                    .el
+                    .to_degrees()
+            };
+            if let Some(rise) = pass.rise() {
+                assert_float_eq!(elevation(rise), 10.0, abs <= 1e-3);
+            }
+            if let Some(set) = pass.set() {
+                assert_float_eq!(elevation(set), 10.0, abs <= 1e-3);
+            }
+            assert!(pass.max_elevation() >= 10.0);
+            assert_float_eq!(
+                elevation(pass.max_elevation_time()),
+                pass.max_elevation(),
+                abs <= 1e-9
+            );
+        }
+    }
+}
@@ -854,3 +854,33 @@ fn dref91_r2016() {
         ReferenceFrame::DREF91_R2016
     );
 }
+
+#[test]
+fn etrf_validity() {
+    let initial_coords = Coordinate::new(
+        ReferenceFrame::ITRF2014,
+        ECEF::new(4027894.006, 307045.600, 4919474.910),
+        Some(ECEF::new(0.01, 0.2, 0.030)),
+        make_epoch(2000),
+    );
+    let (result_coords, warning) = initial_coords
+        .transform_to_with_warning(ReferenceFrame::ETRF2014)
+        .unwrap();
+    assert!(warning.is_none());
+    assert_eq!(
+        result_coords,
+        initial_coords
+            .transform_to(ReferenceFrame::ETRF2014)
+            .unwrap()
+    );
+
+    // ETRS89 isn't defined before 1989.0
+    let early_coords = initial_coords.adjust_epoch(&make_epoch(1985));
+    let (result_coords, warning) = early_coords
+        .transform_to_with_warning(ReferenceFrame::ETRF2014)
+        .unwrap();
+    assert_eq!(result_coords.reference_frame(), ReferenceFrame::ETRF2014);
+    let warning = warning.unwrap();
+    assert_eq!(warning.validity().start(), Some(1989.0));
+    assert_eq!(warning.validity().end(), None);
+}
@@ -0,0 +1,131 @@
+use proptest::prelude::*;
+use std::time::Duration;
+use swiftnav::time::{GpsTime, UtcTime, MJD, WEEK};
+
+/// Leap second events in the BeiDou era, as the first UTC second after the
+/// inserted second
+const LEAP_SECONDS: [(u16, u8, u8); 3] = [(2012, 7, 1), (2015, 7, 1), (2017, 1, 1)];
+
+/// GPS times late enough to be representable in every other time scale
+fn gps_time() -> impl Strategy<Value = GpsTime> {
+    (1400_i16..2600, 0.0..WEEK.as_secs_f64()).prop_map(|(wn, tow)| GpsTime::new(wn, tow).unwrap())
+}
+
+/// GPS times within a few seconds of a leap second being inserted
+fn leap_second_time() -> impl Strategy<Value = GpsTime> {
+    (0..LEAP_SECONDS.len(), -5.0..5.0_f64).prop_map(|(i, offset)| {
+        let (year, month, day) = LEAP_SECONDS[i];
+        let after = UtcTime::from_date(year, month, day, 0, 0, 0.).to_gps_hardcoded();
+        if offset < 0. {
+            after - Duration::from_secs_f64(-offset)
+        } else {
+            after + Duration::from_secs_f64(offset)
+        }
+    })
+}
+
+/// Orders UTC times by their calendar fields, which unlike the GPS time
+/// allows for the 60th second of a minute
+fn utc_key(utc: &UtcTime) -> (u16, u16, u8, u8, f64) {
+    (
+        utc.year(),
+        utc.day_of_year(),
+        utc.hour(),
+        utc.minute(),
+        utc.seconds(),
+    )
+}
+
+proptest! {
+    #[test]
+    fn duration_round_trip(t in gps_time(), secs in 0.0..1e8_f64) {
+        let d = Duration::from_secs_f64(secs);
+        let later = t + d;
+        prop_assert!(later.is_valid());
+        prop_assert!((later.diff(&t) - secs).abs() < 1e-6);
+        prop_assert!((later - d).diff(&t).abs() < 1e-6);
+    }
+
+    #[test]
+    fn diff_is_antisymmetric(a in gps_time(), b in gps_time()) {
+        prop_assert!((a.diff(&b) + b.diff(&a)).abs() < 1e-6);
+        prop_assert_eq!(a.partial_cmp(&b), b.partial_cmp(&a).map(|o| o.reverse()));
+    }
+
+    #[test]
+    fn gnss_time_scales_round_trip(t in gps_time()) {
+        let gal = t.to_gal();
+        prop_assert!(gal.to_gps().diff(&t).abs() < 1e-9);
+        prop_assert!(gal.to_bds().to_gal().to_gps().diff(&t).abs() < 1e-9);
+
+        let bds = t.to_bds();
+        prop_assert!(bds.to_gps().diff(&t).abs() < 1e-9);
+        prop_assert!((0.0..WEEK.as_secs_f64()).contains(&bds.tow()));
+    }
+
+    #[test]
+    fn glonass_round_trip(t in gps_time()) {
+        prop_assume!(!t.is_leap_second_event_hardcoded());
+        let glo = t.to_glo_hardcoded();
+        prop_assert!(glo.to_gps_hardcoded().diff(&t).abs() < 1e-6);
+    }
+
+    #[test]
+    fn utc_round_trip(t in gps_time()) {
+        prop_assume!(!t.is_leap_second_event_hardcoded());
+        let utc = t.to_utc_hardcoded();
+        prop_assert!(utc.to_gps_hardcoded().diff(&t).abs() < 1e-6);
+
+        let mjd = utc.to_mjd();
+        prop_assert!(MJD::from(utc).as_f64() == mjd.as_f64());
+        prop_assert!(mjd.to_utc().to_gps_hardcoded().diff(&t).abs() < 1e-4);
+    }
+
+    #[test]
+    fn utc_is_monotonic_across_leap_seconds(t in leap_second_time(), step in 0.001..2.0_f64) {
+        let later = t + Duration::from_secs_f64(step);
+        let (utc, later_utc) = (t.to_utc_hardcoded(), later.to_utc_hardcoded());
+        prop_assert!(utc_key(&utc) < utc_key(&later_utc));
+
+        let offset_change = later.utc_offset_hardcoded() - t.utc_offset_hardcoded();
+        prop_assert!(offset_change == 0. || offset_change == 1.);
+    }
+
+    #[test]
+    fn iso8601_matches_fields(t in gps_time()) {
+        let utc = t.to_utc_hardcoded();
+        let s = utc.iso8601_str();
+        let (date, time) = s.trim_end_matches('Z').split_once('T').unwrap();
+        let date: Vec<u32> = date.split('-').map(|f| f.parse().unwrap()).collect();
+        let time: Vec<f64> = time.split(':').map(|f| f.parse().unwrap()).collect();
+        prop_assert_eq!(date, vec![utc.year() as u32, utc.month() as u32, utc.day_of_month() as u32]);
+        prop_assert_eq!(time[0], utc.hour() as f64);
+        prop_assert_eq!(time[1], utc.minute() as f64);
+        prop_assert!((time[2] - utc.seconds()).abs() <= 0.0005);
+    }
+
+    #[test]
+    fn wn_tow_string_round_trips(t in gps_time()) {
+        let parsed: GpsTime = t.to_string().parse().unwrap();
+        prop_assert_eq!(parsed.wn(), t.wn());
+        prop_assert_eq!(parsed.tow(), t.tow());
+    }
+
+    #[test]
+    fn rinex_epoch_round_trips(t in gps_time()) {
+        let parsed = GpsTime::from_rinex_epoch(&t.rinex_epoch()).unwrap();
+        prop_assert!(parsed.diff(&t).abs() <= 0.5e-7 + 1e-9);
+    }
+}
+
+#[test]
+fn leap_second_is_inserted() {
+    for (year, month, day) in LEAP_SECONDS.iter() {
+        let after = UtcTime::from_date(*year, *month, *day, 0, 0, 0.).to_gps_hardcoded();
+        let during = after - Duration::from_millis(500);
+        assert!(during.is_leap_second_event_hardcoded());
+        let utc = during.to_utc_hardcoded();
+        assert_eq!((utc.hour(), utc.minute()), (23, 59));
+        assert!(utc.seconds() >= 60.0);
+    }
+}